@@ -1,4 +1,6 @@
+use crate::blocks::Block;
 use crate::items::Item;
+use crate::BlockFacing;
 use mchprs_utils::{map, nbt_unwrap_val};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -24,6 +26,8 @@ pub enum ContainerType {
     Furnace,
     Barrel,
     Hopper,
+    Dispenser,
+    Dropper,
 }
 
 impl FromStr for ContainerType {
@@ -34,6 +38,8 @@ impl FromStr for ContainerType {
             "barrel" => ContainerType::Barrel,
             "furnace" => ContainerType::Furnace,
             "hopper" => ContainerType::Hopper,
+            "dispenser" => ContainerType::Dispenser,
+            "dropper" => ContainerType::Dropper,
             _ => return Err(()),
         })
     }
@@ -45,6 +51,8 @@ impl std::fmt::Display for ContainerType {
             ContainerType::Furnace => "minecraft:furnace",
             ContainerType::Barrel => "minecraft:barrel",
             ContainerType::Hopper => "minecraft:hopper",
+            ContainerType::Dispenser => "minecraft:dispenser",
+            ContainerType::Dropper => "minecraft:dropper",
         })
     }
 }
@@ -55,6 +63,7 @@ impl ContainerType {
             ContainerType::Furnace => 3,
             ContainerType::Barrel => 27,
             ContainerType::Hopper => 5,
+            ContainerType::Dispenser | ContainerType::Dropper => 9,
         }
     }
 
@@ -64,6 +73,141 @@ impl ContainerType {
             ContainerType::Furnace => 14,
             ContainerType::Barrel => 2,
             ContainerType::Hopper => 16,
+            ContainerType::Dispenser | ContainerType::Dropper => 6,
+        }
+    }
+
+    /// Calculates the signal strength a comparator reads from a container with this inventory
+    pub fn comparator_override(self, inventory: &[InventoryEntry]) -> u8 {
+        let fullness_sum: f32 = inventory
+            .iter()
+            .map(|entry| entry.count as f32 / Item::from_id(entry.id).max_stack_size() as f32)
+            .sum();
+        (if fullness_sum > 0.0 { 1.0 } else { 0.0 }
+            + (fullness_sum / self.num_slots() as f32) * 14.0)
+            .floor() as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MusicDisc {
+    Thirteen,
+    Cat,
+    Blocks,
+    Chirp,
+    Far,
+    Mall,
+    Mellohi,
+    Stal,
+    Strad,
+    Ward,
+    Eleven,
+    Wait,
+    Otherside,
+    Relic,
+    Five,
+    Pigstep,
+}
+
+impl FromStr for MusicDisc {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "music_disc_13" => MusicDisc::Thirteen,
+            "music_disc_cat" => MusicDisc::Cat,
+            "music_disc_blocks" => MusicDisc::Blocks,
+            "music_disc_chirp" => MusicDisc::Chirp,
+            "music_disc_far" => MusicDisc::Far,
+            "music_disc_mall" => MusicDisc::Mall,
+            "music_disc_mellohi" => MusicDisc::Mellohi,
+            "music_disc_stal" => MusicDisc::Stal,
+            "music_disc_strad" => MusicDisc::Strad,
+            "music_disc_ward" => MusicDisc::Ward,
+            "music_disc_11" => MusicDisc::Eleven,
+            "music_disc_wait" => MusicDisc::Wait,
+            "music_disc_otherside" => MusicDisc::Otherside,
+            "music_disc_5" => MusicDisc::Five,
+            "music_disc_pigstep" => MusicDisc::Pigstep,
+            "music_disc_relic" => MusicDisc::Relic,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl std::fmt::Display for MusicDisc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.get_name())
+    }
+}
+
+impl MusicDisc {
+    pub fn from_id(id: u32) -> MusicDisc {
+        match id {
+            0 => MusicDisc::Thirteen,
+            1 => MusicDisc::Cat,
+            2 => MusicDisc::Blocks,
+            3 => MusicDisc::Chirp,
+            4 => MusicDisc::Far,
+            5 => MusicDisc::Mall,
+            6 => MusicDisc::Mellohi,
+            7 => MusicDisc::Stal,
+            8 => MusicDisc::Strad,
+            9 => MusicDisc::Ward,
+            10 => MusicDisc::Eleven,
+            11 => MusicDisc::Wait,
+            12 => MusicDisc::Otherside,
+            13 => MusicDisc::Relic,
+            14 => MusicDisc::Five,
+            15 => MusicDisc::Pigstep,
+            _ => panic!("invalid MusicDisc with id {}", id),
+        }
+    }
+
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    /// The item name of the disc, e.g. `music_disc_cat`
+    pub fn get_name(self) -> &'static str {
+        match self {
+            MusicDisc::Thirteen => "music_disc_13",
+            MusicDisc::Cat => "music_disc_cat",
+            MusicDisc::Blocks => "music_disc_blocks",
+            MusicDisc::Chirp => "music_disc_chirp",
+            MusicDisc::Far => "music_disc_far",
+            MusicDisc::Mall => "music_disc_mall",
+            MusicDisc::Mellohi => "music_disc_mellohi",
+            MusicDisc::Stal => "music_disc_stal",
+            MusicDisc::Strad => "music_disc_strad",
+            MusicDisc::Ward => "music_disc_ward",
+            MusicDisc::Eleven => "music_disc_11",
+            MusicDisc::Wait => "music_disc_wait",
+            MusicDisc::Otherside => "music_disc_otherside",
+            MusicDisc::Five => "music_disc_5",
+            MusicDisc::Pigstep => "music_disc_pigstep",
+            MusicDisc::Relic => "music_disc_relic",
+        }
+    }
+
+    /// The signal strength a comparator reads from a jukebox playing this disc
+    pub fn comparator_output(self) -> u8 {
+        match self {
+            MusicDisc::Thirteen => 1,
+            MusicDisc::Cat => 2,
+            MusicDisc::Blocks => 3,
+            MusicDisc::Chirp => 4,
+            MusicDisc::Far => 5,
+            MusicDisc::Mall => 6,
+            MusicDisc::Mellohi => 7,
+            MusicDisc::Stal => 8,
+            MusicDisc::Strad => 9,
+            MusicDisc::Ward => 10,
+            MusicDisc::Eleven => 11,
+            MusicDisc::Wait => 12,
+            MusicDisc::Pigstep => 13,
+            MusicDisc::Otherside | MusicDisc::Relic => 14,
+            MusicDisc::Five => 15,
         }
     }
 }
@@ -79,6 +223,34 @@ pub enum BlockEntity {
         ty: ContainerType,
     },
     Sign(Box<SignBlockEntity>),
+    /// A block that is currently being moved by a piston
+    MovingPiston {
+        block_state: u32,
+        facing: BlockFacing,
+        extending: bool,
+        source: bool,
+    },
+    SculkSensor {
+        last_vibration_frequency: u8,
+        /// The vibration that is currently travelling towards the sensor
+        vibration: Option<Vibration>,
+        calibrated: bool,
+    },
+    Lectern {
+        /// The page the book is opened at
+        page: u32,
+        /// The amount of pages in the book
+        pages: u32,
+    },
+    Jukebox {
+        record: Option<MusicDisc>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Vibration {
+    pub frequency: u8,
+    pub power: u8,
 }
 
 impl BlockEntity {
@@ -90,20 +262,29 @@ impl BlockEntity {
                 ContainerType::Furnace => 0,
                 ContainerType::Barrel => 26,
                 ContainerType::Hopper => 17,
+                ContainerType::Dispenser => 5,
+                ContainerType::Dropper => 6,
             },
             BlockEntity::Sign(_) => 7,
+            BlockEntity::MovingPiston { .. } => 10,
+            BlockEntity::SculkSensor {
+                calibrated: false, ..
+            } => 34,
+            BlockEntity::SculkSensor {
+                calibrated: true, ..
+            } => 35,
+            BlockEntity::Lectern { .. } => 29,
+            BlockEntity::Jukebox { .. } => 4,
         }
     }
 
     fn load_container(slots_nbt: &[nbt::Value], ty: ContainerType) -> Option<BlockEntity> {
         use nbt::Value;
-        let num_slots = ty.num_slots();
-        let mut fullness_sum: f32 = 0.0;
         let mut inventory = Vec::new();
         for item in slots_nbt {
             let item_compound = nbt_unwrap_val!(item, Value::Compound);
-            let count = nbt_unwrap_val!(item_compound["Count"], Value::Byte);
-            let slot = nbt_unwrap_val!(item_compound["Slot"], Value::Byte);
+            let count = *nbt_unwrap_val!(item_compound.get("Count")?, Value::Byte);
+            let slot = *nbt_unwrap_val!(item_compound.get("Slot")?, Value::Byte);
             let namespaced_name = nbt_unwrap_val!(
                 item_compound
                     .get("Id")
@@ -138,13 +319,9 @@ impl BlockEntity {
                 id: item_type.unwrap_or(Item::Redstone {}).get_id(),
                 nbt: tag,
             });
-
-            fullness_sum += count as f32 / item_type.map_or(64, Item::max_stack_size) as f32;
         }
         Some(BlockEntity::Container {
-            comparator_override: (if fullness_sum > 0.0 { 1.0 } else { 0.0 }
-                + (fullness_sum / num_slots as f32) * 14.0)
-                .floor() as u8,
+            comparator_override: ty.comparator_override(&inventory),
             inventory,
             ty,
         })
@@ -154,20 +331,28 @@ impl BlockEntity {
         use nbt::Value;
         match id.trim_start_matches("minecraft:") {
             "comparator" => Some(BlockEntity::Comparator {
-                output_strength: *nbt_unwrap_val!(&nbt["OutputSignal"], Value::Int) as u8,
+                output_strength: *nbt_unwrap_val!(nbt.get("OutputSignal")?, Value::Int) as u8,
             }),
             "furnace" => BlockEntity::load_container(
-                nbt_unwrap_val!(&nbt["Items"], Value::List),
+                nbt_unwrap_val!(nbt.get("Items")?, Value::List),
                 ContainerType::Furnace,
             ),
             "barrel" => BlockEntity::load_container(
-                nbt_unwrap_val!(&nbt["Items"], Value::List),
+                nbt_unwrap_val!(nbt.get("Items")?, Value::List),
                 ContainerType::Barrel,
             ),
             "hopper" => BlockEntity::load_container(
-                nbt_unwrap_val!(&nbt["Items"], Value::List),
+                nbt_unwrap_val!(nbt.get("Items")?, Value::List),
                 ContainerType::Hopper,
             ),
+            "dispenser" => BlockEntity::load_container(
+                nbt_unwrap_val!(nbt.get("Items")?, Value::List),
+                ContainerType::Dispenser,
+            ),
+            "dropper" => BlockEntity::load_container(
+                nbt_unwrap_val!(nbt.get("Items")?, Value::List),
+                ContainerType::Dropper,
+            ),
             "sign" => {
                 let sign = if nbt.contains_key("Text1") {
                     // This is the pre-1.20 encoding
@@ -200,6 +385,65 @@ impl BlockEntity {
                 };
                 Some(BlockEntity::Sign(Box::new(sign)))
             }
+            "piston" => {
+                let state = nbt_unwrap_val!(&nbt["blockState"], Value::Compound);
+                let name = nbt_unwrap_val!(&state["Name"], Value::String);
+                let mut block = Block::from_name(name.trim_start_matches("minecraft:"))?;
+                if let Some(Value::Compound(props)) = state.get("Properties") {
+                    let props = props
+                        .iter()
+                        .filter_map(|(k, v)| match v {
+                            Value::String(v) => Some((k.as_str(), v.as_str())),
+                            _ => None,
+                        })
+                        .collect();
+                    block.set_properties(props);
+                }
+                Some(BlockEntity::MovingPiston {
+                    block_state: block.get_id(),
+                    facing: BlockFacing::from_id(
+                        *nbt_unwrap_val!(&nbt["facing"], Value::Int) as u32
+                    ),
+                    extending: *nbt_unwrap_val!(&nbt["extending"], Value::Byte) != 0,
+                    source: *nbt_unwrap_val!(&nbt["source"], Value::Byte) != 0,
+                })
+            }
+            id @ ("sculk_sensor" | "calibrated_sculk_sensor") => Some(BlockEntity::SculkSensor {
+                last_vibration_frequency: match nbt.get("last_vibration_frequency") {
+                    Some(Value::Int(frequency)) => *frequency as u8,
+                    _ => 0,
+                },
+                vibration: None,
+                calibrated: id == "calibrated_sculk_sensor",
+            }),
+            "lectern" => {
+                let pages = match nbt.get("Book") {
+                    Some(Value::Compound(book)) => match book.get("tag") {
+                        Some(Value::Compound(tag)) => match tag.get("pages") {
+                            Some(Value::List(pages)) => pages.len() as u32,
+                            _ => 1,
+                        },
+                        _ => 1,
+                    },
+                    _ => 0,
+                };
+                Some(BlockEntity::Lectern {
+                    page: match nbt.get("Page") {
+                        Some(Value::Int(page)) => *page as u32,
+                        _ => 0,
+                    },
+                    pages,
+                })
+            }
+            "jukebox" => Some(BlockEntity::Jukebox {
+                record: match nbt.get("RecordItem") {
+                    Some(Value::Compound(item)) => match item.get("id") {
+                        Some(Value::String(id)) => id.trim_start_matches("minecraft:").parse().ok(),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+            }),
             _ => None,
         }
     }
@@ -213,7 +457,7 @@ impl BlockEntity {
         match self {
             BlockEntity::Sign(sign) => Some({
                 let front = sign.front_rows.iter().map(|str| Value::String(str.clone()));
-                let back = sign.front_rows.iter().map(|str| Value::String(str.clone()));
+                let back = sign.back_rows.iter().map(|str| Value::String(str.clone()));
                 nbt::Blob::with_content(map! {
                     "is_waxed" => Value::Byte(0),
                     "front_text" => Value::Compound(map! {
@@ -238,15 +482,16 @@ impl BlockEntity {
             BlockEntity::Container { inventory, ty, .. } => Some({
                 let mut items = Vec::new();
                 for entry in inventory {
-                    let nbt = map! {
+                    let mut nbt = map! {
                         "Count" => nbt::Value::Byte(entry.count),
                         "id" => nbt::Value::String("minecraft:".to_string() + Item::from_id(entry.id).get_name()),
                         "Slot" => nbt::Value::Byte(entry.slot)
                     };
-                    // TODO: item nbt data in containers
-                    // if let Some(tag) = &entry.nbt {
-                    //     let blob = nbt::Blob::from_reader(&mut Cursor::new(tag)).unwrap();
-                    // }
+                    if let Some(tag) = &entry.nbt {
+                        if let Ok(blob) = nbt::Blob::from_reader(&mut tag.as_slice()) {
+                            nbt.insert("tag".to_owned(), Value::Compound(blob.content));
+                        }
+                    }
                     items.push(nbt::Value::Compound(nbt));
                 }
                 nbt::Blob::with_content(map! {
@@ -254,6 +499,132 @@ impl BlockEntity {
                     "Items" => Value::List(items)
                 })
             }),
+            BlockEntity::MovingPiston {
+                block_state,
+                facing,
+                extending,
+                source,
+            } => Some({
+                let block = Block::from_id(*block_state);
+                let props = block
+                    .properties()
+                    .into_iter()
+                    .map(|(k, v)| (k.to_owned(), Value::String(v)))
+                    .collect();
+                nbt::Blob::with_content(map! {
+                    "blockState" => Value::Compound(map! {
+                        "Name" => Value::String("minecraft:".to_owned() + block.get_name()),
+                        "Properties" => Value::Compound(props)
+                    }),
+                    "facing" => Value::Int(facing.get_id() as i32),
+                    "progress" => Value::Float(0.0),
+                    "extending" => Value::Byte(*extending as i8),
+                    "source" => Value::Byte(*source as i8),
+                    "id" => Value::String("minecraft:piston".to_owned())
+                })
+            }),
+            BlockEntity::SculkSensor {
+                last_vibration_frequency,
+                calibrated,
+                ..
+            } => Some({
+                let id = if *calibrated {
+                    "minecraft:calibrated_sculk_sensor"
+                } else {
+                    "minecraft:sculk_sensor"
+                };
+                nbt::Blob::with_content(map! {
+                    "last_vibration_frequency" => Value::Int(*last_vibration_frequency as i32),
+                    "id" => Value::String(id.to_owned())
+                })
+            }),
+            BlockEntity::Lectern { page, pages } => Some({
+                let mut content = map! {
+                    "Page" => Value::Int(*page as i32),
+                    "id" => Value::String("minecraft:lectern".to_owned())
+                };
+                if *pages > 0 {
+                    // Only the amount of pages is kept, so the book is written back with blank pages
+                    let blank_pages = vec![Value::String(String::new()); *pages as usize];
+                    content.insert(
+                        "Book".to_owned(),
+                        Value::Compound(map! {
+                            "id" => Value::String("minecraft:writable_book".to_owned()),
+                            "Count" => Value::Byte(1),
+                            "tag" => Value::Compound(map! {
+                                "pages" => Value::List(blank_pages)
+                            })
+                        }),
+                    );
+                }
+                nbt::Blob::with_content(content)
+            }),
+            BlockEntity::Jukebox { record } => Some({
+                let mut content = map! {
+                    "id" => Value::String("minecraft:jukebox".to_owned())
+                };
+                if let Some(record) = record {
+                    content.insert(
+                        "RecordItem".to_owned(),
+                        Value::Compound(map! {
+                            "id" => Value::String(format!("minecraft:{record}")),
+                            "Count" => Value::Byte(1)
+                        }),
+                    );
+                }
+                nbt::Blob::with_content(content)
+            }),
         }
     }
 }
+
+#[test]
+fn block_entity_nbt_roundtrip_test() {
+    let sign = BlockEntity::Sign(Box::new(SignBlockEntity {
+        front_rows: ["front".into(), String::new(), String::new(), String::new()],
+        back_rows: ["back".into(), String::new(), String::new(), String::new()],
+    }));
+    let blob = sign.to_nbt(false).unwrap();
+    let Some(BlockEntity::Sign(sign)) = BlockEntity::from_nbt("minecraft:sign", &blob.content)
+    else {
+        panic!("sign did not roundtrip");
+    };
+    assert_eq!(sign.front_rows[0], "front");
+    assert_eq!(sign.back_rows[0], "back");
+
+    let mut tag = Vec::new();
+    nbt::Blob::with_content(map! { "Damage" => nbt::Value::Int(3) })
+        .to_writer(&mut tag)
+        .unwrap();
+    let barrel = BlockEntity::Container {
+        comparator_override: 0,
+        inventory: vec![InventoryEntry {
+            id: Item::Redstone {}.get_id(),
+            slot: 4,
+            count: 64,
+            nbt: Some(tag),
+        }],
+        ty: ContainerType::Barrel,
+    };
+    let blob = barrel.to_nbt(false).unwrap();
+    let Some(BlockEntity::Container {
+        comparator_override,
+        inventory,
+        ..
+    }) = BlockEntity::from_nbt("minecraft:barrel", &blob.content)
+    else {
+        panic!("barrel did not roundtrip");
+    };
+    assert_eq!(comparator_override, 1);
+    assert_eq!((inventory[0].slot, inventory[0].count), (4, 64));
+    assert!(inventory[0].nbt.is_some());
+
+    let comparator = BlockEntity::Comparator { output_strength: 9 };
+    let blob = comparator.to_nbt(false).unwrap();
+    assert!(matches!(
+        BlockEntity::from_nbt("minecraft:comparator", &blob.content),
+        Some(BlockEntity::Comparator { output_strength: 9 })
+    ));
+    // Missing fields don't panic
+    assert!(BlockEntity::from_nbt("minecraft:comparator", &HashMap::new()).is_none());
+}
@@ -1,6 +1,8 @@
 mod props;
 
-use crate::{BlockColorVariant, BlockDirection, BlockFacing, BlockProperty, SignType};
+use crate::{
+    BlockColorVariant, BlockDirection, BlockFacing, BlockProperty, SignRotation, SignType,
+};
 use mchprs_proc_macros::BlockTransform;
 use mchprs_utils::map;
 pub use props::*;
@@ -9,6 +11,7 @@ use std::collections::HashMap;
 #[derive(Clone, Copy, Debug)]
 pub enum FlipDirection {
     FlipX,
+    FlipY,
     FlipZ,
 }
 
@@ -55,13 +58,12 @@ noop_block_transform!(
     u32,
     bool,
     BlockColorVariant,
-    BlockFacing,
-    TrapdoorHalf,
     SignType,
-    ButtonFace,
-    LeverFace,
     ComparatorMode,
     Instrument,
+    PistonType,
+    CopperVariant,
+    SculkSensorPhase,
 );
 
 impl BlockTransform for BlockDirection {
@@ -77,6 +79,7 @@ impl BlockTransform for BlockDirection {
                 BlockDirection::South => *self = BlockDirection::North,
                 _ => {}
             },
+            FlipDirection::FlipY => {}
         }
     }
 
@@ -90,6 +93,44 @@ impl BlockTransform for BlockDirection {
     }
 }
 
+impl BlockTransform for BlockFacing {
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipX, BlockFacing::East) => BlockFacing::West,
+            (FlipDirection::FlipX, BlockFacing::West) => BlockFacing::East,
+            (FlipDirection::FlipY, BlockFacing::Up) => BlockFacing::Down,
+            (FlipDirection::FlipY, BlockFacing::Down) => BlockFacing::Up,
+            (FlipDirection::FlipZ, BlockFacing::North) => BlockFacing::South,
+            (FlipDirection::FlipZ, BlockFacing::South) => BlockFacing::North,
+            (_, facing) => facing,
+        }
+    }
+
+    fn rotate90(&mut self) {
+        *self = match *self {
+            BlockFacing::North => BlockFacing::East,
+            BlockFacing::East => BlockFacing::South,
+            BlockFacing::South => BlockFacing::West,
+            BlockFacing::West => BlockFacing::North,
+            facing => facing,
+        }
+    }
+}
+
+impl BlockTransform for SignRotation {
+    fn flip(&mut self, dir: FlipDirection) {
+        self.0 = match dir {
+            FlipDirection::FlipX => (16 - self.0) & 15,
+            FlipDirection::FlipZ => (24 - self.0) & 15,
+            FlipDirection::FlipY => self.0,
+        }
+    }
+
+    fn rotate90(&mut self) {
+        self.0 = (self.0 + 4) & 15;
+    }
+}
+
 impl Block {
     pub fn has_block_entity(self) -> bool {
         matches!(
@@ -100,6 +141,13 @@ impl Block {
                 | Block::Hopper { .. }
                 | Block::Sign { .. }
                 | Block::WallSign { .. }
+                | Block::MovingPiston { .. }
+                | Block::SculkSensor { .. }
+                | Block::CalibratedSculkSensor { .. }
+                | Block::Lectern { .. }
+                | Block::Jukebox { .. }
+                | Block::Dispenser { .. }
+                | Block::Dropper { .. }
         )
     }
 
@@ -151,6 +199,102 @@ fn comparator_id_test() {
     assert_eq!(new, original);
 }
 
+#[test]
+fn copper_bulb_id_test() {
+    let original = Block::CopperBulb {
+        variant: CopperVariant::new(Oxidation::Weathered, true),
+        lit: true,
+        powered: false,
+    };
+    let id = original.get_id();
+    assert_eq!(id, 24717);
+    let new = Block::from_id(id);
+    assert_eq!(new, original);
+}
+
+#[test]
+fn iron_door_id_test() {
+    let original = Block::IronDoor {
+        door: Door {
+            facing: BlockDirection::North,
+            half: DoorHalf::Lower,
+            hinge: DoorHinge::Left,
+            open: false,
+            powered: false,
+        },
+    };
+    let id = original.get_id();
+    assert_eq!(id, 5663);
+    let new = Block::from_id(id);
+    assert_eq!(new, original);
+}
+
+#[test]
+fn vertical_flip_test() {
+    let mut observer = Block::Observer {
+        facing: BlockFacing::Up,
+        powered: false,
+    };
+    observer.flip(FlipDirection::FlipY);
+    assert_eq!(
+        observer,
+        Block::Observer {
+            facing: BlockFacing::Down,
+            powered: false,
+        }
+    );
+
+    let mut trapdoor = Block::IronTrapdoor {
+        facing: BlockDirection::East,
+        half: TrapdoorHalf::Bottom,
+        powered: false,
+    };
+    trapdoor.flip(FlipDirection::FlipY);
+    assert_eq!(
+        trapdoor,
+        Block::IronTrapdoor {
+            facing: BlockDirection::East,
+            half: TrapdoorHalf::Top,
+            powered: false,
+        }
+    );
+    trapdoor.flip(FlipDirection::FlipX);
+    assert_eq!(
+        trapdoor,
+        Block::IronTrapdoor {
+            facing: BlockDirection::West,
+            half: TrapdoorHalf::Top,
+            powered: false,
+        }
+    );
+}
+
+#[test]
+fn rotate_test() {
+    let mut piston = Block::Piston {
+        extended: false,
+        facing: BlockFacing::North,
+    };
+    piston.rotate(RotateAmt::Rotate90);
+    assert_eq!(
+        piston,
+        Block::Piston {
+            extended: false,
+            facing: BlockFacing::East,
+        }
+    );
+
+    let mut sign = Block::Sign {
+        sign_type: SignType(0),
+        rotation: SignRotation(2),
+    };
+    sign.rotate(RotateAmt::Rotate270);
+    let Block::Sign { rotation, .. } = sign else {
+        unreachable!()
+    };
+    assert_eq!(rotation, SignRotation(14));
+}
+
 macro_rules! blocks {
     (
         $(
@@ -542,9 +686,9 @@ blocks! {
     Sign {
         props: {
             sign_type: SignType,
-            rotation: u32
+            rotation: SignRotation
         },
-        get_id: (sign_type.0 << 5) + (rotation << 1) + match sign_type.0 {
+        get_id: (sign_type.0 << 5) + (rotation.0 << 1) + match sign_type.0 {
             0..=5 => 4303,
             6..=7 => 19277 - (6 << 5),
             _ => unreachable!(),
@@ -556,44 +700,44 @@ blocks! {
                 19277..=19339 => ((id - 19277) >> 5) + 6,
                 _ => unreachable!(),
             }),
-            rotation: (match id {
+            rotation: SignRotation((match id {
                 4303..=4525=> id - 4303,
                 19277..=19339 => id - 19277,
                 _ => unreachable!(),
-            } & 0b11110) >> 1
+            } & 0b11110) >> 1)
         },
         from_names(_name): {
             "oak_sign" => {
                 sign_type: SignType(0),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "spruce_sign" => {
                 sign_type: SignType(1),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "birch_sign" => {
                 sign_type: SignType(2),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "acacia_sign" => {
                 sign_type: SignType(3),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "jungle_sign" => {
                 sign_type: SignType(4),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "dark_oak_sign" => {
                 sign_type: SignType(5),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "crimson_sign" => {
                 sign_type: SignType(6),
-                rotation: 0
+                rotation: SignRotation(0)
             },
             "warped_sign" => {
                 sign_type: SignType(7),
-                rotation: 0
+                rotation: SignRotation(0)
             }
         },
         get_name: match sign_type.0 {
@@ -755,20 +899,22 @@ blocks! {
     },
     Observer {
         props: {
-            facing: BlockFacing
+            facing: BlockFacing,
+            powered: bool
         },
-        get_id: (facing.get_id() << 1) + 12551,
-        from_id_offset: 12551,
-        from_id(id): 12551..=12561 => {
-            facing: BlockFacing::from_id(id >> 1)
+        get_id: (facing.get_id() << 1) + !powered as u32 + 12550,
+        from_id_offset: 12550,
+        from_id(id): 12550..=12561 => {
+            facing: BlockFacing::from_id(id >> 1),
+            powered: (id & 1) == 0
         },
         from_names(_name): {
             "observer" => {
-                facing: Default::default()
+                facing: Default::default(),
+                powered: false
             }
         },
         get_name: "observer",
-        solid: true,
         cube: true,
     },
     SeaPickle {
@@ -788,11 +934,18 @@ blocks! {
         get_name: "sea_pickle",
     },
     Target {
-        props: {},
-        get_id: 19381,
-        from_id(_id): 19381 => {},
+        props: {
+            power: u8
+        },
+        get_id: 19381 + power as u32,
+        from_id_offset: 19381,
+        from_id(id): 19381..=19396 => {
+            power: id as u8
+        },
         from_names(_name): {
-            "target" => {}
+            "target" => {
+                power: 0
+            }
         },
         get_name: "target",
         solid: true,
@@ -852,6 +1005,108 @@ blocks! {
         transparent: true,
         cube: true,
     },
+    Dispenser {
+        props: {
+            facing: BlockFacing,
+            triggered: bool
+        },
+        get_id: (facing.get_id() << 1) + !triggered as u32 + 523,
+        from_id_offset: 523,
+        from_id(id): 523..=534 => {
+            facing: BlockFacing::from_id(id >> 1),
+            triggered: (id & 1) == 0
+        },
+        from_names(_name): {
+            "dispenser" => {
+                facing: Default::default(),
+                triggered: false
+            }
+        },
+        get_name: "dispenser",
+        solid: true,
+        cube: true,
+    },
+    Dropper {
+        props: {
+            facing: BlockFacing,
+            triggered: bool
+        },
+        get_id: (facing.get_id() << 1) + !triggered as u32 + 9344,
+        from_id_offset: 9344,
+        from_id(id): 9344..=9355 => {
+            facing: BlockFacing::from_id(id >> 1),
+            triggered: (id & 1) == 0
+        },
+        from_names(_name): {
+            "dropper" => {
+                facing: Default::default(),
+                triggered: false
+            }
+        },
+        get_name: "dropper",
+        solid: true,
+        cube: true,
+    },
+    PoweredRail {
+        props: {
+            powered: bool,
+            shape: RailShape
+        },
+        // Rails are never waterlogged
+        get_id: ((!powered as u32) * 12) + (shape.get_id() << 1) + 1 + 1944,
+        from_id_offset: 1944,
+        from_id(id): 1944..=1967 => {
+            powered: id < 12,
+            shape: RailShape::from_id((id % 12) >> 1)
+        },
+        from_names(_name): {
+            "powered_rail" => {
+                powered: false,
+                shape: Default::default()
+            }
+        },
+        get_name: "powered_rail",
+    },
+    DetectorRail {
+        props: {
+            powered: bool,
+            shape: RailShape
+        },
+        // Rails are never waterlogged
+        get_id: ((!powered as u32) * 12) + (shape.get_id() << 1) + 1 + 1968,
+        from_id_offset: 1968,
+        from_id(id): 1968..=1991 => {
+            powered: id < 12,
+            shape: RailShape::from_id((id % 12) >> 1)
+        },
+        from_names(_name): {
+            "detector_rail" => {
+                powered: false,
+                shape: Default::default()
+            }
+        },
+        get_name: "detector_rail",
+    },
+    ActivatorRail {
+        props: {
+            powered: bool,
+            shape: RailShape
+        },
+        // Rails are never waterlogged
+        get_id: ((!powered as u32) * 12) + (shape.get_id() << 1) + 1 + 9320,
+        from_id_offset: 9320,
+        from_id(id): 9320..=9343 => {
+            powered: id < 12,
+            shape: RailShape::from_id((id % 12) >> 1)
+        },
+        from_names(_name): {
+            "activator_rail" => {
+                powered: false,
+                shape: Default::default()
+            }
+        },
+        get_name: "activator_rail",
+    },
     Sandstone {
         props: {},
         get_id: 535,
@@ -972,6 +1227,48 @@ blocks! {
         // FIXME: You can place repeaters and comparators on it, but not wires?
         cube: true,
     },
+    Lectern {
+        props: {
+            facing: BlockDirection,
+            has_book: bool,
+            powered: bool
+        },
+        get_id: (facing.get_id() << 2) + ((!has_book as u32) << 1) + !powered as u32 + 18450,
+        from_id_offset: 18450,
+        from_id(id): 18450..=18465 => {
+            facing: BlockDirection::from_id(id >> 2),
+            has_book: (id & 2) == 0,
+            powered: (id & 1) == 0
+        },
+        from_names(_name): {
+            "lectern" => {
+                facing: Default::default(),
+                has_book: false,
+                powered: false
+            }
+        },
+        get_name: "lectern",
+        transparent: true,
+        cube: false,
+    },
+    Jukebox {
+        props: {
+            has_record: bool
+        },
+        get_id: !has_record as u32 + 5815,
+        from_id_offset: 5815,
+        from_id(id): 5815..=5816 => {
+            has_record: id == 0
+        },
+        from_names(_name): {
+            "jukebox" => {
+                has_record: false
+            }
+        },
+        get_name: "jukebox",
+        solid: true,
+        cube: true,
+    },
     Concrete {
         props: {
             color: BlockColorVariant
@@ -1202,6 +1499,97 @@ blocks! {
         },
         get_name: "iron_trapdoor",
     },
+    IronDoor {
+        props: {
+            door: Door
+        },
+        get_id: door.get_id() + 5652,
+        from_id_offset: 5652,
+        from_id(id): 5652..=5715 => {
+            door: Door::from_id(id)
+        },
+        from_names(_name): {
+            "iron_door" => {
+                door: Default::default()
+            }
+        },
+        get_name: "iron_door",
+        transparent: true,
+        cube: false,
+    },
+    OakDoor {
+        props: {
+            door: Door
+        },
+        get_id: door.get_id() + 4591,
+        from_id_offset: 4591,
+        from_id(id): 4591..=4654 => {
+            door: Door::from_id(id)
+        },
+        from_names(_name): {
+            "oak_door" => {
+                door: Default::default()
+            }
+        },
+        get_name: "oak_door",
+        transparent: true,
+        cube: false,
+    },
+    OakFenceGate {
+        props: {
+            facing: BlockDirection,
+            in_wall: bool,
+            open: bool,
+            powered: bool
+        },
+        get_id: (facing.get_id() << 3)
+            + ((!in_wall as u32) << 2)
+            + ((!open as u32) << 1)
+            + !powered as u32
+            + 6997,
+        from_id_offset: 6997,
+        from_id(id): 6997..=7028 => {
+            facing: BlockDirection::from_id(id >> 3),
+            in_wall: (id & 4) == 0,
+            open: (id & 2) == 0,
+            powered: (id & 1) == 0
+        },
+        from_names(_name): {
+            "oak_fence_gate" => {
+                facing: Default::default(),
+                in_wall: false,
+                open: false,
+                powered: false
+            }
+        },
+        get_name: "oak_fence_gate",
+        transparent: true,
+        cube: false,
+    },
+    Bell {
+        props: {
+            attachment: BellAttachment,
+            facing: BlockDirection,
+            powered: bool
+        },
+        get_id: (attachment.get_id() << 3) + (facing.get_id() << 1) + !powered as u32 + 18471,
+        from_id_offset: 18471,
+        from_id(id): 18471..=18502 => {
+            attachment: BellAttachment::from_id(id >> 3),
+            facing: BlockDirection::from_id((id >> 1) & 3),
+            powered: (id & 1) == 0
+        },
+        from_names(_name): {
+            "bell" => {
+                attachment: Default::default(),
+                facing: Default::default(),
+                powered: false
+            }
+        },
+        get_name: "bell",
+        transparent: true,
+        cube: false,
+    },
     NoteBlock {
         props: {
             instrument: Instrument,
@@ -1374,6 +1762,199 @@ blocks! {
         get_name: "end_portal_frame",
         transparent: true,
     },
+    StickyPiston {
+        props: {
+            extended: bool,
+            facing: BlockFacing
+        },
+        get_id: (!extended as u32) * 6 + facing.get_id() + 1992,
+        from_id_offset: 1992,
+        from_id(id): 1992..=2003 => {
+            extended: id < 6,
+            facing: BlockFacing::from_id(id % 6)
+        },
+        from_names(_name): {
+            "sticky_piston" => {
+                extended: false,
+                facing: Default::default()
+            }
+        },
+        get_name: "sticky_piston",
+        cube: true,
+    },
+    Piston {
+        props: {
+            extended: bool,
+            facing: BlockFacing
+        },
+        get_id: (!extended as u32) * 6 + facing.get_id() + 2011,
+        from_id_offset: 2011,
+        from_id(id): 2011..=2022 => {
+            extended: id < 6,
+            facing: BlockFacing::from_id(id % 6)
+        },
+        from_names(_name): {
+            "piston" => {
+                extended: false,
+                facing: Default::default()
+            }
+        },
+        get_name: "piston",
+        cube: true,
+    },
+    PistonHead {
+        props: {
+            facing: BlockFacing,
+            short: bool,
+            ty: PistonType
+        },
+        get_id: facing.get_id() * 4 + (!short as u32) * 2 + ty.get_id() + 2023,
+        from_id_offset: 2023,
+        from_id(id): 2023..=2046 => {
+            facing: BlockFacing::from_id(id >> 2),
+            short: (id >> 1) & 1 == 0,
+            ty: PistonType::from_id(id & 1)
+        },
+        from_names(_name): {
+            "piston_head" => {
+                facing: Default::default(),
+                short: false,
+                ty: Default::default()
+            }
+        },
+        get_name: "piston_head",
+    },
+    MovingPiston {
+        props: {
+            facing: BlockFacing,
+            ty: PistonType
+        },
+        get_id: facing.get_id() * 2 + ty.get_id() + 2063,
+        from_id_offset: 2063,
+        from_id(id): 2063..=2074 => {
+            facing: BlockFacing::from_id(id >> 1),
+            ty: PistonType::from_id(id & 1)
+        },
+        from_names(_name): {
+            "moving_piston" => {
+                facing: Default::default(),
+                ty: Default::default()
+            }
+        },
+        get_name: "moving_piston",
+    },
+    CopperBulb {
+        props: {
+            variant: CopperVariant,
+            lit: bool,
+            powered: bool
+        },
+        get_id: (variant.get_id() << 2) + ((!lit as u32) << 1) + !powered as u32 + 24692,
+        from_id_offset: 24692,
+        from_id(id): 24692..=24723 => {
+            variant: CopperVariant::from_id(id >> 2),
+            lit: (id >> 1) & 1 == 0,
+            powered: id & 1 == 0
+        },
+        from_names(_name): {
+            "copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Unaffected, false),
+                lit: false,
+                powered: false
+            },
+            "exposed_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Exposed, false),
+                lit: false,
+                powered: false
+            },
+            "weathered_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Weathered, false),
+                lit: false,
+                powered: false
+            },
+            "oxidized_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Oxidized, false),
+                lit: false,
+                powered: false
+            },
+            "waxed_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Unaffected, true),
+                lit: false,
+                powered: false
+            },
+            "waxed_exposed_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Exposed, true),
+                lit: false,
+                powered: false
+            },
+            "waxed_weathered_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Weathered, true),
+                lit: false,
+                powered: false
+            },
+            "waxed_oxidized_copper_bulb" => {
+                variant: CopperVariant::new(Oxidation::Oxidized, true),
+                lit: false,
+                powered: false
+            }
+        },
+        get_name: match (variant.oxidation, variant.waxed) {
+            (Oxidation::Unaffected, false) => "copper_bulb",
+            (Oxidation::Exposed, false) => "exposed_copper_bulb",
+            (Oxidation::Weathered, false) => "weathered_copper_bulb",
+            (Oxidation::Oxidized, false) => "oxidized_copper_bulb",
+            (Oxidation::Unaffected, true) => "waxed_copper_bulb",
+            (Oxidation::Exposed, true) => "waxed_exposed_copper_bulb",
+            (Oxidation::Weathered, true) => "waxed_weathered_copper_bulb",
+            (Oxidation::Oxidized, true) => "waxed_oxidized_copper_bulb",
+        },
+        solid: true,
+        cube: true,
+    },
+    SculkSensor {
+        props: {
+            power: u8,
+            sculk_sensor_phase: SculkSensorPhase
+        },
+        get_id: power as u32 * 6 + (sculk_sensor_phase.get_id() << 1) + 22320,
+        from_id_offset: 22319,
+        from_id(id): 22319..=22414 => {
+            power: (id / 6) as u8,
+            sculk_sensor_phase: SculkSensorPhase::from_id((id % 6) >> 1)
+        },
+        from_names(_name): {
+            "sculk_sensor" => {
+                power: 0,
+                sculk_sensor_phase: Default::default()
+            }
+        },
+        get_name: "sculk_sensor",
+    },
+    CalibratedSculkSensor {
+        props: {
+            facing: BlockDirection,
+            power: u8,
+            sculk_sensor_phase: SculkSensorPhase
+        },
+        get_id: facing.get_id() * 96
+            + power as u32 * 6
+            + (sculk_sensor_phase.get_id() << 1)
+            + 22416,
+        from_id_offset: 22415,
+        from_id(id): 22415..=22798 => {
+            facing: BlockDirection::from_id(id / 96),
+            power: (id % 96 / 6) as u8,
+            sculk_sensor_phase: SculkSensorPhase::from_id((id % 6) >> 1)
+        },
+        from_names(_name): {
+            "calibrated_sculk_sensor" => {
+                facing: Default::default(),
+                power: 0,
+                sculk_sensor_phase: Default::default()
+            }
+        },
+        get_name: "calibrated_sculk_sensor",
+    },
     Unknown {
         props: {
             id: u32
@@ -1,4 +1,5 @@
 use super::{Block, BlockDirection, BlockProperty, BlockTransform, FlipDirection};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, BlockProperty, BlockTransform)]
@@ -132,6 +133,18 @@ impl LeverFace {
     }
 }
 
+impl BlockTransform for LeverFace {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipY, LeverFace::Floor) => LeverFace::Ceiling,
+            (FlipDirection::FlipY, LeverFace::Ceiling) => LeverFace::Floor,
+            (_, face) => face,
+        }
+    }
+}
+
 impl FromStr for LeverFace {
     type Err = ();
 
@@ -199,6 +212,18 @@ impl ButtonFace {
     }
 }
 
+impl BlockTransform for ButtonFace {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipY, ButtonFace::Floor) => ButtonFace::Ceiling,
+            (FlipDirection::FlipY, ButtonFace::Ceiling) => ButtonFace::Floor,
+            (_, face) => face,
+        }
+    }
+}
+
 impl FromStr for ButtonFace {
     type Err = ();
 
@@ -345,6 +370,7 @@ impl BlockTransform for RedstoneWire {
                 south: self.north,
                 ..*self
             },
+            FlipDirection::FlipY => *self,
         }
     }
 }
@@ -371,6 +397,18 @@ impl TrapdoorHalf {
     }
 }
 
+impl BlockTransform for TrapdoorHalf {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipY, TrapdoorHalf::Top) => TrapdoorHalf::Bottom,
+            (FlipDirection::FlipY, TrapdoorHalf::Bottom) => TrapdoorHalf::Top,
+            (_, half) => half,
+        }
+    }
+}
+
 impl std::fmt::Display for TrapdoorHalf {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
@@ -392,6 +430,431 @@ impl FromStr for TrapdoorHalf {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DoorHalf {
+    Upper,
+    #[default]
+    Lower,
+}
+
+impl DoorHalf {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> DoorHalf {
+        match id {
+            0 => DoorHalf::Upper,
+            1 => DoorHalf::Lower,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn other(self) -> DoorHalf {
+        match self {
+            DoorHalf::Upper => DoorHalf::Lower,
+            DoorHalf::Lower => DoorHalf::Upper,
+        }
+    }
+}
+
+/// Flipping a door vertically swaps its halves so that the upper half stays on top
+impl BlockTransform for DoorHalf {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        if let FlipDirection::FlipY = dir {
+            *self = self.other();
+        }
+    }
+}
+
+impl std::fmt::Display for DoorHalf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DoorHalf::Upper => "upper",
+            DoorHalf::Lower => "lower",
+        })
+    }
+}
+
+impl FromStr for DoorHalf {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "upper" => DoorHalf::Upper,
+            "lower" => DoorHalf::Lower,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DoorHinge {
+    #[default]
+    Left,
+    Right,
+}
+
+impl DoorHinge {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> DoorHinge {
+        match id {
+            0 => DoorHinge::Left,
+            1 => DoorHinge::Right,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn other(self) -> DoorHinge {
+        match self {
+            DoorHinge::Left => DoorHinge::Right,
+            DoorHinge::Right => DoorHinge::Left,
+        }
+    }
+}
+
+impl std::fmt::Display for DoorHinge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DoorHinge::Left => "left",
+            DoorHinge::Right => "right",
+        })
+    }
+}
+
+impl FromStr for DoorHinge {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "left" => DoorHinge::Left,
+            "right" => DoorHinge::Right,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl BlockTransform for DoorHinge {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        // The hinge stays on the same side when a door is turned upside down
+        if !matches!(dir, FlipDirection::FlipY) {
+            *self = self.other();
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, BlockProperty, BlockTransform)]
+pub struct Door {
+    pub facing: BlockDirection,
+    pub half: DoorHalf,
+    pub hinge: DoorHinge,
+    pub open: bool,
+    pub powered: bool,
+}
+
+impl Door {
+    pub(super) fn from_id(id: u32) -> Door {
+        Door {
+            facing: BlockDirection::from_id(id >> 4),
+            half: DoorHalf::from_id((id >> 3) & 1),
+            hinge: DoorHinge::from_id((id >> 2) & 1),
+            open: (id & 2) == 0,
+            powered: (id & 1) == 0,
+        }
+    }
+
+    pub(super) fn get_id(self) -> u32 {
+        (self.facing.get_id() << 4)
+            + (self.half.get_id() << 3)
+            + (self.hinge.get_id() << 2)
+            + ((!self.open as u32) << 1)
+            + !self.powered as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RailShape {
+    #[default]
+    NorthSouth,
+    EastWest,
+    AscendingEast,
+    AscendingWest,
+    AscendingNorth,
+    AscendingSouth,
+}
+
+impl RailShape {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> RailShape {
+        match id {
+            0 => RailShape::NorthSouth,
+            1 => RailShape::EastWest,
+            2 => RailShape::AscendingEast,
+            3 => RailShape::AscendingWest,
+            4 => RailShape::AscendingNorth,
+            5 => RailShape::AscendingSouth,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn is_ascending(self) -> bool {
+        !matches!(self, RailShape::NorthSouth | RailShape::EastWest)
+    }
+}
+
+impl std::fmt::Display for RailShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RailShape::NorthSouth => "north_south",
+            RailShape::EastWest => "east_west",
+            RailShape::AscendingEast => "ascending_east",
+            RailShape::AscendingWest => "ascending_west",
+            RailShape::AscendingNorth => "ascending_north",
+            RailShape::AscendingSouth => "ascending_south",
+        })
+    }
+}
+
+impl FromStr for RailShape {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "north_south" => RailShape::NorthSouth,
+            "east_west" => RailShape::EastWest,
+            "ascending_east" => RailShape::AscendingEast,
+            "ascending_west" => RailShape::AscendingWest,
+            "ascending_north" => RailShape::AscendingNorth,
+            "ascending_south" => RailShape::AscendingSouth,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl BlockTransform for RailShape {
+    fn rotate90(&mut self) {
+        *self = match self {
+            RailShape::NorthSouth => RailShape::EastWest,
+            RailShape::EastWest => RailShape::NorthSouth,
+            RailShape::AscendingNorth => RailShape::AscendingEast,
+            RailShape::AscendingEast => RailShape::AscendingSouth,
+            RailShape::AscendingSouth => RailShape::AscendingWest,
+            RailShape::AscendingWest => RailShape::AscendingNorth,
+        }
+    }
+
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipX, RailShape::AscendingEast) => RailShape::AscendingWest,
+            (FlipDirection::FlipX, RailShape::AscendingWest) => RailShape::AscendingEast,
+            (FlipDirection::FlipZ, RailShape::AscendingNorth) => RailShape::AscendingSouth,
+            (FlipDirection::FlipZ, RailShape::AscendingSouth) => RailShape::AscendingNorth,
+            (_, shape) => shape,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BellAttachment {
+    #[default]
+    Floor,
+    Ceiling,
+    SingleWall,
+    DoubleWall,
+}
+
+impl BellAttachment {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> BellAttachment {
+        match id {
+            0 => BellAttachment::Floor,
+            1 => BellAttachment::Ceiling,
+            2 => BellAttachment::SingleWall,
+            3 => BellAttachment::DoubleWall,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl BlockTransform for BellAttachment {
+    fn rotate90(&mut self) {}
+
+    fn flip(&mut self, dir: FlipDirection) {
+        *self = match (dir, *self) {
+            (FlipDirection::FlipY, BellAttachment::Floor) => BellAttachment::Ceiling,
+            (FlipDirection::FlipY, BellAttachment::Ceiling) => BellAttachment::Floor,
+            (_, attachment) => attachment,
+        }
+    }
+}
+
+impl std::fmt::Display for BellAttachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BellAttachment::Floor => "floor",
+            BellAttachment::Ceiling => "ceiling",
+            BellAttachment::SingleWall => "single_wall",
+            BellAttachment::DoubleWall => "double_wall",
+        })
+    }
+}
+
+impl FromStr for BellAttachment {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "floor" => BellAttachment::Floor,
+            "ceiling" => BellAttachment::Ceiling,
+            "single_wall" => BellAttachment::SingleWall,
+            "double_wall" => BellAttachment::DoubleWall,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PistonType {
+    #[default]
+    Normal,
+    Sticky,
+}
+
+impl PistonType {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> PistonType {
+        match id {
+            0 => PistonType::Normal,
+            1 => PistonType::Sticky,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// `type` can't be used as a field name, so this is always encoded under the `type` key
+impl BlockProperty for PistonType {
+    fn encode(self, props: &mut HashMap<&'static str, String>, _name: &'static str) {
+        let name = match self {
+            PistonType::Normal => "normal",
+            PistonType::Sticky => "sticky",
+        };
+        props.insert("type", name.to_string());
+    }
+
+    fn decode(&mut self, props: &HashMap<&str, &str>, _name: &str) {
+        match props.get("type") {
+            Some(&"normal") => *self = PistonType::Normal,
+            Some(&"sticky") => *self = PistonType::Sticky,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SculkSensorPhase {
+    #[default]
+    Inactive,
+    Active,
+    Cooldown,
+}
+
+impl SculkSensorPhase {
+    pub fn get_id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> SculkSensorPhase {
+        use SculkSensorPhase::*;
+        match id {
+            0 => Inactive,
+            1 => Active,
+            2 => Cooldown,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl std::fmt::Display for SculkSensorPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SculkSensorPhase::Inactive => "inactive",
+            SculkSensorPhase::Active => "active",
+            SculkSensorPhase::Cooldown => "cooldown",
+        })
+    }
+}
+
+impl FromStr for SculkSensorPhase {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "inactive" => SculkSensorPhase::Inactive,
+            "active" => SculkSensorPhase::Active,
+            "cooldown" => SculkSensorPhase::Cooldown,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Oxidation {
+    #[default]
+    Unaffected,
+    Exposed,
+    Weathered,
+    Oxidized,
+}
+
+/// The oxidation and waxing of a copper block, both of which are encoded in the block name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct CopperVariant {
+    pub oxidation: Oxidation,
+    pub waxed: bool,
+}
+
+impl CopperVariant {
+    pub fn new(oxidation: Oxidation, waxed: bool) -> CopperVariant {
+        CopperVariant { oxidation, waxed }
+    }
+
+    pub fn get_id(self) -> u32 {
+        ((self.waxed as u32) << 2) + self.oxidation as u32
+    }
+
+    pub fn from_id(id: u32) -> CopperVariant {
+        let oxidation = match id & 0b11 {
+            0 => Oxidation::Unaffected,
+            1 => Oxidation::Exposed,
+            2 => Oxidation::Weathered,
+            _ => Oxidation::Oxidized,
+        };
+        CopperVariant::new(oxidation, id & 0b100 != 0)
+    }
+}
+
+impl BlockProperty for CopperVariant {
+    // Don't encode: the variant is encoded in the block name
+    fn encode(self, _props: &mut HashMap<&'static str, String>, _name: &'static str) {}
+    fn decode(&mut self, _props: &HashMap<&str, &str>, _name: &str) {}
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Instrument {
     Harp,
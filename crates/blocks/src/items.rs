@@ -1,4 +1,4 @@
-use crate::block_entities::ContainerType;
+use crate::block_entities::{ContainerType, MusicDisc};
 use crate::BlockColorVariant;
 use mchprs_utils::map;
 
@@ -16,6 +16,8 @@ impl ItemStack {
             ContainerType::Barrel => Item::Barrel {},
             ContainerType::Hopper => Item::Hopper {},
             ContainerType::Furnace => Item::Furnace {},
+            ContainerType::Dispenser => Item::Dispenser {},
+            ContainerType::Dropper => Item::Dropper {},
         };
         let slots = container_ty.num_slots() as u32;
 
@@ -255,6 +257,24 @@ items! {
         from_id(_id): 658 => {},
         block: true,
     },
+    Piston {
+        props: {},
+        get_id: 661,
+        from_id(_id): 661 => {},
+        block: true,
+    },
+    StickyPiston {
+        props: {},
+        get_id: 662,
+        from_id(_id): 662 => {},
+        block: true,
+    },
+    Observer {
+        props: {},
+        get_id: 665,
+        from_id(_id): 665 => {},
+        block: true,
+    },
     Hopper {
         props: {},
         get_id: 666,
@@ -335,6 +355,36 @@ items! {
         from_id(_id): 1193 => {},
         block: true,
     },
+    PoweredRail {
+        props: {},
+        get_id: 760,
+        from_id(_id): 760 => {},
+        block: true,
+    },
+    DetectorRail {
+        props: {},
+        get_id: 761,
+        from_id(_id): 761 => {},
+        block: true,
+    },
+    ActivatorRail {
+        props: {},
+        get_id: 763,
+        from_id(_id): 763 => {},
+        block: true,
+    },
+    Dispenser {
+        props: {},
+        get_id: 667,
+        from_id(_id): 667 => {},
+        block: true,
+    },
+    Dropper {
+        props: {},
+        get_id: 668,
+        from_id(_id): 668 => {},
+        block: true,
+    },
     Target {
         props: {},
         get_id: 670,
@@ -359,6 +409,30 @@ items! {
         from_id(_id): 729 => {},
         block: true,
     },
+    IronDoor {
+        props: {},
+        get_id: 709,
+        from_id(_id): 709 => {},
+        block: true,
+    },
+    OakDoor {
+        props: {},
+        get_id: 710,
+        from_id(_id): 710 => {},
+        block: true,
+    },
+    OakFenceGate {
+        props: {},
+        get_id: 749,
+        from_id(_id): 749 => {},
+        block: true,
+    },
+    Bell {
+        props: {},
+        get_id: 1201,
+        from_id(_id): 1201 => {},
+        block: true,
+    },
     NoteBlock {
         props: {},
         get_id: 680,
@@ -371,6 +445,12 @@ items! {
         from_id(_id): 308 => {},
         block: true,
     },
+    Jukebox {
+        props: {},
+        get_id: 309,
+        from_id(_id): 309 => {},
+        block: true,
+    },
     GoldBlock {
         props: {},
         get_id: 89,
@@ -442,6 +522,17 @@ items! {
         get_id: 1003,
         from_id(_id): 1003 => {},
     },
+    MusicDisc {
+        props: {
+            disc: MusicDisc
+        },
+        get_id: 1161 + disc.get_id(),
+        from_id_offset: 1161,
+        from_id(id): 1161..=1176 => {
+            disc: MusicDisc::from_id(id)
+        },
+        max_stack: 1,
+    },
     Unknown {
         props: {
             id: u32
@@ -461,7 +552,7 @@ impl Item {
             "redstone" => Some(Item::Redstone {}),
             "stick" => Some(Item::Redstone {}),
             "wooden_shovel" => Some(Item::TotemOfUndying {}),
-            _ => None,
+            name => name.parse().ok().map(|disc| Item::MusicDisc { disc }),
         }
     }
 
@@ -470,6 +561,7 @@ impl Item {
             Item::Snowball {} => "snowball",
             Item::TotemOfUndying {} => "totem_of_undying",
             Item::MilkBucket {} => "milk_bucket",
+            Item::MusicDisc { disc } => disc.get_name(),
             _ => "redstone",
         }
     }
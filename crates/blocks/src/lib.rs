@@ -226,6 +226,11 @@ pub enum BlockDirection {
 }
 
 impl BlockDirection {
+    pub fn values() -> [BlockDirection; 4] {
+        use BlockDirection::*;
+        [North, South, East, West]
+    }
+
     pub fn opposite(self) -> BlockDirection {
         use BlockDirection::*;
         match self {
@@ -295,8 +300,8 @@ impl BlockDirection {
         }
     }
 
-    pub fn from_rotation(rotation: u32) -> Option<BlockDirection> {
-        match rotation {
+    pub fn from_rotation(rotation: SignRotation) -> Option<BlockDirection> {
+        match rotation.0 {
             0 => Some(BlockDirection::South),
             4 => Some(BlockDirection::West),
             8 => Some(BlockDirection::North),
@@ -331,7 +336,7 @@ impl std::fmt::Display for BlockDirection {
     }
 }
 
-#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BlockFacing {
     North,
     East,
@@ -366,6 +371,29 @@ impl BlockFacing {
         }
     }
 
+    pub fn block_face(self) -> BlockFace {
+        match self {
+            BlockFacing::North => BlockFace::North,
+            BlockFacing::South => BlockFace::South,
+            BlockFacing::East => BlockFace::East,
+            BlockFacing::West => BlockFace::West,
+            BlockFacing::Up => BlockFace::Top,
+            BlockFacing::Down => BlockFace::Bottom,
+        }
+    }
+
+    pub fn opposite(self) -> BlockFacing {
+        use BlockFacing::*;
+        match self {
+            North => South,
+            South => North,
+            East => West,
+            West => East,
+            Up => Down,
+            Down => Up,
+        }
+    }
+
     pub fn offset_pos(self, mut pos: BlockPos, n: i32) -> BlockPos {
         match self {
             BlockFacing::North => pos.z -= n,
@@ -454,3 +482,21 @@ impl BlockProperty for SignType {
     fn encode(self, _props: &mut HashMap<&'static str, String>, _name: &'static str) {}
     fn decode(&mut self, _props: &HashMap<&str, &str>, _name: &str) {}
 }
+
+/// The rotation of a standing sign in sixteenths of a full turn, starting at south
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SignRotation(pub u32);
+
+impl BlockProperty for SignRotation {
+    fn encode(self, props: &mut HashMap<&'static str, String>, name: &'static str) {
+        props.insert(name, self.0.to_string());
+    }
+
+    fn decode(&mut self, props: &HashMap<&str, &str>, name: &str) {
+        if let Some(&str) = props.get(name) {
+            if let Ok(val) = str.parse::<u32>() {
+                self.0 = val & 15;
+            }
+        }
+    }
+}
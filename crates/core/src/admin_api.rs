@@ -0,0 +1,257 @@
+//! An HTTP API for web panels and other tools to manage the server with. Requests have to carry
+//! the configured token as `Authorization: Bearer <token>`, and are answered with JSON.
+//!
+//! - `GET /api/players` lists the players online and the plots they are on
+//! - `GET /api/plots` lists the claimed and running plots, with their owners and performance
+//! - `GET /api/bans` lists the banned players
+//! - `POST /api/plots/<x>/<z>/compile` compiles a running plot with redpiler, with the body as
+//!   the compiler flags
+//! - `POST /api/players/<name>/kick` and `POST /api/players/<name>/ban` kick or ban a player,
+//!   with the body as the reason
+//! - `DELETE /api/players/<name>/ban` unbans a player
+//! - `POST /api/command` runs the body as a console command
+
+use crate::config::AdminApiConfig;
+use crate::server::Message;
+use crate::tcp_server::{self, HttpRequest, Limits};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tracing::{error, info, warn};
+
+/// How long a client has to send its request before the connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the server thread to answer a request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+/// The largest request body that is accepted, in bytes
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// How many connections are handled at once
+const MAX_CONNECTIONS: usize = 16;
+
+/// A request that is answered by the server thread
+#[derive(Debug)]
+pub enum AdminRequest {
+    Players,
+    Plots,
+    Bans,
+    Compile { x: i32, z: i32, flags: String },
+    Kick { player: String, reason: String },
+    Ban { player: String, reason: String },
+    Unban { player: String },
+}
+
+/// The answer to a request, or an error if what it was about doesn't exist
+pub type AdminResponse = Result<Value, String>;
+
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: String,
+}
+
+/// Reads a request. If it isn't valid, the status to respond with is returned instead.
+fn read_request(stream: &TcpStream) -> std::io::Result<Result<Request, &'static str>> {
+    let mut request = match HttpRequest::read(stream)? {
+        Ok(request) => request,
+        Err(status) => return Ok(Err(status)),
+    };
+    let token = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let Some(body) = request.read_body(MAX_BODY_SIZE)? else {
+        return Ok(Err("413 Payload Too Large"));
+    };
+    Ok(Ok(Request {
+        method: request.method,
+        path: request.path,
+        token,
+        body: String::from_utf8_lossy(&body).trim().to_string(),
+    }))
+}
+
+enum Route {
+    Server(AdminRequest),
+    Command(String),
+}
+
+/// Finds what a request asks for. Returns the status to respond with if it isn't valid.
+fn route(request: &Request) -> Result<Route, &'static str> {
+    let segments: Vec<&str> = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let body = request.body.clone();
+    let route = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "players"]) => Route::Server(AdminRequest::Players),
+        ("GET", ["api", "plots"]) => Route::Server(AdminRequest::Plots),
+        ("GET", ["api", "bans"]) => Route::Server(AdminRequest::Bans),
+        ("POST", ["api", "plots", x, z, "compile"]) => {
+            let (Ok(x), Ok(z)) = (x.parse(), z.parse()) else {
+                return Err("400 Bad Request");
+            };
+            Route::Server(AdminRequest::Compile { x, z, flags: body })
+        }
+        ("POST", ["api", "players", player, "kick"]) => Route::Server(AdminRequest::Kick {
+            player: player.to_string(),
+            reason: body,
+        }),
+        ("POST", ["api", "players", player, "ban"]) => Route::Server(AdminRequest::Ban {
+            player: player.to_string(),
+            reason: body,
+        }),
+        ("DELETE", ["api", "players", player, "ban"]) => Route::Server(AdminRequest::Unban {
+            player: player.to_string(),
+        }),
+        ("POST", ["api", "command"]) if !body.is_empty() => Route::Command(body),
+        (_, ["api", "players" | "plots" | "bans" | "command", ..]) => {
+            return Err("405 Method Not Allowed")
+        }
+        _ => return Err("404 Not Found"),
+    };
+    Ok(route)
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    token: &str,
+    sender: Sender<Message>,
+) -> std::io::Result<()> {
+    let request = match read_request(&stream)? {
+        Ok(request) => request,
+        Err(status) => return respond(&mut stream, status, &json!({ "error": status })),
+    };
+    if !token_matches(request.token.as_deref(), token) {
+        if let Ok(address) = stream.peer_addr() {
+            warn!("Unauthorized admin api request from {}", address);
+        }
+        return respond(
+            &mut stream,
+            "401 Unauthorized",
+            &json!({"error": "Unauthorized"}),
+        );
+    }
+    let route = match route(&request) {
+        Ok(route) => route,
+        Err(status) => return respond(&mut stream, status, &json!({ "error": status })),
+    };
+    info!("Admin api request: {} {}", request.method, request.path);
+
+    let response = match route {
+        Route::Server(request) => {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            let _ = sender.send(Message::AdminRequest(request, reply_tx));
+            reply_rx.recv_timeout(RESPONSE_TIMEOUT).ok()
+        }
+        Route::Command(command) => {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            let _ = sender.send(Message::ConsoleCommand(command, reply_tx));
+            reply_rx
+                .recv_timeout(RESPONSE_TIMEOUT)
+                .ok()
+                .map(|output| Ok(json!({ "output": output })))
+        }
+    };
+    match response {
+        Some(Ok(value)) => respond(&mut stream, "200 OK", &value),
+        Some(Err(error)) => respond(&mut stream, "404 Not Found", &json!({ "error": error })),
+        None => respond(
+            &mut stream,
+            "503 Service Unavailable",
+            &json!({"error": "The server did not respond"}),
+        ),
+    }
+}
+
+/// Compares the token in constant time, so that it can't be guessed from how long that takes
+fn token_matches(given: Option<&str>, token: &str) -> bool {
+    given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes())))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/// Starts the admin api on its own thread
+pub fn start(config: &AdminApiConfig, sender: Sender<Message>) {
+    if config.token.is_empty() {
+        error!("The admin api is enabled without a token, so it was not started");
+        return;
+    }
+    let listener = match TcpListener::bind(&config.bind_address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(
+                "Failed to bind admin api to {}: {}",
+                config.bind_address, err
+            );
+            return;
+        }
+    };
+    info!("Admin api listening on {}", config.bind_address);
+
+    let token = config.token.clone();
+    let limits = Limits {
+        max_connections: MAX_CONNECTIONS,
+        read_timeout: REQUEST_TIMEOUT,
+    };
+    tcp_server::serve("Admin API", listener, limits, move |stream| {
+        handle_connection(stream, &token, sender.clone())
+    });
+}
+
+#[test]
+fn route_test() {
+    let request = |method: &str, path: &str, body: &str| Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        token: None,
+        body: body.to_string(),
+    };
+    assert!(matches!(
+        route(&request("GET", "/api/plots/", "")),
+        Ok(Route::Server(AdminRequest::Plots))
+    ));
+    assert!(matches!(
+        route(&request("POST", "/api/plots/-1/2/compile", "--io-only")),
+        Ok(Route::Server(AdminRequest::Compile { x: -1, z: 2, flags })) if flags == "--io-only"
+    ));
+    assert!(matches!(
+        route(&request("DELETE", "/api/players/Player/ban", "")),
+        Ok(Route::Server(AdminRequest::Unban { player })) if player == "Player"
+    ));
+    assert!(matches!(
+        route(&request("POST", "/api/command", "list")),
+        Ok(Route::Command(command)) if command == "list"
+    ));
+    assert!(matches!(
+        route(&request("GET", "/api/command", "")),
+        Err("405 Method Not Allowed")
+    ));
+    assert!(matches!(
+        route(&request("GET", "/other", "")),
+        Err("404 Not Found")
+    ));
+}
+
+#[test]
+fn token_matches_test() {
+    assert!(token_matches(Some("secret"), "secret"));
+    assert!(!token_matches(Some("secreT"), "secret"));
+    assert!(!token_matches(Some("secret2"), "secret"));
+    assert!(!token_matches(None, "secret"));
+}
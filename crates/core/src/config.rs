@@ -1,9 +1,11 @@
 use crate::permissions::PermissionsConfig;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
-use toml_edit::{value, DocumentMut};
+use std::net::IpAddr;
+use toml_edit::{table, value, Array, DocumentMut};
 
 pub static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::load("Config.toml"));
 
@@ -25,6 +27,116 @@ macro_rules! impl_simple_default {
 
 impl_simple_default!(String, i64, bool);
 
+impl ConfigSerializeDefault for Vec<String> {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        doc.entry(name)
+            .or_insert_with(|| value(self.into_iter().collect::<Array>()));
+    }
+}
+
+impl ConfigSerializeDefault for QuirksConfig {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        let Some(quirks) = doc.entry(name).or_insert_with(table).as_table_mut() else {
+            return;
+        };
+        quirks
+            .entry("zero_tick_pistons")
+            .or_insert_with(|| value(self.zero_tick_pistons));
+        quirks
+            .entry("repeater_tick_priority")
+            .or_insert_with(|| value(self.repeater_tick_priority));
+        quirks
+            .entry("comparator_tick_priority")
+            .or_insert_with(|| value(self.comparator_tick_priority));
+    }
+}
+
+impl ConfigSerializeDefault for WorldEditLimitsConfig {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        let Some(limits) = doc.entry(name).or_insert_with(table).as_table_mut() else {
+            return;
+        };
+        limits
+            .entry("max_selection_volume")
+            .or_insert_with(|| value(self.max_selection_volume));
+        limits
+            .entry("max_clipboard_volume")
+            .or_insert_with(|| value(self.max_clipboard_volume));
+        limits
+            .entry("max_operations_per_second")
+            .or_insert_with(|| value(self.max_operations_per_second));
+    }
+}
+
+impl ConfigSerializeDefault for PlotWorldConfig {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        let Some(world) = doc.entry(name).or_insert_with(table).as_table_mut() else {
+            return;
+        };
+        world
+            .entry("generator")
+            .or_insert_with(|| value(self.generator));
+        world
+            .entry("scale")
+            .or_insert_with(|| value(self.scale as i64));
+        world
+            .entry("min_y")
+            .or_insert_with(|| value(self.min_y as i64));
+        world
+            .entry("height")
+            .or_insert_with(|| value(self.height as i64));
+        world
+            .entry("road_width")
+            .or_insert_with(|| value(self.road_width as i64));
+        world
+            .entry("floor_height")
+            .or_insert_with(|| value(self.floor_height as i64));
+        world
+            .entry("floor_block")
+            .or_insert_with(|| value(self.floor_block));
+        world
+            .entry("road_block")
+            .or_insert_with(|| value(self.road_block));
+        world
+            .entry("wall_block")
+            .or_insert_with(|| value(self.wall_block));
+    }
+}
+
+impl ConfigSerializeDefault for WatchdogConfig {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        let Some(watchdog) = doc.entry(name).or_insert_with(table).as_table_mut() else {
+            return;
+        };
+        watchdog
+            .entry("stall_timeout")
+            .or_insert_with(|| value(self.stall_timeout as i64));
+        watchdog
+            .entry("autosave_interval")
+            .or_insert_with(|| value(self.autosave_interval as i64));
+        watchdog
+            .entry("restart_crashed_plots")
+            .or_insert_with(|| value(self.restart_crashed_plots));
+    }
+}
+
+impl ConfigSerializeDefault for ThrottleConfig {
+    fn fix_config(self, name: &str, doc: &mut DocumentMut) {
+        let Some(throttle) = doc.entry(name).or_insert_with(table).as_table_mut() else {
+            return;
+        };
+        throttle
+            .entry("connections_per_minute")
+            .or_insert_with(|| value(self.connections_per_minute as i64));
+        throttle
+            .entry("logins_per_second")
+            .or_insert_with(|| value(self.logins_per_second as i64));
+        throttle
+            .entry("packets_per_second")
+            .or_insert_with(|| value(self.packets_per_second as i64));
+    }
+}
+
 impl<T> ConfigSerializeDefault for Option<T> {
     fn fix_config(self, _: &str, _: &mut DocumentMut) {
         assert!(self.is_none(), "`Some` as default is unimplemented");
@@ -65,16 +177,186 @@ macro_rules! gen_config {
 
 gen_config! {
     bind_address: String = "0.0.0.0:25565".to_string(),
+    listeners: Option<Vec<ListenerConfig>> = None,
+    throttle: ThrottleConfig = ThrottleConfig::default(),
     motd: String = "Minecraft High Performance Redstone Server".to_string(),
     chat_format: String = "<{username}> {message}".to_string(),
+    plot_chat_format: String = "&7[Plot {plot}] &r<{username}> {message}".to_string(),
+    staff_chat_format: String = "&c[Staff] &r<{username}> {message}".to_string(),
     max_players: i64 = 99999,
     view_distance: i64 = 8,
+    compression_threshold: i64 = 256,
+    compression_level: i64 = 6,
+    plot_compression_level: i64 = 3,
     whitelist: bool = false,
     schemati: bool = false,
     luckperms: Option<PermissionsConfig> = None,
     block_in_hitbox: bool = true,
     auto_redpiler: bool = false,
-    velocity: Option<VelocityConfig> = None
+    quasi_connectivity: bool = true,
+    copper_bulb_delay: bool = false,
+    torch_burnout: bool = false,
+    strict_update_order: bool = false,
+    quirks: QuirksConfig = QuirksConfig::default(),
+    worldedit_history_depth: i64 = 25,
+    teleport_history_depth: i64 = 10,
+    worldedit_history_max_size: i64 = 16777216,
+    worldedit_limits: WorldEditLimitsConfig = WorldEditLimitsConfig::default(),
+    plot_world: PlotWorldConfig = PlotWorldConfig::default(),
+    plot_template_dirs: Vec<String> = vec!["./templates".to_string()],
+    max_plot_snapshots: i64 = 10,
+    plot_expiry: Option<PlotExpiryConfig> = None,
+    plot_threads: Option<PlotThreadsConfig> = None,
+    watchdog: WatchdogConfig = WatchdogConfig::default(),
+    online_mode: bool = false,
+    resolve_skins: bool = false,
+    velocity: Option<VelocityConfig> = None,
+    bungeecord: Option<BungeeCordConfig> = None,
+    proxy_protocol: Option<ProxyProtocolConfig> = None,
+    schematic_http: Option<SchematicHttpConfig> = None,
+    tab_list: Option<TabListConfig> = None,
+    server_list: Option<ServerListConfig> = None,
+    resource_pack: Option<ResourcePackConfig> = None,
+    restart_fallback_address: String = String::new(),
+    aliases: Option<HashMap<String, String>> = None,
+    rcon: Option<RconConfig> = None,
+    admin_api: Option<AdminApiConfig> = None,
+    discord: Option<DiscordConfig> = None,
+    webhooks: Option<Vec<WebhookConfig>> = None,
+    telemetry: Option<TelemetryConfig> = None,
+    mqtt: Option<MqttConfig> = None
+}
+
+/// Toggles for vanilla redstone quirks in the interpreter. Disabling them gives an idealized
+/// simulation instead of a bug-compatible one.
+#[derive(Serialize, Deserialize)]
+pub struct QuirksConfig {
+    pub zero_tick_pistons: bool,
+    pub repeater_tick_priority: bool,
+    pub comparator_tick_priority: bool,
+}
+
+impl Default for QuirksConfig {
+    fn default() -> Self {
+        QuirksConfig {
+            zero_tick_pistons: true,
+            repeater_tick_priority: true,
+            comparator_tick_priority: true,
+        }
+    }
+}
+
+/// The layout of the plot grid and what new plots are generated with. Plots saved with a different
+/// scale can't be loaded anymore after it is changed.
+#[derive(Serialize, Deserialize)]
+pub struct PlotWorldConfig {
+    /// The generator new plots are made with, either `flat` or `void`
+    pub generator: String,
+    /// Plots are 2^scale chunks wide
+    pub scale: u32,
+    /// The lowest y blocks can be placed at, a multiple of 16. Plots saved with a different
+    /// range keep their blocks where they are, except for those outside of the new range.
+    pub min_y: i32,
+    /// The height of the world in blocks, a multiple of 16
+    pub height: u32,
+    /// The width of the road between two plots, in blocks. Each plot has half of the road on its
+    /// edges.
+    pub road_width: u32,
+    /// How many layers of blocks new plots are generated with
+    pub floor_height: u32,
+    pub floor_block: String,
+    pub road_block: String,
+    /// The block placed along the edge of each plot, or empty for no wall
+    pub wall_block: String,
+}
+
+impl Default for PlotWorldConfig {
+    fn default() -> Self {
+        PlotWorldConfig {
+            generator: "flat".to_string(),
+            scale: 5,
+            min_y: -64,
+            height: 384,
+            road_width: 2,
+            floor_height: 8,
+            floor_block: "sandstone".to_string(),
+            road_block: "stone_bricks".to_string(),
+            wall_block: String::new(),
+        }
+    }
+}
+
+/// Limits on what players can do with worldedit, so that it can be given out on public servers.
+/// Negative values mean there is no limit. Players with the `worldedit.limit.unrestricted`
+/// permission ignore them.
+#[derive(Serialize, Deserialize)]
+pub struct WorldEditLimitsConfig {
+    pub max_selection_volume: i64,
+    pub max_clipboard_volume: i64,
+    pub max_operations_per_second: i64,
+}
+
+impl Default for WorldEditLimitsConfig {
+    fn default() -> Self {
+        WorldEditLimitsConfig {
+            max_selection_volume: -1,
+            max_clipboard_volume: -1,
+            max_operations_per_second: -1,
+        }
+    }
+}
+
+/// Plots of owners who haven't joined the server for a while are saved to the schematic archive,
+/// cleared and unclaimed so that other players can use them.
+#[derive(Serialize, Deserialize)]
+pub struct PlotExpiryConfig {
+    /// How many days the owner has to be away for before their plots expire
+    pub inactive_days: u64,
+    /// How often to look for expired plots, in hours
+    pub check_interval: u64,
+}
+
+/// Scheduling controls for the threads that plots run on, for hosts where plots compete for the
+/// same cores.
+#[derive(Serialize, Deserialize)]
+pub struct PlotThreadsConfig {
+    /// The cores plot threads are pinned to, handed out in turn. Empty means threads aren't pinned.
+    #[serde(default)]
+    pub cores: Vec<usize>,
+    /// The nice value of plot threads, from -20 (highest priority) to 19 (lowest)
+    #[serde(default)]
+    pub priority: i32,
+    /// How many plots can run above `high_rtps_threshold` at once. 0 means there is no limit.
+    #[serde(default)]
+    pub max_high_rtps_plots: usize,
+    #[serde(default = "default_high_rtps_threshold")]
+    pub high_rtps_threshold: u32,
+}
+
+fn default_high_rtps_threshold() -> u32 {
+    1000
+}
+
+/// The watchdog looks out for plots that stop responding or crash. Crashed plots are restored from
+/// their last save instead of taking their players down with them.
+#[derive(Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// How long a plot can go without updating before admins are told that it stalled, in seconds.
+    /// 0 turns off stall detection.
+    pub stall_timeout: u64,
+    /// How often plots with players on them are saved, in seconds. 0 turns off autosaves.
+    pub autosave_interval: u64,
+    pub restart_crashed_plots: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            stall_timeout: 30,
+            autosave_interval: 300,
+            restart_crashed_plots: true,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -82,3 +364,191 @@ pub struct VelocityConfig {
     pub enabled: bool,
     pub secret: String,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct BungeeCordConfig {
+    pub enabled: bool,
+    /// The addresses of the proxies players may connect through. Anyone else could send a
+    /// forwarded uuid of their choosing, so this should only be left empty if the server can't be
+    /// reached without going through the proxy.
+    #[serde(default)]
+    pub allowed_proxies: Vec<IpAddr>,
+}
+
+/// Limits on how fast clients can connect and send packets, so that bots can't flood the server.
+/// 0 means there is no limit.
+#[derive(Serialize, Deserialize)]
+pub struct ThrottleConfig {
+    /// How many times each address can connect per minute
+    pub connections_per_minute: u32,
+    /// How many new connections are handled each second. The others wait in a queue.
+    pub logins_per_second: u32,
+    /// How many packets each connection can send per second before it is disconnected
+    pub packets_per_second: u32,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            connections_per_minute: 30,
+            logins_per_second: 20,
+            packets_per_second: 1000,
+        }
+    }
+}
+
+/// An address the server accepts connections on in addition to `bind_address`
+#[derive(Serialize, Deserialize)]
+pub struct ListenerConfig {
+    pub bind_address: String,
+    /// Whether connections have to start with a PROXY protocol header
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// The load balancers PROXY protocol headers are accepted from, or empty to trust everyone
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Reading the real address of players from the PROXY protocol header that TCP load balancers like
+/// HAProxy send
+#[derive(Serialize, Deserialize)]
+pub struct ProxyProtocolConfig {
+    pub enabled: bool,
+    /// The addresses of the load balancers. Connections from anywhere else are closed, and if this
+    /// is empty every connection is trusted.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// The Source RCON protocol, for running console commands from other tools
+#[derive(Serialize, Deserialize)]
+pub struct RconConfig {
+    pub bind_address: String,
+    pub password: String,
+}
+
+/// An HTTP API for web panels to manage the server with
+#[derive(Serialize, Deserialize)]
+pub struct AdminApiConfig {
+    pub bind_address: String,
+    /// The token requests have to carry as `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+/// A bot relaying chat between the game and a Discord channel
+#[derive(Serialize, Deserialize)]
+pub struct DiscordConfig {
+    pub token: String,
+    pub channel_id: String,
+    /// How often the channel is checked for new messages, in seconds
+    #[serde(default = "default_discord_poll_interval")]
+    pub poll_interval: u64,
+    /// How messages from Discord are shown in the game. `{username}` and `{message}` are replaced
+    /// with the author and the message.
+    #[serde(default = "default_discord_chat_format")]
+    pub chat_format: String,
+}
+
+/// An MQTT broker that named components on plots are bridged to
+#[derive(Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// The address of the broker, like `localhost:1883`
+    pub broker: String,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The start of every topic, which are `{topic_prefix}/{plot x},{plot z}/{name}`
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+fn default_mqtt_client_id() -> String {
+    "mchprs".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "mchprs".to_string()
+}
+
+/// Crash reports are submitted to maintainers when this is set. They are anonymized first.
+#[derive(Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    pub crash_report_url: String,
+}
+
+/// A URL that is sent a JSON payload when something happens on the server
+#[derive(Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The events the webhook is sent, or every event if this is empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// The JSON that is sent, where `{name}` is replaced with the fields of the event
+    #[serde(default = "default_webhook_payload")]
+    pub payload: String,
+}
+
+fn default_webhook_payload() -> String {
+    r#"{"content": "{message}"}"#.to_string()
+}
+
+fn default_discord_poll_interval() -> u64 {
+    3
+}
+
+fn default_discord_chat_format() -> String {
+    "&9[Discord] &r<{username}> {message}".to_string()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SchematicHttpConfig {
+    pub bind_address: String,
+    /// The address players reach the http server at, used to build download links
+    pub public_url: String,
+    /// How long a download link stays valid, in seconds
+    pub link_lifetime: u64,
+    /// The largest schematic that can be uploaded, in bytes
+    pub max_upload_size: u64,
+}
+
+/// The header and footer of the tab list. `{plot}`, `{tps}`, `{rtps}`, `{redpiler}` and `{online}`
+/// are replaced with the state of the plot the player is on and the number of players online.
+#[derive(Serialize, Deserialize)]
+pub struct TabListConfig {
+    pub header: String,
+    pub footer: String,
+    /// How often the tab list is sent again, in milliseconds
+    pub refresh_interval: u64,
+}
+
+/// What the server looks like in the server list. It is read again when the config changes.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServerListConfig {
+    /// The path of a 64x64 PNG shown next to the server, or empty for none
+    #[serde(default)]
+    pub favicon: String,
+    /// The max player count shown instead of `max_players`
+    pub max_players: Option<i64>,
+    /// Whether hovering over the player count lists the players online
+    #[serde(default)]
+    pub show_players: bool,
+    /// Lines shown when hovering over the player count instead of the players online
+    #[serde(default)]
+    pub sample: Vec<String>,
+}
+
+/// The resource pack players are asked to download when they join
+#[derive(Serialize, Deserialize)]
+pub struct ResourcePackConfig {
+    pub url: String,
+    /// The SHA-1 of the pack as a hex string. The client downloads the pack again when it changes.
+    #[serde(default)]
+    pub hash: String,
+    /// Whether players who decline the pack are kicked
+    #[serde(default)]
+    pub forced: bool,
+    /// The message shown when players are asked to download the pack, which can use `&` codes
+    #[serde(default)]
+    pub prompt: String,
+}
@@ -0,0 +1,209 @@
+//! A bridge between the game chat and a Discord channel. Chat messages, players joining and
+//! leaving, and admin alerts are posted to the channel, and messages in the channel are shown in
+//! the game. It uses the REST api of Discord, polling the channel for new messages.
+
+use crate::config::DiscordConfig;
+use crate::server::Message;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info, warn};
+
+const API_URL: &str = "https://discord.com/api/v10";
+/// The longest message Discord accepts
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    username: String,
+    global_name: Option<String>,
+    #[serde(default)]
+    bot: bool,
+}
+
+#[derive(Deserialize)]
+struct DiscordMessage {
+    id: String,
+    author: DiscordUser,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct RateLimit {
+    retry_after: f64,
+}
+
+/// Escapes the characters Discord would format text with
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub struct DiscordBridge {
+    outgoing: UnboundedSender<String>,
+}
+
+impl DiscordBridge {
+    pub fn start(
+        config: &'static DiscordConfig,
+        rt: &Runtime,
+        server: Sender<Message>,
+    ) -> DiscordBridge {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let client = reqwest::Client::new();
+        rt.spawn(send_messages(client.clone(), config, outgoing_rx));
+        rt.spawn(poll_messages(client, config, server));
+        info!("Discord bridge started for channel {}", config.channel_id);
+        DiscordBridge { outgoing }
+    }
+
+    /// Posts a message to the channel. `content` is Discord markdown, so text from players has to
+    /// be escaped with `escape_markdown`.
+    pub fn send(&self, content: String) {
+        let _ = self.outgoing.send(content);
+    }
+}
+
+fn messages_url(config: &DiscordConfig) -> String {
+    format!("{}/channels/{}/messages", API_URL, config.channel_id)
+}
+
+async fn post_message(
+    client: &reqwest::Client,
+    config: &DiscordConfig,
+    content: &str,
+) -> Result<()> {
+    loop {
+        let res = client
+            .post(messages_url(config))
+            .header("Authorization", format!("Bot {}", config.token))
+            // Players shouldn't be able to ping everyone on the Discord server
+            .json(&json!({ "content": content, "allowed_mentions": { "parse": [] } }))
+            .send()
+            .await?;
+        if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let rate_limit: RateLimit = res.json().await?;
+            tokio::time::sleep(Duration::from_secs_f64(rate_limit.retry_after)).await;
+            continue;
+        }
+        if !res.status().is_success() {
+            bail!("Discord responded with {}", res.status());
+        }
+        return Ok(());
+    }
+}
+
+async fn send_messages(
+    client: reqwest::Client,
+    config: &'static DiscordConfig,
+    mut outgoing: UnboundedReceiver<String>,
+) {
+    while let Some(message) = outgoing.recv().await {
+        // Messages that piled up are sent together, so that busy chats don't hit rate limits
+        let mut content = message;
+        while let Ok(next) = outgoing.try_recv() {
+            if content.len() + next.len() + 1 > MAX_MESSAGE_LENGTH {
+                if let Err(err) = post_message(&client, config, &content).await {
+                    warn!("Failed to send message to Discord: {}", err);
+                }
+                content = next;
+            } else {
+                content.push('\n');
+                content.push_str(&next);
+            }
+        }
+        let mut end = content.len().min(MAX_MESSAGE_LENGTH);
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Err(err) = post_message(&client, config, &content[..end]).await {
+            warn!("Failed to send message to Discord: {}", err);
+        }
+    }
+}
+
+async fn get_messages(
+    client: &reqwest::Client,
+    config: &DiscordConfig,
+    after: Option<&str>,
+) -> Result<Vec<DiscordMessage>> {
+    let mut request = client
+        .get(messages_url(config))
+        .header("Authorization", format!("Bot {}", config.token));
+    request = match after {
+        Some(after) => request.query(&[("after", after), ("limit", "50")]),
+        None => request.query(&[("limit", "1")]),
+    };
+    let res = request.send().await?.error_for_status()?;
+    Ok(res.json().await?)
+}
+
+async fn poll_messages(
+    client: reqwest::Client,
+    config: &'static DiscordConfig,
+    server: Sender<Message>,
+) {
+    // Only messages sent after the server started are shown
+    let mut last_id = loop {
+        match get_messages(&client, config, None).await {
+            Ok(messages) => break messages.first().map(|message| message.id.clone()),
+            Err(err) => {
+                error!("Failed to read Discord channel: {}", err);
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        }
+    };
+    let interval = Duration::from_secs(config.poll_interval.max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        let messages = match &last_id {
+            Some(last_id) => get_messages(&client, config, Some(last_id)).await,
+            // The channel was empty, so everything in it is new
+            None => get_messages(&client, config, Some("0")).await,
+        };
+        let mut messages = match messages {
+            Ok(messages) => messages,
+            Err(err) => {
+                warn!("Failed to read Discord channel: {}", err);
+                continue;
+            }
+        };
+        // Discord lists the newest messages first
+        messages.reverse();
+        for message in messages {
+            last_id = Some(message.id);
+            if message.author.bot || message.content.is_empty() {
+                continue;
+            }
+            let name = message
+                .author
+                .global_name
+                .unwrap_or(message.author.username);
+            if server
+                .send(Message::DiscordChat(name, message.content))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+#[test]
+fn escape_markdown_test() {
+    assert_eq!(escape_markdown("a_b *c*"), "a\\_b \\*c\\*");
+    assert_eq!(escape_markdown("`x` > y"), "\\`x\\` \\> y");
+}
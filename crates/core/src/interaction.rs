@@ -1,14 +1,38 @@
 use crate::config::CONFIG;
 use crate::player::Player;
-use crate::plot::{PlotWorld, PLOT_BLOCK_HEIGHT};
-use mchprs_blocks::block_entities::BlockEntity;
+use crate::plot::{PlotWorld, PLOT_MAX_Y, PLOT_MIN_Y};
+use mchprs_blocks::block_entities::{BlockEntity, MusicDisc};
 use mchprs_blocks::blocks::*;
 use mchprs_blocks::items::{Item, ItemStack};
-use mchprs_blocks::{BlockFace, BlockPos, SignType};
+use mchprs_blocks::{BlockDirection, BlockFace, BlockFacing, BlockPos, SignRotation, SignType};
 use mchprs_network::packets::clientbound::{COpenSignEditor, ClientBoundPacket};
 use mchprs_redstone as redstone;
 use mchprs_utils::nbt_unwrap_val;
-use mchprs_world::World;
+use mchprs_world::{GameEvent, World};
+
+/// The world event that starts playing the record in a jukebox
+const JUKEBOX_PLAY_EVENT: i32 = 1010;
+/// The world event that stops the record playing in a jukebox
+const JUKEBOX_STOP_EVENT: i32 = 1011;
+
+/// Inserts `record` into the jukebox at `pos`, or ejects its record if `record` is `None`
+pub fn set_jukebox_record(world: &mut impl World, pos: BlockPos, record: Option<MusicDisc>) {
+    world.set_block(
+        pos,
+        Block::Jukebox {
+            has_record: record.is_some(),
+        },
+    );
+    world.set_block_entity(pos, BlockEntity::Jukebox { record });
+    match record {
+        Some(disc) => {
+            let item_id = Item::MusicDisc { disc }.get_id() as i32;
+            world.play_world_event(pos, JUKEBOX_PLAY_EVENT, item_id);
+        }
+        None => world.play_world_event(pos, JUKEBOX_STOP_EVENT, 0),
+    }
+    redstone::comparator::update_comparators_around(world, pos);
+}
 
 pub fn on_use(
     block: Block,
@@ -45,6 +69,42 @@ pub fn on_use(
             }
             ActionResult::Pass
         }
+        Block::OakDoor { door } => {
+            redstone::door::set_door_state(world, pos, !door.open, door.powered);
+            ActionResult::Success
+        }
+        Block::OakFenceGate {
+            facing,
+            in_wall,
+            open,
+            powered,
+        } => {
+            // Fence gates always swing away from the player opening them
+            let facing = if !open && facing == player.get_direction().opposite() {
+                player.get_direction()
+            } else {
+                facing
+            };
+            let new_block = Block::OakFenceGate {
+                facing,
+                in_wall,
+                open: !open,
+                powered,
+            };
+            redstone::set_block_state(world, pos, new_block);
+            ActionResult::Success
+        }
+        Block::Jukebox { has_record } => {
+            if has_record {
+                set_jukebox_record(world, pos, None);
+                ActionResult::Success
+            } else if let Some(Item::MusicDisc { disc }) = item_in_hand {
+                set_jukebox_record(world, pos, Some(disc));
+                ActionResult::Success
+            } else {
+                ActionResult::Pass
+            }
+        }
         b if b.has_block_entity() => {
             // Open container
             let block_entity = world.get_block_entity(pos);
@@ -119,7 +179,39 @@ pub fn get_state_for_placement(
             lit: redstone::redstone_lamp_should_be_lit(world, pos),
         },
         Item::RedstoneBlock {} => Block::RedstoneBlock {},
+        Item::Piston {} => Block::Piston {
+            extended: false,
+            facing: get_nearest_looking_direction(context.player).opposite(),
+        },
+        Item::StickyPiston {} => Block::StickyPiston {
+            extended: false,
+            facing: get_nearest_looking_direction(context.player).opposite(),
+        },
+        Item::Observer {} => Block::Observer {
+            facing: get_nearest_looking_direction(context.player),
+            powered: false,
+        },
         Item::Hopper {} => Block::Hopper {},
+        Item::PoweredRail {} | Item::DetectorRail {} | Item::ActivatorRail {} => {
+            let shape = match context.player.get_direction() {
+                BlockDirection::North | BlockDirection::South => RailShape::NorthSouth,
+                BlockDirection::East | BlockDirection::West => RailShape::EastWest,
+            };
+            let powered = false;
+            match item {
+                Item::PoweredRail {} => Block::PoweredRail { powered, shape },
+                Item::DetectorRail {} => Block::DetectorRail { powered, shape },
+                _ => Block::ActivatorRail { powered, shape },
+            }
+        }
+        Item::Dispenser {} => Block::Dispenser {
+            facing: get_nearest_looking_direction(context.player).opposite(),
+            triggered: false,
+        },
+        Item::Dropper {} => Block::Dropper {
+            facing: get_nearest_looking_direction(context.player).opposite(),
+            triggered: false,
+        },
         Item::Terracotta {} => Block::Terracotta {},
         Item::ColoredTerracotta { color } => Block::ColoredTerracotta { color },
         Item::Concrete { color } => Block::Concrete { color },
@@ -141,7 +233,9 @@ pub fn get_state_for_placement(
             BlockFace::Bottom => Block::Air {},
             BlockFace::Top => Block::Sign {
                 sign_type: SignType::from_item_type(sign_type),
-                rotation: (((180.0 + context.player.yaw) * 16.0 / 360.0) + 0.5).floor() as u32 & 15,
+                rotation: SignRotation(
+                    (((180.0 + context.player.yaw) * 16.0 / 360.0) + 0.5).floor() as u32 & 15,
+                ),
             },
             _ => Block::WallSign {
                 sign_type: SignType::from_item_type(sign_type),
@@ -152,7 +246,8 @@ pub fn get_state_for_placement(
             wire: redstone::wire::get_state_for_placement(world, pos),
         },
         Item::Barrel {} => Block::Barrel {},
-        Item::Target {} => Block::Target {},
+        Item::Target {} => Block::Target { power: 0 },
+        Item::Jukebox {} => Block::Jukebox { has_record: false },
         Item::StainedGlass { color } => Block::StainedGlass { color },
         Item::SmoothStoneSlab {} => Block::SmoothStoneSlab {},
         Item::QuartzSlab {} => Block::QuartzSlab {},
@@ -177,6 +272,57 @@ pub fn get_state_for_placement(
                 powered: false,
             },
         },
+        Item::IronDoor {} | Item::OakDoor {} => {
+            let mut door = Door {
+                facing: context.player.get_direction(),
+                ..Default::default()
+            };
+            door.hinge = redstone::door::get_hinge_for_placement(world, door, pos);
+            if !world
+                .get_block(pos.offset(BlockFace::Top))
+                .can_place_block_in()
+            {
+                Block::Air {}
+            } else if item == (Item::IronDoor {}) {
+                Block::IronDoor { door }
+            } else {
+                Block::OakDoor { door }
+            }
+        }
+        Item::OakFenceGate {} => Block::OakFenceGate {
+            facing: context.player.get_direction(),
+            in_wall: false,
+            open: false,
+            powered: false,
+        },
+        Item::Bell {} => match context.block_face {
+            BlockFace::Top => Block::Bell {
+                attachment: BellAttachment::Floor,
+                facing: context.player.get_direction(),
+                powered: false,
+            },
+            BlockFace::Bottom => Block::Bell {
+                attachment: BellAttachment::Ceiling,
+                facing: context.player.get_direction(),
+                powered: false,
+            },
+            face => {
+                let facing = face.unwrap_direction().opposite();
+                let between_walls = world.get_block(pos.offset(facing.block_face())).is_cube()
+                    && world
+                        .get_block(pos.offset(facing.opposite().block_face()))
+                        .is_cube();
+                Block::Bell {
+                    attachment: if between_walls {
+                        BellAttachment::DoubleWall
+                    } else {
+                        BellAttachment::SingleWall
+                    },
+                    facing,
+                    powered: false,
+                }
+            }
+        },
         Item::NoteBlock {} => Block::NoteBlock {
             instrument: Instrument::Harp,
             note: 0,
@@ -218,6 +364,17 @@ fn read_block_entity_tag(nbt: &nbt::Blob, block_id: &str) -> Option<BlockEntity>
     None
 }
 
+/// Returns the direction the player is looking in, including up and down
+pub fn get_nearest_looking_direction(player: &Player) -> BlockFacing {
+    if player.pitch > 45.0 {
+        BlockFacing::Down
+    } else if player.pitch < -45.0 {
+        BlockFacing::Up
+    } else {
+        player.get_direction().block_facing()
+    }
+}
+
 pub fn place_in_world(
     block: Block,
     world: &mut impl World,
@@ -232,18 +389,44 @@ pub fn place_in_world(
         };
     }
     world.set_block(pos, block);
+    if let Some(door) = redstone::door::get_door(block) {
+        let upper = Door {
+            half: DoorHalf::Upper,
+            ..door
+        };
+        world.set_block(
+            pos.offset(BlockFace::Top),
+            redstone::door::with_door(block, upper),
+        );
+    }
     change_surrounding_blocks(world, pos);
     if let Block::RedstoneWire { .. } = block {
         redstone::update_wire_neighbors(world, pos);
     } else {
         redstone::update_surrounding_blocks(world, pos);
     }
+    if let Block::Piston { .. }
+    | Block::StickyPiston { .. }
+    | Block::IronDoor { .. }
+    | Block::OakDoor { .. }
+    | Block::OakFenceGate { .. }
+    | Block::PoweredRail { .. }
+    | Block::ActivatorRail { .. } = block
+    {
+        // Pistons, doors, fence gates and rails check if they are powered as soon as they are
+        // placed
+        redstone::update(block, world, pos);
+    }
+    redstone::sculk::emit_vibration(world, pos, GameEvent::BlockPlace);
 }
 
 pub fn destroy(block: Block, world: &mut impl World, pos: BlockPos) {
     if block.has_block_entity() {
         world.delete_block_entity(pos);
     }
+    if let Block::Jukebox { has_record: true } = block {
+        world.play_world_event(pos, JUKEBOX_STOP_EVENT, 0);
+    }
 
     match block {
         Block::RedstoneWire { .. } => {
@@ -276,12 +459,51 @@ pub fn destroy(block: Block, world: &mut impl World, pos: BlockPos) {
                 }
             }
         }
+        Block::Piston {
+            extended: true,
+            facing,
+        }
+        | Block::StickyPiston {
+            extended: true,
+            facing,
+        } => {
+            world.set_block(pos, Block::Air {});
+            let head_pos = pos.offset(facing.block_face());
+            if let Block::PistonHead { .. } = world.get_block(head_pos) {
+                destroy(Block::Air {}, world, head_pos);
+            }
+            change_surrounding_blocks(world, pos);
+            redstone::update_surrounding_blocks(world, pos);
+        }
+        Block::IronDoor { door } | Block::OakDoor { door } => {
+            world.set_block(pos, Block::Air {});
+            let other_pos = redstone::door::other_half_pos(door, pos);
+            if redstone::door::get_door(world.get_block(other_pos)).is_some() {
+                world.set_block(other_pos, Block::Air {});
+                change_surrounding_blocks(world, other_pos);
+                redstone::update_surrounding_blocks(world, other_pos);
+            }
+            change_surrounding_blocks(world, pos);
+            redstone::update_surrounding_blocks(world, pos);
+        }
+        Block::PistonHead { facing, .. } => {
+            world.set_block(pos, Block::Air {});
+            let base_pos = pos.offset(facing.opposite().block_face());
+            if let Block::Piston { extended: true, .. }
+            | Block::StickyPiston { extended: true, .. } = world.get_block(base_pos)
+            {
+                destroy(Block::Air {}, world, base_pos);
+            }
+            change_surrounding_blocks(world, pos);
+            redstone::update_surrounding_blocks(world, pos);
+        }
         _ => {
             world.set_block(pos, Block::Air {});
             change_surrounding_blocks(world, pos);
             redstone::update_surrounding_blocks(world, pos);
         }
     }
+    redstone::sculk::emit_vibration(world, pos, GameEvent::BlockDestroy);
 }
 
 pub fn is_valid_position(block: Block, world: &impl World, pos: BlockPos) -> bool {
@@ -294,7 +516,10 @@ pub fn is_valid_position(block: Block, world: &impl World, pos: BlockPos) -> boo
         | Block::RedstoneComparator { .. }
         | Block::RedstoneRepeater { .. }
         | Block::Sign { .. }
-        | Block::RedstoneTorch { .. } => {
+        | Block::RedstoneTorch { .. }
+        | Block::PoweredRail { .. }
+        | Block::DetectorRail { .. }
+        | Block::ActivatorRail { .. } => {
             let bottom_block = world.get_block(pos.offset(BlockFace::Bottom));
             bottom_block.is_cube()
         }
@@ -321,6 +546,38 @@ pub fn is_valid_position(block: Block, world: &impl World, pos: BlockPos) -> boo
                 parent_block.is_cube()
             }
         },
+        Block::IronDoor { door } | Block::OakDoor { door } => match door.half {
+            DoorHalf::Lower => {
+                let bottom_block = world.get_block(pos.offset(BlockFace::Bottom));
+                bottom_block.is_cube()
+            }
+            DoorHalf::Upper => {
+                let bottom_block = world.get_block(pos.offset(BlockFace::Bottom));
+                redstone::door::get_door(bottom_block).is_some()
+            }
+        },
+        Block::Bell {
+            attachment, facing, ..
+        } => match attachment {
+            BellAttachment::Floor => {
+                let bottom_block = world.get_block(pos.offset(BlockFace::Bottom));
+                bottom_block.is_cube()
+            }
+            BellAttachment::Ceiling => {
+                let top_block = world.get_block(pos.offset(BlockFace::Top));
+                top_block.is_cube()
+            }
+            BellAttachment::SingleWall => {
+                let parent_block = world.get_block(pos.offset(facing.block_face()));
+                parent_block.is_cube()
+            }
+            BellAttachment::DoubleWall => {
+                world.get_block(pos.offset(facing.block_face())).is_cube()
+                    || world
+                        .get_block(pos.offset(facing.opposite().block_face()))
+                        .is_cube()
+            }
+        },
         Block::StoneButton { button } => match button.face {
             ButtonFace::Floor => {
                 let bottom_block = world.get_block(pos.offset(BlockFace::Bottom));
@@ -347,13 +604,14 @@ pub fn change(block: Block, world: &mut impl World, pos: BlockPos, direction: Bl
     }
     if let Block::RedstoneWire { wire } = block {
         let new_state = redstone::wire::on_neighbor_changed(wire, world, pos, direction);
-        if world.set_block(pos, Block::RedstoneWire { wire: new_state }) {
+        if redstone::set_block_state(world, pos, Block::RedstoneWire { wire: new_state }) {
             redstone::update_wire_neighbors(world, pos);
         }
     }
 }
 
 fn change_surrounding_blocks(world: &mut impl World, pos: BlockPos) {
+    redstone::observer::update_observers(world, pos);
     for direction in &BlockFace::values() {
         let neighbor_pos = pos.offset(*direction);
         let block = world.get_block(neighbor_pos);
@@ -420,7 +678,7 @@ pub fn use_item_on_block(
         return false;
     }
 
-    if can_place && (0..PLOT_BLOCK_HEIGHT).contains(&block_pos.y) {
+    if can_place && (*PLOT_MIN_Y..*PLOT_MAX_Y).contains(&block_pos.y) {
         let block = get_state_for_placement(world, block_pos, item.item_type, &ctx);
 
         match block {
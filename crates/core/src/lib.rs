@@ -2,13 +2,21 @@
 
 #[macro_use]
 mod utils;
+mod admin_api;
 mod config;
+mod discord;
 mod interaction;
 mod permissions;
 mod player;
 pub mod plot;
+mod plugins;
 mod profile;
+mod rcon;
+mod schematic_http;
 pub mod server;
+mod server_list;
+mod tcp_server;
+mod webhooks;
 
 #[macro_use]
 extern crate bitflags;
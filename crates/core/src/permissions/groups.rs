@@ -0,0 +1,309 @@
+//! The built-in permissions, stored in `permissions.toml` in the server directory. Groups grant or
+//! deny nodes and can inherit from other groups, and players are put in groups and can have nodes
+//! of their own. Everyone is in the `default` group.
+
+use super::{PermissionNode, PlayerPermissionsCache};
+use crate::utils::HyphenatedUUID;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::sync::RwLock;
+
+const PERMISSIONS_FILE: &str = "permissions.toml";
+
+const HEADER: &str = "\
+# Permissions for MCHPRS. Nodes starting with `-` are denied, and `*` matches every node below it.
+# Player nodes come first, then the groups of the player and the groups they inherit from, and
+# then the `default` group, which everyone is in. Within each of these, more specific nodes win.
+";
+
+static PERMISSIONS: Lazy<RwLock<Permissions>> = Lazy::new(Default::default);
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Group {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inherits: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct PlayerEntry {
+    /// The username of the player, only there to make the file easier to read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Permissions {
+    #[serde(default)]
+    pub groups: BTreeMap<String, Group>,
+    /// Players by their hyphenated uuid
+    #[serde(default)]
+    pub players: BTreeMap<String, PlayerEntry>,
+}
+
+/// Sets `node` in `permissions` to be granted, denied or neither. Returns false if nothing changed.
+fn set_node(permissions: &mut Vec<String>, node: &str, value: Option<bool>) -> bool {
+    let node = node.trim_start_matches('-');
+    let new = value.map(|value| match value {
+        true => node.to_string(),
+        false => format!("-{}", node),
+    });
+    if new.is_some() && permissions.contains(new.as_ref().unwrap()) {
+        return false;
+    }
+    let len = permissions.len();
+    permissions.retain(|existing| existing.trim_start_matches('-') != node);
+    let removed = permissions.len() != len;
+    match new {
+        Some(new) => {
+            permissions.push(new);
+            true
+        }
+        None => removed,
+    }
+}
+
+/// Turns the nodes of one player or group into permission nodes, with the most specific first so
+/// that `-plots.admin.*` wins over `plots.*`
+fn nodes_by_specificity(permissions: &[String]) -> impl Iterator<Item = PermissionNode> + '_ {
+    let mut permissions: Vec<&String> = permissions.iter().collect();
+    permissions.sort_by_key(|node| {
+        let node = node.trim_start_matches('-');
+        (node.contains('*'), Reverse(node.split('.').count()))
+    });
+    permissions
+        .into_iter()
+        .map(|node| match node.strip_prefix('-') {
+            Some(node) => PermissionNode::new(node, 0, "global".to_string()),
+            None => PermissionNode::new(node, 1, "global".to_string()),
+        })
+}
+
+impl Permissions {
+    fn player_key(uuid: u128) -> String {
+        HyphenatedUUID(uuid).to_string()
+    }
+
+    pub fn player(&self, uuid: u128) -> Option<&PlayerEntry> {
+        self.players.get(&Self::player_key(uuid))
+    }
+
+    fn player_mut(&mut self, uuid: u128, name: &str) -> &mut PlayerEntry {
+        let entry = self.players.entry(Self::player_key(uuid)).or_default();
+        entry.name = Some(name.to_string());
+        entry
+    }
+
+    /// Removes the entry of a player if it no longer does anything
+    fn clean_player(&mut self, uuid: u128) {
+        let key = Self::player_key(uuid);
+        if let Some(entry) = self.players.get(&key) {
+            if entry.groups.is_empty() && entry.permissions.is_empty() {
+                self.players.remove(&key);
+            }
+        }
+    }
+
+    pub fn set_group_node(&mut self, group: &str, node: &str, value: Option<bool>) -> bool {
+        if value.is_none() && !self.groups.contains_key(group) {
+            return false;
+        }
+        let group = self.groups.entry(group.to_string()).or_default();
+        set_node(&mut group.permissions, node, value)
+    }
+
+    pub fn set_player_node(
+        &mut self,
+        uuid: u128,
+        name: &str,
+        node: &str,
+        value: Option<bool>,
+    ) -> bool {
+        let changed = set_node(&mut self.player_mut(uuid, name).permissions, node, value);
+        self.clean_player(uuid);
+        changed
+    }
+
+    /// Makes `group` inherit the nodes of `parent`, or stop inheriting them
+    pub fn set_group_parent(&mut self, group: &str, parent: &str, inherit: bool) -> bool {
+        if !inherit && !self.groups.contains_key(group) {
+            return false;
+        }
+        let inherits = &mut self.groups.entry(group.to_string()).or_default().inherits;
+        if inherit == inherits.iter().any(|p| p == parent) {
+            return false;
+        }
+        match inherit {
+            true => inherits.push(parent.to_string()),
+            false => inherits.retain(|p| p != parent),
+        }
+        true
+    }
+
+    pub fn set_player_group(&mut self, uuid: u128, name: &str, group: &str, member: bool) -> bool {
+        let groups = &mut self.player_mut(uuid, name).groups;
+        let changed = member != groups.iter().any(|g| g == group);
+        match member {
+            true if changed => groups.push(group.to_string()),
+            false => groups.retain(|g| g != group),
+            _ => {}
+        }
+        self.clean_player(uuid);
+        changed
+    }
+
+    /// Adds the groups reachable from `roots` to `order`, breadth first, skipping groups that
+    /// were already visited so inheritance cycles don't loop forever
+    fn visit_groups<'a>(
+        &'a self,
+        roots: impl IntoIterator<Item = &'a str>,
+        visited: &mut HashSet<&'a str>,
+        order: &mut Vec<&'a Group>,
+    ) {
+        let mut queue: VecDeque<&str> = roots.into_iter().collect();
+        while let Some(name) = queue.pop_front() {
+            if !visited.insert(name) {
+                continue;
+            }
+            let Some(group) = self.groups.get(name) else {
+                continue;
+            };
+            order.push(group);
+            queue.extend(group.inherits.iter().map(String::as_str));
+        }
+    }
+
+    /// All nodes that apply to a player, in the order they are looked at
+    fn resolve(&self, uuid: u128) -> Vec<PermissionNode> {
+        let player = self.player(uuid);
+        let mut visited = HashSet::new();
+        let mut groups = Vec::new();
+        let player_groups = player.into_iter().flat_map(|p| p.groups.iter());
+        self.visit_groups(player_groups.map(String::as_str), &mut visited, &mut groups);
+        self.visit_groups(["default"], &mut visited, &mut groups);
+
+        let mut nodes: Vec<_> = player
+            .map(|p| nodes_by_specificity(&p.permissions).collect())
+            .unwrap_or_default();
+        for group in groups {
+            nodes.extend(nodes_by_specificity(&group.permissions));
+        }
+        nodes
+    }
+}
+
+fn read() -> Result<Permissions> {
+    match fs::read_to_string(PERMISSIONS_FILE) {
+        Ok(str) => toml::from_str(&str).context("Failed to parse permissions.toml"),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            // Everyone can do everything until the file is changed, like before there were
+            // permissions
+            let mut permissions = Permissions::default();
+            permissions.set_group_node("default", "*", Some(true));
+            write(&permissions)?;
+            Ok(permissions)
+        }
+        Err(err) => Err(err).context("Failed to read permissions.toml"),
+    }
+}
+
+fn write(permissions: &Permissions) -> Result<()> {
+    let str = toml::to_string(permissions)?;
+    fs::write(PERMISSIONS_FILE, format!("{}\n{}", HEADER, str))
+        .context("Failed to write permissions.toml")
+}
+
+/// Reads `permissions.toml` again, creating it if it doesn't exist. Players keep their current
+/// permissions until their caches are loaded again.
+pub fn reload() -> Result<()> {
+    let permissions = read()?;
+    *PERMISSIONS.write().unwrap() = permissions;
+    Ok(())
+}
+
+/// Changes the permissions with `f` and saves them to `permissions.toml`
+pub fn edit<R>(f: impl FnOnce(&mut Permissions) -> R) -> Result<R> {
+    let mut permissions = PERMISSIONS.write().unwrap();
+    let result = f(&mut permissions);
+    write(&permissions)?;
+    Ok(result)
+}
+
+/// Looks at the permissions without changing them
+pub fn view<R>(f: impl FnOnce(&Permissions) -> R) -> R {
+    f(&PERMISSIONS.read().unwrap())
+}
+
+pub(super) fn load_player_cache(uuid: u128) -> PlayerPermissionsCache {
+    let nodes = PERMISSIONS.read().unwrap().resolve(uuid);
+    PlayerPermissionsCache { nodes }
+}
+
+#[test]
+fn resolve_order() {
+    let permissions: Permissions = toml::from_str(
+        r#"
+        [groups.default]
+        permissions = ["plots.*", "-mchprs.stop"]
+
+        [groups.builder]
+        inherits = ["helper"]
+        permissions = ["worldedit.*", "-worldedit.region.set"]
+
+        [groups.helper]
+        inherits = ["builder"]
+        permissions = ["mchprs.stop", "-plots.claim"]
+
+        [players.00000000-0000-0000-0000-000000000001]
+        groups = ["builder"]
+        permissions = ["plots.claim"]
+        "#,
+    )
+    .unwrap();
+    let cache = PlayerPermissionsCache {
+        nodes: permissions.resolve(1),
+    };
+    let allowed = |node| cache.get_node_val(node).is_some_and(|val| val > 0);
+    // Player nodes win over groups
+    assert!(allowed("plots.claim"));
+    // Exact nodes win over wildcards
+    assert!(!allowed("worldedit.region.set"));
+    assert!(allowed("worldedit.region.replace"));
+    // Inherited groups win over the default group
+    assert!(allowed("mchprs.stop"));
+    assert!(allowed("plots.visit"));
+    assert!(!allowed("mchprs.restart"));
+
+    let cache = PlayerPermissionsCache {
+        nodes: permissions.resolve(2),
+    };
+    assert_eq!(cache.get_node_val("mchprs.stop"), Some(0));
+    assert_eq!(cache.get_node_val("plots.claim"), Some(1));
+    assert_eq!(cache.get_node_val("worldedit.region.set"), None);
+}
+
+#[test]
+fn set_node_replaces_value() {
+    let mut permissions = Permissions::default();
+    assert!(permissions.set_group_node("builder", "worldedit.*", Some(true)));
+    assert!(!permissions.set_group_node("builder", "worldedit.*", Some(true)));
+    assert!(permissions.set_group_node("builder", "worldedit.*", Some(false)));
+    assert_eq!(permissions.groups["builder"].permissions, ["-worldedit.*"]);
+    assert!(permissions.set_group_node("builder", "-worldedit.*", None));
+    assert!(permissions.groups["builder"].permissions.is_empty());
+
+    assert!(permissions.set_player_group(1, "Player", "builder", true));
+    assert!(!permissions.set_player_group(1, "Player", "builder", true));
+    assert!(permissions.set_player_group(1, "Player", "builder", false));
+    // Players without groups or nodes are removed from the file
+    assert!(permissions.players.is_empty());
+}
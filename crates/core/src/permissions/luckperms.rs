@@ -0,0 +1,75 @@
+//! Permissions stored in the database of LuckPerms, which are managed through LuckPerms running
+//! on a proxy or another server.
+
+use super::{PermissionNode, PlayerPermissionsCache};
+use crate::utils::HyphenatedUUID;
+use anyhow::{anyhow, Context, Result};
+use mysql::prelude::*;
+use mysql::{OptsBuilder, Pool, PooledConn, Row};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+static POOL: OnceCell<Pool> = OnceCell::new();
+
+fn conn() -> Result<PooledConn> {
+    Ok(POOL
+        .get()
+        .context("Tried to get conn before permissions init")?
+        .get_conn()?)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermissionsConfig {
+    host: String,
+    db_name: String,
+    username: String,
+    password: String,
+    pub(super) server_context: String,
+}
+
+pub(super) fn init(config: PermissionsConfig) -> Result<()> {
+    let opts = OptsBuilder::new()
+        .ip_or_hostname(Some(config.host))
+        .db_name(Some(config.db_name))
+        .user(Some(config.username))
+        .pass(Some(config.password));
+    let pool = Pool::new(opts)?;
+    POOL.set(pool)
+        .map_err(|_| anyhow!("Tried to init permissions more than once"))?;
+
+    Ok(())
+}
+
+pub(super) fn load_player_cache(uuid: u128) -> Result<PlayerPermissionsCache> {
+    let uuid = HyphenatedUUID(uuid).to_string();
+    let mut conn = conn()?;
+    let res: Vec<Row> = conn.exec(
+        "
+        WITH RECURSIVE groups_inherited AS (
+            SELECT *
+            FROM luckperms_user_permissions
+            WHERE uuid LIKE ?
+            UNION
+            SELECT luckperms_group_permissions.*
+            FROM groups_inherited, luckperms_group_permissions
+            WHERE luckperms_group_permissions.name = SUBSTR(groups_inherited.permission, 7)
+        )
+        SELECT *
+        FROM groups_inherited;
+    ",
+        (&uuid,),
+    )?;
+
+    let mut nodes = Vec::new();
+    for row in res {
+        let path = String::from_value(row[2].clone());
+        let node = PermissionNode::new(
+            &path,
+            FromValue::from_value(row[3].clone()),
+            FromValue::from_value(row[4].clone()),
+        );
+        nodes.push(node);
+    }
+
+    Ok(PlayerPermissionsCache { nodes })
+}
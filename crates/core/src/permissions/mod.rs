@@ -1,23 +1,13 @@
-use crate::config::CONFIG;
-use crate::utils::HyphenatedUUID;
-use anyhow::{anyhow, Context, Result};
-use mysql::prelude::*;
-use mysql::{OptsBuilder, Pool, PooledConn, Row};
-use once_cell::sync::OnceCell;
-use serde::{Deserialize, Serialize};
+//! Permissions are dotted nodes like `plots.claim`, which can be granted or denied to players. They
+//! come from LuckPerms if it is configured, and from the groups in `permissions.toml` otherwise.
 
-static POOL: OnceCell<Pool> = OnceCell::new();
+pub mod groups;
+mod luckperms;
 
-fn conn() -> Result<PooledConn> {
-    Ok(POOL
-        .get()
-        .context("Tried to get conn before permissions init")?
-        .get_conn()?)
-}
+pub use luckperms::PermissionsConfig;
 
-fn config() -> &'static PermissionsConfig {
-    CONFIG.luckperms.as_ref().unwrap()
-}
+use crate::config::CONFIG;
+use anyhow::Result;
 
 #[derive(Debug)]
 enum PathSegment {
@@ -33,19 +23,41 @@ struct PermissionNode {
 }
 
 impl PermissionNode {
+    fn new(path: &str, value: i32, server_context: String) -> PermissionNode {
+        let path = path
+            .split('.')
+            .map(|s| match s {
+                "*" => PathSegment::WildCard,
+                s => PathSegment::Named(s.to_owned()),
+            })
+            .collect();
+        PermissionNode {
+            path,
+            value,
+            server_context,
+        }
+    }
+
     fn matches(&self, str: &str) -> bool {
-        if self.server_context != "global" && self.server_context != config().server_context {
+        if self.server_context != "global"
+            && CONFIG
+                .luckperms
+                .as_ref()
+                .is_none_or(|config| self.server_context != config.server_context)
+        {
             return false;
         }
 
         for (i, segment) in str.split('.').enumerate() {
-            match &self.path[i] {
-                PathSegment::WildCard => return true,
-                PathSegment::Named(name) => {
+            match self.path.get(i) {
+                Some(PathSegment::WildCard) => return true,
+                Some(PathSegment::Named(name)) => {
                     if name != segment {
                         return false;
                     }
                 }
+                // Nodes don't grant the nodes below them unless they end in a wildcard
+                None => return false,
             }
         }
         true
@@ -68,65 +80,22 @@ impl PlayerPermissionsCache {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct PermissionsConfig {
-    host: String,
-    db_name: String,
-    username: String,
-    password: String,
-    server_context: String,
+/// Whether permissions are managed through LuckPerms instead of `permissions.toml`
+pub fn uses_luckperms() -> bool {
+    CONFIG.luckperms.is_some()
 }
 
-pub fn init(config: PermissionsConfig) -> Result<()> {
-    let opts = OptsBuilder::new()
-        .ip_or_hostname(Some(config.host))
-        .db_name(Some(config.db_name))
-        .user(Some(config.username))
-        .pass(Some(config.password));
-    let pool = Pool::new(opts)?;
-    POOL.set(pool)
-        .map_err(|_| anyhow!("Tried to init permissions more than once"))?;
-
-    Ok(())
+pub fn init() -> Result<()> {
+    match &CONFIG.luckperms {
+        Some(config) => luckperms::init(config.clone()),
+        None => groups::reload(),
+    }
 }
 
 pub fn load_player_cache(uuid: u128) -> Result<PlayerPermissionsCache> {
-    let uuid = HyphenatedUUID(uuid).to_string();
-    let mut conn = conn()?;
-    let res: Vec<Row> = conn.exec(
-        "
-        WITH RECURSIVE groups_inherited AS (
-            SELECT *
-            FROM luckperms_user_permissions
-            WHERE uuid LIKE ?
-            UNION
-            SELECT luckperms_group_permissions.*
-            FROM groups_inherited, luckperms_group_permissions
-            WHERE luckperms_group_permissions.name = SUBSTR(groups_inherited.permission, 7)
-        )
-        SELECT *
-        FROM groups_inherited;
-    ",
-        (&uuid,),
-    )?;
-
-    let mut nodes = Vec::new();
-    for row in res {
-        let path_str = String::from_value(row[2].clone());
-        let path = path_str
-            .split('.')
-            .map(|s| match s {
-                "*" => PathSegment::WildCard,
-                s => PathSegment::Named(s.to_owned()),
-            })
-            .collect();
-        let node = PermissionNode {
-            path,
-            server_context: FromValue::from_value(row[4].clone()),
-            value: FromValue::from_value(row[3].clone()),
-        };
-        nodes.push(node);
+    if uses_luckperms() {
+        luckperms::load_player_cache(uuid)
+    } else {
+        Ok(groups::load_player_cache(uuid))
     }
-
-    Ok(PlayerPermissionsCache { nodes })
 }
@@ -1,6 +1,9 @@
 use crate::config::CONFIG;
 use crate::permissions::{self, PlayerPermissionsCache};
-use crate::plot::worldedit::{WorldEditClipboard, WorldEditUndo};
+use crate::plot::chunk_sender::ChunkSender;
+use crate::plot::worldedit::{
+    self, SelectionMode, WorldEditBrush, WorldEditClipboard, WorldEditHistory, WorldEditJob,
+};
 use crate::plot::PLOT_SCALE;
 use crate::utils::{self, HyphenatedUUID};
 use byteorder::{BigEndian, ReadBytesExt};
@@ -10,11 +13,13 @@ use mchprs_blocks::{BlockDirection, BlockFacing, BlockPos};
 use mchprs_network::packets::clientbound::*;
 use mchprs_network::packets::{PacketEncoder, PlayerProperty, SlotData};
 use mchprs_network::{PlayerConn, PlayerPacketSender};
-use mchprs_text::{ColorCode, TextComponent, TextComponentBuilder};
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fs::{self, OpenOptions};
 use std::io::{Cursor, Write};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{Instant, SystemTime};
 use tracing::{error, warn};
@@ -22,6 +27,11 @@ use tracing::{error, warn};
 pub type EntityId = u32;
 static ENTITY_ID_COUNTER: AtomicU32 = AtomicU32::new(0);
 
+/// Allocates a new unique entity id
+pub fn next_entity_id() -> EntityId {
+    ENTITY_ID_COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum Gamemode {
     Creative,
@@ -51,6 +61,44 @@ pub struct PlayerData {
     fly_speed: f32,
     walk_speed: f32,
     gamemode: Gamemode,
+    /// The uuids of the players whose chat messages are hidden from this player
+    ignored_players: Vec<u128>,
+}
+
+/// Player data as it was saved before ignore lists were added
+#[derive(Deserialize)]
+struct LegacyPlayerData {
+    on_ground: bool,
+    flying: bool,
+    motion: [f64; 3],
+    position: [f64; 3],
+    rotation: [f32; 2],
+    inventory: Vec<InventoryEntry>,
+    selected_item_slot: i32,
+    fly_speed: f32,
+    walk_speed: f32,
+    gamemode: Gamemode,
+}
+
+impl PlayerData {
+    fn from_bytes(data: &[u8]) -> bincode::Result<PlayerData> {
+        bincode::deserialize(data).or_else(|err| {
+            let legacy: LegacyPlayerData = bincode::deserialize(data).map_err(|_| err)?;
+            Ok(PlayerData {
+                on_ground: legacy.on_ground,
+                flying: legacy.flying,
+                motion: legacy.motion,
+                position: legacy.position,
+                rotation: legacy.rotation,
+                inventory: legacy.inventory,
+                selected_item_slot: legacy.selected_item_slot,
+                fly_speed: legacy.fly_speed,
+                walk_speed: legacy.walk_speed,
+                gamemode: legacy.gamemode,
+                ignored_players: Vec::new(),
+            })
+        })
+    }
 }
 
 impl Default for PlayerData {
@@ -66,10 +114,56 @@ impl Default for PlayerData {
             fly_speed: 1.0,
             walk_speed: 1.0,
             gamemode: Gamemode::Creative,
+            ignored_players: Vec::new(),
+        }
+    }
+}
+
+/// Where the chat messages a player sends go
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ChatChannel {
+    #[default]
+    Global,
+    /// Only the players on the same plot
+    Plot,
+    /// Only the players with the `mchprs.chat.staff` permission
+    Staff,
+}
+
+impl ChatChannel {
+    /// The permission needed to talk in the channel
+    pub fn permission(self) -> Option<&'static str> {
+        match self {
+            ChatChannel::Global => None,
+            ChatChannel::Plot => Some("plots.chat"),
+            ChatChannel::Staff => Some("mchprs.chat.staff"),
         }
     }
 }
 
+impl FromStr for ChatChannel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "global" | "g" => ChatChannel::Global,
+            "plot" | "p" => ChatChannel::Plot,
+            "staff" | "s" => ChatChannel::Staff,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl fmt::Display for ChatChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChatChannel::Global => "global",
+            ChatChannel::Plot => "plot",
+            ChatChannel::Staff => "staff",
+        })
+    }
+}
+
 bitflags! {
     #[derive(Debug)]
     pub struct SkinParts: u32 {
@@ -115,7 +209,7 @@ impl PlayerPos {
 
     pub fn plot_pos(self) -> (i32, i32) {
         let (chunk_x, chunk_z) = self.chunk_pos();
-        (chunk_x >> PLOT_SCALE, chunk_z >> PLOT_SCALE)
+        (chunk_x >> *PLOT_SCALE, chunk_z >> *PLOT_SCALE)
     }
 }
 
@@ -138,6 +232,8 @@ pub struct Player {
     pub last_chunk_x: i32,
     /// The last Z chunk the player was in. This is used for updated view position.
     pub last_chunk_z: i32,
+    /// The chunks in view that have yet to be sent
+    pub chunk_sender: ChunkSender,
     /// The player's head yaw rotation.
     pub yaw: f32,
     /// The player's head pitch rotation.
@@ -159,15 +255,40 @@ pub struct Player {
     pub first_position: Option<BlockPos>,
     /// The worldedit second position.
     pub second_position: Option<BlockPos>,
+    pub worldedit_selection_mode: SelectionMode,
+    /// The vertices of a convex selection, in the order they were selected. The first and second
+    /// positions are kept as the bounding box of these.
+    pub worldedit_points: Vec<BlockPos>,
     /// The worldedit current clipboard.
     pub worldedit_clipboard: Option<WorldEditClipboard>,
+    /// A clipboard shared by another player along with their name, waiting for //clipboard accept
+    pub worldedit_shared_clipboard: Option<(String, WorldEditClipboard)>,
     /// The saved sections used for worldedit //undo
     /// Each entry stores the plot coords and the clipboard
-    pub worldedit_undo: Vec<WorldEditUndo>,
-    pub worldedit_redo: Vec<WorldEditUndo>,
+    pub worldedit_history: WorldEditHistory,
+    /// Brushes bound to items, keyed by item id
+    pub worldedit_brushes: FxHashMap<u32, WorldEditBrush>,
+    /// Whether the selection is outlined with particles
+    pub worldedit_show_selection: bool,
+    /// Whether the region the clipboard would be pasted to is outlined with particles
+    pub worldedit_show_clipboard: bool,
+    pub worldedit_last_visualized: Instant,
+    /// The large edit that is being applied in batches, if there is one
+    pub worldedit_job: Option<WorldEditJob>,
+    /// When the operations in the last second were done, for the operations per second limit
+    pub worldedit_recent_operations: Vec<Instant>,
     /// Commands are stored so they can be handled after packets
     pub command_queue: Vec<String>,
-    permissions_cache: Option<PlayerPermissionsCache>,
+    /// Where chat messages go, changed with `/channel` or `/plot chat`
+    pub chat_channel: ChatChannel,
+    /// The uuids of the players whose chat messages are hidden from this player
+    pub ignored_players: Vec<u128>,
+    /// The name of the player that `/reply` answers, who is the last player a private message
+    /// was sent to or received from
+    pub reply_to: Option<String>,
+    /// Where the player was before each teleport, newest last, for `/back`
+    pub teleport_history: Vec<PlayerPos>,
+    permissions_cache: PlayerPermissionsCache,
 }
 
 impl fmt::Debug for Player {
@@ -208,10 +329,8 @@ impl Player {
                 nbt,
             });
         }
-        let permissions_cache = CONFIG
-            .luckperms
-            .is_some()
-            .then(|| permissions::load_player_cache(uuid).unwrap());
+
+        let permissions_cache = permissions::load_player_cache(uuid).unwrap();
         Player {
             uuid,
             username,
@@ -228,7 +347,8 @@ impl Player {
             yaw: player_data.rotation[1],
             last_chunk_x: 0,
             last_chunk_z: 0,
-            entity_id: ENTITY_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            chunk_sender: ChunkSender::default(),
+            entity_id: next_entity_id(),
             client,
             flying: player_data.flying,
             sprinting: false,
@@ -241,10 +361,22 @@ impl Player {
             last_keep_alive_sent: Instant::now(),
             first_position: None,
             second_position: None,
+            worldedit_selection_mode: SelectionMode::Cuboid,
+            worldedit_points: Vec::new(),
             worldedit_clipboard: None,
-            worldedit_undo: Vec::new(),
-            worldedit_redo: Vec::new(),
+            worldedit_shared_clipboard: None,
+            worldedit_history: WorldEditHistory::load(uuid),
+            worldedit_brushes: FxHashMap::default(),
+            worldedit_show_selection: false,
+            worldedit_show_clipboard: false,
+            worldedit_last_visualized: Instant::now(),
+            worldedit_job: None,
+            worldedit_recent_operations: Vec::new(),
             command_queue: Vec::new(),
+            chat_channel: ChatChannel::Global,
+            ignored_players: player_data.ignored_players,
+            reply_to: None,
+            teleport_history: Vec::new(),
             permissions_cache,
         }
     }
@@ -259,7 +391,7 @@ impl Player {
     ) -> Player {
         let filename = format!("./world/players/{:032x}", uuid);
         if let Ok(data) = fs::read(&filename) {
-            let player_data: PlayerData = match bincode::deserialize(&data) {
+            let player_data = match PlayerData::from_bytes(&data) {
                 Ok(data) => data,
                 Err(_) => {
                     warn!("There was an error loading the player data for {}, player data will be backed up and reset.", username);
@@ -282,7 +414,8 @@ impl Player {
         }
     }
 
-    /// Saves the player to `./world/players/{uuid}`. This will create
+    /// Saves the player to `./world/players/{uuid}` and their worldedit history to
+    /// `./world/worldedit_history/{uuid}`. This will create
     /// the file if it does not already exist.
     pub fn save(&self) {
         let mut file = OpenOptions::new()
@@ -317,9 +450,12 @@ impl Player {
             rotation: [self.pitch, self.yaw],
             selected_item_slot: self.selected_slot as i32,
             walk_speed: self.walk_speed,
+            ignored_players: self.ignored_players.clone(),
         })
         .unwrap();
         file.write_all(&data).unwrap();
+
+        self.worldedit_history.save();
     }
 
     /// Manages keep alives and packet reading. Return true if the view position should be updated.
@@ -383,11 +519,27 @@ impl Player {
         }
     }
 
+    /// Teleports the player and remembers where they were, so that they can return with `/back`
     pub fn teleport(&mut self, pos: PlayerPos) {
+        let from = self.pos;
+        if !self.move_to(pos) {
+            return;
+        }
+        let depth = CONFIG.teleport_history_depth.max(0) as usize;
+        self.teleport_history.push(from);
+        if self.teleport_history.len() > depth {
+            let excess = self.teleport_history.len() - depth;
+            self.teleport_history.drain(..excess);
+        }
+    }
+
+    /// Teleports the player without adding to their `/back` history, for when the server moves
+    /// them. Returns false if the position is invalid.
+    pub fn move_to(&mut self, pos: PlayerPos) -> bool {
         // Prevent from teleporting to Infinity or NaN
         if !pos.x.is_finite() || !pos.y.is_finite() || !pos.z.is_finite() {
             self.send_error_message("We just saved you from a game crash, don't try it again!");
-            return;
+            return false;
         }
 
         let player_position_and_look = CSynchronizePlayerPosition {
@@ -402,6 +554,7 @@ impl Player {
         .encode();
         self.pos = pos;
         self.client.send_packet(&player_position_and_look);
+        true
     }
 
     /// Sends the `ChatMessage` packet containing the raw text component
@@ -436,7 +589,26 @@ impl Player {
         );
     }
 
+    /// Sends the player a worldedit message about an edit, with a button that undoes it
+    pub fn send_worldedit_edit_message(&self, message: &str) {
+        let undo = TextComponentBuilder::new("[Undo]".to_string())
+            .color_code(ColorCode::Gray)
+            .click_event(ClickEvent::run_command("//undo"))
+            .hover_text("Click to undo this edit")
+            .finish();
+        self.send_raw_system_message(
+            TextComponentBuilder::new(format!("{} ", message))
+                .color_code(ColorCode::LightPurple)
+                .append(undo)
+                .finish(),
+        );
+    }
+
     pub fn worldedit_set_first_position(&mut self, pos: BlockPos) {
+        if self.worldedit_selection_mode != SelectionMode::Cuboid {
+            worldedit::select_point(self, pos, true);
+            return;
+        }
         self.send_worldedit_message(&format!(
             "First position set to ({}, {}, {})",
             pos.x, pos.y, pos.z
@@ -446,6 +618,10 @@ impl Player {
     }
 
     pub fn worldedit_set_second_position(&mut self, pos: BlockPos) {
+        if self.worldedit_selection_mode != SelectionMode::Cuboid {
+            worldedit::select_point(self, pos, false);
+            return;
+        }
         self.send_worldedit_message(&format!(
             "Second position set to ({}, {}, {})",
             pos.x, pos.y, pos.z
@@ -463,6 +639,69 @@ impl Player {
         self.client.send_packet(&cui_plugin_message);
     }
 
+    /// Sends the player to another server. Clients older than 1.20.5 can't be transferred, so they
+    /// are kicked with the address to reconnect to instead.
+    pub fn transfer(&self, host: &str, port: u16) {
+        if self.client.protocol_version() < CTransfer::MIN_PROTOCOL_VERSION {
+            self.kick(format!("Please reconnect to {}:{}", host, port).into());
+            return;
+        }
+        let transfer = CTransfer {
+            host: host.to_string(),
+            port,
+        }
+        .encode();
+        self.client.send_packet(&transfer);
+    }
+
+    /// Plays a sound at the player. `name` is the identifier of a sound event, like
+    /// `minecraft:block.note_block.pling`.
+    pub fn play_sound(&self, name: &str, volume: f32, pitch: f32) {
+        let sound_effect = CSoundEffect {
+            sound_id: 0,
+            sound_name: Some(name.to_string()),
+            range: None,
+            // Master
+            sound_category: 0,
+            x: (self.pos.x * 8.0) as i32,
+            y: (self.pos.y * 8.0) as i32,
+            z: (self.pos.z * 8.0) as i32,
+            volume,
+            pitch,
+            seed: 0,
+        }
+        .encode();
+        self.client.send_packet(&sound_effect);
+    }
+
+    pub fn notify(&self, notification: &Notification) {
+        match notification {
+            Notification::Chat(message) => self.send_chat_message(message),
+            Notification::Title(text) => {
+                self.send_packet(&CSetTitleText { text: text.clone() }.encode());
+            }
+            Notification::Subtitle(text) => {
+                self.send_packet(&CSetSubtitleText { text: text.clone() }.encode());
+            }
+            Notification::ActionBar(text) => self.send_action_bar(text.clone()),
+            Notification::TitleTimes(times) => {
+                let times = CSetTitleAnimationTimes {
+                    fade_in: times.fade_in,
+                    stay: times.stay,
+                    fade_out: times.fade_out,
+                }
+                .encode();
+                self.send_packet(&times);
+            }
+            Notification::ClearTitle => self.clear_title(),
+            Notification::Sound {
+                name,
+                volume,
+                pitch,
+            } => self.play_sound(name, *volume, *pitch),
+        }
+    }
+
     /// Sends the player the disconnect packet, it is still up to the player to end the network
     /// stream.
     pub fn kick(&self, reason: TextComponent) {
@@ -491,16 +730,16 @@ impl Player {
     }
 
     pub fn has_permission(&self, node: &str) -> bool {
-        if let Some(cache) = &self.permissions_cache {
-            if let Some(val) = cache.get_node_val(node) {
-                val > 0
-            } else {
-                // Node is not in database
-                false
-            }
-        } else {
-            // Permissions is not enabled
-            true
+        self.permissions_cache
+            .get_node_val(node)
+            .is_some_and(|val| val > 0)
+    }
+
+    /// Loads the permissions of the player again, after they were changed with `/perm`
+    pub fn reload_permissions(&mut self) {
+        match permissions::load_player_cache(self.uuid) {
+            Ok(cache) => self.permissions_cache = cache,
+            Err(err) => error!("Failed to load permissions of {}: {:?}", self.username, err),
         }
     }
 
@@ -609,6 +848,44 @@ impl Player {
     }
 }
 
+/// How long a title fades in, stays and fades out, in ticks
+#[derive(Debug, Clone, Copy)]
+pub struct TitleTimes {
+    pub fade_in: i32,
+    pub stay: i32,
+    pub fade_out: i32,
+}
+
+impl Default for TitleTimes {
+    fn default() -> Self {
+        TitleTimes {
+            fade_in: 10,
+            stay: 70,
+            fade_out: 20,
+        }
+    }
+}
+
+/// Something shown or played to players. These are sent by `/title`, `/playsound` and plugins
+/// through the server thread, since the players can be on any plot.
+#[derive(Debug, Clone)]
+pub enum Notification {
+    /// A message in chat
+    Chat(Vec<TextComponent>),
+    Title(TextComponent),
+    /// Shown below the next title
+    Subtitle(TextComponent),
+    ActionBar(TextComponent),
+    TitleTimes(TitleTimes),
+    ClearTitle,
+    /// A sound event played at the player, which can also be a sound of a resource pack
+    Sound {
+        name: String,
+        volume: f32,
+        pitch: f32,
+    },
+}
+
 pub trait PacketSender {
     fn send_packet(&self, data: &PacketEncoder);
 
@@ -640,6 +917,15 @@ pub trait PacketSender {
                 .finish(),
         )
     }
+
+    /// Shows text above the hotbar for a few seconds
+    fn send_action_bar(&self, text: TextComponent) {
+        self.send_packet(&CSetActionBarText { text }.encode());
+    }
+
+    fn clear_title(&self) {
+        self.send_packet(&CClearTitles { reset: false }.encode());
+    }
 }
 
 impl PacketSender for PlayerPacketSender {
@@ -653,3 +939,23 @@ impl PacketSender for Player {
         self.client.send_packet(data);
     }
 }
+
+#[test]
+fn player_data_from_before_ignore_lists_loads() {
+    let data = PlayerData {
+        ignored_players: vec![42],
+        ..Default::default()
+    };
+    let bytes = bincode::serialize(&data).unwrap();
+    assert_eq!(
+        PlayerData::from_bytes(&bytes).unwrap().ignored_players,
+        vec![42]
+    );
+
+    // Older player data ends where the length and entries of the ignore list would be
+    let legacy = &bytes[..bytes.len() - 8 - 16];
+    let loaded = PlayerData::from_bytes(legacy).unwrap();
+    assert!(loaded.ignored_players.is_empty());
+    assert_eq!(loaded.position, data.position);
+    assert!(PlayerData::from_bytes(&legacy[..10]).is_err());
+}
@@ -0,0 +1,209 @@
+//! Who is allowed to do what on a plot. The owner can do everything, and can allow other players
+//! to build, use worldedit, control redpiler or open containers on their plot with
+//! `/plot add`, `/plot trust`, `/plot deny` and `/plot remove`. Denied players can't enter the
+//! plot, and visitors can be sent away with `/plot kick`.
+
+use super::{database, Plot};
+use crate::player::{PacketSender, Player};
+use crate::utils::HyphenatedUUID;
+use mchprs_save_data::plot_data::{PlotMember, PlotPermission};
+
+/// The permissions members get from `/plot add` if none are given
+const DEFAULT_MEMBER_PERMISSIONS: [PlotPermission; 2] =
+    [PlotPermission::Build, PlotPermission::Containers];
+
+pub struct PlotAccess {
+    pub owner: Option<u128>,
+    pub members: Vec<PlotMember>,
+    pub denied: Vec<u128>,
+}
+
+impl PlotAccess {
+    /// Returns true if the player is allowed to do this on the plot. Players with the bypass
+    /// permission can do everything unless the owner has denied them.
+    pub fn allows(&self, player: &Player, permission: PlotPermission) -> bool {
+        let bypass_node = match (permission, self.owner) {
+            (PlotPermission::WorldEdit, _) => "plots.worldedit.bypass",
+            (_, Some(_)) => "plots.admin.interact.other",
+            (_, None) => "plots.admin.interact.unowned",
+        };
+        if self.owner == Some(player.uuid) {
+            return true;
+        }
+        if self.denied.contains(&player.uuid) {
+            return false;
+        }
+        if player.has_permission(bypass_node) {
+            return true;
+        }
+        self.members
+            .iter()
+            .any(|member| member.uuid == player.uuid && member.permissions.contains(&permission))
+    }
+
+    /// Returns true if the player isn't allowed to enter the plot
+    pub fn is_denied(&self, player: &Player) -> bool {
+        self.denied.contains(&player.uuid) && !player.has_permission("plots.admin.entry.denied")
+    }
+
+    /// Only the owner can change who is allowed on the plot, unless the player is an admin
+    fn can_manage(&self, player: &Player) -> bool {
+        self.owner.is_some()
+            && (self.owner == Some(player.uuid) || player.has_permission("plots.admin.members"))
+    }
+
+    fn remove(&mut self, uuid: u128) -> bool {
+        let len = self.members.len() + self.denied.len();
+        self.members.retain(|member| member.uuid != uuid);
+        self.denied.retain(|denied| *denied != uuid);
+        len != self.members.len() + self.denied.len()
+    }
+}
+
+fn format_permissions(permissions: &[PlotPermission]) -> String {
+    let names: Vec<&str> = permissions.iter().map(|p| p.name()).collect();
+    names.join(", ")
+}
+
+fn username(uuid: u128) -> String {
+    let uuid = format!("{:032x}", uuid);
+    database::get_cached_username(uuid.clone()).unwrap_or(uuid)
+}
+
+impl Plot {
+    /// Returns false and tells the player if they aren't allowed to do this on the plot
+    pub(super) fn check_plot_permission(&self, player: usize, permission: PlotPermission) -> bool {
+        let player = &self.players[player];
+        if self.access.allows(player, permission) {
+            return true;
+        }
+        player.send_no_permission_message();
+        false
+    }
+
+    /// Sends the player to spawn. They are moved to the plot there once the plot notices that they
+    /// are outside of it.
+    fn send_off_plot(&mut self, player: usize, message: &str) {
+        let player = &mut self.players[player];
+        self.locked_players.remove(&player.entity_id);
+        Plot::send_player_away(self.world.x, self.world.z, player);
+        player.send_error_message(message);
+    }
+
+    /// Handles `/plot kick`
+    pub(super) fn handle_kick_command(&mut self, player: usize, args: &[&str]) {
+        if !self.access.can_manage(&self.players[player])
+            && !self.players[player].has_permission("plots.admin.kick")
+        {
+            self.players[player].send_error_message("You can only do this on your own plot.");
+            return;
+        }
+        let [name] = args else {
+            self.players[player].send_error_message("Usage: /plot kick <player>");
+            return;
+        };
+        let target = self
+            .players
+            .iter()
+            .position(|p| p.username.eq_ignore_ascii_case(name));
+        let Some(target) = target else {
+            self.players[player].send_error_message(&format!("{} is not on this plot.", name));
+            return;
+        };
+        if self.access.owner == Some(self.players[target].uuid) {
+            self.players[player].send_error_message("That player owns this plot.");
+            return;
+        }
+        self.send_off_plot(target, "You have been kicked from the plot.");
+        self.players[player].send_system_message(&format!("{} has been kicked.", name));
+    }
+
+    /// Sends the members of the plot and the players denied from it
+    pub(super) fn send_plot_members(&self, player: usize) {
+        let player = &self.players[player];
+        for member in &self.access.members {
+            player.send_system_message(&format!(
+                "Member {}: {}",
+                username(member.uuid),
+                format_permissions(&member.permissions)
+            ));
+        }
+        if !self.access.denied.is_empty() {
+            let denied: Vec<String> = self.access.denied.iter().map(|&u| username(u)).collect();
+            player.send_system_message(&format!("Denied: {}", denied.join(", ")));
+        }
+    }
+
+    /// Handles `/plot add`, `/plot trust`, `/plot deny` and `/plot remove`
+    pub(super) fn handle_member_command(&mut self, player: usize, command: &str, args: &[&str]) {
+        if !self.access.can_manage(&self.players[player]) {
+            self.players[player].send_error_message("You can only do this on your own plot.");
+            return;
+        }
+        let Some((&name, args)) = args.split_first() else {
+            self.players[player].send_error_message(&format!("Usage: /plot {} <player>", command));
+            return;
+        };
+        let uuid = database::get_user_uuid(name)
+            .and_then(|uuid| uuid.parse::<HyphenatedUUID>().ok())
+            .map(|uuid| uuid.0);
+        let Some(uuid) = uuid else {
+            self.players[player]
+                .send_error_message(&format!("{} has never joined the server.", name));
+            return;
+        };
+        if self.access.owner == Some(uuid) {
+            self.players[player].send_error_message("That player owns this plot.");
+            return;
+        }
+
+        let message = match command {
+            "add" | "trust" => {
+                let permissions = if command == "trust" {
+                    PlotPermission::ALL.to_vec()
+                } else if args.is_empty() {
+                    DEFAULT_MEMBER_PERMISSIONS.to_vec()
+                } else {
+                    let permissions: Result<Vec<PlotPermission>, ()> =
+                        args.iter().map(|arg| arg.parse()).collect();
+                    let Ok(permissions) = permissions else {
+                        self.players[player].send_error_message(&format!(
+                            "Permissions must be any of [{}]",
+                            format_permissions(&PlotPermission::ALL)
+                        ));
+                        return;
+                    };
+                    permissions
+                };
+                let message = format!(
+                    "{} can now use the plot with: {}",
+                    name,
+                    format_permissions(&permissions)
+                );
+                self.access.remove(uuid);
+                self.access.members.push(PlotMember { uuid, permissions });
+                message
+            }
+            "deny" => {
+                self.access.remove(uuid);
+                self.access.denied.push(uuid);
+                let target = self.players.iter().position(|p| p.uuid == uuid);
+                if let Some(target) = target {
+                    if self.access.is_denied(&self.players[target]) {
+                        self.send_off_plot(target, "You have been denied from the plot.");
+                    }
+                }
+                format!("{} has been denied from the plot.", name)
+            }
+            _ => {
+                if !self.access.remove(uuid) {
+                    self.players[player]
+                        .send_error_message(&format!("{} isn't added to the plot.", name));
+                    return;
+                }
+                format!("{} has been removed from the plot.", name)
+            }
+        };
+        self.players[player].send_system_message(&message);
+    }
+}
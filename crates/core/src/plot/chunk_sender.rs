@@ -0,0 +1,188 @@
+//! Chunks are sent to clients in batches instead of all at once. After each batch the client says
+//! how many chunks per tick it can take, and the next batches are sized to that, so that clients on
+//! slow connections aren't flooded when they join or teleport. This follows the pacing of the
+//! vanilla server.
+
+use super::Plot;
+use mchprs_network::packets::clientbound::{
+    CChunkBatchFinished, CChunkBatchStart, ClientBoundPacket,
+};
+use mchprs_world::storage::Chunk;
+use std::time::{Duration, Instant};
+
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+/// How many chunks per tick are sent until the client tells us otherwise
+const START_CHUNKS_PER_TICK: f32 = 9.0;
+/// Only one batch is sent until the client acknowledges it, so that the pace is known early
+const START_MAX_UNACKNOWLEDGED_BATCHES: u32 = 1;
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+/// At most one batch is sent per tick
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub struct ChunkSender {
+    /// The chunks that have yet to be sent
+    pending: Vec<(i32, i32)>,
+    desired_chunks_per_tick: f32,
+    /// How many chunks the next batch may have
+    batch_quota: f32,
+    unacknowledged_batches: u32,
+    max_unacknowledged_batches: u32,
+    last_batch: Option<Instant>,
+}
+
+impl Default for ChunkSender {
+    fn default() -> Self {
+        ChunkSender {
+            pending: Vec::new(),
+            desired_chunks_per_tick: START_CHUNKS_PER_TICK,
+            batch_quota: 0.0,
+            unacknowledged_batches: 0,
+            max_unacknowledged_batches: START_MAX_UNACKNOWLEDGED_BATCHES,
+            last_batch: None,
+        }
+    }
+}
+
+impl ChunkSender {
+    pub fn queue(&mut self, chunk_x: i32, chunk_z: i32) {
+        if !self.pending.contains(&(chunk_x, chunk_z)) {
+            self.pending.push((chunk_x, chunk_z));
+        }
+    }
+
+    /// Removes a chunk that went out of view before it was sent
+    pub fn unqueue(&mut self, chunk_x: i32, chunk_z: i32) {
+        self.pending.retain(|&chunk| chunk != (chunk_x, chunk_z));
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Takes every pending chunk, for clients that don't acknowledge batches
+    pub fn take_all(&mut self) -> Vec<(i32, i32)> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn on_batch_received(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+        self.desired_chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+        if self.unacknowledged_batches == 0 {
+            self.batch_quota = 1.0;
+        }
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+
+    /// Takes the chunks of the next batch, nearest to `center` first. This is empty if the client
+    /// is still behind or a batch was already sent this tick.
+    pub fn next_batch(&mut self, center: (i32, i32)) -> Vec<(i32, i32)> {
+        if self.pending.is_empty()
+            || self.unacknowledged_batches >= self.max_unacknowledged_batches
+            || self
+                .last_batch
+                .is_some_and(|last_batch| last_batch.elapsed() < BATCH_INTERVAL)
+        {
+            return Vec::new();
+        }
+        self.last_batch = Some(Instant::now());
+
+        let max_batch_size = self.desired_chunks_per_tick.max(1.0);
+        self.batch_quota = (self.batch_quota + self.desired_chunks_per_tick).min(max_batch_size);
+        if self.batch_quota < 1.0 {
+            return Vec::new();
+        }
+
+        let batch_size = (self.batch_quota as usize).min(self.pending.len());
+        // Farthest first, so that the nearest chunks can be split off the end
+        self.pending.sort_unstable_by_key(|&(x, z)| {
+            let (dx, dz) = (x - center.0, z - center.1);
+            std::cmp::Reverse(dx * dx + dz * dz)
+        });
+        let mut batch = self.pending.split_off(self.pending.len() - batch_size);
+        batch.reverse();
+
+        self.batch_quota -= batch_size as f32;
+        self.unacknowledged_batches += 1;
+        batch
+    }
+}
+
+impl Plot {
+    fn send_chunk(&self, player_idx: usize, chunk_x: i32, chunk_z: i32) {
+        let client = &self.players[player_idx].client;
+        if !Plot::chunk_in_plot_bounds(self.world.x, self.world.z, chunk_x, chunk_z) {
+            client.send_packet(&Chunk::encode_empty_packet(
+                chunk_x,
+                chunk_z,
+                *super::PLOT_SECTIONS,
+            ));
+        } else {
+            let chunk = &self.world.chunks[self.world.get_chunk_index_for_chunk(chunk_x, chunk_z)];
+            client.send_packet(chunk.encode_packet());
+        }
+    }
+
+    /// Sends the next batch of the chunks queued for a player, if it is time for one
+    pub(super) fn send_chunk_batch(&mut self, player_idx: usize) {
+        let player = &mut self.players[player_idx];
+        if player.client.protocol_version() < CChunkBatchStart::MIN_PROTOCOL_VERSION {
+            // Older clients don't acknowledge batches, so they get their chunks right away
+            for (chunk_x, chunk_z) in player.chunk_sender.take_all() {
+                self.send_chunk(player_idx, chunk_x, chunk_z);
+            }
+            return;
+        }
+
+        let batch = player.chunk_sender.next_batch(player.pos.chunk_pos());
+        if batch.is_empty() {
+            return;
+        }
+        let player = &self.players[player_idx];
+        player.client.send_packet(&CChunkBatchStart.encode());
+        for &(chunk_x, chunk_z) in &batch {
+            self.send_chunk(player_idx, chunk_x, chunk_z);
+        }
+        let batch_finished = CChunkBatchFinished {
+            batch_size: batch.len() as i32,
+        }
+        .encode();
+        player.client.send_packet(&batch_finished);
+    }
+}
+
+#[test]
+fn first_batch_is_nearest_and_waits_for_ack() {
+    let mut sender = ChunkSender::default();
+    for x in -4..=4 {
+        for z in -4..=4 {
+            sender.queue(x, z);
+        }
+    }
+
+    let batch = sender.next_batch((0, 0));
+    assert_eq!(batch.len(), START_CHUNKS_PER_TICK as usize);
+    assert_eq!(batch[0], (0, 0));
+    assert!(batch.iter().all(|&(x, z)| x.abs() <= 1 && z.abs() <= 1));
+    // The client hasn't acknowledged the first batch yet
+    assert!(sender.next_batch((0, 0)).is_empty());
+
+    sender.on_batch_received(2.5);
+    sender.last_batch = None;
+    assert_eq!(sender.next_batch((0, 0)).len(), 2);
+}
+
+#[test]
+fn unqueued_chunks_are_not_sent() {
+    let mut sender = ChunkSender::default();
+    sender.queue(0, 0);
+    sender.queue(0, 0);
+    sender.queue(1, 0);
+    sender.unqueue(1, 0);
+    assert_eq!(sender.next_batch((0, 0)), vec![(0, 0)]);
+}
@@ -0,0 +1,444 @@
+//! Unit tests for circuits on a plot. A test sets named inputs, runs the circuit for some ticks and
+//! checks named outputs, in as many steps as it needs. Components are named with signs, see
+//! `PlotHandle::find_named`. The tests of a plot are kept in `./world/plots/p{x},{z}.tests.toml`,
+//! which can be written by hand or with `/test create` and `/test step`:
+//!
+//! ```toml
+//! [[test]]
+//! name = "one_plus_one"
+//!
+//! [[test.step]]
+//! set = { a0 = true, b0 = true }
+//! ticks = 10
+//! expect = { s0 = false, s1 = true }
+//! ```
+//!
+//! Every test runs against its own compile of the plot, so tests don't affect each other or the
+//! plot itself.
+//!
+//! Outputs can also declare what they should read with signs on them, like `expect:1@t+40` for a
+//! lamp that should be on 40 ticks after the compile. `/verify` runs the circuit without any inputs
+//! and reports the outputs that don't match.
+
+use super::Plot;
+use crate::player::PacketSender;
+use anyhow::Result;
+use mchprs_plugin::PlotHandle;
+use mchprs_redpiler::{Compiler, CompilerOptions, TaskMonitor};
+use mchprs_save_data::plot_data::PlotPermission;
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Default)]
+struct TestFile {
+    /// The flags tests are compiled with instead of the defaults of the plot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flags: Option<String>,
+    #[serde(default, rename = "test")]
+    tests: Vec<CircuitTest>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CircuitTest {
+    name: String,
+    #[serde(default, rename = "step")]
+    steps: Vec<TestStep>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+struct TestStep {
+    /// Inputs that are turned on or off before the ticks run
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    set: BTreeMap<String, bool>,
+    /// How many redstone ticks run
+    #[serde(default)]
+    ticks: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    expect: BTreeMap<String, Expected>,
+}
+
+/// What an output should read after a step
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+enum Expected {
+    Powered(bool),
+    Power(u8),
+}
+
+impl Expected {
+    fn matches(self, power: u8) -> bool {
+        match self {
+            Expected::Powered(powered) => (power > 0) == powered,
+            Expected::Power(expected) => power == expected,
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Powered(true) => write!(f, "on"),
+            Expected::Powered(false) => write!(f, "off"),
+            Expected::Power(power) => write!(f, "{}", power),
+        }
+    }
+}
+
+fn parse_powered(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" => Some(true),
+        "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses the arguments of `/test step`, like `set a=on b=off ticks 10 expect sum=15 carry=off`
+fn parse_step(args: &[&str]) -> Result<TestStep, String> {
+    let mut step = TestStep::default();
+    let mut section = None;
+    for &arg in args {
+        if matches!(arg, "set" | "ticks" | "expect") {
+            section = Some(arg);
+            continue;
+        }
+        if section == Some("ticks") {
+            step.ticks = arg
+                .parse()
+                .map_err(|_| format!("{} is not a number of ticks", arg))?;
+            continue;
+        }
+        let Some((name, value)) = arg.split_once('=') else {
+            return Err(format!("Expected name=value, not {}", arg));
+        };
+        match section {
+            Some("set") => {
+                let powered = parse_powered(value)
+                    .ok_or_else(|| format!("{} must be set to on or off", name))?;
+                step.set.insert(name.to_string(), powered);
+            }
+            Some("expect") => {
+                let expected = match parse_powered(value) {
+                    Some(powered) => Expected::Powered(powered),
+                    None => match value.parse() {
+                        Ok(power) if power <= 15 => Expected::Power(power),
+                        _ => return Err(format!("{} must be on, off or 0 to 15", name)),
+                    },
+                };
+                step.expect.insert(name.to_string(), expected);
+            }
+            _ => return Err(format!("{} has to come after set or expect", arg)),
+        }
+    }
+    Ok(step)
+}
+
+fn tests_path(plot_x: i32, plot_z: i32) -> PathBuf {
+    PathBuf::from(format!("./world/plots/p{},{}.tests.toml", plot_x, plot_z))
+}
+
+fn load_tests(plot_x: i32, plot_z: i32) -> Result<TestFile> {
+    match fs::read_to_string(tests_path(plot_x, plot_z)) {
+        Ok(file) => Ok(toml::from_str(&file)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn save_tests(plot_x: i32, plot_z: i32, tests: &TestFile) -> Result<()> {
+    fs::write(tests_path(plot_x, plot_z), toml::to_string(tests)?)?;
+    Ok(())
+}
+
+impl Plot {
+    /// Handles `/test`
+    pub(super) fn handle_test_command(&mut self, player: usize, args: &[&str]) {
+        if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+            return;
+        }
+        let (plot_x, plot_z) = (self.world.x, self.world.z);
+        let mut file = match load_tests(plot_x, plot_z) {
+            Ok(file) => file,
+            Err(err) => {
+                self.players[player]
+                    .send_error_message(&format!("The tests of this plot can't be read: {}", err));
+                return;
+            }
+        };
+        let position = |name: &str| file.tests.iter().position(|test| test.name == name);
+        match args {
+            ["run"] => self.run_circuit_tests(player, &file, None),
+            ["run", name] => self.run_circuit_tests(player, &file, Some(*name)),
+            ["list"] => {
+                if file.tests.is_empty() {
+                    self.players[player].send_system_message("This plot has no tests.");
+                }
+                for test in &file.tests {
+                    self.players[player].send_system_message(&format!(
+                        "{} ({} steps)",
+                        test.name,
+                        test.steps.len()
+                    ));
+                }
+            }
+            ["create", name] => {
+                if position(name).is_some() {
+                    self.players[player]
+                        .send_error_message(&format!("There already is a test named {}.", name));
+                    return;
+                }
+                file.tests.push(CircuitTest {
+                    name: name.to_string(),
+                    steps: Vec::new(),
+                });
+                self.save_circuit_tests(player, &file, &format!("Created test {}.", name));
+            }
+            ["remove", name] => {
+                let Some(idx) = position(name) else {
+                    self.players[player]
+                        .send_error_message(&format!("There is no test named {}.", name));
+                    return;
+                };
+                file.tests.remove(idx);
+                self.save_circuit_tests(player, &file, &format!("Removed test {}.", name));
+            }
+            ["step", name, step @ ..] if !step.is_empty() => {
+                let Some(idx) = position(name) else {
+                    self.players[player]
+                        .send_error_message(&format!("There is no test named {}.", name));
+                    return;
+                };
+                let step = match parse_step(step) {
+                    Ok(step) => step,
+                    Err(err) => {
+                        self.players[player].send_error_message(&err);
+                        return;
+                    }
+                };
+                let test = &mut file.tests[idx];
+                test.steps.push(step);
+                let message = format!("Added step {} to test {}.", test.steps.len(), name);
+                self.save_circuit_tests(player, &file, &message);
+            }
+            _ => self.players[player].send_error_message(
+                "Usage: /test <run [name] | list | create <name> | remove <name> | step <name> [set <input>=<on|off>...] [ticks <n>] [expect <output>=<on|off|power>...]>",
+            ),
+        }
+    }
+
+    fn save_circuit_tests(&mut self, player: usize, file: &TestFile, message: &str) {
+        match save_tests(self.world.x, self.world.z, file) {
+            Ok(()) => self.players[player].send_system_message(message),
+            Err(err) => {
+                error!(
+                    "Failed to save the tests of plot {},{}: {:?}",
+                    self.world.x, self.world.z, err
+                );
+                self.players[player].send_error_message("There was an error saving the tests.");
+            }
+        }
+    }
+
+    fn run_circuit_tests(&mut self, player: usize, file: &TestFile, only: Option<&str>) {
+        let tests: Vec<&CircuitTest> = file
+            .tests
+            .iter()
+            .filter(|test| only.is_none() || only == Some(test.name.as_str()))
+            .collect();
+        if tests.is_empty() {
+            self.players[player].send_error_message("There are no tests to run.");
+            return;
+        }
+        // Tests compile the plot as it is built, not in the middle of a run
+        if self.redpiler.is_active() {
+            self.reset_redpiler();
+            self.players[player].send_system_message("Redpiler was reset to run the tests.");
+        }
+
+        let flags = file
+            .flags
+            .clone()
+            .unwrap_or_else(|| self.settings.compiler_flags.clone());
+        let options = CompilerOptions::parse(&flags);
+        let mut passed = 0;
+        for test in &tests {
+            self.watchdog.beat();
+            let message = match self.run_circuit_test(test, &options) {
+                Ok(()) => {
+                    passed += 1;
+                    format!("&aPASS &f{}", test.name)
+                }
+                Err(problem) => format!("&cFAIL &f{}&7: {}", test.name, problem),
+            };
+            self.players[player].send_chat_message(&TextComponent::from_legacy_text(&message));
+        }
+        let color = if passed == tests.len() { 'a' } else { 'c' };
+        self.players[player].send_chat_message(&TextComponent::from_legacy_text(&format!(
+            "&{}{}/{} tests passed",
+            color,
+            passed,
+            tests.len()
+        )));
+    }
+
+    /// Handles `/verify [flags]`, which checks the `expect:` signs on the outputs of the plot
+    pub(super) fn handle_verify_command(&mut self, player: usize, args: &[&str]) {
+        if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+            return;
+        }
+        if self.redpiler.is_active() {
+            self.reset_redpiler();
+            self.players[player].send_system_message("Redpiler was reset to verify the plot.");
+        }
+
+        let flags = match args {
+            [] => self.settings.compiler_flags.clone(),
+            _ => args.join(" "),
+        };
+        let monitor: Arc<TaskMonitor> = Default::default();
+        let mut compiler = Compiler::default();
+        compiler.compile(
+            &self.world,
+            self.world.get_corners(),
+            CompilerOptions::parse(&flags),
+            self.world.to_be_ticked.clone(),
+            monitor.clone(),
+        );
+        self.send_redpiler_warnings(&monitor.warnings());
+
+        let expectations = compiler.expectations().to_vec();
+        if expectations.is_empty() {
+            self.players[player].send_error_message(
+                "There are no signs like expect:1@t+40 on the outputs of this plot.",
+            );
+            return;
+        }
+        let mut tick = 0;
+        let mut mismatches = Vec::new();
+        for expectation in &expectations {
+            if expectation.tick > tick {
+                self.watchdog.beat();
+                compiler.tickn(expectation.tick - tick);
+                tick = expectation.tick;
+            }
+            let strength = compiler.read_output(expectation.pos).unwrap_or(0);
+            if !expectation.output.matches(strength) {
+                mismatches.push((expectation, strength));
+            }
+        }
+
+        for (expectation, strength) in &mismatches {
+            let pos = expectation.pos;
+            self.players[player].send_raw_system_message(
+                TextComponentBuilder::new(format!(
+                    "{}, {}, {} should be {} at t+{} but is {}",
+                    pos.x, pos.y, pos.z, expectation.output, expectation.tick, strength
+                ))
+                .color_code(ColorCode::Red)
+                .click_event(ClickEvent::run_command(format!(
+                    "/tp {} {} {}",
+                    pos.x, pos.y, pos.z
+                )))
+                .hover_text("Click to teleport")
+                .finish(),
+            );
+        }
+        let met = expectations.len() - mismatches.len();
+        let color = if mismatches.is_empty() { 'a' } else { 'c' };
+        self.players[player].send_chat_message(&TextComponent::from_legacy_text(&format!(
+            "&{}{}/{} expectations met after {} ticks",
+            color,
+            met,
+            expectations.len(),
+            tick
+        )));
+    }
+
+    /// Runs a test on its own compiler, which leaves the world alone. Returns what went wrong in
+    /// the first step that failed.
+    fn run_circuit_test(
+        &self,
+        test: &CircuitTest,
+        options: &CompilerOptions,
+    ) -> Result<(), String> {
+        let mut compiler = Compiler::default();
+        compiler.compile(
+            &self.world,
+            self.world.get_corners(),
+            options.clone(),
+            self.world.to_be_ticked.clone(),
+            Default::default(),
+        );
+        let find = |name: &str| {
+            self.find_named(name)
+                .ok_or_else(|| format!("there is no component named {}", name))
+        };
+        for (i, step) in test.steps.iter().enumerate() {
+            let fail = |problem: String| format!("step {}: {}", i + 1, problem);
+            for (name, &powered) in &step.set {
+                let pos = find(name).map_err(fail)?;
+                if !compiler.set_input(pos, powered) {
+                    return Err(fail(format!("{} is not an input", name)));
+                }
+            }
+            compiler.tickn(step.ticks);
+            let mut mismatches = Vec::new();
+            for (name, &expected) in &step.expect {
+                let pos = find(name).map_err(fail)?;
+                let power = compiler.read_output(pos).unwrap_or(0);
+                if !expected.matches(power) {
+                    mismatches.push(format!("{} is {}, expected {}", name, power, expected));
+                }
+            }
+            if !mismatches.is_empty() {
+                return Err(fail(mismatches.join(", ")));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn circuit_test_parse_test() {
+    let step = parse_step(&[
+        "set", "a=on", "b=false", "ticks", "10", "expect", "s=15", "c=off",
+    ]);
+    let mut expected = TestStep {
+        ticks: 10,
+        ..Default::default()
+    };
+    expected.set.insert("a".to_string(), true);
+    expected.set.insert("b".to_string(), false);
+    expected.expect.insert("s".to_string(), Expected::Power(15));
+    expected
+        .expect
+        .insert("c".to_string(), Expected::Powered(false));
+    assert_eq!(step, Ok(expected));
+    assert!(parse_step(&["a=on"]).is_err());
+    assert!(parse_step(&["expect", "s=16"]).is_err());
+    assert!(parse_step(&["ticks", "soon"]).is_err());
+
+    let file: TestFile = toml::from_str(
+        r#"
+        [[test]]
+        name = "adder"
+
+        [[test.step]]
+        set = { a = true }
+        ticks = 4
+        expect = { s = 1, c = false }
+        "#,
+    )
+    .unwrap();
+    let step = &file.tests[0].steps[0];
+    assert_eq!(step.expect["s"], Expected::Power(1));
+    assert_eq!(step.expect["c"], Expected::Powered(false));
+    let saved: TestFile = toml::from_str(&toml::to_string(&file).unwrap()).unwrap();
+    assert_eq!(saved.tests[0].steps, file.tests[0].steps);
+}
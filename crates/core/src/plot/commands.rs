@@ -1,22 +1,32 @@
-use super::{database, worldedit, Plot, PlotWorld};
-use crate::player::{Gamemode, PacketSender, PlayerPos};
+use super::{database, threads, worldedit, Plot, PlotWorld};
+use crate::config::CONFIG;
+use crate::interaction;
+use crate::permissions::{self, groups};
+use crate::player::{ChatChannel, Gamemode, Notification, PacketSender, PlayerPos, TitleTimes};
 use crate::plot::data::sleep_time_for_tps;
+use crate::plugins;
 use crate::profile::PlayerProfile;
-use crate::server::Message;
+use crate::schematic_http;
+use crate::server::{parse_address, Message};
+use crate::utils::HyphenatedUUID;
+use mchprs_blocks::blocks::Block;
 use mchprs_blocks::items::ItemStack;
+use mchprs_blocks::{BlockFacing, BlockPos};
 use mchprs_network::packets::clientbound::{
     CCommands, CCommandsNode as Node, CDeclareCommandsNodeParser as Parser, ClientBoundPacket,
 };
 use mchprs_network::packets::PacketEncoder;
 use mchprs_network::PlayerPacketSender;
+use mchprs_plugin::{ArgSpec, ArgType};
 use mchprs_redpiler::CompilerOptions;
-use mchprs_save_data::plot_data::{Tps, WorldSendRate};
-use mchprs_text::TextComponent;
+use mchprs_save_data::plot_data::{PlotPermission, Tps, WorldSendRate};
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
+use mchprs_world::{ItemFrame, World};
 use once_cell::sync::Lazy;
 use std::ops::Add;
 use std::str::FromStr;
 use std::time::Instant;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
 // Parses a relative or absolute coordinate relative to a reference coordinate
 fn parse_relative_coord<F: FromStr + Add + Add<Output = F>>(
@@ -32,6 +42,98 @@ fn parse_relative_coord<F: FromStr + Add + Add<Output = F>>(
     }
 }
 
+/// How many plots `/plot top` shows on each page
+const TOP_PLOTS_PER_PAGE: usize = 10;
+
+/// A line of a plot listing that teleports the player to the plot when clicked
+fn plot_list_entry(text: String, plot_x: i32, plot_z: i32) -> TextComponent {
+    TextComponentBuilder::new(text)
+        .color_code(ColorCode::Yellow)
+        .click_event(ClickEvent::run_command(format!(
+            "/plot tp {} {}",
+            plot_x, plot_z
+        )))
+        .hover_text("Click to visit")
+        .finish()
+}
+
+/// The permission needed for each command, except for `/plot` and worldedit commands, which have
+/// permissions for each of their subcommands
+fn command_permission(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "whitelist" => "mchprs.whitelist",
+        "rtps" => "mchprs.rtps",
+        "radv" | "radvance" => "mchprs.radvance",
+        "toggleautorp" => "mchprs.toggleautorp",
+        "itemframe" => "mchprs.itemframe",
+        "jukebox" => "mchprs.jukebox",
+        "toggleqc" => "mchprs.toggleqc",
+        "togglestrict" => "mchprs.togglestrict",
+        "teleport" | "tp" => "mchprs.teleport",
+        "back" => "mchprs.back",
+        "stop" => "mchprs.stop",
+        "transfer" => "mchprs.transfer",
+        "title" => "mchprs.title",
+        "playsound" => "mchprs.playsound",
+        "restart" => "mchprs.restart",
+        "threads" => "mchprs.threads",
+        "pc" => "plots.chat",
+        "msg" | "tell" | "w" | "reply" | "r" => "mchprs.msg",
+        "ignore" => "mchprs.ignore",
+        "channel" | "ch" => "mchprs.channel",
+        "redpiler" | "rp" => "mchprs.redpiler",
+        "speed" => "mchprs.speed",
+        "gamemode" | "gmc" | "gmsp" => "mchprs.gamemode",
+        "container" => "mchprs.container",
+        "worldsendrate" | "wsr" => "mchprs.worldsendrate",
+        "perm" => "mchprs.permissions",
+        "test" => "mchprs.test",
+        "verify" => "mchprs.verify",
+        _ => return None,
+    })
+}
+
+/// Fills the arguments given to an alias into the command it stands for. `$1` to `$9` are replaced
+/// with single arguments and `$*` with all of them, and if the command uses neither, the arguments
+/// are appended to it. Returns `None` if an argument the command uses wasn't given.
+fn expand_alias(template: &str, args: &[&str]) -> Option<String> {
+    // Aliases are written the way they are typed in chat
+    let template = template.strip_prefix('/').unwrap_or(template);
+    let mut expanded = String::new();
+    let mut used_args = false;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('$', Some('*')) => {
+                chars.next();
+                expanded.push_str(&args.join(" "));
+                used_args = true;
+            }
+            ('$', Some(&digit @ '1'..='9')) => {
+                chars.next();
+                let idx = digit as usize - '1' as usize;
+                expanded.push_str(args.get(idx)?);
+                used_args = true;
+            }
+            _ => expanded.push(c),
+        }
+    }
+    if !used_args && !args.is_empty() {
+        expanded.push(' ');
+        expanded.push_str(&args.join(" "));
+    }
+    Some(expanded.trim_end().to_string())
+}
+
+/// Formats a list of groups or nodes for `/perm`
+fn join_or_none(list: &[String]) -> String {
+    if list.is_empty() {
+        "none".to_string()
+    } else {
+        list.join(", ")
+    }
+}
+
 impl Plot {
     /// Handles a command that starts with `/plot` or `/p`
     fn handle_plot_command(&mut self, player: usize, command: &str, args: &[&str]) {
@@ -43,9 +145,23 @@ impl Plot {
             "auto" | "a" => "plots.auto",
             "middle" => "plots.middle",
             "visit" | "v" => "plots.visit",
+            "home" | "h" => "plots.home",
+            "list" => "plots.list",
+            "rate" => "plots.rate",
+            "top" => "plots.top",
             "teleport" | "tp" => "plots.visit",
             "lock" | "unlock" => "plots.lock",
             "sel" | "select" => "plots.select",
+            "add" => "plots.add",
+            "trust" => "plots.trust",
+            "deny" => "plots.deny",
+            "remove" => "plots.remove",
+            "kick" => "plots.kick",
+            "chat" => "plots.chat",
+            "download" => "plots.download",
+            "snapshot" => "plots.snapshot",
+            "time" => "plots.time",
+            "weather" => "plots.weather",
             _ => {
                 self.players[player].send_error_message("Invalid argument for /plot");
                 return;
@@ -63,14 +179,34 @@ impl Plot {
                         "Plot owner is: {}",
                         database::get_cached_username(owner.clone()).unwrap_or(owner)
                     ));
+                    self.send_plot_members(player);
+                    if let Some((rating, count)) = database::get_plot_rating(plot_x, plot_z) {
+                        self.players[player].send_system_message(&format!(
+                            "Rating: {:.1}/5 from {} player(s)",
+                            rating, count
+                        ));
+                    }
                 } else {
                     self.players[player].send_system_message("Plot is not owned by anyone.");
                 }
             }
             "claim" | "c" => {
+                let template = match args {
+                    [] => None,
+                    ["--template", name] => Some(*name),
+                    _ => {
+                        self.players[player]
+                            .send_error_message("Usage: /plot claim [--template <name>]");
+                        return;
+                    }
+                };
                 if database::is_claimed(plot_x, plot_z).unwrap() {
                     self.players[player].send_system_message("Plot is already claimed!");
-                } else {
+                } else if template.is_some()
+                    && !self.players[player].has_permission("plots.claim.template")
+                {
+                    self.players[player].send_no_permission_message();
+                } else if template.is_none_or(|name| self.apply_template(player, name)) {
                     self.claim_plot(plot_x, plot_z, player);
                 }
             }
@@ -121,6 +257,131 @@ impl Plot {
                         .send_system_message(&format!("{} does not own any plots.", args[0]));
                 }
             }
+            "home" | "h" => {
+                let plots = database::get_owned_plots(&self.players[player].username);
+                if plots.is_empty() {
+                    self.players[player].send_system_message("You do not own any plots.");
+                    return;
+                }
+                // Without an index, each use goes to the next plot the player owns
+                let idx = match args {
+                    [] => plots
+                        .iter()
+                        .position(|&plot| plot == (plot_x, plot_z))
+                        .map_or(0, |idx| (idx + 1) % plots.len()),
+                    [idx] => match idx.parse::<usize>() {
+                        Ok(idx) if (1..=plots.len()).contains(&idx) => idx - 1,
+                        _ => {
+                            self.players[player]
+                                .send_error_message(&format!("Plot range (1, {}).", plots.len()));
+                            return;
+                        }
+                    },
+                    _ => {
+                        self.players[player].send_error_message("Usage: /plot home [index]");
+                        return;
+                    }
+                };
+                let (home_x, home_z) = plots[idx];
+                let center = Plot::get_center(home_x, home_z);
+                self.players[player].teleport(PlayerPos::new(center.0, 64.0, center.1));
+                self.players[player].send_system_message(&format!(
+                    "Teleported to your plot {} of {} at {},{}",
+                    idx + 1,
+                    plots.len(),
+                    home_x,
+                    home_z
+                ));
+            }
+            "list" => {
+                let name = match args {
+                    [] => self.players[player].username.clone(),
+                    [name] => name.to_string(),
+                    _ => {
+                        self.players[player].send_error_message("Usage: /plot list [player]");
+                        return;
+                    }
+                };
+                let plots = database::get_owned_plots(&name);
+                if plots.is_empty() {
+                    self.players[player]
+                        .send_system_message(&format!("{} does not own any plots.", name));
+                    return;
+                }
+                self.players[player].send_system_message(&format!("Plots of {}:", name));
+                for (i, &(x, z)) in plots.iter().enumerate() {
+                    let rating = match database::get_plot_rating(x, z) {
+                        Some((rating, count)) => format!(" - {:.1}/5 ({})", rating, count),
+                        None => String::new(),
+                    };
+                    self.players[player].send_raw_system_message(plot_list_entry(
+                        format!("{}. {},{}{}", i + 1, x, z, rating),
+                        x,
+                        z,
+                    ));
+                }
+            }
+            "rate" => {
+                let rating = match args {
+                    [rating] => rating.parse::<u32>().ok().filter(|r| (1..=5).contains(r)),
+                    _ => None,
+                };
+                let Some(rating) = rating else {
+                    self.players[player].send_error_message("Usage: /plot rate <1-5>");
+                    return;
+                };
+                let uuid = self.players[player].uuid;
+                match database::get_plot_owner(plot_x, plot_z) {
+                    None => {
+                        self.players[player].send_error_message("Plot is not owned by anyone.");
+                    }
+                    Some(owner) if owner == format!("{:032x}", uuid) => {
+                        self.players[player].send_error_message("You can't rate your own plot.");
+                    }
+                    Some(_) => {
+                        database::rate_plot(plot_x, plot_z, &format!("{:032x}", uuid), rating);
+                        self.players[player].send_system_message(&format!(
+                            "You rated plot {},{} {}/5.",
+                            plot_x, plot_z, rating
+                        ));
+                    }
+                }
+            }
+            "top" => {
+                let page = match args {
+                    [] => Some(1),
+                    [page] => page.parse::<usize>().ok().filter(|&page| page > 0),
+                    _ => None,
+                };
+                let Some(page) = page else {
+                    self.players[player].send_error_message("Usage: /plot top [page]");
+                    return;
+                };
+                let offset = (page - 1) * TOP_PLOTS_PER_PAGE;
+                let plots = database::get_top_plots(TOP_PLOTS_PER_PAGE, offset);
+                if plots.is_empty() {
+                    self.players[player].send_system_message("There are no rated plots here.");
+                    return;
+                }
+                self.players[player]
+                    .send_system_message(&format!("Top rated plots (page {}):", page));
+                for (i, plot) in plots.iter().enumerate() {
+                    self.players[player].send_raw_system_message(plot_list_entry(
+                        format!(
+                            "{}. {},{} by {} - {:.1}/5 ({})",
+                            offset + i + 1,
+                            plot.plot_x,
+                            plot.plot_z,
+                            plot.owner,
+                            plot.rating,
+                            plot.num_ratings
+                        ),
+                        plot.plot_x,
+                        plot.plot_z,
+                    ));
+                }
+                self.players[player].send_system_message("Click on a plot to visit it.");
+            }
             "teleport" | "tp" => {
                 if args.len() != 2 {
                     self.players[player].send_error_message("Invalid number of arguments!");
@@ -162,21 +423,127 @@ impl Plot {
                     self.players[player].send_system_message("You are not locked to this plot.");
                 }
             }
+            "kick" => self.handle_kick_command(player, args),
+            "chat" => {
+                if args.is_empty() {
+                    let player = &mut self.players[player];
+                    player.chat_channel = if player.chat_channel == ChatChannel::Plot {
+                        ChatChannel::Global
+                    } else {
+                        ChatChannel::Plot
+                    };
+                    if player.chat_channel == ChatChannel::Plot {
+                        player.send_system_message(
+                            "Your chat messages now only go to the players on your plot.",
+                        );
+                    } else {
+                        player.send_system_message("Your chat messages now go to everyone.");
+                    }
+                } else {
+                    self.send_plot_chat(player, &args.join(" "));
+                }
+            }
+            "download" => self.download_plot(player),
+            "snapshot" => self.handle_snapshot_command(player, args),
+            "time" => self.handle_time_command(player, args),
+            "weather" => self.handle_weather_command(player, args),
             "select" | "sel" => {
                 let corners = self.world.get_corners();
+                worldedit::convert_to_cuboid(&mut self.players[player]);
                 self.players[player].worldedit_set_first_position(corners.0);
                 self.players[player].worldedit_set_second_position(corners.1);
             }
+            "add" | "trust" | "deny" | "remove" => {
+                self.handle_member_command(player, command, args);
+            }
             _ => self.players[player].send_error_message("Invalid argument for /plot"),
         }
     }
 
     /// Handles a command that starts with `/redpiler` or `/rp`
+    /// Saves the whole plot as a schematic in the player's schematic folder and gives them a
+    /// download link if schematic downloads are enabled
+    fn download_plot(&mut self, player: usize) {
+        let uuid = self.players[player].uuid;
+        if self.access.owner != Some(uuid)
+            && !self.players[player].has_permission("plots.admin.download")
+        {
+            self.players[player].send_error_message("You can only download your own plot.");
+            return;
+        }
+
+        let start_time = Instant::now();
+        let name = format!("plot_{}_{}.schem", self.world.x, self.world.z);
+        let file_name = if CONFIG.schemati {
+            format!("{}/{}", HyphenatedUUID(uuid), name)
+        } else {
+            name
+        };
+        let clipboard = match worldedit::save_plot_schematic(&file_name, &mut self.world) {
+            Ok(clipboard) => clipboard,
+            Err(err) => {
+                error!("There was an error saving plot {}: {:?}", file_name, err);
+                self.players[player].send_error_message("There was an error saving the plot.");
+                return;
+            }
+        };
+        let player = &self.players[player];
+        worldedit::index_saved_schematic(&file_name, &player.username, &clipboard);
+        player.send_system_message(&format!(
+            "The plot was saved as {} ({:?})",
+            file_name,
+            start_time.elapsed()
+        ));
+        if let Some(url) = schematic_http::create_download_link(&file_name) {
+            player.send_chat_message(&TextComponent::from_legacy_text(&format!(
+                "&dThe plot can be downloaded once from: {}",
+                url
+            )));
+        }
+    }
+
+    /// Sends the core, rtps and utilization of every running plot thread, busiest first
+    fn send_thread_report(&self, player: usize) {
+        let player = &self.players[player];
+        let infos = threads::thread_infos();
+        let (high_plots, max_high_plots) = threads::high_tps_plots();
+        let mut header = format!("&6{} plot thread(s) running", infos.len());
+        if let Some(max) = max_high_plots {
+            header += &format!(", {}/{} at a high rtps", high_plots, max);
+        }
+        player.send_chat_message(&TextComponent::from_legacy_text(&header));
+        for info in infos {
+            let core = match info.core {
+                Some(core) => format!("core {}", core),
+                None => "unpinned".to_string(),
+            };
+            player.send_chat_message(&TextComponent::from_legacy_text(&format!(
+                "&6p{},{}: &a{:.1}% busy&6, {} rtps, {}",
+                info.x,
+                info.z,
+                info.utilization * 100.0,
+                info.tps,
+                core
+            )));
+        }
+    }
+
     fn handle_redpiler_command(&mut self, player: usize, command: &str, args: &[&str]) {
+        let controls_redpiler = matches!(
+            command,
+            "compile" | "c" | "reset" | "r" | "defaults" | "record" | "replay" | "stop"
+        );
+        if controls_redpiler && !self.check_plot_permission(player, PlotPermission::Redpiler) {
+            return;
+        }
         match command {
             "compile" | "c" => {
                 let start_time = Instant::now();
-                let args = args.join(" ");
+                let args = if args.is_empty() {
+                    self.settings.compiler_flags.clone()
+                } else {
+                    args.join(" ")
+                };
                 let options = CompilerOptions::parse(&args);
 
                 if options.optimize {
@@ -208,23 +575,257 @@ impl Plot {
             "reset" | "r" => {
                 self.reset_redpiler();
             }
+            "defaults" => {
+                self.settings.compiler_flags = args.join(" ");
+                let message = if args.is_empty() {
+                    "Redpiler will compile without flags by default.".to_string()
+                } else {
+                    format!(
+                        "Redpiler will compile with {} by default.",
+                        self.settings.compiler_flags
+                    )
+                };
+                self.players[player].send_system_message(&message);
+            }
+            "record" => match args {
+                [name] => self.start_recording(player, name, None),
+                [name, flags @ ..] => self.start_recording(player, name, Some(flags.join(" "))),
+                [] => self.players[player]
+                    .send_error_message("Usage: /redpiler record <name> [flags]"),
+            },
+            "replay" => match args {
+                [name] => self.start_replay(player, name),
+                _ => self.players[player].send_error_message("Usage: /redpiler replay <name>"),
+            },
+            "stop" => self.stop_replay(),
             _ => self.players[player].send_error_message("Invalid argument for /redpiler"),
         }
     }
 
+    /// Returns the position and facing an item frame on the face of the block the player is
+    /// looking at would have
+    fn targeted_item_frame(&self, player: usize) -> Option<(BlockPos, BlockFacing)> {
+        let player = &self.players[player];
+        let attached_pos = worldedit::ray_trace_block(
+            &self.world,
+            player.pos,
+            player.pitch as f64,
+            player.yaw as f64,
+            10.0,
+        )?;
+        let facing = interaction::get_nearest_looking_direction(player).opposite();
+        Some((attached_pos.offset(facing.block_face()), facing))
+    }
+
+    /// Handles a command that starts with `/itemframe`
+    fn handle_item_frame_command(&mut self, player: usize, command: &str, args: &[&str]) {
+        if !self.check_plot_permission(player, PlotPermission::Build) {
+            return;
+        }
+        let Some((pos, facing)) = self.targeted_item_frame(player) else {
+            self.players[player].send_error_message("You are not looking at a block!");
+            return;
+        };
+        self.reset_redpiler();
+
+        match command {
+            "place" => {
+                let slot = 36 + self.players[player].selected_slot as usize;
+                let item = self.players[player].inventory[slot]
+                    .as_ref()
+                    .map(|item| item.item_type.get_id());
+                self.set_item_frame(ItemFrame {
+                    pos,
+                    facing,
+                    item,
+                    rotation: 0,
+                    entity_id: 0,
+                });
+            }
+            "rotate" => {
+                let Some(frame) = self.world.get_item_frame(pos, facing) else {
+                    self.players[player].send_error_message("There is no item frame there!");
+                    return;
+                };
+                let rotation = match args {
+                    [] => (frame.rotation + 1) % 8,
+                    [rotation] => match rotation.parse::<u8>() {
+                        Ok(rotation @ 0..=7) => rotation,
+                        _ => {
+                            self.players[player]
+                                .send_error_message("The rotation must be between 0 and 7!");
+                            return;
+                        }
+                    },
+                    _ => {
+                        self.players[player].send_error_message("Usage: /itemframe rotate [0-7]");
+                        return;
+                    }
+                };
+                let frame = ItemFrame {
+                    rotation,
+                    ..frame.clone()
+                };
+                self.set_item_frame(frame);
+            }
+            "remove" => {
+                if !self.remove_item_frame(pos, facing) {
+                    self.players[player].send_error_message("There is no item frame there!");
+                }
+            }
+            _ => self.players[player].send_error_message("Invalid argument for /itemframe"),
+        }
+    }
+
+    /// Handles `/perm`, which changes the permissions in `permissions.toml`
+    fn handle_perm_command(&mut self, player: usize, args: &[&str]) {
+        if args == ["reload"] {
+            if !permissions::uses_luckperms() {
+                if let Err(err) = groups::reload() {
+                    error!("Failed to reload permissions: {:?}", err);
+                    self.players[player]
+                        .send_error_message(&format!("Failed to reload permissions: {:#}", err));
+                    return;
+                }
+            }
+            let _ = self.message_sender.send(Message::ReloadPermissions);
+            self.players[player].send_system_message("Permissions were reloaded.");
+            return;
+        }
+        if permissions::uses_luckperms() {
+            self.players[player]
+                .send_error_message("Permissions are managed through LuckPerms on this server.");
+            return;
+        }
+
+        let node_value = |action: &str| match action {
+            "allow" => Some(true),
+            "deny" => Some(false),
+            _ => None,
+        };
+        let result = match args {
+            ["group", group] => {
+                let info = groups::view(|permissions| {
+                    permissions
+                        .groups
+                        .get(*group)
+                        .map(|group| (group.inherits.clone(), group.permissions.clone()))
+                });
+                let Some((inherits, nodes)) = info else {
+                    self.players[player].send_error_message("That group doesn't exist.");
+                    return;
+                };
+                self.players[player].send_system_message(&format!(
+                    "Group {} inherits from: {}",
+                    group,
+                    join_or_none(&inherits)
+                ));
+                self.players[player]
+                    .send_system_message(&format!("Permissions: {}", join_or_none(&nodes)));
+                return;
+            }
+            ["group", group, action @ ("allow" | "deny" | "unset"), node] => {
+                groups::edit(|permissions| {
+                    permissions.set_group_node(group, node, node_value(action))
+                })
+            }
+            ["group", group, action @ ("addparent" | "removeparent"), parent] => {
+                groups::edit(|permissions| {
+                    permissions.set_group_parent(group, parent, *action == "addparent")
+                })
+            }
+            ["player", username, rest @ ..] => {
+                let Some(uuid) = database::get_user_uuid(username) else {
+                    self.players[player].send_error_message("That player has never joined.");
+                    return;
+                };
+                let name = database::get_cached_username(uuid.clone())
+                    .unwrap_or_else(|| username.to_string());
+                let uuid = u128::from_str_radix(&uuid, 16).unwrap();
+                match rest {
+                    [] => {
+                        let (groups, nodes) = groups::view(|permissions| {
+                            permissions
+                                .player(uuid)
+                                .map(|entry| (entry.groups.clone(), entry.permissions.clone()))
+                                .unwrap_or_default()
+                        });
+                        self.players[player].send_system_message(&format!(
+                            "{} is in the groups: {} (and default)",
+                            name,
+                            join_or_none(&groups)
+                        ));
+                        self.players[player]
+                            .send_system_message(&format!("Permissions: {}", join_or_none(&nodes)));
+                        return;
+                    }
+                    [action @ ("allow" | "deny" | "unset"), node] => groups::edit(|permissions| {
+                        permissions.set_player_node(uuid, &name, node, node_value(action))
+                    }),
+                    [action @ ("addgroup" | "removegroup"), group] => groups::edit(|permissions| {
+                        permissions.set_player_group(uuid, &name, group, *action == "addgroup")
+                    }),
+                    _ => {
+                        self.players[player].send_error_message(
+                            "Usage: /perm player <player> [<allow|deny|unset> <node>|<addgroup|removegroup> <group>]",
+                        );
+                        return;
+                    }
+                }
+            }
+            _ => {
+                self.players[player].send_error_message(
+                    "Usage: /perm <reload|group <group> ...|player <player> ...>",
+                );
+                return;
+            }
+        };
+
+        match result {
+            Ok(true) => {
+                let _ = self.message_sender.send(Message::ReloadPermissions);
+                self.players[player].send_system_message("Permissions were changed.");
+            }
+            Ok(false) => self.players[player].send_system_message("Nothing was changed."),
+            Err(err) => {
+                error!("Failed to save permissions: {:?}", err);
+                self.players[player].send_error_message("Failed to save permissions.");
+            }
+        }
+    }
+
     // Returns true if packets should stop being handled
-    pub(super) fn handle_command(
-        &mut self,
-        player: usize,
-        command: &str,
-        mut args: Vec<&str>,
-    ) -> bool {
+    pub(super) fn handle_command(&mut self, player: usize, command: &str, args: Vec<&str>) -> bool {
+        let alias = CONFIG
+            .aliases
+            .as_ref()
+            .and_then(|aliases| aliases.get(command));
+        let Some(template) = alias else {
+            return self.run_command(player, command, args);
+        };
+        let Some(expanded) = expand_alias(template, &args) else {
+            self.players[player].send_error_message(&format!(
+                "Not enough arguments for /{}, which stands for /{}",
+                command,
+                template.trim_start_matches('/')
+            ));
+            return false;
+        };
+        // Aliases aren't expanded again, so that they can't refer to each other in a loop
+        let mut args: Vec<&str> = expanded.split(' ').collect();
+        let command = args.remove(0);
+        self.run_command(player, command, args)
+    }
+
+    fn run_command(&mut self, player: usize, command: &str, mut args: Vec<&str>) -> bool {
         info!(
             "{} issued command: {} {}",
             self.players[player].username,
             command,
             args.join(" ")
         );
+        self.watchdog
+            .record_command(&self.players[player].username, command, &args);
 
         // Handle worldedit commands
         if worldedit::execute_command(self, player, command, &mut args) {
@@ -232,6 +833,13 @@ impl Plot {
             return false;
         }
 
+        if let Some(permission_node) = command_permission(command) {
+            if !self.players[player].has_permission(permission_node) {
+                self.players[player].send_no_permission_message();
+                return false;
+            }
+        }
+
         match command {
             "whitelist" => match args.as_slice() {
                 ["add", username] => {
@@ -293,6 +901,9 @@ impl Plot {
                     return false;
                 }
 
+                if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+                    return false;
+                }
                 let tps = if let Ok(tps) = args[0].parse::<u32>() {
                     Tps::Limited(tps)
                 } else if !args[0].is_empty() && "unlimited".starts_with(args[0]) {
@@ -302,6 +913,13 @@ impl Plot {
                     return false;
                 };
 
+                if let Err(max_tps) = threads::set_plot_tps(self.world.x, self.world.z, tps) {
+                    self.players[player].send_error_message(&format!(
+                        "Too many plots are running at a high rtps right now. This plot can run at up to {} rtps.",
+                        max_tps
+                    ));
+                    return false;
+                }
                 self.sleep_time = sleep_time_for_tps(tps);
                 self.timings.set_tps(tps);
                 self.tps = tps;
@@ -309,6 +927,9 @@ impl Plot {
                 self.players[player].send_system_message("The rtps was successfully set.");
             }
             "radv" | "radvance" => {
+                if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+                    return false;
+                }
                 if args.is_empty() {
                     self.players[player]
                         .send_error_message("Please specify a number of ticks to advance.");
@@ -333,7 +954,11 @@ impl Plot {
                 ));
             }
             "toggleautorp" => {
+                if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+                    return false;
+                }
                 self.auto_redpiler = !self.auto_redpiler;
+                self.settings.auto_redpiler = Some(self.auto_redpiler);
                 if self.auto_redpiler {
                     self.players[player]
                         .send_system_message("Automatic redpiler compilation has been enabled.");
@@ -342,6 +967,82 @@ impl Plot {
                         .send_system_message("Automatic redpiler compilation has been disabled.");
                 }
             }
+            "itemframe" => {
+                if args.is_empty() {
+                    self.players[player]
+                        .send_error_message("Usage: /itemframe [place | rotate | remove]");
+                    return false;
+                }
+                let command = args.remove(0);
+                self.handle_item_frame_command(player, command, &args);
+            }
+            "jukebox" => {
+                let record = match args.as_slice() {
+                    [] => None,
+                    [disc] => {
+                        let name = disc.trim_start_matches("music_disc_");
+                        match format!("music_disc_{name}").parse() {
+                            Ok(disc) => Some(disc),
+                            Err(()) => {
+                                self.players[player].send_error_message("Unknown music disc!");
+                                return false;
+                            }
+                        }
+                    }
+                    _ => {
+                        self.players[player].send_error_message("Usage: /jukebox [disc]");
+                        return false;
+                    }
+                };
+                if !self.check_plot_permission(player, PlotPermission::Build) {
+                    return false;
+                }
+                let p = &self.players[player];
+                let pos = worldedit::ray_trace_block(
+                    &self.world,
+                    p.pos,
+                    p.pitch as f64,
+                    p.yaw as f64,
+                    10.0,
+                );
+                let Some(pos) =
+                    pos.filter(|&pos| matches!(self.world.get_block(pos), Block::Jukebox { .. }))
+                else {
+                    self.players[player].send_error_message("You are not looking at a jukebox!");
+                    return false;
+                };
+                self.reset_redpiler();
+                interaction::set_jukebox_record(&mut self.world, pos, record);
+            }
+            "toggleqc" => {
+                if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+                    return false;
+                }
+                self.world.quasi_connectivity = !self.world.quasi_connectivity;
+                self.settings.quasi_connectivity = Some(self.world.quasi_connectivity);
+                if self.world.quasi_connectivity {
+                    self.players[player]
+                        .send_system_message("Quasi-connectivity has been enabled.");
+                } else {
+                    self.players[player]
+                        .send_system_message("Quasi-connectivity has been disabled.");
+                }
+            }
+            "togglestrict" => {
+                if !self.check_plot_permission(player, PlotPermission::Redpiler) {
+                    return false;
+                }
+                self.reset_redpiler();
+                self.world.strict_update_order = !self.world.strict_update_order;
+                self.settings.strict_update_order = Some(self.world.strict_update_order);
+                if self.world.strict_update_order {
+                    self.players[player]
+                        .send_system_message("Strict update order has been enabled.");
+                } else {
+                    self.players[player]
+                        .send_system_message("Strict update order has been disabled.");
+                }
+            }
             "teleport" | "tp" => {
                 if args.len() == 3 {
                     let player_pos = self.players[player].pos;
@@ -383,9 +1084,115 @@ impl Plot {
                         .send_error_message("Invalid number of arguments for teleport command!");
                 }
             }
+            "back" => {
+                let player = &mut self.players[player];
+                let Some(pos) = player.teleport_history.pop() else {
+                    player.send_error_message("There is nowhere to go back to.");
+                    return false;
+                };
+                player.move_to(pos);
+            }
             "stop" => {
                 let _ = self.message_sender.send(Message::Shutdown);
             }
+            "transfer" => {
+                let (address, username) = match args.as_slice() {
+                    [address] => (address, Some(self.players[player].username.clone())),
+                    [address, "*"] => (address, None),
+                    [address, username] => (address, Some(username.to_string())),
+                    _ => {
+                        self.players[player]
+                            .send_error_message("Usage: /transfer <host[:port]> [player | *]");
+                        return false;
+                    }
+                };
+                let Some((host, port)) = parse_address(address) else {
+                    self.players[player].send_error_message("Unable to parse the address!");
+                    return false;
+                };
+                let _ = self
+                    .message_sender
+                    .send(Message::Transfer(username, host, port));
+            }
+            "title" => {
+                let text = |args: &[&str]| TextComponent {
+                    extra: TextComponent::from_legacy_text(&args.join(" ")),
+                    ..Default::default()
+                };
+                let notification = match args.as_slice() {
+                    [_, "title", rest @ ..] if !rest.is_empty() => Notification::Title(text(rest)),
+                    [_, "subtitle", rest @ ..] if !rest.is_empty() => {
+                        Notification::Subtitle(text(rest))
+                    }
+                    [_, "actionbar", rest @ ..] if !rest.is_empty() => {
+                        Notification::ActionBar(text(rest))
+                    }
+                    [_, "clear"] => Notification::ClearTitle,
+                    [_, "times", fade_in, stay, fade_out] => {
+                        let (Ok(fade_in), Ok(stay), Ok(fade_out)) =
+                            (fade_in.parse(), stay.parse(), fade_out.parse())
+                        else {
+                            self.players[player].send_error_message("Unable to parse the times!");
+                            return false;
+                        };
+                        Notification::TitleTimes(TitleTimes {
+                            fade_in,
+                            stay,
+                            fade_out,
+                        })
+                    }
+                    _ => {
+                        self.players[player].send_error_message(
+                            "Usage: /title <player | *> <title | subtitle | actionbar | clear | times> ...",
+                        );
+                        return false;
+                    }
+                };
+                let username = match args[0] {
+                    "*" => None,
+                    username => Some(username.to_string()),
+                };
+                let _ = self
+                    .message_sender
+                    .send(Message::Notify(username, notification));
+            }
+            "playsound" => {
+                let (Some(name), Some(volume), Some(pitch)) = (
+                    args.first(),
+                    args.get(2)
+                        .map_or(Ok(1.0), |volume| volume.parse::<f32>())
+                        .ok(),
+                    args.get(3)
+                        .map_or(Ok(1.0), |pitch| pitch.parse::<f32>())
+                        .ok(),
+                ) else {
+                    self.players[player].send_error_message(
+                        "Usage: /playsound <sound> [player | *] [volume] [pitch]",
+                    );
+                    return false;
+                };
+                let username = match args.get(1) {
+                    Some(&"*") => None,
+                    Some(username) => Some(username.to_string()),
+                    None => Some(self.players[player].username.clone()),
+                };
+                let notification = Notification::Sound {
+                    name: name.to_string(),
+                    volume,
+                    pitch: pitch.clamp(0.0, 2.0),
+                };
+                let _ = self
+                    .message_sender
+                    .send(Message::Notify(username, notification));
+            }
+            "restart" => {
+                if parse_address(&CONFIG.restart_fallback_address).is_none() {
+                    self.players[player]
+                        .send_error_message("No valid `restart_fallback_address` is configured.");
+                    return false;
+                }
+                let _ = self.message_sender.send(Message::Restart);
+            }
             "plot" | "p" => {
                 if args.is_empty() {
                     self.players[player].send_error_message("Invalid number of arguments!");
@@ -394,6 +1201,110 @@ impl Plot {
                 let command = args.remove(0);
                 self.handle_plot_command(player, command, &args);
             }
+            "threads" => {
+                self.send_thread_report(player);
+            }
+            "pc" => {
+                if args.is_empty() {
+                    self.players[player].send_error_message("Usage: /pc <message>");
+                    return false;
+                }
+                self.send_plot_chat(player, &args.join(" "));
+            }
+            "msg" | "tell" | "w" => {
+                let [username, message @ ..] = args.as_slice() else {
+                    self.players[player].send_error_message("Usage: /msg <player> <message>");
+                    return false;
+                };
+                if message.is_empty() {
+                    self.players[player].send_error_message("Usage: /msg <player> <message>");
+                    return false;
+                }
+                self.send_private_chat(player, username, message.join(" "));
+            }
+            "reply" | "r" => {
+                if args.is_empty() {
+                    self.players[player].send_error_message("Usage: /reply <message>");
+                    return false;
+                }
+                let Some(username) = self.players[player].reply_to.clone() else {
+                    self.players[player]
+                        .send_error_message("You have not sent or received any private messages.");
+                    return false;
+                };
+                self.send_private_chat(player, &username, args.join(" "));
+            }
+            "ignore" => match args.as_slice() {
+                [] => {
+                    let ignored = &self.players[player].ignored_players;
+                    if ignored.is_empty() {
+                        self.players[player].send_system_message("You are not ignoring anyone.");
+                        return false;
+                    }
+                    let names: Vec<String> = ignored
+                        .iter()
+                        .map(|uuid| {
+                            let uuid = format!("{:032x}", uuid);
+                            database::get_cached_username(uuid.clone()).unwrap_or(uuid)
+                        })
+                        .collect();
+                    self.players[player]
+                        .send_system_message(&format!("You are ignoring: {}", names.join(", ")));
+                }
+                [username] => {
+                    let Some(uuid) = database::get_user_uuid(username)
+                        .and_then(|uuid| u128::from_str_radix(&uuid, 16).ok())
+                    else {
+                        self.players[player].send_error_message("Player not found!");
+                        return false;
+                    };
+                    let player = &mut self.players[player];
+                    if uuid == player.uuid {
+                        player.send_error_message("You can't ignore yourself.");
+                    } else if let Some(idx) = player.ignored_players.iter().position(|&u| u == uuid)
+                    {
+                        player.ignored_players.remove(idx);
+                        player.send_system_message(&format!(
+                            "You are no longer ignoring {}.",
+                            username
+                        ));
+                    } else {
+                        player.ignored_players.push(uuid);
+                        player.send_system_message(&format!(
+                            "You are now ignoring {}. Their chat and private messages are hidden from you.",
+                            username
+                        ));
+                    }
+                }
+                _ => {
+                    self.players[player].send_error_message("Usage: /ignore [player]");
+                    return false;
+                }
+            },
+            "channel" | "ch" => {
+                let Some(channel) = args.first().and_then(|channel| channel.parse().ok()) else {
+                    self.players[player]
+                        .send_error_message("Usage: /channel <global | plot | staff> [message]");
+                    return false;
+                };
+                if args.len() > 1 {
+                    self.send_chat(player, channel, args[1..].join(" "));
+                    return false;
+                }
+                let player = &mut self.players[player];
+                if channel
+                    .permission()
+                    .is_some_and(|permission| !player.has_permission(permission))
+                {
+                    player.send_no_permission_message();
+                    return false;
+                }
+                player.chat_channel = channel;
+                player.send_system_message(&format!(
+                    "Your chat messages now go to the {} channel.",
+                    channel
+                ));
+            }
             "redpiler" | "rp" => {
                 if args.is_empty() {
                     self.players[player].send_error_message("Invalid number of arguments!");
@@ -520,7 +1431,13 @@ impl Plot {
                 self.players[player]
                     .send_system_message("The world send rate was successfully set.");
             }
-            _ => self.players[player].send_error_message("Command not found!"),
+            "perm" => self.handle_perm_command(player, &args),
+            "test" => self.handle_test_command(player, &args),
+            "verify" => self.handle_verify_command(player, &args),
+            _ => match plugins::command(command) {
+                Some((plugin, spec)) => self.run_plugin_command(plugin, &spec, player, &args),
+                None => self.players[player].send_error_message("Command not found!"),
+            },
         }
         false
     }
@@ -539,487 +1456,1815 @@ bitflags! {
 
 // In the future a DSL or some type of generation would be much better.
 // For more information, see https://wiki.vg/Command_Data
+/// The command tree that is sent to clients for command autocomplete. It is also used to find
+/// which argument a suggestion request is for, see `plot::suggestions`.
+pub static COMMAND_NODES: Lazy<Vec<Node>> = Lazy::new(|| {
+    let mut nodes = vec![
+        // 0: Root Node
+        Node {
+            flags: CommandFlags::ROOT.bits() as i8,
+            children: vec![
+                1, 4, 5, 6, 8, 10, 11, 13, 18, 30, 34, 41, 43, 44, 45, 49, 51, 52, 53, 58, 60, 86,
+                88, 102, 103, 106, 107, 118, 123, 126, 127, 128, 130, 131, 133, 138, 139, 142, 143,
+                144, 165, 174,
+            ],
+            redirect_node: None,
+            name: None,
+            parser: None,
+            suggestions_type: None,
+        },
+        // 1: /teleport
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![2, 3],
+            redirect_node: None,
+            name: Some("teleport"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 2: /teleport [x, y, z]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("x, y, z"),
+            parser: Some(Parser::Vec3),
+            suggestions_type: None,
+        },
+        // 3: /teleport [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)), // Only allow one player
+            suggestions_type: None,
+        },
+        // 4: /tp
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(1),
+            name: Some("tp"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 5: /stop
+        Node {
+            flags: (CommandFlags::EXECUTABLE | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("stop"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 6: /rtps
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![7],
+            redirect_node: None,
+            name: Some("rtps"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 7: /rtps [rtps]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("rtps"),
+            parser: Some(Parser::Integer(0, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 8: /radvance
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![9],
+            redirect_node: None,
+            name: Some("radvance"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 9: /radvance [rticks]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("rticks"),
+            parser: Some(Parser::Integer(0, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 10: /radv
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(9),
+            name: Some("radv"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 11: /speed
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![12],
+            redirect_node: None,
+            name: Some("speed"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 12: /speed [speed]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("speed"),
+            parser: Some(Parser::Float(0.0, 10.0)),
+            suggestions_type: None,
+        },
+        // 13: /plot
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![
+                14, 15, 16, 17, 19, 20, 21, 22, 24, 25, 27, 28, 29, 61, 64, 66, 67, 72, 73, 75, 76,
+                78, 80, 82, 84, 89, 95, 97,
+            ],
+            redirect_node: None,
+            name: Some("plot"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 14: /plot info
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("info"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 15: /plot i
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(14),
+            name: Some("i"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 16: /plot claim
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![70],
+            redirect_node: None,
+            name: Some("claim"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 17: /plot c
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(9),
+            name: Some("c"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 18: /p
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(13),
+            name: Some("p"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 19: /p auto
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("auto"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 20: /p a
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(19),
+            name: Some("a"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 21: /p middle
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("middle"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 22: /p visit
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![23],
+            redirect_node: None,
+            name: Some("visit"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 23: /p visit [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 24: /p v
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(22),
+            name: Some("v"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 25: /p teleport
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![26],
+            redirect_node: None,
+            name: Some("teleport"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 26: /p teleport [x, z]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("x, z"),
+            parser: Some(Parser::Vec2),
+            suggestions_type: None,
+        },
+        // 27: /p tp
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(25),
+            name: Some("tp"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 28: /p select
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("select"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 29: /p sel
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(28),
+            name: Some("sel"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 30: /whitelist
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![31, 32],
+            redirect_node: None,
+            name: Some("whitelist"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 31: /whitelist add
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![33],
+            redirect_node: None,
+            name: Some("add"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 32: /whitelist remove
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![33],
+            redirect_node: None,
+            name: Some("remove"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 33: /whitelist add|remove [username]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("username"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 34: /container
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![35, 36, 37],
+            redirect_node: None,
+            name: Some("container"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 35: /container barrel
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![38],
+            redirect_node: None,
+            name: Some("barrel"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 36: /container hopper
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![38],
+            redirect_node: None,
+            name: Some("hopper"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 37: /container furnace
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![38],
+            redirect_node: None,
+            name: Some("furnace"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 38: /container [type] [power]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("power"),
+            parser: Some(Parser::Integer(0, 15)),
+            suggestions_type: None,
+        },
+        // 39: /plot lock
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("lock"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 40: /plot unlock
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("unlock"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 41: //load
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![42],
+            redirect_node: None,
+            name: Some("/load"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 42: //load [filename]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("filename"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 43: /toggleautorp
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("toggleautorp"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 44: /redpiler
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            // Children are compile, inspect, reset, defaults, record, replay, stop
+            children: vec![46, 47, 48, 68, 160, 161, 164],
+            redirect_node: None,
+            name: Some("redpiler"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 45: /rp
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(44), // Redirect to /redpiler
+            name: Some("rp"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 46: /redpiler compile
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("compile"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 47: /redpiler inspect
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("inspect"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 48: /redpiler reset
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("reset"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 49: /worldsendrate
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![50],
+            redirect_node: None,
+            name: Some("worldsendrate"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 50: /worldsendrate [rticks]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("hertz"),
+            parser: Some(Parser::Integer(0, 1000)),
+            suggestions_type: None,
+        },
+        // 51: /wsr
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(49),
+            name: Some("wsr"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 52: /toggleqc
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("toggleqc"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 53: /itemframe
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![54, 55, 57],
+            redirect_node: None,
+            name: Some("itemframe"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 54: /itemframe place
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("place"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 55: /itemframe rotate
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![56],
+            redirect_node: None,
+            name: Some("rotate"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 56: /itemframe rotate [rotation]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("rotation"),
+            parser: Some(Parser::Integer(0, 7)),
+            suggestions_type: None,
+        },
+        // 57: /itemframe remove
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("remove"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 58: /jukebox
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![59],
+            redirect_node: None,
+            name: Some("jukebox"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 59: /jukebox [disc]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("disc"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 60: /togglestrict
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("togglestrict"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 61: /plot add
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![62],
+            redirect_node: None,
+            name: Some("add"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 62: /plot add [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![63],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 63: /plot add [player] [permissions]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("permissions"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 64: /plot trust
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![65],
+            redirect_node: None,
+            name: Some("trust"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 65: /plot trust|deny|remove [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 66: /plot deny
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![65],
+            redirect_node: None,
+            name: Some("deny"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 67: /plot remove
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![65],
+            redirect_node: None,
+            name: Some("remove"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 68: /redpiler defaults
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![69],
+            redirect_node: None,
+            name: Some("defaults"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 69: /redpiler defaults [flags]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("flags"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 70: /plot claim --template
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![71],
+            redirect_node: None,
+            name: Some("--template"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 71: /plot claim --template <name>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 72: /plot download
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("download"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 73: /plot home
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![74],
+            redirect_node: None,
+            name: Some("home"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 74: /plot home [index]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("index"),
+            parser: Some(Parser::Integer(1, i32::MAX)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 75: /plot h
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
+            children: vec![],
+            redirect_node: Some(73),
+            name: Some("h"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 76: /plot list
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![77],
+            redirect_node: None,
+            name: Some("list"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 77: /plot list [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 78: /plot rate
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![79],
+            redirect_node: None,
+            name: Some("rate"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 79: /plot rate <rating>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("rating"),
+            parser: Some(Parser::Integer(1, 5)),
+            suggestions_type: None,
+        },
+        // 80: /plot top
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![81],
+            redirect_node: None,
+            name: Some("top"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 81: /plot top [page]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("page"),
+            parser: Some(Parser::Integer(1, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 82: /plot kick
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![83],
+            redirect_node: None,
+            name: Some("kick"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 83: /plot kick <player>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 84: /plot chat
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![85],
+            redirect_node: None,
+            name: Some("chat"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 85: /plot chat [message]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("message"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 86: /pc
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![87],
+            redirect_node: None,
+            name: Some("pc"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 87: /pc <message>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("message"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 88: /threads
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("threads"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 89: /plot snapshot
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![90, 92, 93],
+            redirect_node: None,
+            name: Some("snapshot"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 90: /plot snapshot create
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![91],
+            redirect_node: None,
+            name: Some("create"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 91: /plot snapshot create [name]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 92: /plot snapshot list
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("list"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 93: /plot snapshot restore
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![94],
+            redirect_node: None,
+            name: Some("restore"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 94: /plot snapshot restore <id>
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("id"),
+            parser: Some(Parser::Integer(1, i32::MAX)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 95: /plot time
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![96],
+            redirect_node: None,
+            name: Some("time"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 96: /plot time [time]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("time"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 97: /plot weather
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![98, 99, 100, 101],
+            redirect_node: None,
+            name: Some("weather"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 98: /plot weather clear
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("clear"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 99: /plot weather rain
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("rain"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 100: /plot weather thunder
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("thunder"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 101: /plot weather reset
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("reset"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 102: /back
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("back"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 103: /transfer
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![104],
+            redirect_node: None,
+            name: Some("transfer"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 104: /transfer [address]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![105],
+            redirect_node: None,
+            name: Some("address"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 105: /transfer [address] [targets]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("targets"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 106: /restart
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("restart"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 107: /title
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![108],
+            redirect_node: None,
+            name: Some("title"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 108: /title [targets]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::HAS_SUGGESTIONS_TYPE).bits() as i8,
+            children: vec![109, 111, 112, 113, 114],
+            redirect_node: None,
+            name: Some("targets"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 109: /title [targets] title
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![110],
+            redirect_node: None,
+            name: Some("title"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 110: /title [targets] title [text]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("text"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 111: /title [targets] subtitle
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![110],
+            redirect_node: None,
+            name: Some("subtitle"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 112: /title [targets] actionbar
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![110],
+            redirect_node: None,
+            name: Some("actionbar"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 113: /title [targets] clear
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("clear"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 114: /title [targets] times
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![115],
+            redirect_node: None,
+            name: Some("times"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 115: /title [targets] times [fadeIn]
+        Node {
+            flags: CommandFlags::ARGUMENT.bits() as i8,
+            children: vec![116],
+            redirect_node: None,
+            name: Some("fadeIn"),
+            parser: Some(Parser::Integer(0, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 116: /title [targets] times [fadeIn] [stay]
+        Node {
+            flags: CommandFlags::ARGUMENT.bits() as i8,
+            children: vec![117],
+            redirect_node: None,
+            name: Some("stay"),
+            parser: Some(Parser::Integer(0, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 117: /title [targets] times [fadeIn] [stay] [fadeOut]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("fadeOut"),
+            parser: Some(Parser::Integer(0, i32::MAX)),
+            suggestions_type: None,
+        },
+        // 118: /playsound
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![119],
+            redirect_node: None,
+            name: Some("playsound"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 119: /playsound [sound]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![120],
+            redirect_node: None,
+            name: Some("sound"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 120: /playsound [sound] [targets]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![121],
+            redirect_node: None,
+            name: Some("targets"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 121: /playsound [sound] [targets] [volume]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![122],
+            redirect_node: None,
+            name: Some("volume"),
+            parser: Some(Parser::Float(0.0, f32::MAX)),
+            suggestions_type: None,
+        },
+        // 122: /playsound [sound] [targets] [volume] [pitch]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("pitch"),
+            parser: Some(Parser::Float(0.0, 2.0)),
+            suggestions_type: None,
+        },
+        // 123: /msg
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![124],
+            redirect_node: None,
+            name: Some("msg"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 124: /msg [player]
+        Node {
+            flags: CommandFlags::ARGUMENT.bits() as i8,
+            children: vec![125],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 125: /msg [player] [message]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("message"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 126: /tell
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(123),
+            name: Some("tell"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 127: /w
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(123),
+            name: Some("w"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 128: /reply
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![129],
+            redirect_node: None,
+            name: Some("reply"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 129: /reply [message]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("message"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 130: /r
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(128),
+            name: Some("r"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 131: /ignore
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![132],
+            redirect_node: None,
+            name: Some("ignore"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 132: /ignore [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 133: /channel
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![134, 135, 136],
+            redirect_node: None,
+            name: Some("channel"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 134: /channel global
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![137],
+            redirect_node: None,
+            name: Some("global"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 135: /channel plot
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![137],
+            redirect_node: None,
+            name: Some("plot"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 136: /channel staff
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![137],
+            redirect_node: None,
+            name: Some("staff"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 137: /channel [channel] [message]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("message"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 138: /ch
+        Node {
+            flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
+            children: vec![],
+            redirect_node: Some(133),
+            name: Some("ch"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 139: /gamemode
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![140, 141],
+            redirect_node: None,
+            name: Some("gamemode"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 140: /gamemode creative
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("creative"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 141: /gamemode spectator
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("spectator"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 142: /gmc
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("gmc"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 143: /gmsp
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("gmsp"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 144: /perm
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![145, 146, 155],
+            redirect_node: None,
+            name: Some("perm"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 145: /perm reload
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("reload"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 146: /perm group
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![147],
+            redirect_node: None,
+            name: Some("group"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 147: /perm group [group]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![148, 149, 150, 151, 152],
+            redirect_node: None,
+            name: Some("group"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 148: /perm <group|player> [name] allow
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![153],
+            redirect_node: None,
+            name: Some("allow"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 149: /perm <group|player> [name] deny
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![153],
+            redirect_node: None,
+            name: Some("deny"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 150: /perm <group|player> [name] unset
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![153],
+            redirect_node: None,
+            name: Some("unset"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 151: /perm group [group] addparent
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![154],
+            redirect_node: None,
+            name: Some("addparent"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 152: /perm group [group] removeparent
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![154],
+            redirect_node: None,
+            name: Some("removeparent"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 153: /perm <group|player> [name] <allow|deny|unset> [node]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("node"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 154: /perm group [group] <addparent|removeparent> [parent]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("parent"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 155: /perm player
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![156],
+            redirect_node: None,
+            name: Some("player"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 156: /perm player [player]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![148, 149, 150, 157, 158],
+            redirect_node: None,
+            name: Some("player"),
+            parser: Some(Parser::Entity(3)),
+            suggestions_type: None,
+        },
+        // 157: /perm player [player] addgroup
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![159],
+            redirect_node: None,
+            name: Some("addgroup"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 158: /perm player [player] removegroup
+        Node {
+            flags: CommandFlags::LITERAL.bits() as i8,
+            children: vec![159],
+            redirect_node: None,
+            name: Some("removegroup"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 159: /perm player [player] <addgroup|removegroup> [group]
+        Node {
+            flags: (CommandFlags::ARGUMENT
+                | CommandFlags::EXECUTABLE
+                | CommandFlags::HAS_SUGGESTIONS_TYPE)
+                .bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("group"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: Some("minecraft:ask_server"),
+        },
+        // 160: /redpiler record
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![162],
+            redirect_node: None,
+            name: Some("record"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 161: /redpiler replay
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![163],
+            redirect_node: None,
+            name: Some("replay"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 162: /redpiler record <name> [flags]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![69],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 163: /redpiler replay <name>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 164: /redpiler stop
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("stop"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 165: /test
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![166, 167, 168, 169, 170],
+            redirect_node: None,
+            name: Some("test"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 166: /test run
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![171],
+            redirect_node: None,
+            name: Some("run"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 167: /test list
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("list"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 168: /test create
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![171],
+            redirect_node: None,
+            name: Some("create"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 169: /test remove
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![171],
+            redirect_node: None,
+            name: Some("remove"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 170: /test step
+        Node {
+            flags: (CommandFlags::LITERAL).bits() as i8,
+            children: vec![172],
+            redirect_node: None,
+            name: Some("step"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 171: /test <run|create|remove> <name>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 172: /test step <name>
+        Node {
+            flags: (CommandFlags::ARGUMENT).bits() as i8,
+            children: vec![173],
+            redirect_node: None,
+            name: Some("name"),
+            parser: Some(Parser::String(0)),
+            suggestions_type: None,
+        },
+        // 173: /test step <name> <step>
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("step"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+        // 174: /verify
+        Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![175],
+            redirect_node: None,
+            name: Some("verify"),
+            parser: None,
+            suggestions_type: None,
+        },
+        // 175: /verify [flags]
+        Node {
+            flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![],
+            redirect_node: None,
+            name: Some("flags"),
+            parser: Some(Parser::String(2)),
+            suggestions_type: None,
+        },
+    ];
+    super::worldedit::add_command_completions(&mut nodes);
+    nodes
+});
+
 /// The `DeclareCommands` packet that is sent when the player joins.
-/// This is used for command autocomplete.
 pub static DECLARE_COMMANDS: Lazy<PacketEncoder> = Lazy::new(|| {
-    let mut packet = CCommands {
-        nodes: vec![
-            // 0: Root Node
-            Node {
-                flags: CommandFlags::ROOT.bits() as i8,
-                children: vec![
-                    1, 4, 5, 6, 8, 10, 11, 13, 18, 30, 34, 41, 43, 44, 45, 49, 51,
-                ],
-                redirect_node: None,
-                name: None,
-                parser: None,
-                suggestions_type: None,
-            },
-            // 1: /teleport
-            Node {
-                flags: CommandFlags::LITERAL.bits() as i8,
-                children: vec![2, 3],
-                redirect_node: None,
-                name: Some("teleport"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 2: /teleport [x, y, z]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("x, y, z"),
-                parser: Some(Parser::Vec3),
-                suggestions_type: None,
-            },
-            // 3: /teleport [player]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("player"),
-                parser: Some(Parser::Entity(3)), // Only allow one player
-                suggestions_type: None,
-            },
-            // 4: /tp
-            Node {
-                flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
-                children: vec![],
-                redirect_node: Some(1),
-                name: Some("tp"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 5: /stop
-            Node {
-                flags: (CommandFlags::EXECUTABLE | CommandFlags::LITERAL).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("stop"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 6: /rtps
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![7],
-                redirect_node: None,
-                name: Some("rtps"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 7: /rtps [rtps]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("rtps"),
-                parser: Some(Parser::Integer(0, i32::MAX)),
-                suggestions_type: None,
-            },
-            // 8: /radvance
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![9],
-                redirect_node: None,
-                name: Some("radvance"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 9: /radvance [rticks]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("rticks"),
-                parser: Some(Parser::Integer(0, i32::MAX)),
-                suggestions_type: None,
-            },
-            // 10: /radv
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(9),
-                name: Some("radv"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 11: /speed
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![12],
-                redirect_node: None,
-                name: Some("speed"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 12: /speed [speed]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("speed"),
-                parser: Some(Parser::Float(0.0, 10.0)),
-                suggestions_type: None,
-            },
-            // 13: /plot
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![14, 15, 16, 17, 19, 20, 21, 22, 24, 25, 27, 28, 29],
-                redirect_node: None,
-                name: Some("plot"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 14: /plot info
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("info"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 15: /plot i
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(14),
-                name: Some("i"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 16: /plot claim
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("claim"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 17: /plot c
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(9),
-                name: Some("c"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 18: /p
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(13),
-                name: Some("p"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 19: /p auto
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("auto"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 20: /p a
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(19),
-                name: Some("a"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 21: /p middle
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("middle"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 22: /p visit
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![23],
-                redirect_node: None,
-                name: Some("visit"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 23: /p visit [player]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("player"),
-                parser: Some(Parser::Entity(3)),
-                suggestions_type: None,
-            },
-            // 24: /p v
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(22),
-                name: Some("v"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 25: /p teleport
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![26],
-                redirect_node: None,
-                name: Some("teleport"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 26: /p teleport [x, z]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("x, z"),
-                parser: Some(Parser::Vec2),
-                suggestions_type: None,
-            },
-            // 27: /p tp
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(25),
-                name: Some("tp"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 28: /p select
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("select"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 29: /p sel
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(28),
-                name: Some("sel"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 30: /whitelist
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![31, 32],
-                redirect_node: None,
-                name: Some("whitelist"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 31: /whitelist add
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![33],
-                redirect_node: None,
-                name: Some("add"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 32: /whitelist remove
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![33],
-                redirect_node: None,
-                name: Some("remove"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 33: /whitelist add|remove [username]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("username"),
-                parser: Some(Parser::Entity(3)),
-                suggestions_type: None,
-            },
-            // 34: /container
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![35, 36, 37],
-                redirect_node: None,
-                name: Some("container"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 35: /container barrel
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![38],
-                redirect_node: None,
-                name: Some("barrel"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 36: /container hopper
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![38],
-                redirect_node: None,
-                name: Some("hopper"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 37: /container furnace
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![38],
-                redirect_node: None,
-                name: Some("furnace"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 38: /container [type] [power]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("power"),
-                parser: Some(Parser::Integer(0, 15)),
-                suggestions_type: None,
-            },
-            // 39: /plot lock
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("lock"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 40: /plot unlock
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("unlock"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 41: //load
-            Node {
-                flags: (CommandFlags::LITERAL).bits() as i8,
-                children: vec![42],
-                redirect_node: None,
-                name: Some("/load"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 42: //load [filename]
-            Node {
-                flags: (CommandFlags::ARGUMENT
-                    | CommandFlags::EXECUTABLE
-                    | CommandFlags::HAS_SUGGESTIONS_TYPE)
-                    .bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("filename"),
-                parser: Some(Parser::String(0)),
-                suggestions_type: Some("minecraft:ask_server"),
-            },
-            // 43: /toggleautorp
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("toggleautorp"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 44: /redpiler
-            Node {
-                flags: CommandFlags::LITERAL.bits() as i8,
-                children: vec![46, 47, 48], // Children are compile, inspect, reset
-                redirect_node: None,
-                name: Some("redpiler"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 45: /rp
-            Node {
-                flags: (CommandFlags::REDIRECT | CommandFlags::LITERAL).bits() as i8,
-                children: vec![],
-                redirect_node: Some(44), // Redirect to /redpiler
-                name: Some("rp"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 46: /redpiler compile
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("compile"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 47: /redpiler inspect
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("inspect"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 48: /redpiler reset
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("reset"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 49: /worldsendrate
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![50],
-                redirect_node: None,
-                name: Some("worldsendrate"),
-                parser: None,
-                suggestions_type: None,
-            },
-            // 50: /worldsendrate [rticks]
-            Node {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
-                children: vec![],
-                redirect_node: None,
-                name: Some("hertz"),
-                parser: Some(Parser::Integer(0, 1000)),
-                suggestions_type: None,
-            },
-            // 51: /wsr
-            Node {
-                flags: (CommandFlags::LITERAL | CommandFlags::REDIRECT).bits() as i8,
-                children: vec![],
-                redirect_node: Some(49),
-                name: Some("wsr"),
-                parser: None,
-                suggestions_type: None,
-            },
-        ],
+    let mut nodes = COMMAND_NODES.clone();
+    add_alias_completions(&mut nodes);
+    add_plugin_completions(&mut nodes);
+    CCommands {
+        nodes,
         root_index: 0,
-    };
-    super::worldedit::add_command_completions(&mut packet.nodes);
-    packet.encode()
+    }
+    .encode()
 });
+
+/// Adds the aliases from the config to the command tree, so that clients know about them. Aliases
+/// that have the name of another command are left out.
+fn add_alias_completions(nodes: &mut Vec<Node>) {
+    let Some(aliases) = &CONFIG.aliases else {
+        return;
+    };
+    let args_idx = nodes.len() as i32;
+    nodes.push(Node {
+        flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+        children: vec![],
+        redirect_node: None,
+        name: Some("arguments"),
+        parser: Some(Parser::String(2)),
+        suggestions_type: None,
+    });
+    for name in aliases.keys() {
+        let taken = nodes[0]
+            .children
+            .iter()
+            .any(|&idx| nodes[idx as usize].name == Some(name.as_str()));
+        if taken {
+            continue;
+        }
+        let idx = nodes.len() as i32;
+        nodes.push(Node {
+            flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
+            children: vec![args_idx],
+            redirect_node: None,
+            // The command tree lives as long as the server
+            name: Some(Box::leak(name.to_string().into_boxed_str())),
+            parser: None,
+            suggestions_type: None,
+        });
+        nodes[0].children.push(idx);
+    }
+}
+
+/// Adds the commands of plugins to the command tree. Commands that don't declare their arguments
+/// accept anything after them.
+fn add_plugin_completions(nodes: &mut Vec<Node>) {
+    let specs = plugins::commands();
+    if specs.is_empty() {
+        return;
+    }
+    let args_idx = nodes.len() as i32;
+    nodes.push(Node {
+        flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+        children: vec![],
+        redirect_node: None,
+        name: Some("arguments"),
+        parser: Some(Parser::String(2)),
+        suggestions_type: None,
+    });
+    for spec in specs {
+        let taken = nodes[0]
+            .children
+            .iter()
+            .any(|&idx| nodes[idx as usize].name == Some(spec.name.as_str()));
+        if taken {
+            continue;
+        }
+        let (children, executable) = match &spec.args {
+            Some(args) => plugin_arg_nodes(nodes, args),
+            None => (vec![args_idx], true),
+        };
+        let mut flags = CommandFlags::LITERAL;
+        flags.set(CommandFlags::EXECUTABLE, executable);
+        let idx = nodes.len() as i32;
+        nodes.push(Node {
+            flags: flags.bits() as i8,
+            children,
+            redirect_node: None,
+            // The command tree lives as long as the server
+            name: Some(Box::leak(spec.name.into_boxed_str())),
+            parser: None,
+            suggestions_type: None,
+        });
+        nodes[0].children.push(idx);
+    }
+}
+
+/// Adds the nodes for the arguments of a plugin command. Returns the nodes of the first argument,
+/// and whether the command can be run without it.
+fn plugin_arg_nodes(nodes: &mut Vec<Node>, args: &[ArgSpec]) -> (Vec<i32>, bool) {
+    let Some((arg, rest)) = args.split_first() else {
+        return (vec![], true);
+    };
+    let (children, rest_optional) = plugin_arg_nodes(nodes, rest);
+    let mut flags = CommandFlags::empty();
+    flags.set(CommandFlags::EXECUTABLE, rest_optional);
+    let flags = flags.bits();
+    let leak = |name: &str| -> &'static str { Box::leak(name.to_string().into_boxed_str()) };
+    let mut push = |node: Node| {
+        nodes.push(node);
+        nodes.len() as i32 - 1
+    };
+    let parser = match &arg.ty {
+        ArgType::Choice(choices) => {
+            let idxs = choices
+                .iter()
+                .map(|choice| {
+                    push(Node {
+                        flags: (flags | CommandFlags::LITERAL.bits()) as i8,
+                        children: children.clone(),
+                        redirect_node: None,
+                        name: Some(leak(choice)),
+                        parser: None,
+                        suggestions_type: None,
+                    })
+                })
+                .collect();
+            return (idxs, arg.optional);
+        }
+        ArgType::Word => Parser::String(0),
+        ArgType::Text => Parser::String(2),
+        ArgType::Integer { min, max } => Parser::Integer(*min, *max),
+        ArgType::Float { min, max } => Parser::Float(*min, *max),
+        ArgType::BlockPos => Parser::BlockPos,
+        ArgType::Player => Parser::Entity(3),
+    };
+    let idx = push(Node {
+        flags: (flags | CommandFlags::ARGUMENT.bits()) as i8,
+        children,
+        redirect_node: None,
+        name: Some(leak(&arg.name)),
+        parser: Some(parser),
+        suggestions_type: None,
+    });
+    (vec![idx], arg.optional)
+}
+
+#[test]
+fn expand_alias_test() {
+    assert_eq!(
+        expand_alias("/redpiler compile --io-only", &[]).as_deref(),
+        Some("redpiler compile --io-only")
+    );
+    assert_eq!(
+        expand_alias("//set", &["stone"]).as_deref(),
+        Some("/set stone")
+    );
+    assert_eq!(
+        expand_alias("//replace $2 $1", &["stone", "glass"]).as_deref(),
+        Some("/replace glass stone")
+    );
+    assert_eq!(
+        expand_alias("/msg $1 hello $*", &["Player", "again"]).as_deref(),
+        Some("msg Player hello Player again")
+    );
+    assert_eq!(expand_alias("/rtps $1", &[]), None);
+}
+
+#[test]
+fn plugin_arg_nodes_test() {
+    let spec = mchprs_plugin::CommandSpec::new("light", "")
+        .usage("<state:on|off> [delay:int]")
+        .unwrap();
+    let mut nodes = Vec::new();
+    let (first, executable) = plugin_arg_nodes(&mut nodes, spec.args.as_deref().unwrap());
+    assert!(!executable);
+    assert_eq!(first.len(), 2);
+    let delay = &nodes[0];
+    assert_eq!(delay.name, Some("delay"));
+    assert!(matches!(
+        delay.parser,
+        Some(Parser::Integer(i32::MIN, i32::MAX))
+    ));
+    for idx in first {
+        let choice = &nodes[idx as usize];
+        let flags = CommandFlags::from_bits_truncate(choice.flags as u32);
+        // The delay is optional, so the command can end after the choice
+        assert!(flags.contains(CommandFlags::LITERAL | CommandFlags::EXECUTABLE));
+        assert_eq!(choice.children, vec![0]);
+    }
+}
@@ -1,6 +1,8 @@
-use super::{Plot, PlotWorld, PLOT_WIDTH};
-use anyhow::{Context, Result};
+use super::{generator, PlotWorld, PLOT_MAX_Y, PLOT_MIN_Y, PLOT_SCALE, PLOT_SECTIONS, PLOT_WIDTH};
+use crate::config::CONFIG;
+use anyhow::{anyhow, Context, Error, Result};
 use mchprs_save_data::plot_data::{ChunkData, PlotData, Tps, WorldSendRate};
+use mchprs_world::storage::Chunk;
 use once_cell::sync::Lazy;
 use std::path::Path;
 use std::time::Duration;
@@ -19,16 +21,66 @@ pub fn sleep_time_for_tps(tps: Tps) -> Duration {
     }
 }
 
+/// The zstd level plots are saved at
+pub fn compression_level() -> i32 {
+    CONFIG.plot_compression_level.clamp(0, 22) as i32
+}
+
 pub fn load_plot(path: impl AsRef<Path>) -> Result<PlotData> {
     let path = path.as_ref();
     if path.exists() {
-        Ok(PlotData::load_from_file(path)
-            .with_context(|| format!("error loading plot save file at {}", path.display()))?)
+        PlotData::upgrade_file(path, compression_level())
+            .with_context(|| format!("error upgrading plot save file at {}", path.display()))?;
+        let data = PlotData::load_from_file(path)
+            .with_context(|| format!("error loading plot save file at {}", path.display()))?;
+        check_height(&data.chunk_data)
+            .with_context(|| format!("error loading plot save file at {}", path.display()))?;
+        Ok(data)
     } else {
         Ok(EMPTY_PLOT.clone())
     }
 }
 
+/// Fails if saved chunks have blocks outside of the world height in the config. Loading them
+/// would remove those blocks, and saving the plot afterwards would lose them for good.
+pub fn check_height(chunk_data: &[ChunkData]) -> Result<()> {
+    if chunk_data
+        .iter()
+        .all(|chunk| chunk.fits_height(*PLOT_MIN_Y, *PLOT_SECTIONS))
+    {
+        return Ok(());
+    }
+    Err(height_error())
+}
+
+fn height_error() -> Error {
+    anyhow!(
+        "the plot has blocks outside of the world height of y {} to {} in the config. Change \
+         min_y and height in the plot_world section so that they fit, or the blocks would be lost.",
+        *PLOT_MIN_Y,
+        *PLOT_MAX_Y - 1
+    )
+}
+
+/// Loads the saved chunks of a plot and fits them to the world height in the config, which may
+/// have changed since the plot was saved. Fails if that would remove blocks.
+pub fn load_chunks(chunk_data: Vec<ChunkData>, plot_x: i32, plot_z: i32) -> Result<Vec<Chunk>> {
+    chunk_data
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut chunk = c.load(
+                (plot_x << *PLOT_SCALE) + i as i32 / *PLOT_WIDTH,
+                (plot_z << *PLOT_SCALE) + i as i32 % *PLOT_WIDTH,
+            );
+            if chunk.set_height(*PLOT_MIN_Y, *PLOT_SECTIONS) {
+                return Err(height_error());
+            }
+            Ok(chunk)
+        })
+        .collect()
+}
+
 pub fn empty_plot() -> PlotData {
     EMPTY_PLOT.clone()
 }
@@ -36,12 +88,18 @@ pub fn empty_plot() -> PlotData {
 static EMPTY_PLOT: Lazy<PlotData> = Lazy::new(|| {
     let template_path = Path::new("./world/plots/pTEMPLATE");
     if template_path.exists() {
-        PlotData::load_from_file(template_path).expect("failed to read template plot")
+        PlotData::upgrade_file(template_path, compression_level())
+            .expect("failed to upgrade template plot");
+        let data = PlotData::load_from_file(template_path).expect("failed to read template plot");
+        if let Err(err) = check_height(&data.chunk_data) {
+            panic!("failed to read template plot: {}", err);
+        }
+        data
     } else {
         let mut chunks = Vec::new();
-        for chunk_x in 0..PLOT_WIDTH {
-            for chunk_z in 0..PLOT_WIDTH {
-                chunks.push(Plot::generate_chunk(8, chunk_x, chunk_z));
+        for chunk_x in 0..*PLOT_WIDTH {
+            for chunk_z in 0..*PLOT_WIDTH {
+                chunks.push(generator::generate_chunk(chunk_x, chunk_z));
             }
         }
         let mut world = PlotWorld {
@@ -50,6 +108,11 @@ static EMPTY_PLOT: Lazy<PlotData> = Lazy::new(|| {
             chunks,
             to_be_ticked: Vec::new(),
             packet_senders: Vec::new(),
+            quasi_connectivity: true,
+            torch_burnout: None,
+            item_frames: Vec::new(),
+            strict_update_order: false,
+            block_events: Vec::new(),
         };
         let chunk_data: Vec<ChunkData> = world.chunks.iter_mut().map(ChunkData::new).collect();
         PlotData {
@@ -57,6 +120,11 @@ static EMPTY_PLOT: Lazy<PlotData> = Lazy::new(|| {
             world_send_rate: WorldSendRate::default(),
             chunk_data,
             pending_ticks: Vec::new(),
+            item_frames: Vec::new(),
+            members: Vec::new(),
+            denied: Vec::new(),
+            settings: Default::default(),
+            redpiler_flags: None,
         }
     }
 });
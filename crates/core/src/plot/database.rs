@@ -1,6 +1,7 @@
 use once_cell::sync::Lazy;
 use rusqlite::{params, Connection};
 use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 static CONN: Lazy<Mutex<Connection>> = Lazy::new(|| {
     Mutex::new(Connection::open("./world/plots.db").expect("Error opening plot database!"))
@@ -46,6 +47,22 @@ pub fn get_cached_username(uuid: String) -> Option<String> {
         .ok()
 }
 
+/// Looks up the uuid of a player who has joined the server before by their username
+pub fn get_user_uuid(name: &str) -> Option<String> {
+    lock()
+        .query_row(
+            "SELECT
+                uuid
+            FROM
+                user
+            WHERE
+                name=?1 COLLATE NOCASE",
+            params![name],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+}
+
 pub fn get_owned_plots(player: &str) -> Vec<(i32, i32)> {
     let conn = lock();
     let mut stmt = conn
@@ -59,8 +76,10 @@ pub fn get_owned_plots(player: &str) -> Vec<(i32, i32)> {
                 JOIN
                     user ON user.id = userplot.user_id
                 WHERE
-                    name=?1
-                    AND is_owner=TRUE",
+                    name=?1 COLLATE NOCASE
+                    AND is_owner=TRUE
+                ORDER BY
+                    plot.id",
         )
         .unwrap();
     stmt.query_map(params![player], |row| Ok((row.get(0)?, row.get(1)?)))
@@ -99,15 +118,185 @@ pub fn claim_plot(plot_x: i32, plot_z: i32, uuid: &str) {
     .unwrap();
 }
 
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as i64)
+}
+
 pub fn ensure_user(uuid: &str, name: &str) {
     lock()
         .execute(
-            "INSERT INTO user(uuid, name)
-                VALUES (?1, ?2)
-                ON CONFLICT (uuid) DO UPDATE SET name = ?3",
-            params![uuid, name, name],
+            "INSERT INTO user(uuid, name, last_seen)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT (uuid) DO UPDATE SET name = ?2, last_seen = ?3",
+            params![uuid, name, now()],
+        )
+        .unwrap();
+}
+
+/// Remembers that the player was on the server just now, which keeps their plots from expiring
+pub fn update_last_seen(uuid: &str) {
+    lock()
+        .execute(
+            "UPDATE user SET last_seen = ?1 WHERE uuid = ?2",
+            params![now(), uuid],
+        )
+        .unwrap();
+}
+
+/// Returns the position and owner uuid of every plot whose owner hasn't been seen for `max_age`
+/// seconds
+pub fn get_expired_plots(max_age: i64) -> Vec<(i32, i32, String)> {
+    let conn = lock();
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT
+                    plot_x, plot_z, uuid
+                FROM
+                    plot
+                JOIN
+                    userplot ON userplot.plot_id = plot.id
+                JOIN
+                    user ON user.id = userplot.user_id
+                WHERE
+                    last_seen < ?1
+                    AND is_owner=TRUE",
         )
         .unwrap();
+    stmt.query_map(params![now() - max_age], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect()
+}
+
+/// Returns the position and owner name of every claimed plot
+pub fn get_claimed_plots() -> Vec<(i32, i32, String)> {
+    let conn = lock();
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT
+                    plot_x, plot_z, name
+                FROM
+                    plot
+                JOIN
+                    userplot ON userplot.plot_id = plot.id
+                JOIN
+                    user ON user.id = userplot.user_id
+                WHERE
+                    is_owner=TRUE
+                ORDER BY
+                    plot.id",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+pub fn unclaim_plot(plot_x: i32, plot_z: i32) {
+    let conn = lock();
+    conn.execute(
+        "DELETE FROM userplot WHERE plot_id IN
+            (SELECT id FROM plot WHERE plot_x = ?1 AND plot_z = ?2)",
+        params![plot_x, plot_z],
+    )
+    .unwrap();
+    conn.execute(
+        "DELETE FROM plotrating WHERE plot_id IN
+            (SELECT id FROM plot WHERE plot_x = ?1 AND plot_z = ?2)",
+        params![plot_x, plot_z],
+    )
+    .unwrap();
+    conn.execute(
+        "DELETE FROM plot WHERE plot_x = ?1 AND plot_z = ?2",
+        params![plot_x, plot_z],
+    )
+    .unwrap();
+}
+
+/// Sets the player's rating of a claimed plot, replacing their previous rating
+pub fn rate_plot(plot_x: i32, plot_z: i32, uuid: &str, rating: u32) {
+    lock()
+        .execute(
+            "INSERT INTO plotrating(plot_id, user_id, rating)
+                VALUES(
+                    (SELECT id FROM plot WHERE plot_x = ?1 AND plot_z = ?2),
+                    (SELECT id FROM user WHERE user.uuid = ?3),
+                    ?4
+                )
+                ON CONFLICT (plot_id, user_id) DO UPDATE SET rating = ?4",
+            params![plot_x, plot_z, uuid, rating],
+        )
+        .unwrap();
+}
+
+/// Returns the average rating of the plot and how many players rated it
+pub fn get_plot_rating(plot_x: i32, plot_z: i32) -> Option<(f64, u32)> {
+    lock()
+        .query_row(
+            "SELECT
+                AVG(rating), COUNT(rating)
+            FROM
+                plotrating
+            JOIN
+                plot ON plot.id = plotrating.plot_id
+            WHERE
+                plot_x=?1
+                AND plot_z=?2",
+            params![plot_x, plot_z],
+            |row| Ok((row.get::<_, Option<f64>>(0)?, row.get(1)?)),
+        )
+        .ok()
+        .and_then(|(rating, count)| Some((rating?, count)))
+}
+
+/// A plot in the list of the best rated plots
+pub struct RatedPlot {
+    pub plot_x: i32,
+    pub plot_z: i32,
+    pub owner: String,
+    pub rating: f64,
+    pub num_ratings: u32,
+}
+
+/// Returns the best rated plots, skipping the first `offset`
+pub fn get_top_plots(limit: usize, offset: usize) -> Vec<RatedPlot> {
+    let conn = lock();
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT
+                    plot_x, plot_z, name, AVG(rating) AS average, COUNT(rating)
+                FROM
+                    plotrating
+                JOIN
+                    plot ON plot.id = plotrating.plot_id
+                JOIN
+                    userplot ON userplot.plot_id = plot.id AND is_owner=TRUE
+                JOIN
+                    user ON user.id = userplot.user_id
+                GROUP BY
+                    plot.id
+                ORDER BY
+                    average DESC, COUNT(rating) DESC
+                LIMIT ?1 OFFSET ?2",
+        )
+        .unwrap();
+    stmt.query_map(params![limit, offset], |row| {
+        Ok(RatedPlot {
+            plot_x: row.get(0)?,
+            plot_z: row.get(1)?,
+            owner: row.get(2)?,
+            rating: row.get(3)?,
+            num_ratings: row.get(4)?,
+        })
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect()
 }
 
 pub fn init() {
@@ -117,12 +306,25 @@ pub fn init() {
         "CREATE TABLE IF NOT EXISTS user(
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             uuid BLOB(16) UNIQUE NOT NULL,
-            name VARCHAR(16) NOT NULL
+            name VARCHAR(16) NOT NULL,
+            last_seen INTEGER NOT NULL DEFAULT 0
         )",
         [],
     )
     .unwrap();
 
+    // Databases from before plots could expire don't know when players were last seen. Everyone
+    // is counted as seen now so that their plots don't expire right away.
+    if conn.prepare("SELECT last_seen FROM user").is_err() {
+        conn.execute(
+            "ALTER TABLE user ADD COLUMN last_seen INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .unwrap();
+        conn.execute("UPDATE user SET last_seen = ?1", params![now()])
+            .unwrap();
+    }
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS plot(
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -144,4 +346,17 @@ pub fn init() {
         [],
     )
     .unwrap();
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plotrating(
+            plot_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            rating INTEGER NOT NULL,
+            UNIQUE(plot_id, user_id),
+            FOREIGN KEY(plot_id) REFERENCES plot(id),
+            FOREIGN KEY(user_id) REFERENCES user(id)
+        )",
+        [],
+    )
+    .unwrap();
 }
@@ -0,0 +1,66 @@
+//! Plots whose owners haven't joined the server for `inactive_days` are saved to the schematic
+//! archive, cleared and unclaimed, so that long-running servers don't run out of free plots. The
+//! archived plots are kept in `archive/<owner>/` inside of the schematic folder.
+
+use super::{data, database, worldedit, PlotWorld};
+use crate::config::PlotExpiryConfig;
+use anyhow::{Context, Result};
+use std::fs;
+use tracing::{error, info};
+
+/// Loads the plot from its save file and saves it to the archive
+fn archive_plot(plot_x: i32, plot_z: i32, owner: &str, path: &str) -> Result<()> {
+    let plot_data = data::load_plot(path)?;
+    let chunks = data::load_chunks(plot_data.chunk_data, plot_x, plot_z)?;
+    let mut world = PlotWorld {
+        x: plot_x,
+        z: plot_z,
+        chunks,
+        to_be_ticked: plot_data.pending_ticks,
+        packet_senders: Vec::new(),
+        quasi_connectivity: true,
+        torch_burnout: None,
+        item_frames: Vec::new(),
+        strict_update_order: false,
+        block_events: Vec::new(),
+    };
+    let date = chrono::Utc::now().format("%Y-%m-%d");
+    let file_name = format!(
+        "archive/{}/plot_{}_{}_{}.schem",
+        owner, plot_x, plot_z, date
+    );
+    let clipboard = worldedit::save_plot_schematic(&file_name, &mut world)
+        .with_context(|| format!("error saving {}", file_name))?;
+    worldedit::index_saved_schematic(&file_name, owner, &clipboard);
+    Ok(())
+}
+
+/// Archives, clears and unclaims the plots of inactive owners. Plots that are loaded right now
+/// are skipped, since someone is using them.
+pub fn expire_inactive_plots(config: &PlotExpiryConfig, is_loaded: impl Fn(i32, i32) -> bool) {
+    let max_age = config.inactive_days as i64 * 24 * 60 * 60;
+    for (plot_x, plot_z, owner_uuid) in database::get_expired_plots(max_age) {
+        if is_loaded(plot_x, plot_z) {
+            continue;
+        }
+        let owner = database::get_cached_username(owner_uuid.clone()).unwrap_or(owner_uuid);
+        info!(
+            "Plot {},{} of {} has expired, archiving and clearing it",
+            plot_x, plot_z, owner
+        );
+
+        let path = format!("./world/plots/p{},{}", plot_x, plot_z);
+        if fs::metadata(&path).is_ok() {
+            if let Err(err) = archive_plot(plot_x, plot_z, &owner, &path) {
+                // The plot is kept so that nothing is lost
+                error!("Failed to archive plot {},{}: {:?}", plot_x, plot_z, err);
+                continue;
+            }
+            if let Err(err) = fs::remove_file(&path) {
+                error!("Failed to clear plot {},{}: {}", plot_x, plot_z, err);
+                continue;
+            }
+        }
+        database::unclaim_plot(plot_x, plot_z);
+    }
+}
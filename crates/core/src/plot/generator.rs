@@ -0,0 +1,48 @@
+//! New plots are made by a `PlotGenerator` from the world crate. By default it is picked with the
+//! `generator` option in the `plot_world` section of the config, but a custom generator can be set
+//! with `set_plot_generator` before the server starts.
+
+use super::{PLOT_BLOCK_WIDTH, PLOT_MIN_Y, PLOT_SECTIONS};
+use crate::config::CONFIG;
+use mchprs_blocks::blocks::Block;
+use mchprs_world::generator::{FlatPlotGenerator, PlotGenerator, PlotLayout, VoidGenerator};
+use mchprs_world::storage::Chunk;
+use once_cell::sync::OnceCell;
+
+static GENERATOR: OnceCell<Box<dyn PlotGenerator>> = OnceCell::new();
+
+/// Replaces the generator set in the config. This has to be called before the server is started,
+/// and the generator is given back if one was already set.
+pub fn set_plot_generator(generator: Box<dyn PlotGenerator>) -> Result<(), Box<dyn PlotGenerator>> {
+    GENERATOR.set(generator)
+}
+
+fn generator_from_config() -> Box<dyn PlotGenerator> {
+    let config = &CONFIG.plot_world;
+    match config.generator.as_str() {
+        "flat" => {
+            let wall = (!config.wall_block.is_empty())
+                .then(|| Block::from_name(&config.wall_block).expect("invalid plot wall block"));
+            Box::new(FlatPlotGenerator {
+                floor: Block::from_name(&config.floor_block).expect("invalid plot floor block"),
+                road: Block::from_name(&config.road_block).expect("invalid plot road block"),
+                wall,
+                floor_height: config.floor_height,
+                road_width: config.road_width,
+            })
+        }
+        "void" => Box::new(VoidGenerator),
+        generator => panic!("unknown plot generator: {}", generator),
+    }
+}
+
+/// Generates a chunk of a new plot
+pub(super) fn generate_chunk(x: i32, z: i32) -> Chunk {
+    let generator = GENERATOR.get_or_init(generator_from_config);
+    let layout = PlotLayout {
+        plot_block_width: *PLOT_BLOCK_WIDTH,
+        min_y: *PLOT_MIN_Y,
+        num_sections: *PLOT_SECTIONS,
+    };
+    generator.generate_chunk(&layout, x, z)
+}
@@ -0,0 +1,130 @@
+use super::Plot;
+use crate::player;
+use mchprs_blocks::{BlockFacing, BlockPos};
+use mchprs_network::packets::clientbound::{
+    CRemoveEntities, CSetEntityMetadata, CSetEntityMetadataEntry, CSpawnEntity, ClientBoundPacket,
+};
+use mchprs_network::packets::{PacketEncoder, PacketEncoderExt, SlotData};
+use mchprs_redstone as redstone;
+use mchprs_world::ItemFrame;
+
+/// The entity type id of `minecraft:item_frame`
+const ITEM_FRAME_ENTITY_TYPE: i32 = 57;
+
+/// The direction id the client expects as the spawn data of hanging entities
+fn direction_id(facing: BlockFacing) -> i32 {
+    match facing {
+        BlockFacing::Down => 0,
+        BlockFacing::Up => 1,
+        BlockFacing::North => 2,
+        BlockFacing::South => 3,
+        BlockFacing::West => 4,
+        BlockFacing::East => 5,
+    }
+}
+
+fn spawn_packet(frame: &ItemFrame) -> PacketEncoder {
+    CSpawnEntity {
+        entity_id: frame.entity_id as i32,
+        entity_uuid: frame.entity_id as u128,
+        entity_type: ITEM_FRAME_ENTITY_TYPE,
+        x: frame.pos.x as f64 + 0.5,
+        y: frame.pos.y as f64 + 0.5,
+        z: frame.pos.z as f64 + 0.5,
+        pitch: 0.0,
+        yaw: 0.0,
+        head_yaw: 0.0,
+        data: direction_id(frame.facing),
+        velocity_x: 0,
+        velocity_y: 0,
+        velocity_z: 0,
+    }
+    .encode()
+}
+
+fn metadata_packet(frame: &ItemFrame) -> PacketEncoder {
+    let mut item = Vec::new();
+    item.write_slot_data(&frame.item.map(|item_id| SlotData {
+        item_count: 1,
+        item_id: item_id as i32,
+        nbt: None,
+    }));
+    let mut rotation = Vec::new();
+    rotation.write_varint(frame.rotation as i32);
+    CSetEntityMetadata {
+        entity_id: frame.entity_id as i32,
+        metadata: vec![
+            CSetEntityMetadataEntry {
+                index: 8,
+                metadata_type: 7,
+                value: item,
+            },
+            CSetEntityMetadataEntry {
+                index: 9,
+                metadata_type: 1,
+                value: rotation,
+            },
+        ],
+    }
+    .encode()
+}
+
+impl Plot {
+    pub(super) fn spawn_item_frames_for_player(&self, player: usize) {
+        for frame in &self.world.item_frames {
+            let client = &self.players[player].client;
+            client.send_packet(&spawn_packet(frame));
+            client.send_packet(&metadata_packet(frame));
+        }
+    }
+
+    /// Places an item frame, or changes the item frame that already exists at the same position
+    /// with the same facing.
+    pub(super) fn set_item_frame(&mut self, mut frame: ItemFrame) {
+        let existing = self
+            .world
+            .item_frames
+            .iter_mut()
+            .find(|other| other.pos == frame.pos && other.facing == frame.facing);
+        let packets = match existing {
+            Some(existing) => {
+                frame.entity_id = existing.entity_id;
+                *existing = frame.clone();
+                vec![metadata_packet(&frame)]
+            }
+            None => {
+                frame.entity_id = player::next_entity_id();
+                self.world.item_frames.push(frame.clone());
+                vec![spawn_packet(&frame), metadata_packet(&frame)]
+            }
+        };
+        for player in &self.players {
+            for packet in &packets {
+                player.client.send_packet(packet);
+            }
+        }
+        redstone::comparator::update_comparators_around(&mut self.world, frame.pos);
+    }
+
+    /// Removes the item frame at `pos` with `facing`. Returns false if there was no such item frame.
+    pub(super) fn remove_item_frame(&mut self, pos: BlockPos, facing: BlockFacing) -> bool {
+        let Some(idx) = self
+            .world
+            .item_frames
+            .iter()
+            .position(|frame| frame.pos == pos && frame.facing == facing)
+        else {
+            return false;
+        };
+        let frame = self.world.item_frames.remove(idx);
+        let remove_entity = CRemoveEntities {
+            entity_ids: vec![frame.entity_id as i32],
+        }
+        .encode();
+        for player in &self.players {
+            player.client.send_packet(&remove_entity);
+        }
+        redstone::comparator::update_comparators_around(&mut self.world, pos);
+        true
+    }
+}
@@ -1,58 +1,118 @@
+mod access;
+pub mod chunk_sender;
+mod circuit_tests;
 pub mod commands;
 mod data;
 pub mod database;
+pub mod expiry;
+pub mod generator;
+mod item_frames;
 mod monitor;
 mod packet_handlers;
+mod plugin_handle;
+mod replay;
 mod scoreboard;
+mod sky;
+mod snapshot;
+mod suggestions;
+mod tab_list;
+mod template;
+pub mod threads;
+pub mod watchdog;
 pub mod worldedit;
 
 use crate::config::CONFIG;
 use crate::interaction;
 use crate::interaction::UseOnBlockContext;
-use crate::player::{EntityId, Gamemode, PacketSender, Player, PlayerPos};
-use crate::server::{BroadcastMessage, Message, PrivMessage};
+use crate::player::{self, ChatChannel, EntityId, Gamemode, PacketSender, Player, PlayerPos};
+use crate::server::{self, BroadcastMessage, Message, PrivMessage};
 use crate::utils::HyphenatedUUID;
+use crate::webhooks::WebhookEvent;
+use access::PlotAccess;
 use anyhow::Error;
 use bus::BusReader;
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::Block;
 use mchprs_blocks::items::Item;
-use mchprs_blocks::{BlockFace, BlockPos};
+use mchprs_blocks::{BlockFace, BlockFacing, BlockPos};
 use mchprs_network::packets::clientbound::*;
 use mchprs_network::packets::serverbound::SUseItemOn;
 use mchprs_network::PlayerPacketSender;
-use mchprs_redpiler::{Compiler, CompilerOptions};
-use mchprs_save_data::plot_data::{ChunkData, PlotData, Tps, WorldSendRate};
-use mchprs_text::TextComponent;
+use mchprs_plugin::events::{
+    BlockBreak, BlockPlace, ChunkLoad, CommandRun, CompileFinish, CompileStart, PlotLoad,
+    PlotUnload,
+};
+use mchprs_plugin::Event;
+use mchprs_redpiler::{Compiler, CompilerOptions, TaskMonitor};
+use mchprs_save_data::plot_data::{
+    ChunkData, PlotData, PlotPermission, PlotSettings, Tps, WorldSendRate,
+};
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
 use mchprs_world::storage::Chunk;
-use mchprs_world::{TickEntry, TickPriority, World};
+use mchprs_world::{BlockEvent, ItemFrame, TickEntry, TickPriority, TorchBurnout, World};
 use monitor::TimingsMonitor;
+use once_cell::sync::Lazy;
 use scoreboard::RedpilerState;
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
 
-use self::data::sleep_time_for_tps;
+use self::data::{compression_level, sleep_time_for_tps};
+use self::replay::{ReplayInput, ReplayState};
 use self::scoreboard::Scoreboard;
-
-/// The width of a plot (2^n)
-pub const PLOT_SCALE: u32 = 5;
+use self::threads::PlotThread;
+use self::watchdog::PlotWatchdog;
+
+/// The width of a plot (2^n), set with `scale` in the `plot_world` section of the config
+pub static PLOT_SCALE: Lazy<u32> = Lazy::new(|| {
+    let scale = CONFIG.plot_world.scale;
+    assert!(
+        (1..=8).contains(&scale),
+        "the plot scale must be between 1 and 8"
+    );
+    scale
+});
 
 /// The width of a plot counted in chunks
-pub const PLOT_WIDTH: i32 = 2i32.pow(PLOT_SCALE);
+pub static PLOT_WIDTH: Lazy<i32> = Lazy::new(|| 2i32.pow(*PLOT_SCALE));
 /// The plot width in blocks
-pub const PLOT_BLOCK_WIDTH: i32 = PLOT_WIDTH * 16;
-pub const NUM_CHUNKS: usize = PLOT_WIDTH.pow(2) as usize;
-
-/// The height of the world in sections (Default: 24, Max: 127)
-pub const PLOT_SECTIONS: usize = 24;
-/// The plot height in blocks
-pub const PLOT_BLOCK_HEIGHT: i32 = PLOT_SECTIONS as i32 * 16;
+pub static PLOT_BLOCK_WIDTH: Lazy<i32> = Lazy::new(|| *PLOT_WIDTH * 16);
+pub static NUM_CHUNKS: Lazy<usize> = Lazy::new(|| PLOT_WIDTH.pow(2) as usize);
+
+/// The lowest y of the world, set with `min_y` in the `plot_world` section of the config
+pub static PLOT_MIN_Y: Lazy<i32> = Lazy::new(|| {
+    let min_y = CONFIG.plot_world.min_y;
+    assert!(min_y % 16 == 0, "the world min_y must be a multiple of 16");
+    assert!(
+        (-2032..=0).contains(&min_y),
+        "the world min_y must be between -2032 and 0"
+    );
+    min_y
+});
+/// The plot height in blocks, set with `height` in the `plot_world` section of the config
+pub static PLOT_BLOCK_HEIGHT: Lazy<i32> = Lazy::new(|| {
+    let height = CONFIG.plot_world.height as i32;
+    assert!(
+        height % 16 == 0,
+        "the world height must be a multiple of 16"
+    );
+    assert!(
+        height > 0 && *PLOT_MIN_Y + height <= 2032,
+        "the top of the world must be above 0 and at most 2032"
+    );
+    height
+});
+/// The height of the world in sections
+pub static PLOT_SECTIONS: Lazy<usize> = Lazy::new(|| *PLOT_BLOCK_HEIGHT as usize / 16);
+/// The y one above the highest block of the world
+pub static PLOT_MAX_Y: Lazy<i32> = Lazy::new(|| *PLOT_MIN_Y + *PLOT_BLOCK_HEIGHT);
 
 const ERROR_IO_ONLY: &str = "This plot cannot be interacted with while redpiler is active with `--io-only`. To stop redpiler, run `/redpiler reset`.";
 
@@ -79,6 +139,8 @@ pub struct Plot {
     last_player_time: Instant,
     /// The last time the world changes were sent to the player
     last_world_send_time: Instant,
+    /// The last time the tab list was sent to the players
+    last_tab_list_refresh: Instant,
     /// The duration we should sleep for after every update
     sleep_time: Duration,
     /// When this is false, the update loop will end and the thread will stop.
@@ -87,10 +149,22 @@ pub struct Plot {
     /// If true, the plot will remain running even if no players are on for a long time.
     always_running: bool,
     auto_redpiler: bool,
+    settings: PlotSettings,
+    /// The flags redpiler was running with when the server shut down. The plot is compiled with
+    /// them again when it starts running.
+    resume_redpiler: Option<String>,
 
-    owner: Option<u128>,
+    access: PlotAccess,
     async_rt: Runtime,
     scoreboard: Scoreboard,
+    thread: PlotThread,
+    watchdog: PlotWatchdog,
+    /// Set when the plot thread panicked. The plot isn't saved or unloaded normally after that.
+    crashed: bool,
+    /// Components plugins are told about when their output changes, with the last output seen
+    output_watches: Vec<(BlockPos, Option<u8>)>,
+    /// The replay that is being recorded or played back while redpiler is running
+    replay: Option<ReplayState>,
 }
 
 pub struct PlotWorld {
@@ -99,22 +173,27 @@ pub struct PlotWorld {
     pub chunks: Vec<Chunk>,
     pub to_be_ticked: Vec<TickEntry>,
     pub packet_senders: Vec<PlayerPacketSender>,
+    pub quasi_connectivity: bool,
+    pub torch_burnout: Option<TorchBurnout>,
+    pub item_frames: Vec<ItemFrame>,
+    pub strict_update_order: bool,
+    pub block_events: Vec<BlockEvent>,
 }
 
 impl PlotWorld {
     fn get_chunk_index_for_chunk(&self, chunk_x: i32, chunk_z: i32) -> usize {
-        let local_x = chunk_x - self.x * PLOT_WIDTH;
-        let local_z = chunk_z - self.z * PLOT_WIDTH;
-        (local_x * PLOT_WIDTH + local_z).unsigned_abs() as usize
+        let local_x = chunk_x - self.x * *PLOT_WIDTH;
+        let local_z = chunk_z - self.z * *PLOT_WIDTH;
+        (local_x * *PLOT_WIDTH + local_z).unsigned_abs() as usize
     }
 
     fn get_chunk_index_for_block(&self, block_x: i32, block_z: i32) -> Option<usize> {
-        let chunk_x = (block_x - (self.x * PLOT_BLOCK_WIDTH)) >> 4;
-        let chunk_z = (block_z - (self.z * PLOT_BLOCK_WIDTH)) >> 4;
-        if chunk_x >= PLOT_WIDTH || chunk_z >= PLOT_WIDTH {
+        let chunk_x = (block_x - (self.x * *PLOT_BLOCK_WIDTH)) >> 4;
+        let chunk_z = (block_z - (self.z * *PLOT_BLOCK_WIDTH)) >> 4;
+        if chunk_x >= *PLOT_WIDTH || chunk_z >= *PLOT_WIDTH {
             return None;
         }
-        Some(((chunk_x << PLOT_SCALE) + chunk_z).unsigned_abs() as usize)
+        Some(((chunk_x << *PLOT_SCALE) + chunk_z).unsigned_abs() as usize)
     }
 
     fn flush_block_changes(&mut self) {
@@ -130,13 +209,9 @@ impl PlotWorld {
     }
 
     pub fn get_corners(&self) -> (BlockPos, BlockPos) {
-        const W: i32 = PLOT_BLOCK_WIDTH;
-        let first_pos = BlockPos::new(self.x * W, 0, self.z * W);
-        let second_pos = BlockPos::new(
-            (self.x + 1) * W - 1,
-            PLOT_BLOCK_HEIGHT - 1,
-            (self.z + 1) * W - 1,
-        );
+        let w = *PLOT_BLOCK_WIDTH;
+        let first_pos = BlockPos::new(self.x * w, *PLOT_MIN_Y, self.z * w);
+        let second_pos = BlockPos::new((self.x + 1) * w - 1, *PLOT_MAX_Y - 1, (self.z + 1) * w - 1);
         (first_pos, second_pos)
     }
 }
@@ -150,17 +225,12 @@ impl World for PlotWorld {
         };
 
         // Check to see if block is within height limit
-        if pos.y >= PLOT_BLOCK_HEIGHT || pos.y < 0 {
+        if pos.y >= *PLOT_MAX_Y || pos.y < *PLOT_MIN_Y {
             return false;
         }
 
         let chunk = &mut self.chunks[chunk_index];
-        chunk.set_block(
-            (pos.x & 0xF) as u32,
-            pos.y as u32,
-            (pos.z & 0xF) as u32,
-            block,
-        )
+        chunk.set_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32, block)
     }
 
     /// Returns the block state id of the block at `pos`
@@ -170,7 +240,7 @@ impl World for PlotWorld {
             None => return 0,
         };
         let chunk = &self.chunks[chunk_index];
-        chunk.get_block((pos.x & 0xF) as u32, pos.y as u32, (pos.z & 0xF) as u32)
+        chunk.get_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32)
     }
 
     fn delete_block_entity(&mut self, pos: BlockPos) {
@@ -232,6 +302,82 @@ impl World for PlotWorld {
         self.to_be_ticked.iter().any(|e| e.pos == pos)
     }
 
+    fn quasi_connectivity(&self) -> bool {
+        self.quasi_connectivity
+    }
+
+    fn copper_bulb_delay(&self) -> bool {
+        CONFIG.copper_bulb_delay
+    }
+
+    fn zero_tick_pistons(&self) -> bool {
+        CONFIG.quirks.zero_tick_pistons
+    }
+
+    fn repeater_tick_priority(&self) -> bool {
+        CONFIG.quirks.repeater_tick_priority
+    }
+
+    fn comparator_tick_priority(&self) -> bool {
+        CONFIG.quirks.comparator_tick_priority
+    }
+
+    fn strict_update_order(&self) -> bool {
+        self.strict_update_order
+    }
+
+    fn queue_block_event(&mut self, event: BlockEvent) {
+        if !self.block_events.contains(&event) {
+            self.block_events.push(event);
+        }
+    }
+
+    fn get_item_frame(&self, pos: BlockPos, facing: BlockFacing) -> Option<&ItemFrame> {
+        self.item_frames
+            .iter()
+            .find(|frame| frame.pos == pos && frame.facing == facing)
+    }
+
+    fn torch_burnout(&mut self) -> Option<&mut TorchBurnout> {
+        self.torch_burnout.as_mut()
+    }
+
+    fn play_world_event(&mut self, pos: BlockPos, event: i32, data: i32) {
+        let world_event = CWorldEvent {
+            event,
+            x: pos.x,
+            y: pos.y,
+            z: pos.z,
+            data,
+            disable_relative_volume: false,
+        }
+        .encode();
+        for player in &self.packet_senders {
+            player.send_packet(&world_event);
+        }
+    }
+
+    fn play_block_event(
+        &mut self,
+        pos: BlockPos,
+        block_type: i32,
+        action_id: u8,
+        action_param: u8,
+    ) {
+        let block_action = CBlockAction {
+            x: pos.x,
+            y: pos.y,
+            z: pos.z,
+            action_id,
+            action_param,
+            block_type,
+        }
+        .encode();
+        for player in &self.packet_senders {
+            player.send_packet(&block_action);
+        }
+    }
+
     fn play_sound(
         &mut self,
         pos: BlockPos,
@@ -246,7 +392,6 @@ impl World for PlotWorld {
         let sound_effect_data = CSoundEffect {
             sound_id: sound_id + 1,
             sound_name: None,
-            has_fixed_range: None,
             range: None,
             sound_category,
             x: pos.x * 8 + 4,
@@ -269,7 +414,10 @@ impl Plot {
     fn tickn(&mut self, ticks: u64) {
         if self.redpiler.is_active() {
             self.timings.tickn(ticks);
-            self.redpiler.tickn(ticks);
+            match self.replay {
+                Some(_) => self.replay_tickn(ticks),
+                None => self.redpiler.tickn(ticks),
+            }
             return;
         }
 
@@ -281,10 +429,16 @@ impl Plot {
     fn tick(&mut self) {
         self.timings.tick();
         if self.redpiler.is_active() {
-            self.redpiler.tick();
+            match self.replay {
+                Some(_) => self.replay_tickn(1),
+                None => self.redpiler.tick(),
+            }
             return;
         }
 
+        if let Some(torch_burnout) = &mut self.world.torch_burnout {
+            torch_burnout.tick();
+        }
         self.world
             .to_be_ticked
             .sort_by_key(|e| (e.ticks_left, e.tick_priority));
@@ -295,6 +449,12 @@ impl Plot {
             let entry = self.world.to_be_ticked.remove(0);
             mchprs_redstone::tick(self.world.get_block(entry.pos), &mut self.world, entry.pos);
         }
+        // Block events can queue more block events, which run in the same tick
+        while !self.world.block_events.is_empty() {
+            for event in std::mem::take(&mut self.world.block_events) {
+                mchprs_redstone::run_block_event(&mut self.world, event);
+            }
+        }
     }
 
     /// Send a block change to all connected players
@@ -320,6 +480,68 @@ impl Plot {
         self.message_sender.send(broadcast_message).unwrap();
     }
 
+    /// Sends a chat message from the player to everyone in `channel`
+    fn send_chat(&mut self, player: usize, channel: ChatChannel, message: String) {
+        if channel
+            .permission()
+            .is_some_and(|permission| !self.players[player].has_permission(permission))
+        {
+            self.players[player].send_no_permission_message();
+            return;
+        }
+        let player_ref = &self.players[player];
+        let (uuid, username) = (player_ref.uuid, player_ref.username.clone());
+        match channel {
+            ChatChannel::Global => {
+                let _ = self
+                    .message_sender
+                    .send(Message::ChatInfo(uuid, username, message));
+            }
+            ChatChannel::Plot => self.send_plot_chat(player, &message),
+            ChatChannel::Staff => {
+                let _ = self
+                    .message_sender
+                    .send(Message::StaffChatInfo(uuid, username, message));
+            }
+        }
+    }
+
+    /// Sends a private message from the player to the player named `username`, who can be on any
+    /// plot
+    fn send_private_chat(&mut self, player: usize, username: &str, message: String) {
+        let player = &mut self.players[player];
+        player.reply_to = Some(username.to_string());
+        let _ = self.message_sender.send(Message::PrivateChat(
+            player.uuid,
+            player.username.clone(),
+            username.to_string(),
+            message,
+            PlayerPacketSender::new(&player.client),
+        ));
+    }
+
+    /// Sends a chat message from the player to only the players on this plot
+    fn send_plot_chat(&mut self, player: usize, message: &str) {
+        let player = &self.players[player];
+        let sender = player.uuid;
+        info!(
+            "[Plot {},{}] <{}> {}",
+            self.world.x, self.world.z, player.username, message
+        );
+        let message = TextComponent::from_legacy_text(
+            &CONFIG
+                .plot_chat_format
+                .replace("{plot}", &format!("{},{}", self.world.x, self.world.z))
+                .replace("{username}", &player.username)
+                .replace("{message}", message),
+        );
+        for player in &self.players {
+            if !player.ignored_players.contains(&sender) {
+                player.send_chat_message(&message);
+            }
+        }
+    }
+
     pub fn broadcast_plot_chat_message(&mut self, message: &str) {
         for player in &mut self.players {
             player.send_chat_message(&TextComponent::from_legacy_text(message));
@@ -353,7 +575,9 @@ impl Plot {
 
     fn set_pressure_plate(&mut self, pos: BlockPos, powered: bool) {
         if self.redpiler.is_active() {
-            self.redpiler.set_pressure_plate(pos, powered);
+            if self.record_replay_input(ReplayInput::PressurePlate { pos, powered }) {
+                self.redpiler.set_pressure_plate(pos, powered);
+            }
             return;
         }
 
@@ -381,7 +605,15 @@ impl Plot {
         false
     }
 
-    fn enter_plot(&mut self, player: Player) {
+    fn enter_plot(&mut self, mut player: Player) {
+        if self.access.is_denied(&player) {
+            player.send_error_message("You are denied from this plot.");
+            Plot::send_player_away(self.world.x, self.world.z, &mut player);
+            self.message_sender
+                .send(Message::PlayerLeavePlot(player))
+                .unwrap();
+            return;
+        }
         self.save();
         let spawn_player = player.spawn_packet().encode();
         let metadata = player.metadata_packet().encode();
@@ -412,8 +644,12 @@ impl Plot {
             .packet_senders
             .push(PlayerPacketSender::new(&player.client));
         self.scoreboard.add_player(&player);
+        self.send_time(&player);
+        self.send_weather(&player);
+        self.send_tab_list(&player);
         self.players.push(player);
         self.update_view_pos_for_player(self.players.len() - 1, true);
+        self.spawn_item_frames_for_player(self.players.len() - 1);
     }
 
     fn get_chunk_distance(x1: i32, z1: i32, x2: i32, z2: i32) -> u32 {
@@ -430,20 +666,13 @@ impl Plot {
         was_loaded: bool,
         should_be_loaded: bool,
     ) {
+        let chunk_sender = &mut self.players[player_idx].chunk_sender;
         if was_loaded && !should_be_loaded {
             // let unload_chunk = CUnloadChunk { chunk_x, chunk_z }.encode();
             // self.players[player_idx].client.send_packet(&unload_chunk);
+            chunk_sender.unqueue(chunk_x, chunk_z);
         } else if !was_loaded && should_be_loaded {
-            if !Plot::chunk_in_plot_bounds(self.world.x, self.world.z, chunk_x, chunk_z) {
-                self.players[player_idx]
-                    .client
-                    .send_packet(&Chunk::encode_empty_packet(chunk_x, chunk_z, PLOT_SECTIONS));
-            } else {
-                let chunk_data = self.world.chunks
-                    [self.world.get_chunk_index_for_chunk(chunk_x, chunk_z)]
-                .encode_packet();
-                self.players[player_idx].client.send_packet(&chunk_data);
-            }
+            chunk_sender.queue(chunk_x, chunk_z);
         }
     }
 
@@ -515,6 +744,12 @@ impl Plot {
             return;
         }
 
+        // Brushes are applied when the client follows up with a use item packet
+        if use_item_on.hand == 0 && worldedit::holding_brush(&self.players[player]) {
+            cancel(self);
+            return;
+        }
+
         if let Some(item) = &item_in_hand {
             let has_permission = self.players[player].has_permission("worldedit.selection.pos");
             if item.item_type == (Item::WEWand {}) && has_permission {
@@ -529,15 +764,18 @@ impl Plot {
             }
         }
 
-        if let Some(owner) = self.owner {
-            let player = &mut self.players[player];
-            if owner != player.uuid && !player.has_permission("plots.admin.interact.other") {
-                player.send_no_permission_message();
-                cancel(self);
-                return;
-            }
-        } else if !self.players[player].has_permission("plots.admin.interact.unowned") {
-            self.players[player].send_no_permission_message();
+        // Opening containers is allowed separately from changing blocks
+        let opens_container = !self.players[player].crouching
+            && matches!(
+                self.world.get_block_entity(block_pos),
+                Some(BlockEntity::Container { .. })
+            );
+        let permission = if opens_container {
+            PlotPermission::Containers
+        } else {
+            PlotPermission::Build
+        };
+        if !self.check_plot_permission(player, permission) {
             cancel(self);
             return;
         }
@@ -546,6 +784,13 @@ impl Plot {
             let block = self.world.get_block(block_pos);
             let lever_or_button = matches!(block, Block::Lever { .. } | Block::StoneButton { .. });
             if lever_or_button && !self.players[player].crouching {
+                if !self.record_replay_input(ReplayInput::Use { pos: block_pos }) {
+                    self.players[player].send_error_message(
+                        "Inputs are ignored while a replay is playing. To stop it, run `/redpiler stop`.",
+                    );
+                    cancel(self);
+                    return;
+                }
                 self.redpiler.on_use_block(block_pos);
                 self.redpiler.flush(&mut self.world);
                 self.world.flush_block_changes();
@@ -564,6 +809,8 @@ impl Plot {
         }
 
         if let Some(item) = item_in_hand {
+            let offset_pos = block_pos.offset(block_face);
+            let before = [block_pos, offset_pos].map(|pos| self.world.get_block_raw(pos));
             let cancelled = interaction::use_item_on_block(
                 &item,
                 &mut self.world,
@@ -578,6 +825,20 @@ impl Plot {
                 cancel(self);
             }
             self.world.flush_block_changes();
+            // The block is placed either in place of the clicked block or next to it. Blocks that
+            // only changed their state, like a repeater changing its delay, weren't placed.
+            for (pos, before) in [block_pos, offset_pos].into_iter().zip(before) {
+                let after = self.world.get_block_raw(pos);
+                if Block::from_id(after).get_name() != Block::from_id(before).get_name()
+                    && after != 0
+                {
+                    self.dispatch_plugin_event(Event::BlockPlace(BlockPlace {
+                        username: self.players[player].username.clone(),
+                        pos,
+                        block: after,
+                    }));
+                }
+            }
             return;
         }
 
@@ -621,15 +882,7 @@ impl Plot {
             }
         }
 
-        if let Some(owner) = self.owner {
-            let player = &mut self.players[player];
-            if owner != player.uuid && !player.has_permission("plots.admin.interact.other") {
-                player.send_no_permission_message();
-                self.send_block_change(block_pos, block.get_id());
-                return;
-            }
-        } else if !self.players[player].has_permission("plots.admin.interact.unowned") {
-            self.players[player].send_no_permission_message();
+        if !self.check_plot_permission(player, PlotPermission::Build) {
             self.send_block_change(block_pos, block.get_id());
             return;
         }
@@ -647,6 +900,11 @@ impl Plot {
 
         interaction::destroy(block, &mut self.world, block_pos);
         self.world.flush_block_changes();
+        self.dispatch_plugin_event(Event::BlockBreak(BlockBreak {
+            username: self.players[player].username.clone(),
+            pos: block_pos,
+            block: block.get_id(),
+        }));
 
         let effect = CWorldEvent {
             event: 2001,
@@ -675,25 +933,60 @@ impl Plot {
         self.timings.reset_timings();
     }
 
+    /// Lists the problems redpiler found while compiling, each of which teleports to where it
+    /// was found when clicked
+    fn send_redpiler_warnings(&self, warnings: &[(BlockPos, String)]) {
+        if warnings.is_empty() {
+            return;
+        }
+        let header = format!("Redpiler found {} warning(s):", warnings.len());
+        for player in &self.players {
+            player.send_error_message(&header);
+            for (pos, message) in warnings {
+                player.send_raw_system_message(
+                    TextComponentBuilder::new(format!(
+                        "{} at {}, {}, {}",
+                        message, pos.x, pos.y, pos.z
+                    ))
+                    .color_code(ColorCode::Gold)
+                    .click_event(ClickEvent::run_command(format!(
+                        "/tp {} {} {}",
+                        pos.x, pos.y, pos.z
+                    )))
+                    .hover_text("Click to teleport")
+                    .finish(),
+                );
+            }
+        }
+    }
+
     fn start_redpiler(&mut self, options: CompilerOptions) {
         debug!("Starting redpiler");
         self.scoreboard
             .set_redpiler_state(&self.players, RedpilerState::Compiling);
         self.scoreboard
             .set_redpiler_options(&self.players, &options);
+        let (optimize, io_only) = (options.optimize, options.io_only);
+        self.dispatch_plugin_event(Event::CompileStart(CompileStart {
+            x: self.world.x,
+            z: self.world.z,
+            flags: options.to_flags(),
+        }));
 
         let bounds = self.world.get_corners();
-        // TODO: use monitor
-        let monitor = Default::default();
+        let monitor: Arc<TaskMonitor> = Default::default();
         let ticks = self.world.to_be_ticked.drain(..).collect();
 
         let mut players_need_updates = HashSet::new();
+        let start_time = Instant::now();
+        let mut last_progress_update = start_time;
         thread::scope(|s| {
             let handle = s.spawn(|| {
                 self.redpiler
-                    .compile(&self.world, bounds, options, ticks, monitor)
+                    .compile(&self.world, bounds, options, ticks, monitor.clone())
             });
             while !handle.is_finished() {
+                self.watchdog.beat();
                 // We'll update the players so that they don't time out.
                 for player_idx in 0..self.players.len() {
                     if self.players[player_idx].update() {
@@ -703,6 +996,14 @@ impl Plot {
                         players_need_updates.insert(player_idx);
                     }
                 }
+                if last_progress_update.elapsed() > Duration::from_millis(500) {
+                    last_progress_update = Instant::now();
+                    let progress =
+                        format!("Compiling... ({:.1}s)", start_time.elapsed().as_secs_f32());
+                    for player in &self.players {
+                        player.send_action_bar(TextComponent::from(progress.as_str()));
+                    }
+                }
                 thread::sleep(Duration::from_millis(20));
             }
         });
@@ -714,6 +1015,20 @@ impl Plot {
 
         self.scoreboard
             .set_redpiler_state(&self.players, RedpilerState::Running);
+        let compiled = format!("Compiled in {:.2}s", start_time.elapsed().as_secs_f32());
+        for player in &self.players {
+            player.send_action_bar(TextComponent::from(compiled.as_str()));
+        }
+        let warnings = monitor.warnings();
+        self.send_redpiler_warnings(&warnings);
+        self.dispatch_plugin_event(Event::CompileFinish(CompileFinish {
+            x: self.world.x,
+            z: self.world.z,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            warnings: warnings.len(),
+            optimize,
+            io_only,
+        }));
 
         self.reset_timings();
     }
@@ -721,6 +1036,7 @@ impl Plot {
     /// Redpiler needs to reset implicitly in the case of any block changes done by a player. This
     /// can be
     fn reset_redpiler(&mut self) {
+        self.stop_replay();
         if self.redpiler.is_active() {
             debug!("Discarding redpiler");
             let bounds = self.world.get_corners();
@@ -748,16 +1064,23 @@ impl Plot {
     fn leave_plot(&mut self, uuid: u128) -> Player {
         let player_idx = self.players.iter().position(|p| p.uuid == uuid).unwrap();
         self.world.packet_senders.remove(player_idx);
-        let player = self.players.remove(player_idx);
+        let mut player = self.players.remove(player_idx);
+        worldedit::cancel_player_job(&self.world, &mut player);
+        player.chunk_sender.clear();
 
         let destroy_other_entities = CRemoveEntities {
-            entity_ids: self.players.iter().map(|p| p.entity_id as i32).collect(),
+            entity_ids: self
+                .players
+                .iter()
+                .map(|p| p.entity_id as i32)
+                .chain(self.world.item_frames.iter().map(|f| f.entity_id as i32))
+                .collect(),
         }
         .encode();
         player.client.send_packet(&destroy_other_entities);
 
-        let chunk_offset_x = self.world.x << PLOT_SCALE;
-        let chunk_offset_z = self.world.z << PLOT_SCALE;
+        let chunk_offset_x = self.world.x << *PLOT_SCALE;
+        let chunk_offset_z = self.world.z << *PLOT_SCALE;
         for chunk in &self.world.chunks {
             player.client.send_packet(
                 &CUnloadChunk {
@@ -774,7 +1097,7 @@ impl Plot {
     }
 
     fn chunk_in_plot_bounds(plot_x: i32, plot_z: i32, chunk_x: i32, chunk_z: i32) -> bool {
-        let (x, z) = (chunk_x >> PLOT_SCALE, chunk_z >> PLOT_SCALE);
+        let (x, z) = (chunk_x >> *PLOT_SCALE, chunk_z >> *PLOT_SCALE);
         plot_x == x && plot_z == z
     }
 
@@ -785,16 +1108,26 @@ impl Plot {
     pub fn claim_plot(&mut self, plot_x: i32, plot_z: i32, player: usize) {
         let player = &mut self.players[player];
         database::claim_plot(plot_x, plot_z, &format!("{:032x}", player.uuid));
+        if (plot_x, plot_z) == (self.world.x, self.world.z) {
+            self.access.owner = Some(player.uuid);
+        }
         let center = Plot::get_center(plot_x, plot_z);
         player.teleport(PlayerPos::new(center.0, 64.0, center.1));
         player.send_system_message(&format!("Claimed plot {},{}", plot_x, plot_z));
+        let _ = self
+            .message_sender
+            .send(Message::Webhook(WebhookEvent::PlotClaimed {
+                player: player.username.clone(),
+                plot_x,
+                plot_z,
+            }));
     }
 
     pub fn get_center(plot_x: i32, plot_z: i32) -> (f64, f64) {
-        const WIDTH: f64 = PLOT_BLOCK_WIDTH as f64;
+        let width = *PLOT_BLOCK_WIDTH as f64;
         (
-            plot_x as f64 * WIDTH + WIDTH / 2.0,
-            plot_z as f64 * WIDTH + WIDTH / 2.0,
+            plot_x as f64 * width + width / 2.0,
+            plot_z as f64 * width + width / 2.0,
         )
     }
 
@@ -835,6 +1168,10 @@ impl Plot {
             let player_idx = player_idx - removal_offset;
             let commands: Vec<String> = self.players[player_idx].command_queue.drain(..).collect();
             for command in commands {
+                self.dispatch_plugin_event(Event::CommandRun(CommandRun {
+                    username: self.players[player_idx].username.clone(),
+                    command: command.clone(),
+                }));
                 let mut args: Vec<&str> = command.split(' ').collect();
                 let command = args.remove(0);
                 if self.handle_command(player_idx, command, args) {
@@ -847,9 +1184,20 @@ impl Plot {
     fn handle_messages(&mut self) {
         while let Ok(message) = self.message_receiver.try_recv() {
             match message {
-                BroadcastMessage::Chat(_sender, message) => {
+                BroadcastMessage::Chat(sender, message) => {
                     for player in &mut self.players {
-                        player.send_chat_message(&message);
+                        if !player.ignored_players.contains(&sender) {
+                            player.send_chat_message(&message);
+                        }
+                    }
+                }
+                BroadcastMessage::StaffChat(sender, message) => {
+                    for player in &self.players {
+                        if player.has_permission("mchprs.chat.staff")
+                            && !player.ignored_players.contains(&sender)
+                        {
+                            player.send_chat_message(&message);
+                        }
                     }
                 }
                 BroadcastMessage::PlayerJoinedInfo(player_join_info) => {
@@ -881,6 +1229,11 @@ impl Plot {
                     }
                 }
                 BroadcastMessage::Shutdown => {
+                    // Redpiler keeps the state of the circuit to itself, so it is written back
+                    // into the world along with the ticks it scheduled, and the plot is compiled
+                    // again when it is loaded
+                    self.resume_redpiler = self.redpiler.current_flags().map(|o| o.to_flags());
+                    self.reset_redpiler();
                     let mut players: Vec<Player> = self.players.drain(..).collect();
                     for player in players.iter_mut() {
                         player.save();
@@ -890,6 +1243,51 @@ impl Plot {
                     self.running = false;
                     return;
                 }
+                BroadcastMessage::AdminAlert(alert) => {
+                    for player in &self.players {
+                        if player.has_permission("mchprs.alerts") {
+                            player.send_error_message(&alert);
+                        }
+                    }
+                }
+                BroadcastMessage::Transfer(username, host, port) => {
+                    for player in &self.players {
+                        if username
+                            .as_ref()
+                            .is_none_or(|name| *name == player.username)
+                        {
+                            player.transfer(&host, port);
+                        }
+                    }
+                }
+                BroadcastMessage::Notify(username, notification) => {
+                    for player in &self.players {
+                        if username
+                            .as_ref()
+                            .is_none_or(|name| *name == player.username)
+                        {
+                            player.notify(&notification);
+                        }
+                    }
+                }
+                BroadcastMessage::ReloadPermissions => {
+                    for player in &mut self.players {
+                        player.reload_permissions();
+                    }
+                }
+                BroadcastMessage::Save => {
+                    self.save();
+                    for player in &self.players {
+                        player.save();
+                    }
+                }
+                BroadcastMessage::Kick(username, reason) => {
+                    for player in &self.players {
+                        if player.username == username {
+                            player.kick(reason.as_str().into());
+                        }
+                    }
+                }
                 BroadcastMessage::PlayerUpdateGamemode(uuid, gamemode) => {
                     let player_info = CPlayerInfoUpdate {
                         players: vec![CPlayerInfoUpdatePlayer {
@@ -919,6 +1317,36 @@ impl Plot {
                     }
                     self.enter_plot(player);
                 }
+                PrivMessage::ClipboardShared(uuid, username, clipboard) => {
+                    if let Some(player) = self.players.iter_mut().find(|p| p.uuid == uuid) {
+                        player.send_worldedit_message(&format!(
+                            "{} shared their clipboard with you. Use //clipboard accept to replace your clipboard with it.",
+                            username
+                        ));
+                        player.worldedit_shared_clipboard = Some((username, clipboard));
+                    }
+                }
+                PrivMessage::PrivateChat(uuid, sender, username, message) => {
+                    if let Some(player) = self.players.iter_mut().find(|p| p.uuid == uuid) {
+                        if player.ignored_players.contains(&sender) {
+                            continue;
+                        }
+                        player.send_raw_system_message(server::private_chat_message(
+                            &format!("{} -> me", username),
+                            &username,
+                            &message,
+                        ));
+                        player.reply_to = Some(username);
+                    }
+                }
+                PrivMessage::Compile(flags) => {
+                    let flags = match flags.as_str() {
+                        "" => self.settings.compiler_flags.clone(),
+                        flags => flags.to_string(),
+                    };
+                    self.reset_redpiler();
+                    self.start_redpiler(CompilerOptions::parse(&flags));
+                }
             }
         }
     }
@@ -947,11 +1375,14 @@ impl Plot {
     /// Remove disconnected players
     fn remove_dc_players(&mut self) {
         let message_sender = &mut self.message_sender;
+        let world = &self.world;
 
         let mut disconnected_players = Vec::new();
-        self.players.retain(|player| {
+        self.players.retain_mut(|player| {
             let alive = player.client.alive();
             if !alive {
+                // Finishes the history of a running job so that it is saved
+                worldedit::cancel_player_job(world, player);
                 player.save();
                 message_sender
                     .send(Message::PlayerLeft(player.uuid))
@@ -971,6 +1402,9 @@ impl Plot {
             if self.players[player_idx].update() {
                 self.update_view_pos_for_player(player_idx, false);
             }
+            self.send_chunk_batch(player_idx);
+            worldedit::visualize(&mut self.players[player_idx]);
+            worldedit::run_job(self, player_idx);
         }
         // Handle received packets
         for player_idx in 0..self.players.len() {
@@ -979,7 +1413,9 @@ impl Plot {
     }
 
     fn update(&mut self) {
+        self.watchdog.beat();
         self.handle_messages();
+        self.run_plugin_tasks();
 
         // Only tick if there are players in the plot
         if !self.players.is_empty() {
@@ -1032,13 +1468,15 @@ impl Plot {
                     }
                 }
                 self.last_nspt = Some(self.last_update_time.elapsed() / ticks_completed);
+                self.check_output_watches();
             }
 
             if self.auto_redpiler
                 && !self.redpiler.is_active()
                 && (self.tps == Tps::Unlimited || self.timings.is_running_behind())
             {
-                self.start_redpiler(Default::default());
+                let options = CompilerOptions::parse(&self.settings.compiler_flags);
+                self.start_redpiler(options);
             }
 
             let now = Instant::now();
@@ -1047,6 +1485,12 @@ impl Plot {
                 self.last_world_send_time = now;
                 self.world.flush_block_changes();
             }
+
+            if self.watchdog.autosave_due() {
+                self.save();
+            }
+
+            self.refresh_tab_list();
         } else {
             self.timings.set_ticking(false);
             // Unload plot after 600 seconds unless the plot should be always loaded
@@ -1069,31 +1513,6 @@ impl Plot {
         Runtime::new().unwrap()
     }
 
-    fn generate_chunk(layers: i32, x: i32, z: i32) -> Chunk {
-        let mut chunk = Chunk::empty(x, z, PLOT_SECTIONS);
-
-        for ry in 0..layers {
-            for rx in 0..16 {
-                for rz in 0..16 {
-                    let block_x = (x << 4) | rx;
-                    let block_z = (z << 4) | rz;
-
-                    let block = if block_x % PLOT_BLOCK_WIDTH == 0
-                        || block_z % PLOT_BLOCK_WIDTH == 0
-                        || (block_x + 1) % PLOT_BLOCK_WIDTH == 0
-                        || (block_z + 1) % PLOT_BLOCK_WIDTH == 0
-                    {
-                        Block::StoneBricks {}
-                    } else {
-                        Block::Sandstone {}
-                    };
-                    chunk.set_block(rx as u32, ry as u32, rz as u32, block.get_id());
-                }
-            }
-        }
-        chunk
-    }
-
     fn from_data(
         plot_data: PlotData,
         x: i32,
@@ -1102,38 +1521,55 @@ impl Plot {
         tx: Sender<Message>,
         priv_rx: Receiver<PrivMessage>,
         always_running: bool,
-    ) -> Plot {
-        let chunk_x_offset = x << PLOT_SCALE;
-        let chunk_z_offset = z << PLOT_SCALE;
-        let chunks: Vec<Chunk> = plot_data
-            .chunk_data
-            .into_iter()
-            .enumerate()
-            .map(|(i, c)| {
-                c.load(
-                    chunk_x_offset + i as i32 / PLOT_WIDTH,
-                    chunk_z_offset + i as i32 % PLOT_WIDTH,
-                )
-            })
-            .collect();
-        if chunks.len() != NUM_CHUNKS {
+    ) -> Result<Plot, (Error, Sender<Message>)> {
+        let chunks = match data::load_chunks(plot_data.chunk_data, x, z) {
+            Ok(chunks) => chunks,
+            Err(err) => return Err((err, tx)),
+        };
+        if chunks.len() != *NUM_CHUNKS {
             error!("This plot has the wrong number of chunks!");
             let possible_scale = (chunks.len() as f64).sqrt().log2();
-            error!("Note: it most likely came from a server running plot scale {}, this server is running a plot scale of {}", possible_scale, PLOT_SCALE);
+            error!("Note: it most likely came from a server running plot scale {}, this server is running a plot scale of {}", possible_scale, *PLOT_SCALE);
         }
+        let settings = plot_data.settings;
         let world = PlotWorld {
             x,
             z,
             chunks,
             to_be_ticked: plot_data.pending_ticks,
             packet_senders: Vec::new(),
+            quasi_connectivity: settings
+                .quasi_connectivity
+                .unwrap_or(CONFIG.quasi_connectivity),
+            torch_burnout: CONFIG.torch_burnout.then(TorchBurnout::default),
+            strict_update_order: settings
+                .strict_update_order
+                .unwrap_or(CONFIG.strict_update_order),
+            block_events: Vec::new(),
+            item_frames: plot_data
+                .item_frames
+                .into_iter()
+                .map(|frame| ItemFrame {
+                    entity_id: player::next_entity_id(),
+                    ..frame
+                })
+                .collect(),
         };
-        let tps = plot_data.tps;
+        let mut tps = plot_data.tps;
+        if let Err(max_tps) = threads::set_plot_tps(x, z, tps) {
+            info!(
+                "Plot {},{} is limited to {} rtps because too many plots run at a high rtps",
+                x, z, max_tps
+            );
+            tps = max_tps;
+            threads::set_plot_tps(x, z, tps).unwrap();
+        }
         let world_send_rate = plot_data.world_send_rate;
-        Plot {
+        Ok(Plot {
             last_player_time: Instant::now(),
             last_update_time: Instant::now(),
             last_world_send_time: Instant::now(),
+            last_tab_list_refresh: Instant::now(),
             lag_time: Duration::new(0, 0),
             sleep_time: sleep_time_for_tps(tps),
             last_nspt: None,
@@ -1143,17 +1579,29 @@ impl Plot {
             players: Vec::new(),
             locked_players: HashSet::new(),
             running: true,
-            auto_redpiler: CONFIG.auto_redpiler,
+            auto_redpiler: settings.auto_redpiler.unwrap_or(CONFIG.auto_redpiler),
+            settings,
+            resume_redpiler: plot_data.redpiler_flags,
             tps,
             world_send_rate,
             always_running,
             redpiler: Default::default(),
             timings: TimingsMonitor::new(tps),
-            owner: database::get_plot_owner(x, z).map(|s| s.parse::<HyphenatedUUID>().unwrap().0),
+            access: PlotAccess {
+                owner: database::get_plot_owner(x, z)
+                    .map(|s| s.parse::<HyphenatedUUID>().unwrap().0),
+                members: plot_data.members,
+                denied: plot_data.denied,
+            },
             async_rt: Plot::create_async_rt(),
             scoreboard: Default::default(),
+            thread: PlotThread::new(x, z),
+            watchdog: PlotWatchdog::new(x, z),
+            crashed: false,
+            output_watches: Vec::new(),
+            replay: None,
             world,
-        }
+        })
     }
 
     fn load(
@@ -1165,9 +1613,9 @@ impl Plot {
         always_running: bool,
     ) -> Result<Plot, (Error, Sender<Message>)> {
         let plot_path = format!("./world/plots/p{},{}", x, z);
-        Ok(if Path::new(&plot_path).exists() {
+        let plot_data = if Path::new(&plot_path).exists() {
             match data::load_plot(plot_path) {
-                Ok(data) => Plot::from_data(data, x, z, rx, tx, priv_rx, always_running),
+                Ok(data) => data,
                 Err(err) => {
                     return Result::Err((
                         err.context(format!("error loading plot {},{}", x, z)),
@@ -1176,8 +1624,10 @@ impl Plot {
                 }
             }
         } else {
-            Plot::from_data(data::empty_plot(), x, z, rx, tx, priv_rx, always_running)
-        })
+            data::empty_plot()
+        };
+        Plot::from_data(plot_data, x, z, rx, tx, priv_rx, always_running)
+            .map_err(|(err, tx)| (err.context(format!("error loading plot {},{}", x, z)), tx))
     }
 
     fn save(&mut self) {
@@ -1188,24 +1638,48 @@ impl Plot {
             world_send_rate: self.world_send_rate,
             chunk_data,
             pending_ticks: world.to_be_ticked.clone(),
+            item_frames: world.item_frames.clone(),
+            members: self.access.members.clone(),
+            denied: self.access.denied.clone(),
+            settings: self.settings.clone(),
+            redpiler_flags: self.resume_redpiler.clone(),
         };
-        data.save_to_file(format!("./world/plots/p{},{}", world.x, world.z))
-            .unwrap();
+        data.save_to_file(
+            format!("./world/plots/p{},{}", world.x, world.z),
+            compression_level(),
+        )
+        .unwrap();
 
         self.reset_timings();
     }
 
     fn run(&mut self, initial_player: Option<Player>) {
         let _guard = self.async_rt.enter();
+        self.thread.configure();
+
+        self.dispatch_plugin_event(Event::PlotLoad(PlotLoad {
+            x: self.world.x,
+            z: self.world.z,
+        }));
+        let chunks: Vec<_> = self.world.chunks.iter().map(|c| (c.x, c.z)).collect();
+        for (x, z) in chunks {
+            self.dispatch_plugin_event(Event::ChunkLoad(ChunkLoad { x, z }));
+        }
 
         if let Some(player) = initial_player {
             self.enter_plot(player);
         }
 
+        if let Some(flags) = self.resume_redpiler.take() {
+            self.start_redpiler(CompilerOptions::parse(&flags));
+        }
+
         while self.running {
             // Fast path, for super high RTPS
             if self.sleep_time <= Duration::from_millis(5) && !self.players.is_empty() {
+                let before = Instant::now();
                 self.update();
+                self.thread.record_update(before.elapsed());
                 if self.tps != Tps::Unlimited {
                     thread::yield_now();
                 }
@@ -1215,6 +1689,7 @@ impl Plot {
             let before = Instant::now();
             self.update();
             let delta = Instant::now().duration_since(before);
+            self.thread.record_update(delta);
 
             if delta < self.sleep_time {
                 let sleep_time = self.sleep_time - delta;
@@ -1236,7 +1711,7 @@ impl Plot {
         } else {
             Plot::get_center(0, 0)
         };
-        player.teleport(PlayerPos::new(px, 64.0, pz));
+        player.move_to(PlayerPos::new(px, 64.0, pz));
     }
 
     pub fn load_and_run(
@@ -1252,7 +1727,14 @@ impl Plot {
             .name(format!("p{},{}", x, z))
             .spawn(
                 move || match Plot::load(x, z, rx, tx, priv_rx, always_running) {
-                    Ok(mut plot) => plot.run(initial_player),
+                    Ok(mut plot) => {
+                        // A panic only takes down this plot, which is then restored by the watchdog
+                        let result =
+                            panic::catch_unwind(AssertUnwindSafe(|| plot.run(initial_player)));
+                        if result.is_err() {
+                            plot.handle_crash();
+                        }
+                    }
                     Err((err, tx)) => {
                         if let Some(mut player) = initial_player {
                             player.send_error_message("There was an error loading that plot.");
@@ -1270,6 +1752,14 @@ impl Plot {
 
 impl Drop for Plot {
     fn drop(&mut self) {
+        if self.crashed {
+            // The watchdog already took care of the players and the server
+            return;
+        }
+        self.dispatch_plugin_event(Event::PlotUnload(PlotUnload {
+            x: self.world.x,
+            z: self.world.z,
+        }));
         if !self.players.is_empty() {
             for player in &mut self.players {
                 player.save(); // just in case
@@ -1288,28 +1778,35 @@ impl Drop for Plot {
                     .unwrap();
             }
         }
-        let world = &self.world;
-        self.message_sender
-            .send(Message::PlotUnload(world.x, world.z))
-            .unwrap();
-
         self.reset_redpiler();
         self.world
             .chunks
             .iter_mut()
             .for_each(|chunk| chunk.compress());
+        // The server exits once every plot unloaded when it shuts down, so the plot has to be
+        // saved before that
         self.save();
+
+        let world = &self.world;
+        threads::remove_plot(world.x, world.z);
+        watchdog::remove_plot(world.x, world.z);
+        self.message_sender
+            .send(Message::PlotUnload(world.x, world.z))
+            .unwrap();
     }
 }
 
 #[test]
 fn chunk_save_and_load_test() {
-    let mut chunk = Chunk::empty(1, 1, PLOT_SECTIONS);
+    let mut chunk = Chunk::empty(1, 1, 24).with_min_y(-64);
     chunk.set_block(13, 63, 12, 332);
     chunk.set_block(13, 62, 12, 331);
+    chunk.set_block(13, -50, 12, 333);
     let chunk_data = ChunkData::new(&mut chunk);
     let loaded_chunk = chunk_data.load(1, 1);
+    assert_eq!(loaded_chunk.min_y, -64);
     assert_eq!(loaded_chunk.get_block(13, 63, 12), 332);
     assert_eq!(loaded_chunk.get_block(13, 62, 12), 331);
+    assert_eq!(loaded_chunk.get_block(13, -50, 12), 333);
     assert_eq!(loaded_chunk.get_block(13, 64, 12), 0);
 }
@@ -1,8 +1,7 @@
-use super::Plot;
+use super::{worldedit, Plot};
 use crate::config::CONFIG;
 use crate::player::{PacketSender, PlayerPos, SkinParts};
-use crate::server::Message;
-use crate::utils::{self, HyphenatedUUID};
+use crate::utils;
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::items::{Item, ItemStack};
 use mchprs_blocks::BlockPos;
@@ -10,10 +9,7 @@ use mchprs_network::packets::clientbound::*;
 use mchprs_network::packets::serverbound::*;
 use mchprs_world::World;
 use serde_json::json;
-use std::fs;
-use std::path::PathBuf;
 use std::time::Instant;
-use tracing::error;
 
 impl Plot {
     pub(super) fn handle_packets_for_player(&mut self, player: usize) {
@@ -30,57 +26,19 @@ impl ServerBoundPacketHandler for Plot {
         packet: SCommandSuggestionsRequest,
         player_idx: usize,
     ) {
-        if !packet.text.starts_with("//load ") {
-            return;
-        }
-
-        let mut path = PathBuf::from("./schems");
-        if CONFIG.schemati {
-            let uuid = self.players[player_idx].uuid;
-            path.push(HyphenatedUUID(uuid).to_string());
-        }
-
-        let current = &packet.text[7..];
-        let mut res = CCommandSuggestionsResponse {
-            id: packet.transaction_id,
-            start: 7,
-            length: current.len() as i32,
-            matches: Vec::new(),
-        };
-
-        let dir = match fs::read_dir(path) {
-            Ok(dir) => dir,
-            Err(err) => {
-                if err.kind() != std::io::ErrorKind::NotFound {
-                    error!("There was an error completing //load");
-                    error!("{}", err);
-                }
-                return;
-            }
-        };
-
-        for entry in dir {
-            let entry = entry.unwrap();
-            if entry.file_type().unwrap().is_file() {
-                let name = entry.file_name();
-                let name = name.to_string_lossy();
-                if name.starts_with(current) {
-                    let m = CCommandSuggestionsResponseMatch {
-                        match_: name.to_string(),
-                        tooltip: None,
-                    };
-                    res.matches.push(m);
-                }
-            }
-        }
-
-        self.players[player_idx].send_packet(&res.encode());
+        self.send_command_suggestions(player_idx, packet.transaction_id, &packet.text);
     }
 
     fn handle_keep_alive(&mut self, _keep_alive: SKeepAlive, player_idx: usize) {
         self.players[player_idx].last_keep_alive_received = Instant::now();
     }
 
+    fn handle_chunk_batch_received(&mut self, packet: SChunkBatchReceived, player_idx: usize) {
+        self.players[player_idx]
+            .chunk_sender
+            .on_batch_received(packet.chunks_per_tick);
+    }
+
     fn handle_set_creative_mode_slot(
         &mut self,
         creative_inventory_action: SSetCreativeModeSlot,
@@ -164,6 +122,18 @@ impl ServerBoundPacketHandler for Plot {
         self.players[player].send_packet(&acknowledge_block_change);
     }
 
+    fn handle_use_item(&mut self, use_item: SUseItem, player: usize) {
+        if use_item.hand == 0 {
+            worldedit::use_brush(self, player);
+        }
+
+        let acknowledge_block_change = CAcknowledgeBlockChange {
+            sequence_id: use_item.sequence,
+        }
+        .encode();
+        self.players[player].send_packet(&acknowledge_block_change);
+    }
+
     fn handle_chat_command(&mut self, chat_command: SChatCommand, player: usize) {
         self.players[player]
             .command_queue
@@ -171,10 +141,8 @@ impl ServerBoundPacketHandler for Plot {
     }
 
     fn handle_chat_message(&mut self, chat_message: SChatMessage, player: usize) {
-        let message = chat_message.message;
-        let player = &self.players[player];
-        let broadcast_message = Message::ChatInfo(player.uuid, player.username.clone(), message);
-        self.message_sender.send(broadcast_message).unwrap();
+        let channel = self.players[player].chat_channel;
+        self.send_chat(player, channel, chat_message.message);
     }
 
     fn handle_client_information(&mut self, client_settings: SClientInformation, player: usize) {
@@ -198,7 +166,8 @@ impl ServerBoundPacketHandler for Plot {
 
     fn handle_plugin_message(&mut self, plugin_message: SPluginMessage, player: usize) {
         if plugin_message.channel == "worldedit:cui" {
-            self.players[player].worldedit_send_cui("s|cuboid");
+            let mode = self.players[player].worldedit_selection_mode;
+            self.players[player].worldedit_send_cui(&format!("s|{}", mode.cui_name()));
         }
     }
 
@@ -406,6 +375,16 @@ impl ServerBoundPacketHandler for Plot {
         }
     }
 
+    fn handle_resource_pack_response(&mut self, response: SResourcePackResponse, player: usize) {
+        let forced = CONFIG
+            .resource_pack
+            .as_ref()
+            .is_some_and(|pack| pack.forced);
+        if forced && response.result.is_failure() {
+            self.players[player].kick("This server requires its resource pack.".into());
+        }
+    }
+
     fn handle_set_held_item(&mut self, held_item_change: SSetHeldItem, player: usize) {
         let entity_equipment = CSetEquipment {
             entity_id: self.players[player].entity_id as i32,
@@ -0,0 +1,236 @@
+use super::replay::ReplayInput;
+use super::{Plot, PLOT_MAX_Y, PLOT_MIN_Y};
+use crate::player::PacketSender;
+use crate::plugins;
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::{BlockDirection, BlockFace, BlockPos};
+use mchprs_plugin::events::OutputChange;
+use mchprs_plugin::{CommandSender, CommandSpec, Event, PlotHandle, Plugin};
+use mchprs_text::TextComponent;
+use mchprs_world::World;
+use serde_json::Value;
+
+impl PlotHandle for Plot {
+    fn x(&self) -> i32 {
+        self.world.x
+    }
+
+    fn z(&self) -> i32 {
+        self.world.z
+    }
+
+    fn get_block(&self, pos: BlockPos) -> Option<u32> {
+        self.in_bounds(pos).then(|| self.world.get_block_raw(pos))
+    }
+
+    fn set_block(&mut self, pos: BlockPos, block: u32) -> bool {
+        if !self.in_bounds(pos) {
+            return false;
+        }
+        // Redpiler wouldn't know about the change
+        self.reset_redpiler();
+        self.world.set_block_raw(pos, block);
+        true
+    }
+
+    fn use_block(&mut self, pos: BlockPos) -> bool {
+        if !self.in_bounds(pos) {
+            return false;
+        }
+        let block = self.world.get_block(pos);
+        if !matches!(block, Block::Lever { .. } | Block::StoneButton { .. }) {
+            return false;
+        }
+        if self.redpiler.is_active() {
+            if !self.record_replay_input(ReplayInput::Use { pos }) {
+                return false;
+            }
+            self.redpiler.on_use_block(pos);
+            self.redpiler.flush(&mut self.world);
+        } else {
+            mchprs_redstone::on_use(block, &mut self.world, pos);
+        }
+        true
+    }
+
+    fn read_output(&self, pos: BlockPos) -> Option<u8> {
+        self.redpiler.read_output(pos)
+    }
+
+    fn set_input(&mut self, pos: BlockPos, powered: bool) -> bool {
+        if self.is_replay_playing() || !self.redpiler.set_input(pos, powered) {
+            return false;
+        }
+        self.record_replay_input(ReplayInput::SetInput { pos, powered });
+        self.redpiler.flush(&mut self.world);
+        true
+    }
+
+    fn find_named(&self, name: &str) -> Option<BlockPos> {
+        for chunk in &self.world.chunks {
+            for (&local_pos, block_entity) in &chunk.block_entities {
+                let BlockEntity::Sign(sign) = block_entity else {
+                    continue;
+                };
+                if sign_text(&sign.front_rows[0]).as_deref().map(str::trim) != Some(name) {
+                    continue;
+                }
+                let pos = BlockPos::new(
+                    chunk.x * 16 + local_pos.x,
+                    local_pos.y,
+                    chunk.z * 16 + local_pos.z,
+                );
+                if let Some(target) = self.sign_target(pos) {
+                    return Some(target);
+                }
+            }
+        }
+        None
+    }
+
+    fn watch_output(&mut self, pos: BlockPos) {
+        if !self
+            .output_watches
+            .iter()
+            .any(|(watched, _)| *watched == pos)
+        {
+            let power = self.redpiler.read_output(pos);
+            self.output_watches.push((pos, power));
+        }
+    }
+
+    fn players(&self) -> Vec<String> {
+        self.players
+            .iter()
+            .map(|player| player.username.clone())
+            .collect()
+    }
+
+    fn send_message(&mut self, username: &str, message: &str) {
+        if let Some(player) = self.players.iter().find(|p| p.username == username) {
+            player.send_chat_message(&TextComponent::from_legacy_text(message));
+        }
+    }
+}
+
+impl Plot {
+    fn in_bounds(&self, pos: BlockPos) -> bool {
+        Plot::in_plot_bounds(self.world.x, self.world.z, pos.x, pos.z)
+            && (*PLOT_MIN_Y..*PLOT_MAX_Y).contains(&pos.y)
+    }
+
+    /// The component a sign is placed on. Like redpiler annotations, signs on a wall can also name
+    /// the component above or below the block they hang on.
+    fn sign_target(&self, pos: BlockPos) -> Option<BlockPos> {
+        let targets = match self.world.get_block(pos) {
+            Block::Sign { rotation, .. } => {
+                let facing = BlockDirection::from_rotation(rotation)?;
+                vec![pos.offset(facing.opposite().block_face())]
+            }
+            Block::WallSign { facing, .. } => {
+                let behind = pos.offset(facing.opposite().block_face());
+                vec![
+                    behind,
+                    behind.offset(BlockFace::Top),
+                    behind.offset(BlockFace::Bottom),
+                ]
+            }
+            _ => return None,
+        };
+        targets
+            .into_iter()
+            .find(|&target| is_circuit_component(self.world.get_block(target)))
+    }
+
+    /// Tells plugins about outputs that changed since the last time they were checked
+    pub(super) fn check_output_watches(&mut self) {
+        let mut changes = Vec::new();
+        for (pos, last) in &mut self.output_watches {
+            let power = self.redpiler.read_output(*pos);
+            if let (Some(power), Some(_)) = (power, *last) {
+                if Some(power) != *last {
+                    changes.push(OutputChange { pos: *pos, power });
+                }
+            }
+            *last = power;
+        }
+        for change in changes {
+            self.dispatch_plugin_event(Event::OutputChange(change));
+        }
+    }
+
+    /// Tells plugins about something that happened on this plot and sends the blocks they changed
+    pub(super) fn dispatch_plugin_event(&mut self, event: Event) {
+        plugins::dispatch(&event, Some(self));
+        self.world.flush_block_changes();
+    }
+
+    pub(super) fn run_plugin_tasks(&mut self) {
+        if plugins::run_plot_tasks(self) {
+            self.world.flush_block_changes();
+        }
+    }
+
+    pub(super) fn run_plugin_command(
+        &mut self,
+        plugin: &dyn Plugin,
+        spec: &CommandSpec,
+        player: usize,
+        args: &[&str],
+    ) {
+        if let Some(permission) = &spec.permission {
+            if !self.players[player].has_permission(permission) {
+                self.players[player].send_no_permission_message();
+                return;
+            }
+        }
+        let origin = self.players[player].pos.block_pos();
+        let args = match spec.parse(args, origin) {
+            Ok(args) => args,
+            Err(err) => {
+                self.players[player].send_error_message(&format!(
+                    "{}. Usage: {}",
+                    err,
+                    spec.usage_string()
+                ));
+                return;
+            }
+        };
+        let username = self.players[player].username.clone();
+        let uuid = self.players[player].uuid;
+        plugin.on_command(
+            &spec.name,
+            &args,
+            &mut CommandSender {
+                username: &username,
+                uuid,
+                plot: self,
+            },
+        );
+        self.world.flush_block_changes();
+    }
+}
+
+/// The text of a sign row, which is stored as a json text component
+fn sign_text(row: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(row).ok()?;
+    Some(json.get("text")?.as_str()?.to_owned())
+}
+
+fn is_circuit_component(block: Block) -> bool {
+    matches!(
+        block,
+        Block::Lever { .. }
+            | Block::StoneButton { .. }
+            | Block::StonePressurePlate { .. }
+            | Block::RedstoneLamp { .. }
+            | Block::IronTrapdoor { .. }
+            | Block::NoteBlock { .. }
+            | Block::RedstoneRepeater { .. }
+            | Block::RedstoneComparator { .. }
+            | Block::RedstoneTorch { .. }
+            | Block::RedstoneWallTorch { .. }
+            | Block::RedstoneWire { .. }
+    )
+}
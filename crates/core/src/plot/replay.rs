@@ -0,0 +1,321 @@
+//! Replays record the inputs a compiled plot gets from outside, like players flipping levers and
+//! standing on pressure plates or plugins driving inputs, together with the tick they happened
+//! on. Playing one back compiles the plot with the same flags and feeds the inputs in on the same
+//! ticks, so that a circuit that glitched after hours of use can be run into the same glitch again.
+//! Replays are stored as JSON in `./world/replays/p{x},{z}/`.
+//!
+//! A replay only reproduces a run if it starts from the same build. Recording takes a snapshot of
+//! the plot first when the player is allowed to, which can be restored before playing it back.
+
+use super::Plot;
+use crate::player::PacketSender;
+use anyhow::{bail, Result};
+use mchprs_blocks::BlockPos;
+use mchprs_redpiler::CompilerOptions;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplayInput {
+    /// A lever was flipped or a button pressed
+    Use {
+        pos: BlockPos,
+    },
+    PressurePlate {
+        pos: BlockPos,
+        powered: bool,
+    },
+    /// A plugin or script turned an input on or off
+    SetInput {
+        pos: BlockPos,
+        powered: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplayEvent {
+    /// How many ticks ran since the compile before the input happened
+    pub tick: u64,
+    #[serde(flatten)]
+    pub input: ReplayInput,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Replay {
+    /// The flags the plot was compiled with
+    pub flags: String,
+    /// How many ticks the recording ran for
+    pub ticks: u64,
+    /// The snapshot of the plot taken before recording, if there is one
+    pub snapshot: Option<u32>,
+    pub inputs: Vec<ReplayEvent>,
+}
+
+pub(super) enum ReplayState {
+    Recording {
+        name: String,
+        replay: Replay,
+    },
+    Playing {
+        name: String,
+        replay: Replay,
+        /// The index of the next input to feed in
+        next: usize,
+        tick: u64,
+    },
+}
+
+fn replay_dir(plot_x: i32, plot_z: i32) -> PathBuf {
+    PathBuf::from(format!("./world/replays/p{},{}", plot_x, plot_z))
+}
+
+/// Replay names become file names, so only a few characters are allowed
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn save_replay(plot_x: i32, plot_z: i32, name: &str, replay: &Replay) -> Result<()> {
+    let dir = replay_dir(plot_x, plot_z);
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join(format!("{}.json", name)),
+        serde_json::to_string_pretty(replay)?,
+    )?;
+    Ok(())
+}
+
+fn load_replay(plot_x: i32, plot_z: i32, name: &str) -> Result<Replay> {
+    let path = replay_dir(plot_x, plot_z).join(format!("{}.json", name));
+    let mut replay: Replay = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if replay.inputs.iter().any(|event| event.tick > replay.ticks) {
+        bail!("replay has inputs after its last tick");
+    }
+    // Inputs are fed in order, so edited replays have to be sorted again
+    replay.inputs.sort_by_key(|event| event.tick);
+    Ok(replay)
+}
+
+impl Plot {
+    /// Handles `/redpiler record <name> [flags]`
+    pub(super) fn start_recording(&mut self, player: usize, name: &str, flags: Option<String>) {
+        if !is_valid_name(name) {
+            self.players[player].send_error_message(
+                "Replay names can only have letters, numbers, - and _ and can be up to 64 characters long.",
+            );
+            return;
+        }
+        let flags = flags.unwrap_or_else(|| self.settings.compiler_flags.clone());
+        self.reset_redpiler();
+
+        let snapshot = if self.can_manage_snapshots(player) {
+            let creator = self.players[player].username.clone();
+            match self.create_snapshot(format!("before replay {}", name), creator) {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    error!(
+                        "Failed to create snapshot of plot {},{}: {:?}",
+                        self.world.x, self.world.z, err
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let options = CompilerOptions::parse(&flags);
+        let flags = options.to_flags();
+        self.start_redpiler(options);
+        self.replay = Some(ReplayState::Recording {
+            name: name.to_string(),
+            replay: Replay {
+                flags,
+                snapshot,
+                ..Default::default()
+            },
+        });
+        self.players[player].send_system_message(&format!(
+            "Recording replay {}. Stop with /redpiler stop.",
+            name
+        ));
+    }
+
+    /// Handles `/redpiler replay <name>`
+    pub(super) fn start_replay(&mut self, player: usize, name: &str) {
+        if !is_valid_name(name) {
+            self.players[player].send_error_message("That is not a valid replay name.");
+            return;
+        }
+        let replay = match load_replay(self.world.x, self.world.z, name) {
+            Ok(replay) => replay,
+            Err(err) => {
+                self.players[player]
+                    .send_error_message(&format!("Could not load replay {}: {}", name, err));
+                return;
+            }
+        };
+        self.reset_redpiler();
+        self.start_redpiler(CompilerOptions::parse(&replay.flags));
+        let message = format!(
+            "Playing replay {} with {} inputs over {} ticks.",
+            name,
+            replay.inputs.len(),
+            replay.ticks
+        );
+        self.replay = Some(ReplayState::Playing {
+            name: name.to_string(),
+            replay,
+            next: 0,
+            tick: 0,
+        });
+        self.players[player].send_system_message(&message);
+    }
+
+    /// Ends the recording or playback, which is saved if it was a recording. This happens when
+    /// redpiler resets.
+    pub(super) fn stop_replay(&mut self) {
+        let message = match self.replay.take() {
+            Some(ReplayState::Recording { name, replay }) => {
+                match save_replay(self.world.x, self.world.z, &name, &replay) {
+                    Ok(()) => match replay.snapshot {
+                        Some(id) => format!(
+                            "Saved replay {} of {} ticks. It starts from snapshot #{}.",
+                            name, replay.ticks, id
+                        ),
+                        None => format!("Saved replay {} of {} ticks.", name, replay.ticks),
+                    },
+                    Err(err) => {
+                        error!(
+                            "Failed to save replay {} of plot {},{}: {:?}",
+                            name, self.world.x, self.world.z, err
+                        );
+                        format!("There was an error saving replay {}.", name)
+                    }
+                }
+            }
+            Some(ReplayState::Playing { name, tick, .. }) => {
+                format!("Stopped replay {} after {} ticks.", name, tick)
+            }
+            None => return,
+        };
+        for player in &self.players {
+            player.send_system_message(&message);
+        }
+    }
+
+    /// Records an input if a replay is being recorded. Returns false if a replay is playing, in
+    /// which case inputs from outside are ignored so that they don't change the outcome.
+    pub(super) fn record_replay_input(&mut self, input: ReplayInput) -> bool {
+        match &mut self.replay {
+            Some(ReplayState::Recording { replay, .. }) => {
+                let tick = replay.ticks;
+                replay.inputs.push(ReplayEvent { tick, input });
+                true
+            }
+            Some(ReplayState::Playing { .. }) => false,
+            None => true,
+        }
+    }
+
+    /// Inputs from outside are ignored while a replay is playing
+    pub(super) fn is_replay_playing(&self) -> bool {
+        matches!(self.replay, Some(ReplayState::Playing { .. }))
+    }
+
+    /// Runs `ticks` redpiler ticks, stopping to feed in the inputs of a replay that is playing
+    pub(super) fn replay_tickn(&mut self, mut ticks: u64) {
+        while ticks > 0 {
+            self.feed_replay_inputs();
+            let batch = match &self.replay {
+                Some(ReplayState::Playing {
+                    replay, next, tick, ..
+                }) => {
+                    let until = replay.inputs.get(*next).map_or(replay.ticks, |e| e.tick);
+                    until.saturating_sub(*tick).clamp(1, ticks)
+                }
+                _ => ticks,
+            };
+            self.redpiler.tickn(batch);
+            ticks -= batch;
+
+            match &mut self.replay {
+                Some(ReplayState::Recording { replay, .. }) => replay.ticks += batch,
+                Some(ReplayState::Playing { tick, .. }) => *tick += batch,
+                None => {}
+            }
+            if let Some(ReplayState::Playing {
+                name, replay, tick, ..
+            }) = &self.replay
+            {
+                if *tick >= replay.ticks {
+                    let message = format!("Replay {} finished after {} ticks.", name, tick);
+                    for player in &self.players {
+                        player.send_system_message(&message);
+                    }
+                    self.replay = None;
+                }
+            }
+        }
+    }
+
+    fn feed_replay_inputs(&mut self) {
+        let Some(ReplayState::Playing {
+            replay, next, tick, ..
+        }) = &mut self.replay
+        else {
+            return;
+        };
+        while let Some(event) = replay.inputs.get(*next).filter(|e| e.tick <= *tick) {
+            match event.input {
+                ReplayInput::Use { pos } => self.redpiler.on_use_block(pos),
+                ReplayInput::PressurePlate { pos, powered } => {
+                    self.redpiler.set_pressure_plate(pos, powered)
+                }
+                ReplayInput::SetInput { pos, powered } => {
+                    self.redpiler.set_input(pos, powered);
+                }
+            }
+            *next += 1;
+        }
+    }
+}
+
+#[test]
+fn replay_json_test() {
+    let replay = Replay {
+        flags: "--io-only".to_string(),
+        ticks: 100,
+        snapshot: Some(3),
+        inputs: vec![
+            ReplayEvent {
+                tick: 0,
+                input: ReplayInput::Use {
+                    pos: BlockPos::new(1, 2, 3),
+                },
+            },
+            ReplayEvent {
+                tick: 40,
+                input: ReplayInput::PressurePlate {
+                    pos: BlockPos::new(-4, 5, 6),
+                    powered: true,
+                },
+            },
+        ],
+    };
+    let json = serde_json::to_string(&replay).unwrap();
+    assert!(json.contains(
+        r#"{"tick":40,"type":"pressure_plate","pos":{"x":-4,"y":5,"z":6},"powered":true}"#
+    ));
+    assert_eq!(serde_json::from_str::<Replay>(&json).unwrap(), replay);
+
+    assert!(is_valid_name("cpu-glitch_2"));
+    assert!(!is_valid_name("../plots"));
+    assert!(!is_valid_name(""));
+}
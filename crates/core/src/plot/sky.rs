@@ -0,0 +1,138 @@
+//! The time of day and the weather are set per plot with `/plot time` and `/plot weather`, so that
+//! builders get the same lighting on their plot no matter what the rest of the server looks like.
+//! They are only sent to the players on the plot.
+
+use super::Plot;
+use crate::player::{PacketSender, Player};
+use mchprs_network::packets::clientbound::{
+    CGameEvent, CGameEventType, ClientBoundPacket, UpdateTime,
+};
+use mchprs_save_data::plot_data::{PlotPermission, PlotWeather};
+
+/// The time of plots that haven't set one
+const DEFAULT_TIME: i64 = 6000;
+const DAY_LENGTH: i64 = 24000;
+
+fn parse_time(time: &str) -> Option<i64> {
+    Some(match time {
+        "day" => 1000,
+        "noon" => 6000,
+        "night" => 13000,
+        "midnight" => 18000,
+        _ => time.parse::<i64>().ok()?.rem_euclid(DAY_LENGTH),
+    })
+}
+
+fn weather_names() -> String {
+    let names: Vec<&str> = PlotWeather::ALL.iter().map(|w| w.name()).collect();
+    names.join(", ")
+}
+
+impl Plot {
+    fn time(&self) -> i64 {
+        self.settings.time.unwrap_or(DEFAULT_TIME)
+    }
+
+    fn weather(&self) -> PlotWeather {
+        self.settings.weather.unwrap_or(PlotWeather::Clear)
+    }
+
+    pub(super) fn send_time(&self, player: &Player) {
+        let time = UpdateTime {
+            world_age: 0,
+            // A negative time stops the daylight cycle. A whole day is added so that this still
+            // works for a time of 0.
+            time_of_day: -(self.time() + DAY_LENGTH),
+        }
+        .encode();
+        player.client.send_packet(&time);
+    }
+
+    pub(super) fn send_weather(&self, player: &Player) {
+        let (begin, rain, thunder) = match self.weather() {
+            PlotWeather::Clear => (CGameEventType::EndRaining, 0.0, 0.0),
+            PlotWeather::Rain => (CGameEventType::BeginRaining, 1.0, 0.0),
+            PlotWeather::Thunder => (CGameEventType::BeginRaining, 1.0, 1.0),
+        };
+        let events = [
+            (begin, 0.0),
+            (CGameEventType::RainLevelChange, rain),
+            (CGameEventType::ThunderLevelChange, thunder),
+        ];
+        for (reason, value) in events {
+            player
+                .client
+                .send_packet(&CGameEvent { reason, value }.encode());
+        }
+    }
+
+    /// Handles `/plot time`
+    pub(super) fn handle_time_command(&mut self, player: usize, args: &[&str]) {
+        let [time] = args else {
+            self.players[player]
+                .send_system_message(&format!("The time on this plot is {}.", self.time()));
+            return;
+        };
+        if !self.check_plot_permission(player, PlotPermission::Build) {
+            return;
+        }
+        if *time == "reset" {
+            self.settings.time = None;
+        } else {
+            let Some(time) = parse_time(time) else {
+                self.players[player].send_error_message(
+                    "Usage: /plot time [ticks | day | noon | night | midnight | reset]",
+                );
+                return;
+            };
+            self.settings.time = Some(time);
+        }
+        for player in &self.players {
+            self.send_time(player);
+        }
+        self.players[player]
+            .send_system_message(&format!("The time on this plot is now {}.", self.time()));
+    }
+
+    /// Handles `/plot weather`
+    pub(super) fn handle_weather_command(&mut self, player: usize, args: &[&str]) {
+        let [weather] = args else {
+            self.players[player].send_system_message(&format!(
+                "The weather on this plot is {}.",
+                self.weather().name()
+            ));
+            return;
+        };
+        if !self.check_plot_permission(player, PlotPermission::Build) {
+            return;
+        }
+        if *weather == "reset" {
+            self.settings.weather = None;
+        } else {
+            let Ok(weather) = weather.parse::<PlotWeather>() else {
+                self.players[player].send_error_message(&format!(
+                    "The weather must be any of [{}, reset]",
+                    weather_names()
+                ));
+                return;
+            };
+            self.settings.weather = Some(weather);
+        }
+        for player in &self.players {
+            self.send_weather(player);
+        }
+        self.players[player].send_system_message(&format!(
+            "The weather on this plot is now {}.",
+            self.weather().name()
+        ));
+    }
+}
+
+#[test]
+fn parse_time_test() {
+    assert_eq!(parse_time("noon"), Some(6000));
+    assert_eq!(parse_time("18000"), Some(18000));
+    assert_eq!(parse_time("30000"), Some(6000));
+    assert_eq!(parse_time("-1000"), Some(23000));
+    assert_eq!(parse_time("dusk"), None);
+}
@@ -0,0 +1,323 @@
+//! Snapshots are point-in-time copies of a plot that its owner can roll back to with
+//! `/plot snapshot restore`, for when something goes wrong that is too big for worldedit undo, like
+//! a bad mass edit or a trusted member griefing the plot. They are stored compressed in
+//! `./world/snapshots/p{x},{z}/`, and only the newest `max_plot_snapshots` of a plot are kept.
+
+use super::{database, worldedit, Plot};
+use crate::config::CONFIG;
+use crate::player::{self, PacketSender};
+use anyhow::{bail, Context, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use mchprs_network::packets::clientbound::{CRemoveEntities, ClientBoundPacket};
+use mchprs_save_data::plot_data::{ChunkData, VERSION};
+use mchprs_world::{ItemFrame, TickEntry};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+/// Shown by `/plot snapshot list` without decompressing the snapshot
+#[derive(Serialize, Deserialize)]
+struct SnapshotInfo {
+    name: String,
+    /// Unix timestamp of when the snapshot was made
+    created: i64,
+    creator: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotData {
+    chunk_data: Vec<ChunkData>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+}
+
+fn snapshot_dir(plot_x: i32, plot_z: i32) -> PathBuf {
+    PathBuf::from(format!("./world/snapshots/p{},{}", plot_x, plot_z))
+}
+
+fn snapshot_path(plot_x: i32, plot_z: i32, id: u32) -> PathBuf {
+    snapshot_dir(plot_x, plot_z).join(format!("{}.snapshot", id))
+}
+
+/// Returns the ids of the plot's snapshots, oldest first
+pub(super) fn snapshot_ids(plot_x: i32, plot_z: i32) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir(snapshot_dir(plot_x, plot_z)) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "snapshot" {
+                return None;
+            }
+            path.file_stem()?.to_str()?.parse().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// Describes a snapshot like `#3: before rewiring (2024-01-01 12:00 UTC by Player)`
+pub(super) fn describe_snapshot(plot_x: i32, plot_z: i32, id: u32) -> String {
+    match read_snapshot_info(&snapshot_path(plot_x, plot_z, id)) {
+        Ok((_, info)) if info.name.is_empty() => {
+            format!("#{}: {} by {}", id, format_time(info.created), info.creator)
+        }
+        Ok((_, info)) => format!(
+            "#{}: {} ({} by {})",
+            id,
+            info.name,
+            format_time(info.created),
+            info.creator
+        ),
+        Err(_) => format!("#{}: unreadable", id),
+    }
+}
+
+/// Snapshots start with the plot data version, so that snapshots from older versions aren't
+/// loaded as garbage, followed by the info and then the compressed data
+fn write_snapshot(path: &Path, info: &SnapshotInfo, data: &SnapshotData) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_u32::<LittleEndian>(VERSION)?;
+    bincode::serialize_into(&mut writer, info)?;
+    let mut encoder = DeflateEncoder::new(writer, Compression::default());
+    bincode::serialize_into(&mut encoder, data)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+fn read_snapshot_info(path: &Path) -> Result<(BufReader<File>, SnapshotInfo)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != VERSION {
+        bail!("snapshot was made with plot data version {}", version);
+    }
+    let info = bincode::deserialize_from(&mut reader)?;
+    Ok((reader, info))
+}
+
+fn read_snapshot(path: &Path) -> Result<SnapshotData> {
+    let (reader, _) = read_snapshot_info(path)?;
+    let data: SnapshotData =
+        bincode::deserialize_from(DeflateDecoder::new(reader)).context("snapshot is corrupted")?;
+    super::data::check_height(&data.chunk_data)?;
+    Ok(data)
+}
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+impl Plot {
+    /// Only the owner can manage the snapshots of a plot, unless the player is an admin
+    pub(super) fn can_manage_snapshots(&self, player: usize) -> bool {
+        let player = &self.players[player];
+        self.access.owner == Some(player.uuid) || player.has_permission("plots.admin.snapshot")
+    }
+
+    /// Saves a snapshot of the plot as it is now and deletes the oldest snapshots that go over
+    /// the limit. Returns the id of the new snapshot.
+    pub(super) fn create_snapshot(&mut self, name: String, creator: String) -> Result<u32> {
+        let (plot_x, plot_z) = (self.world.x, self.world.z);
+        fs::create_dir_all(snapshot_dir(plot_x, plot_z))?;
+        let mut ids = snapshot_ids(plot_x, plot_z);
+        let id = ids.last().map_or(1, |id| id + 1);
+
+        let info = SnapshotInfo {
+            name,
+            created: database::now(),
+            creator,
+        };
+        let data = SnapshotData {
+            chunk_data: self.world.chunks.iter_mut().map(ChunkData::new).collect(),
+            pending_ticks: self.world.to_be_ticked.clone(),
+            item_frames: self.world.item_frames.clone(),
+        };
+        write_snapshot(&snapshot_path(plot_x, plot_z, id), &info, &data)?;
+        ids.push(id);
+
+        let max_snapshots = CONFIG.max_plot_snapshots.max(1) as usize;
+        if ids.len() > max_snapshots {
+            for old_id in &ids[..ids.len() - max_snapshots] {
+                fs::remove_file(snapshot_path(plot_x, plot_z, *old_id))?;
+            }
+        }
+        Ok(id)
+    }
+
+    /// Replaces the plot with the snapshot and sends the restored plot to its players
+    fn restore_snapshot(&mut self, data: SnapshotData) -> Result<()> {
+        let chunks = super::data::load_chunks(data.chunk_data, self.world.x, self.world.z)?;
+        self.reset_redpiler();
+        for player in &mut self.players {
+            worldedit::cancel_player_job(&self.world, player);
+        }
+
+        for (chunk, restored) in self.world.chunks.iter_mut().zip(chunks) {
+            *chunk = restored;
+        }
+        self.world.to_be_ticked = data.pending_ticks;
+
+        let remove_frames = CRemoveEntities {
+            entity_ids: self
+                .world
+                .item_frames
+                .iter()
+                .map(|frame| frame.entity_id as i32)
+                .collect(),
+        }
+        .encode();
+        self.world.item_frames = data
+            .item_frames
+            .into_iter()
+            .map(|frame| ItemFrame {
+                entity_id: player::next_entity_id(),
+                ..frame
+            })
+            .collect();
+
+        let view_distance = CONFIG.view_distance as u32;
+        for player_idx in 0..self.players.len() {
+            let player = &self.players[player_idx];
+            let (chunk_x, chunk_z) = player.pos.chunk_pos();
+            for chunk in &self.world.chunks {
+                if Plot::get_chunk_distance(chunk.x, chunk.z, chunk_x, chunk_z) <= view_distance {
+                    player.client.send_packet(chunk.encode_packet());
+                }
+            }
+            player.client.send_packet(&remove_frames);
+            self.spawn_item_frames_for_player(player_idx);
+        }
+        self.reset_timings();
+        Ok(())
+    }
+
+    /// Handles `/plot snapshot`
+    pub(super) fn handle_snapshot_command(&mut self, player: usize, args: &[&str]) {
+        if !self.can_manage_snapshots(player) {
+            self.players[player].send_error_message("You can only do this on your own plot.");
+            return;
+        }
+        let (plot_x, plot_z) = (self.world.x, self.world.z);
+        match args {
+            ["create", name @ ..] => {
+                let name = name.join(" ");
+                let creator = self.players[player].username.clone();
+                match self.create_snapshot(name, creator) {
+                    Ok(id) => self.players[player]
+                        .send_system_message(&format!("Snapshot #{} was created.", id)),
+                    Err(err) => {
+                        error!(
+                            "Failed to create snapshot of plot {},{}: {:?}",
+                            plot_x, plot_z, err
+                        );
+                        self.players[player]
+                            .send_error_message("There was an error creating the snapshot.");
+                    }
+                }
+            }
+            ["list"] => {
+                let ids = snapshot_ids(plot_x, plot_z);
+                if ids.is_empty() {
+                    self.players[player].send_system_message("This plot has no snapshots.");
+                    return;
+                }
+                for id in ids.into_iter().rev() {
+                    self.players[player]
+                        .send_system_message(&describe_snapshot(plot_x, plot_z, id));
+                }
+            }
+            ["restore", id] => {
+                let Ok(id) = id.trim_start_matches('#').parse::<u32>() else {
+                    self.players[player].send_error_message("Unable to parse snapshot id!");
+                    return;
+                };
+                let path = snapshot_path(plot_x, plot_z, id);
+                if !path.is_file() {
+                    self.players[player]
+                        .send_error_message(&format!("There is no snapshot #{}.", id));
+                    return;
+                }
+                let data = match read_snapshot(&path) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        error!(
+                            "Failed to load snapshot {} of plot {},{}: {:?}",
+                            id, plot_x, plot_z, err
+                        );
+                        self.players[player]
+                            .send_error_message("There was an error loading the snapshot.");
+                        return;
+                    }
+                };
+                // The plot as it is now can be brought back if the wrong snapshot was restored
+                let backup = self.create_snapshot(
+                    format!("before restoring #{}", id),
+                    self.players[player].username.clone(),
+                );
+                if let Err(err) = backup {
+                    error!(
+                        "Failed to create snapshot of plot {},{}: {:?}",
+                        plot_x, plot_z, err
+                    );
+                    self.players[player].send_error_message(
+                        "There was an error saving the plot before restoring the snapshot.",
+                    );
+                    return;
+                }
+                if let Err(err) = self.restore_snapshot(data) {
+                    error!(
+                        "Failed to restore snapshot {} of plot {},{}: {:?}",
+                        id, plot_x, plot_z, err
+                    );
+                    self.players[player]
+                        .send_error_message("There was an error loading the snapshot.");
+                    return;
+                }
+                self.players[player]
+                    .send_system_message(&format!("Snapshot #{} was restored.", id));
+            }
+            _ => self.players[player]
+                .send_error_message("Usage: /plot snapshot <create [name] | list | restore <id>>"),
+        }
+    }
+}
+
+#[test]
+fn snapshot_write_and_read_test() {
+    use mchprs_world::storage::Chunk;
+
+    let mut chunk = Chunk::empty(0, 0, 24);
+    chunk.set_block(3, 70, 9, 332);
+    let info = SnapshotInfo {
+        name: "before the grief".to_string(),
+        created: 1_700_000_000,
+        creator: "Owner".to_string(),
+    };
+    let data = SnapshotData {
+        chunk_data: vec![ChunkData::new(&mut chunk)],
+        pending_ticks: Vec::new(),
+        item_frames: Vec::new(),
+    };
+
+    let path = std::env::temp_dir().join("mchprs_snapshot_test.snapshot");
+    write_snapshot(&path, &info, &data).unwrap();
+    let (_, read_info) = read_snapshot_info(&path).unwrap();
+    let read_data = read_snapshot(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(read_info.name, info.name);
+    assert_eq!(read_info.created, info.created);
+    assert_eq!(read_info.creator, info.creator);
+    let loaded = read_data.chunk_data.into_iter().next().unwrap().load(0, 0);
+    assert_eq!(loaded.get_block(3, 70, 9), 332);
+    assert_eq!(loaded.get_block(3, 71, 9), 0);
+}
@@ -0,0 +1,278 @@
+//! Suggestions for the command arguments that clients ask the server about while a command is
+//! typed. These are the arguments with the `minecraft:ask_server` suggestions type in the command
+//! tree, and what they are completed with depends on the name of the argument.
+
+use super::commands::{CommandFlags, COMMAND_NODES};
+use super::{database, snapshot, worldedit, Plot};
+use crate::permissions::groups;
+use crate::player::PacketSender;
+use crate::server::ONLINE_PLAYER_NAMES;
+use mchprs_blocks::blocks::Block;
+use mchprs_network::packets::clientbound::{
+    CCommandSuggestionsResponse, CCommandSuggestionsResponseMatch, CCommandsNode,
+    CDeclareCommandsNodeParser, ClientBoundPacket,
+};
+use mchprs_text::TextComponent;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet};
+
+type Parser = CDeclareCommandsNodeParser;
+
+/// The names of all blocks, along with the values each of their states can have
+static BLOCK_STATES: Lazy<BTreeMap<&'static str, BTreeMap<&'static str, BTreeSet<String>>>> =
+    Lazy::new(|| {
+        let mut blocks: BTreeMap<_, BTreeMap<_, BTreeSet<_>>> = BTreeMap::new();
+        for id in 0..=u16::MAX as u32 {
+            let block = Block::from_id(id);
+            let name = block.get_name();
+            // This also skips the ids of unknown blocks
+            if Block::from_name(name).is_none() {
+                continue;
+            }
+            let states = blocks.entry(name).or_default();
+            for (key, value) in block.properties() {
+                states.entry(key).or_default().insert(value);
+            }
+        }
+        // Some properties are part of the name instead, like the color of wool
+        for states in blocks.values_mut() {
+            states.retain(|_, values| values.len() > 1);
+        }
+        blocks
+    });
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// Finds the argument that is being typed at the end of `text`, which is what follows `node` in
+/// the command. Returns the argument along with where it starts in `text`.
+fn find_argument(node: &CCommandsNode, text: &str) -> Option<(&'static CCommandsNode, usize)> {
+    for &child_idx in &node.children {
+        let child = &COMMAND_NODES[child_idx as usize];
+        let next = child
+            .redirect_node
+            .map_or(child, |idx| &COMMAND_NODES[idx as usize]);
+        let flags = CommandFlags::from_bits_truncate(child.flags as u32);
+        if !flags.contains(CommandFlags::ARGUMENT) {
+            if let Some((word, rest)) = text.split_once(' ') {
+                if Some(word) == child.name {
+                    if let Some((argument, start)) = find_argument(next, rest) {
+                        return Some((argument, word.len() + 1 + start));
+                    }
+                }
+            }
+            continue;
+        }
+
+        let words = match child.parser {
+            Some(Parser::Vec3 | Parser::BlockPos) => 3,
+            Some(Parser::Vec2) => 2,
+            // Greedy phrases take up the rest of the command
+            Some(Parser::String(2)) => usize::MAX,
+            _ => 1,
+        };
+        let end = text
+            .match_indices(' ')
+            .nth(words.saturating_sub(1))
+            .map(|(idx, _)| idx);
+        match end {
+            Some(end) => {
+                if let Some((argument, start)) = find_argument(next, &text[end + 1..]) {
+                    return Some((argument, end + 1 + start));
+                }
+            }
+            None if child.suggestions_type == Some("minecraft:ask_server") => {
+                return Some((child, 0));
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Suggests what comes next in a pattern or mask, which is either a block name or one of its
+/// states. Returns where in `input` the suggestions start.
+fn block_suggestions(input: &str) -> (usize, Vec<String>) {
+    // Only the last block of a list like `50%stone,glass` is being typed
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => start = idx + 1,
+            _ => {}
+        }
+    }
+    if let Some(idx) = input[start..].find('%') {
+        start += idx + 1;
+    }
+    if input[start..].starts_with('!') {
+        start += 1;
+    }
+
+    let part = &input[start..];
+    let Some((name, states)) = part.split_once('[') else {
+        let part = part.trim_start_matches("minecraft:");
+        let names = BLOCK_STATES
+            .keys()
+            .filter(|name| name.starts_with(part))
+            .map(|name| name.to_string())
+            .collect();
+        return (start, names);
+    };
+    let Some(block_states) = BLOCK_STATES.get(name.trim_start_matches("minecraft:")) else {
+        return (start, Vec::new());
+    };
+    let state_start = states.rfind(',').map_or(0, |idx| idx + 1);
+    let state = &states[state_start..];
+    let start = start + name.len() + 1 + state_start;
+    match state.split_once('=') {
+        None => {
+            let keys = block_states
+                .keys()
+                .filter(|key| key.starts_with(state))
+                .map(|key| format!("{}=", key))
+                .collect();
+            (start, keys)
+        }
+        Some((key, value)) => {
+            let values = block_states
+                .get(key)
+                .into_iter()
+                .flatten()
+                .filter(|state_value| state_value.starts_with(value))
+                .cloned()
+                .collect();
+            (start + key.len() + 1, values)
+        }
+    }
+}
+
+impl Plot {
+    /// Suggests values for the argument named `name`, of which `input` is typed so far. Returns
+    /// where in `input` the suggestions start and the suggestions with their tooltips.
+    fn argument_suggestions(
+        &self,
+        player: usize,
+        name: &str,
+        text: &str,
+        input: &str,
+    ) -> (usize, Vec<(String, Option<String>)>) {
+        let player = &self.players[player];
+        let suggestions = match name {
+            "targets" => {
+                let names = ONLINE_PLAYER_NAMES.lock().unwrap();
+                std::iter::once("*".to_string())
+                    .chain(names.iter().cloned())
+                    .filter(|name| starts_with_ignore_case(name, input))
+                    .map(|name| (name, None))
+                    .collect()
+            }
+            "index" => database::get_owned_plots(&player.username)
+                .into_iter()
+                .enumerate()
+                .map(|(idx, (x, z))| ((idx + 1).to_string(), Some(format!("{},{}", x, z))))
+                .filter(|(idx, _)| idx.starts_with(input))
+                .collect(),
+            "id" => snapshot::snapshot_ids(self.world.x, self.world.z)
+                .into_iter()
+                .rev()
+                .map(|id| {
+                    let description = snapshot::describe_snapshot(self.world.x, self.world.z, id);
+                    (id.to_string(), Some(description))
+                })
+                .filter(|(id, _)| id.starts_with(input))
+                .collect(),
+            "name" | "filename" => {
+                let library = text.split(' ').any(|word| word == "-g");
+                worldedit::schematic_suggestions(player, library, input)
+                    .into_iter()
+                    .map(|name| (name, None))
+                    .collect()
+            }
+            "group" | "parent" => groups::view(|permissions| {
+                permissions
+                    .groups
+                    .keys()
+                    .filter(|group| group.starts_with(input))
+                    .map(|group| (group.clone(), None))
+                    .collect()
+            }),
+            "pattern" | "mask" | "from" | "to" => {
+                let (start, names) = block_suggestions(input);
+                let suggestions = names.into_iter().map(|name| (name, None)).collect();
+                return (start, suggestions);
+            }
+            _ => Vec::new(),
+        };
+        (0, suggestions)
+    }
+
+    /// Answers a suggestion request of a client for the command in `text`
+    pub(super) fn send_command_suggestions(&self, player: usize, transaction_id: i32, text: &str) {
+        let Some(command) = text.strip_prefix('/') else {
+            return;
+        };
+        let Some((argument, start)) = find_argument(&COMMAND_NODES[0], command) else {
+            return;
+        };
+        // Skip the slash
+        let start = start + 1;
+        let input = &text[start..];
+        let (offset, suggestions) =
+            self.argument_suggestions(player, argument.name.unwrap_or_default(), text, input);
+        let response = CCommandSuggestionsResponse {
+            id: transaction_id,
+            start: (start + offset) as i32,
+            length: (input.len() - offset) as i32,
+            matches: suggestions
+                .into_iter()
+                .map(|(suggestion, tooltip)| CCommandSuggestionsResponseMatch {
+                    match_: suggestion,
+                    tooltip: tooltip.map(TextComponent::from),
+                })
+                .collect(),
+        }
+        .encode();
+        self.players[player].send_packet(&response);
+    }
+}
+
+#[test]
+fn find_argument_test() {
+    let argument =
+        |text| find_argument(&COMMAND_NODES[0], text).map(|(node, start)| (node.name, start));
+    assert_eq!(argument("title Pla"), Some((Some("targets"), 6)));
+    assert_eq!(
+        argument("playsound block.note_block.harp "),
+        Some((Some("targets"), 32))
+    );
+    assert_eq!(argument("plot home "), Some((Some("index"), 10)));
+    // Redirects continue with the arguments of the command they redirect to
+    assert_eq!(argument("p snapshot restore 1"), Some((Some("id"), 19)));
+    assert_eq!(argument("/set stone,gla"), Some((Some("pattern"), 5)));
+    assert_eq!(
+        argument("perm player Player addgroup b"),
+        Some((Some("group"), 28))
+    );
+    assert_eq!(argument("title Player title Hello"), None);
+    assert_eq!(argument("unknown "), None);
+}
+
+#[test]
+fn block_suggestions_test() {
+    let (start, names) = block_suggestions("50%stone,redstone_w");
+    assert_eq!(start, 9);
+    assert_eq!(names, ["redstone_wall_torch", "redstone_wire"]);
+
+    let (start, keys) = block_suggestions("!repeater[delay=2,fa");
+    assert_eq!(start, 18);
+    assert_eq!(keys, ["facing="]);
+
+    let (start, values) = block_suggestions("repeater[delay=");
+    assert_eq!(start, 15);
+    assert_eq!(values, ["1", "2", "3", "4"]);
+}
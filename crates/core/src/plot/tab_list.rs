@@ -0,0 +1,66 @@
+//! The header and footer of the tab list are set with the `[tab_list]` section of the config. The
+//! placeholders in them are filled in with the performance of the plot the player is on, so they
+//! are sent by the plot and refreshed while players are on it.
+
+use super::Plot;
+use crate::config::CONFIG;
+use crate::player::Player;
+use crate::server::ONLINE_PLAYER_COUNT;
+use mchprs_network::packets::clientbound::{CSetTabListHeaderAndFooter, ClientBoundPacket};
+use mchprs_text::TextComponent;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+impl Plot {
+    fn fill_tab_list_placeholders(&self, format: &str) -> TextComponent {
+        let rtps = match self.timings.generate_report() {
+            Some(report) => format!("{:.1}", report.ten_s),
+            None => "-".to_string(),
+        };
+        let redpiler = if self.redpiler.is_active() {
+            "active"
+        } else {
+            "inactive"
+        };
+        let text = format
+            .replace("{plot}", &format!("{}, {}", self.world.x, self.world.z))
+            .replace("{tps}", &self.tps.to_string())
+            .replace("{rtps}", &rtps)
+            .replace("{redpiler}", redpiler)
+            .replace(
+                "{online}",
+                &ONLINE_PLAYER_COUNT.load(Ordering::Relaxed).to_string(),
+            );
+        TextComponent {
+            extra: TextComponent::from_legacy_text(&text),
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn send_tab_list(&self, player: &Player) {
+        let Some(config) = &CONFIG.tab_list else {
+            return;
+        };
+        let tab_list = CSetTabListHeaderAndFooter {
+            header: self.fill_tab_list_placeholders(&config.header),
+            footer: self.fill_tab_list_placeholders(&config.footer),
+        }
+        .encode();
+        player.client.send_packet(&tab_list);
+    }
+
+    /// Sends the tab list to the players on the plot again once the refresh interval has passed
+    pub(super) fn refresh_tab_list(&mut self) {
+        let Some(config) = &CONFIG.tab_list else {
+            return;
+        };
+        let interval = Duration::from_millis(config.refresh_interval);
+        if self.last_tab_list_refresh.elapsed() < interval {
+            return;
+        }
+        self.last_tab_list_refresh = Instant::now();
+        for player in &self.players {
+            self.send_tab_list(player);
+        }
+    }
+}
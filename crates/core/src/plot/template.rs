@@ -0,0 +1,110 @@
+//! Plot templates let players start a plot from a prepared build with `/plot claim --template`.
+//! A template is a schematic named `<name>.schem` in one of the `plot_template_dirs`, with an
+//! optional `<name>.toml` next to it holding the plot settings the template comes with.
+
+use super::worldedit::{load_schematic_file, paste_clipboard};
+use super::Plot;
+use crate::config::CONFIG;
+use crate::player::PacketSender;
+use anyhow::{Context, Result};
+use mchprs_blocks::BlockPos;
+use mchprs_save_data::plot_data::PlotSettings;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+/// Returns the path of the template's schematic, or `None` if no template directory has it
+fn find_template(name: &str) -> Option<PathBuf> {
+    // Template names can't be used to read schematics outside of the template directories
+    if name.is_empty() || name.contains(['/', '\\', '.']) {
+        return None;
+    }
+    CONFIG
+        .plot_template_dirs
+        .iter()
+        .map(|dir| Path::new(dir).join(format!("{}.schem", name)))
+        .find(|path| path.is_file())
+}
+
+/// Returns the names of all templates, sorted
+fn list_templates() -> Vec<String> {
+    let mut names = Vec::new();
+    for dir in &CONFIG.plot_template_dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "schem") {
+                if let Some(name) = path.file_stem() {
+                    names.push(name.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn load_settings(schematic_path: &Path) -> Result<PlotSettings> {
+    let path = schematic_path.with_extension("toml");
+    if !path.exists() {
+        return Ok(PlotSettings::default());
+    }
+    let settings = fs::read_to_string(&path)?;
+    toml::from_str(&settings).with_context(|| format!("invalid settings in {}", path.display()))
+}
+
+impl Plot {
+    /// Pastes the template with its lowest corner at the lowest corner of the plot and applies
+    /// its settings. Returns false and tells the player if the template couldn't be used.
+    pub(super) fn apply_template(&mut self, player: usize, name: &str) -> bool {
+        let Some(path) = find_template(name) else {
+            let templates = list_templates();
+            let message = if templates.is_empty() {
+                format!("There is no template called {}.", name)
+            } else {
+                format!(
+                    "There is no template called {}. Templates: {}",
+                    name,
+                    templates.join(", ")
+                )
+            };
+            self.players[player].send_error_message(&message);
+            return false;
+        };
+        let template = load_schematic_file(&path).and_then(|clipboard| {
+            let settings = load_settings(&path)?;
+            Ok((clipboard, settings))
+        });
+        let (clipboard, settings) = match template {
+            Ok(template) => template,
+            Err(err) => {
+                error!("Failed to load plot template {}: {:?}", name, err);
+                self.players[player].send_error_message("There was an error loading the template.");
+                return false;
+            }
+        };
+
+        self.reset_redpiler();
+        let (corner, _) = self.world.get_corners();
+        let pos =
+            corner + BlockPos::new(clipboard.offset_x, clipboard.offset_y, clipboard.offset_z);
+        paste_clipboard(&mut self.world, &clipboard, pos, false);
+
+        self.auto_redpiler = settings.auto_redpiler.unwrap_or(CONFIG.auto_redpiler);
+        self.world.quasi_connectivity = settings
+            .quasi_connectivity
+            .unwrap_or(CONFIG.quasi_connectivity);
+        self.world.strict_update_order = settings
+            .strict_update_order
+            .unwrap_or(CONFIG.strict_update_order);
+        self.settings = settings;
+        for player in &self.players {
+            self.send_time(player);
+            self.send_weather(player);
+        }
+        true
+    }
+}
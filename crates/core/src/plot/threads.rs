@@ -0,0 +1,182 @@
+//! Every plot runs on its own thread. On shared hosts these threads can be pinned to cores and
+//! given a priority with the `plot_threads` section of the config, and the number of plots running
+//! at a high rtps can be limited so that a few plots can't slow down everyone else. How busy each
+//! thread is can be seen with `/threads`.
+
+use crate::config::{PlotThreadsConfig, CONFIG};
+use mchprs_save_data::plot_data::Tps;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// How often the utilization of a thread is updated
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy)]
+pub struct ThreadInfo {
+    pub x: i32,
+    pub z: i32,
+    pub core: Option<usize>,
+    pub tps: Tps,
+    /// The fraction of the time the thread spent updating the plot, from 0 to 1
+    pub utilization: f32,
+}
+
+static THREADS: Lazy<Mutex<HashMap<(i32, i32), ThreadInfo>>> = Lazy::new(Default::default);
+static NEXT_CORE: AtomicUsize = AtomicUsize::new(0);
+
+fn is_high_tps(config: &PlotThreadsConfig, tps: Tps) -> bool {
+    match tps {
+        Tps::Limited(tps) => tps > config.high_rtps_threshold,
+        Tps::Unlimited => true,
+    }
+}
+
+/// Records the rtps the plot runs at. If it is a high rtps and too many other plots already run at
+/// one, nothing is changed and the highest rtps the plot can run at is returned instead.
+pub fn set_plot_tps(x: i32, z: i32, tps: Tps) -> Result<(), Tps> {
+    let mut threads = THREADS.lock().unwrap();
+    if let Some(config) = &CONFIG.plot_threads {
+        if config.max_high_rtps_plots > 0 && is_high_tps(config, tps) {
+            let high_plots = threads
+                .values()
+                .filter(|info| (info.x, info.z) != (x, z) && is_high_tps(config, info.tps))
+                .count();
+            if high_plots >= config.max_high_rtps_plots {
+                return Err(Tps::Limited(config.high_rtps_threshold));
+            }
+        }
+    }
+    threads
+        .entry((x, z))
+        .or_insert_with(|| ThreadInfo {
+            x,
+            z,
+            core: None,
+            tps,
+            utilization: 0.0,
+        })
+        .tps = tps;
+    Ok(())
+}
+
+/// Returns the number of plots running at a high rtps and the most that are allowed to, if there
+/// is a limit
+pub fn high_tps_plots() -> (usize, Option<usize>) {
+    let Some(config) = &CONFIG.plot_threads else {
+        return (0, None);
+    };
+    let threads = THREADS.lock().unwrap();
+    let count = threads
+        .values()
+        .filter(|info| is_high_tps(config, info.tps))
+        .count();
+    let max = (config.max_high_rtps_plots > 0).then_some(config.max_high_rtps_plots);
+    (count, max)
+}
+
+/// Returns all running plot threads, busiest first
+pub fn thread_infos() -> Vec<ThreadInfo> {
+    let threads = THREADS.lock().unwrap();
+    let mut infos: Vec<ThreadInfo> = threads.values().copied().collect();
+    infos.sort_by(|a, b| b.utilization.total_cmp(&a.utilization));
+    infos
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> bool {
+    // SAFETY: `cpu_set_t` is a plain bitmask which is valid when zeroed, and the calling thread is
+    // always a valid target.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_priority(priority: i32) -> bool {
+    // On Linux the nice value belongs to the thread, so this only affects the calling thread
+    // SAFETY: `setpriority` has no memory safety requirements.
+    unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, priority) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(_core: usize) -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_priority(_priority: i32) -> bool {
+    false
+}
+
+/// Keeps track of how busy the thread of a plot is
+pub(super) struct PlotThread {
+    x: i32,
+    z: i32,
+    busy_time: Duration,
+    last_report: Instant,
+}
+
+impl PlotThread {
+    pub(super) fn new(x: i32, z: i32) -> PlotThread {
+        PlotThread {
+            x,
+            z,
+            busy_time: Duration::ZERO,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Pins the calling thread to the next core and sets its priority, as set in the config
+    pub(super) fn configure(&self) {
+        let Some(config) = &CONFIG.plot_threads else {
+            return;
+        };
+        if !config.cores.is_empty() {
+            let next = NEXT_CORE.fetch_add(1, Ordering::Relaxed);
+            let core = config.cores[next % config.cores.len()];
+            if pin_to_core(core) {
+                if let Some(info) = THREADS.lock().unwrap().get_mut(&(self.x, self.z)) {
+                    info.core = Some(core);
+                }
+            } else {
+                warn!(
+                    "Failed to pin the thread of plot {},{} to core {}",
+                    self.x, self.z, core
+                );
+            }
+        }
+        if config.priority != 0 && !set_priority(config.priority) {
+            warn!(
+                "Failed to set the priority of the thread of plot {},{}",
+                self.x, self.z
+            );
+        }
+    }
+
+    /// Adds the time spent on an update, and updates the utilization once a second
+    pub(super) fn record_update(&mut self, busy: Duration) {
+        self.busy_time += busy;
+        let elapsed = self.last_report.elapsed();
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+        let utilization = (self.busy_time.as_secs_f32() / elapsed.as_secs_f32()).min(1.0);
+        if let Some(info) = THREADS.lock().unwrap().get_mut(&(self.x, self.z)) {
+            info.utilization = utilization;
+        }
+        self.busy_time = Duration::ZERO;
+        self.last_report = Instant::now();
+    }
+}
+
+/// Forgets the plot. This has to happen before the server is told that the plot unloaded, so that
+/// it doesn't race with the plot being loaded again.
+pub(super) fn remove_plot(x: i32, z: i32) {
+    THREADS.lock().unwrap().remove(&(x, z));
+}
@@ -0,0 +1,409 @@
+//! The watchdog keeps one plot from taking down the rest of the server. A panic on a plot thread is
+//! caught, a crash report with the backtrace, the state of redpiler and the last commands run on
+//! the plot is written to `world/crashes` along with the plot's last save, and the plot is
+//! restarted from its last save. If telemetry is configured, an anonymized copy of the report is
+//! submitted as well. Plots that stop updating for `stall_timeout` seconds are reported to
+//! admins, but can't be restarted since their thread is still running.
+
+use super::{threads, Plot};
+use crate::config::CONFIG;
+use crate::player::{PacketSender, Player};
+use crate::server::Message;
+use backtrace::Backtrace;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+/// Plots that crash this soon after they were loaded aren't restarted, so that a plot which
+/// crashes right away doesn't keep restarting forever
+const CRASH_LOOP_TIME: Duration = Duration::from_secs(60);
+/// How often the watchdog looks for stalled plots
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How many of the last commands run on a plot are kept for crash reports
+const RECENT_COMMANDS: usize = 20;
+
+/// Heartbeats are stored as milliseconds since this instant so that they can be atomic
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+/// The heartbeats of the running plots by their position
+type Heartbeats = HashMap<(i32, i32), Arc<Heartbeat>>;
+
+static HEARTBEATS: Lazy<Mutex<Heartbeats>> = Lazy::new(Default::default);
+
+struct PanicReport {
+    message: String,
+    location: String,
+    backtrace: Backtrace,
+}
+
+thread_local! {
+    /// The last panic on this thread, kept for the crash report
+    static LAST_PANIC: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+/// Remembers the panic for the crash report. This is called from the panic hook, so it runs on
+/// the thread that panicked.
+pub fn record_panic(info: &PanicHookInfo<'_>, backtrace: Backtrace) {
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    };
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+    LAST_PANIC.set(Some(PanicReport {
+        message,
+        location,
+        backtrace,
+    }));
+}
+
+fn millis_since_start() -> u64 {
+    START.elapsed().as_millis() as u64
+}
+
+struct Heartbeat {
+    /// The last time the plot updated, in milliseconds since `START`
+    last_beat: AtomicU64,
+    /// Whether admins have been told that the plot stalled
+    stalled: AtomicBool,
+}
+
+#[derive(Serialize, Clone)]
+struct RecentCommand {
+    /// How long after the plot was loaded the command was run, in seconds
+    time: f64,
+    player: String,
+    command: String,
+}
+
+/// The plot's side of the watchdog
+pub(super) struct PlotWatchdog {
+    heartbeat: Arc<Heartbeat>,
+    loaded_at: Instant,
+    last_autosave: Instant,
+    recent_commands: VecDeque<RecentCommand>,
+}
+
+impl PlotWatchdog {
+    pub(super) fn new(x: i32, z: i32) -> PlotWatchdog {
+        let heartbeat = Arc::new(Heartbeat {
+            last_beat: AtomicU64::new(millis_since_start()),
+            stalled: AtomicBool::new(false),
+        });
+        HEARTBEATS
+            .lock()
+            .unwrap()
+            .insert((x, z), Arc::clone(&heartbeat));
+        PlotWatchdog {
+            heartbeat,
+            loaded_at: Instant::now(),
+            last_autosave: Instant::now(),
+            recent_commands: VecDeque::new(),
+        }
+    }
+
+    /// Remembers a command for the crash report
+    pub(super) fn record_command(&mut self, player: &str, command: &str, args: &[&str]) {
+        if self.recent_commands.len() == RECENT_COMMANDS {
+            self.recent_commands.pop_front();
+        }
+        let mut command = command.to_string();
+        for arg in args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+        self.recent_commands.push_back(RecentCommand {
+            time: self.loaded_at.elapsed().as_secs_f64(),
+            player: player.to_string(),
+            command,
+        });
+    }
+
+    /// Lets the watchdog know that the plot is still updating
+    pub(super) fn beat(&self) {
+        self.heartbeat
+            .last_beat
+            .store(millis_since_start(), Ordering::Relaxed);
+    }
+
+    /// Returns true if it is time to save the plot again
+    pub(super) fn autosave_due(&mut self) -> bool {
+        let interval = CONFIG.watchdog.autosave_interval;
+        if interval == 0 || self.last_autosave.elapsed() < Duration::from_secs(interval) {
+            return false;
+        }
+        self.last_autosave = Instant::now();
+        true
+    }
+}
+
+/// Forgets the plot. Like `threads::remove_plot`, this has to happen before the server is told
+/// that the plot unloaded.
+pub(super) fn remove_plot(x: i32, z: i32) {
+    HEARTBEATS.lock().unwrap().remove(&(x, z));
+}
+
+/// Returns an alert for every plot that stalled or started responding again since the last check
+fn check_stalls(timeout: Duration) -> Vec<String> {
+    let now = millis_since_start();
+    let heartbeats = HEARTBEATS.lock().unwrap();
+    let mut alerts = Vec::new();
+    for (&(x, z), heartbeat) in heartbeats.iter() {
+        let since_beat =
+            Duration::from_millis(now.saturating_sub(heartbeat.last_beat.load(Ordering::Relaxed)));
+        let stalled = since_beat >= timeout;
+        if heartbeat.stalled.swap(stalled, Ordering::Relaxed) == stalled {
+            continue;
+        }
+        alerts.push(if stalled {
+            format!(
+                "[Watchdog] Plot {},{} hasn't responded for {} seconds.",
+                x,
+                z,
+                since_beat.as_secs()
+            )
+        } else {
+            format!("[Watchdog] Plot {},{} is responding again.", x, z)
+        });
+    }
+    alerts
+}
+
+/// Starts the thread that tells admins about plots which stop responding
+pub fn start(sender: Sender<Message>) {
+    let timeout = CONFIG.watchdog.stall_timeout;
+    if timeout == 0 {
+        return;
+    }
+    let timeout = Duration::from_secs(timeout);
+    thread::Builder::new()
+        .name("watchdog".to_string())
+        .spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            for alert in check_stalls(timeout) {
+                warn!("{}", alert);
+                if sender.send(Message::AdminAlert(alert)).is_err() {
+                    return;
+                }
+            }
+        })
+        .unwrap();
+}
+
+#[derive(Serialize)]
+struct PanicSummary {
+    message: String,
+    location: String,
+    backtrace: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RedpilerSummary {
+    active: bool,
+    flags: Option<String>,
+    nodes: Option<usize>,
+    links: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CrashReport {
+    version: &'static str,
+    time: String,
+    plot_x: i32,
+    plot_z: i32,
+    panic: Option<PanicSummary>,
+    rtps: String,
+    /// How long the plot was loaded for before it crashed, in seconds
+    uptime: f64,
+    redpiler: RedpilerSummary,
+    pending_ticks: usize,
+    players: Vec<String>,
+    recent_commands: Vec<RecentCommand>,
+}
+
+impl CrashReport {
+    fn new(plot: &Plot, panic: Option<&PanicReport>) -> CrashReport {
+        let stats = plot.redpiler.stats();
+        CrashReport {
+            version: env!("CARGO_PKG_VERSION"),
+            time: chrono::Utc::now().to_rfc3339(),
+            plot_x: plot.world.x,
+            plot_z: plot.world.z,
+            panic: panic.map(|panic| PanicSummary {
+                message: panic.message.clone(),
+                location: panic.location.clone(),
+                backtrace: format!("{:?}", panic.backtrace)
+                    .lines()
+                    .map(str::to_string)
+                    .collect(),
+            }),
+            rtps: plot.tps.to_string(),
+            uptime: plot.watchdog.loaded_at.elapsed().as_secs_f64(),
+            redpiler: RedpilerSummary {
+                active: plot.redpiler.is_active(),
+                flags: plot.redpiler.current_flags().map(|o| o.to_flags()),
+                nodes: stats.map(|stats| stats.nodes),
+                links: stats.map(|stats| stats.links),
+            },
+            pending_ticks: plot.world.to_be_ticked.len(),
+            players: plot.players.iter().map(|p| p.username.clone()).collect(),
+            recent_commands: plot.watchdog.recent_commands.iter().cloned().collect(),
+        }
+    }
+
+    /// Removes everything about the players from the report. Commands only keep their name, since
+    /// their arguments can be player names or chat.
+    fn anonymize(mut self) -> CrashReport {
+        self.players.clear();
+        for command in &mut self.recent_commands {
+            command.player.clear();
+            command
+                .command
+                .truncate(command.command.find(' ').unwrap_or(command.command.len()));
+        }
+        self
+    }
+}
+
+/// Writes the crash report and a copy of the plot's last save. Returns the folder they were
+/// written to.
+fn write_crash_report(report: &CrashReport) -> std::io::Result<String> {
+    let (x, z) = (report.plot_x, report.plot_z);
+    let dir = format!(
+        "./world/crashes/p{},{}_{}",
+        x,
+        z,
+        chrono::Utc::now().format("%Y-%m-%d_%H-%M-%S")
+    );
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        format!("{}/report.json", dir),
+        serde_json::to_string_pretty(report).unwrap(),
+    )?;
+
+    let save_path = format!("./world/plots/p{},{}", x, z);
+    if Path::new(&save_path).exists() {
+        fs::copy(&save_path, format!("{}/p{},{}", dir, x, z))?;
+    }
+    Ok(dir)
+}
+
+impl Plot {
+    /// Called on the plot thread after a panic was caught. The plot is restored from its last
+    /// save, or unloaded if it can't be restarted, and the world it was running is thrown away
+    /// since it might be broken.
+    pub(super) fn handle_crash(&mut self) {
+        self.crashed = true;
+        let (x, z) = (self.world.x, self.world.z);
+        threads::remove_plot(x, z);
+        remove_plot(x, z);
+
+        let panic = LAST_PANIC.take();
+        let crash_report = CrashReport::new(self, panic.as_ref());
+        let report = match write_crash_report(&crash_report) {
+            Ok(dir) => format!("A crash report was saved to {}.", dir),
+            Err(err) => {
+                error!("Failed to write crash report for plot {},{}: {}", x, z, err);
+                "The crash report couldn't be saved.".to_string()
+            }
+        };
+        if CONFIG.telemetry.is_some() {
+            let anonymized = serde_json::to_value(crash_report.anonymize()).unwrap();
+            let _ = self
+                .message_sender
+                .send(Message::SubmitCrashReport(anonymized));
+        }
+
+        let restart = CONFIG.watchdog.restart_crashed_plots
+            && self.watchdog.loaded_at.elapsed() > CRASH_LOOP_TIME
+            && (self.always_running || !self.players.is_empty());
+        for player in &mut self.players {
+            player.save();
+            if restart {
+                player.send_error_message(
+                    "The plot you are in has crashed and was restored from its last save.",
+                );
+            } else {
+                Plot::send_player_away(x, z, player);
+                player.send_error_message("The plot you were previously in has crashed!");
+            }
+        }
+        let mut players: Vec<Player> = Vec::new();
+        while !self.players.is_empty() {
+            let uuid = self.players[0].uuid;
+            players.push(self.leave_plot(uuid));
+        }
+
+        let message = panic.map(|panic| panic.message).unwrap_or_default();
+        let outcome = if restart {
+            "It was restored from its last save."
+        } else {
+            "It was unloaded."
+        };
+        let _ = self.message_sender.send(Message::AdminAlert(format!(
+            "[Watchdog] Plot {},{} crashed: {} {} {}",
+            x, z, message, outcome, report
+        )));
+        if restart {
+            let _ = self
+                .message_sender
+                .send(Message::PlotRestart(x, z, players));
+        } else {
+            for player in players {
+                let _ = self.message_sender.send(Message::PlayerLeavePlot(player));
+            }
+            let _ = self.message_sender.send(Message::PlotUnload(x, z));
+        }
+    }
+}
+
+#[test]
+fn anonymize_test() {
+    let mut watchdog = PlotWatchdog::new(i32::MAX, i32::MAX);
+    for i in 0..=RECENT_COMMANDS {
+        watchdog.record_command("Player", "tp", &["Other", &i.to_string()]);
+    }
+    assert_eq!(watchdog.recent_commands.len(), RECENT_COMMANDS);
+    assert_eq!(watchdog.recent_commands[0].command, "tp Other 1");
+    remove_plot(i32::MAX, i32::MAX);
+
+    let report = CrashReport {
+        version: "",
+        time: String::new(),
+        plot_x: 0,
+        plot_z: 0,
+        panic: None,
+        rtps: String::new(),
+        uptime: 0.0,
+        redpiler: RedpilerSummary {
+            active: false,
+            flags: None,
+            nodes: None,
+            links: None,
+        },
+        pending_ticks: 0,
+        players: vec!["Player".to_string()],
+        recent_commands: watchdog.recent_commands.into(),
+    }
+    .anonymize();
+    assert!(report.players.is_empty());
+    assert!(report
+        .recent_commands
+        .iter()
+        .all(|command| command.player.is_empty() && command.command == "tp"));
+}
@@ -0,0 +1,396 @@
+//! Reads blocks out of vanilla Anvil worlds, so that builds from survival or creative worlds can
+//! be pasted into plots and compiled. Worlds are read from `./imports/<name>/`, which is the folder
+//! of a world with `level.dat` and `region/` in it. Worlds saved by Minecraft 1.13 or later can be
+//! read, and blocks that were renamed since are given their current names.
+//! https://minecraft.wiki/w/Region_file_format
+//! https://minecraft.wiki/w/Chunk_format
+
+use super::WorldEditClipboard;
+use anyhow::{bail, Context, Result};
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::BlockPos;
+use mchprs_world::storage::PalettedBitBuffer;
+use nbt::{Blob, Map, Value};
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const IMPORTS_FOLDER: &str = "./imports";
+/// The most blocks that can be imported at once, even for players without a clipboard limit, so
+/// that the clipboard always fits in memory
+pub const MAX_IMPORT_VOLUME: u64 = 64 * 1024 * 1024;
+
+/// The data version of 17w47a, the first version with block names instead of numeric ids
+const FLATTENING_VERSION: i32 = 1451;
+/// The data version of 20w17a, after which block states no longer span two longs
+const NON_SPANNING_VERSION: i32 = 2527;
+/// The data version of 21w43a, which moved the contents of the `Level` compound into the root
+/// and renamed most of it
+const NEW_CHUNK_FORMAT_VERSION: i32 = 2844;
+
+/// Blocks that were renamed, with the data version they were renamed in
+const RENAMED_BLOCKS: &[(&str, i32, &str)] = &[
+    ("stone_slab", 1901, "smooth_stone_slab"),
+    ("sign", 1901, "oak_sign"),
+    ("wall_sign", 1901, "oak_wall_sign"),
+    ("grass_path", 2681, "dirt_path"),
+    ("grass", 3679, "short_grass"),
+];
+
+fn get<'a>(nbt: &'a Map<String, Value>, key: &str) -> Result<&'a Value> {
+    nbt.get(key)
+        .with_context(|| format!("chunk is missing {}", key))
+}
+
+fn get_compound<'a>(nbt: &'a Map<String, Value>, key: &str) -> Result<&'a Map<String, Value>> {
+    match get(nbt, key)? {
+        Value::Compound(compound) => Ok(compound),
+        _ => bail!("{} is not a compound", key),
+    }
+}
+
+/// Lists that are left out or empty are treated the same
+fn get_list<'a>(nbt: &'a Map<String, Value>, key: &str) -> Result<&'a [Value]> {
+    match nbt.get(key) {
+        Some(Value::List(list)) => Ok(list),
+        Some(_) => bail!("{} is not a list", key),
+        None => Ok(&[]),
+    }
+}
+
+fn get_int(nbt: &Map<String, Value>, key: &str) -> Result<i32> {
+    match get(nbt, key)? {
+        Value::Byte(value) => Ok(*value as i32),
+        Value::Short(value) => Ok(*value as i32),
+        Value::Int(value) => Ok(*value),
+        _ => bail!("{} is not an integer", key),
+    }
+}
+
+/// The region files of a world, which are read when they are first needed
+struct Regions {
+    dir: PathBuf,
+    files: FxHashMap<(i32, i32), Option<Vec<u8>>>,
+}
+
+impl Regions {
+    /// Reads a chunk, or returns `None` if it was never generated
+    fn read_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Option<Blob>> {
+        let (region_x, region_z) = (chunk_x >> 5, chunk_z >> 5);
+        let region = match self.files.entry((region_x, region_z)) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let path = self.dir.join(format!("r.{}.{}.mca", region_x, region_z));
+                let file = match fs::read(path) {
+                    Ok(file) => Some(file),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(err.into()),
+                };
+                entry.insert(file)
+            }
+        };
+        let Some(region) = region else {
+            return Ok(None);
+        };
+
+        let header = (((chunk_x & 31) + (chunk_z & 31) * 32) * 4) as usize;
+        let location = region
+            .get(header..header + 4)
+            .context("region file header is cut off")?;
+        let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        if sector == 0 {
+            return Ok(None);
+        }
+        let start = sector * 4096;
+        let length = region
+            .get(start..start + 4)
+            .context("chunk is outside of the region file")?;
+        let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+        let mut data = region
+            .get(start + 4..start + 4 + length)
+            .context("chunk is cut off")?;
+        let compression = data.first().copied().context("chunk is empty")?;
+        data = &data[1..];
+        Ok(Some(match compression {
+            1 => Blob::from_gzip_reader(&mut data)?,
+            2 => Blob::from_zlib_reader(&mut data)?,
+            3 => Blob::from_reader(&mut data)?,
+            4 => bail!("chunks compressed with LZ4 can't be read"),
+            _ if compression & 128 != 0 => {
+                bail!("chunks that are too large for the region file can't be read")
+            }
+            _ => bail!("unknown chunk compression {}", compression),
+        }))
+    }
+}
+
+fn block_from_palette(entry: &Value, data_version: i32) -> Result<u32> {
+    let Value::Compound(entry) = entry else {
+        bail!("palette entry is not a compound");
+    };
+    let Value::String(name) = get(entry, "Name")? else {
+        bail!("palette entry name is not a string");
+    };
+    let mut name = name.trim_start_matches("minecraft:");
+    for &(old_name, version, new_name) in RENAMED_BLOCKS {
+        if data_version < version && name == old_name {
+            name = new_name;
+        }
+    }
+    // Blocks that aren't implemented are imported as air, like in schematics
+    let mut block = Block::from_name(name).unwrap_or(Block::Air {});
+    if let Some(Value::Compound(properties)) = entry.get("Properties") {
+        let properties: HashMap<&str, &str> = properties
+            .iter()
+            .filter_map(|(key, value)| match value {
+                Value::String(value) => Some((key.as_str(), value.as_str())),
+                _ => None,
+            })
+            .collect();
+        block.set_properties(properties);
+    }
+    Ok(block.get_id())
+}
+
+/// Unpacks the palette indices of the 4096 blocks in a chunk section
+fn unpack_section(data: &[i64], palette_len: usize, spanning: bool) -> Result<Vec<usize>> {
+    let bits = (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(4) as usize;
+    let mask = (1u64 << bits) - 1;
+    let per_long = 64 / bits;
+    let mut indices = Vec::with_capacity(4096);
+    for i in 0..4096 {
+        let index = if spanning {
+            let bit = i * bits;
+            let (word, offset) = (bit / 64, bit % 64);
+            let mut value = *data.get(word).context("block states are cut off")? as u64 >> offset;
+            if offset + bits > 64 {
+                let next = *data.get(word + 1).context("block states are cut off")? as u64;
+                value |= next << (64 - offset);
+            }
+            value & mask
+        } else {
+            let word = *data.get(i / per_long).context("block states are cut off")? as u64;
+            (word >> ((i % per_long) * bits)) & mask
+        };
+        indices.push(index as usize);
+    }
+    Ok(indices)
+}
+
+/// The blocks of a chunk, by section y
+struct ChunkBlocks {
+    sections: FxHashMap<i32, Vec<u32>>,
+    block_entities: Vec<(BlockPos, BlockEntity)>,
+}
+
+fn read_chunk_blocks(chunk: &Blob) -> Result<ChunkBlocks> {
+    let data_version = match chunk.content.get("DataVersion") {
+        Some(Value::Int(version)) => *version,
+        _ => 0,
+    };
+    if data_version < FLATTENING_VERSION {
+        bail!("the world has to be opened and saved with Minecraft 1.13 or later first");
+    }
+    let new_format = data_version >= NEW_CHUNK_FORMAT_VERSION;
+    let level = match new_format {
+        true => &chunk.content,
+        false => get_compound(&chunk.content, "Level")?,
+    };
+
+    let mut sections = FxHashMap::default();
+    for section in get_list(level, if new_format { "sections" } else { "Sections" })? {
+        let Value::Compound(section) = section else {
+            bail!("section is not a compound");
+        };
+        let section_y = get_int(section, "Y")?;
+        let (palette, data) = match new_format {
+            true => match section.get("block_states") {
+                Some(Value::Compound(states)) => (get_list(states, "palette")?, states.get("data")),
+                _ => continue,
+            },
+            false => (get_list(section, "Palette")?, section.get("BlockStates")),
+        };
+        if palette.is_empty() {
+            continue;
+        }
+        let palette = palette
+            .iter()
+            .map(|entry| block_from_palette(entry, data_version))
+            .collect::<Result<Vec<u32>>>()?;
+        let blocks = match data {
+            Some(Value::LongArray(data)) => {
+                let spanning = data_version < NON_SPANNING_VERSION;
+                unpack_section(data, palette.len(), spanning)?
+                    .into_iter()
+                    .map(|index| palette.get(index).copied().unwrap_or(0))
+                    .collect()
+            }
+            // Sections of a single block leave out the data
+            _ => vec![palette[0]; 4096],
+        };
+        sections.insert(section_y, blocks);
+    }
+
+    let mut block_entities = Vec::new();
+    let key = if new_format {
+        "block_entities"
+    } else {
+        "TileEntities"
+    };
+    for block_entity in get_list(level, key)? {
+        let Value::Compound(block_entity) = block_entity else {
+            continue;
+        };
+        let Some(Value::String(id)) = block_entity.get("id") else {
+            continue;
+        };
+        let pos = BlockPos::new(
+            get_int(block_entity, "x")?,
+            get_int(block_entity, "y")?,
+            get_int(block_entity, "z")?,
+        );
+        if let Some(parsed) = BlockEntity::from_nbt(id, block_entity) {
+            block_entities.push((pos, parsed));
+        }
+    }
+    Ok(ChunkBlocks {
+        sections,
+        block_entities,
+    })
+}
+
+/// World names are folder names, so they can't lead out of the imports folder
+fn world_dir(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("{} is not a valid world name", name);
+    }
+    Ok(Path::new(IMPORTS_FOLDER).join(name))
+}
+
+/// The size of the area between two corners, or `None` if it has more than `MAX_IMPORT_VOLUME`
+/// blocks
+pub fn import_size(first_pos: BlockPos, second_pos: BlockPos) -> Option<(u32, u32, u32)> {
+    let length = |a: i32, b: i32| u32::try_from((a as i64 - b as i64).abs() + 1).ok();
+    let size_x = length(first_pos.x, second_pos.x)?;
+    let size_y = length(first_pos.y, second_pos.y)?;
+    let size_z = length(first_pos.z, second_pos.z)?;
+    let volume = (size_x as u64)
+        .checked_mul(size_y as u64)?
+        .checked_mul(size_z as u64)?;
+    (volume <= MAX_IMPORT_VOLUME).then_some((size_x, size_y, size_z))
+}
+
+/// Reads the blocks between two corners of a world into a clipboard, which has its lowest corner
+/// at the position it is pasted at
+pub fn import_world(
+    name: &str,
+    first_pos: BlockPos,
+    second_pos: BlockPos,
+) -> Result<WorldEditClipboard> {
+    let region_dir = world_dir(name)?.join("region");
+    if !region_dir.is_dir() {
+        return Err(io::Error::from(io::ErrorKind::NotFound).into());
+    }
+    let mut regions = Regions {
+        dir: region_dir,
+        files: FxHashMap::default(),
+    };
+
+    let Some((size_x, size_y, size_z)) = import_size(first_pos, second_pos) else {
+        bail!("the area has more than {} blocks", MAX_IMPORT_VOLUME);
+    };
+    let start = first_pos.min(second_pos);
+    let end = first_pos.max(second_pos);
+    let mut clipboard = WorldEditClipboard {
+        offset_x: 0,
+        offset_y: 0,
+        offset_z: 0,
+        size_x,
+        size_y,
+        size_z,
+        data: PalettedBitBuffer::new((size_x * size_y * size_z) as usize, 9),
+        block_entities: FxHashMap::default(),
+        copied_at: None,
+    };
+
+    for chunk_x in start.x >> 4..=end.x >> 4 {
+        for chunk_z in start.z >> 4..=end.z >> 4 {
+            let Some(chunk) = regions
+                .read_chunk(chunk_x, chunk_z)
+                .with_context(|| format!("could not read chunk {}, {}", chunk_x, chunk_z))?
+            else {
+                continue;
+            };
+            let chunk = read_chunk_blocks(&chunk)
+                .with_context(|| format!("could not read chunk {}, {}", chunk_x, chunk_z))?;
+
+            let xs = (chunk_x << 4).max(start.x)..=((chunk_x << 4) + 15).min(end.x);
+            let zs = (chunk_z << 4).max(start.z)..=((chunk_z << 4) + 15).min(end.z);
+            for y in start.y..=end.y {
+                let Some(section) = chunk.sections.get(&(y >> 4)) else {
+                    continue;
+                };
+                for z in zs.clone() {
+                    for x in xs.clone() {
+                        let block =
+                            section[(((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize];
+                        let index = ((y - start.y) as u32 * size_z + (z - start.z) as u32) * size_x
+                            + (x - start.x) as u32;
+                        clipboard.data.set_entry(index as usize, block);
+                    }
+                }
+            }
+            for (pos, block_entity) in chunk.block_entities {
+                if (start.x..=end.x).contains(&pos.x)
+                    && (start.y..=end.y).contains(&pos.y)
+                    && (start.z..=end.z).contains(&pos.z)
+                {
+                    clipboard.block_entities.insert(pos - start, block_entity);
+                }
+            }
+        }
+    }
+    Ok(clipboard)
+}
+
+#[test]
+fn unpack_section_test() {
+    // 5 bits per block: 12 blocks per long without spanning, or 12.8 with it
+    let indices: Vec<u64> = (0..4096).map(|i| (i * 7 % 17) as u64).collect();
+    let mut packed = vec![0i64; 4096 / 12 + 1];
+    for (i, &index) in indices.iter().enumerate() {
+        packed[i / 12] |= (index << ((i % 12) * 5)) as i64;
+    }
+    let unpacked = unpack_section(&packed, 17, false).unwrap();
+    assert!(unpacked.iter().zip(&indices).all(|(&a, &b)| a as u64 == b));
+
+    let mut packed = vec![0i64; 4096 * 5 / 64];
+    for (i, &index) in indices.iter().enumerate() {
+        let bit = i * 5;
+        packed[bit / 64] |= (index << (bit % 64)) as i64;
+        if bit % 64 + 5 > 64 {
+            packed[bit / 64 + 1] |= (index >> (64 - bit % 64)) as i64;
+        }
+    }
+    let unpacked = unpack_section(&packed, 17, true).unwrap();
+    assert!(unpacked.iter().zip(&indices).all(|(&a, &b)| a as u64 == b));
+
+    assert_eq!(
+        import_size(BlockPos::new(5, -64, 0), BlockPos::new(-4, 319, 0)),
+        Some((10, 384, 1))
+    );
+    assert_eq!(
+        import_size(BlockPos::new(i32::MIN, 0, 0), BlockPos::new(i32::MAX, 0, 0)),
+        None
+    );
+    assert_eq!(
+        import_size(BlockPos::new(0, 0, 0), BlockPos::new(65535, 65535, 65535)),
+        None
+    );
+
+    assert!(world_dir("../world").is_err());
+    assert!(world_dir("survival").is_ok());
+}
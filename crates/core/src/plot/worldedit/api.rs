@@ -0,0 +1,196 @@
+//! The worldedit engine for plugins and scripts. An [`EditSession`] edits a plot the same way the
+//! worldedit commands do, and records its edits in an undo history, which can be the history of a
+//! player so that they can undo the edits themselves.
+
+use super::brush::clamp_to_plot;
+use super::selection::Region;
+use super::{
+    create_clipboard, mask_clipboard, paste_clipboard, record_undo, SelectionMode,
+    WorldEditClipboard, WorldEditHistory, WorldEditMask, WorldEditOperation, WorldEditPattern,
+};
+use crate::player::Player;
+use crate::plot::PlotWorld;
+use mchprs_blocks::BlockPos;
+use mchprs_world::World;
+
+/// An area of a plot to edit. Like player selections, it is either the cuboid between two
+/// positions or a shape made from a list of points inside of that cuboid.
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub first_pos: BlockPos,
+    pub second_pos: BlockPos,
+    pub mode: SelectionMode,
+    /// The points of the shape, unused for cuboids
+    pub points: Vec<BlockPos>,
+}
+
+impl Selection {
+    pub fn cuboid(first_pos: BlockPos, second_pos: BlockPos) -> Selection {
+        Selection {
+            first_pos,
+            second_pos,
+            mode: SelectionMode::Cuboid,
+            points: Vec::new(),
+        }
+    }
+
+    /// The current selection of the player, if they have made one
+    pub fn of_player(player: &Player) -> Option<Selection> {
+        Some(Selection {
+            first_pos: player.first_position?,
+            second_pos: player.second_position?,
+            mode: player.worldedit_selection_mode,
+            points: player.worldedit_points.clone(),
+        })
+    }
+
+    /// Returns the operation over the part of the selection inside of the plot
+    fn operation(&self, plot: &PlotWorld) -> Option<WorldEditOperation> {
+        let min = self.first_pos.min(self.second_pos);
+        let max = self.first_pos.max(self.second_pos);
+        let (min, max) = clamp_to_plot(plot, min, max)?;
+        let region = Region::new(self.mode, &self.points);
+        Some(WorldEditOperation::new(min, max, region))
+    }
+}
+
+pub struct EditSession<'a> {
+    plot: &'a mut PlotWorld,
+    history: &'a mut WorldEditHistory,
+}
+
+impl<'a> EditSession<'a> {
+    pub fn new(plot: &'a mut PlotWorld, history: &'a mut WorldEditHistory) -> EditSession<'a> {
+        EditSession { plot, history }
+    }
+
+    /// Creates a session which records its edits in the history of the player
+    pub fn for_player(plot: &'a mut PlotWorld, player: &'a mut Player) -> EditSession<'a> {
+        EditSession::new(plot, &mut player.worldedit_history)
+    }
+
+    fn record_undo(&mut self, operation: &WorldEditOperation) {
+        let first_pos = BlockPos::new(
+            *operation.x_range.start(),
+            *operation.y_range.start(),
+            *operation.z_range.start(),
+        );
+        let second_pos = BlockPos::new(
+            *operation.x_range.end(),
+            *operation.y_range.end(),
+            *operation.z_range.end(),
+        );
+        record_undo(self.plot, self.history, first_pos, second_pos);
+    }
+
+    /// Edits the blocks in the selection for which `block_id` returns a block. Returns the
+    /// number of blocks that were changed.
+    fn edit(
+        &mut self,
+        selection: &Selection,
+        mut block_id: impl FnMut(&PlotWorld, BlockPos) -> Option<u32>,
+    ) -> usize {
+        let Some(mut operation) = selection.operation(self.plot) else {
+            return 0;
+        };
+        self.record_undo(&operation);
+        for x in operation.x_range() {
+            for y in operation.y_range() {
+                for z in operation.z_range() {
+                    let pos = BlockPos::new(x, y, z);
+                    if !operation.contains(pos) {
+                        continue;
+                    }
+                    let Some(id) = block_id(self.plot, pos) else {
+                        continue;
+                    };
+                    if self.plot.set_block_raw(pos, id) {
+                        operation.update_block();
+                    }
+                }
+            }
+        }
+        self.history.finish(self.plot);
+        operation.blocks_updated()
+    }
+
+    /// Sets every block in the selection, like `//set`
+    pub fn set(&mut self, selection: &Selection, pattern: &WorldEditPattern) -> usize {
+        self.edit(selection, |_, _| Some(pattern.pick().get_id()))
+    }
+
+    /// Sets the blocks in the selection matching the mask, like `//replace`
+    pub fn replace(
+        &mut self,
+        selection: &Selection,
+        mask: &WorldEditMask,
+        pattern: &WorldEditPattern,
+    ) -> usize {
+        self.edit(selection, |plot, pos| {
+            mask.matches(plot.get_block(pos))
+                .then(|| pattern.pick().get_id())
+        })
+    }
+
+    /// Counts the blocks in the selection matching the mask, like `//count`
+    pub fn count(&self, selection: &Selection, mask: &WorldEditMask) -> usize {
+        let Some(operation) = selection.operation(self.plot) else {
+            return 0;
+        };
+        let mut count = 0;
+        for x in operation.x_range() {
+            for y in operation.y_range() {
+                for z in operation.z_range() {
+                    let pos = BlockPos::new(x, y, z);
+                    if operation.contains(pos) && mask.matches(self.plot.get_block(pos)) {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// Copies the selection into a clipboard which is pasted relative to `origin`, like `//copy`
+    pub fn copy(&mut self, selection: &Selection, origin: BlockPos) -> WorldEditClipboard {
+        let start_pos = selection.first_pos.min(selection.second_pos);
+        let region = Region::new(selection.mode, &selection.points);
+        let mut clipboard =
+            create_clipboard(self.plot, origin, selection.first_pos, selection.second_pos);
+        mask_clipboard(&mut clipboard, &region, start_pos);
+        clipboard
+    }
+
+    /// Pastes the clipboard like `//paste` does when standing at `pos`
+    pub fn paste(&mut self, clipboard: &WorldEditClipboard, pos: BlockPos, skip_air: bool) {
+        let first_pos = BlockPos::new(
+            pos.x - clipboard.offset_x,
+            pos.y - clipboard.offset_y,
+            pos.z - clipboard.offset_z,
+        );
+        let second_pos = first_pos
+            + BlockPos::new(
+                clipboard.size_x as i32 - 1,
+                clipboard.size_y as i32 - 1,
+                clipboard.size_z as i32 - 1,
+            );
+        let Some(operation) = Selection::cuboid(first_pos, second_pos).operation(self.plot) else {
+            return;
+        };
+        self.record_undo(&operation);
+        paste_clipboard(self.plot, clipboard, pos, skip_air);
+        self.history.finish(self.plot);
+    }
+
+    /// Reverts the last edit in the history. Returns false if there was nothing to undo in this
+    /// plot.
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(self.plot).is_ok()
+    }
+
+    /// Applies the last undone edit again. Returns false if there was nothing to redo in this
+    /// plot.
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(self.plot).is_ok()
+    }
+}
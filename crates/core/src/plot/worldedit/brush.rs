@@ -0,0 +1,226 @@
+//! Brushes are bound to a held item and get applied wherever the player is looking when they
+//! right click with that item.
+
+use super::{
+    capture_undo, limits, paste_clipboard, ray_trace_block, WorldEditClipboard, WorldEditMask,
+    WorldEditPattern,
+};
+use crate::player::{PacketSender, Player};
+use crate::plot::{Plot, PlotWorld, PLOT_BLOCK_HEIGHT, PLOT_BLOCK_WIDTH, PLOT_MIN_Y};
+use mchprs_blocks::items::Item;
+use mchprs_blocks::{BlockFace, BlockPos};
+use mchprs_save_data::plot_data::PlotPermission;
+use mchprs_world::World;
+
+/// The maximum distance a brush can be used from
+const BRUSH_RANGE: f64 = 300.0;
+
+#[derive(Debug, Clone)]
+pub enum BrushShape {
+    Sphere {
+        pattern: WorldEditPattern,
+    },
+    Cylinder {
+        pattern: WorldEditPattern,
+        height: u32,
+    },
+    Paste {
+        clipboard: WorldEditClipboard,
+        skip_air: bool,
+    },
+}
+
+impl BrushShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrushShape::Sphere { .. } => "Sphere",
+            BrushShape::Cylinder { .. } => "Cylinder",
+            BrushShape::Paste { .. } => "Paste",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldEditBrush {
+    pub shape: BrushShape,
+    /// The radius of sphere and cylinder brushes
+    pub size: u32,
+    /// Sphere and cylinder brushes only replace blocks matching this mask
+    pub mask: Option<WorldEditMask>,
+}
+
+impl WorldEditBrush {
+    pub fn new(shape: BrushShape, size: u32) -> WorldEditBrush {
+        WorldEditBrush {
+            shape,
+            size,
+            mask: None,
+        }
+    }
+
+    fn apply(&self, plot: &mut PlotWorld, player: &mut Player, target: BlockPos) {
+        let radius = self.size as i32;
+        // Adding half a block makes the edges look less jagged
+        let radius_sq = (self.size as f64 + 0.5).powi(2);
+        match &self.shape {
+            BrushShape::Sphere { pattern } => {
+                let offset = BlockPos::new(radius, radius, radius);
+                self.fill(
+                    plot,
+                    player,
+                    target - offset,
+                    target + offset,
+                    pattern,
+                    |pos| {
+                        let d = pos - target;
+                        ((d.x * d.x + d.y * d.y + d.z * d.z) as f64) <= radius_sq
+                    },
+                );
+            }
+            BrushShape::Cylinder { pattern, height } => {
+                let min = BlockPos::new(target.x - radius, target.y, target.z - radius);
+                let max = BlockPos::new(
+                    target.x + radius,
+                    target.y + *height as i32 - 1,
+                    target.z + radius,
+                );
+                self.fill(plot, player, min, max, pattern, |pos| {
+                    let d = pos - target;
+                    ((d.x * d.x + d.z * d.z) as f64) <= radius_sq
+                });
+            }
+            BrushShape::Paste {
+                clipboard,
+                skip_air,
+            } => {
+                // Pasting on top of the block that was clicked works just like standing on it and
+                // running //paste
+                let pos = target.offset(BlockFace::Top);
+                let min = BlockPos::new(
+                    pos.x - clipboard.offset_x,
+                    pos.y - clipboard.offset_y,
+                    pos.z - clipboard.offset_z,
+                );
+                let max = min
+                    + BlockPos::new(
+                        clipboard.size_x as i32 - 1,
+                        clipboard.size_y as i32 - 1,
+                        clipboard.size_z as i32 - 1,
+                    );
+                let Some((min, max)) = clamp_to_plot(plot, min, max) else {
+                    return;
+                };
+                capture_undo(plot, player, min, max);
+                paste_clipboard(plot, clipboard, pos, *skip_air);
+            }
+        }
+    }
+
+    fn fill(
+        &self,
+        plot: &mut PlotWorld,
+        player: &mut Player,
+        min: BlockPos,
+        max: BlockPos,
+        pattern: &WorldEditPattern,
+        contains: impl Fn(BlockPos) -> bool,
+    ) {
+        let Some((min, max)) = clamp_to_plot(plot, min, max) else {
+            return;
+        };
+        capture_undo(plot, player, min, max);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let pos = BlockPos::new(x, y, z);
+                    if !contains(pos) {
+                        continue;
+                    }
+                    if let Some(mask) = &self.mask {
+                        if !mask.matches(plot.get_block(pos)) {
+                            continue;
+                        }
+                    }
+                    plot.delete_block_entity(pos);
+                    plot.set_block_raw(pos, pattern.pick().get_id());
+                }
+            }
+        }
+    }
+}
+
+/// Shrinks the region so that it doesn't leave the plot. Returns `None` if there is nothing left.
+pub(super) fn clamp_to_plot(
+    plot: &PlotWorld,
+    min: BlockPos,
+    max: BlockPos,
+) -> Option<(BlockPos, BlockPos)> {
+    let plot_min = BlockPos::new(
+        plot.x * *PLOT_BLOCK_WIDTH,
+        *PLOT_MIN_Y,
+        plot.z * *PLOT_BLOCK_WIDTH,
+    );
+    let plot_max = plot_min
+        + BlockPos::new(*PLOT_BLOCK_WIDTH, *PLOT_BLOCK_HEIGHT, *PLOT_BLOCK_WIDTH)
+        - BlockPos::new(1, 1, 1);
+    let min = min.max(plot_min);
+    let max = max.min(plot_max);
+    if min.x > max.x || min.y > max.y || min.z > max.z {
+        return None;
+    }
+    Some((min, max))
+}
+
+/// The item in the player's main hand that a brush can be bound to
+pub(super) fn brush_item(player: &Player) -> Option<Item> {
+    player.inventory[player.selected_slot as usize + 36]
+        .as_ref()
+        .map(|item| item.item_type)
+}
+
+/// Returns true if the player is holding an item with a brush bound to it
+pub fn holding_brush(player: &Player) -> bool {
+    brush_item(player).is_some_and(|item| player.worldedit_brushes.contains_key(&item.get_id()))
+}
+
+/// Applies the brush bound to the item the player is holding. Returns false if there is no brush
+/// bound to the item.
+pub fn use_brush(plot: &mut Plot, player_idx: usize) -> bool {
+    let player = &mut plot.players[player_idx];
+    let Some(item) = brush_item(player) else {
+        return false;
+    };
+    // The brush is taken out while it is applied so the undo history can be written to
+    let Some(brush) = player.worldedit_brushes.remove(&item.get_id()) else {
+        return false;
+    };
+
+    if !plot.access.allows(player, PlotPermission::WorldEdit) {
+        player.send_no_permission_message();
+    } else if player.worldedit_job.is_some() {
+        player.send_error_message(
+            "Wait for your current operation to finish, or stop it with //cancel.",
+        );
+    } else if let Some(target) = ray_trace_block(
+        &plot.world,
+        player.pos,
+        player.pitch as f64,
+        player.yaw as f64,
+        BRUSH_RANGE,
+    ) {
+        if limits::take_operation(&mut plot.players[player_idx]) {
+            plot.reset_redpiler();
+            brush.apply(&mut plot.world, &mut plot.players[player_idx], target);
+            plot.players[player_idx]
+                .worldedit_history
+                .finish(&plot.world);
+        }
+    } else {
+        player.send_error_message("No block in sight!");
+    }
+
+    plot.players[player_idx]
+        .worldedit_brushes
+        .insert(item.get_id(), brush);
+    true
+}
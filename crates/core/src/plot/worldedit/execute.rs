@@ -1,18 +1,25 @@
 use super::*;
 use crate::config::CONFIG;
 use crate::player::PacketSender;
-use crate::plot::PLOT_BLOCK_HEIGHT;
+use crate::plot::{PLOT_MAX_Y, PLOT_MIN_Y};
+use crate::schematic_http;
 use crate::utils::{self, HyphenatedUUID};
 use mchprs_blocks::block_entities::InventoryEntry;
 use mchprs_blocks::blocks::{Block, FlipDirection, RotateAmt};
 use mchprs_blocks::items::{Item, ItemStack};
 use mchprs_blocks::{BlockFace, BlockFacing, BlockPos};
 use mchprs_network::packets::clientbound::*;
-use mchprs_text::{ColorCode, TextComponentBuilder};
+use mchprs_network::PlayerPacketSender;
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
 use once_cell::sync::Lazy;
-use schematic::{load_schematic, save_schematic};
+use rustc_hash::FxHashSet;
+use schematic::{
+    index_loaded_schematic, index_saved_schematic, load_schematic, save_schematic,
+    search_schematics,
+};
+use std::net::{IpAddr, SocketAddr};
 use std::time::Instant;
-use tracing::error;
+use tracing::{debug, error};
 
 pub(super) fn execute_wand(ctx: CommandExecuteContext<'_>) {
     let item = ItemStack {
@@ -37,76 +44,294 @@ pub(super) fn execute_wand(ctx: CommandExecuteContext<'_>) {
 }
 
 pub(super) fn execute_set(ctx: CommandExecuteContext<'_>) {
-    let start_time = Instant::now();
-    let pattern = ctx.arguments[0].unwrap_pattern();
+    let pattern = ctx.arguments[0].unwrap_pattern().clone();
 
-    let mut operation = worldedit_start_operation(ctx.player);
+    let operation = worldedit_start_operation(ctx.player);
     capture_undo(
         ctx.plot,
         ctx.player,
         ctx.player.first_position.unwrap(),
         ctx.player.second_position.unwrap(),
     );
+    let job = WorldEditJob::new(ctx.plot, "set", JobKind::Set { pattern }, operation);
+    start_job(ctx.plot, ctx.player, job);
+}
+
+/// Maps positions in the selection to the coordinates used in expressions. By default the
+/// selection spans from -1 to 1 on every axis.
+struct ExpressionSpace {
+    origin: [f64; 3],
+    scale: [f64; 3],
+}
+
+impl ExpressionSpace {
+    fn new(ctx: &CommandExecuteContext<'_>, first_pos: BlockPos, second_pos: BlockPos) -> Self {
+        let min = first_pos.min(second_pos);
+        let max = first_pos.max(second_pos);
+        let center = [
+            (min.x + max.x) as f64 / 2.0,
+            (min.y + max.y) as f64 / 2.0,
+            (min.z + max.z) as f64 / 2.0,
+        ];
+        if ctx.has_flag('r') {
+            ExpressionSpace {
+                origin: [0.0; 3],
+                scale: [1.0; 3],
+            }
+        } else if ctx.has_flag('c') {
+            ExpressionSpace {
+                origin: center,
+                scale: [1.0; 3],
+            }
+        } else {
+            let half_size = |min: i32, max: i32| ((max - min) as f64 / 2.0).max(1.0);
+            ExpressionSpace {
+                origin: center,
+                scale: [
+                    half_size(min.x, max.x),
+                    half_size(min.y, max.y),
+                    half_size(min.z, max.z),
+                ],
+            }
+        }
+    }
+
+    fn to_expression(&self, pos: BlockPos) -> [f64; 3] {
+        [
+            (pos.x as f64 - self.origin[0]) / self.scale[0],
+            (pos.y as f64 - self.origin[1]) / self.scale[1],
+            (pos.z as f64 - self.origin[2]) / self.scale[2],
+        ]
+    }
+
+    fn to_world(&self, coords: [f64; 3]) -> BlockPos {
+        BlockPos::new(
+            (coords[0] * self.scale[0] + self.origin[0]).round() as i32,
+            (coords[1] * self.scale[1] + self.origin[1]).round() as i32,
+            (coords[2] * self.scale[2] + self.origin[2]).round() as i32,
+        )
+    }
+}
+
+pub(super) fn execute_generate(ctx: CommandExecuteContext<'_>) {
+    let start_time = Instant::now();
+    let pattern = ctx.arguments[0].unwrap_pattern();
+    let expression = ctx.arguments[1].unwrap_expression();
+
+    let first_pos = ctx.player.first_position.unwrap();
+    let second_pos = ctx.player.second_position.unwrap();
+    let space = ExpressionSpace::new(&ctx, first_pos, second_pos);
+
+    let mut operation = worldedit_start_operation(ctx.player);
+    capture_undo(ctx.plot, ctx.player, first_pos, second_pos);
     for x in operation.x_range() {
         for y in operation.y_range() {
             for z in operation.z_range() {
                 let block_pos = BlockPos::new(x, y, z);
-                let block_id = pattern.pick().get_id();
-
-                if ctx.plot.set_block_raw(block_pos, block_id) {
+                let mut coords = space.to_expression(block_pos);
+                if expression.eval(&mut coords) <= 0.0 {
+                    continue;
+                }
+                if ctx.plot.set_block_raw(block_pos, pattern.pick().get_id()) {
                     operation.update_block();
                 }
             }
         }
     }
 
-    let blocks_updated = operation.blocks_updated();
-
-    ctx.player.send_worldedit_message(&format!(
+    ctx.player.send_worldedit_edit_message(&format!(
         "Operation completed: {} block(s) affected ({:?})",
-        blocks_updated,
+        operation.blocks_updated(),
         start_time.elapsed()
     ));
 }
 
-pub(super) fn execute_replace(ctx: CommandExecuteContext<'_>) {
+pub(super) fn execute_deform(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
+    let expression = ctx.arguments[0].unwrap_expression();
 
-    let filter = ctx.arguments[0].unwrap_mask();
-    let pattern = ctx.arguments[1].unwrap_pattern();
+    let first_pos = ctx.player.first_position.unwrap();
+    let second_pos = ctx.player.second_position.unwrap();
+    let space = ExpressionSpace::new(&ctx, first_pos, second_pos);
 
     let mut operation = worldedit_start_operation(ctx.player);
-    capture_undo(
-        ctx.plot,
-        ctx.player,
-        ctx.player.first_position.unwrap(),
-        ctx.player.second_position.unwrap(),
-    );
+    // Every block is read before anything is changed, so that blocks aren't moved twice
+    let mut changes = Vec::new();
     for x in operation.x_range() {
         for y in operation.y_range() {
             for z in operation.z_range() {
                 let block_pos = BlockPos::new(x, y, z);
+                let mut coords = space.to_expression(block_pos);
+                expression.eval(&mut coords);
+                let source = space.to_world(coords);
+                let in_plot = Plot::in_plot_bounds(ctx.plot.x, ctx.plot.z, source.x, source.z)
+                    && (*PLOT_MIN_Y..*PLOT_MAX_Y).contains(&source.y);
+                let block_id = if in_plot {
+                    ctx.plot.get_block_raw(source)
+                } else {
+                    0
+                };
+                changes.push((block_pos, block_id));
+            }
+        }
+    }
+
+    capture_undo(ctx.plot, ctx.player, first_pos, second_pos);
+    for (block_pos, block_id) in changes {
+        if ctx.plot.set_block_raw(block_pos, block_id) {
+            ctx.plot.delete_block_entity(block_pos);
+            operation.update_block();
+        }
+    }
+
+    ctx.player.send_worldedit_edit_message(&format!(
+        "Operation completed: {} block(s) affected ({:?})",
+        operation.blocks_updated(),
+        start_time.elapsed()
+    ));
+}
 
-                if filter.matches(ctx.plot.get_block(block_pos)) {
-                    let block_id = pattern.pick().get_id();
+/// Returns every block on the line between two positions, without any diagonal gaps
+fn line_points(from: BlockPos, to: BlockPos) -> Vec<BlockPos> {
+    let d = to - from;
+    let steps = d.x.abs().max(d.y.abs()).max(d.z.abs());
+    if steps == 0 {
+        return vec![from];
+    }
+    (0..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            BlockPos::new(
+                from.x + (d.x as f64 * t).round() as i32,
+                from.y + (d.y as f64 * t).round() as i32,
+                from.z + (d.z as f64 * t).round() as i32,
+            )
+        })
+        .collect()
+}
+
+/// Returns the blocks on a Catmull-Rom spline going through all the points in order
+fn curve_points(points: &[BlockPos]) -> Vec<BlockPos> {
+    let to_vec = |pos: BlockPos| [pos.x as f64, pos.y as f64, pos.z as f64];
+    let mut blocks = vec![points[0]];
+    for i in 0..points.len() - 1 {
+        // The end points are repeated so that the curve starts and ends on them
+        let p0 = to_vec(points[i.saturating_sub(1)]);
+        let p1 = to_vec(points[i]);
+        let p2 = to_vec(points[i + 1]);
+        let p3 = to_vec(points[(i + 2).min(points.len() - 1)]);
+
+        let d = points[i + 1] - points[i];
+        let samples = d.x.abs().max(d.y.abs()).max(d.z.abs()).max(1) * 4;
+        for sample in 1..=samples {
+            let t = sample as f64 / samples as f64;
+            let [x, y, z]: [f64; 3] = std::array::from_fn(|axis| {
+                let (p0, p1, p2, p3) = (p0[axis], p1[axis], p2[axis], p3[axis]);
+                0.5 * (2.0 * p1
+                    + (p2 - p0) * t
+                    + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+                    + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+            });
+            let pos = BlockPos::new(x.round() as i32, y.round() as i32, z.round() as i32);
+            let last = *blocks.last().unwrap();
+            if pos != last {
+                // Samples can still be more than a block apart where the curve bends
+                blocks.extend(line_points(last, pos).into_iter().skip(1));
+            }
+        }
+    }
+    blocks
+}
 
-                    if ctx.plot.set_block_raw(block_pos, block_id) {
-                        operation.update_block();
+/// Sets the blocks in a sphere around each of the points to the pattern in the first argument
+fn draw_points(ctx: CommandExecuteContext<'_>, points: &[BlockPos], thickness: u32) {
+    let start_time = Instant::now();
+    let pattern = ctx.arguments[0].unwrap_pattern();
+    let radius = thickness as i32;
+    // Adding half a block makes the edges look less jagged
+    let radius_sq = (thickness as f64 + 0.5).powi(2);
+
+    let mut blocks = FxHashSet::default();
+    for &point in points {
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    if ((x * x + y * y + z * z) as f64) <= radius_sq {
+                        blocks.insert(point + BlockPos::new(x, y, z));
                     }
                 }
             }
         }
     }
 
-    let blocks_updated = operation.blocks_updated();
+    let offset = BlockPos::new(radius, radius, radius);
+    let min = points.iter().fold(points[0], |min, &pos| min.min(pos)) - offset;
+    let max = points.iter().fold(points[0], |max, &pos| max.max(pos)) + offset;
+    let mut blocks_updated = 0;
+    if let Some((min, max)) = clamp_to_plot(ctx.plot, min, max) {
+        capture_undo(ctx.plot, ctx.player, min, max);
+        for pos in blocks {
+            // Blocks outside of the plot were left out of the undo as well
+            if pos.min(min) != min || pos.max(max) != max {
+                continue;
+            }
+            ctx.plot.delete_block_entity(pos);
+            if ctx.plot.set_block_raw(pos, pattern.pick().get_id()) {
+                blocks_updated += 1;
+            }
+        }
+    }
 
-    ctx.player.send_worldedit_message(&format!(
+    ctx.player.send_worldedit_edit_message(&format!(
         "Operation completed: {} block(s) affected ({:?})",
         blocks_updated,
         start_time.elapsed()
     ));
 }
 
+pub(super) fn execute_line(ctx: CommandExecuteContext<'_>) {
+    let thickness = ctx.arguments[1].unwrap_uint();
+    let points = line_points(
+        ctx.player.first_position.unwrap(),
+        ctx.player.second_position.unwrap(),
+    );
+    draw_points(ctx, &points, thickness);
+}
+
+pub(super) fn execute_curve(ctx: CommandExecuteContext<'_>) {
+    if ctx.player.worldedit_selection_mode != SelectionMode::Convex
+        || ctx.player.worldedit_points.len() < 2
+    {
+        ctx.player.send_error_message(
+            "Select at least two points with //sel convex to draw a curve through.",
+        );
+        return;
+    }
+    let thickness = ctx.arguments[1].unwrap_uint();
+    let points = curve_points(&ctx.player.worldedit_points);
+    draw_points(ctx, &points, thickness);
+}
+
+pub(super) fn execute_replace(ctx: CommandExecuteContext<'_>) {
+    let mask = ctx.arguments[0].unwrap_mask().clone();
+    let pattern = ctx.arguments[1].unwrap_pattern().clone();
+
+    let operation = worldedit_start_operation(ctx.player);
+    capture_undo(
+        ctx.plot,
+        ctx.player,
+        ctx.player.first_position.unwrap(),
+        ctx.player.second_position.unwrap(),
+    );
+    let job = WorldEditJob::new(
+        ctx.plot,
+        "replace",
+        JobKind::Replace { mask, pattern },
+        operation,
+    );
+    start_job(ctx.plot, ctx.player, job);
+}
+
 pub(super) fn execute_count(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
 
@@ -118,7 +343,7 @@ pub(super) fn execute_count(ctx: CommandExecuteContext<'_>) {
         for y in operation.y_range() {
             for z in operation.z_range() {
                 let block_pos = BlockPos::new(x, y, z);
-                if filter.matches(ctx.plot.get_block(block_pos)) {
+                if operation.contains(block_pos) && filter.matches(ctx.plot.get_block(block_pos)) {
                     blocks_counted += 1;
                 }
             }
@@ -132,16 +357,73 @@ pub(super) fn execute_count(ctx: CommandExecuteContext<'_>) {
     ));
 }
 
+pub(super) fn execute_distr(ctx: CommandExecuteContext<'_>) {
+    let start_time = Instant::now();
+
+    let mut counts: FxHashMap<&'static str, usize> = FxHashMap::default();
+    if ctx.has_flag('c') {
+        let Some(clipboard) = &ctx.player.worldedit_clipboard else {
+            ctx.player
+                .send_error_message("Your clipboard is empty. Use //copy first.");
+            return;
+        };
+        for i in 0..clipboard.data.entries() {
+            let block = Block::from_id(clipboard.data.get_entry(i));
+            *counts.entry(block.get_name()).or_default() += 1;
+        }
+    } else {
+        if ctx.player.first_position.is_none() || ctx.player.second_position.is_none() {
+            ctx.player
+                .send_error_message("Make a region selection first.");
+            return;
+        }
+        let operation = worldedit_start_operation(ctx.player);
+        for x in operation.x_range() {
+            for y in operation.y_range() {
+                for z in operation.z_range() {
+                    let block_pos = BlockPos::new(x, y, z);
+                    if !operation.contains(block_pos) {
+                        continue;
+                    }
+                    let block = ctx.plot.get_block(block_pos);
+                    *counts.entry(block.get_name()).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let total: usize = counts.values().sum();
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    ctx.player.send_worldedit_message(&format!(
+        "Block distribution of {} block(s) ({:?})",
+        total,
+        start_time.elapsed()
+    ));
+    for (name, count) in counts {
+        let percentage = count as f64 * 100.0 / total as f64;
+        ctx.player
+            .send_worldedit_message(&format!("{:>6.2}% {} ({})", percentage, name, count));
+    }
+}
+
 pub(super) fn execute_copy(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
 
+    if !limits::check_clipboard_selection(ctx.player) {
+        return;
+    }
+
     let origin = ctx.player.pos.block_pos();
-    let clipboard = create_clipboard(
-        ctx.plot,
-        origin,
-        ctx.player.first_position.unwrap(),
-        ctx.player.second_position.unwrap(),
+    let first_pos = ctx.player.first_position.unwrap();
+    let second_pos = ctx.player.second_position.unwrap();
+    let region = Region::new(
+        ctx.player.worldedit_selection_mode,
+        &ctx.player.worldedit_points,
     );
+    let mut clipboard = create_clipboard(ctx.plot, origin, first_pos, second_pos);
+    mask_clipboard(&mut clipboard, &region, first_pos.min(second_pos));
     ctx.player.worldedit_clipboard = Some(clipboard);
 
     ctx.player.send_worldedit_message(&format!(
@@ -153,15 +435,24 @@ pub(super) fn execute_copy(ctx: CommandExecuteContext<'_>) {
 pub(super) fn execute_cut(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
 
+    if !limits::check_clipboard_selection(ctx.player) {
+        return;
+    }
+
     let first_pos = ctx.player.first_position.unwrap();
     let second_pos = ctx.player.second_position.unwrap();
 
     capture_undo(ctx.plot, ctx.player, first_pos, second_pos);
 
     let origin = ctx.player.pos.block_pos();
-    let clipboard = create_clipboard(ctx.plot, origin, first_pos, second_pos);
+    let region = Region::new(
+        ctx.player.worldedit_selection_mode,
+        &ctx.player.worldedit_points,
+    );
+    let mut clipboard = create_clipboard(ctx.plot, origin, first_pos, second_pos);
+    mask_clipboard(&mut clipboard, &region, first_pos.min(second_pos));
     ctx.player.worldedit_clipboard = Some(clipboard);
-    clear_area(ctx.plot, first_pos, second_pos);
+    clear_area(ctx.plot, first_pos, second_pos, &region);
 
     ctx.player.send_worldedit_message(&format!(
         "Your selection was cut. ({:?})",
@@ -194,10 +485,10 @@ pub(super) fn execute_move(mut ctx: CommandExecuteContext<'_>) {
         plot_x: ctx.plot.x,
         plot_z: ctx.plot.z,
     };
-    ctx.player.worldedit_undo.push(undo);
+    ctx.player.worldedit_history.record(ctx.plot, undo);
 
     let clipboard = create_clipboard(ctx.plot, zero_pos, first_pos, second_pos);
-    clear_area(ctx.plot, first_pos, second_pos);
+    clear_area(ctx.plot, first_pos, second_pos, &Region::Cuboid);
     paste_clipboard(
         ctx.plot,
         &clipboard,
@@ -220,55 +511,154 @@ pub(super) fn execute_move(mut ctx: CommandExecuteContext<'_>) {
 }
 
 pub(super) fn execute_paste(ctx: CommandExecuteContext<'_>) {
-    let start_time = Instant::now();
+    let Some(mut clipboard) = ctx.player.worldedit_clipboard.clone() else {
+        ctx.player.send_system_message("Your clipboard is empty!");
+        return;
+    };
 
-    if ctx.player.worldedit_clipboard.is_some() {
-        // Here I am cloning the clipboard. This is bad. Don't do this.
-        let cb = &ctx.player.worldedit_clipboard.clone().unwrap();
-        let pos = ctx.player.pos.block_pos();
-        let offset_x = pos.x - cb.offset_x;
-        let offset_y = pos.y - cb.offset_y;
-        let offset_z = pos.z - cb.offset_z;
-        let first_pos = BlockPos::new(offset_x, offset_y, offset_z);
-        let second_pos = BlockPos::new(
-            offset_x + cb.size_x as i32,
-            offset_y + cb.size_y as i32,
-            offset_z + cb.size_z as i32,
-        );
-        capture_undo(ctx.plot, ctx.player, first_pos, second_pos);
-        paste_clipboard(ctx.plot, cb, pos, ctx.has_flag('a'));
-        if ctx.has_flag('u') {
-            update(ctx.plot, first_pos, second_pos);
-        }
-        ctx.player.send_worldedit_message(&format!(
-            "Your clipboard was pasted. ({:?})",
-            start_time.elapsed()
-        ));
+    let pos = if ctx.has_flag('o') {
+        let Some(pos) = clipboard.copied_at else {
+            ctx.player
+                .send_error_message("The clipboard wasn't copied from the world.");
+            return;
+        };
+        pos
     } else {
-        ctx.player.send_system_message("Your clipboard is empty!");
+        ctx.player.pos.block_pos()
+    };
+
+    // The clipboard is rotated first and then flipped, the same as running //rotate and then
+    // //flip before pasting
+    if let Some(degrees) = ctx.flag_argument('r').map(Argument::unwrap_uint) {
+        match rotation_from_degrees(degrees) {
+            Ok(Some(rotate_amt)) => clipboard = rotate_clipboard(&clipboard, rotate_amt),
+            Ok(None) => {}
+            Err(()) => {
+                ctx.player
+                    .send_error_message("Rotate amount must be a multiple of 90.");
+                return;
+            }
+        }
+    }
+    if let Some(direction) = ctx.flag_argument('f').map(Argument::unwrap_direction) {
+        clipboard = flip_clipboard(&clipboard, direction);
+    }
+
+    let first_pos = BlockPos::new(
+        pos.x - clipboard.offset_x,
+        pos.y - clipboard.offset_y,
+        pos.z - clipboard.offset_z,
+    );
+    let second_pos = first_pos
+        + BlockPos::new(
+            clipboard.size_x as i32 - 1,
+            clipboard.size_y as i32 - 1,
+            clipboard.size_z as i32 - 1,
+        );
+    capture_undo(ctx.plot, ctx.player, first_pos, second_pos);
+
+    let operation = WorldEditOperation::new(first_pos, second_pos, Region::Cuboid);
+    let kind = JobKind::Paste {
+        clipboard,
+        skip_air: ctx.has_flag('a'),
+        update: ctx.has_flag('u'),
+    };
+    let job = WorldEditJob::new(ctx.plot, "paste", kind, operation);
+    start_job(ctx.plot, ctx.player, job);
+}
+
+pub(super) fn execute_cancel(ctx: CommandExecuteContext<'_>) {
+    if !cancel_player_job(ctx.plot, ctx.player) {
+        ctx.player
+            .send_error_message("You don't have an operation running.");
     }
 }
 
+/// Schematic names can be inside of folders, but never outside of the schematic folder
 static SCHEMATI_VALIDATE_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"[a-zA-Z0-9_.]+\.schem(atic)?").unwrap());
+    Lazy::new(|| Regex::new(r"^(?:[a-zA-Z0-9_-]+/)*[a-zA-Z0-9_.-]+\.schem(?:atic)?$").unwrap());
 
-pub(super) fn execute_load(ctx: CommandExecuteContext<'_>) {
-    let start_time = Instant::now();
+/// The folder inside of the schematic folder that holds the server-wide schematic library
+const LIBRARY_FOLDER: &str = "library/";
 
-    let mut file_name = ctx.arguments[0].unwrap_string().clone();
-    if !SCHEMATI_VALIDATE_REGEX.is_match(&file_name) {
+/// Resolves the schematic name a player gave to its path inside of the schematic folder. Library
+/// schematics are used with the `-g` flag, everything else is the player's own schematic.
+/// Sends the player an error and returns `None` if the name can't be used.
+fn schematic_path(ctx: &CommandExecuteContext<'_>, name: &str) -> Option<String> {
+    if !SCHEMATI_VALIDATE_REGEX.is_match(name) {
         ctx.player.send_error_message("Filename is invalid");
-        return;
+        return None;
+    }
+
+    if ctx.has_flag('g') {
+        Some(format!("{}{}", LIBRARY_FOLDER, name))
+    } else if CONFIG.schemati {
+        Some(format!("{}/{}", HyphenatedUUID(ctx.player.uuid), name))
+    } else if name.starts_with(LIBRARY_FOLDER) {
+        // Otherwise the library permissions could be skipped by naming the folder directly
+        ctx.player
+            .send_error_message("Use the -g flag to use schematics from the library.");
+        None
+    } else {
+        Some(name.to_owned())
+    }
+}
+
+/// The names of the player's schematics that start with `prefix`, or those of the library
+/// schematics if `library` is set, for suggesting them while a schematic name is typed
+pub fn schematic_suggestions(player: &Player, library: bool, prefix: &str) -> Vec<String> {
+    let root = if library {
+        LIBRARY_FOLDER.to_owned()
+    } else if CONFIG.schemati {
+        HyphenatedUUID(player.uuid).to_string() + "/"
+    } else {
+        String::new()
+    };
+    search_schematics(&root, "")
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with(prefix))
+        // Without schemati the library is inside of the folder everyone's schematics are in
+        .filter(|name| library || !name.starts_with(LIBRARY_FOLDER))
+        .collect()
+}
+
+/// Checks the per-schematic permission for using a library schematic, which looks like
+/// `worldedit.schematic.library.load.cpu.alu` for `library/cpu/alu.schem`. Schematics outside of
+/// the library need no extra permission.
+fn has_library_permission(ctx: &CommandExecuteContext<'_>, action: &str, path: &str) -> bool {
+    let Some(name) = path.strip_prefix(LIBRARY_FOLDER) else {
+        return true;
+    };
+    let name = name
+        .trim_end_matches(".schematic")
+        .trim_end_matches(".schem")
+        .replace('/', ".");
+    let node = format!("worldedit.schematic.library.{}.{}", action, name);
+    if !ctx.player.has_permission(&node) {
+        ctx.player.send_no_permission_message();
+        return false;
     }
+    true
+}
 
-    if CONFIG.schemati {
-        let prefix = HyphenatedUUID(ctx.player.uuid).to_string() + "/";
-        file_name.insert_str(0, &prefix);
+pub(super) fn execute_load(ctx: CommandExecuteContext<'_>) {
+    let start_time = Instant::now();
+
+    let Some(file_name) = schematic_path(&ctx, ctx.arguments[0].unwrap_string()) else {
+        return;
+    };
+    if !has_library_permission(&ctx, "load", &file_name) {
+        return;
     }
 
     let clipboard = load_schematic(&file_name);
     match clipboard {
         Ok(cb) => {
+            index_loaded_schematic(&file_name, &cb);
+            if !limits::check_clipboard_volume(ctx.player, &cb) {
+                return;
+            }
             ctx.player.worldedit_clipboard = Some(cb);
             ctx.player.send_worldedit_message(&format!(
                 "The schematic was loaded to your clipboard. Do //paste to birth it into the world. ({:?})",
@@ -292,23 +682,86 @@ pub(super) fn execute_load(ctx: CommandExecuteContext<'_>) {
     }
 }
 
+fn parse_import_corner(arg: &str) -> Option<BlockPos> {
+    let mut coords = arg.split(',').map(|coord| coord.trim().parse::<i32>());
+    let pos = BlockPos::new(
+        coords.next()?.ok()?,
+        coords.next()?.ok()?,
+        coords.next()?.ok()?,
+    );
+    coords.next().is_none().then_some(pos)
+}
+
+pub(super) fn execute_importworld(ctx: CommandExecuteContext<'_>) {
+    let start_time = Instant::now();
+
+    let world = ctx.arguments[0].unwrap_string();
+    let (Some(first_pos), Some(second_pos)) = (
+        parse_import_corner(ctx.arguments[1].unwrap_string()),
+        parse_import_corner(ctx.arguments[2].unwrap_string()),
+    ) else {
+        ctx.player
+            .send_error_message("Corners have to be given as x,y,z, for example 0,-64,0");
+        return;
+    };
+    let Some((size_x, size_y, size_z)) = anvil::import_size(first_pos, second_pos) else {
+        ctx.player.send_error_message(&format!(
+            "At most {} blocks can be imported at once.",
+            anvil::MAX_IMPORT_VOLUME
+        ));
+        return;
+    };
+    let volume = size_x as u64 * size_y as u64 * size_z as u64;
+    if !limits::check_clipboard(ctx.player, volume) {
+        return;
+    }
+
+    match anvil::import_world(world, first_pos, second_pos) {
+        Ok(cb) => {
+            ctx.player.worldedit_clipboard = Some(cb);
+            ctx.player.send_worldedit_message(&format!(
+                "The area was imported to your clipboard. Do //paste to birth it into the world. ({:?})",
+                start_time.elapsed()
+            ));
+        }
+        Err(e) => {
+            if let Some(e) = e.downcast_ref::<std::io::Error>() {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    let msg = "The specified world could not be found in the imports folder.";
+                    ctx.player.send_error_message(msg);
+                    return;
+                }
+            }
+            error!("There was an error importing a world:");
+            error!("{:#}", e);
+            ctx.player.send_error_message(
+                "There was an error importing the world. Check console for more details.",
+            );
+        }
+    }
+}
+
 pub(super) fn execute_save(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
 
-    let mut file_name = ctx.arguments[0].unwrap_string().clone();
-    if !SCHEMATI_VALIDATE_REGEX.is_match(&file_name) {
-        ctx.player.send_error_message("Filename is invalid");
+    let Some(file_name) = schematic_path(&ctx, ctx.arguments[0].unwrap_string()) else {
+        return;
+    };
+    if !has_library_permission(&ctx, "save", &file_name) {
         return;
     }
 
-    if CONFIG.schemati {
-        let prefix = HyphenatedUUID(ctx.player.uuid).to_string() + "/";
-        file_name.insert_str(0, &prefix);
+    let version = ctx.arguments[1].unwrap_uint();
+    if !matches!(version, 2 | 3) {
+        ctx.player
+            .send_error_message("Only schematic versions 2 and 3 can be saved.");
+        return;
     }
 
     let clipboard = ctx.player.worldedit_clipboard.as_ref().unwrap();
-    match save_schematic(&file_name, clipboard) {
+    match save_schematic(&file_name, clipboard, version) {
         Ok(_) => {
+            index_saved_schematic(&file_name, &ctx.player.username, clipboard);
             ctx.player.send_worldedit_message(&format!(
                 "The schematic was saved sucessfuly. ({:?})",
                 start_time.elapsed()
@@ -323,6 +776,284 @@ pub(super) fn execute_save(ctx: CommandExecuteContext<'_>) {
     }
 }
 
+const SCHEMATICS_PER_PAGE: usize = 10;
+
+pub(super) fn execute_schem_list(ctx: CommandExecuteContext<'_>) {
+    let page = ctx.arguments[0].unwrap_uint().max(1) as usize;
+    let filter = ctx.arguments[1].unwrap_string();
+    // Everything up to the last slash is the folder to list, the rest is searched for
+    let (folder, search) = match filter.rsplit_once('/') {
+        Some((folder, search)) => (format!("{}/", folder), search),
+        None => (String::new(), filter.as_str()),
+    };
+    let root = if ctx.has_flag('g') {
+        LIBRARY_FOLDER.to_owned()
+    } else if CONFIG.schemati {
+        HyphenatedUUID(ctx.player.uuid).to_string() + "/"
+    } else {
+        String::new()
+    };
+
+    let schematics = search_schematics(&(root + folder.as_str()), search);
+    if schematics.is_empty() {
+        ctx.player.send_error_message("No schematics were found.");
+        return;
+    }
+    let pages = schematics.len().div_ceil(SCHEMATICS_PER_PAGE);
+    if page > pages {
+        ctx.player
+            .send_error_message(&format!("There are only {} page(s).", pages));
+        return;
+    }
+
+    ctx.player.send_worldedit_message(&format!(
+        "{} schematic(s), page {}/{}:",
+        schematics.len(),
+        page,
+        pages
+    ));
+    for (name, metadata) in schematics
+        .iter()
+        .skip((page - 1) * SCHEMATICS_PER_PAGE)
+        .take(SCHEMATICS_PER_PAGE)
+    {
+        let [size_x, size_y, size_z] = metadata.size;
+        let author = metadata.author.as_deref().unwrap_or("unknown author");
+        let created = chrono::DateTime::from_timestamp(metadata.created as i64, 0)
+            .map_or_else(String::new, |date| date.format("%Y-%m-%d").to_string());
+        let load_flags = if ctx.has_flag('g') { "-g " } else { "" };
+        ctx.player.send_raw_system_message(
+            TextComponentBuilder::new(format!(
+                "{}{} - {}x{}x{} by {} on {}",
+                folder, name, size_x, size_y, size_z, author, created
+            ))
+            .color_code(ColorCode::LightPurple)
+            .click_event(ClickEvent::suggest_command(format!(
+                "//schem load {}{}{}",
+                load_flags, folder, name
+            )))
+            .hover_text("Click to load this schematic")
+            .finish(),
+        );
+    }
+}
+
+pub(super) fn execute_schem_download(ctx: CommandExecuteContext<'_>) {
+    let Some(file_name) = schematic_path(&ctx, ctx.arguments[0].unwrap_string()) else {
+        return;
+    };
+    if !has_library_permission(&ctx, "load", &file_name) {
+        return;
+    }
+
+    if !std::path::Path::new("./schems").join(&file_name).is_file() {
+        ctx.player
+            .send_error_message("The specified schematic file could not be found.");
+        return;
+    }
+
+    let Some(url) = schematic_http::create_download_link(&file_name) else {
+        ctx.player
+            .send_error_message("Schematic downloads are not enabled on this server.");
+        return;
+    };
+    ctx.player
+        .send_chat_message(&TextComponent::from_legacy_text(&format!(
+            "&dThe schematic can be downloaded once from: {}",
+            url
+        )));
+}
+
+pub(super) fn execute_schem_upload(ctx: CommandExecuteContext<'_>) {
+    let Some(config) = &CONFIG.schematic_http else {
+        ctx.player
+            .send_error_message("Schematic uploads are not enabled on this server.");
+        return;
+    };
+    let max_size = config.max_upload_size;
+
+    let url = match reqwest::Url::parse(ctx.arguments[0].unwrap_string()) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => {
+            ctx.player.send_error_message("The url is invalid.");
+            return;
+        }
+    };
+
+    // Default to the last part of the url as the file name
+    let mut name = ctx.arguments[1].unwrap_string().clone();
+    if name.is_empty() {
+        name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_owned();
+    }
+    let Some(file_name) = schematic_path(&ctx, &name) else {
+        return;
+    };
+
+    let username = ctx.player.username.clone();
+    let packet_sender = PlayerPacketSender::new(&ctx.player.client);
+    ctx.player
+        .send_worldedit_message("Downloading the schematic...");
+    // Plot threads run inside of their async runtime, so the download can be spawned onto it
+    tokio::spawn(async move {
+        let data = match download_schematic(url, max_size).await {
+            Ok(data) => data,
+            Err(DownloadError::TooLarge) => {
+                packet_sender.send_error_message(&format!(
+                    "The schematic is larger than {} bytes.",
+                    max_size
+                ));
+                return;
+            }
+            // The reason isn't shown, so that uploads can't be used to find out which hosts and
+            // ports the server can reach
+            Err(DownloadError::Failed(err)) => {
+                debug!("Failed to download schematic: {:?}", err);
+                packet_sender.send_error_message("There was an error downloading the schematic.");
+                return;
+            }
+        };
+
+        // The schematic is only moved into place once it's known to be valid, so that a bad
+        // upload doesn't replace an existing schematic
+        let upload_name = file_name.clone() + ".upload";
+        let upload_path = std::path::Path::new("./schems").join(&upload_name);
+        let result = upload_path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&upload_path, data));
+        if let Err(err) = result {
+            error!("There was an error saving an uploaded schematic: {}", err);
+            packet_sender.send_error_message("There was an error saving the schematic.");
+            return;
+        }
+        let clipboard = match load_schematic(&upload_name) {
+            Ok(clipboard) => clipboard,
+            Err(err) => {
+                let _ = std::fs::remove_file(&upload_path);
+                packet_sender
+                    .send_error_message(&format!("The file is not a valid schematic: {}", err));
+                return;
+            }
+        };
+        if let Err(err) = std::fs::rename(&upload_path, format!("./schems/{}", file_name)) {
+            error!("There was an error saving an uploaded schematic: {}", err);
+            packet_sender.send_error_message("There was an error saving the schematic.");
+            return;
+        }
+
+        index_saved_schematic(&file_name, &username, &clipboard);
+        packet_sender.send_raw_system_message(
+            TextComponentBuilder::new("The schematic was uploaded sucessfully.".to_string())
+                .color_code(ColorCode::LightPurple)
+                .finish(),
+        );
+    });
+}
+
+enum DownloadError {
+    TooLarge,
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for DownloadError {
+    fn from(err: anyhow::Error) -> Self {
+        DownloadError::Failed(err)
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        DownloadError::Failed(err.into())
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(err: std::io::Error) -> Self {
+        DownloadError::Failed(err.into())
+    }
+}
+
+/// Whether players may make the server connect to the address. Anything on the server's own
+/// network, like services on loopback or cloud metadata endpoints, is off limits.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8 and the shared address space of carrier-grade NAT, 100.64.0.0/10
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local, fc00::/7, and link-local, fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Downloads the file at the url, as long as it isn't larger than `max_size`. The host is
+/// resolved once and checked with `is_public_address`, and the connection is made to exactly
+/// that address so that a second lookup can't point it somewhere else.
+async fn download_schematic(url: reqwest::Url, max_size: u64) -> Result<Vec<u8>, DownloadError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("the url has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_owned();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow::anyhow!("the url has no port"))?;
+    let addr: SocketAddr = tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} could not be resolved", host))?;
+    if !is_public_address(addr.ip()) {
+        return Err(anyhow::anyhow!("{} resolves to the non-public address {}", host, addr).into());
+    }
+
+    let client = reqwest::Client::builder()
+        .resolve(&host, addr)
+        // Redirects and proxies would connect to addresses that weren't checked
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .build()?;
+    let mut response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("the server responded with {}", response.status()).into());
+    }
+    if response.content_length().is_some_and(|len| len > max_size) {
+        return Err(DownloadError::TooLarge);
+    }
+    // The content length can't be trusted, so the limit is checked while reading as well
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() as u64 > max_size {
+            return Err(DownloadError::TooLarge);
+        }
+    }
+    Ok(data)
+}
+
 pub(super) fn execute_stack(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
 
@@ -354,7 +1085,15 @@ pub(super) fn execute_stack(ctx: CommandExecuteContext<'_>) {
         plot_x: ctx.plot.x,
         plot_z: ctx.plot.z,
     };
-    ctx.player.worldedit_undo.push(undo);
+    ctx.player.worldedit_history.record(ctx.plot, undo);
+
+    if ctx.has_flag('s') {
+        let offset = (stack_amt * stack_offset) as i32;
+        ctx.player
+            .worldedit_set_first_position(direction.offset_pos(pos1, offset));
+        ctx.player
+            .worldedit_set_second_position(direction.offset_pos(pos2, offset));
+    }
 
     ctx.player.send_worldedit_message(&format!(
         "Your selection was stacked. ({:?})",
@@ -363,87 +1102,55 @@ pub(super) fn execute_stack(ctx: CommandExecuteContext<'_>) {
 }
 
 pub(super) fn execute_undo(ctx: CommandExecuteContext<'_>) {
-    if ctx.player.worldedit_undo.is_empty() {
-        ctx.player
-            .send_error_message("There is nothing left to undo.");
-        return;
-    }
-    let undo = ctx.player.worldedit_undo.pop().unwrap();
-    if undo.plot_x != ctx.plot.x || undo.plot_z != ctx.plot.z {
-        ctx.player
-            .send_error_message("Cannot undo outside of your current plot.");
-        return;
-    }
-    let redo = WorldEditUndo {
-        clipboards: undo
-            .clipboards
-            .iter()
-            .map(|clipboard| {
-                let first_pos = BlockPos {
-                    x: undo.pos.x - clipboard.offset_x,
-                    y: undo.pos.y - clipboard.offset_y,
-                    z: undo.pos.z - clipboard.offset_z,
-                };
-                let second_pos = BlockPos {
-                    x: first_pos.x + clipboard.size_x as i32 - 1,
-                    y: first_pos.y + clipboard.size_y as i32 - 1,
-                    z: first_pos.z + clipboard.size_z as i32 - 1,
-                };
-                create_clipboard(ctx.plot, undo.pos, first_pos, second_pos)
-            })
-            .collect(),
-        ..undo
+    let message = match ctx.player.worldedit_history.undo(ctx.plot) {
+        Ok(()) => return,
+        Err(RestoreError::Empty) => "There is nothing left to undo.",
+        Err(RestoreError::OtherPlot) => "Cannot undo outside of your current plot.",
+        Err(RestoreError::Unreadable) => "Your undo history could not be read.",
     };
-    for clipboard in &undo.clipboards {
-        paste_clipboard(ctx.plot, clipboard, undo.pos, false);
-    }
-    ctx.player.worldedit_redo.push(redo);
+    ctx.player.send_error_message(message);
 }
 
 pub(super) fn execute_redo(ctx: CommandExecuteContext<'_>) {
-    if ctx.player.worldedit_redo.is_empty() {
-        ctx.player
-            .send_error_message("There is nothing left to redo.");
-        return;
-    }
-    let redo = ctx.player.worldedit_redo.pop().unwrap();
-    if redo.plot_x != ctx.plot.x || redo.plot_z != ctx.plot.z {
-        ctx.player
-            .send_error_message("Cannot redo outside of your current plot.");
-        return;
-    }
-    let undo = WorldEditUndo {
-        clipboards: redo
-            .clipboards
-            .iter()
-            .map(|clipboard| {
-                let first_pos = BlockPos {
-                    x: redo.pos.x - clipboard.offset_x,
-                    y: redo.pos.y - clipboard.offset_y,
-                    z: redo.pos.z - clipboard.offset_z,
-                };
-                let second_pos = BlockPos {
-                    x: first_pos.x + clipboard.size_x as i32 - 1,
-                    y: first_pos.y + clipboard.size_y as i32 - 1,
-                    z: first_pos.z + clipboard.size_z as i32 - 1,
-                };
-                create_clipboard(ctx.plot, redo.pos, first_pos, second_pos)
-            })
-            .collect(),
-        ..redo
+    let message = match ctx.player.worldedit_history.redo(ctx.plot) {
+        Ok(()) => return,
+        Err(RestoreError::Empty) => "There is nothing left to redo.",
+        Err(RestoreError::OtherPlot) => "Cannot redo outside of your current plot.",
+        Err(RestoreError::Unreadable) => "Your redo history could not be read.",
     };
-    for clipboard in &redo.clipboards {
-        paste_clipboard(ctx.plot, clipboard, redo.pos, false);
-    }
-    ctx.player.worldedit_undo.push(undo);
+    ctx.player.send_error_message(message);
 }
 
 pub(super) fn execute_sel(ctx: CommandExecuteContext<'_>) {
     let player = ctx.player;
+    let selector = ctx.arguments[0].unwrap_string();
+    if !selector.is_empty() {
+        let Ok(mode) = selector.parse() else {
+            player.send_error_message("Unknown selector. Use cuboid, poly, cyl or convex.");
+            return;
+        };
+        player.worldedit_selection_mode = mode;
+    }
     player.first_position = None;
     player.second_position = None;
-    player.send_worldedit_message("Selection cleared.");
-    player.worldedit_send_cui("s|cuboid");
+    player.worldedit_points.clear();
+    match player.worldedit_selection_mode {
+        _ if selector.is_empty() => player.send_worldedit_message("Selection cleared."),
+        SelectionMode::Cuboid => player.send_worldedit_message(
+            "Cuboid selector: left click for the first position, right click for the second.",
+        ),
+        SelectionMode::Poly => player.send_worldedit_message(
+            "2D polygon selector: left click to start a selection, right click to add vertices.",
+        ),
+        SelectionMode::Cylinder => player.send_worldedit_message(
+            "Cylindrical selector: left click to set the center, right click to extend the radius.",
+        ),
+        SelectionMode::Convex => player.send_worldedit_message(
+            "Convex selector: left click to start a selection, right click to add vertices.",
+        ),
+    }
+    let mode = player.worldedit_selection_mode;
+    player.worldedit_send_cui(&format!("s|{}", mode.cui_name()));
 }
 
 pub(super) fn execute_pos1(ctx: CommandExecuteContext<'_>) {
@@ -484,6 +1191,173 @@ pub(super) fn execute_hpos2(mut ctx: CommandExecuteContext<'_>) {
     }
 }
 
+pub(super) fn execute_clipboard(ctx: CommandExecuteContext<'_>) {
+    let player = ctx.player;
+    match ctx.arguments[0].unwrap_string().as_str() {
+        "share" => {
+            let other_username = ctx.arguments[1].unwrap_string();
+            if other_username.is_empty() {
+                player.send_error_message("Specify the player to share your clipboard with.");
+                return;
+            }
+            let Some(clipboard) = player.worldedit_clipboard.clone() else {
+                player.send_error_message("Your clipboard is empty. Use //copy first.");
+                return;
+            };
+            let _ = ctx.message_sender.send(Message::ShareClipboard(
+                player.username.clone(),
+                other_username.clone(),
+                clipboard,
+                PlayerPacketSender::new(&player.client),
+            ));
+        }
+        "accept" => {
+            let Some((username, clipboard)) = player.worldedit_shared_clipboard.take() else {
+                player.send_error_message("Nobody has shared a clipboard with you.");
+                return;
+            };
+            if !limits::check_clipboard_volume(player, &clipboard) {
+                return;
+            }
+            player.worldedit_clipboard = Some(clipboard);
+            player.send_worldedit_message(&format!(
+                "The clipboard shared by {} is now your clipboard.",
+                username
+            ));
+        }
+        _ => player.send_error_message("Unknown action. Use either share or accept."),
+    }
+}
+
+pub(super) fn execute_visualize(ctx: CommandExecuteContext<'_>) {
+    let player = ctx.player;
+    let (name, enabled) = match ctx.arguments[0].unwrap_string().as_str() {
+        "selection" => {
+            player.worldedit_show_selection = !player.worldedit_show_selection;
+            ("Selection outline", player.worldedit_show_selection)
+        }
+        "clipboard" => {
+            player.worldedit_show_clipboard = !player.worldedit_show_clipboard;
+            ("Paste preview", player.worldedit_show_clipboard)
+        }
+        "off" => {
+            player.worldedit_show_selection = false;
+            player.worldedit_show_clipboard = false;
+            player.send_worldedit_message("All visualizations disabled.");
+            return;
+        }
+        _ => {
+            player.send_error_message("Unknown target. Use either selection, clipboard or off.");
+            return;
+        }
+    };
+    let state = if enabled { "enabled" } else { "disabled" };
+    player.send_worldedit_message(&format!("{} {}.", name, state));
+}
+
+fn bind_brush(player: &mut Player, shape: BrushShape, size: u32) {
+    let Some(item) = brush::brush_item(player) else {
+        player.send_error_message("Hold the item you want to bind the brush to.");
+        return;
+    };
+    if item.is_block() || item == (Item::WEWand {}) {
+        player.send_error_message("Brushes can't be bound to blocks or the wand.");
+        return;
+    }
+    let name = shape.name();
+    player
+        .worldedit_brushes
+        .insert(item.get_id(), WorldEditBrush::new(shape, size));
+    player.send_worldedit_message(&format!(
+        "{} brush bound to {}. Right click to use it.",
+        name,
+        item.get_name()
+    ));
+}
+
+/// Gets the brush bound to the held item, or tells the player that there is none
+fn held_brush(player: &mut Player) -> Option<&mut WorldEditBrush> {
+    let id = brush::brush_item(player)
+        .map(Item::get_id)
+        .filter(|id| player.worldedit_brushes.contains_key(id));
+    let Some(id) = id else {
+        player.send_error_message("There is no brush bound to the item you are holding.");
+        return None;
+    };
+    player.worldedit_brushes.get_mut(&id)
+}
+
+pub(super) fn execute_brush_sphere(ctx: CommandExecuteContext<'_>) {
+    let pattern = ctx.arguments[0].unwrap_pattern().clone();
+    let radius = ctx.arguments[1].unwrap_uint();
+    bind_brush(ctx.player, BrushShape::Sphere { pattern }, radius);
+}
+
+pub(super) fn execute_brush_cyl(ctx: CommandExecuteContext<'_>) {
+    let pattern = ctx.arguments[0].unwrap_pattern().clone();
+    let radius = ctx.arguments[1].unwrap_uint();
+    let height = ctx.arguments[2].unwrap_uint();
+    bind_brush(ctx.player, BrushShape::Cylinder { pattern, height }, radius);
+}
+
+pub(super) fn execute_brush_paste(ctx: CommandExecuteContext<'_>) {
+    let clipboard = ctx.player.worldedit_clipboard.clone().unwrap();
+    let skip_air = ctx.has_flag('a');
+    bind_brush(
+        ctx.player,
+        BrushShape::Paste {
+            clipboard,
+            skip_air,
+        },
+        0,
+    );
+}
+
+pub(super) fn execute_brush_size(ctx: CommandExecuteContext<'_>) {
+    let size = ctx.arguments[0].unwrap_uint();
+    if let Some(brush) = held_brush(ctx.player) {
+        brush.size = size;
+        ctx.player
+            .send_worldedit_message(&format!("Brush size set to {}.", size));
+    }
+}
+
+pub(super) fn execute_brush_mask(ctx: CommandExecuteContext<'_>) {
+    let mask = ctx.arguments[0].unwrap_string();
+    let mask = if mask.is_empty() {
+        None
+    } else {
+        match WorldEditMask::from_str(mask) {
+            Ok(mask) => Some(mask),
+            Err(err) => {
+                ctx.player
+                    .send_error_message(&format!("Error parsing mask: {}", err));
+                return;
+            }
+        }
+    };
+    if let Some(brush) = held_brush(ctx.player) {
+        let message = if mask.is_some() {
+            "Brush mask set."
+        } else {
+            "Brush mask cleared."
+        };
+        brush.mask = mask;
+        ctx.player.send_worldedit_message(message);
+    }
+}
+
+pub(super) fn execute_brush_none(ctx: CommandExecuteContext<'_>) {
+    let player = ctx.player;
+    let removed =
+        brush::brush_item(player).and_then(|item| player.worldedit_brushes.remove(&item.get_id()));
+    if removed.is_some() {
+        player.send_worldedit_message("Brush unbound from the held item.");
+    } else {
+        player.send_error_message("There is no brush bound to the item you are holding.");
+    }
+}
+
 pub(super) fn execute_expand(ctx: CommandExecuteContext<'_>) {
     let amount = ctx.arguments[0].unwrap_uint();
     let direction = ctx.arguments[1].unwrap_direction();
@@ -516,6 +1390,14 @@ pub(super) fn execute_shift(ctx: CommandExecuteContext<'_>) {
     let amount = ctx.arguments[0].unwrap_uint();
     let direction = ctx.arguments[1].unwrap_direction();
     let player = ctx.player;
+    if player.worldedit_selection_mode != SelectionMode::Cuboid {
+        selection::shift_points(
+            player,
+            direction.offset_pos(BlockPos::zero(), amount as i32),
+        );
+        player.send_worldedit_message(&format!("Region shifted {} block(s).", amount));
+        return;
+    }
     let first_pos = player.first_position.unwrap();
     let second_pos = player.second_position.unwrap();
 
@@ -549,6 +1431,15 @@ pub(super) fn execute_flip(ctx: CommandExecuteContext<'_>) {
 
     let direction = ctx.arguments[0].unwrap_direction();
     let clipboard = ctx.player.worldedit_clipboard.as_ref().unwrap();
+    ctx.player.worldedit_clipboard = Some(flip_clipboard(clipboard, direction));
+    ctx.player.send_worldedit_message(&format!(
+        "The clipboard copy has been flipped. ({:?})",
+        start_time.elapsed()
+    ));
+}
+
+/// Mirrors the clipboard along the axis of the direction
+fn flip_clipboard(clipboard: &WorldEditClipboard, direction: BlockFacing) -> WorldEditClipboard {
     let size_x = clipboard.size_x;
     let size_y = clipboard.size_y;
     let size_z = clipboard.size_z;
@@ -580,7 +1471,7 @@ pub(super) fn execute_flip(ctx: CommandExecuteContext<'_>) {
         match direction {
             BlockFacing::East | BlockFacing::West => block.flip(FlipDirection::FlipX),
             BlockFacing::North | BlockFacing::South => block.flip(FlipDirection::FlipZ),
-            _ => {}
+            BlockFacing::Up | BlockFacing::Down => block.flip(FlipDirection::FlipY),
         }
         newcpdata.set_entry(n_i as usize, block.get_id());
 
@@ -603,7 +1494,7 @@ pub(super) fn execute_flip(ctx: CommandExecuteContext<'_>) {
         clipboard.offset_y,
         clipboard.offset_z,
     ));
-    let cb = WorldEditClipboard {
+    WorldEditClipboard {
         offset_x: offset.x,
         offset_y: offset.y,
         offset_z: offset.z,
@@ -616,28 +1507,32 @@ pub(super) fn execute_flip(ctx: CommandExecuteContext<'_>) {
             .iter()
             .map(|(pos, e)| (flip_pos(*pos), e.clone()))
             .collect(),
-    };
+        copied_at: clipboard.copied_at,
+    }
+}
 
-    ctx.player.worldedit_clipboard = Some(cb);
-    ctx.player.send_worldedit_message(&format!(
-        "The clipboard copy has been flipped. ({:?})",
-        start_time.elapsed()
-    ));
+/// Converts degrees to a rotation. Returns `Ok(None)` if the clipboard doesn't need to be rotated
+/// and `Err` if the degrees aren't a multiple of 90.
+fn rotation_from_degrees(degrees: u32) -> Result<Option<RotateAmt>, ()> {
+    match degrees % 360 {
+        0 => Ok(None),
+        90 => Ok(Some(RotateAmt::Rotate90)),
+        180 => Ok(Some(RotateAmt::Rotate180)),
+        270 => Ok(Some(RotateAmt::Rotate270)),
+        _ => Err(()),
+    }
 }
 
 pub(super) fn execute_rotate(ctx: CommandExecuteContext<'_>) {
     let start_time = Instant::now();
-    let rotate_amt = ctx.arguments[0].unwrap_uint();
-    let rotate_amt = match rotate_amt % 360 {
-        0 => {
+    let rotate_amt = match rotation_from_degrees(ctx.arguments[0].unwrap_uint()) {
+        Ok(Some(rotate_amt)) => rotate_amt,
+        Ok(None) => {
             ctx.player
                 .send_worldedit_message("Successfully rotated by 0! That took a lot of work.");
             return;
         }
-        90 => RotateAmt::Rotate90,
-        180 => RotateAmt::Rotate180,
-        270 => RotateAmt::Rotate270,
-        _ => {
+        Err(()) => {
             ctx.player
                 .send_error_message("Rotate amount must be a multiple of 90.");
             return;
@@ -645,6 +1540,15 @@ pub(super) fn execute_rotate(ctx: CommandExecuteContext<'_>) {
     };
 
     let clipboard = ctx.player.worldedit_clipboard.as_ref().unwrap();
+    ctx.player.worldedit_clipboard = Some(rotate_clipboard(clipboard, rotate_amt));
+    ctx.player.send_worldedit_message(&format!(
+        "The clipboard copy has been rotated. ({:?})",
+        start_time.elapsed()
+    ));
+}
+
+/// Rotates the clipboard clockwise around its origin
+fn rotate_clipboard(clipboard: &WorldEditClipboard, rotate_amt: RotateAmt) -> WorldEditClipboard {
     let size_x = clipboard.size_x;
     let size_y = clipboard.size_y;
     let size_z = clipboard.size_z;
@@ -709,7 +1613,7 @@ pub(super) fn execute_rotate(ctx: CommandExecuteContext<'_>) {
         clipboard.offset_y,
         clipboard.offset_z,
     ));
-    let cb = WorldEditClipboard {
+    WorldEditClipboard {
         offset_x: offset.x,
         offset_y: offset.y,
         offset_z: offset.z,
@@ -722,18 +1626,13 @@ pub(super) fn execute_rotate(ctx: CommandExecuteContext<'_>) {
             .iter()
             .map(|(pos, e)| (rotate_pos(*pos), e.clone()))
             .collect(),
-    };
-
-    ctx.player.worldedit_clipboard = Some(cb);
-    ctx.player.send_worldedit_message(&format!(
-        "The clipboard copy has been rotated. ({:?})",
-        start_time.elapsed()
-    ));
+        copied_at: clipboard.copied_at,
+    }
 }
 
 pub(super) fn execute_help(mut ctx: CommandExecuteContext<'_>) {
     let command_name = ctx.arguments[0].unwrap_string().clone();
-    let slash_command_name = "/".to_owned() + &command_name;
+    let slash_command_name = "/".to_owned() + command_name.as_str();
     let player = &mut ctx.player;
 
     let maybe_command = COMMANDS
@@ -881,7 +1780,7 @@ pub(super) fn execute_ascend(ctx: CommandExecuteContext<'_>) {
     let player_pos = player.pos.block_pos();
     let mut player_y = player_pos.y;
 
-    for (y, _) in (player_y..=PLOT_BLOCK_HEIGHT).enumerate() {
+    for (y, _) in (player_y..=*PLOT_MAX_Y).enumerate() {
         if levels == 0 {
             break;
         }
@@ -984,7 +1883,7 @@ pub(super) fn execute_rstack(ctx: CommandExecuteContext<'_>) {
     }
 
     let player = ctx.player;
-    player.worldedit_undo.push(undo);
+    player.worldedit_history.record(ctx.plot, undo);
 
     player.send_worldedit_message(&format!(
         "Your selection was stacked successfully. ({:?})",
@@ -1025,6 +1924,14 @@ pub(super) fn execute_replace_container(ctx: CommandExecuteContext<'_>) {
         ContainerType::Furnace => Block::Furnace {},
         ContainerType::Barrel => Block::Barrel {},
         ContainerType::Hopper => Block::Hopper {},
+        ContainerType::Dispenser => Block::Dispenser {
+            facing: Default::default(),
+            triggered: false,
+        },
+        ContainerType::Dropper => Block::Dropper {
+            facing: Default::default(),
+            triggered: false,
+        },
     };
     let slots = to.num_slots() as u32;
 
@@ -1037,7 +1944,11 @@ pub(super) fn execute_replace_container(ctx: CommandExecuteContext<'_>) {
 
                 if !matches!(
                     block,
-                    Block::Furnace {} | Block::Barrel {} | Block::Hopper {}
+                    Block::Furnace {}
+                        | Block::Barrel {}
+                        | Block::Hopper {}
+                        | Block::Dispenser { .. }
+                        | Block::Dropper { .. }
                 ) {
                     continue;
                 }
@@ -1090,3 +2001,48 @@ pub(super) fn execute_replace_container(ctx: CommandExecuteContext<'_>) {
 pub(super) fn execute_unimplemented(_ctx: CommandExecuteContext<'_>) {
     unimplemented!("Unimplimented worldedit command");
 }
+
+#[test]
+fn curve_points_test() {
+    let points = [
+        BlockPos::new(0, 0, 0),
+        BlockPos::new(8, 4, 0),
+        BlockPos::new(8, 4, 12),
+    ];
+    let blocks = curve_points(&points);
+    for point in points {
+        assert!(blocks.contains(&point));
+    }
+    // Consecutive blocks always touch, at least diagonally
+    for pair in blocks.windows(2) {
+        let d = pair[1] - pair[0];
+        assert!(d.x.abs() <= 1 && d.y.abs() <= 1 && d.z.abs() <= 1);
+    }
+    assert_eq!(
+        line_points(BlockPos::new(0, 0, 0), BlockPos::new(3, 1, 0)).len(),
+        4
+    );
+}
+
+#[test]
+fn is_public_address_test() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "0.0.0.0",
+        "100.64.0.1",
+        "::1",
+        "::",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public_address(ip.parse().unwrap()), "{}", ip);
+    }
+    for ip in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+        assert!(is_public_address(ip.parse().unwrap()), "{}", ip);
+    }
+}
@@ -0,0 +1,468 @@
+//! A small expression language used by //generate and //deform.
+//!
+//! Expressions are compiled to a list of stack machine operations once, and can then be evaluated
+//! for every block in a selection. A program is one or more statements separated by `;`, and its
+//! value is the value of the last statement. Statements can assign to variables with `=`, `+=`,
+//! `-=`, `*=` and `/=`. Comparisons and logical operators evaluate to `1` or `0`.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub struct ExpressionParseError(String);
+
+impl fmt::Display for ExpressionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid expression: {}", self.0)
+    }
+}
+
+impl std::error::Error for ExpressionParseError {}
+
+type ParseResult<T> = Result<T, ExpressionParseError>;
+
+fn error<T>(msg: impl Into<String>) -> ParseResult<T> {
+    Err(ExpressionParseError(msg.into()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    /// Operators and punctuation
+    Symbol(&'static str),
+}
+
+// Longer symbols come first so that they are matched before their prefixes
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "+", "-", "*", "/", "%", "^", "<",
+    ">", "!", "=", "(", ")", ",", ";", "?", ":",
+];
+
+fn tokenize(src: &str) -> ParseResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            match rest[..len].parse() {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_) => return error(format!("bad number `{}`", &rest[..len])),
+            }
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_owned()));
+            len
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        } else {
+            return error(format!("unexpected character `{}`", c));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        let bool_val = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a % b,
+            BinaryOp::Pow => a.powf(b),
+            BinaryOp::Lt => bool_val(a < b),
+            BinaryOp::Le => bool_val(a <= b),
+            BinaryOp::Gt => bool_val(a > b),
+            BinaryOp::Ge => bool_val(a >= b),
+            BinaryOp::Eq => bool_val(a == b),
+            BinaryOp::Ne => bool_val(a != b),
+            BinaryOp::And => bool_val(a != 0.0 && b != 0.0),
+            BinaryOp::Or => bool_val(a != 0.0 || b != 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Pow,
+    Exp,
+    Ln,
+    Log10,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<(Function, usize)> {
+        Some(match name {
+            "sin" => (Function::Sin, 1),
+            "cos" => (Function::Cos, 1),
+            "tan" => (Function::Tan, 1),
+            "asin" => (Function::Asin, 1),
+            "acos" => (Function::Acos, 1),
+            "atan" => (Function::Atan, 1),
+            "atan2" => (Function::Atan2, 2),
+            "sqrt" => (Function::Sqrt, 1),
+            "abs" => (Function::Abs, 1),
+            "floor" => (Function::Floor, 1),
+            "ceil" => (Function::Ceil, 1),
+            "round" => (Function::Round, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            "pow" => (Function::Pow, 2),
+            "exp" => (Function::Exp, 1),
+            "ln" => (Function::Ln, 1),
+            "log10" => (Function::Log10, 1),
+            _ => return None,
+        })
+    }
+
+    fn apply(self, stack: &mut Vec<f64>) {
+        let a = stack.pop().unwrap();
+        let result = match self {
+            Function::Sin => a.sin(),
+            Function::Cos => a.cos(),
+            Function::Tan => a.tan(),
+            Function::Asin => a.asin(),
+            Function::Acos => a.acos(),
+            Function::Atan => a.atan(),
+            Function::Sqrt => a.sqrt(),
+            Function::Abs => a.abs(),
+            Function::Floor => a.floor(),
+            Function::Ceil => a.ceil(),
+            Function::Round => a.round(),
+            Function::Exp => a.exp(),
+            Function::Ln => a.ln(),
+            Function::Log10 => a.log10(),
+            // For two argument functions, `a` is the second argument
+            Function::Atan2 => stack.pop().unwrap().atan2(a),
+            Function::Min => stack.pop().unwrap().min(a),
+            Function::Max => stack.pop().unwrap().max(a),
+            Function::Pow => stack.pop().unwrap().powf(a),
+        };
+        stack.push(result);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Const(f64),
+    Load(usize),
+    /// Stores the top of the stack in a variable, leaving it on the stack
+    Store(usize),
+    Pop,
+    Neg,
+    Not,
+    Binary(BinaryOp),
+    /// Pops the condition, then value if true, then value if false
+    Select,
+    Call(Function),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a [&'a str],
+    ops: Vec<Op>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_symbol(&self, symbols: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Symbol(s)) if symbols.contains(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn eat_symbol(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+        let symbol = self.peek_symbol(symbols);
+        if symbol.is_some() {
+            self.pos += 1;
+        }
+        symbol
+    }
+
+    fn expect_symbol(&mut self, symbol: &'static str) -> ParseResult<()> {
+        match self.eat_symbol(&[symbol]) {
+            Some(_) => Ok(()),
+            None => error(format!("expected `{}`", symbol)),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Option<usize> {
+        self.variables.iter().position(|v| *v == name)
+    }
+
+    fn program(&mut self) -> ParseResult<()> {
+        loop {
+            self.statement()?;
+            if self.eat_symbol(&[";"]).is_none() || self.peek().is_none() {
+                break;
+            }
+            self.ops.push(Op::Pop);
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => error(format!("unexpected {:?}", token)),
+        }
+    }
+
+    fn statement(&mut self) -> ParseResult<()> {
+        if let (Some(Token::Ident(name)), Some(Token::Symbol(op))) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+        {
+            let compound = match *op {
+                "=" => Some(None),
+                "+=" => Some(Some(BinaryOp::Add)),
+                "-=" => Some(Some(BinaryOp::Sub)),
+                "*=" => Some(Some(BinaryOp::Mul)),
+                "/=" => Some(Some(BinaryOp::Div)),
+                _ => None,
+            };
+            if let Some(compound) = compound {
+                let Some(var) = self.variable(name) else {
+                    return error(format!("can't assign to `{}`", name));
+                };
+                self.pos += 2;
+                if compound.is_some() {
+                    self.ops.push(Op::Load(var));
+                }
+                self.expr()?;
+                if let Some(op) = compound {
+                    self.ops.push(Op::Binary(op));
+                }
+                self.ops.push(Op::Store(var));
+                return Ok(());
+            }
+        }
+        self.expr()
+    }
+
+    fn expr(&mut self) -> ParseResult<()> {
+        self.binary(0)?;
+        if self.eat_symbol(&["?"]).is_some() {
+            self.expr()?;
+            self.expect_symbol(":")?;
+            self.expr()?;
+            self.ops.push(Op::Select);
+        }
+        Ok(())
+    }
+
+    /// Parses left associative binary operators, from lowest to highest precedence
+    fn binary(&mut self, level: usize) -> ParseResult<()> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("<", BinaryOp::Lt),
+                ("<=", BinaryOp::Le),
+                (">", BinaryOp::Gt),
+                (">=", BinaryOp::Ge),
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        self.binary(level + 1)?;
+        while let Some(&(_, op)) = ops
+            .iter()
+            .find(|(symbol, _)| self.peek_symbol(&[*symbol]).is_some())
+        {
+            self.pos += 1;
+            self.binary(level + 1)?;
+            self.ops.push(Op::Binary(op));
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> ParseResult<()> {
+        match self.eat_symbol(&["-", "!", "+"]) {
+            Some("-") => {
+                self.unary()?;
+                self.ops.push(Op::Neg);
+            }
+            Some("!") => {
+                self.unary()?;
+                self.ops.push(Op::Not);
+            }
+            Some(_) => self.unary()?,
+            None => {
+                self.primary()?;
+                // Exponentiation is right associative and binds tighter than negation on its left
+                if self.eat_symbol(&["^"]).is_some() {
+                    self.unary()?;
+                    self.ops.push(Op::Binary(BinaryOp::Pow));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn primary(&mut self) -> ParseResult<()> {
+        let Some(token) = self.peek().cloned() else {
+            return error("unexpected end of expression");
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => self.ops.push(Op::Const(n)),
+            Token::Symbol("(") => {
+                self.expr()?;
+                self.expect_symbol(")")?;
+            }
+            Token::Ident(name) if self.peek_symbol(&["("]).is_some() => {
+                let Some((function, arg_count)) = Function::from_name(&name) else {
+                    return error(format!("unknown function `{}`", name));
+                };
+                self.pos += 1;
+                for i in 0..arg_count {
+                    if i > 0 {
+                        self.expect_symbol(",")?;
+                    }
+                    self.expr()?;
+                }
+                self.expect_symbol(")")?;
+                self.ops.push(Op::Call(function));
+            }
+            Token::Ident(name) => match name.as_str() {
+                "pi" => self.ops.push(Op::Const(std::f64::consts::PI)),
+                "e" => self.ops.push(Op::Const(std::f64::consts::E)),
+                _ => match self.variable(&name) {
+                    Some(var) => self.ops.push(Op::Load(var)),
+                    None => return error(format!("unknown variable `{}`", name)),
+                },
+            },
+            Token::Symbol(symbol) => return error(format!("unexpected `{}`", symbol)),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    ops: Vec<Op>,
+}
+
+impl Expression {
+    /// Compiles an expression that can read and assign the given variables. When evaluating, the
+    /// values of the variables are passed in the same order.
+    pub fn compile(src: &str, variables: &[&str]) -> Result<Expression, ExpressionParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            variables,
+            ops: Vec::new(),
+        };
+        if parser.tokens.is_empty() {
+            return error("expression is empty");
+        }
+        parser.program()?;
+        Ok(Expression { ops: parser.ops })
+    }
+
+    pub fn eval(&self, variables: &mut [f64]) -> f64 {
+        let mut stack = Vec::with_capacity(8);
+        for op in &self.ops {
+            match *op {
+                Op::Const(n) => stack.push(n),
+                Op::Load(var) => stack.push(variables[var]),
+                Op::Store(var) => variables[var] = *stack.last().unwrap(),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Neg => {
+                    let a = stack.pop().unwrap();
+                    stack.push(-a);
+                }
+                Op::Not => {
+                    let a = stack.pop().unwrap();
+                    stack.push(if a == 0.0 { 1.0 } else { 0.0 });
+                }
+                Op::Binary(op) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(op.apply(a, b));
+                }
+                Op::Select => {
+                    let if_false = stack.pop().unwrap();
+                    let if_true = stack.pop().unwrap();
+                    let condition = stack.pop().unwrap();
+                    stack.push(if condition != 0.0 { if_true } else { if_false });
+                }
+                Op::Call(function) => function.apply(&mut stack),
+            }
+        }
+        stack.pop().unwrap_or(0.0)
+    }
+}
+
+#[test]
+fn expression_eval_test() {
+    let eval = |src: &str, vars: &mut [f64]| {
+        Expression::compile(src, &["x", "y", "z"])
+            .unwrap()
+            .eval(vars)
+    };
+    assert_eq!(eval("1 + 2 * 3 - 4 / 2", &mut [0.0; 3]), 5.0);
+    assert_eq!(eval("-2^2", &mut [0.0; 3]), -4.0);
+    assert_eq!(eval("x*x + y*y < 1 && z >= 0", &mut [0.5, 0.5, 0.0]), 1.0);
+    assert_eq!(eval("max(x, 3) > 2 ? 10 : 20", &mut [1.0, 0.0, 0.0]), 10.0);
+
+    let mut vars = [1.0, 2.0, 3.0];
+    assert_eq!(eval("x = y * 2; z -= 1; x + z", &mut vars), 6.0);
+    assert_eq!(vars, [4.0, 2.0, 2.0]);
+
+    for invalid in ["", "1 +", "foo", "sin(1, 2)", "(1", "pi = 3", "1 $ 2"] {
+        assert!(Expression::compile(invalid, &["x"]).is_err(), "{}", invalid);
+    }
+}
@@ -0,0 +1,405 @@
+//! Worldedit undo and redo history. While an edit is running, the blocks it is going to change are
+//! kept in memory. Once it has finished, only the blocks that actually changed are written to disk
+//! as a compressed diff, so that deep histories of edits touching millions of blocks don't have to
+//! be kept in memory. The diffs of a player are stored in `./world/worldedit_history/{uuid}/`
+//! along with an index of their history, so that it survives relogs and server restarts.
+
+use super::WorldEditUndo;
+use crate::config::CONFIG;
+use crate::plot::PlotWorld;
+use anyhow::{Context, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::BlockPos;
+use mchprs_world::World;
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::error;
+
+/// The blocks changed by an edit, with their ids from before and after it. Positions are stored
+/// as the difference to the previous position, which keeps them small enough to compress well.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+struct BlockDiff {
+    deltas: Vec<[i32; 3]>,
+    old: Vec<u32>,
+    new: Vec<u32>,
+    /// Block entities from before and after the edit, stored as uncompressed NBT
+    old_block_entities: Vec<([i32; 3], Vec<u8>)>,
+    new_block_entities: Vec<([i32; 3], Vec<u8>)>,
+    #[serde(skip)]
+    last_pos: [i32; 3],
+}
+
+fn save_block_entity(pos: BlockPos, block_entity: &BlockEntity) -> Option<([i32; 3], Vec<u8>)> {
+    let blob = block_entity.to_nbt(false)?;
+    let mut data = Vec::new();
+    blob.to_writer(&mut data).ok()?;
+    Some(([pos.x, pos.y, pos.z], data))
+}
+
+fn load_block_entities(saved: &[([i32; 3], Vec<u8>)]) -> Result<Vec<(BlockPos, BlockEntity)>> {
+    let mut block_entities = Vec::new();
+    for ([x, y, z], nbt) in saved {
+        let blob = nbt::Blob::from_reader(&mut nbt.as_slice())?;
+        let Some(nbt::Value::String(id)) = blob.get("id") else {
+            continue;
+        };
+        if let Some(block_entity) = BlockEntity::from_nbt(id, &blob.content) {
+            block_entities.push((BlockPos::new(*x, *y, *z), block_entity));
+        }
+    }
+    Ok(block_entities)
+}
+
+impl BlockDiff {
+    /// Compares the blocks from before the edit with the blocks that are in the plot now
+    fn new(plot: &PlotWorld, undo: &WorldEditUndo) -> BlockDiff {
+        let mut diff = BlockDiff::default();
+        // Clipboards later in the list are restored last, so they win where clipboards overlap
+        let overlapping = undo.clipboards.len() > 1;
+        let mut seen = FxHashSet::default();
+        for clipboard in undo.clipboards.iter().rev() {
+            let start = BlockPos::new(
+                undo.pos.x - clipboard.offset_x,
+                undo.pos.y - clipboard.offset_y,
+                undo.pos.z - clipboard.offset_z,
+            );
+            let mut i = 0;
+            for y in 0..clipboard.size_y as i32 {
+                for z in 0..clipboard.size_z as i32 {
+                    for x in 0..clipboard.size_x as i32 {
+                        let offset = BlockPos::new(x, y, z);
+                        let pos = start + offset;
+                        let old = clipboard.data.get_entry(i);
+                        i += 1;
+                        if overlapping && !seen.insert(pos) {
+                            continue;
+                        }
+
+                        let new = plot.get_block_raw(pos);
+                        let old_block_entity = clipboard.block_entities.get(&offset);
+                        let new_block_entity = plot.get_block_entity(pos);
+                        if old == new && old_block_entity.is_none() && new_block_entity.is_none() {
+                            continue;
+                        }
+                        diff.push(pos, old, new);
+                        diff.old_block_entities
+                            .extend(old_block_entity.and_then(|be| save_block_entity(pos, be)));
+                        diff.new_block_entities
+                            .extend(new_block_entity.and_then(|be| save_block_entity(pos, be)));
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    fn push(&mut self, pos: BlockPos, old: u32, new: u32) {
+        let [last_x, last_y, last_z] = self.last_pos;
+        self.deltas
+            .push([pos.x - last_x, pos.y - last_y, pos.z - last_z]);
+        self.old.push(old);
+        self.new.push(new);
+        self.last_pos = [pos.x, pos.y, pos.z];
+    }
+
+    fn positions(&self) -> impl Iterator<Item = BlockPos> + '_ {
+        self.deltas.iter().scan(BlockPos::zero(), |pos, [x, y, z]| {
+            *pos = *pos + BlockPos::new(*x, *y, *z);
+            Some(*pos)
+        })
+    }
+
+    /// Sets the blocks back to how they were before the edit, or to how they were after it if
+    /// `undo` is false
+    fn apply(&self, plot: &mut PlotWorld, undo: bool) -> Result<()> {
+        let (ids, block_entities) = if undo {
+            (&self.old, &self.old_block_entities)
+        } else {
+            (&self.new, &self.new_block_entities)
+        };
+        let block_entities = load_block_entities(block_entities)?;
+
+        for (pos, id) in self.positions().zip(ids) {
+            // The block entity of the replaced block would otherwise stay around
+            plot.delete_block_entity(pos);
+            plot.set_block_raw(pos, *id);
+        }
+        // Send block changes before we send block entity data, otherwise it'll be ignored
+        plot.flush_block_changes();
+        for (pos, block_entity) in block_entities {
+            plot.set_block_entity(pos, block_entity);
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        bincode::serialize_into(&mut encoder, self)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decode(data: &[u8]) -> Result<BlockDiff> {
+        let diff = bincode::deserialize_from(DeflateDecoder::new(data))
+            .context("worldedit history diff is corrupted")?;
+        Ok(diff)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HistoryEntry {
+    /// The number of the file the diff is saved in
+    file: u64,
+    plot_x: i32,
+    plot_z: i32,
+    /// The size of the diff file in bytes
+    size: u64,
+}
+
+/// The entries of a history, which is saved as the index of its folder
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct HistoryIndex {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    next_file: u64,
+}
+
+/// Why the last entry of a history couldn't be undone or redone
+#[derive(Debug, PartialEq, Eq)]
+pub enum RestoreError {
+    /// There is nothing left to undo or redo
+    Empty,
+    /// The last entry was made in a different plot. It is kept so it can still be restored from
+    /// the plot it belongs to.
+    OtherPlot,
+    /// The diff of the entry couldn't be read from disk, so the entry was dropped
+    Unreadable,
+}
+
+pub struct WorldEditHistory {
+    /// The uuid of the player the history belongs to, which names the folder of the history
+    uuid: u128,
+    index: HistoryIndex,
+    /// The edit which is currently running, with the blocks from before it started
+    pending: Option<WorldEditUndo>,
+}
+
+fn history_dir(uuid: u128) -> PathBuf {
+    PathBuf::from(format!("./world/worldedit_history/{:032x}", uuid))
+}
+
+fn diff_path(uuid: u128, file: u64) -> PathBuf {
+    history_dir(uuid).join(file.to_string())
+}
+
+fn delete_entries(uuid: u128, entries: impl IntoIterator<Item = HistoryEntry>) {
+    for entry in entries {
+        let _ = fs::remove_file(diff_path(uuid, entry.file));
+    }
+}
+
+fn load_index(uuid: u128) -> Result<HistoryIndex> {
+    let data = fs::read(history_dir(uuid).join("index"))?;
+    let index = bincode::deserialize(&data).context("worldedit history index is corrupted")?;
+    Ok(index)
+}
+
+/// Removes diffs which aren't in the index, like the ones written after the index was last saved
+/// when the server crashed
+fn remove_unused_diffs(uuid: u128, index: &HistoryIndex) {
+    let Ok(dir) = fs::read_dir(history_dir(uuid)) else {
+        return;
+    };
+    let used: FxHashSet<u64> = index
+        .undo
+        .iter()
+        .chain(&index.redo)
+        .map(|entry| entry.file)
+        .collect();
+    for entry in dir.flatten() {
+        let name = entry.file_name();
+        let Some(file) = name.to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        if !used.contains(&file) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+impl WorldEditHistory {
+    /// Loads the history of the player, or an empty history if there is none
+    pub fn load(uuid: u128) -> WorldEditHistory {
+        let dir = history_dir(uuid);
+        // Histories used to be saved as a single file, which can't be restored anymore
+        if dir.is_file() {
+            let _ = fs::remove_file(&dir);
+        }
+
+        let index = match load_index(uuid) {
+            Ok(index) => index,
+            Err(err) => {
+                // Players who never used worldedit don't have a history yet
+                if err
+                    .downcast_ref::<std::io::Error>()
+                    .is_none_or(|err| err.kind() != std::io::ErrorKind::NotFound)
+                {
+                    error!("Failed to load worldedit history: {}", err);
+                }
+                HistoryIndex::default()
+            }
+        };
+        remove_unused_diffs(uuid, &index);
+        WorldEditHistory {
+            uuid,
+            index,
+            pending: None,
+        }
+    }
+
+    /// Saves the index of the history. The diffs are written to disk as soon as they are made.
+    pub fn save(&self) {
+        let dir = history_dir(self.uuid);
+        if self.index.undo.is_empty() && self.index.redo.is_empty() {
+            let _ = fs::remove_dir_all(dir);
+            return;
+        }
+
+        let data = match bincode::serialize(&self.index) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Failed to serialize worldedit history: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(dir.join("index"), data) {
+            error!("Failed to save worldedit history: {}", err);
+        }
+    }
+
+    /// Starts recording an edit. This clears the redo history, and the blocks from before the
+    /// edit are kept until [`finish`](Self::finish) is called.
+    pub(super) fn record(&mut self, plot: &PlotWorld, undo: WorldEditUndo) {
+        self.finish(plot);
+        delete_entries(self.uuid, self.index.redo.drain(..));
+        self.pending = Some(undo);
+    }
+
+    /// Writes the diff of the edit being recorded to disk. This is called once the edit has
+    /// finished changing the plot.
+    pub(super) fn finish(&mut self, plot: &PlotWorld) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        // The plot the edit was made in isn't loaded anymore, so there is nothing to compare with
+        if pending.plot_x != plot.x || pending.plot_z != plot.z {
+            return;
+        }
+
+        let file = self.index.next_file;
+        self.index.next_file += 1;
+        let write_diff = || -> Result<u64> {
+            let data = BlockDiff::new(plot, &pending).encode()?;
+            fs::create_dir_all(history_dir(self.uuid))?;
+            fs::File::create(diff_path(self.uuid, file))?.write_all(&data)?;
+            Ok(data.len() as u64)
+        };
+        match write_diff() {
+            Ok(size) => {
+                self.index.undo.push(HistoryEntry {
+                    file,
+                    plot_x: pending.plot_x,
+                    plot_z: pending.plot_z,
+                    size,
+                });
+                self.trim();
+            }
+            Err(err) => error!("Failed to save worldedit history: {}", err),
+        }
+    }
+
+    /// Drops the oldest entries of the undo history past the configured depth. Entries are also
+    /// dropped while the history is larger than the configured size, except for the latest one.
+    fn trim(&mut self) {
+        let depth = CONFIG.worldedit_history_depth.max(0) as usize;
+        let max_size = CONFIG.worldedit_history_max_size.max(0) as u64;
+        let undo = &self.index.undo;
+        let mut size: u64 = undo.iter().chain(&self.index.redo).map(|e| e.size).sum();
+        let mut dropped = 0;
+        while dropped < undo.len()
+            && (undo.len() - dropped > depth || (size > max_size && undo.len() - dropped > 1))
+        {
+            size -= undo[dropped].size;
+            dropped += 1;
+        }
+        delete_entries(self.uuid, self.index.undo.drain(..dropped));
+    }
+
+    /// Reverts the last edit of the undo history
+    pub fn undo(&mut self, plot: &mut PlotWorld) -> Result<(), RestoreError> {
+        self.restore(plot, true)
+    }
+
+    /// Applies the last edit of the redo history again
+    pub fn redo(&mut self, plot: &mut PlotWorld) -> Result<(), RestoreError> {
+        self.restore(plot, false)
+    }
+
+    fn restore(&mut self, plot: &mut PlotWorld, undo: bool) -> Result<(), RestoreError> {
+        self.finish(plot);
+        let (from, to) = if undo {
+            (&mut self.index.undo, &mut self.index.redo)
+        } else {
+            (&mut self.index.redo, &mut self.index.undo)
+        };
+        let last = from.last().ok_or(RestoreError::Empty)?;
+        if last.plot_x != plot.x || last.plot_z != plot.z {
+            return Err(RestoreError::OtherPlot);
+        }
+
+        let entry = from.pop().unwrap();
+        let path = diff_path(self.uuid, entry.file);
+        let diff = fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| BlockDiff::decode(&data));
+        match diff.and_then(|diff| diff.apply(plot, undo)) {
+            Ok(()) => {
+                to.push(entry);
+                Ok(())
+            }
+            Err(err) => {
+                error!("Failed to restore worldedit history: {}", err);
+                let _ = fs::remove_file(path);
+                Err(RestoreError::Unreadable)
+            }
+        }
+    }
+}
+
+#[test]
+fn block_diff_encode_test() {
+    let mut diff = BlockDiff::default();
+    diff.push(BlockPos::new(5, 64, -3), 1, 0);
+    diff.push(BlockPos::new(6, 64, -3), 1, 0);
+    diff.push(BlockPos::new(-2, 70, 9), 0, 8);
+    diff.new_block_entities
+        .push(([-2, 70, 9], vec![10, 0, 0, 0]));
+
+    let decoded = BlockDiff::decode(&diff.encode().unwrap()).unwrap();
+    assert_eq!(
+        decoded.positions().collect::<Vec<_>>(),
+        [
+            BlockPos::new(5, 64, -3),
+            BlockPos::new(6, 64, -3),
+            BlockPos::new(-2, 70, 9)
+        ]
+    );
+    assert_eq!(decoded.old, [1, 1, 0]);
+    assert_eq!(decoded.new, [0, 0, 8]);
+    assert_eq!(decoded.new_block_entities, diff.new_block_entities);
+}
@@ -0,0 +1,277 @@
+//! Large edits run as jobs which are applied a batch at a time every time the plot updates, so
+//! that the plot doesn't freeze while millions of blocks are being changed. The progress of a job
+//! is shown to its player with a bossbar, and it can be stopped with `//cancel`.
+
+use super::{WorldEditClipboard, WorldEditMask, WorldEditOperation, WorldEditPattern};
+use crate::player::{PacketSender, Player};
+use crate::plot::{Plot, PlotWorld};
+use mchprs_blocks::BlockPos;
+use mchprs_network::packets::clientbound::{CBossEvent, CBossEventAction, ClientBoundPacket};
+use mchprs_text::{ColorCode, TextComponentBuilder};
+use mchprs_world::World;
+use std::time::{Duration, Instant};
+
+/// How long a job can run for each time the plot updates
+const BATCH_TIME: Duration = Duration::from_millis(25);
+/// How many blocks are edited between checks of the batch time
+const BLOCKS_PER_CHECK: usize = 4096;
+
+const BOSSBAR_COLOR_PURPLE: i32 = 5;
+
+pub(super) enum JobKind {
+    Set {
+        pattern: WorldEditPattern,
+    },
+    Replace {
+        mask: WorldEditMask,
+        pattern: WorldEditPattern,
+    },
+    Paste {
+        clipboard: WorldEditClipboard,
+        skip_air: bool,
+        update: bool,
+    },
+}
+
+pub struct WorldEditJob {
+    kind: JobKind,
+    /// The command that started the job, used in messages
+    name: &'static str,
+    operation: WorldEditOperation,
+    /// The index of the next block to edit. Blocks are visited in the same order as clipboard
+    /// data is stored, which is x first, then z, then y.
+    next: usize,
+    plot_x: i32,
+    plot_z: i32,
+    start_time: Instant,
+    /// The uuid of the bossbar, if it is being shown
+    bossbar: Option<u128>,
+    /// The progress last sent to the bossbar, in percent
+    last_progress: u32,
+}
+
+impl WorldEditJob {
+    pub(super) fn new(
+        plot: &PlotWorld,
+        name: &'static str,
+        kind: JobKind,
+        operation: WorldEditOperation,
+    ) -> WorldEditJob {
+        WorldEditJob {
+            kind,
+            name,
+            operation,
+            next: 0,
+            plot_x: plot.x,
+            plot_z: plot.z,
+            start_time: Instant::now(),
+            bossbar: None,
+            last_progress: 0,
+        }
+    }
+
+    fn size(&self) -> [usize; 3] {
+        [
+            self.operation.x_range.clone().count(),
+            self.operation.y_range.clone().count(),
+            self.operation.z_range.clone().count(),
+        ]
+    }
+
+    fn total(&self) -> usize {
+        self.size().iter().product()
+    }
+
+    /// Edits blocks until the batch time runs out. Returns true if the job has finished.
+    fn run_batch(&mut self, plot: &mut PlotWorld) -> bool {
+        let batch_start = Instant::now();
+        let [size_x, size_y, size_z] = self.size();
+        let total = self.total();
+        let min = BlockPos::new(
+            *self.operation.x_range.start(),
+            *self.operation.y_range.start(),
+            *self.operation.z_range.start(),
+        );
+
+        while self.next < total {
+            let end = (self.next + BLOCKS_PER_CHECK).min(total);
+            for i in self.next..end {
+                let pos = min
+                    + BlockPos::new(
+                        (i % size_x) as i32,
+                        (i / (size_x * size_z)) as i32,
+                        (i / size_x % size_z) as i32,
+                    );
+                let block_id = match &self.kind {
+                    JobKind::Set { pattern } => {
+                        if !self.operation.contains(pos) {
+                            continue;
+                        }
+                        pattern.pick().get_id()
+                    }
+                    JobKind::Replace { mask, pattern } => {
+                        if !self.operation.contains(pos) || !mask.matches(plot.get_block(pos)) {
+                            continue;
+                        }
+                        pattern.pick().get_id()
+                    }
+                    JobKind::Paste {
+                        clipboard,
+                        skip_air,
+                        ..
+                    } => {
+                        let entry = clipboard.data.get_entry(i);
+                        if *skip_air && entry == 0 {
+                            continue;
+                        }
+                        // The block entity of the replaced block would otherwise stay around
+                        plot.delete_block_entity(pos);
+                        entry
+                    }
+                };
+                if plot.set_block_raw(pos, block_id) {
+                    self.operation.update_block();
+                }
+            }
+            self.next = end;
+
+            if batch_start.elapsed() > BATCH_TIME {
+                break;
+            }
+        }
+
+        if self.next < total {
+            return false;
+        }
+
+        if let JobKind::Paste {
+            clipboard, update, ..
+        } = &self.kind
+        {
+            // Send block changes before we send block entity data, otherwise it'll be ignored
+            plot.flush_block_changes();
+            for (pos, block_entity) in &clipboard.block_entities {
+                plot.set_block_entity(min + *pos, block_entity.clone());
+            }
+            if *update {
+                let max =
+                    min + BlockPos::new(size_x as i32 - 1, size_y as i32 - 1, size_z as i32 - 1);
+                super::update(plot, min, max);
+            }
+        }
+        true
+    }
+
+    fn finish(&self, plot: &PlotWorld, player: &mut Player) {
+        player.worldedit_history.finish(plot);
+        self.hide_bossbar(player);
+        let message = match self.kind {
+            JobKind::Paste { .. } => format!(
+                "Your clipboard was pasted. ({:?})",
+                self.start_time.elapsed()
+            ),
+            _ => format!(
+                "Operation completed: {} block(s) affected ({:?})",
+                self.operation.blocks_updated(),
+                self.start_time.elapsed()
+            ),
+        };
+        player.send_worldedit_edit_message(&message);
+    }
+
+    fn update_bossbar(&mut self, player: &Player) {
+        let progress = self.next as f32 / self.total() as f32;
+        let percent = (progress * 100.0) as u32;
+        let action = match self.bossbar {
+            None => {
+                self.bossbar = Some(rand::random());
+                let title = format!("//{}", self.name);
+                CBossEventAction::Add {
+                    title: TextComponentBuilder::new(title)
+                        .color_code(ColorCode::LightPurple)
+                        .finish(),
+                    progress,
+                    color: BOSSBAR_COLOR_PURPLE,
+                    division: 0,
+                    flags: 0,
+                }
+            }
+            Some(_) if percent != self.last_progress => CBossEventAction::UpdateProgress(progress),
+            Some(_) => return,
+        };
+        self.last_progress = percent;
+        player.send_packet(
+            &CBossEvent {
+                uuid: self.bossbar.unwrap(),
+                action,
+            }
+            .encode(),
+        );
+    }
+
+    fn hide_bossbar(&self, player: &Player) {
+        if let Some(uuid) = self.bossbar {
+            player.send_packet(
+                &CBossEvent {
+                    uuid,
+                    action: CBossEventAction::Remove,
+                }
+                .encode(),
+            );
+        }
+    }
+}
+
+/// Runs the first batch of the job right away. If that doesn't finish it, the rest is run by
+/// `run_job` while the plot updates.
+pub(super) fn start_job(plot: &mut PlotWorld, player: &mut Player, mut job: WorldEditJob) {
+    if job.run_batch(plot) {
+        job.finish(plot, player);
+        return;
+    }
+    job.update_bossbar(player);
+    player.worldedit_job = Some(job);
+}
+
+/// Continues the job of the player, if they have one
+pub fn run_job(plot: &mut Plot, player_idx: usize) {
+    let Some(mut job) = plot.players[player_idx].worldedit_job.take() else {
+        return;
+    };
+    if job.plot_x != plot.world.x || job.plot_z != plot.world.z {
+        cancel_job(&plot.world, &mut plot.players[player_idx], job);
+        return;
+    }
+
+    // The blocks are changed underneath redpiler, so it has to be recompiled afterwards
+    plot.reset_redpiler();
+    let player = &mut plot.players[player_idx];
+    if job.run_batch(&mut plot.world) {
+        job.finish(&plot.world, player);
+    } else {
+        job.update_bossbar(player);
+        player.worldedit_job = Some(job);
+    }
+}
+
+fn cancel_job(plot: &PlotWorld, player: &mut Player, job: WorldEditJob) {
+    // The blocks changed so far can still be undone
+    player.worldedit_history.finish(plot);
+    job.hide_bossbar(player);
+    player.send_worldedit_message(&format!(
+        "//{} was cancelled after changing {} block(s). Use //undo to revert them.",
+        job.name,
+        job.operation.blocks_updated()
+    ));
+}
+
+/// Cancels the job of the player. Returns false if they don't have one.
+pub fn cancel_player_job(plot: &PlotWorld, player: &mut Player) -> bool {
+    match player.worldedit_job.take() {
+        Some(job) => {
+            cancel_job(plot, player, job);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,85 @@
+//! Enforces the `worldedit_limits` section of the config. Players with the
+//! `worldedit.limit.unrestricted` permission aren't limited.
+
+use super::WorldEditClipboard;
+use crate::config::CONFIG;
+use crate::player::{PacketSender, Player};
+use std::time::{Duration, Instant};
+
+/// Returns the limit if it applies to the player
+fn player_limit(player: &Player, limit: i64) -> Option<u64> {
+    if limit < 0 || player.has_permission("worldedit.limit.unrestricted") {
+        return None;
+    }
+    Some(limit as u64)
+}
+
+fn selection_volume(player: &Player) -> u64 {
+    let (Some(first_pos), Some(second_pos)) = (player.first_position, player.second_position)
+    else {
+        return 0;
+    };
+    let size = first_pos.max(second_pos) - first_pos.min(second_pos);
+    (size.x as u64 + 1) * (size.y as u64 + 1) * (size.z as u64 + 1)
+}
+
+/// Returns false and tells the player if their selection is larger than they are allowed to use
+pub(super) fn check_selection_volume(player: &Player) -> bool {
+    let Some(limit) = player_limit(player, CONFIG.worldedit_limits.max_selection_volume) else {
+        return true;
+    };
+    let volume = selection_volume(player);
+    if volume > limit {
+        player.send_error_message(&format!(
+            "Your selection has {} blocks, but you can only use up to {}.",
+            volume, limit
+        ));
+        return false;
+    }
+    true
+}
+
+/// Returns false and tells the player if they aren't allowed to have a clipboard this large
+pub(super) fn check_clipboard(player: &Player, volume: u64) -> bool {
+    let Some(limit) = player_limit(player, CONFIG.worldedit_limits.max_clipboard_volume) else {
+        return true;
+    };
+    if volume > limit {
+        player.send_error_message(&format!(
+            "The clipboard would have {} blocks, but you can only have up to {}.",
+            volume, limit
+        ));
+        return false;
+    }
+    true
+}
+
+/// Returns false and tells the player if they aren't allowed to have the clipboard
+pub(super) fn check_clipboard_volume(player: &Player, clipboard: &WorldEditClipboard) -> bool {
+    let volume = clipboard.size_x as u64 * clipboard.size_y as u64 * clipboard.size_z as u64;
+    check_clipboard(player, volume)
+}
+
+/// Returns false and tells the player if they aren't allowed to copy their selection
+pub(super) fn check_clipboard_selection(player: &Player) -> bool {
+    check_clipboard(player, selection_volume(player))
+}
+
+/// Counts an operation that changes the world towards the player's operations per second.
+/// Returns false and tells the player if they are over the limit.
+pub(super) fn take_operation(player: &mut Player) -> bool {
+    let Some(limit) = player_limit(player, CONFIG.worldedit_limits.max_operations_per_second)
+    else {
+        return true;
+    };
+    let now = Instant::now();
+    player
+        .worldedit_recent_operations
+        .retain(|time| now - *time < Duration::from_secs(1));
+    if player.worldedit_recent_operations.len() as u64 >= limit {
+        player.send_error_message("You are using worldedit too quickly, slow down.");
+        return false;
+    }
+    player.worldedit_recent_operations.push(now);
+    true
+}
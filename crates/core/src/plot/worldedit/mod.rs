@@ -1,27 +1,55 @@
 //! [Worldedit](https://github.com/EngineHub/WorldEdit) and [RedstoneTools](https://github.com/paulikauro/RedstoneTools) implementation
 
+mod anvil;
+mod api;
+mod brush;
 mod execute;
+mod expression;
+mod history;
+mod job;
+mod limits;
+mod pattern;
 mod schematic;
+mod selection;
+mod visualize;
 
 use super::commands::CommandFlags;
 use super::{Plot, PlotWorld};
 use crate::player::{PacketSender, Player, PlayerPos};
+use crate::server::Message;
+pub use api::{EditSession, Selection};
+use brush::clamp_to_plot;
+pub use brush::{holding_brush, use_brush, BrushShape, WorldEditBrush};
+pub use execute::schematic_suggestions;
 use execute::*;
+use expression::Expression;
+pub use history::{RestoreError, WorldEditHistory};
+pub use job::{cancel_player_job, run_job, WorldEditJob};
+use job::{start_job, JobKind};
 use mchprs_blocks::block_entities::{BlockEntity, ContainerType};
 use mchprs_blocks::blocks::Block;
 use mchprs_blocks::{BlockFacing, BlockPos};
 use mchprs_network::packets::clientbound::{CCommandsNode, CDeclareCommandsNodeParser};
+use mchprs_save_data::plot_data::PlotPermission;
 use mchprs_utils::map;
 use mchprs_world::storage::PalettedBitBuffer;
 use mchprs_world::{for_each_block_mut_optimized, World};
 use once_cell::sync::Lazy;
-use rand::Rng;
+pub use pattern::{
+    PatternParseError, PatternParseResult, WorldEditMask, WorldEditPattern, WorldEditPatternPart,
+};
 use regex::Regex;
 use rustc_hash::FxHashMap;
+pub use schematic::load_schematic_file;
+pub(super) use schematic::{index_saved_schematic, save_plot_schematic};
+use selection::Region;
+pub use selection::{convert_to_cuboid, select_point, SelectionMode};
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::mpsc::Sender;
+pub use visualize::visualize;
 
 // Attempts to execute a worldedit command. Returns true of the command was handled.
 // The command is not handled if it is not found in the worldedit commands and alias lists.
@@ -32,7 +60,13 @@ pub fn execute_command(
     args: &mut Vec<&str>,
 ) -> bool {
     let player = &mut plot.players[player_idx];
-    let command = if let Some(command) = COMMANDS.get(command) {
+    let subcommand = args
+        .first()
+        .and_then(|sub| COMMANDS.get(format!("{} {}", command, sub).as_str()));
+    let command = if let Some(command) = subcommand {
+        args.remove(0);
+        command
+    } else if let Some(command) = COMMANDS.get(command) {
         command
     } else if let Some(command) = ALIASES.get(command) {
         let mut alias: Vec<&str> = command.split(' ').collect();
@@ -40,22 +74,22 @@ pub fn execute_command(
         args.append(&mut alias);
         &COMMANDS[command]
     } else {
-        return false;
+        let mut subcommands: Vec<&str> = COMMANDS
+            .keys()
+            .filter_map(|name| name.strip_prefix(command)?.strip_prefix(' '))
+            .collect();
+        if subcommands.is_empty() {
+            return false;
+        }
+        subcommands.sort_unstable();
+        player.send_error_message(&format!("Usage: /{} <{}>", command, subcommands.join("|")));
+        return true;
     };
 
-    let wea = player.has_permission("plots.worldedit.bypass");
-    if !wea {
-        if let Some(owner) = plot.owner {
-            if owner != player.uuid {
-                // tried to worldedit on plot that wasn't theirs
-                player.send_no_permission_message();
-                return true;
-            }
-        } else {
-            // tried to worldedit on unclaimed plot
-            player.send_no_permission_message();
-            return true;
-        }
+    if !plot.access.allows(player, PlotPermission::WorldEdit) {
+        // tried to worldedit on a plot that wasn't theirs
+        player.send_no_permission_message();
+        return true;
     }
 
     if !command.permission_node.is_empty() && !player.has_permission(command.permission_node) {
@@ -63,6 +97,17 @@ pub fn execute_command(
         return true;
     }
 
+    if command.requires_positions && !limits::check_selection_volume(player) {
+        return true;
+    }
+
+    if command.mutates_world && player.worldedit_job.is_some() {
+        player.send_error_message(
+            "Wait for your current operation to finish, or stop it with //cancel.",
+        );
+        return true;
+    }
+
     if command.requires_positions {
         let plot_x = plot.world.x;
         let plot_z = plot.world.z;
@@ -90,6 +135,7 @@ pub fn execute_command(
     let flag_descs = command.flags;
 
     let mut ctx_flags = Vec::new();
+    let mut flag_arguments = Vec::new();
 
     let total_arg_count = args.len();
     let mut arg_iter = args.iter().copied().enumerate();
@@ -118,7 +164,20 @@ pub fn execute_command(
                         ));
                         return true;
                     }
-                    arg_iter.next();
+                    let (_, value) = arg_iter.next().unwrap();
+                    let desc = ArgumentDescription {
+                        name: "",
+                        argument_type,
+                        description: flag_desc.description,
+                        default: None,
+                    };
+                    match Argument::parse(player, &desc, Some(value)) {
+                        Ok(argument) => flag_arguments.push((flag, argument)),
+                        Err(err) => {
+                            player.send_error_message(&err.to_string());
+                            return true;
+                        }
+                    }
                     with_argument = true;
                 }
                 ctx_flags.push(flag);
@@ -130,6 +189,15 @@ pub fn execute_command(
 
     let arg_descs = command.arguments;
 
+    // Expressions take up the rest of the arguments since they can contain spaces
+    let joined;
+    if let Some(last) = arg_descs.last() {
+        if matches!(last.argument_type, ArgumentType::Expression) && args.len() > arg_descs.len() {
+            joined = args.split_off(arg_descs.len() - 1).join(" ");
+            args.push(&joined);
+        }
+    }
+
     if args.len() > arg_descs.len() {
         player.send_error_message("Too many arguments.");
         return true;
@@ -146,16 +214,27 @@ pub fn execute_command(
             }
         }
     }
+    if command.mutates_world && !limits::take_operation(&mut plot.players[player_idx]) {
+        return true;
+    }
     if command.mutates_world {
         plot.reset_redpiler();
     }
     let ctx = CommandExecuteContext {
         plot: &mut plot.world,
         player: &mut plot.players[player_idx],
+        message_sender: &plot.message_sender,
         arguments,
         flags: ctx_flags,
+        flag_arguments,
     };
     (command.execute_fn)(ctx);
+
+    // Edits running as a job are finished along with the job
+    let player = &mut plot.players[player_idx];
+    if player.worldedit_job.is_none() {
+        player.worldedit_history.finish(&plot.world);
+    }
     true
 }
 
@@ -197,7 +276,11 @@ enum ArgumentType {
     Mask,
     Pattern,
     String,
+    /// The name of a saved schematic, which is suggested while it is typed
+    Schematic,
     ContainerType,
+    /// An expression with the variables `x`, `y` and `z`
+    Expression,
 }
 
 #[derive(Debug, Clone)]
@@ -206,9 +289,10 @@ enum Argument {
     Direction(BlockFacing),
     DirectionVector(BlockPos),
     Pattern(WorldEditPattern),
-    Mask(WorldEditPattern),
+    Mask(WorldEditMask),
     String(String),
     ContainerType(ContainerType),
+    Expression(Expression),
 }
 
 impl Argument {
@@ -240,7 +324,7 @@ impl Argument {
         }
     }
 
-    fn unwrap_mask(&self) -> &WorldEditPattern {
+    fn unwrap_mask(&self) -> &WorldEditMask {
         match self {
             Argument::Mask(val) => val,
             _ => panic!("Argument was not a Mask"),
@@ -254,6 +338,13 @@ impl Argument {
         }
     }
 
+    fn unwrap_expression(&self) -> &Expression {
+        match self {
+            Argument::Expression(val) => val,
+            _ => panic!("Argument was not an Expression"),
+        }
+    }
+
     fn unwrap_container_type(&self) -> ContainerType {
         match self {
             Argument::ContainerType(val) => *val,
@@ -309,12 +400,11 @@ impl Argument {
                 Ok(pattern) => Ok(Argument::Pattern(pattern)),
                 Err(err) => Err(ArgumentParseError::new(arg_type, &err.to_string())),
             },
-            // Masks are net yet implemented, so in the meantime they can be treated as patterns
-            ArgumentType::Mask => match WorldEditPattern::from_str(arg) {
+            ArgumentType::Mask => match WorldEditMask::from_str(arg) {
                 Ok(pattern) => Ok(Argument::Mask(pattern)),
                 Err(err) => Err(ArgumentParseError::new(arg_type, &err.to_string())),
             },
-            ArgumentType::String => Ok(Argument::String(arg.to_owned())),
+            ArgumentType::String | ArgumentType::Schematic => Ok(Argument::String(arg.to_owned())),
             ArgumentType::DirectionVector => {
                 let mut vec = BlockPos::new(0, 0, 0);
                 let player_facing = player.get_facing();
@@ -352,6 +442,10 @@ impl Argument {
 
                 Ok(Argument::DirectionVector(vec))
             }
+            ArgumentType::Expression => match Expression::compile(arg, &["x", "y", "z"]) {
+                Ok(expression) => Ok(Argument::Expression(expression)),
+                Err(err) => Err(ArgumentParseError::new(arg_type, &err.to_string())),
+            },
             ArgumentType::ContainerType => match arg.parse::<ContainerType>() {
                 Ok(ty) => Ok(Argument::ContainerType(ty)),
                 Err(_) => Err(ArgumentParseError::new(
@@ -371,6 +465,14 @@ struct ArgumentDescription {
 }
 
 macro_rules! argument {
+    ($name:literal, String, $desc:literal, $default:literal) => {
+        ArgumentDescription {
+            name: $name,
+            argument_type: ArgumentType::String,
+            description: $desc,
+            default: Some(Argument::String(String::from($default))),
+        }
+    };
     ($name:literal, $type:ident, $desc:literal) => {
         ArgumentDescription {
             name: $name,
@@ -396,6 +498,13 @@ struct FlagDescription {
 }
 
 macro_rules! flag {
+    ($name:literal, Some($type:ident), $desc:literal) => {
+        FlagDescription {
+            letter: $name,
+            argument_type: Some(ArgumentType::$type),
+            description: $desc,
+        }
+    };
     ($name:literal, $type:ident, $desc:literal) => {
         FlagDescription {
             letter: $name,
@@ -408,14 +517,24 @@ macro_rules! flag {
 struct CommandExecuteContext<'a> {
     plot: &'a mut PlotWorld,
     player: &'a mut Player,
+    message_sender: &'a Sender<Message>,
     arguments: Vec<Argument>,
     flags: Vec<char>,
+    /// The values given to flags which take an argument
+    flag_arguments: Vec<(char, Argument)>,
 }
 
 impl CommandExecuteContext<'_> {
     fn has_flag(&self, c: char) -> bool {
         self.flags.contains(&c)
     }
+
+    fn flag_argument(&self, c: char) -> Option<&Argument> {
+        self.flag_arguments
+            .iter()
+            .find(|(flag, _)| *flag == c)
+            .map(|(_, argument)| argument)
+    }
 }
 
 struct WorldeditCommand {
@@ -446,6 +565,61 @@ impl Default for WorldeditCommand {
     }
 }
 
+// String defaults can't be promoted to a `&'static` slice like the other arguments can
+static CLIPBOARD_ARGUMENTS: Lazy<[ArgumentDescription; 2]> = Lazy::new(|| {
+    [
+        argument!("action", String, "Either share or accept"),
+        argument!(
+            "player",
+            String,
+            "The player to share the clipboard with",
+            ""
+        ),
+    ]
+});
+
+static SCHEM_LIST_ARGUMENTS: Lazy<[ArgumentDescription; 2]> = Lazy::new(|| {
+    [
+        argument!("page", UnsignedInteger, "The page of the list to show", 1),
+        argument!(
+            "filter",
+            String,
+            "Part of the schematic name to search for, optionally after a folder like folder/",
+            ""
+        ),
+    ]
+});
+
+static SCHEM_UPLOAD_ARGUMENTS: Lazy<[ArgumentDescription; 2]> = Lazy::new(|| {
+    [
+        argument!("url", String, "The url to download the schematic from"),
+        argument!(
+            "name",
+            String,
+            "The file name to save the schematic as, taken from the url if left out",
+            ""
+        ),
+    ]
+});
+
+static SEL_ARGUMENTS: Lazy<[ArgumentDescription; 1]> = Lazy::new(|| {
+    [argument!(
+        "selector",
+        String,
+        "Either cuboid, poly, cyl or convex, or nothing to only clear the selection",
+        ""
+    )]
+});
+
+static BRUSH_MASK_ARGUMENTS: Lazy<[ArgumentDescription; 1]> = Lazy::new(|| {
+    [argument!(
+        "mask",
+        String,
+        "Only replace blocks matching this mask, or nothing to clear the mask",
+        ""
+    )]
+});
+
 static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
     map! {
         "up" => WorldeditCommand {
@@ -506,9 +680,11 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             ..Default::default()
         },
         "/sel" => WorldeditCommand {
+            arguments: &*SEL_ARGUMENTS,
             execute_fn: execute_sel,
             description: "Choose a region selector",
             mutates_world: false,
+            permission_node: "worldedit.selection.sel",
             ..Default::default()
         },
         "/set" => WorldeditCommand {
@@ -518,7 +694,7 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             requires_positions: true,
             execute_fn: execute_set,
             description: "Sets all the blocks in the region",
-            permission_node: "worldedit.region.stack",
+            permission_node: "worldedit.region.set",
             ..Default::default()
         },
         "/replace" => WorldeditCommand {
@@ -554,10 +730,20 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             flags: &[
                 flag!('a', None, "Skip air blocks"),
                 flag!('u', None, "Also update all affected blocks"),
+                flag!('o', None, "Paste at the position the clipboard was copied from"),
+                flag!('r', Some(UnsignedInteger), "Rotate the pasted blocks clockwise by a multiple of 90 degrees"),
+                flag!('f', Some(Direction), "Flip the pasted blocks along the axis of a direction"),
             ],
             permission_node: "worldedit.clipboard.paste",
             ..Default::default()
         },
+        "/cancel" => WorldeditCommand {
+            execute_fn: execute_cancel,
+            description: "Stops your running //set, //replace or //paste",
+            mutates_world: false,
+            permission_node: "worldedit.cancel",
+            ..Default::default()
+        },
         "/undo" => WorldeditCommand {
             execute_fn: execute_undo,
             description: "Undoes the last action (from history)",
@@ -579,7 +765,8 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             execute_fn: execute_stack,
             description: "Repeat the contents of the selection",
             flags: &[
-                flag!('a', None, "Ignore air blocks")
+                flag!('a', None, "Ignore air blocks"),
+                flag!('s', None, "Shift the selection to the last stacked copy")
             ],
             permission_node: "worldedit.region.stack",
             ..Default::default()
@@ -610,9 +797,22 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             mutates_world: false,
             ..Default::default()
         },
+        "/distr" => WorldeditCommand {
+            execute_fn: execute_distr,
+            description: "Lists the types of blocks in the selection and how often they occur",
+            flags: &[
+                flag!('c', None, "Use the clipboard instead of the selection"),
+            ],
+            permission_node: "worldedit.analysis.distr",
+            mutates_world: false,
+            ..Default::default()
+        },
         "/load" => WorldeditCommand {
             arguments: &[
-                argument!("name", String, "The file name of the schematic to load")
+                argument!("name", Schematic, "The file name of the schematic to load")
+            ],
+            flags: &[
+                flag!('g', None, "Load the schematic from the shared library")
             ],
             execute_fn: execute_load,
             description: "Loads a schematic file into the clipboard",
@@ -621,9 +821,25 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             normal_completions: false,
             ..Default::default()
         },
+        "/importworld" => WorldeditCommand {
+            arguments: &[
+                argument!("world", String, "The folder name of the world in the imports folder"),
+                argument!("from", String, "The first corner of the area to import, as x,y,z"),
+                argument!("to", String, "The second corner of the area to import, as x,y,z")
+            ],
+            execute_fn: execute_importworld,
+            description: "Imports an area of a vanilla world into the clipboard",
+            permission_node: "worldedit.clipboard.importworld",
+            mutates_world: false,
+            ..Default::default()
+        },
         "/save" => WorldeditCommand {
             arguments: &[
-                argument!("name", String, "The file name of the schematic to save")
+                argument!("name", String, "The file name of the schematic to save"),
+                argument!("version", UnsignedInteger, "The Sponge schematic version to save as (2 or 3)", 2)
+            ],
+            flags: &[
+                flag!('g', None, "Save the schematic to the shared library")
             ],
             requires_clipboard: true,
             execute_fn: execute_save,
@@ -632,6 +848,195 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             mutates_world: false,
             ..Default::default()
         },
+        "schem list" => WorldeditCommand {
+            arguments: &*SCHEM_LIST_ARGUMENTS,
+            flags: &[
+                flag!('g', None, "List the schematics in the shared library")
+            ],
+            execute_fn: execute_schem_list,
+            description: "Lists the saved schematics along with their size, author and creation date",
+            permission_node: "worldedit.schematic.list",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "schem load" => WorldeditCommand {
+            arguments: &[
+                argument!("name", Schematic, "The file name of the schematic to load")
+            ],
+            flags: &[
+                flag!('g', None, "Load the schematic from the shared library")
+            ],
+            execute_fn: execute_load,
+            description: "Loads a schematic file into the clipboard",
+            permission_node: "worldedit.clipboard.load",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "schem save" => WorldeditCommand {
+            arguments: &[
+                argument!("name", String, "The file name of the schematic to save"),
+                argument!("version", UnsignedInteger, "The Sponge schematic version to save as (2 or 3)", 2)
+            ],
+            flags: &[
+                flag!('g', None, "Save the schematic to the shared library")
+            ],
+            requires_clipboard: true,
+            execute_fn: execute_save,
+            description: "Save a schematic file from the clipboard",
+            permission_node: "worldedit.clipboard.save",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "schem download" => WorldeditCommand {
+            arguments: &[
+                argument!("name", Schematic, "The file name of the schematic to download")
+            ],
+            flags: &[
+                flag!('g', None, "Download the schematic from the shared library")
+            ],
+            execute_fn: execute_schem_download,
+            description: "Creates a one-time link to download a schematic from",
+            permission_node: "worldedit.schematic.download",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "schem upload" => WorldeditCommand {
+            arguments: &*SCHEM_UPLOAD_ARGUMENTS,
+            execute_fn: execute_schem_upload,
+            description: "Downloads a schematic from a url into your schematic folder",
+            permission_node: "worldedit.schematic.upload",
+            mutates_world: false,
+            normal_completions: false,
+            ..Default::default()
+        },
+        "/clipboard" => WorldeditCommand {
+            arguments: &*CLIPBOARD_ARGUMENTS,
+            execute_fn: execute_clipboard,
+            description: "Share your clipboard with another player or accept a shared clipboard",
+            permission_node: "worldedit.clipboard.share",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "/visualize" => WorldeditCommand {
+            arguments: &[
+                argument!("target", String, "Either selection, clipboard or off")
+            ],
+            execute_fn: execute_visualize,
+            description: "Toggle showing the selection or where the clipboard would be pasted",
+            permission_node: "worldedit.visualize",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush sphere" => WorldeditCommand {
+            arguments: &[
+                argument!("pattern", Pattern, "The pattern of blocks to set"),
+                argument!("radius", UnsignedInteger, "The radius of the sphere", 2)
+            ],
+            execute_fn: execute_brush_sphere,
+            description: "Bind a sphere brush to the held item",
+            permission_node: "worldedit.brush.sphere",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush cyl" => WorldeditCommand {
+            arguments: &[
+                argument!("pattern", Pattern, "The pattern of blocks to set"),
+                argument!("radius", UnsignedInteger, "The radius of the cylinder", 2),
+                argument!("height", UnsignedInteger, "The height of the cylinder", 1)
+            ],
+            execute_fn: execute_brush_cyl,
+            description: "Bind a cylinder brush to the held item",
+            permission_node: "worldedit.brush.cylinder",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush paste" => WorldeditCommand {
+            requires_clipboard: true,
+            execute_fn: execute_brush_paste,
+            description: "Bind a brush that pastes the clipboard to the held item",
+            flags: &[
+                flag!('a', None, "Skip air blocks"),
+            ],
+            permission_node: "worldedit.brush.clipboard",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush size" => WorldeditCommand {
+            arguments: &[
+                argument!("size", UnsignedInteger, "The new radius of the brush")
+            ],
+            execute_fn: execute_brush_size,
+            description: "Change the size of the brush bound to the held item",
+            permission_node: "worldedit.brush.options.size",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush mask" => WorldeditCommand {
+            arguments: &*BRUSH_MASK_ARGUMENTS,
+            execute_fn: execute_brush_mask,
+            description: "Set the mask of the brush bound to the held item",
+            permission_node: "worldedit.brush.options.mask",
+            mutates_world: false,
+            ..Default::default()
+        },
+        "brush none" => WorldeditCommand {
+            execute_fn: execute_brush_none,
+            description: "Unbind the brush from the held item",
+            mutates_world: false,
+            permission_node: "worldedit.brush.none",
+            ..Default::default()
+        },
+        "/generate" => WorldeditCommand {
+            arguments: &[
+                argument!("pattern", Pattern, "The pattern of blocks to set"),
+                argument!("expression", Expression, "The expression which is positive for blocks to set")
+            ],
+            requires_positions: true,
+            execute_fn: execute_generate,
+            description: "Generates a shape according to an expression",
+            flags: &[
+                flag!('r', None, "Use world coordinates in the expression"),
+                flag!('c', None, "Use the center of the selection as the origin, without scaling"),
+            ],
+            permission_node: "worldedit.generation.shape",
+            ..Default::default()
+        },
+        "/deform" => WorldeditCommand {
+            arguments: &[
+                argument!("expression", Expression, "The expression which moves x, y and z to where to copy each block from")
+            ],
+            requires_positions: true,
+            execute_fn: execute_deform,
+            description: "Deforms the selection with an expression",
+            flags: &[
+                flag!('r', None, "Use world coordinates in the expression"),
+                flag!('c', None, "Use the center of the selection as the origin, without scaling"),
+            ],
+            permission_node: "worldedit.region.deform",
+            ..Default::default()
+        },
+        "/line" => WorldeditCommand {
+            arguments: &[
+                argument!("pattern", Pattern, "The pattern of blocks to set"),
+                argument!("thickness", UnsignedInteger, "The radius of the line", 0)
+            ],
+            requires_positions: true,
+            execute_fn: execute_line,
+            description: "Draws a line between the two corners of the selection",
+            permission_node: "worldedit.region.line",
+            ..Default::default()
+        },
+        "/curve" => WorldeditCommand {
+            arguments: &[
+                argument!("pattern", Pattern, "The pattern of blocks to set"),
+                argument!("thickness", UnsignedInteger, "The radius of the curve", 0)
+            ],
+            requires_positions: true,
+            execute_fn: execute_curve,
+            description: "Draws a spline through the points of a convex selection",
+            permission_node: "worldedit.region.curve",
+            ..Default::default()
+        },
         "/expand" => WorldeditCommand {
             arguments: &[
                 argument!("amount", UnsignedInteger, "Amount to expand the selection by"),
@@ -676,6 +1081,7 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             execute_fn: execute_flip,
             description: "Flip the contents of the clipboard across the origin",
             mutates_world: false,
+            permission_node: "worldedit.clipboard.flip",
             ..Default::default()
         },
         "/rotate" => WorldeditCommand {
@@ -686,6 +1092,7 @@ static COMMANDS: Lazy<HashMap<&'static str, WorldeditCommand>> = Lazy::new(|| {
             execute_fn: execute_rotate,
             description: "Rotate the contents of the clipboard",
             mutates_world: false,
+            permission_node: "worldedit.clipboard.rotate",
             ..Default::default()
         },
         "/rstack" => WorldeditCommand {
@@ -764,16 +1171,12 @@ static ALIASES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
         "/h1" => "/hpos1",
         "/h2" => "/hpos2",
         "/rs" => "/rstack",
+        "/gen" => "/generate",
+        "/vis" => "/visualize",
         "/rc" => "/replacecontainer"
     }
 });
 
-#[derive(Debug, Clone)]
-pub struct WorldEditPatternPart {
-    pub weight: f32,
-    pub block_id: u32,
-}
-
 #[derive(Clone, Debug)]
 pub struct WorldEditClipboard {
     pub offset_x: i32,
@@ -784,8 +1187,12 @@ pub struct WorldEditClipboard {
     pub size_z: u32,
     pub data: PalettedBitBuffer,
     pub block_entities: FxHashMap<BlockPos, BlockEntity>,
+    /// The position the clipboard was copied relative to, which is where `//paste -o` pastes it.
+    /// `None` for clipboards that weren't copied from the world, like loaded schematics.
+    pub copied_at: Option<BlockPos>,
 }
 
+/// The blocks an edit is going to change, kept in the history until the edit has finished
 #[derive(Clone, Debug)]
 pub struct WorldEditUndo {
     clipboards: Vec<WorldEditClipboard>,
@@ -794,117 +1201,16 @@ pub struct WorldEditUndo {
     plot_z: i32,
 }
 
-pub enum PatternParseError {
-    UnknownBlock(String),
-    InvalidPattern(String),
-}
-
-impl fmt::Display for PatternParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            PatternParseError::UnknownBlock(block) => write!(f, "unknown block: {}", block),
-            PatternParseError::InvalidPattern(pattern) => write!(f, "invalid pattern: {}", pattern),
-        }
-    }
-}
-
-pub type PatternParseResult<T> = std::result::Result<T, PatternParseError>;
-
-#[derive(Debug, Clone)]
-pub struct WorldEditPattern {
-    pub parts: Vec<WorldEditPatternPart>,
-}
-
-impl FromStr for WorldEditPattern {
-    type Err = PatternParseError;
-
-    fn from_str(pattern_str: &str) -> PatternParseResult<WorldEditPattern> {
-        let mut pattern = WorldEditPattern { parts: Vec::new() };
-        for part in pattern_str.split(',') {
-            static RE: Lazy<Regex> = Lazy::new(|| {
-                Regex::new(r"^(([0-9]+(\.[0-9]+)?)%)?(=)?([0-9]+|(minecraft:)?[a-zA-Z_]+)(:([0-9]+)|\[(([a-zA-Z_]+=[a-zA-Z0-9]+,?)+?)\])?((\|([^|]*?)){1,4})?$").unwrap()
-            });
-
-            let pattern_match = RE
-                .captures(part)
-                .ok_or_else(|| PatternParseError::InvalidPattern(part.to_owned()))?;
-
-            let block = if pattern_match.get(4).is_some() {
-                Block::from_id(
-                    pattern_match
-                        .get(5)
-                        .map_or("0", |m| m.as_str())
-                        .parse::<u32>()
-                        .unwrap(),
-                )
-            } else {
-                let block_name = pattern_match
-                    .get(5)
-                    .unwrap()
-                    .as_str()
-                    .trim_start_matches("minecraft:");
-                Block::from_name(block_name)
-                    .ok_or_else(|| PatternParseError::UnknownBlock(part.to_owned()))?
-            };
-
-            let weight = pattern_match
-                .get(2)
-                .map_or("100", |m| m.as_str())
-                .parse::<f32>()
-                .unwrap()
-                / 100.0;
-
-            pattern.parts.push(WorldEditPatternPart {
-                weight,
-                block_id: block.get_id(),
-            });
-        }
-
-        Ok(pattern)
-    }
-}
-
-impl WorldEditPattern {
-    pub fn matches(&self, block: Block) -> bool {
-        let block_id = block.get_id();
-        self.parts.iter().any(|part| part.block_id == block_id)
-    }
-
-    pub fn pick(&self) -> Block {
-        let mut weight_sum = 0.0;
-        for part in &self.parts {
-            weight_sum += part.weight;
-        }
-
-        let mut rng = rand::rng();
-        let mut random = rng.random_range(0.0..weight_sum);
-
-        let mut selected = &WorldEditPatternPart {
-            block_id: 0,
-            weight: 0.0,
-        };
-
-        for part in &self.parts {
-            random -= part.weight;
-            if random <= 0.0 {
-                selected = part;
-                break;
-            }
-        }
-
-        Block::from_id(selected.block_id)
-    }
-}
-
 struct WorldEditOperation {
     blocks_updated: usize,
+    region: Region,
     x_range: RangeInclusive<i32>,
     y_range: RangeInclusive<i32>,
     z_range: RangeInclusive<i32>,
 }
 
 impl WorldEditOperation {
-    fn new(first_pos: BlockPos, second_pos: BlockPos) -> WorldEditOperation {
+    fn new(first_pos: BlockPos, second_pos: BlockPos, region: Region) -> WorldEditOperation {
         let start_pos = first_pos.min(second_pos);
         let end_pos = first_pos.max(second_pos);
 
@@ -914,6 +1220,7 @@ impl WorldEditOperation {
 
         WorldEditOperation {
             blocks_updated: 0,
+            region,
             x_range,
             y_range,
             z_range,
@@ -928,6 +1235,11 @@ impl WorldEditOperation {
         self.blocks_updated
     }
 
+    /// Returns true if the position is part of the selection, which might not be a cuboid
+    fn contains(&self, pos: BlockPos) -> bool {
+        self.region.contains(pos)
+    }
+
     fn x_range(&self) -> RangeInclusive<i32> {
         self.x_range.clone()
     }
@@ -980,7 +1292,8 @@ pub fn ray_trace_block(
 fn worldedit_start_operation(player: &mut Player) -> WorldEditOperation {
     let first_pos = player.first_position.unwrap();
     let second_pos = player.second_position.unwrap();
-    WorldEditOperation::new(first_pos, second_pos)
+    let region = Region::new(player.worldedit_selection_mode, &player.worldedit_points);
+    WorldEditOperation::new(first_pos, second_pos, region)
 }
 
 fn create_clipboard(
@@ -1004,6 +1317,7 @@ fn create_clipboard(
         size_z,
         data: PalettedBitBuffer::new((size_x * size_y * size_z) as usize, 9),
         block_entities: FxHashMap::default(),
+        copied_at: Some(origin),
     };
     let mut i = 0;
     for y in start_pos.y..=end_pos.y {
@@ -1026,13 +1340,37 @@ fn create_clipboard(
     cb
 }
 
-fn clear_area(plot: &mut PlotWorld, first_pos: BlockPos, second_pos: BlockPos) {
+/// Sets the blocks in the clipboard which aren't part of the region to air
+fn mask_clipboard(cb: &mut WorldEditClipboard, region: &Region, start_pos: BlockPos) {
+    if matches!(region, Region::Cuboid) {
+        return;
+    }
+    let mut i = 0;
+    for y in 0..cb.size_y as i32 {
+        for z in 0..cb.size_z as i32 {
+            for x in 0..cb.size_x as i32 {
+                let relative = BlockPos::new(x, y, z);
+                if !region.contains(start_pos + relative) {
+                    cb.data.set_entry(i, 0);
+                    cb.block_entities.remove(&relative);
+                }
+                i += 1;
+            }
+        }
+    }
+}
+
+fn clear_area(plot: &mut PlotWorld, first_pos: BlockPos, second_pos: BlockPos, region: &Region) {
     let start_pos = first_pos.min(second_pos);
     let end_pos = first_pos.max(second_pos);
     for y in start_pos.y..=end_pos.y {
         for z in start_pos.z..=end_pos.z {
             for x in start_pos.x..=end_pos.x {
-                plot.set_block_raw(BlockPos::new(x, y, z), 0);
+                let pos = BlockPos::new(x, y, z);
+                if region.contains(pos) {
+                    plot.delete_block_entity(pos);
+                    plot.set_block_raw(pos, 0);
+                }
             }
         }
     }
@@ -1041,15 +1379,20 @@ fn clear_area(plot: &mut PlotWorld, first_pos: BlockPos, second_pos: BlockPos) {
         for chunk_z in (start_pos.z >> 4)..=(end_pos.z >> 4) {
             if let Some(chunk) = plot.get_chunk(chunk_x, chunk_z) {
                 let chunk_data = chunk.encode_packet();
-                for player in &mut plot.packet_senders {
-                    player.send_packet(&chunk_data);
+                for player in &plot.packet_senders {
+                    player.send_packet(chunk_data);
                 }
             }
         }
     }
 }
 
-fn paste_clipboard(plot: &mut PlotWorld, cb: &WorldEditClipboard, pos: BlockPos, ignore_air: bool) {
+pub(super) fn paste_clipboard(
+    plot: &mut PlotWorld,
+    cb: &WorldEditClipboard,
+    pos: BlockPos,
+    ignore_air: bool,
+) {
     let offset_x = pos.x - cb.offset_x;
     let offset_y = pos.y - cb.offset_y;
     let offset_z = pos.z - cb.offset_z;
@@ -1072,7 +1415,10 @@ fn paste_clipboard(plot: &mut PlotWorld, cb: &WorldEditClipboard, pos: BlockPos,
                 if ignore_air && entry == 0 {
                     continue;
                 }
-                plot.set_block_raw(BlockPos::new(x, y, z), entry);
+                let pos = BlockPos::new(x, y, z);
+                // The block entity of the replaced block would otherwise stay around
+                plot.delete_block_entity(pos);
+                plot.set_block_raw(pos, entry);
             }
         }
     }
@@ -1095,6 +1441,16 @@ fn capture_undo(
     player: &mut Player,
     first_pos: BlockPos,
     second_pos: BlockPos,
+) {
+    record_undo(plot, &mut player.worldedit_history, first_pos, second_pos);
+}
+
+/// Saves the blocks in the area to the undo history before they are changed
+fn record_undo(
+    plot: &mut PlotWorld,
+    history: &mut WorldEditHistory,
+    first_pos: BlockPos,
+    second_pos: BlockPos,
 ) {
     let origin = first_pos.min(second_pos);
     let cb = create_clipboard(plot, origin, first_pos, second_pos);
@@ -1104,12 +1460,11 @@ fn capture_undo(
         plot_x: plot.x,
         plot_z: plot.z,
     };
-
-    player.worldedit_undo.push(undo);
-    player.worldedit_redo.clear();
+    history.record(plot, undo);
 }
 
 fn expand_selection(player: &mut Player, amount: BlockPos, contract: bool) {
+    selection::convert_to_cuboid(player);
     let mut p1 = player.first_position.unwrap();
     let mut p2 = player.second_position.unwrap();
 
@@ -1168,8 +1523,33 @@ pub fn add_command_completions(nodes: &mut Vec<CCommandsNode>) {
             // These commands have completions manually defined in plot::commands
             continue;
         }
+        // Subcommands are nested under a literal node for their parent command
+        let (parent_idx, name) = match name.split_once(' ') {
+            Some((parent, name)) => {
+                let existing = nodes[0]
+                    .children
+                    .iter()
+                    .copied()
+                    .find(|&idx| nodes[idx as usize].name == Some(parent));
+                let parent_idx = existing.unwrap_or_else(|| {
+                    let parent_idx = nodes.len() as i32;
+                    nodes[0].children.push(parent_idx);
+                    nodes.push(CCommandsNode {
+                        flags: CommandFlags::LITERAL.bits() as i8,
+                        children: Vec::new(),
+                        redirect_node: None,
+                        name: Some(parent),
+                        parser: None,
+                        suggestions_type: None,
+                    });
+                    parent_idx
+                });
+                (parent_idx as usize, name)
+            }
+            None => (0, *name),
+        };
         let command_idx = nodes.len() as i32;
-        nodes[0].children.push(command_idx);
+        nodes[parent_idx].children.push(command_idx);
         nodes.push(CCommandsNode {
             flags: (CommandFlags::LITERAL | CommandFlags::EXECUTABLE).bits() as i8,
             children: Vec::new(),
@@ -1182,16 +1562,27 @@ pub fn add_command_completions(nodes: &mut Vec<CCommandsNode>) {
             let parent_idx = nodes.len() - 1;
             let arg_idx = nodes.len() as i32;
             nodes[parent_idx].children.push(arg_idx);
+            // The server suggests blocks and schematic names, see `plot::suggestions`
+            let asks_server = matches!(
+                arg.argument_type,
+                ArgumentType::Pattern | ArgumentType::Mask | ArgumentType::Schematic
+            );
+            let mut flags = CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE;
+            if asks_server {
+                flags |= CommandFlags::HAS_SUGGESTIONS_TYPE;
+            }
             nodes.push(CCommandsNode {
-                flags: (CommandFlags::ARGUMENT | CommandFlags::EXECUTABLE).bits() as i8,
+                flags: flags.bits() as i8,
                 children: Vec::new(),
                 redirect_node: None,
                 name: Some(arg.name),
                 parser: Some(match arg.argument_type {
                     ArgumentType::UnsignedInteger => Parser::Integer(0, i32::MAX),
+                    // Greedy phrase
+                    ArgumentType::Expression => Parser::String(2),
                     _ => Parser::String(0),
                 }),
-                suggestions_type: None,
+                suggestions_type: asks_server.then_some("minecraft:ask_server"),
             });
         }
     }
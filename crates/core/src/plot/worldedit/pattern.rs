@@ -0,0 +1,288 @@
+//! Parsing for WorldEdit style patterns and masks.
+//!
+//! A pattern is a comma separated list of blocks to pick from, each with an optional weight
+//! (`50%stone,50%glass`). A mask is a comma separated list of conditions, of which any has to
+//! match (`stone,repeater[delay=2]`). Blocks in masks match every state unless states are given.
+//! Masks can also be `#existing` to match everything but air, or be negated with `!`.
+
+use mchprs_blocks::blocks::Block;
+use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum PatternParseError {
+    UnknownBlock(String),
+    InvalidPattern(String),
+    InvalidState(String),
+}
+
+impl fmt::Display for PatternParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternParseError::UnknownBlock(block) => write!(f, "unknown block: {}", block),
+            PatternParseError::InvalidPattern(pattern) => write!(f, "invalid pattern: {}", pattern),
+            PatternParseError::InvalidState(state) => write!(f, "invalid block state: {}", state),
+        }
+    }
+}
+
+pub type PatternParseResult<T> = std::result::Result<T, PatternParseError>;
+
+/// Splits on commas that aren't inside of block state brackets
+fn split_parts(input: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Parses a block name or `=id`, optionally followed by block states in brackets. Returns the
+/// block along with the states that were given.
+fn parse_block(input: &str) -> PatternParseResult<(Block, Vec<(&str, &str)>)> {
+    let (name, states) = match input.split_once('[') {
+        Some((name, states)) => {
+            let states = states
+                .strip_suffix(']')
+                .ok_or_else(|| PatternParseError::InvalidPattern(input.to_owned()))?;
+            (name, Some(states))
+        }
+        None => (input, None),
+    };
+
+    let mut block = if let Some(id) = name.strip_prefix('=') {
+        let id = id
+            .parse::<u32>()
+            .map_err(|_| PatternParseError::InvalidPattern(input.to_owned()))?;
+        Block::from_id(id)
+    } else {
+        Block::from_name(name.trim_start_matches("minecraft:"))
+            .ok_or_else(|| PatternParseError::UnknownBlock(name.to_owned()))?
+    };
+
+    let mut parsed_states = Vec::new();
+    if let Some(states) = states.filter(|states| !states.is_empty()) {
+        for state in states.split(',') {
+            let (key, value) = state
+                .split_once('=')
+                .ok_or_else(|| PatternParseError::InvalidState(state.to_owned()))?;
+            parsed_states.push((key, value));
+        }
+        block.set_properties(parsed_states.iter().copied().collect());
+        // Invalid values are either ignored or produce a different block once encoded, so check
+        // that the block state still has all of them
+        block = Block::from_id(block.get_id());
+        let properties = block.properties();
+        for &(key, value) in &parsed_states {
+            if properties.get(key).map(String::as_str) != Some(value) {
+                return Err(PatternParseError::InvalidState(format!(
+                    "{}={}",
+                    key, value
+                )));
+            }
+        }
+    }
+
+    Ok((block, parsed_states))
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldEditPatternPart {
+    pub weight: f32,
+    pub block_id: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldEditPattern {
+    pub parts: Vec<WorldEditPatternPart>,
+}
+
+impl FromStr for WorldEditPattern {
+    type Err = PatternParseError;
+
+    fn from_str(pattern_str: &str) -> PatternParseResult<WorldEditPattern> {
+        let mut pattern = WorldEditPattern { parts: Vec::new() };
+        for part in split_parts(pattern_str) {
+            let (weight, block) = match part.split_once('%') {
+                Some((weight, block)) => {
+                    let weight = weight
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|weight| *weight >= 0.0)
+                        .ok_or_else(|| PatternParseError::InvalidPattern(part.to_owned()))?;
+                    (weight, block)
+                }
+                None => (100.0, part),
+            };
+            let (block, _) = parse_block(block)?;
+
+            pattern.parts.push(WorldEditPatternPart {
+                weight: weight / 100.0,
+                block_id: block.get_id(),
+            });
+        }
+
+        if pattern.parts.iter().all(|part| part.weight == 0.0) {
+            return Err(PatternParseError::InvalidPattern(pattern_str.to_owned()));
+        }
+        Ok(pattern)
+    }
+}
+
+impl WorldEditPattern {
+    pub fn pick(&self) -> Block {
+        let mut weight_sum = 0.0;
+        for part in &self.parts {
+            weight_sum += part.weight;
+        }
+
+        let mut rng = rand::rng();
+        let mut random = rng.random_range(0.0..weight_sum);
+
+        let mut selected = &WorldEditPatternPart {
+            block_id: 0,
+            weight: 0.0,
+        };
+
+        for part in &self.parts {
+            random -= part.weight;
+            if random <= 0.0 {
+                selected = part;
+                break;
+            }
+        }
+
+        Block::from_id(selected.block_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WorldEditMask {
+    /// Matches a block type, limited to the given states if there are any
+    Block {
+        name: &'static str,
+        states: Vec<(String, String)>,
+    },
+    /// Matches a single block state id, used for `=id`
+    BlockId(u32),
+    /// Matches every block except air
+    Existing,
+    Not(Box<WorldEditMask>),
+    /// Matches if any of the masks match
+    Any(Vec<WorldEditMask>),
+}
+
+impl WorldEditMask {
+    fn parse_part(part: &str) -> PatternParseResult<WorldEditMask> {
+        if let Some(part) = part.strip_prefix('!') {
+            return Ok(WorldEditMask::Not(Box::new(WorldEditMask::parse_part(
+                part,
+            )?)));
+        }
+        if let Some(name) = part.strip_prefix('#') {
+            return match name {
+                "existing" => Ok(WorldEditMask::Existing),
+                _ => Err(PatternParseError::InvalidPattern(part.to_owned())),
+            };
+        }
+
+        let (block, states) = parse_block(part)?;
+        if part.starts_with('=') {
+            return Ok(WorldEditMask::BlockId(block.get_id()));
+        }
+        Ok(WorldEditMask::Block {
+            name: block.get_name(),
+            states: states
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        })
+    }
+
+    pub fn matches(&self, block: Block) -> bool {
+        match self {
+            WorldEditMask::Block { name, states } => {
+                if block.get_name() != *name {
+                    return false;
+                }
+                if states.is_empty() {
+                    return true;
+                }
+                let properties = block.properties();
+                states
+                    .iter()
+                    .all(|(key, value)| properties.get(key.as_str()) == Some(value))
+            }
+            WorldEditMask::BlockId(id) => block.get_id() == *id,
+            WorldEditMask::Existing => !matches!(block, Block::Air {}),
+            WorldEditMask::Not(mask) => !mask.matches(block),
+            WorldEditMask::Any(masks) => masks.iter().any(|mask| mask.matches(block)),
+        }
+    }
+}
+
+impl FromStr for WorldEditMask {
+    type Err = PatternParseError;
+
+    fn from_str(mask_str: &str) -> PatternParseResult<WorldEditMask> {
+        let mut masks = split_parts(mask_str)
+            .into_iter()
+            .map(WorldEditMask::parse_part)
+            .collect::<PatternParseResult<Vec<_>>>()?;
+        Ok(if masks.len() == 1 {
+            masks.pop().unwrap()
+        } else {
+            WorldEditMask::Any(masks)
+        })
+    }
+}
+
+#[test]
+fn pattern_parse_test() {
+    let pattern = WorldEditPattern::from_str("25%stone,75%minecraft:glass").unwrap();
+    assert_eq!(pattern.parts.len(), 2);
+    assert_eq!(pattern.parts[0].weight, 0.25);
+    assert_eq!(pattern.parts[1].block_id, Block::Glass {}.get_id());
+
+    let pattern = WorldEditPattern::from_str("repeater[delay=2,facing=west]").unwrap();
+    let properties = Block::from_id(pattern.parts[0].block_id).properties();
+    assert_eq!(properties["delay"], "2");
+    assert_eq!(properties["facing"], "west");
+
+    assert!(WorldEditPattern::from_str("repeater[delay=9]").is_err());
+    assert!(WorldEditPattern::from_str("not_a_block").is_err());
+}
+
+#[test]
+fn mask_matches_test() {
+    let mut repeater = Block::from_name("repeater").unwrap();
+    let stone = Block::from_name("stone").unwrap();
+
+    let mask = WorldEditMask::from_str("repeater").unwrap();
+    repeater.set_properties(std::collections::HashMap::from([("delay", "3")]));
+    assert!(mask.matches(repeater));
+    assert!(!mask.matches(stone));
+
+    let mask = WorldEditMask::from_str("repeater[delay=2],stone").unwrap();
+    assert!(!mask.matches(repeater));
+    assert!(mask.matches(stone));
+    repeater.set_properties(std::collections::HashMap::from([("delay", "2")]));
+    assert!(mask.matches(repeater));
+
+    let mask = WorldEditMask::from_str("!air").unwrap();
+    assert!(mask.matches(stone));
+    assert!(!mask.matches(Block::Air {}));
+    assert!(WorldEditMask::from_str("#existing").unwrap().matches(stone));
+}
@@ -1,20 +1,29 @@
-//! This implements Sponge Schematic Specification ver. 2
+//! This implements Sponge Schematic Specification ver. 1, 2 and 3. Schematics are saved as
+//! ver. 2 unless ver. 3 is requested.
 //! https://github.com/SpongePowered/Schematic-Specification/blob/master/versions/schematic-2.md
+//! https://github.com/SpongePowered/Schematic-Specification/blob/master/versions/schematic-3.md
 
-use super::WorldEditClipboard;
+use super::{create_clipboard, WorldEditClipboard};
+use crate::plot::PlotWorld;
 use crate::server::MC_DATA_VERSION;
 use anyhow::{bail, Context, Result};
 use itertools::Itertools;
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::Block;
 use mchprs_blocks::BlockPos;
+use mchprs_save_data::schematic_index::{SchematicIndex, SchematicIndexError, SchematicMetadata};
 use mchprs_world::storage::PalettedBitBuffer;
+use mchprs_world::TickEntry;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rustc_hash::FxHashMap;
 use serde::Serialize;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
 
 macro_rules! nbt_as {
     // I'm not sure if path is the right type here.
@@ -29,7 +38,7 @@ macro_rules! nbt_as {
 
 fn parse_block(str: &str) -> Option<Block> {
     static RE: Lazy<Regex> =
-        Lazy::new(|| Regex::new(r"(?:minecraft:)?([a-z_]+)(?:\[([a-z=,0-9]+)\])?").unwrap());
+        Lazy::new(|| Regex::new(r"(?:minecraft:)?([a-z_]+)(?:\[([a-z_=,0-9]+)\])?").unwrap());
     let captures = RE.captures(str)?;
     let mut block = Block::from_name(captures.get(1)?.as_str()).unwrap_or(Block::Air {});
     if let Some(properties_match) = captures.get(2) {
@@ -44,22 +53,156 @@ fn parse_block(str: &str) -> Option<Block> {
 }
 
 pub fn load_schematic(file_name: &str) -> Result<WorldEditClipboard> {
-    let mut file = File::open("./schems/".to_owned() + file_name)?;
+    load_schematic_file(Path::new("./schems").join(file_name))
+}
+
+pub fn load_schematic_file(path: impl AsRef<Path>) -> Result<WorldEditClipboard> {
+    let mut file = File::open(path)?;
     let nbt = nbt::Blob::from_gzip_reader(&mut file)?;
 
+    // Version 3 wraps everything in a `Schematic` compound
     let root = if nbt.content.contains_key("Schematic") {
         nbt_as!(&nbt["Schematic"], nbt::Value::Compound)
     } else {
         &nbt.content
     };
 
-    let version = nbt_as!(root["Version"], nbt::Value::Int);
+    let version = nbt_as!(
+        *root.get("Version").context("schematic has no version")?,
+        nbt::Value::Int
+    );
     match version {
-        2 | 3 => load_schematic_sponge(root, version),
+        1..=3 => load_schematic_sponge(root, version),
         _ => bail!("unknown schematic version: {}", version),
     }
 }
 
+const INDEX_PATH: &str = "./schems/index.bin";
+
+/// The index is shared between all plots. It is rebuilt from the files in the schematic folder
+/// the first time it's used if there is no index file yet.
+static SCHEMATIC_INDEX: Lazy<Mutex<SchematicIndex>> = Lazy::new(|| Mutex::new(load_index()));
+
+fn load_index() -> SchematicIndex {
+    match SchematicIndex::load_from_file(INDEX_PATH) {
+        Ok(index) => return index,
+        Err(SchematicIndexError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => error!("Failed to load the schematic index, rebuilding it: {}", err),
+    }
+    info!("Building the schematic index");
+    let index = rebuild_index();
+    save_index(&index);
+    index
+}
+
+fn save_index(index: &SchematicIndex) {
+    if let Err(err) = fs::create_dir_all("./schems") {
+        error!("Failed to create the schematic folder: {}", err);
+        return;
+    }
+    if let Err(err) = index.save_to_file(INDEX_PATH) {
+        error!("Failed to save the schematic index: {}", err);
+    }
+}
+
+fn rebuild_index() -> SchematicIndex {
+    let root = Path::new("./schems");
+    let mut index = SchematicIndex::default();
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                folders.push(path);
+                continue;
+            }
+            let Ok(name) = path.strip_prefix(root) else {
+                continue;
+            };
+            let name = name.to_string_lossy().replace('\\', "/");
+            if !name.ends_with(".schem") && !name.ends_with(".schematic") {
+                continue;
+            }
+            match load_schematic(&name) {
+                Ok(clipboard) => index.insert(name.clone(), file_metadata(&name, &clipboard)),
+                Err(err) => error!("Failed to index schematic {}: {}", name, err),
+            }
+        }
+    }
+    index
+}
+
+fn clipboard_size(clipboard: &WorldEditClipboard) -> [u32; 3] {
+    [clipboard.size_x, clipboard.size_y, clipboard.size_z]
+}
+
+/// Metadata for schematics that were put into the schematic folder by hand, which only have
+/// their modification time to go by
+fn file_metadata(file_name: &str, clipboard: &WorldEditClipboard) -> SchematicMetadata {
+    let created = fs::metadata(format!("./schems/{}", file_name))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+    SchematicMetadata {
+        author: None,
+        size: clipboard_size(clipboard),
+        created,
+    }
+}
+
+/// Adds a schematic that was just saved to the index
+pub fn index_saved_schematic(file_name: &str, author: &str, clipboard: &WorldEditClipboard) {
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let metadata = SchematicMetadata {
+        author: Some(author.to_owned()),
+        size: clipboard_size(clipboard),
+        created,
+    };
+    let mut index = SCHEMATIC_INDEX.lock().unwrap();
+    index.insert(file_name.to_owned(), metadata);
+    save_index(&index);
+}
+
+/// Adds a schematic that was just loaded to the index if it isn't in there yet
+pub fn index_loaded_schematic(file_name: &str, clipboard: &WorldEditClipboard) {
+    let mut index = SCHEMATIC_INDEX.lock().unwrap();
+    if index.get(file_name).is_none() {
+        index.insert(file_name.to_owned(), file_metadata(file_name, clipboard));
+        save_index(&index);
+    }
+}
+
+/// Returns the schematics in the folder whose name contains the search, with their names relative
+/// to the folder
+pub fn search_schematics(folder: &str, search: &str) -> Vec<(String, SchematicMetadata)> {
+    let index = SCHEMATIC_INDEX.lock().unwrap();
+    index
+        .search(folder, search)
+        .map(|(name, metadata)| (name.to_owned(), metadata.clone()))
+        .collect()
+}
+
+/// Reads a varint from the block data, advancing `i` past it
+fn read_varint(blocks: &[i8], i: &mut usize) -> Result<u32> {
+    let mut value = 0;
+    // Max varint length is 5
+    for varint_len in 0..5 {
+        let byte = *blocks.get(*i).context("block data ended unexpectedly")? as u8;
+        *i += 1;
+        value |= ((byte & 0b0111_1111) as u32) << (varint_len * 7);
+        if byte & 0b1000_0000 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("varint in block data is too long")
+}
+
 fn read_block_container(
     nbt: &nbt::Map<String, nbt::Value>,
     version: i32,
@@ -69,7 +212,10 @@ fn read_block_container(
 ) -> Result<(PalettedBitBuffer, FxHashMap<BlockPos, BlockEntity>)> {
     use nbt::Value;
 
-    let nbt_palette = nbt_as!(&nbt["Palette"], Value::Compound);
+    let nbt_palette = nbt_as!(
+        nbt.get("Palette").context("schematic has no palette")?,
+        Value::Compound
+    );
     let mut palette: FxHashMap<u32, u32> = FxHashMap::default();
     for (k, v) in nbt_palette {
         let id = *nbt_as!(v, Value::Int) as u32;
@@ -78,36 +224,38 @@ fn read_block_container(
     }
 
     let data_name = match version {
-        2 => "BlockData",
+        1 | 2 => "BlockData",
         3 => "Data",
         _ => unreachable!(),
     };
-    let blocks: Vec<u8> = nbt_as!(&nbt[data_name], Value::ByteArray)
-        .iter()
-        .map(|b| *b as u8)
-        .collect();
+    let blocks = nbt_as!(
+        nbt.get(data_name).context("schematic has no block data")?,
+        Value::ByteArray
+    );
 
     let mut data = PalettedBitBuffer::new((size_x * size_y * size_z) as usize, 9);
     let mut i = 0;
     for y_offset in (0..size_y).map(|y| y * size_z * size_x) {
         for z_offset in (0..size_z).map(|z| z * size_x) {
             for x in 0..size_x {
-                let mut blockstate_id = 0;
-                // Max varint length is 5
-                for varint_len in 0..=5 {
-                    blockstate_id |= ((blocks[i] & 127) as u32) << (varint_len * 7);
-                    if (blocks[i] & 128) != 128 {
-                        i += 1;
-                        break;
-                    }
-                    i += 1;
-                }
-                let entry = *palette.get(&blockstate_id).unwrap();
+                let blockstate_id = read_varint(blocks, &mut i)?;
+                let entry = *palette
+                    .get(&blockstate_id)
+                    .with_context(|| format!("block {} is not in the palette", blockstate_id))?;
                 data.set_entry((y_offset + z_offset + x) as usize, entry);
             }
         }
     }
-    let block_entities = nbt_as!(&nbt["BlockEntities"], Value::List);
+
+    let block_entities_name = match version {
+        1 => "TileEntities",
+        2 | 3 => "BlockEntities",
+        _ => unreachable!(),
+    };
+    let block_entities = match nbt.get(block_entities_name) {
+        Some(block_entities) => nbt_as!(block_entities, Value::List).as_slice(),
+        None => &[],
+    };
     let mut parsed_block_entities = FxHashMap::default();
     for block_entity in block_entities {
         let val = nbt_as!(block_entity, Value::Compound);
@@ -118,10 +266,9 @@ fn read_block_container(
             z: pos_array[2],
         };
         let id = nbt_as!(&val.get("Id").unwrap_or_else(|| &val["id"]), Value::String);
-        let data = match version {
-            2 => val,
-            3 => nbt_as!(&val["Data"], Value::Compound),
-            _ => unreachable!(),
+        let data = match (version, val.get("Data")) {
+            (3, Some(data)) => nbt_as!(data, Value::Compound),
+            _ => val,
         };
         if let Some(parsed) = BlockEntity::from_nbt(id, data) {
             parsed_block_entities.insert(pos, parsed);
@@ -131,43 +278,66 @@ fn read_block_container(
     Ok((data, parsed_block_entities))
 }
 
+/// Reads the offset WorldEdit stores in the metadata of version 1 and 2 schematics
+fn read_we_offset(nbt: &nbt::Map<String, nbt::Value>) -> Result<(i32, i32, i32)> {
+    use nbt::Value;
+
+    let Some(metadata) = nbt.get("Metadata") else {
+        return Ok((0, 0, 0));
+    };
+    let metadata = nbt_as!(metadata, Value::Compound);
+    let read = |name| -> Result<i32> {
+        Ok(match metadata.get(name) {
+            Some(offset) => *nbt_as!(offset, Value::Int),
+            None => 0,
+        })
+    };
+    Ok((read("WEOffsetX")?, read("WEOffsetY")?, read("WEOffsetZ")?))
+}
+
 fn load_schematic_sponge(
     nbt: &nbt::Map<String, nbt::Value>,
     version: i32,
 ) -> Result<WorldEditClipboard> {
     use nbt::Value;
 
-    let size_x = nbt_as!(nbt["Width"], Value::Short) as u32;
-    let size_z = nbt_as!(nbt["Length"], Value::Short) as u32;
-    let size_y = nbt_as!(nbt["Height"], Value::Short) as u32;
+    let size_x = nbt_as!(nbt["Width"], Value::Short) as u16 as u32;
+    let size_z = nbt_as!(nbt["Length"], Value::Short) as u16 as u32;
+    let size_y = nbt_as!(nbt["Height"], Value::Short) as u16 as u32;
 
     let (offset_x, offset_y, offset_z) = match version {
-        2 => {
-            let metadata = nbt_as!(&nbt["Metadata"], Value::Compound);
-            (
-                -nbt_as!(metadata["WEOffsetX"], Value::Int),
-                -nbt_as!(metadata["WEOffsetY"], Value::Int),
-                -nbt_as!(metadata["WEOffsetZ"], Value::Int),
-            )
-        }
-        3 => {
-            let offset_array = nbt_as!(&nbt["Offset"], Value::IntArray);
-            (-offset_array[0], -offset_array[1], -offset_array[2])
+        1 | 2 => {
+            let (x, y, z) = read_we_offset(nbt)?;
+            (-x, -y, -z)
         }
+        3 => match nbt.get("Offset") {
+            Some(offset) => {
+                let offset_array = nbt_as!(offset, Value::IntArray);
+                (-offset_array[0], -offset_array[1], -offset_array[2])
+            }
+            None => (0, 0, 0),
+        },
         _ => unreachable!(),
     };
 
-    let (data, block_entities) = read_block_container(
-        match version {
-            2 => nbt,
-            3 => nbt_as!(&nbt["Blocks"], Value::Compound),
-            _ => unreachable!(),
+    let (data, block_entities) = match version {
+        1 | 2 => read_block_container(nbt, version, size_x, size_y, size_z)?,
+        // Schematics without any blocks leave out the block container
+        3 => match nbt.get("Blocks") {
+            Some(blocks) => read_block_container(
+                nbt_as!(blocks, Value::Compound),
+                version,
+                size_x,
+                size_y,
+                size_z,
+            )?,
+            None => (
+                PalettedBitBuffer::new((size_x * size_y * size_z) as usize, 9),
+                FxHashMap::default(),
+            ),
         },
-        version,
-        size_x,
-        size_y,
-        size_z,
-    )?;
+        _ => unreachable!(),
+    };
     Ok(WorldEditClipboard {
         size_x,
         size_y,
@@ -177,6 +347,7 @@ fn load_schematic_sponge(
         offset_z,
         data,
         block_entities,
+        copied_at: None,
     })
 }
 
@@ -207,18 +378,57 @@ struct Schematic {
     data_version: i32,
 }
 
-pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard) -> Result<()> {
-    let mut path = PathBuf::from("./schems");
-    path.push(file_name);
-    fs::create_dir_all(path.parent().unwrap())?;
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlockContainerV3 {
+    palette: nbt::Blob,
+    #[serde(serialize_with = "nbt::i8_array")]
+    data: Vec<i8>,
+    block_entities: Vec<nbt::Blob>,
+}
 
-    let mut file = File::create("./schems/".to_owned() + file_name)?;
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SchematicV3 {
+    version: i32,
+    data_version: i32,
+    width: i16,
+    height: i16,
+    length: i16,
+    #[serde(serialize_with = "nbt::i32_array")]
+    offset: Vec<i32>,
+    blocks: BlockContainerV3,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<MetadataV3>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PendingTick {
+    #[serde(serialize_with = "nbt::i32_array")]
+    pos: Vec<i32>,
+    ticks: i32,
+    priority: i32,
+}
+
+#[derive(Serialize)]
+struct MetadataV3 {
+    #[serde(rename = "MCHPRSPendingTicks")]
+    pending_ticks: Vec<PendingTick>,
+}
+
+/// Version 3 schematics are stored in a `Schematic` compound inside of an unnamed root compound
+#[derive(Serialize)]
+struct SchematicV3Root {
+    #[serde(rename = "Schematic")]
+    schematic: SchematicV3,
+}
+
+/// Encodes the blocks of the clipboard into a palette and varint block data
+fn write_block_data(clipboard: &WorldEditClipboard) -> Result<(nbt::Blob, Vec<i8>)> {
     let size_x = clipboard.size_x;
     let size_y = clipboard.size_y;
     let size_z = clipboard.size_z;
-    let offset_x = -clipboard.offset_x;
-    let offset_y = -clipboard.offset_y;
-    let offset_z = -clipboard.offset_z;
     let blocks = &clipboard.data;
 
     let mut data = Vec::new();
@@ -249,7 +459,7 @@ pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard) -> Result
                 };
 
                 loop {
-                    let mut temp = (idx & 0b1111_1111) as u8;
+                    let mut temp = (idx & 0b0111_1111) as u8;
                     idx >>= 7;
                     if idx != 0 {
                         temp |= 0b1000_0000;
@@ -267,13 +477,96 @@ pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard) -> Result
     for (i, entry) in pallette.iter().enumerate() {
         encoded_pallete.insert(entry, i as i32)?;
     }
+    Ok((encoded_pallete, data))
+}
+
+pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard, version: u32) -> Result<()> {
+    write_schematic(file_name, clipboard, version, &[])
+}
+
+/// Saves a whole plot as a version 3 schematic. The pending ticks are kept in the metadata since
+/// the specification has no place for them.
+pub fn save_plot_schematic(file_name: &str, plot: &mut PlotWorld) -> Result<WorldEditClipboard> {
+    let (first_pos, second_pos) = plot.get_corners();
+    let clipboard = create_clipboard(plot, first_pos, first_pos, second_pos);
+    let pending_ticks: Vec<TickEntry> = plot
+        .to_be_ticked
+        .iter()
+        .map(|tick| TickEntry {
+            pos: tick.pos - first_pos,
+            ..tick.clone()
+        })
+        .collect();
+    write_schematic(file_name, &clipboard, 3, &pending_ticks)?;
+    Ok(clipboard)
+}
+
+fn write_schematic(
+    file_name: &str,
+    clipboard: &WorldEditClipboard,
+    version: u32,
+    pending_ticks: &[TickEntry],
+) -> Result<()> {
+    if !matches!(version, 2 | 3) {
+        bail!("cannot save schematic version {}", version);
+    }
+
+    let mut path = PathBuf::from("./schems");
+    path.push(file_name);
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    let mut file = File::create("./schems/".to_owned() + file_name)?;
+    let offset_x = -clipboard.offset_x;
+    let offset_y = -clipboard.offset_y;
+    let offset_z = -clipboard.offset_z;
+    let (palette, data) = write_block_data(clipboard)?;
 
     let mut block_entities = Vec::new();
     for (pos, block_entity) in &clipboard.block_entities {
-        if let Some(mut blob) = block_entity.to_nbt(false) {
-            blob.insert("Pos", nbt::Value::IntArray(vec![pos.x, pos.y, pos.z]))?;
-            block_entities.push(blob);
-        }
+        let Some(blob) = block_entity.to_nbt(false) else {
+            continue;
+        };
+        let id = blob.get("id").cloned().context("block entity has no id")?;
+        let mut entry = if version == 3 {
+            let mut entry = nbt::Blob::new();
+            entry.insert("Data", nbt::Value::Compound(blob.content))?;
+            entry
+        } else {
+            blob
+        };
+        entry.insert("Id", id)?;
+        entry.insert("Pos", nbt::Value::IntArray(vec![pos.x, pos.y, pos.z]))?;
+        block_entities.push(entry);
+    }
+
+    if version == 3 {
+        let schematic = SchematicV3Root {
+            schematic: SchematicV3 {
+                version: 3,
+                data_version: MC_DATA_VERSION,
+                width: clipboard.size_x as i16,
+                height: clipboard.size_y as i16,
+                length: clipboard.size_z as i16,
+                offset: vec![offset_x, offset_y, offset_z],
+                blocks: BlockContainerV3 {
+                    palette,
+                    data,
+                    block_entities,
+                },
+                metadata: (!pending_ticks.is_empty()).then(|| MetadataV3 {
+                    pending_ticks: pending_ticks
+                        .iter()
+                        .map(|tick| PendingTick {
+                            pos: vec![tick.pos.x, tick.pos.y, tick.pos.z],
+                            ticks: tick.ticks_left as i32,
+                            priority: tick.tick_priority as i32,
+                        })
+                        .collect(),
+                }),
+            },
+        };
+        nbt::to_gzip_writer(&mut file, &schematic, None)?;
+        return Ok(());
     }
 
     let metadata = Metadata {
@@ -282,12 +575,12 @@ pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard) -> Result
         offset_z,
     };
     let schematic = Schematic {
-        width: size_x as i16,
-        length: size_z as i16,
-        height: size_y as i16,
+        width: clipboard.size_x as i16,
+        length: clipboard.size_z as i16,
+        height: clipboard.size_y as i16,
         block_data: data,
         block_entities,
-        palette: encoded_pallete,
+        palette,
         metadata,
         version: 2,
         data_version: MC_DATA_VERSION,
@@ -296,3 +589,12 @@ pub fn save_schematic(file_name: &str, clipboard: &WorldEditClipboard) -> Result
 
     Ok(())
 }
+
+#[test]
+fn parse_block_state_test() {
+    let block = parse_block("minecraft:note_block[instrument=iron_xylophone,note=7,powered=false]")
+        .unwrap();
+    let properties = block.properties();
+    assert_eq!(properties["instrument"], "iron_xylophone");
+    assert_eq!(properties["note"], "7");
+}
@@ -0,0 +1,356 @@
+//! Selections other than cuboids. Their points are kept in `Player::worldedit_points`, while the
+//! first and second positions are kept as their bounding box. Commands iterate over the bounding
+//! box and use a [`Region`] to skip the blocks that aren't part of the selection.
+
+use crate::player::Player;
+use mchprs_blocks::BlockPos;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    Cuboid,
+    /// A polygon on the x/z plane, spanning from the lowest to the highest of its vertices
+    Poly,
+    /// An upright elliptic cylinder. The first point is its center and the other points extend
+    /// its radius.
+    Cylinder,
+    /// The convex hull of any number of vertices, which //curve goes through in order
+    Convex,
+}
+
+impl SelectionMode {
+    /// The name of the selection shape in the WorldEditCUI protocol
+    pub fn cui_name(self) -> &'static str {
+        match self {
+            SelectionMode::Cuboid => "cuboid",
+            SelectionMode::Poly => "polygon2d",
+            SelectionMode::Cylinder => "cylinder",
+            SelectionMode::Convex => "polyhedron",
+        }
+    }
+}
+
+impl FromStr for SelectionMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cuboid" => Ok(SelectionMode::Cuboid),
+            "poly" | "polygon" => Ok(SelectionMode::Poly),
+            "cyl" | "cylinder" => Ok(SelectionMode::Cylinder),
+            "convex" | "hull" | "polyhedron" => Ok(SelectionMode::Convex),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The shape of a selection, used to check which blocks of its bounding box are selected
+pub enum Region {
+    Cuboid,
+    Poly(Vec<[i64; 2]>),
+    Cylinder {
+        center: [f64; 2],
+        radius: [f64; 2],
+    },
+    /// The half-spaces whose intersection is the convex hull, as normals and offsets
+    Convex(Vec<([f64; 3], f64)>),
+}
+
+impl Region {
+    pub fn new(mode: SelectionMode, points: &[BlockPos]) -> Region {
+        if points.is_empty() {
+            return Region::Cuboid;
+        }
+        match mode {
+            SelectionMode::Cuboid => Region::Cuboid,
+            SelectionMode::Poly => {
+                Region::Poly(points.iter().map(|p| [p.x as i64, p.z as i64]).collect())
+            }
+            SelectionMode::Cylinder => {
+                let [center, radius] = cylinder_shape(points);
+                Region::Cylinder { center, radius }
+            }
+            SelectionMode::Convex => Region::Convex(hull_planes(points)),
+        }
+    }
+
+    /// Returns true if the position is part of the selection. Positions outside of the bounding
+    /// box aren't checked for.
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        match self {
+            Region::Cuboid => true,
+            Region::Poly(vertices) => polygon_contains(vertices, [pos.x as i64, pos.z as i64]),
+            Region::Cylinder { center, radius } => {
+                // Adding half a block makes the edges look less jagged
+                let dx = (pos.x as f64 - center[0]) / (radius[0] + 0.5);
+                let dz = (pos.z as f64 - center[1]) / (radius[1] + 0.5);
+                dx * dx + dz * dz <= 1.0
+            }
+            Region::Convex(planes) => {
+                let pos = [pos.x as f64, pos.y as f64, pos.z as f64];
+                // Blocks which are at most half a block outside still count, so that flat or
+                // thin hulls select something
+                planes
+                    .iter()
+                    .all(|(normal, offset)| dot(*normal, pos) - offset <= 0.5)
+            }
+        }
+    }
+}
+
+/// Returns the center and the radius on the x and z axes
+fn cylinder_shape(points: &[BlockPos]) -> [[f64; 2]; 2] {
+    let center = points[0];
+    let mut radius = [0.0, 0.0];
+    for point in &points[1..] {
+        radius[0] = f64::max(radius[0], (point.x - center.x).abs() as f64);
+        radius[1] = f64::max(radius[1], (point.z - center.z).abs() as f64);
+    }
+    [[center.x as f64, center.z as f64], radius]
+}
+
+fn polygon_contains(vertices: &[[i64; 2]], [x, z]: [i64; 2]) -> bool {
+    let mut inside = false;
+    for i in 0..vertices.len() {
+        let [ax, az] = vertices[i];
+        let [bx, bz] = vertices[(i + 1) % vertices.len()];
+        // Blocks on the edges are always part of the polygon
+        let cross = (bx - ax) * (z - az) - (bz - az) * (x - ax);
+        if cross == 0 && x >= ax.min(bx) && x <= ax.max(bx) && z >= az.min(bz) && z <= az.max(bz) {
+            return true;
+        }
+        if (az > z) != (bz > z) {
+            let intersect_x = ax as f64 + (z - az) as f64 * (bx - ax) as f64 / (bz - az) as f64;
+            if (x as f64) < intersect_x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Finds the planes that bound the convex hull of the points. Every plane through three of the
+/// points which has all points on one side of it is a face of the hull. Planes through two points
+/// and along an axis are checked as well so that hulls which are flat or a line are bounded too.
+fn hull_planes(points: &[BlockPos]) -> Vec<([f64; 3], f64)> {
+    let points: Vec<[f64; 3]> = points
+        .iter()
+        .map(|p| [p.x as f64, p.y as f64, p.z as f64])
+        .collect();
+    let sub = |a: [f64; 3], b: [f64; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    let mut normals = Vec::new();
+    for (i, &a) in points.iter().enumerate() {
+        for (j, &b) in points.iter().enumerate().skip(i + 1) {
+            for axis in axes {
+                normals.push((a, cross(sub(b, a), axis)));
+            }
+            for &c in &points[j + 1..] {
+                normals.push((a, cross(sub(b, a), sub(c, a))));
+            }
+        }
+    }
+
+    let mut planes: Vec<([f64; 3], f64)> = Vec::new();
+    for (origin, normal) in normals {
+        let length = dot(normal, normal).sqrt();
+        if length < 1e-9 {
+            continue;
+        }
+        let normal = normal.map(|n| n / length);
+        for normal in [normal, normal.map(|n| -n)] {
+            let offset = dot(normal, origin);
+            let bounds_all = points.iter().all(|&p| dot(normal, p) - offset <= 1e-9);
+            let duplicate = planes
+                .iter()
+                .any(|(n, o)| (dot(*n, normal) - 1.0).abs() < 1e-9 && (o - offset).abs() < 1e-9);
+            if bounds_all && !duplicate {
+                planes.push((normal, offset));
+            }
+        }
+    }
+    planes
+}
+
+/// Returns the bounding box of a selection that isn't a cuboid
+fn bounds(mode: SelectionMode, points: &[BlockPos]) -> (BlockPos, BlockPos) {
+    let mut min = points[0];
+    let mut max = points[0];
+    for &point in points {
+        min = min.min(point);
+        max = max.max(point);
+    }
+    if mode == SelectionMode::Cylinder {
+        let center = points[0];
+        let [_, [radius_x, radius_z]] = cylinder_shape(points);
+        let (radius_x, radius_z) = (radius_x as i32, radius_z as i32);
+        min = BlockPos::new(center.x - radius_x, min.y, center.z - radius_z);
+        max = BlockPos::new(center.x + radius_x, max.y, center.z + radius_z);
+    }
+    (min, max)
+}
+
+fn send_cui(player: &mut Player) {
+    let mode = player.worldedit_selection_mode;
+    let points = &player.worldedit_points;
+    let mut messages = vec![format!("s|{}", mode.cui_name())];
+    match mode {
+        SelectionMode::Cuboid => {}
+        SelectionMode::Poly => {
+            for (i, p) in points.iter().enumerate() {
+                messages.push(format!("p2|{}|{}|{}|0", i, p.x, p.z));
+            }
+        }
+        SelectionMode::Cylinder => {
+            let center = points[0];
+            let [_, [radius_x, radius_z]] = cylinder_shape(points);
+            messages.push(format!("e|0|{}|{}|{}", center.x, center.y, center.z));
+            messages.push(format!("e|1|{}|0|{}", radius_x, radius_z));
+        }
+        SelectionMode::Convex => {
+            for (i, p) in points.iter().enumerate() {
+                messages.push(format!("p|{}|{}|{}|{}|0", i, p.x, p.y, p.z));
+            }
+        }
+    }
+    if matches!(mode, SelectionMode::Poly | SelectionMode::Cylinder) {
+        let (min, max) = bounds(mode, points);
+        messages.push(format!("mm|{}|{}", min.y, max.y));
+    }
+    for message in messages {
+        player.worldedit_send_cui(&message);
+    }
+}
+
+fn update_bounds(player: &mut Player) {
+    let (min, max) = bounds(player.worldedit_selection_mode, &player.worldedit_points);
+    player.first_position = Some(min);
+    player.second_position = Some(max);
+    send_cui(player);
+}
+
+/// Selects a point in any mode but cuboid. The first position starts a new selection and the
+/// second position adds a point to it.
+pub fn select_point(player: &mut Player, pos: BlockPos, first: bool) {
+    let mode = player.worldedit_selection_mode;
+    if first || player.worldedit_points.is_empty() {
+        player.worldedit_points = vec![pos];
+        player.send_worldedit_message(&match mode {
+            SelectionMode::Cylinder => format!(
+                "Started new cylindrical selection at ({}, {}, {})",
+                pos.x, pos.y, pos.z
+            ),
+            _ => format!(
+                "Started new selection with vertex ({}, {}, {})",
+                pos.x, pos.y, pos.z
+            ),
+        });
+    } else {
+        if player.worldedit_points.contains(&pos) {
+            return;
+        }
+        player.worldedit_points.push(pos);
+        if mode == SelectionMode::Cylinder {
+            let [_, [radius_x, radius_z]] = cylinder_shape(&player.worldedit_points);
+            let (min, max) = bounds(mode, &player.worldedit_points);
+            player.send_worldedit_message(&format!(
+                "Radius set to {}/{} with a height of {}",
+                radius_x,
+                radius_z,
+                max.y - min.y + 1
+            ));
+        } else {
+            player.send_worldedit_message(&format!(
+                "Added vertex ({}, {}, {}) to the selection",
+                pos.x, pos.y, pos.z
+            ));
+        }
+    }
+    update_bounds(player);
+}
+
+/// Moves every point of the selection
+pub fn shift_points(player: &mut Player, offset: BlockPos) {
+    for point in &mut player.worldedit_points {
+        *point = *point + offset;
+    }
+    if !player.worldedit_points.is_empty() {
+        update_bounds(player);
+    }
+}
+
+/// Turns the selection into a cuboid covering its bounding box, for commands which can only
+/// change cuboids
+pub fn convert_to_cuboid(player: &mut Player) {
+    if player.worldedit_selection_mode == SelectionMode::Cuboid {
+        return;
+    }
+    player.worldedit_selection_mode = SelectionMode::Cuboid;
+    player.worldedit_points.clear();
+    player.worldedit_send_cui("s|cuboid");
+    if let (Some(first), Some(second)) = (player.first_position, player.second_position) {
+        player.worldedit_send_cui(&format!("p|0|{}|{}|{}|0", first.x, first.y, first.z));
+        player.worldedit_send_cui(&format!("p|1|{}|{}|{}|0", second.x, second.y, second.z));
+        player.send_worldedit_message("Your selection was turned into a cuboid.");
+    }
+}
+
+#[test]
+fn region_contains_test() {
+    let poly = Region::new(
+        SelectionMode::Poly,
+        &[
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(10, 5, 0),
+            BlockPos::new(0, 0, 10),
+        ],
+    );
+    assert!(poly.contains(BlockPos::new(5, 3, 5)));
+    assert!(poly.contains(BlockPos::new(3, 0, 3)));
+    assert!(!poly.contains(BlockPos::new(8, 0, 8)));
+
+    let cylinder = Region::new(
+        SelectionMode::Cylinder,
+        &[BlockPos::new(0, 0, 0), BlockPos::new(4, 2, 0)],
+    );
+    assert!(cylinder.contains(BlockPos::new(4, 0, 0)));
+    assert!(!cylinder.contains(BlockPos::new(0, 0, 1)));
+
+    let convex = Region::new(
+        SelectionMode::Convex,
+        &[
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(8, 0, 0),
+            BlockPos::new(0, 8, 0),
+            BlockPos::new(0, 0, 8),
+        ],
+    );
+    assert!(convex.contains(BlockPos::new(2, 2, 2)));
+    assert!(!convex.contains(BlockPos::new(4, 4, 4)));
+
+    // A flat triangle still selects the blocks in it
+    let flat = Region::new(
+        SelectionMode::Convex,
+        &[
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(8, 0, 0),
+            BlockPos::new(0, 0, 8),
+        ],
+    );
+    assert!(flat.contains(BlockPos::new(2, 0, 2)));
+    assert!(!flat.contains(BlockPos::new(6, 0, 6)));
+    assert!(!flat.contains(BlockPos::new(2, 1, 2)));
+}
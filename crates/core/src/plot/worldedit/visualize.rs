@@ -0,0 +1,90 @@
+//! Draws the outline of the selection and of where the clipboard would be pasted with particles,
+//! so that players can see what they are about to edit before they do it.
+
+use crate::player::{PacketSender, Player};
+use mchprs_blocks::BlockPos;
+use mchprs_network::packets::clientbound::{CParticle, CParticleData, ClientBoundPacket};
+use std::time::{Duration, Instant};
+
+/// How often the outlines are redrawn. Dust particles stay around for about a second.
+const VISUALIZE_INTERVAL: Duration = Duration::from_millis(500);
+/// The most particles used for a single outline. They are spread further apart on large regions.
+const MAX_OUTLINE_PARTICLES: f64 = 600.0;
+const SELECTION_COLOR: [f32; 3] = [1.0, 0.35, 0.1];
+const CLIPBOARD_COLOR: [f32; 3] = [0.2, 0.7, 1.0];
+
+/// Draws the edges of the region, including the outer faces of `max`
+fn draw_outline(player: &Player, min: BlockPos, max: BlockPos, color: [f32; 3]) {
+    let min = [min.x as f64, min.y as f64, min.z as f64];
+    let max = [max.x as f64 + 1.0, max.y as f64 + 1.0, max.z as f64 + 1.0];
+    let size: [f64; 3] = std::array::from_fn(|axis| max[axis] - min[axis]);
+    let total_length = 4.0 * (size[0] + size[1] + size[2]);
+    let spacing = (total_length / MAX_OUTLINE_PARTICLES).max(0.5);
+
+    for axis in 0..3 {
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let steps = (size[axis] / spacing).ceil() as i32;
+        for corner in 0..4 {
+            let mut pos = min;
+            if corner & 1 != 0 {
+                pos[a] = max[a];
+            }
+            if corner & 2 != 0 {
+                pos[b] = max[b];
+            }
+            for step in 0..=steps {
+                pos[axis] = min[axis] + size[axis] * step as f64 / steps as f64;
+                let particle = CParticle {
+                    particle: CParticleData::Dust { color, scale: 1.0 },
+                    // Otherwise particles further than 32 blocks away aren't shown
+                    long_distance: true,
+                    x: pos[0],
+                    y: pos[1],
+                    z: pos[2],
+                    offset_x: 0.0,
+                    offset_y: 0.0,
+                    offset_z: 0.0,
+                    max_speed: 0.0,
+                    count: 1,
+                }
+                .encode();
+                player.send_packet(&particle);
+            }
+        }
+    }
+}
+
+/// Redraws the outlines the player has turned on with //visualize, if it's time to
+pub fn visualize(player: &mut Player) {
+    if !player.worldedit_show_selection && !player.worldedit_show_clipboard {
+        return;
+    }
+    if player.worldedit_last_visualized.elapsed() < VISUALIZE_INTERVAL {
+        return;
+    }
+    player.worldedit_last_visualized = Instant::now();
+
+    if player.worldedit_show_selection {
+        if let (Some(first), Some(second)) = (player.first_position, player.second_position) {
+            draw_outline(
+                player,
+                first.min(second),
+                first.max(second),
+                SELECTION_COLOR,
+            );
+        }
+    }
+    if player.worldedit_show_clipboard {
+        if let Some(cb) = &player.worldedit_clipboard {
+            // This is the same region that //paste would paste to
+            let min = player.pos.block_pos() - BlockPos::new(cb.offset_x, cb.offset_y, cb.offset_z);
+            let max = min
+                + BlockPos::new(
+                    cb.size_x as i32 - 1,
+                    cb.size_y as i32 - 1,
+                    cb.size_z as i32 - 1,
+                );
+            draw_outline(player, min, max, CLIPBOARD_COLOR);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Loads the plugins in the `plugins` directory and passes events and commands on to them. See the
+//! `mchprs_plugin` crate for the api native plugins are written against, and `wasm` for
+//! WebAssembly plugins.
+
+mod mqtt;
+mod scheduler;
+mod scripts;
+mod storage;
+mod wasm;
+
+use crate::config::CONFIG;
+use crate::player::Notification;
+use crate::server::Message;
+use anyhow::{bail, Context, Result};
+use libloading::{Library, Symbol};
+use mchprs_plugin::{
+    CommandSpec, Event, EventBus, MainTask, PlotHandle, PlotTask, Plugin, ServerHandle, Storage,
+    StorageScope, TaskHandle, API_VERSION,
+};
+use mchprs_text::TextComponent;
+use mqtt::MqttBridge;
+use once_cell::sync::{Lazy, OnceCell};
+use scripts::ScriptHost;
+use std::ffi::c_void;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use tracing::{error, info};
+use wasm::WasmPlugin;
+
+pub use scheduler::{run_main_tasks, run_plot_tasks};
+
+const PLUGINS_DIR: &str = "./plugins";
+
+static PLUGINS: OnceCell<Vec<Box<dyn Plugin>>> = OnceCell::new();
+static EVENTS: Lazy<EventBus> = Lazy::new(EventBus::new);
+
+fn plugins() -> &'static [Box<dyn Plugin>] {
+    PLUGINS.get().map_or(&[], Vec::as_slice)
+}
+
+/// The event bus every event is published on
+pub fn events() -> &'static EventBus {
+    &EVENTS
+}
+
+fn load_plugin(path: &Path) -> Result<Box<dyn Plugin>> {
+    // SAFETY: Plugins are trusted to be built with `declare_plugin!`, which exports these
+    // functions with these signatures.
+    unsafe {
+        let library = Library::new(path)?;
+        let api_version: Symbol<'_, extern "C" fn() -> u32> = library
+            .get(mchprs_plugin::API_VERSION_SYMBOL)
+            .context("Not an MCHPRS plugin")?;
+        let api_version = api_version();
+        if api_version != API_VERSION {
+            bail!(
+                "Built for api version {}, but the server has version {}",
+                api_version,
+                API_VERSION
+            );
+        }
+        let create: Symbol<'_, extern "C" fn() -> *mut c_void> = library
+            .get(mchprs_plugin::CREATE_SYMBOL)
+            .context("Not an MCHPRS plugin")?;
+        let plugin = *Box::from_raw(create() as *mut Box<dyn Plugin>);
+        // The code of the plugin has to stay loaded for as long as the plugin is used, which is
+        // until the process exits
+        std::mem::forget(library);
+        Ok(plugin)
+    }
+}
+
+/// Loads every plugin in the plugins directory. Plugins that fail to load are skipped.
+pub fn load() {
+    let mut plugins: Vec<Box<dyn Plugin>> = vec![Box::new(ScriptHost::new())];
+    if let Some(config) = &CONFIG.mqtt {
+        plugins.push(Box::new(MqttBridge::new(config)));
+    }
+    for entry in fs::read_dir(PLUGINS_DIR).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let plugin = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("wasm") => {
+                WasmPlugin::load(&path).map(|plugin| Box::new(plugin) as Box<dyn Plugin>)
+            }
+            Some(ext) if ext == std::env::consts::DLL_EXTENSION => load_plugin(&path),
+            _ => continue,
+        };
+        match plugin {
+            Ok(plugin) => {
+                info!("Loaded plugin {}", plugin.name());
+                plugins.push(plugin);
+            }
+            Err(err) => error!("Failed to load plugin {}: {:#}", path.display(), err),
+        }
+    }
+    let _ = PLUGINS.set(plugins);
+}
+
+struct Server {
+    sender: Sender<Message>,
+}
+
+impl Server {
+    fn notify(&self, username: Option<&str>, message: &str) {
+        let notification = Notification::Chat(TextComponent::from_legacy_text(message));
+        let _ = self
+            .sender
+            .send(Message::Notify(username.map(str::to_string), notification));
+    }
+}
+
+impl ServerHandle for Server {
+    fn broadcast(&self, message: &str) {
+        self.notify(None, message);
+    }
+
+    fn send_message(&self, username: &str, message: &str) {
+        self.notify(Some(username), message);
+    }
+
+    fn run_console_command(&self, command: &str) {
+        // Nobody waits for the output
+        let (output, _) = mpsc::channel();
+        let _ = self
+            .sender
+            .send(Message::ConsoleCommand(command.to_string(), output));
+    }
+
+    fn events(&self) -> &EventBus {
+        events()
+    }
+
+    fn schedule(&self, delay: u64, period: Option<u64>, task: MainTask) -> TaskHandle {
+        scheduler::schedule(delay, period, task)
+    }
+
+    fn schedule_on_plot(
+        &self,
+        x: i32,
+        z: i32,
+        delay: u64,
+        period: Option<u64>,
+        task: PlotTask,
+    ) -> TaskHandle {
+        scheduler::schedule_on_plot(x, z, delay, period, task)
+    }
+
+    fn storage(&self, plugin: &str, scope: StorageScope) -> Arc<dyn Storage> {
+        storage::open(plugin, scope)
+    }
+}
+
+pub fn enable_all(sender: Sender<Message>) {
+    let server: Arc<dyn ServerHandle> = Arc::new(Server { sender });
+    scheduler::schedule(
+        storage::SAVE_INTERVAL,
+        Some(storage::SAVE_INTERVAL),
+        Box::new(storage::save),
+    );
+    for plugin in plugins() {
+        plugin.on_enable(server.clone());
+    }
+}
+
+pub fn disable_all() {
+    for plugin in plugins() {
+        plugin.on_disable();
+    }
+}
+
+/// Saves the storage of plugins that changed
+pub fn save_storage() {
+    storage::save();
+}
+
+/// Tells every plugin about an event and publishes it on the event bus
+pub fn dispatch(event: &Event, mut plot: Option<&mut dyn PlotHandle>) {
+    for plugin in plugins() {
+        // Reborrowed for each plugin, which needs a shorter lifetime for the trait object
+        let plot = plot.as_mut().map(|plot| &mut **plot as &mut dyn PlotHandle);
+        plugin.on_event(event, plot);
+    }
+    events().publish(event);
+}
+
+/// The plugin that registered a command, along with the command
+pub fn command(name: &str) -> Option<(&'static dyn Plugin, CommandSpec)> {
+    plugins().iter().find_map(|plugin| {
+        let spec = plugin
+            .commands()
+            .into_iter()
+            .find(|spec| spec.name == name)?;
+        Some((plugin.as_ref(), spec))
+    })
+}
+
+/// The commands registered by plugins
+pub fn commands() -> Vec<CommandSpec> {
+    plugins()
+        .iter()
+        .flat_map(|plugin| plugin.commands())
+        .collect()
+}
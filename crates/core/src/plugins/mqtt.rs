@@ -0,0 +1,502 @@
+//! Bridges named components on plots to an MQTT broker, so that circuits can be wired to home
+//! automation and other devices. Plots pick the components with `/mqtt`: outputs are published to
+//! `{prefix}/{x},{z}/{name}` whenever their signal strength changes while redpiler is running, and
+//! inputs are turned on and off by messages to `{prefix}/{x},{z}/{name}/set`. Components are named
+//! with signs, see `PlotHandle::find_named`.
+//!
+//! Only the parts of MQTT 3.1.1 that are needed for this are spoken, and only with QoS 0.
+
+use crate::config::MqttConfig;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use mchprs_plugin::{
+    ArgSpec, ArgType, BlockPos, CommandArgs, CommandSender, CommandSpec, Event, PlotHandle, Plugin,
+    ServerHandle, Storage, StorageScope,
+};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const PINGREQ: u8 = 12;
+
+/// How long the broker waits for a packet before it drops the connection. It is pinged twice as
+/// often when nothing is published.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// The largest packet that is accepted from the broker
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+const PLUGIN_NAME: &str = "mqtt";
+/// The key the components of a plot are kept under in its storage
+const STORAGE_KEY: &str = "bridge";
+
+#[derive(Debug, PartialEq)]
+enum Packet {
+    ConnAck {
+        code: u8,
+    },
+    Publish {
+        topic: String,
+        payload: Vec<u8>,
+    },
+    /// Packets that are read but not needed, like acknowledgements of subscriptions and pings
+    Other,
+}
+
+fn write_string(buf: &mut Vec<u8>, string: &str) {
+    buf.write_u16::<BigEndian>(string.len() as u16).unwrap();
+    buf.extend_from_slice(string.as_bytes());
+}
+
+fn write_packet(writer: &mut impl Write, header: u8, body: &[u8]) -> io::Result<()> {
+    let mut buf = vec![header];
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        buf.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 {
+            break;
+        }
+    }
+    buf.extend_from_slice(body);
+    writer.write_all(&buf)
+}
+
+fn write_connect(writer: &mut impl Write, config: &MqttConfig) -> io::Result<()> {
+    let mut body = Vec::new();
+    write_string(&mut body, "MQTT");
+    // Protocol level 4 is MQTT 3.1.1
+    body.push(4);
+    let mut flags = 0b10; // Clean session
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    body.push(flags);
+    body.write_u16::<BigEndian>(KEEP_ALIVE.as_secs() as u16)?;
+    write_string(&mut body, &config.client_id);
+    for field in [&config.username, &config.password].into_iter().flatten() {
+        write_string(&mut body, field);
+    }
+    write_packet(writer, CONNECT << 4, &body)
+}
+
+fn write_subscribe(writer: &mut impl Write, filter: &str) -> io::Result<()> {
+    let mut body = Vec::new();
+    // The packet id, which is only needed to match the acknowledgement
+    body.write_u16::<BigEndian>(1)?;
+    write_string(&mut body, filter);
+    // QoS 0
+    body.push(0);
+    write_packet(writer, SUBSCRIBE << 4 | 0b10, &body)
+}
+
+fn write_publish(writer: &mut impl Write, topic: &str, payload: &str) -> io::Result<()> {
+    let mut body = Vec::new();
+    write_string(&mut body, topic);
+    body.extend_from_slice(payload.as_bytes());
+    // Outputs are retained, so that new subscribers get their current state right away
+    write_packet(writer, PUBLISH << 4 | 1, &body)
+}
+
+fn read_packet(reader: &mut impl Read) -> io::Result<Packet> {
+    let header = reader.read_u8()?;
+    let mut len = 0;
+    for shift in (0..28).step_by(7) {
+        let byte = reader.read_u8()?;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > MAX_PACKET_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("packet of {} bytes is too large", len),
+        ));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    let mut body = body.as_slice();
+    Ok(match header >> 4 {
+        CONNACK => Packet::ConnAck {
+            code: body.get(1).copied().unwrap_or(u8::MAX),
+        },
+        PUBLISH => {
+            let len = body.read_u16::<BigEndian>()? as usize;
+            let topic = body.get(..len).ok_or(io::ErrorKind::UnexpectedEof)?;
+            let topic = String::from_utf8_lossy(topic).into_owned();
+            body = &body[len..];
+            // Messages with a higher QoS than we subscribed with can't arrive, but have a packet id
+            if (header >> 1) & 0b11 > 0 {
+                body.read_u16::<BigEndian>()?;
+            }
+            Packet::Publish {
+                topic,
+                payload: body.to_vec(),
+            }
+        }
+        _ => Packet::Other,
+    })
+}
+
+/// The plot and the name of the input a message to `topic` sets
+fn parse_input_topic(prefix: &str, topic: &str) -> Option<(i32, i32, String)> {
+    let rest = topic.strip_prefix(prefix)?.strip_prefix('/')?;
+    let (plot, rest) = rest.split_once('/')?;
+    let name = rest.strip_suffix("/set")?;
+    let (x, z) = plot.split_once(',')?;
+    Some((x.parse().ok()?, z.parse().ok()?, name.to_string()))
+}
+
+fn parse_powered(payload: &[u8]) -> Option<bool> {
+    let payload = std::str::from_utf8(payload).ok()?.trim().to_lowercase();
+    match payload.as_str() {
+        "on" | "true" => Some(true),
+        "off" | "false" => Some(false),
+        power => power.parse::<u32>().ok().map(|power| power > 0),
+    }
+}
+
+/// The components of a plot that are bridged
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PlotBridge {
+    outputs: Vec<String>,
+    inputs: Vec<String>,
+}
+
+impl PlotBridge {
+    fn load(storage: &dyn Storage) -> PlotBridge {
+        storage
+            .get(STORAGE_KEY)
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, storage: &dyn Storage) {
+        storage.set(STORAGE_KEY, &serde_json::to_vec(self).unwrap());
+    }
+}
+
+pub struct MqttBridge {
+    config: &'static MqttConfig,
+    server: OnceCell<Arc<dyn ServerHandle>>,
+    /// Messages to publish, as topic and payload
+    outgoing: Sender<(String, String)>,
+    receiver: Mutex<Option<Receiver<(String, String)>>>,
+    /// The names of the outputs that are watched on each loaded plot, by their position
+    watched: Mutex<HashMap<(i32, i32), HashMap<BlockPos, String>>>,
+}
+
+impl MqttBridge {
+    pub fn new(config: &'static MqttConfig) -> MqttBridge {
+        let (outgoing, receiver) = mpsc::channel();
+        MqttBridge {
+            config,
+            server: OnceCell::new(),
+            outgoing,
+            receiver: Mutex::new(Some(receiver)),
+            watched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn storage(&self, x: i32, z: i32) -> Option<Arc<dyn Storage>> {
+        let server = self.server.get()?;
+        Some(server.storage(PLUGIN_NAME, StorageScope::Plot { x, z }))
+    }
+
+    fn topic(&self, x: i32, z: i32, name: &str) -> String {
+        format!("{}/{},{}/{}", self.config.topic_prefix, x, z, name)
+    }
+
+    /// Watches the outputs of a plot and publishes their current state, which is needed again
+    /// whenever the plot is loaded or compiled
+    fn watch(&self, plot: &mut dyn PlotHandle) {
+        let (x, z) = (plot.x(), plot.z());
+        let Some(storage) = self.storage(x, z) else {
+            return;
+        };
+        let mut watched = HashMap::new();
+        for name in PlotBridge::load(&*storage).outputs {
+            let Some(pos) = plot.find_named(&name) else {
+                continue;
+            };
+            plot.watch_output(pos);
+            if let Some(power) = plot.read_output(pos) {
+                let _ = self
+                    .outgoing
+                    .send((self.topic(x, z, &name), power.to_string()));
+            }
+            watched.insert(pos, name);
+        }
+        self.watched.lock().unwrap().insert((x, z), watched);
+    }
+
+    fn handle_command(&self, args: &CommandArgs, sender: &mut CommandSender<'_>) {
+        let (x, z) = (sender.plot.x(), sender.plot.z());
+        let Some(storage) = self.storage(x, z) else {
+            return;
+        };
+        let mut bridge = PlotBridge::load(&*storage);
+        let action = args.string("action").unwrap_or_default();
+        if action == "list" {
+            sender.reply(&format!(
+                "&7Outputs: &f{}\n&7Inputs: &f{}",
+                bridge.outputs.join(", "),
+                bridge.inputs.join(", ")
+            ));
+            return;
+        }
+
+        let Some(name) = args.string("name") else {
+            sender.reply(&format!("&cUsage: /mqtt {} <name>", action));
+            return;
+        };
+        if name.contains(['/', '+', '#']) {
+            sender.reply("&cNames can't contain /, + or #");
+            return;
+        }
+        let topic = self.topic(x, z, name);
+        match action {
+            "publish" if !bridge.outputs.iter().any(|output| output == name) => {
+                bridge.outputs.push(name.to_string());
+                sender.reply(&format!("&aPublishing {} to {}", name, topic));
+            }
+            "subscribe" if !bridge.inputs.iter().any(|input| input == name) => {
+                bridge.inputs.push(name.to_string());
+                sender.reply(&format!("&aSetting {} from {}/set", name, topic));
+            }
+            "publish" | "subscribe" => {
+                sender.reply(&format!("&c{} is already bridged", name));
+                return;
+            }
+            _ => {
+                let len = bridge.outputs.len() + bridge.inputs.len();
+                bridge.outputs.retain(|output| output != name);
+                bridge.inputs.retain(|input| input != name);
+                if bridge.outputs.len() + bridge.inputs.len() == len {
+                    sender.reply(&format!("&c{} isn't bridged", name));
+                    return;
+                }
+                sender.reply(&format!("&aStopped bridging {}", name));
+            }
+        }
+        if action != "remove" && sender.plot.find_named(name).is_none() {
+            sender.reply(&format!("&eThere is no component named {} yet", name));
+        }
+        bridge.save(&*storage);
+        self.watch(sender.plot);
+    }
+}
+
+impl Plugin for MqttBridge {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn on_enable(&self, server: Arc<dyn ServerHandle>) {
+        let _ = self.server.set(server.clone());
+        let Some(receiver) = self.receiver.lock().unwrap().take() else {
+            return;
+        };
+        let config = self.config;
+        std::thread::Builder::new()
+            .name("MQTT".to_string())
+            .spawn(move || run(config, receiver, server))
+            .unwrap();
+    }
+
+    fn on_event(&self, event: &Event, plot: Option<&mut dyn PlotHandle>) {
+        let Some(plot) = plot else {
+            return;
+        };
+        match event {
+            Event::PlotLoad(_) | Event::CompileFinish(_) => self.watch(plot),
+            Event::PlotUnload(_) => {
+                self.watched.lock().unwrap().remove(&(plot.x(), plot.z()));
+            }
+            Event::OutputChange(change) => {
+                let (x, z) = (plot.x(), plot.z());
+                let watched = self.watched.lock().unwrap();
+                if let Some(name) = watched.get(&(x, z)).and_then(|plot| plot.get(&change.pos)) {
+                    let _ = self
+                        .outgoing
+                        .send((self.topic(x, z, name), change.power.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        let actions = ["publish", "subscribe", "remove", "list"];
+        vec![
+            CommandSpec::new("mqtt", "Bridges named components on the plot to MQTT")
+                .arg(ArgSpec::new(
+                    "action",
+                    ArgType::Choice(actions.map(str::to_string).to_vec()),
+                ))
+                .arg(ArgSpec::optional("name", ArgType::Word))
+                .permission("mchprs.mqtt"),
+        ]
+    }
+
+    fn on_command(&self, _command: &str, args: &CommandArgs, sender: &mut CommandSender<'_>) {
+        self.handle_command(args, sender);
+    }
+}
+
+/// Keeps a connection to the broker, and connects again when it is lost
+fn run(config: &MqttConfig, outgoing: Receiver<(String, String)>, server: Arc<dyn ServerHandle>) {
+    // The last state of every output, which is published again after connecting
+    let mut states = HashMap::new();
+    loop {
+        match session(config, &outgoing, &server, &mut states) {
+            Ok(()) => return,
+            Err(err) => warn!("MQTT connection to {} failed: {}", config.broker, err),
+        }
+        std::thread::sleep(RECONNECT_DELAY);
+        while let Ok((topic, payload)) = outgoing.try_recv() {
+            states.insert(topic, payload);
+        }
+    }
+}
+
+/// Runs a connection until it fails. Returns `Ok` once nothing can be published anymore.
+fn session(
+    config: &MqttConfig,
+    outgoing: &Receiver<(String, String)>,
+    server: &Arc<dyn ServerHandle>,
+    states: &mut HashMap<String, String>,
+) -> io::Result<()> {
+    let mut stream = TcpStream::connect(&config.broker)?;
+    stream.set_read_timeout(Some(KEEP_ALIVE))?;
+    write_connect(&mut stream, config)?;
+    match read_packet(&mut stream)? {
+        Packet::ConnAck { code: 0 } => {}
+        Packet::ConnAck { code } => {
+            return Err(io::Error::other(format!(
+                "the broker refused the connection with code {}",
+                code
+            )))
+        }
+        _ => return Err(io::ErrorKind::InvalidData.into()),
+    }
+    stream.set_read_timeout(None)?;
+    write_subscribe(&mut stream, &format!("{}/+/+/set", config.topic_prefix))?;
+    info!("Connected to MQTT broker {}", config.broker);
+    for (topic, payload) in states.iter() {
+        write_publish(&mut stream, topic, payload)?;
+    }
+
+    let mut reader = stream.try_clone()?;
+    let prefix = config.topic_prefix.clone();
+    let server = server.clone();
+    std::thread::Builder::new()
+        .name("MQTT reader".to_string())
+        .spawn(move || {
+            while let Ok(packet) = read_packet(&mut reader) {
+                if let Packet::Publish { topic, payload } = packet {
+                    set_input(&*server, &prefix, &topic, &payload);
+                }
+            }
+            // Makes the next ping fail, which connects again
+            let _ = reader.shutdown(Shutdown::Both);
+        })?;
+
+    let result = loop {
+        match outgoing.recv_timeout(KEEP_ALIVE / 2) {
+            Ok((topic, payload)) => {
+                if let Err(err) = write_publish(&mut stream, &topic, &payload) {
+                    states.insert(topic, payload);
+                    break Err(err);
+                }
+                states.insert(topic, payload);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if let Err(err) = write_packet(&mut stream, PINGREQ << 4, &[]) {
+                    break Err(err);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+        }
+    };
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
+
+/// Sets an input on the thread of its plot
+fn set_input(server: &dyn ServerHandle, prefix: &str, topic: &str, payload: &[u8]) {
+    let (Some((x, z, name)), Some(powered)) =
+        (parse_input_topic(prefix, topic), parse_powered(payload))
+    else {
+        return;
+    };
+    let storage = server.storage(PLUGIN_NAME, StorageScope::Plot { x, z });
+    server.schedule_on_plot(
+        x,
+        z,
+        0,
+        None,
+        Box::new(move |plot| {
+            // Only inputs the plot subscribed to can be set
+            if !PlotBridge::load(&*storage).inputs.contains(&name) {
+                return;
+            }
+            if let Some(pos) = plot.find_named(&name) {
+                plot.set_input(pos, powered);
+            }
+        }),
+    );
+}
+
+#[test]
+fn mqtt_packet_test() {
+    let config = MqttConfig {
+        broker: String::new(),
+        client_id: "mchprs".to_string(),
+        username: Some("user".to_string()),
+        password: None,
+        topic_prefix: "mchprs".to_string(),
+    };
+    let mut buf = Vec::new();
+    write_connect(&mut buf, &config).unwrap();
+    assert_eq!(
+        buf,
+        b"\x10\x18\x00\x04MQTT\x04\x82\x00\x1e\x00\x06mchprs\x00\x04user".to_vec()
+    );
+
+    // Long packets take more than one byte for their length
+    let payload = "x".repeat(200);
+    let mut buf = Vec::new();
+    write_publish(&mut buf, "a/b", &payload).unwrap();
+    assert_eq!(&buf[..3], &[0x31, 205, 1]);
+    assert_eq!(
+        read_packet(&mut buf.as_slice()).unwrap(),
+        Packet::Publish {
+            topic: "a/b".to_string(),
+            payload: payload.into_bytes(),
+        }
+    );
+
+    assert_eq!(
+        parse_input_topic("mchprs", "mchprs/-1,2/door/set"),
+        Some((-1, 2, "door".to_string()))
+    );
+    assert_eq!(parse_input_topic("mchprs", "mchprs/-1,2/door"), None);
+    assert_eq!(parse_powered(b" ON "), Some(true));
+    assert_eq!(parse_powered(b"0"), Some(false));
+    assert_eq!(parse_powered(b"maybe"), None);
+}
@@ -0,0 +1,169 @@
+//! Runs the tasks plugins schedule. Tasks for the main thread are run by the server, and tasks for
+//! a plot by the thread of that plot while it is loaded.
+
+use mchprs_plugin::tasks::GAME_TICK;
+use mchprs_plugin::{MainTask, PlotHandle, PlotTask, TaskHandle};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Task<F> {
+    handle: TaskHandle,
+    due: Instant,
+    period: Option<Duration>,
+    run: F,
+}
+
+/// The tasks of each plot by its position
+type PlotTasks = HashMap<(i32, i32), Vec<Task<PlotTask>>>;
+
+static MAIN_TASKS: Lazy<Mutex<Vec<Task<MainTask>>>> = Lazy::new(Default::default);
+static PLOT_TASKS: Lazy<Mutex<PlotTasks>> = Lazy::new(Default::default);
+
+fn ticks(ticks: u64) -> Duration {
+    GAME_TICK * ticks.min(u32::MAX as u64) as u32
+}
+
+impl<F> Task<F> {
+    fn new(delay: u64, period: Option<u64>, run: F) -> Task<F> {
+        Task {
+            handle: TaskHandle::new(),
+            due: Instant::now() + ticks(delay),
+            // A period of 0 would run the task on every update
+            period: period.map(|period| ticks(period.max(1))),
+            run,
+        }
+    }
+
+    /// Whether the task should be kept after it ran, which moves it to its next run
+    fn reschedule(&mut self, now: Instant) -> bool {
+        match self.period {
+            Some(period) if self.handle.is_active() => {
+                // Runs that were missed while the thread was busy are skipped
+                self.due = (self.due + period).max(now);
+                true
+            }
+            _ => {
+                self.handle.cancel();
+                false
+            }
+        }
+    }
+}
+
+/// Takes the tasks that are due out of `tasks`, and drops the ones that were cancelled
+fn take_due<F>(tasks: &mut Vec<Task<F>>, now: Instant) -> Vec<Task<F>> {
+    tasks.retain(|task| task.handle.is_active());
+    let (due, waiting) = std::mem::take(tasks)
+        .into_iter()
+        .partition(|task| task.due <= now);
+    *tasks = waiting;
+    due
+}
+
+pub fn schedule(delay: u64, period: Option<u64>, task: MainTask) -> TaskHandle {
+    let task = Task::new(delay, period, task);
+    let handle = task.handle.clone();
+    MAIN_TASKS.lock().unwrap().push(task);
+    handle
+}
+
+pub fn schedule_on_plot(
+    x: i32,
+    z: i32,
+    delay: u64,
+    period: Option<u64>,
+    task: PlotTask,
+) -> TaskHandle {
+    let task = Task::new(delay, period, task);
+    let handle = task.handle.clone();
+    PLOT_TASKS
+        .lock()
+        .unwrap()
+        .entry((x, z))
+        .or_default()
+        .push(task);
+    handle
+}
+
+/// Runs the tasks for the main thread that are due
+pub fn run_main_tasks() {
+    let now = Instant::now();
+    // The lock isn't held while tasks run, so that they can schedule more tasks
+    let due = take_due(&mut MAIN_TASKS.lock().unwrap(), now);
+    for mut task in due {
+        // An earlier task could have cancelled it
+        if !task.handle.is_active() {
+            continue;
+        }
+        (task.run)();
+        if task.reschedule(now) {
+            MAIN_TASKS.lock().unwrap().push(task);
+        }
+    }
+}
+
+/// Runs the tasks for a plot that are due. Returns false if there were none.
+pub fn run_plot_tasks(plot: &mut dyn PlotHandle) -> bool {
+    let key = (plot.x(), plot.z());
+    let now = Instant::now();
+    let due = match PLOT_TASKS.lock().unwrap().get_mut(&key) {
+        Some(tasks) => take_due(tasks, now),
+        None => return false,
+    };
+    if due.is_empty() {
+        return false;
+    }
+    for mut task in due {
+        if !task.handle.is_active() {
+            continue;
+        }
+        (task.run)(plot);
+        if task.reschedule(now) {
+            PLOT_TASKS
+                .lock()
+                .unwrap()
+                .entry(key)
+                .or_default()
+                .push(task);
+        }
+    }
+    let mut tasks = PLOT_TASKS.lock().unwrap();
+    if tasks.get(&key).is_some_and(Vec::is_empty) {
+        tasks.remove(&key);
+    }
+    true
+}
+
+#[test]
+fn scheduler_test() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = |runs: &Arc<AtomicUsize>| -> MainTask {
+        let runs = runs.clone();
+        Box::new(move || {
+            runs.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+    let once = schedule(0, None, counter(&runs));
+    let repeating = schedule(0, Some(1), counter(&runs));
+    let later = schedule(1_000, None, counter(&runs));
+
+    run_main_tasks();
+    assert_eq!(runs.load(Ordering::Relaxed), 2);
+    assert!(!once.is_active());
+    assert!(repeating.is_active());
+
+    std::thread::sleep(GAME_TICK);
+    run_main_tasks();
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+
+    repeating.cancel();
+    later.cancel();
+    std::thread::sleep(GAME_TICK);
+    run_main_tasks();
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+}
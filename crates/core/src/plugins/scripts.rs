@@ -0,0 +1,791 @@
+//! Runs Rhai scripts from the `scripts` directory, so that admins can automate things without
+//! building a plugin. Scripts are loaded again when they change.
+//!
+//! A script handles events by defining functions named after them, which get the event as a map:
+//! `on_player_join`, `on_player_leave`, `on_chat`, `on_block_place`, `on_block_break`,
+//! `on_plot_load`, `on_plot_unload`, `on_output_change`, `on_chunk_load`, `on_compile_start`,
+//! `on_compile_finish` and `on_command_run`. Handlers can keep state in `this`, which is kept when
+//! the script is reloaded.
+//!
+//! Commands are registered with `register_command(name, description, Fn)`, or with
+//! `register_command(#{ name, description, usage, permission }, Fn)` to declare their arguments
+//! (see `CommandSpec::usage`). The handler gets the arguments and the name of the player, and the
+//! parsed arguments as a map if it takes a third parameter.
+//!
+//! `run_later(ticks, Fn)` and `run_every(ticks, Fn)` schedule a function on the plot of the current
+//! event or command, or on the main thread outside of them. The tasks of a script are cancelled
+//! when it is reloaded or removed.
+//!
+//! `storage()`, `plot_storage()` and `player_storage(uuid)` give the storage scripts share, for
+//! themselves, for the plot of the current event or command, and for a player. Values are stored
+//! as JSON, so they keep their type.
+
+use anyhow::Result;
+use mchprs_plugin::{
+    ArgValue, BlockPos, CommandArgs, CommandSender, CommandSpec, Event, PlotHandle, Plugin,
+    ServerHandle, Storage, StorageScope, TaskHandle,
+};
+use once_cell::sync::OnceCell;
+use rhai::{
+    Array, CallFnOptions, Dynamic, Engine, EvalAltResult, FnPtr, Map, NativeCallContext, Scope, AST,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+const SCRIPTS_DIR: &str = "./scripts";
+/// The name scripts have as a plugin, which they share their storage under
+const PLUGIN_NAME: &str = "scripts";
+/// How often the scripts are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+/// How many operations a script can run per call, so that a broken script can't hang a plot
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+static SERVER: OnceCell<Arc<dyn ServerHandle>> = OnceCell::new();
+
+thread_local! {
+    /// The plot of the event or command that is being handled. The lifetime is erased, since it
+    /// is only set while a `PlotGuard` borrows the plot.
+    static CURRENT_PLOT: Cell<Option<*mut (dyn PlotHandle + 'static)>> = const { Cell::new(None) };
+    /// The commands registered by the script that is being loaded
+    static LOADING_COMMANDS: RefCell<Vec<(CommandSpec, FnPtr)>> = const { RefCell::new(Vec::new()) };
+    /// The scripts that are running on this thread, with the one that tasks are scheduled for
+    /// last. Calls nest when a script causes an event.
+    static RUNNING_SCRIPTS: RefCell<Vec<(Weak<Scripts>, PathBuf)>> = const { RefCell::new(Vec::new()) };
+    /// The tasks scheduled by the script that is running
+    static SCHEDULED_TASKS: RefCell<Vec<TaskHandle>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Makes a plot the current plot for as long as it lives. The plot that was current before, for a
+/// call that this one is nested in, is current again when it is dropped, also when unwinding.
+struct PlotGuard<'a> {
+    previous: Option<*mut (dyn PlotHandle + 'static)>,
+    _plot: PhantomData<&'a mut dyn PlotHandle>,
+}
+
+impl<'a> PlotGuard<'a> {
+    fn set(plot: Option<&'a mut dyn PlotHandle>) -> PlotGuard<'a> {
+        // SAFETY: The guard keeps the plot borrowed, and takes the pointer out again before the
+        // borrow ends
+        let plot = plot.map(|plot| unsafe {
+            std::mem::transmute::<&mut dyn PlotHandle, *mut (dyn PlotHandle + 'static)>(plot)
+        });
+        PlotGuard {
+            previous: CURRENT_PLOT.with(|current| current.replace(plot)),
+            _plot: PhantomData,
+        }
+    }
+}
+
+impl Drop for PlotGuard<'_> {
+    fn drop(&mut self) {
+        CURRENT_PLOT.with(|current| current.set(self.previous));
+    }
+}
+
+/// Marks a script as running on this thread for as long as it lives
+struct RunningGuard;
+
+impl RunningGuard {
+    fn enter(scripts: Weak<Scripts>, path: &Path) -> RunningGuard {
+        RUNNING_SCRIPTS.with(|running| running.borrow_mut().push((scripts, path.to_path_buf())));
+        RunningGuard
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING_SCRIPTS.with(|running| running.borrow_mut().pop());
+    }
+}
+
+fn with_plot<R>(f: impl FnOnce(&mut dyn PlotHandle) -> R) -> Option<R> {
+    // SAFETY: The pointer is only set while a `PlotGuard` borrows the plot
+    CURRENT_PLOT.with(|plot| plot.get().map(|plot| f(unsafe { &mut *plot })))
+}
+
+fn server() -> Option<&'static Arc<dyn ServerHandle>> {
+    SERVER.get()
+}
+
+/// The storage of a scope, as a value in scripts
+#[derive(Clone)]
+struct ScriptStorage(Arc<dyn Storage>);
+
+fn storage(scope: StorageScope) -> Result<ScriptStorage, Box<EvalAltResult>> {
+    let server = server().ok_or("The server isn't running")?;
+    Ok(ScriptStorage(server.storage(PLUGIN_NAME, scope)))
+}
+
+fn to_json(value: Dynamic) -> String {
+    // Only maps can be formatted as JSON, so the value is wrapped in one
+    let mut map = Map::new();
+    map.insert("value".into(), value);
+    rhai::format_map_as_json(&map)
+}
+
+fn from_json(engine: &Engine, json: &[u8]) -> Dynamic {
+    std::str::from_utf8(json)
+        .ok()
+        .and_then(|json| engine.parse_json(json, true).ok())
+        .and_then(|mut map| map.remove("value"))
+        .unwrap_or(Dynamic::UNIT)
+}
+
+type Commands = Vec<(CommandSpec, FnPtr)>;
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+    state: Dynamic,
+    tasks: Vec<TaskHandle>,
+}
+
+impl Script {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn cancel_tasks(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.cancel();
+        }
+    }
+
+    fn handles(&self, function: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == params)
+    }
+}
+
+/// A script that is loaded. Each script is locked on its own while it runs, so that a slow script
+/// only holds up the threads calling into it.
+#[derive(Clone)]
+struct LoadedScript {
+    script: Arc<Mutex<Script>>,
+    /// Kept outside of the script, so that they can be looked up while it runs
+    commands: Commands,
+}
+
+/// Locks a script to run it, or returns `None` if it is already running on this thread, in which
+/// case it isn't run again from inside itself
+fn lock_script(script: &Mutex<Script>, path: &Path) -> Option<MutexGuard<'_, Script>> {
+    let running =
+        RUNNING_SCRIPTS.with(|running| running.borrow().iter().any(|(_, running)| running == path));
+    (!running).then(|| script.lock().unwrap())
+}
+
+struct Scripts {
+    /// For scheduled tasks to call back into
+    this: Weak<Scripts>,
+    engine: Engine,
+    /// The loaded scripts by their path. This is only locked to look them up, not while they run.
+    scripts: Mutex<BTreeMap<PathBuf, LoadedScript>>,
+    /// When the scripts were changed when they were last loaded, also for those that failed to
+    /// load
+    modified: Mutex<HashMap<PathBuf, SystemTime>>,
+}
+
+/// Runs the scripts as if they were a plugin
+pub struct ScriptHost {
+    // Shared with the thread that reloads the scripts
+    inner: Arc<Scripts>,
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|text| info!("[scripts] {}", text));
+    engine.on_debug(|text, source, pos| info!("[scripts] {:?} {}: {}", source, pos, text));
+
+    engine.register_fn("broadcast", |message: &str| {
+        if let Some(server) = server() {
+            server.broadcast(message);
+        }
+    });
+    engine.register_fn("send_message", |username: &str, message: &str| {
+        if let Some(server) = server() {
+            server.send_message(username, message);
+        }
+    });
+    engine.register_fn("run_command", |command: &str| {
+        if let Some(server) = server() {
+            server.run_console_command(command);
+        }
+    });
+    engine.register_fn(
+        "register_command",
+        |name: &str, description: &str, handler: FnPtr| {
+            LOADING_COMMANDS.with(|commands| {
+                commands
+                    .borrow_mut()
+                    .push((CommandSpec::new(name, description), handler))
+            });
+        },
+    );
+    engine.register_fn(
+        "register_command",
+        |spec: Map, handler: FnPtr| -> Result<(), Box<EvalAltResult>> {
+            let field = |name: &str| {
+                spec.get(name)
+                    .and_then(|value| value.clone().into_string().ok())
+            };
+            let name = field("name").ok_or("The command has no name")?;
+            let mut spec = CommandSpec::new(name, field("description").unwrap_or_default());
+            if let Some(usage) = field("usage") {
+                spec = spec.usage(&usage)?;
+            }
+            if let Some(permission) = field("permission") {
+                spec = spec.permission(permission);
+            }
+            LOADING_COMMANDS.with(|commands| commands.borrow_mut().push((spec, handler)));
+            Ok(())
+        },
+    );
+
+    engine
+        .register_type_with_name::<TaskHandle>("TaskHandle")
+        .register_fn("cancel", |task: &mut TaskHandle| task.cancel());
+    engine.register_fn("run_later", |ticks: i64, function: FnPtr| {
+        schedule(ticks, None, function)
+    });
+    engine.register_fn("run_every", |ticks: i64, function: FnPtr| {
+        schedule(ticks, Some(ticks), function)
+    });
+
+    engine
+        .register_type_with_name::<ScriptStorage>("Storage")
+        .register_fn(
+            "get",
+            |ctx: NativeCallContext<'_>, storage: &mut ScriptStorage, key: &str| -> Dynamic {
+                storage
+                    .0
+                    .get(key)
+                    .map_or(Dynamic::UNIT, |json| from_json(ctx.engine(), &json))
+            },
+        )
+        .register_fn(
+            "set",
+            |storage: &mut ScriptStorage, key: &str, value: Dynamic| {
+                storage.0.set(key, to_json(value).as_bytes());
+            },
+        )
+        .register_fn("remove", |storage: &mut ScriptStorage, key: &str| {
+            storage.0.remove(key)
+        })
+        .register_fn("keys", |storage: &mut ScriptStorage| -> Array {
+            storage.0.keys().into_iter().map(Dynamic::from).collect()
+        });
+    engine.register_fn("storage", || storage(StorageScope::Plugin));
+    engine.register_fn("plot_storage", || {
+        let (x, z) = with_plot(|plot| (plot.x(), plot.z()))
+            .ok_or("There is no plot outside of plot events and commands")?;
+        storage(StorageScope::Plot { x, z })
+    });
+    engine.register_fn("player_storage", |uuid: &str| {
+        let uuid = u128::from_str_radix(uuid, 16).map_err(|_| "Not a uuid")?;
+        storage(StorageScope::Player(uuid))
+    });
+
+    // These only work in handlers of plot events and commands
+    engine.register_fn("plot_x", || with_plot(|plot| plot.x() as i64).unwrap_or(0));
+    engine.register_fn("plot_z", || with_plot(|plot| plot.z() as i64).unwrap_or(0));
+    engine.register_fn("plot_players", || -> Array {
+        with_plot(|plot| plot.players().into_iter().map(Dynamic::from).collect())
+            .unwrap_or_default()
+    });
+    engine.register_fn("get_block", |x: i64, y: i64, z: i64| -> i64 {
+        with_plot(|plot| plot.get_block(pos(x, y, z)))
+            .flatten()
+            .map_or(-1, i64::from)
+    });
+    engine.register_fn("set_block", |x: i64, y: i64, z: i64, block: i64| {
+        with_plot(|plot| plot.set_block(pos(x, y, z), block as u32)).unwrap_or(false)
+    });
+    engine.register_fn("use_block", |x: i64, y: i64, z: i64| {
+        with_plot(|plot| plot.use_block(pos(x, y, z))).unwrap_or(false)
+    });
+
+    // Components of the compiled circuit can be given by position or by the name on their sign
+    engine.register_fn("read_output", |x: i64, y: i64, z: i64| -> i64 {
+        with_plot(|plot| plot.read_output(pos(x, y, z)))
+            .flatten()
+            .map_or(-1, i64::from)
+    });
+    engine.register_fn("read_output", |name: &str| -> i64 {
+        with_plot(|plot| plot.read_output(plot.find_named(name)?))
+            .flatten()
+            .map_or(-1, i64::from)
+    });
+    engine.register_fn("set_input", |x: i64, y: i64, z: i64, powered: bool| {
+        with_plot(|plot| plot.set_input(pos(x, y, z), powered)).unwrap_or(false)
+    });
+    engine.register_fn("set_input", |name: &str, powered: bool| {
+        with_plot(|plot| {
+            let pos = plot.find_named(name)?;
+            Some(plot.set_input(pos, powered))
+        })
+        .flatten()
+        .unwrap_or(false)
+    });
+    engine.register_fn("find_named", |name: &str| -> Dynamic {
+        with_plot(|plot| plot.find_named(name))
+            .flatten()
+            .map_or(Dynamic::UNIT, pos_array)
+    });
+    engine.register_fn("watch_output", |x: i64, y: i64, z: i64| {
+        with_plot(|plot| plot.watch_output(pos(x, y, z)));
+    });
+    engine.register_fn("watch_output", |name: &str| {
+        with_plot(|plot| {
+            if let Some(pos) = plot.find_named(name) {
+                plot.watch_output(pos);
+            }
+        });
+    });
+    engine
+}
+
+/// Schedules a function of the script that is running
+fn schedule(delay: i64, period: Option<i64>, function: FnPtr) -> TaskHandle {
+    let current = RUNNING_SCRIPTS.with(|running| running.borrow().last().cloned());
+    let (Some(server), Some((scripts, path))) = (server(), current) else {
+        return TaskHandle::new();
+    };
+    let function = function.fn_name().to_string();
+    let delay = delay.max(0) as u64;
+    let period = period.map(|period| period.max(1) as u64);
+    let plot = with_plot(|plot| (plot.x(), plot.z()));
+    let task = match plot {
+        Some((x, z)) => server.schedule_on_plot(
+            x,
+            z,
+            delay,
+            period,
+            Box::new(move |plot| {
+                if let Some(scripts) = scripts.upgrade() {
+                    scripts.run_task(&path, &function, Some(plot));
+                }
+            }),
+        ),
+        None => server.schedule(
+            delay,
+            period,
+            Box::new(move || {
+                if let Some(scripts) = scripts.upgrade() {
+                    scripts.run_task(&path, &function, None);
+                }
+            }),
+        ),
+    };
+    SCHEDULED_TASKS.with(|tasks| tasks.borrow_mut().push(task.clone()));
+    task
+}
+
+fn pos(x: i64, y: i64, z: i64) -> BlockPos {
+    BlockPos::new(x as i32, y as i32, z as i32)
+}
+
+fn pos_array(pos: BlockPos) -> Dynamic {
+    vec![
+        Dynamic::from(pos.x as i64),
+        Dynamic::from(pos.y as i64),
+        Dynamic::from(pos.z as i64),
+    ]
+    .into()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// The name of the function that handles an event and the event as a map
+fn event_call(event: &Event) -> Option<(&'static str, Map)> {
+    let mut map = Map::new();
+    let mut set = |key: &str, value: Dynamic| {
+        map.insert(key.into(), value);
+    };
+    let function = match event {
+        Event::PlayerJoin(join) => {
+            set("username", join.username.clone().into());
+            set("uuid", format!("{:032x}", join.uuid).into());
+            "on_player_join"
+        }
+        Event::PlayerLeave(leave) => {
+            set("username", leave.username.clone().into());
+            set("uuid", format!("{:032x}", leave.uuid).into());
+            "on_player_leave"
+        }
+        Event::Chat(chat) => {
+            set("username", chat.username.clone().into());
+            set("message", chat.message.clone().into());
+            "on_chat"
+        }
+        Event::BlockPlace(place) => {
+            set("username", place.username.clone().into());
+            set("pos", pos_array(place.pos));
+            set("block", (place.block as i64).into());
+            "on_block_place"
+        }
+        Event::BlockBreak(broken) => {
+            set("username", broken.username.clone().into());
+            set("pos", pos_array(broken.pos));
+            set("block", (broken.block as i64).into());
+            "on_block_break"
+        }
+        Event::PlotLoad(_) => "on_plot_load",
+        Event::PlotUnload(_) => "on_plot_unload",
+        Event::OutputChange(change) => {
+            set("pos", pos_array(change.pos));
+            set("power", (change.power as i64).into());
+            "on_output_change"
+        }
+        Event::ChunkLoad(load) => {
+            set("x", (load.x as i64).into());
+            set("z", (load.z as i64).into());
+            "on_chunk_load"
+        }
+        Event::CompileStart(start) => {
+            set("flags", start.flags.clone().into());
+            "on_compile_start"
+        }
+        Event::CompileFinish(finish) => {
+            set("duration_ms", (finish.duration_ms as i64).into());
+            set("warnings", (finish.warnings as i64).into());
+            "on_compile_finish"
+        }
+        Event::CommandRun(run) => {
+            set("username", run.username.clone().into());
+            set("command", run.command.clone().into());
+            "on_command_run"
+        }
+        _ => return None,
+    };
+    Some((function, map))
+}
+
+impl ScriptHost {
+    pub fn new() -> ScriptHost {
+        ScriptHost {
+            inner: Arc::new_cyclic(|this| Scripts {
+                this: this.clone(),
+                engine: new_engine(),
+                scripts: Mutex::new(BTreeMap::new()),
+                modified: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl Scripts {
+    /// Compiles a script and runs it, with new state
+    fn load(&self, path: &Path) -> Result<(Script, Commands)> {
+        let ast = self.engine.compile_file(path.to_path_buf())?;
+        LOADING_COMMANDS.with(|commands| commands.borrow_mut().clear());
+        let (result, tasks) = self.running(path, || {
+            self.engine.run_ast_with_scope(&mut Scope::new(), &ast)
+        });
+        let commands = LOADING_COMMANDS.with(|commands| commands.take());
+        if let Err(err) = result {
+            for task in tasks {
+                task.cancel();
+            }
+            return Err(err.into());
+        }
+        let script = Script {
+            path: path.to_path_buf(),
+            ast,
+            state: Map::new().into(),
+            tasks,
+        };
+        Ok((script, commands))
+    }
+
+    /// Runs `f` as the script at `path`, and returns the tasks it scheduled
+    fn running<R>(&self, path: &Path, f: impl FnOnce() -> R) -> (R, Vec<TaskHandle>) {
+        let _running = RunningGuard::enter(self.this.clone(), path);
+        // The tasks of a script this call is nested in are given back to it afterwards
+        let outer_tasks = SCHEDULED_TASKS.with(|tasks| tasks.take());
+        let result = f();
+        let tasks = SCHEDULED_TASKS.with(|tasks| tasks.replace(outer_tasks));
+        (result, tasks)
+    }
+
+    /// The loaded scripts, in the order of their paths
+    fn loaded(&self) -> Vec<(PathBuf, LoadedScript)> {
+        let scripts = self.scripts.lock().unwrap();
+        scripts
+            .iter()
+            .map(|(path, loaded)| (path.clone(), loaded.clone()))
+            .collect()
+    }
+
+    fn run_task(&self, path: &Path, function: &str, plot: Option<&mut dyn PlotHandle>) {
+        let Some(loaded) = self.scripts.lock().unwrap().get(path).cloned() else {
+            return;
+        };
+        if let Some(mut script) = lock_script(&loaded.script, path) {
+            self.call(&mut script, function, (), plot);
+        }
+    }
+
+    /// Loads scripts that were added or changed, and drops the ones that were removed
+    fn reload(&self) {
+        let mut paths: Vec<PathBuf> = fs::read_dir(SCRIPTS_DIR)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+
+        // Scripts are only locked to change them, and not while the others are compiled, so that
+        // events keep being handled meanwhile
+        let mut removed = Vec::new();
+        self.scripts.lock().unwrap().retain(|path, loaded| {
+            let exists = paths.contains(path);
+            if !exists {
+                removed.push(loaded.script.clone());
+            }
+            exists
+        });
+        for script in removed {
+            let mut script = script.lock().unwrap();
+            script.cancel_tasks();
+            info!("Unloaded script {}", script.name());
+        }
+
+        let mut last_modified = self.modified.lock().unwrap();
+        last_modified.retain(|path, _| paths.contains(path));
+        for path in paths {
+            let Some(modified) = modified(&path) else {
+                continue;
+            };
+            if last_modified.insert(path.clone(), modified) == Some(modified) {
+                continue;
+            }
+            let existing = self.scripts.lock().unwrap().get(&path).cloned();
+            match (self.load(&path), existing) {
+                (Ok((loaded, commands)), Some(existing)) => {
+                    {
+                        let mut script = existing.script.lock().unwrap();
+                        script.cancel_tasks();
+                        script.ast = loaded.ast;
+                        script.tasks = loaded.tasks;
+                        info!("Reloaded script {}", script.name());
+                    }
+                    let script = existing.script;
+                    self.scripts
+                        .lock()
+                        .unwrap()
+                        .insert(path, LoadedScript { script, commands });
+                }
+                (Ok((loaded, commands)), None) => {
+                    info!("Loaded script {}", path.display());
+                    let script = Arc::new(Mutex::new(loaded));
+                    self.scripts
+                        .lock()
+                        .unwrap()
+                        .insert(path, LoadedScript { script, commands });
+                }
+                // The old version keeps running until the script is fixed
+                (Err(err), _) => error!("Failed to load script {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    fn call(
+        &self,
+        script: &mut Script,
+        function: &str,
+        args: impl rhai::FuncArgs,
+        plot: Option<&mut dyn PlotHandle>,
+    ) {
+        let _plot = PlotGuard::set(plot);
+        let Script {
+            path, ast, state, ..
+        } = script;
+        let (result, tasks) = self.running(path, || {
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+            self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                ast,
+                function,
+                args,
+            )
+        });
+        script.tasks.retain(TaskHandle::is_active);
+        script.tasks.extend(tasks);
+        if let Err(err) = result {
+            warn!("Script {} failed in {}: {}", script.name(), function, err);
+        }
+    }
+}
+
+impl Plugin for ScriptHost {
+    fn name(&self) -> &str {
+        PLUGIN_NAME
+    }
+
+    fn on_enable(&self, server: Arc<dyn ServerHandle>) {
+        let _ = SERVER.set(server);
+        self.inner.reload();
+        let scripts = self.inner.clone();
+        std::thread::Builder::new()
+            .name("Script reloader".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(RELOAD_INTERVAL);
+                scripts.reload();
+            })
+            .unwrap();
+    }
+
+    fn on_event(&self, event: &Event, mut plot: Option<&mut dyn PlotHandle>) {
+        let Some((function, map)) = event_call(event) else {
+            return;
+        };
+        for (path, loaded) in self.inner.loaded() {
+            let Some(mut script) = lock_script(&loaded.script, &path) else {
+                continue;
+            };
+            if !script.handles(function, 1) {
+                continue;
+            }
+            let plot = plot.as_mut().map(|plot| &mut **plot as &mut dyn PlotHandle);
+            self.inner.call(&mut script, function, (map.clone(),), plot);
+        }
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        let scripts = self.inner.scripts.lock().unwrap();
+        scripts
+            .values()
+            .flat_map(|loaded| loaded.commands.iter().map(|(spec, _)| spec.clone()))
+            .collect()
+    }
+
+    fn on_command(&self, command: &str, args: &CommandArgs, sender: &mut CommandSender<'_>) {
+        let found = self.inner.loaded().into_iter().find_map(|(path, loaded)| {
+            let (_, handler) = loaded
+                .commands
+                .iter()
+                .find(|(spec, _)| spec.name == command)?;
+            let handler = handler.fn_name().to_string();
+            Some((path, loaded.script, handler))
+        });
+        let Some((path, script, handler)) = found else {
+            return;
+        };
+        let Some(mut script) = lock_script(&script, &path) else {
+            return;
+        };
+        let raw: Array = args
+            .raw
+            .iter()
+            .map(|arg| Dynamic::from(arg.clone()))
+            .collect();
+        let username = sender.username.to_string();
+        // Handlers with a third parameter get the parsed arguments too
+        if script.handles(&handler, 3) {
+            let values: Map = args
+                .values
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        ArgValue::String(value) => value.clone().into(),
+                        ArgValue::Integer(value) => (*value as i64).into(),
+                        ArgValue::Float(value) => (*value as f64).into(),
+                        ArgValue::BlockPos(pos) => pos_array(*pos),
+                    };
+                    (name.into(), value)
+                })
+                .collect();
+            self.inner.call(
+                &mut script,
+                &handler,
+                (raw, username, values),
+                Some(&mut *sender.plot),
+            );
+        } else {
+            self.inner.call(
+                &mut script,
+                &handler,
+                (raw, username),
+                Some(&mut *sender.plot),
+            );
+        }
+    }
+}
+
+#[test]
+fn script_test() {
+    let path = std::env::temp_dir().join("mchprs_script_test.rhai");
+    fs::write(
+        &path,
+        r#"
+        fn on_chat(event) {
+            this.messages = (this.messages ?? 0) + 1;
+            this.last = event.message;
+        }
+        fn greet(args, username) {
+            this.greeted = username + " " + args[0];
+        }
+        register_command("greet", "Greets you", Fn("greet"));
+        register_command(#{ name: "fill", usage: "<to:pos> [count:int]" }, Fn("greet"));
+        "#,
+    )
+    .unwrap();
+    let host = ScriptHost::new();
+    let (script, commands) = host.inner.load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let shared = Arc::new(Mutex::new(script));
+    host.inner.scripts.lock().unwrap().insert(
+        path.clone(),
+        LoadedScript {
+            script: shared.clone(),
+            commands,
+        },
+    );
+    assert_eq!(
+        host.commands(),
+        vec![
+            CommandSpec::new("greet", "Greets you"),
+            CommandSpec::new("fill", "")
+                .usage("<to:pos> [count:int]")
+                .unwrap()
+        ]
+    );
+
+    let chat = |message: &str| {
+        Event::Chat(mchprs_plugin::events::Chat {
+            username: "Player".to_string(),
+            message: message.to_string(),
+        })
+    };
+    host.on_event(&chat("hello"), None);
+    host.on_event(&chat("again"), None);
+    // A script isn't run again from inside itself
+    let (nested, _) = host
+        .inner
+        .running(&path, || lock_script(&shared, &path).is_none());
+    assert!(nested);
+    let mut script = shared.lock().unwrap();
+    host.inner.call(
+        &mut script,
+        "greet",
+        (vec![Dynamic::from("hi".to_string())], "Player".to_string()),
+        None,
+    );
+    let state = script.state.read_lock::<Map>().unwrap();
+    assert_eq!(state["messages"].as_int().unwrap(), 2);
+    assert_eq!(state["last"].clone().into_string().unwrap(), "again");
+    assert_eq!(state["greeted"].clone().into_string().unwrap(), "Player hi");
+}
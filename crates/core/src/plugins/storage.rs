@@ -0,0 +1,148 @@
+//! Keeps the storage of plugins in `./world/plugin_data`. Storage is loaded when it is first used
+//! and kept in memory, and changes are saved by `save` every so often.
+
+use mchprs_plugin::{Storage, StorageScope};
+use mchprs_save_data::plugin_data::{PluginData, PluginDataError};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+const STORAGE_DIR: &str = "./world/plugin_data";
+/// How often changed storage is saved, in game ticks
+pub const SAVE_INTERVAL: u64 = 20 * 30;
+
+pub struct Store {
+    path: PathBuf,
+    data: Mutex<StoreData>,
+}
+
+struct StoreData {
+    data: PluginData,
+    /// Whether it changed since it was saved
+    dirty: bool,
+}
+
+static STORES: Lazy<Mutex<HashMap<PathBuf, Arc<Store>>>> = Lazy::new(Default::default);
+
+/// Plugin names are used as folder names, so anything that isn't safe in one is replaced
+fn folder_name(plugin: &str) -> String {
+    plugin
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn path(plugin: &str, scope: StorageScope) -> PathBuf {
+    let folder = PathBuf::from(STORAGE_DIR).join(folder_name(plugin));
+    match scope {
+        StorageScope::Plugin => folder.join("plugin"),
+        StorageScope::Plot { x, z } => folder.join("plots").join(format!("p{},{}", x, z)),
+        StorageScope::Player(uuid) => folder.join("players").join(format!("{:032x}", uuid)),
+    }
+}
+
+pub fn open(plugin: &str, scope: StorageScope) -> Arc<Store> {
+    let path = path(plugin, scope);
+    let mut stores = STORES.lock().unwrap();
+    stores
+        .entry(path.clone())
+        .or_insert_with(|| {
+            let data = if path.exists() {
+                PluginData::load_from_file(&path).unwrap_or_else(|err| {
+                    // The file is kept until something is stored, so it can still be recovered
+                    error!("Failed to load plugin data {}: {}", path.display(), err);
+                    PluginData::default()
+                })
+            } else {
+                PluginData::default()
+            };
+            Arc::new(Store {
+                path,
+                data: Mutex::new(StoreData { data, dirty: false }),
+            })
+        })
+        .clone()
+}
+
+impl Store {
+    fn save(&self) {
+        let mut store = self.data.lock().unwrap();
+        if !store.dirty {
+            return;
+        }
+        store.dirty = false;
+        let data = &store.data;
+        let result: Result<(), PluginDataError> = if data.is_empty() {
+            fs::remove_file(&self.path).or_else(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(err.into()),
+            })
+        } else {
+            fs::create_dir_all(self.path.parent().unwrap())
+                .map_err(Into::into)
+                .and_then(|_| data.save_to_file(&self.path))
+        };
+        if let Err(err) = result {
+            error!(
+                "Failed to save plugin data {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+impl Storage for Store {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.data.lock().unwrap().data.get(key).map(<[u8]>::to_vec)
+    }
+
+    fn set(&self, key: &str, value: &[u8]) {
+        let mut store = self.data.lock().unwrap();
+        store.data.insert(key.to_string(), value.to_vec());
+        store.dirty = true;
+    }
+
+    fn remove(&self, key: &str) -> bool {
+        let mut store = self.data.lock().unwrap();
+        let removed = store.data.remove(key).is_some();
+        store.dirty |= removed;
+        removed
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.data
+            .lock()
+            .unwrap()
+            .data
+            .keys()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Saves the storage that changed since it was last saved
+pub fn save() {
+    let stores: Vec<Arc<Store>> = STORES.lock().unwrap().values().cloned().collect();
+    for store in stores {
+        store.save();
+    }
+}
+
+#[test]
+fn storage_path_test() {
+    assert_eq!(
+        path("../my plugin", StorageScope::Plot { x: -1, z: 2 }),
+        PathBuf::from("./world/plugin_data/___my_plugin/plots/p-1,2")
+    );
+    assert_eq!(
+        path("scripts", StorageScope::Player(1)),
+        PathBuf::from("./world/plugin_data/scripts/players/00000000000000000000000000000001")
+    );
+}
@@ -0,0 +1,759 @@
+//! Runs WebAssembly plugins. Unlike native plugins, these can only do what the host functions
+//! below let them, and are limited in how long they run and how much memory they use, so plugins
+//! that aren't trusted can be run safely.
+//!
+//! Strings are passed as a pointer and a length into the memory of the plugin, and are UTF-8.
+//! Modules export:
+//! - `memory`
+//! - `alloc(len: i32) -> i32`, which allocates memory the host writes into. The plugin owns it
+//!   afterwards.
+//! - `on_enable()`, `on_disable()`, `on_event(ptr: i32, len: i32)` and
+//!   `on_command(ptr: i32, len: i32)`, which are all optional. Events and commands are JSON.
+//!
+//! They can import these functions from the `mchprs` module:
+//! - `log(ptr, len)`
+//! - `broadcast(ptr, len)`
+//! - `send_message(username_ptr, username_len, message_ptr, message_len)`
+//! - `register_command(name_ptr, name_len, description_ptr, description_len)`
+//! - `register_command_json(ptr, len)`, which takes an object with `name`, `description`, and
+//!   optionally `usage` (see `CommandSpec::usage`) and `permission`. Commands get their parsed
+//!   arguments in `values`.
+//! - `get_block(x, y, z) -> i32`, which returns -1 outside of plot events and commands
+//! - `set_block(x, y, z, block) -> i32`
+//! - `use_block(x, y, z) -> i32`
+//! - `read_output(x, y, z) -> i32`, which returns -1 if redpiler isn't running or there is no
+//!   component at the position
+//! - `set_input(x, y, z, powered) -> i32`
+//! - `find_named(ptr, len, out_ptr) -> i32`, which writes the position of the named component as
+//!   three little endian `i32`s to `out_ptr` and returns 1 if it was found
+//! - `watch_output(x, y, z)`
+//! - `schedule(delay, period, task) -> i32`, which calls the `on_task(ptr: i32, len: i32)` export
+//!   with `task` after `delay` game ticks, and then every `period` game ticks if it isn't 0. The
+//!   task runs on the plot of the current event or command, or on the main thread outside of them.
+//!   Returns a handle for `cancel_task(handle)`.
+//! - `storage_get(scope_ptr, scope_len, key_ptr, key_len, out_ptr, out_len) -> i32`, which writes
+//!   as much of the value as fits into `out_len` bytes at `out_ptr` and returns its length, or -1
+//!   if there is none. The scope is `plugin`, `plot` for the plot of the current event or command,
+//!   or the uuid of a player in hex.
+//! - `storage_set(scope_ptr, scope_len, key_ptr, key_len, value_ptr, value_len) -> i32`
+//! - `storage_remove(scope_ptr, scope_len, key_ptr, key_len) -> i32`
+
+use anyhow::{anyhow, Context, Result};
+use mchprs_plugin::{
+    ArgValue, BlockPos, CommandArgs, CommandSender, CommandSpec, Event, PlotHandle, Plugin,
+    ServerHandle, Storage, StorageScope, TaskHandle,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use tracing::{info, warn};
+use wasmi::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+/// How many instructions a plugin can run per call
+const FUEL_PER_CALL: u64 = 50_000_000;
+/// How much memory a plugin can use
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// The plot of the event or command that is being handled. The lifetime is erased, since it is
+/// only set while a `LentPlot` borrows the plot.
+struct PlotPtr(*mut (dyn PlotHandle + 'static));
+
+// SAFETY: The pointer is only set while a `LentPlot` borrows the plot on the thread that is calling
+// into the plugin. The store is behind the runtime's mutex during that time, so no other thread
+// can reach it before it is cleared again.
+unsafe impl Send for PlotPtr {}
+
+/// Lends a plot to the plugin for as long as it lives, giving access to the store meanwhile. The
+/// plot is taken out of the store again when it is dropped, also when the call unwinds, so the
+/// pointer never outlives the borrow of the plot.
+struct LentPlot<'s, 'p> {
+    store: &'s mut Store<HostState>,
+    _plot: PhantomData<&'p mut dyn PlotHandle>,
+}
+
+impl<'s, 'p> LentPlot<'s, 'p> {
+    fn new(
+        store: &'s mut Store<HostState>,
+        plot: Option<&'p mut dyn PlotHandle>,
+    ) -> LentPlot<'s, 'p> {
+        // SAFETY: The guard keeps the plot borrowed, and clears the pointer before the borrow ends
+        store.data_mut().plot = plot.map(|plot| {
+            PlotPtr(unsafe {
+                std::mem::transmute::<&mut dyn PlotHandle, *mut (dyn PlotHandle + 'static)>(plot)
+            })
+        });
+        LentPlot {
+            store,
+            _plot: PhantomData,
+        }
+    }
+}
+
+impl Deref for LentPlot<'_, '_> {
+    type Target = Store<HostState>;
+
+    fn deref(&self) -> &Store<HostState> {
+        self.store
+    }
+}
+
+impl DerefMut for LentPlot<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Store<HostState> {
+        self.store
+    }
+}
+
+impl Drop for LentPlot<'_, '_> {
+    fn drop(&mut self) {
+        self.store.data_mut().plot = None;
+    }
+}
+
+struct HostState {
+    name: String,
+    server: Option<Arc<dyn ServerHandle>>,
+    commands: Vec<CommandSpec>,
+    plot: Option<PlotPtr>,
+    limits: StoreLimits,
+    /// The runtime the state is in, for scheduled tasks to call into
+    runtime: Weak<Mutex<Runtime>>,
+    tasks: HashMap<i32, TaskHandle>,
+    next_task: i32,
+}
+
+impl HostState {
+    fn plot(&mut self) -> Option<&mut dyn PlotHandle> {
+        // SAFETY: The pointer is only set while a `LentPlot` borrows the plot
+        self.plot.as_mut().map(|plot| unsafe { &mut *plot.0 })
+    }
+}
+
+struct Runtime {
+    store: Store<HostState>,
+    instance: Instance,
+}
+
+pub struct WasmPlugin {
+    name: String,
+    runtime: Arc<Mutex<Runtime>>,
+}
+
+fn memory(caller: &Caller<'_, HostState>) -> Option<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Some(memory),
+        _ => None,
+    }
+}
+
+fn read_bytes(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let data = memory(caller)?.data(caller);
+    let bytes = data.get(ptr as usize..(ptr as usize).checked_add(len as usize)?)?;
+    Some(bytes.to_vec())
+}
+
+fn read_str(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).ok()
+}
+
+/// The storage of the plugin for a scope, and the key in it
+fn storage(
+    caller: &mut Caller<'_, HostState>,
+    (scope_ptr, scope_len): (i32, i32),
+    (key_ptr, key_len): (i32, i32),
+) -> Option<(Arc<dyn Storage>, String)> {
+    let scope = read_str(caller, scope_ptr, scope_len)?;
+    let key = read_str(caller, key_ptr, key_len)?;
+    let state = caller.data_mut();
+    let scope = match scope.as_str() {
+        "plugin" => StorageScope::Plugin,
+        "plot" => {
+            let plot = state.plot()?;
+            StorageScope::Plot {
+                x: plot.x(),
+                z: plot.z(),
+            }
+        }
+        uuid => StorageScope::Player(u128::from_str_radix(uuid, 16).ok()?),
+    };
+    let storage = state.server.as_ref()?.storage(&state.name, scope);
+    Some((storage, key))
+}
+
+fn link(linker: &mut Linker<HostState>) -> Result<()> {
+    linker.func_wrap(
+        "mchprs",
+        "log",
+        |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            if let Some(message) = read_str(&caller, ptr, len) {
+                info!("[{}] {}", caller.data().name, message);
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "broadcast",
+        |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            if let (Some(message), Some(server)) =
+                (read_str(&caller, ptr, len), &caller.data().server)
+            {
+                server.broadcast(&message);
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "send_message",
+        |caller: Caller<'_, HostState>, user_ptr: i32, user_len: i32, ptr: i32, len: i32| {
+            let username = read_str(&caller, user_ptr, user_len);
+            let message = read_str(&caller, ptr, len);
+            if let (Some(username), Some(message), Some(server)) =
+                (username, message, &caller.data().server)
+            {
+                server.send_message(&username, &message);
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "register_command",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ptr: i32, len: i32| {
+            let name = read_str(&caller, name_ptr, name_len);
+            let description = read_str(&caller, ptr, len);
+            if let (Some(name), Some(description)) = (name, description) {
+                caller
+                    .data_mut()
+                    .commands
+                    .push(CommandSpec::new(name, description));
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "register_command_json",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let Some(json) = read_str(&caller, ptr, len) else {
+                return;
+            };
+            let state = caller.data_mut();
+            match command_spec(&json) {
+                Ok(spec) => state.commands.push(spec),
+                Err(err) => warn!("Plugin {} registered a bad command: {}", state.name, err),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "get_block",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32| -> i32 {
+            caller
+                .data_mut()
+                .plot()
+                .and_then(|plot| plot.get_block(BlockPos::new(x, y, z)))
+                .map_or(-1, |block| block as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "set_block",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32, block: i32| -> i32 {
+            caller
+                .data_mut()
+                .plot()
+                .is_some_and(|plot| plot.set_block(BlockPos::new(x, y, z), block as u32))
+                as i32
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "use_block",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32| -> i32 {
+            caller
+                .data_mut()
+                .plot()
+                .is_some_and(|plot| plot.use_block(BlockPos::new(x, y, z))) as i32
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "read_output",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32| -> i32 {
+            caller
+                .data_mut()
+                .plot()
+                .and_then(|plot| plot.read_output(BlockPos::new(x, y, z)))
+                .map_or(-1, i32::from)
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "set_input",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32, powered: i32| -> i32 {
+            caller
+                .data_mut()
+                .plot()
+                .is_some_and(|plot| plot.set_input(BlockPos::new(x, y, z), powered != 0))
+                as i32
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "find_named",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32, out_ptr: i32| -> i32 {
+            let Some(name) = read_str(&caller, ptr, len) else {
+                return 0;
+            };
+            let Some(pos) = caller
+                .data_mut()
+                .plot()
+                .and_then(|plot| plot.find_named(&name))
+            else {
+                return 0;
+            };
+            let Some(memory) = memory(&caller) else {
+                return 0;
+            };
+            let mut bytes = [0; 12];
+            for (i, coord) in [pos.x, pos.y, pos.z].into_iter().enumerate() {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&coord.to_le_bytes());
+            }
+            memory.write(&mut caller, out_ptr as usize, &bytes).is_ok() as i32
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "watch_output",
+        |mut caller: Caller<'_, HostState>, x: i32, y: i32, z: i32| {
+            if let Some(plot) = caller.data_mut().plot() {
+                plot.watch_output(BlockPos::new(x, y, z));
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "schedule",
+        |mut caller: Caller<'_, HostState>, delay: i32, period: i32, task: i32| -> i32 {
+            let state = caller.data_mut();
+            let Some(server) = state.server.clone() else {
+                return -1;
+            };
+            let runtime = state.runtime.clone();
+            let name = state.name.clone();
+            let arg = json!({ "task": task });
+            let delay = delay.max(0) as u64;
+            let period = (period > 0).then_some(period as u64);
+            let handle = match state.plot().map(|plot| (plot.x(), plot.z())) {
+                Some((x, z)) => server.schedule_on_plot(
+                    x,
+                    z,
+                    delay,
+                    period,
+                    Box::new(move |plot| run_task(&runtime, &name, &arg, Some(plot))),
+                ),
+                None => server.schedule(
+                    delay,
+                    period,
+                    Box::new(move || run_task(&runtime, &name, &arg, None)),
+                ),
+            };
+            state.tasks.retain(|_, handle| handle.is_active());
+            state.next_task += 1;
+            state.tasks.insert(state.next_task, handle);
+            state.next_task
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "cancel_task",
+        |mut caller: Caller<'_, HostState>, handle: i32| {
+            if let Some(handle) = caller.data_mut().tasks.remove(&handle) {
+                handle.cancel();
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "storage_get",
+        |mut caller: Caller<'_, HostState>,
+         scope_ptr: i32,
+         scope_len: i32,
+         key_ptr: i32,
+         key_len: i32,
+         out_ptr: i32,
+         out_len: i32|
+         -> i32 {
+            let Some((storage, key)) =
+                storage(&mut caller, (scope_ptr, scope_len), (key_ptr, key_len))
+            else {
+                return -1;
+            };
+            let (Some(value), Some(memory)) = (storage.get(&key), memory(&caller)) else {
+                return -1;
+            };
+            let written = value.len().min(out_len.max(0) as usize);
+            if memory
+                .write(&mut caller, out_ptr as usize, &value[..written])
+                .is_err()
+            {
+                return -1;
+            }
+            value.len() as i32
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "storage_set",
+        |mut caller: Caller<'_, HostState>,
+         scope_ptr: i32,
+         scope_len: i32,
+         key_ptr: i32,
+         key_len: i32,
+         value_ptr: i32,
+         value_len: i32|
+         -> i32 {
+            let value = read_bytes(&caller, value_ptr, value_len);
+            let storage = storage(&mut caller, (scope_ptr, scope_len), (key_ptr, key_len));
+            let (Some((storage, key)), Some(value)) = (storage, value) else {
+                return 0;
+            };
+            storage.set(&key, &value);
+            1
+        },
+    )?;
+    linker.func_wrap(
+        "mchprs",
+        "storage_remove",
+        |mut caller: Caller<'_, HostState>,
+         scope_ptr: i32,
+         scope_len: i32,
+         key_ptr: i32,
+         key_len: i32|
+         -> i32 {
+            storage(&mut caller, (scope_ptr, scope_len), (key_ptr, key_len))
+                .is_some_and(|(storage, key)| storage.remove(&key)) as i32
+        },
+    )?;
+    Ok(())
+}
+
+fn command_spec(json: &str) -> Result<CommandSpec> {
+    let json: Value = serde_json::from_str(json)?;
+    let field = |name: &str| json.get(name).and_then(Value::as_str);
+    let name = field("name").context("The command has no name")?;
+    let mut spec = CommandSpec::new(name, field("description").unwrap_or_default());
+    if let Some(usage) = field("usage") {
+        spec = spec.usage(usage).map_err(|err| anyhow!(err))?;
+    }
+    if let Some(permission) = field("permission") {
+        spec = spec.permission(permission);
+    }
+    Ok(spec)
+}
+
+fn run_task(
+    runtime: &Weak<Mutex<Runtime>>,
+    name: &str,
+    arg: &Value,
+    plot: Option<&mut dyn PlotHandle>,
+) {
+    if let Some(runtime) = runtime.upgrade() {
+        call(&runtime, name, "on_task", Some(arg), plot);
+    }
+}
+
+/// Calls an export of the plugin if it has it, with `arg` written into its memory
+fn call(
+    runtime: &Mutex<Runtime>,
+    name: &str,
+    export: &str,
+    arg: Option<&Value>,
+    plot: Option<&mut dyn PlotHandle>,
+) {
+    let mut runtime = runtime.lock().unwrap();
+    let Runtime { store, instance } = &mut *runtime;
+    let result = call_export(&mut LentPlot::new(store, plot), instance, export, arg);
+    if let Err(err) = result {
+        warn!("Plugin {} failed in {}: {:#}", name, export, err);
+    }
+}
+
+impl WasmPlugin {
+    pub fn load(path: &Path) -> Result<WasmPlugin> {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let bytes = std::fs::read(path)?;
+        WasmPlugin::new(name, &bytes)
+    }
+
+    fn new(name: String, wasm: &[u8]) -> Result<WasmPlugin> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm)?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                name: name.clone(),
+                server: None,
+                commands: Vec::new(),
+                plot: None,
+                limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+                runtime: Weak::new(),
+                tasks: HashMap::new(),
+                next_task: 0,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).expect("fuel is turned on");
+
+        let mut linker = Linker::new(&engine);
+        link(&mut linker)?;
+        let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+        instance
+            .get_memory(&store, "memory")
+            .context("The module doesn't export its memory")?;
+        instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .context("The module doesn't export `alloc`")?;
+
+        let runtime = Arc::new(Mutex::new(Runtime { store, instance }));
+        runtime.lock().unwrap().store.data_mut().runtime = Arc::downgrade(&runtime);
+        Ok(WasmPlugin { name, runtime })
+    }
+
+    fn call(&self, export: &str, arg: Option<&Value>, plot: Option<&mut dyn PlotHandle>) {
+        call(&self.runtime, &self.name, export, arg, plot);
+    }
+}
+
+fn call_export(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    export: &str,
+    arg: Option<&Value>,
+) -> Result<()> {
+    store.set_fuel(FUEL_PER_CALL).expect("fuel is turned on");
+    match arg {
+        Some(arg) => {
+            let Ok(func) = instance.get_typed_func::<(i32, i32), ()>(&*store, export) else {
+                return Ok(());
+            };
+            let arg = arg.to_string();
+            let len = i32::try_from(arg.len())?;
+            let alloc: TypedFunc<i32, i32> = instance.get_typed_func(&*store, "alloc")?;
+            let ptr = alloc.call(&mut *store, len)?;
+            let memory = instance
+                .get_memory(&*store, "memory")
+                .ok_or_else(|| anyhow!("The module doesn't export its memory"))?;
+            memory
+                .write(&mut *store, ptr as usize, arg.as_bytes())
+                .map_err(|err| anyhow!("{}", err))?;
+            func.call(&mut *store, (ptr, len))?;
+        }
+        None => {
+            let Ok(func) = instance.get_typed_func::<(), ()>(&*store, export) else {
+                return Ok(());
+            };
+            func.call(&mut *store, ())?;
+        }
+    }
+    Ok(())
+}
+
+fn event_json(event: &Event, plot: Option<&dyn PlotHandle>) -> Option<Value> {
+    let pos = |pos: &BlockPos| json!([pos.x, pos.y, pos.z]);
+    let mut json = match event {
+        Event::PlayerJoin(join) => json!({
+            "event": "player_join",
+            "username": join.username,
+            "uuid": format!("{:032x}", join.uuid),
+        }),
+        Event::PlayerLeave(leave) => json!({
+            "event": "player_leave",
+            "username": leave.username,
+            "uuid": format!("{:032x}", leave.uuid),
+        }),
+        Event::Chat(chat) => json!({
+            "event": "chat",
+            "username": chat.username,
+            "message": chat.message,
+        }),
+        Event::BlockPlace(place) => json!({
+            "event": "block_place",
+            "username": place.username,
+            "pos": pos(&place.pos),
+            "block": place.block,
+        }),
+        Event::BlockBreak(broken) => json!({
+            "event": "block_break",
+            "username": broken.username,
+            "pos": pos(&broken.pos),
+            "block": broken.block,
+        }),
+        Event::PlotLoad(_) => json!({ "event": "plot_load" }),
+        Event::PlotUnload(_) => json!({ "event": "plot_unload" }),
+        Event::OutputChange(change) => json!({
+            "event": "output_change",
+            "pos": pos(&change.pos),
+            "power": change.power,
+        }),
+        Event::ChunkLoad(load) => json!({
+            "event": "chunk_load",
+            "chunk": [load.x, load.z],
+        }),
+        Event::CompileStart(start) => json!({
+            "event": "compile_start",
+            "flags": start.flags,
+        }),
+        Event::CompileFinish(finish) => json!({
+            "event": "compile_finish",
+            "duration_ms": finish.duration_ms,
+            "warnings": finish.warnings,
+        }),
+        Event::CommandRun(run) => json!({
+            "event": "command_run",
+            "username": run.username,
+            "command": run.command,
+        }),
+        _ => return None,
+    };
+    if let Some(plot) = plot {
+        json["plot"] = json!([plot.x(), plot.z()]);
+    }
+    Some(json)
+}
+
+impl Plugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_enable(&self, server: Arc<dyn ServerHandle>) {
+        self.runtime.lock().unwrap().store.data_mut().server = Some(server);
+        self.call("on_enable", None, None);
+    }
+
+    fn on_disable(&self) {
+        self.call("on_disable", None, None);
+    }
+
+    fn on_event(&self, event: &Event, plot: Option<&mut dyn PlotHandle>) {
+        let Some(json) = event_json(event, plot.as_deref()) else {
+            return;
+        };
+        self.call("on_event", Some(&json), plot);
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        self.runtime.lock().unwrap().store.data().commands.clone()
+    }
+
+    fn on_command(&self, command: &str, args: &CommandArgs, sender: &mut CommandSender<'_>) {
+        let values: serde_json::Map<String, Value> = args
+            .values
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    ArgValue::String(value) => json!(value),
+                    ArgValue::Integer(value) => json!(value),
+                    ArgValue::Float(value) => json!(value),
+                    ArgValue::BlockPos(pos) => json!([pos.x, pos.y, pos.z]),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        let json = json!({
+            "command": command,
+            "args": args.raw,
+            "values": values,
+            "username": sender.username,
+            "uuid": format!("{:032x}", sender.uuid),
+            "plot": [sender.plot.x(), sender.plot.z()],
+        });
+        self.call("on_command", Some(&json), Some(&mut *sender.plot));
+    }
+}
+
+#[test]
+fn wasm_plugin_test() {
+    struct TestPlot;
+    impl PlotHandle for TestPlot {
+        fn x(&self) -> i32 {
+            0
+        }
+        fn z(&self) -> i32 {
+            0
+        }
+        fn get_block(&self, _pos: BlockPos) -> Option<u32> {
+            Some(5)
+        }
+        fn set_block(&mut self, _pos: BlockPos, _block: u32) -> bool {
+            false
+        }
+        fn use_block(&mut self, _pos: BlockPos) -> bool {
+            false
+        }
+        fn read_output(&self, _pos: BlockPos) -> Option<u8> {
+            None
+        }
+        fn set_input(&mut self, _pos: BlockPos, _powered: bool) -> bool {
+            false
+        }
+        fn find_named(&self, _name: &str) -> Option<BlockPos> {
+            None
+        }
+        fn watch_output(&mut self, _pos: BlockPos) {}
+        fn players(&self) -> Vec<String> {
+            Vec::new()
+        }
+        fn send_message(&mut self, _username: &str, _message: &str) {}
+    }
+
+    let wasm = wat::parse_str(
+        r#"
+        (module
+            (import "mchprs" "register_command" (func $register (param i32 i32 i32 i32)))
+            (import "mchprs" "get_block" (func $get_block (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "hi")
+            (global $block (export "block") (mut i32) (i32.const 0))
+            (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "on_enable") (call $register (i32.const 0) (i32.const 2) (i32.const 0) (i32.const 0)))
+            (func (export "on_event") (param i32 i32)
+                (global.set $block (call $get_block (i32.const 0) (i32.const 0) (i32.const 0))))
+            (func (export "spin") (loop (br 0)))
+        )
+        "#,
+    )
+    .unwrap();
+    let plugin = WasmPlugin::new("test".to_string(), &wasm).unwrap();
+    plugin.call("on_enable", None, None);
+    assert_eq!(plugin.commands(), vec![CommandSpec::new("hi", "")]);
+
+    let event = Event::PlotLoad(mchprs_plugin::events::PlotLoad { x: 0, z: 0 });
+    plugin.on_event(&event, None);
+    let block = |plugin: &WasmPlugin| {
+        let runtime = plugin.runtime.lock().unwrap();
+        let global = runtime
+            .instance
+            .get_global(&runtime.store, "block")
+            .unwrap();
+        global.get(&runtime.store).i32().unwrap()
+    };
+    // There is no plot to get the block from
+    assert_eq!(block(&plugin), -1);
+    plugin.on_event(&event, Some(&mut TestPlot));
+    assert_eq!(block(&plugin), 5);
+
+    // Plugins that run forever are stopped, and can still be called afterwards
+    plugin.call("spin", None, None);
+    plugin.on_event(&event, None);
+    assert_eq!(block(&plugin), -1);
+}
@@ -1,6 +1,29 @@
 use crate::utils::HyphenatedUUID;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use mchprs_network::packets::PlayerProperty;
+use reqwest::StatusCode;
 use serde::Deserialize;
+use std::time::Duration;
+
+/// How long looking up a skin may take before the player joins without one
+const SKIN_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+impl From<ProfileProperty> for PlayerProperty {
+    fn from(property: ProfileProperty) -> PlayerProperty {
+        PlayerProperty {
+            name: property.name,
+            value: property.value,
+            signature: property.signature,
+        }
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct PlayerProfile {
@@ -8,6 +31,9 @@ pub struct PlayerProfile {
     pub uuid: HyphenatedUUID,
     #[serde(rename = "name")]
     pub username: String,
+    /// The skin and cape of the player. Only the session server sends these.
+    #[serde(default)]
+    pub properties: Vec<ProfileProperty>,
 }
 
 impl PlayerProfile {
@@ -25,4 +51,51 @@ impl PlayerProfile {
             .await?;
         Ok(res)
     }
+
+    /// Asks the session server whether the player logged in to the server with the given hash,
+    /// which verifies that they own the account
+    pub async fn has_joined(username: &str, server_hash: &str) -> Result<PlayerProfile> {
+        let client = reqwest::Client::new();
+        let res = client
+            .get("https://sessionserver.mojang.com/session/minecraft/hasJoined")
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await?
+            .error_for_status()?;
+        // The session server responds without content if the player didn't join
+        if res.status() != StatusCode::OK {
+            bail!("the session server didn't verify the player");
+        }
+        Ok(res.json::<PlayerProfile>().await?)
+    }
+
+    /// Looks up the signed skin and cape of the account with the given username
+    pub async fn lookup_textures(username: &str) -> Result<Vec<ProfileProperty>> {
+        let client = reqwest::Client::builder()
+            .timeout(SKIN_LOOKUP_TIMEOUT)
+            .build()?;
+        let profile = client
+            .get(format!(
+                "https://api.mojang.com/users/profiles/minecraft/{}",
+                username
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PlayerProfile>()
+            .await?;
+        let url = format!(
+            "https://sessionserver.mojang.com/session/minecraft/profile/{:032x}",
+            profile.uuid.0
+        );
+        let profile = client
+            .get(url)
+            .query(&[("unsigned", "false")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PlayerProfile>()
+            .await?;
+        Ok(profile.properties)
+    }
 }
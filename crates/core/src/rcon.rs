@@ -0,0 +1,221 @@
+//! A server for the Source RCON protocol, which lets hosting panels and other tools run console
+//! commands. See https://developer.valvesoftware.com/wiki/Source_RCON_Protocol
+
+use crate::config::RconConfig;
+use crate::server::Message;
+use crate::tcp_server::{self, Limits};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tracing::{error, info, warn};
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The largest packet clients may send, which is what vanilla accepts as well
+const MAX_REQUEST_SIZE: i32 = 1460;
+/// Longer responses are split into multiple packets
+const MAX_RESPONSE_BODY: usize = 4096;
+/// How long to wait for the server thread to run a command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a client has to log in before the connection is dropped
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a client that is logged in can go without sending a command before the connection is
+/// dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// How many connections are handled at once. This also limits how many passwords can be tried
+/// at the same time.
+const MAX_CONNECTIONS: usize = 8;
+/// How long to wait before answering a failed login, which is then disconnected. This slows
+/// down guessing the password.
+const FAILED_LOGIN_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+fn read_packet(reader: &mut impl Read) -> io::Result<Packet> {
+    let size = reader.read_i32::<LittleEndian>()?;
+    // The id, type and the two null bytes at the end
+    if !(10..=MAX_REQUEST_SIZE).contains(&size) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid packet size {}", size),
+        ));
+    }
+    let id = reader.read_i32::<LittleEndian>()?;
+    let kind = reader.read_i32::<LittleEndian>()?;
+    let mut body = vec![0; size as usize - 8];
+    reader.read_exact(&mut body)?;
+    let end = body.iter().position(|&b| b == 0).unwrap_or(body.len());
+    body.truncate(end);
+    Ok(Packet {
+        id,
+        kind,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn write_packet(writer: &mut impl Write, packet: &Packet) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(packet.body.len() + 14);
+    buf.write_i32::<LittleEndian>(packet.body.len() as i32 + 10)?;
+    buf.write_i32::<LittleEndian>(packet.id)?;
+    buf.write_i32::<LittleEndian>(packet.kind)?;
+    buf.extend_from_slice(packet.body.as_bytes());
+    buf.extend_from_slice(&[0, 0]);
+    writer.write_all(&buf)
+}
+
+/// Sends the output of a command, split into as many packets as it needs
+fn write_response(writer: &mut impl Write, id: i32, output: &str) -> io::Result<()> {
+    let mut rest = output;
+    loop {
+        let mut end = rest.len().min(MAX_RESPONSE_BODY);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (body, next) = rest.split_at(end);
+        let packet = Packet {
+            id,
+            kind: SERVERDATA_RESPONSE_VALUE,
+            body: body.to_string(),
+        };
+        write_packet(writer, &packet)?;
+        if next.is_empty() {
+            return Ok(());
+        }
+        rest = next;
+    }
+}
+
+/// Compares the password in constant time, so that it can't be guessed from how long that takes
+fn password_matches(given: &str, password: &str) -> bool {
+    given.as_bytes().ct_eq(password.as_bytes()).into()
+}
+
+fn handle_connection(stream: TcpStream, password: &str, sender: Sender<Message>) -> io::Result<()> {
+    let address = stream.peer_addr()?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    let mut authenticated = false;
+    loop {
+        let packet = match read_packet(&mut reader) {
+            Ok(packet) => packet,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        match packet.kind {
+            SERVERDATA_AUTH => {
+                authenticated = password_matches(&packet.body, password);
+                if !authenticated {
+                    warn!("Failed RCON login from {}", address);
+                    std::thread::sleep(FAILED_LOGIN_DELAY);
+                }
+                let response = Packet {
+                    id: if authenticated { packet.id } else { -1 },
+                    kind: SERVERDATA_AUTH_RESPONSE,
+                    body: String::new(),
+                };
+                write_packet(&mut writer, &response)?;
+                if !authenticated {
+                    return Ok(());
+                }
+                stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+            }
+            SERVERDATA_EXECCOMMAND if authenticated => {
+                info!("RCON ({}) issued command: {}", address, packet.body);
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if sender
+                    .send(Message::ConsoleCommand(packet.body, reply_tx))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+                let output = reply_rx
+                    .recv_timeout(COMMAND_TIMEOUT)
+                    .unwrap_or_else(|_| "The server did not respond to the command.".to_string());
+                write_response(&mut writer, packet.id, &output)?;
+            }
+            _ => {
+                // Clients have to log in first, so this can only be a client that didn't
+                let response = Packet {
+                    id: -1,
+                    kind: SERVERDATA_AUTH_RESPONSE,
+                    body: String::new(),
+                };
+                write_packet(&mut writer, &response)?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Starts the RCON server on its own thread
+pub fn start(config: &RconConfig, sender: Sender<Message>) {
+    if config.password.is_empty() {
+        error!("RCON is enabled without a password, so it was not started");
+        return;
+    }
+    let listener = match TcpListener::bind(&config.bind_address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(
+                "Failed to bind RCON server to {}: {}",
+                config.bind_address, err
+            );
+            return;
+        }
+    };
+    info!("RCON listening on {}", config.bind_address);
+
+    let password = config.password.clone();
+    let limits = Limits {
+        max_connections: MAX_CONNECTIONS,
+        read_timeout: LOGIN_TIMEOUT,
+    };
+    tcp_server::serve("RCON", listener, limits, move |stream| {
+        handle_connection(stream, &password, sender.clone())
+    });
+}
+
+#[test]
+fn packet_roundtrip_test() {
+    let packet = Packet {
+        id: 7,
+        kind: SERVERDATA_EXECCOMMAND,
+        body: "list".to_string(),
+    };
+    let mut buf = Vec::new();
+    write_packet(&mut buf, &packet).unwrap();
+    assert_eq!(&buf[..4], &14i32.to_le_bytes());
+    assert_eq!(read_packet(&mut buf.as_slice()).unwrap(), packet);
+
+    let mut buf = Vec::new();
+    write_response(&mut buf, 3, &"a".repeat(MAX_RESPONSE_BODY + 1)).unwrap();
+    let mut reader = buf.as_slice();
+    let first = Packet {
+        id: 3,
+        kind: SERVERDATA_RESPONSE_VALUE,
+        body: "a".repeat(MAX_RESPONSE_BODY),
+    };
+    let mut first_buf = Vec::new();
+    write_packet(&mut first_buf, &first).unwrap();
+    assert_eq!(&buf[..first_buf.len()], first_buf.as_slice());
+    reader = &reader[first_buf.len()..];
+    assert_eq!(read_packet(&mut reader).unwrap().body, "a");
+}
+
+#[test]
+fn password_matches_test() {
+    assert!(password_matches("secret", "secret"));
+    assert!(!password_matches("secreT", "secret"));
+    assert!(!password_matches("", "secret"));
+}
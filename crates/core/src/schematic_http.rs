@@ -0,0 +1,129 @@
+//! A small embedded HTTP server for downloading schematics. `/schem download` hands out one-time
+//! links which are served from here, so that schematics can be taken off the server without
+//! access to its file system.
+
+use crate::config::{SchematicHttpConfig, CONFIG};
+use crate::tcp_server::{self, HttpRequest, Limits};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use rustc_hash::FxHashMap;
+use std::fs;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// How long a client has to send its request before the connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many connections are handled at once
+const MAX_CONNECTIONS: usize = 32;
+
+struct DownloadLink {
+    /// The path of the schematic relative to the schematic folder
+    file_name: String,
+    expires: Instant,
+}
+
+static DOWNLOAD_LINKS: Lazy<Mutex<FxHashMap<String, DownloadLink>>> = Lazy::new(Default::default);
+
+/// Starts the HTTP server on its own thread
+pub fn start(config: &SchematicHttpConfig) {
+    let listener = match TcpListener::bind(&config.bind_address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(
+                "Failed to bind schematic http server to {}: {}",
+                config.bind_address, err
+            );
+            return;
+        }
+    };
+    info!("Schematic http server listening on {}", config.bind_address);
+
+    let limits = Limits {
+        max_connections: MAX_CONNECTIONS,
+        read_timeout: REQUEST_TIMEOUT,
+    };
+    tcp_server::serve("Schematic HTTP", listener, limits, handle_connection);
+}
+
+/// Creates a link which can be used once to download the schematic. Returns `None` if the
+/// server isn't enabled.
+pub fn create_download_link(file_name: &str) -> Option<String> {
+    let config = CONFIG.schematic_http.as_ref()?;
+    let token = format!("{:032x}", rand::rng().random::<u128>());
+
+    let mut links = DOWNLOAD_LINKS.lock().unwrap();
+    let now = Instant::now();
+    links.retain(|_, link| link.expires > now);
+    links.insert(
+        token.clone(),
+        DownloadLink {
+            file_name: file_name.to_owned(),
+            expires: now + Duration::from_secs(config.link_lifetime),
+        },
+    );
+
+    Some(format!(
+        "{}/schematics/{}",
+        config.public_url.trim_end_matches('/'),
+        token
+    ))
+}
+
+/// Takes the link out of the list so that it can't be used again
+fn redeem_token(token: &str) -> Option<String> {
+    let link = DOWNLOAD_LINKS.lock().unwrap().remove(token)?;
+    (link.expires > Instant::now()).then_some(link.file_name)
+}
+
+fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let request = match HttpRequest::read(&stream)? {
+        Ok(request) => request,
+        Err(status) => return respond(&mut stream, status, &[], b""),
+    };
+    if request.method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", &[], b"");
+    }
+
+    let file_name = request
+        .path
+        .strip_prefix("/schematics/")
+        .and_then(redeem_token);
+    let Some(file_name) = file_name else {
+        return respond(&mut stream, "404 Not Found", &[], b"");
+    };
+    match fs::read(format!("./schems/{}", file_name)) {
+        Ok(data) => {
+            let name = file_name.rsplit('/').next().unwrap_or(&file_name);
+            let disposition = format!("attachment; filename=\"{}\"", name);
+            let headers = [
+                ("Content-Type", "application/octet-stream"),
+                ("Content-Disposition", disposition.as_str()),
+            ];
+            respond(&mut stream, "200 OK", &headers, &data)
+        }
+        Err(_) => respond(&mut stream, "404 Not Found", &[], b""),
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        response += &format!("{}: {}\r\n", name, value);
+    }
+    response += "\r\n";
+    stream.write_all(response.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
@@ -1,42 +1,70 @@
-use crate::config::CONFIG;
-use crate::player::{Gamemode, PacketSender, Player};
+use crate::admin_api::{AdminRequest, AdminResponse};
+use crate::config::{BungeeCordConfig, CONFIG};
+use crate::discord::{escape_markdown, DiscordBridge};
+use crate::permissions::groups;
+use crate::player::{Gamemode, Notification, PacketSender, Player};
 use crate::plot::commands::DECLARE_COMMANDS;
-use crate::plot::{self, database, Plot, PLOT_BLOCK_HEIGHT};
+use crate::plot::worldedit::WorldEditClipboard;
+use crate::plot::{self, database, Plot, PLOT_BLOCK_HEIGHT, PLOT_MIN_Y};
+use crate::profile::{PlayerProfile, ProfileProperty};
+use crate::server_list::ServerList;
 use crate::utils::HyphenatedUUID;
-use crate::{permissions, utils};
+use crate::webhooks::{self, WebhookEvent};
+use crate::{admin_api, permissions, plugins, rcon, schematic_http, utils};
+use anyhow::{anyhow, bail};
 use backtrace::Backtrace;
 use bus::Bus;
 use hmac::{Hmac, Mac};
+use mchprs_network::encryption::{self, ServerKey};
 use mchprs_network::packets::clientbound::{
-    CConfigurationPluginMessage, CDisconnectLogin, CFinishConfiguration, CGameEvent,
-    CGameEventType, CLogin, CLoginPluginRequest, CLoginSuccess, CPlayerInfoActions,
-    CPlayerInfoAddPlayer, CPlayerInfoUpdate, CPlayerInfoUpdatePlayer, CPong, CRegistryBiome,
-    CRegistryBiomeEffects, CRegistryData, CRegistryDataCodec, CRegistryDimensionType, CResponse,
-    CSetCompression, CSetContainerContent, CSetHeldItem, CSynchronizePlayerPosition,
-    ClientBoundPacket, UpdateTime,
+    CAddResourcePack, CConfigurationPluginMessage, CDisconnectLogin, CEncryptionRequest,
+    CFinishConfiguration, CGameEvent, CGameEventType, CLogin, CLoginPluginRequest, CLoginSuccess,
+    CPlayerInfoActions, CPlayerInfoAddPlayer, CPlayerInfoUpdate, CPlayerInfoUpdatePlayer, CPong,
+    CRegistryBiome, CRegistryBiomeEffects, CRegistryData, CRegistryDataCodec,
+    CRegistryDimensionType, CResponse, CSetCompression, CSetContainerContent, CSetHeldItem,
+    CSynchronizePlayerPosition, ClientBoundPacket, UpdateTime,
 };
 use mchprs_network::packets::serverbound::{
-    SAcknowledgeFinishConfiguration, SHandshake, SLoginAcknowledged, SLoginPluginResponse,
-    SLoginStart, SPing, SRequest, ServerBoundPacketHandler, VelocityResponseData,
+    SAcknowledgeFinishConfiguration, SEncryptionResponse, SHandshake, SLoginAcknowledged,
+    SLoginPluginResponse, SLoginStart, SPing, SRequest, ServerBoundPacketHandler,
+    VelocityResponseData,
 };
-use mchprs_network::packets::{PacketEncoderExt, PlayerProperty, SlotData, COMPRESSION_THRESHOLD};
-use mchprs_network::{NetworkServer, NetworkState, PlayerPacketSender};
-use mchprs_text::TextComponent;
+use mchprs_network::packets::{PacketCompression, PacketEncoderExt, PlayerProperty, SlotData};
+use mchprs_network::protocol::{MappedProtocol, Protocols};
+use mchprs_network::proxy_protocol::ProxyProtocol;
+use mchprs_network::throttle::Throttle;
+use mchprs_network::{Listener, NetworkServer, NetworkState, PlayerPacketSender};
+use mchprs_plugin::events::{Chat, CompileFinish, PlayerJoin, PlayerLeave};
+use mchprs_plugin::Event;
+use mchprs_text::{ClickEvent, ColorCode, TextComponent, TextComponentBuilder};
 use mchprs_utils::map;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::Sha256;
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::Cursor;
+use std::io::{BufReader, Cursor};
+use std::net::IpAddr;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
 use tracing::{debug, error, info, warn};
 
 pub const MC_VERSION: &str = "1.20.4";
 pub const MC_DATA_VERSION: i32 = 3700;
 pub const PROTOCOL_VERSION: i32 = 765;
+/// The Velocity forwarding version that is requested from the proxy
+const VELOCITY_MODERN_DEFAULT: u8 = 1;
+/// The number of players on the server, for the plots to show in the tab list
+pub static ONLINE_PLAYER_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// The names of the players on the server, for the plots to suggest in commands
+pub static ONLINE_PLAYER_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// How long looked up skins are kept before they are looked up again
+const SKIN_CACHE_DURATION: Duration = Duration::from_secs(60 * 60);
 
 /// `Message` gets send from a plot thread to the server thread.
 #[derive(Debug)]
@@ -44,6 +72,13 @@ pub enum Message {
     /// This message is sent to the server thread when a player sends a chat message,
     /// It contains the uuid and name of the player and the raw message the player sent.
     ChatInfo(u128, String, String),
+    /// This message is sent to the server thread when a player sends a message in the staff
+    /// channel. It contains the uuid and name of the player and the raw message.
+    StaffChatInfo(u128, String, String),
+    /// This message is sent to the server thread when a player runs /msg or /reply. It contains
+    /// the uuid and name of the sending player, the name of the receiving player, the message,
+    /// and a packet sender to report back to the sending player.
+    PrivateChat(u128, String, String, String, PlayerPacketSender),
     /// This message is sent to the server thread when a player joins the server.
     PlayerJoined(Player),
     /// This message is sent to the server thread when a player leaves the server.
@@ -56,10 +91,54 @@ pub enum Message {
     PlayerUpdateGamemode(u128, Gamemode),
     /// This message is sent to the server thread when a plot unloads itself.
     PlotUnload(i32, i32),
+    /// This message is sent to the server thread when a plot crashed and should be restarted
+    /// from its last save. It contains the players that were on the plot.
+    PlotRestart(i32, i32, Vec<Player>),
+    /// This message is sent to the server thread when admins should be told about something,
+    /// like a plot crashing.
+    AdminAlert(String),
+    /// This message is sent to the server thread when the session server answered whether a
+    /// player logging in in online mode owns their account. It contains the id of the connection
+    /// and the profile of the player if they do.
+    LoginVerified(u32, Option<PlayerProfile>),
+    /// This message is sent to the server thread when the skin of a player logging in in offline
+    /// mode was looked up. It contains the id of the connection, the username and the properties
+    /// of the account, which are empty if it wasn't found.
+    SkinResolved(u32, String, Vec<PlayerProperty>),
     /// This message is sent to the server thread when a player runs /whitelist add.
     WhitelistAdd(u128, String, PlayerPacketSender),
     /// This message is sent to the server thread when a player runs /whitelist remove.
     WhitelistRemove(u128, PlayerPacketSender),
+    /// This message is sent to the server thread when a player runs //clipboard share.
+    /// It contains the name of the sharing player, the name of the receiving player,
+    /// the clipboard, and a packet sender to report back to the sharing player.
+    ShareClipboard(String, String, WorldEditClipboard, PlayerPacketSender),
+    /// This message is sent to the server thread when a player runs /transfer. It contains the
+    /// name of the player to transfer, or `None` for everyone, and the address to send them to.
+    Transfer(Option<String>, String, u16),
+    /// This message is sent to the server thread when a player runs /title or /playsound, or a
+    /// plugin sends a message. It contains the name of the player to notify, or `None` for
+    /// everyone.
+    Notify(Option<String>, Notification),
+    /// This message is sent to the server thread when a player runs /restart.
+    Restart,
+    /// This message is sent to the server thread when a player changes permissions with /perm.
+    ReloadPermissions,
+    /// This message is sent to the server thread when a command is run from the console, like
+    /// over RCON. It contains the command and where to send its output.
+    ConsoleCommand(String, Sender<String>),
+    /// This message is sent to the server thread when the admin api is asked for something. It
+    /// contains the request and where to send the response.
+    AdminRequest(AdminRequest, Sender<AdminResponse>),
+    /// This message is sent to the server thread when someone wrote in the Discord channel. It
+    /// contains the name of the author and the message.
+    DiscordChat(String, String),
+    /// This message is sent to the server thread when a plot crashed and telemetry is enabled. It
+    /// contains the anonymized crash report.
+    SubmitCrashReport(serde_json::Value),
+    /// This message is sent to the server thread when a plot has something to send to webhooks,
+    /// like a finished compile.
+    Webhook(WebhookEvent),
     /// This message is sent to the server thread when a player runs /stop.
     Shutdown,
 }
@@ -72,6 +151,9 @@ pub enum BroadcastMessage {
     /// This message is broadcasted for chat messages. It contains the uuid of the player and
     /// the raw json data to send to the clients.
     Chat(u128, Vec<TextComponent>),
+    /// This message is broadcasted for staff chat messages. It is only shown to players with
+    /// the `mchprs.chat.staff` permission.
+    StaffChat(u128, Vec<TextComponent>),
     /// This message is broadcasted when a player joins the server. It is used to update
     /// the tab-list on all connected clients.
     PlayerJoinedInfo(PlayerJoinInfo),
@@ -80,17 +162,45 @@ pub enum BroadcastMessage {
     PlayerLeft(u128),
     /// This message is broadcasted when a player changes their gamemode,
     PlayerUpdateGamemode(u128, Gamemode),
+    /// This message is broadcasted to tell admins about something. It is only shown to players
+    /// with the `mchprs.alerts` permission.
+    AdminAlert(String),
+    /// This message is broadcasted to send players to another server. It contains the name of
+    /// the player to transfer, or `None` for everyone, and the address to send them to.
+    Transfer(Option<String>, String, u16),
+    /// This message is broadcasted to show a title or play a sound to players. It contains the
+    /// name of the player to notify, or `None` for everyone.
+    Notify(Option<String>, Notification),
+    /// This message is broadcasted when permissions changed, so that every plot loads the
+    /// permissions of its players again.
+    ReloadPermissions,
+    /// This message is broadcasted when the plots should save, like when `save` is run from the
+    /// console.
+    Save,
+    /// This message is broadcasted to disconnect a player from the server. It contains the name
+    /// of the player and the reason.
+    Kick(String, String),
     /// This message is broadcasted when the server is stopping, either through the stop
     /// command or through the ctrl+c handler.
     Shutdown,
 }
 
 /// `PrivMessage` gets send from the server thread directly to a plot thread.
-/// This only happens when a player is getting transfered to a plot.
+/// This happens when a player is getting transfered to a plot, or when a
+/// clipboard is shared with a player on that plot.
 #[derive(Debug)]
 pub enum PrivMessage {
     PlayerEnterPlot(Player),
     PlayerTeleportOther(Player, String),
+    /// Contains the uuid of the receiving player, the name of the sharing player,
+    /// and the shared clipboard.
+    ClipboardShared(u128, String, WorldEditClipboard),
+    /// Contains the uuid of the receiving player, the uuid and name of the sending player,
+    /// and the message.
+    PrivateChat(u128, u128, String, String),
+    /// Compiles the plot with redpiler, with the compiler flags or the defaults of the plot if
+    /// they are empty.
+    Compile(String),
 }
 
 /// This is the data that gets sent in the `PlayerJoinedInfo` broadcast message.
@@ -124,6 +234,13 @@ struct WhitelistEntry {
     name: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct BanEntry {
+    uuid: HyphenatedUUID,
+    name: String,
+    reason: String,
+}
+
 /// This represents a minecraft server
 pub struct MinecraftServer {
     network: NetworkServer,
@@ -133,6 +250,16 @@ pub struct MinecraftServer {
     online_players: FxHashMap<u128, PlayerListEntry>,
     running_plots: Vec<PlotListEntry>,
     whitelist: Option<Vec<WhitelistEntry>>,
+    bans: Vec<BanEntry>,
+    last_expiry_check: Instant,
+    /// The key players are verified with in online mode
+    server_key: Option<ServerKey>,
+    /// The skins looked up in offline mode by lowercase username, with the time they were looked
+    /// up at
+    skin_cache: HashMap<String, (Instant, Vec<PlayerProperty>)>,
+    server_list: ServerList,
+    async_rt: Runtime,
+    discord: Option<DiscordBridge>,
 }
 
 impl MinecraftServer {
@@ -141,6 +268,7 @@ impl MinecraftServer {
         std::panic::set_hook(Box::new(|panic_info| {
             let backtrace = Backtrace::new();
             error!("plot {}\n{:?}", panic_info, backtrace);
+            plot::watchdog::record_panic(panic_info, backtrace);
         }));
 
         info!("Starting server...");
@@ -148,13 +276,12 @@ impl MinecraftServer {
 
         // Create world folders if they don't exist yet
         fs::create_dir_all("./world/players").unwrap();
+        fs::create_dir_all("./world/worldedit_history").unwrap();
         fs::create_dir_all("./world/plots").unwrap();
         fs::create_dir_all("./schems").unwrap();
 
         plot::database::init();
 
-        let bind_addr = CONFIG.bind_address.clone();
-
         // Create thread messaging structs
         let (plot_tx, server_rx) = mpsc::channel();
         let bus = Bus::new(100);
@@ -175,20 +302,58 @@ impl MinecraftServer {
             .unwrap_or_default()
         });
 
-        if let Some(permissions_config) = &CONFIG.luckperms {
-            permissions::init(permissions_config.clone()).unwrap();
+        let bans = match fs::read_to_string("banned-players.json") {
+            Ok(bans) => serde_json::from_str(&bans).expect("Failed to parse banned-players.json"),
+            Err(_) => Vec::new(),
+        };
+
+        permissions::init().unwrap();
+        plugins::load();
+
+        if let Some(schematic_http_config) = &CONFIG.schematic_http {
+            schematic_http::start(schematic_http_config);
+        }
+
+        if let Some(rcon_config) = &CONFIG.rcon {
+            rcon::start(rcon_config, plot_tx.clone());
+        }
+
+        if let Some(admin_api_config) = &CONFIG.admin_api {
+            admin_api::start(admin_api_config, plot_tx.clone());
         }
 
         // Create server struct
         let mut server = MinecraftServer {
-            network: NetworkServer::new(bind_addr),
+            network: NetworkServer::new(listeners(), load_protocols(), throttle(), compression()),
             broadcaster: bus,
             receiver: server_rx,
             plot_sender: plot_tx,
             online_players: FxHashMap::default(),
             running_plots: Vec::new(),
             whitelist,
+            bans,
+            last_expiry_check: Instant::now(),
+            server_key: online_mode().then(ServerKey::generate),
+            skin_cache: HashMap::new(),
+            server_list: ServerList::new(),
+            async_rt: Runtime::new().unwrap(),
+            discord: None,
         };
+        server.discord = CONFIG.discord.as_ref().map(|discord_config| {
+            DiscordBridge::start(discord_config, &server.async_rt, server.plot_sender.clone())
+        });
+        let sender = server.plot_sender.clone();
+        plugins::events().subscribe(move |finish: &CompileFinish| {
+            let _ = sender.send(Message::Webhook(WebhookEvent::CompileFinished {
+                plot_x: finish.x,
+                plot_z: finish.z,
+                duration_ms: finish.duration_ms,
+                warnings: finish.warnings,
+                optimize: finish.optimize,
+                io_only: finish.io_only,
+            }));
+        });
+        plugins::enable_all(server.plot_sender.clone());
 
         // Load the spawn area plot on server start
         // This plot should be always active
@@ -208,7 +373,11 @@ impl MinecraftServer {
             priv_message_sender: spawn_tx,
         });
 
+        server.expire_inactive_plots();
+        plot::watchdog::start(server.plot_sender.clone());
+
         info!("Done! Start took {:?}", start_time.elapsed());
+        server.fire_webhook(WebhookEvent::ServerStarted);
 
         loop {
             server.update();
@@ -225,6 +394,22 @@ impl MinecraftServer {
         }
     }
 
+    fn expire_inactive_plots(&mut self) {
+        self.last_expiry_check = Instant::now();
+        let Some(config) = &CONFIG.plot_expiry else {
+            return;
+        };
+        // Players who have been online the whole time are still active
+        for uuid in self.online_players.keys() {
+            database::update_last_seen(&format!("{:032x}", uuid));
+        }
+        plot::expiry::expire_inactive_plots(config, |plot_x, plot_z| {
+            self.running_plots
+                .iter()
+                .any(|p| p.plot_x == plot_x && p.plot_z == plot_z)
+        });
+    }
+
     /// Removes the plot entry from the `running_plots` list
     fn handle_plot_unload(&mut self, plot_x: i32, plot_z: i32) {
         let index = self
@@ -236,23 +421,58 @@ impl MinecraftServer {
         }
     }
 
+    /// Loads the crashed plot again from its last save and sends its players back to it
+    fn handle_plot_restart(&mut self, plot_x: i32, plot_z: i32, players: Vec<Player>) {
+        self.handle_plot_unload(plot_x, plot_z);
+        let (priv_tx, priv_rx) = mpsc::channel();
+        Plot::load_and_run(
+            plot_x,
+            plot_z,
+            self.broadcaster.add_rx(),
+            self.plot_sender.clone(),
+            priv_rx,
+            // Only the spawn plot is always running
+            plot_x == 0 && plot_z == 0,
+            None,
+        );
+        self.running_plots.push(PlotListEntry {
+            plot_x,
+            plot_z,
+            priv_message_sender: priv_tx,
+        });
+        for player in players {
+            self.send_player_to_plot(player, false);
+        }
+    }
+
     fn graceful_shutdown(&mut self) {
         info!("Commencing graceful shutdown...");
+        plugins::disable_all();
         self.broadcaster.broadcast(BroadcastMessage::Shutdown);
         // Wait for all plots to save and unload
         while !self.running_plots.is_empty() {
             while let Ok(message) = self.receiver.try_recv() {
-                if let Message::PlotUnload(plot_x, plot_z) = message {
-                    self.handle_plot_unload(plot_x, plot_z);
+                match message {
+                    Message::PlotUnload(plot_x, plot_z)
+                    | Message::PlotRestart(plot_x, plot_z, _) => {
+                        self.handle_plot_unload(plot_x, plot_z);
+                    }
+                    _ => {}
                 }
                 std::thread::sleep(Duration::from_millis(2));
             }
         }
+        // Plugins can still store things while plots unload
+        plugins::save_storage();
 
         if let Some(whitelist) = &self.whitelist {
             fs::write("whitelist.json", serde_json::to_string(whitelist).unwrap()).unwrap();
         }
 
+        // Unlike other events, this has to be sent before the process exits
+        self.async_rt
+            .block_on(webhooks::fire(WebhookEvent::ServerStopped));
+
         std::process::exit(0);
     }
 
@@ -268,6 +488,7 @@ impl MinecraftServer {
                 gamemode: player.gamemode,
             };
             self.online_players.insert(player.uuid, player_list_entry);
+            self.update_online_players();
         } else {
             self.update_player_entry(player.uuid, plot_x, plot_z);
         }
@@ -335,6 +556,22 @@ impl MinecraftServer {
         .encode();
         player.client.send_packet(&join_game);
 
+        if let Some(resource_pack) = &CONFIG.resource_pack {
+            let prompt_message = (!resource_pack.prompt.is_empty()).then(|| TextComponent {
+                extra: TextComponent::from_legacy_text(&resource_pack.prompt),
+                ..Default::default()
+            });
+            let add_resource_pack = CAddResourcePack {
+                uuid: resource_pack_uuid(&resource_pack.url),
+                url: resource_pack.url.clone(),
+                hash: resource_pack.hash.to_lowercase(),
+                forced: resource_pack.forced,
+                prompt_message,
+            }
+            .encode();
+            player.client.send_packet(&add_resource_pack);
+        }
+
         let entity_metadata = player.metadata_packet().encode();
         player.client.send_packet(&entity_metadata);
 
@@ -445,7 +682,7 @@ impl MinecraftServer {
                 let plugin_message = CLoginPluginRequest {
                     channel: "velocity:player_info".to_string(),
                     message_id,
-                    data: vec![1], // MODERN_DEFAULT
+                    data: vec![VELOCITY_MODERN_DEFAULT],
                 }
                 .encode();
                 clients[client_idx].send_packet(&plugin_message);
@@ -453,19 +690,158 @@ impl MinecraftServer {
             }
         }
 
+        if let Some(server_key) = &self.server_key {
+            let verify_token: [u8; 4] = rand::random();
+            clients[client_idx].verify_token = Some(verify_token.to_vec());
+            let encryption_request = CEncryptionRequest {
+                server_id: String::new(),
+                public_key: server_key.public_key().to_vec(),
+                verify_token: verify_token.to_vec(),
+            }
+            .encode();
+            clients[client_idx].send_packet(&encryption_request);
+            return;
+        }
+
+        // Players forwarded by BungeeCord already have their skin
+        if CONFIG.resolve_skins && clients[client_idx].properties.is_empty() {
+            match self.skin_cache.get(&username.to_lowercase()) {
+                Some((resolved_at, properties)) if resolved_at.elapsed() < SKIN_CACHE_DURATION => {
+                    clients[client_idx].properties = properties.clone();
+                }
+                _ => {
+                    self.resolve_skin(client_idx, username);
+                    return;
+                }
+            }
+        }
+
+        self.complete_player_login(client_idx);
+    }
+
+    /// Looks up the skin of a player logging in in offline mode. The login is completed once the
+    /// lookup is done.
+    fn resolve_skin(&mut self, client_idx: usize, username: String) {
+        let client_id = self.network.handshaking_clients[client_idx].id();
+        let sender = self.plot_sender.clone();
+        self.async_rt.spawn(async move {
+            let properties = match PlayerProfile::lookup_textures(&username).await {
+                Ok(properties) => properties.into_iter().map(PlayerProperty::from).collect(),
+                Err(err) => {
+                    debug!("Failed to look up the skin of {}: {:?}", username, err);
+                    Vec::new()
+                }
+            };
+            let _ = sender.send(Message::SkinResolved(client_id, username, properties));
+        });
+    }
+
+    fn handle_skin_resolved(
+        &mut self,
+        client_id: u32,
+        username: String,
+        properties: Vec<PlayerProperty>,
+    ) {
+        // Failed lookups are cached too so that the API isn't asked again on every join
+        self.skin_cache.insert(
+            username.to_lowercase(),
+            (Instant::now(), properties.clone()),
+        );
+        let clients = &mut self.network.handshaking_clients;
+        let Some(client_idx) = clients.iter().position(|client| client.id() == client_id) else {
+            return;
+        };
+        clients[client_idx].properties = properties;
         self.complete_player_login(client_idx);
     }
 
+    fn disconnect_login(&mut self, client_idx: usize, reason: &str) {
+        let client = &mut self.network.handshaking_clients[client_idx];
+        let disconnect = CDisconnectLogin {
+            reason: json!({ "text": reason }).to_string(),
+        }
+        .encode();
+        client.send_packet(&disconnect);
+        client.close_connection();
+    }
+
+    /// Handles the answer of the session server for a player logging in in online mode
+    fn handle_login_verified(&mut self, client_id: u32, profile: Option<PlayerProfile>) {
+        let clients = &mut self.network.handshaking_clients;
+        // The player might have disconnected while they were being verified
+        let Some(client_idx) = clients.iter().position(|client| client.id() == client_id) else {
+            return;
+        };
+        let Some(profile) = profile else {
+            self.disconnect_login(client_idx, "Failed to verify username!");
+            return;
+        };
+        let client = &mut clients[client_idx];
+        client.uuid = Some(profile.uuid.0);
+        client.username = Some(profile.username);
+        client.properties = profile
+            .properties
+            .into_iter()
+            .map(PlayerProperty::from)
+            .collect();
+        self.complete_player_login(client_idx);
+    }
+
+    /// Takes the uuid, address and skin that BungeeCord forwarded in the handshake. Players that
+    /// didn't connect through an allowed proxy are disconnected.
+    fn handle_bungeecord_handshake(
+        &mut self,
+        config: &BungeeCordConfig,
+        server_address: &str,
+        client_idx: usize,
+    ) {
+        let client = &mut self.network.handshaking_clients[client_idx];
+        let from_proxy = config.allowed_proxies.is_empty()
+            || client
+                .address
+                .is_some_and(|address| config.allowed_proxies.contains(&address));
+        let forwarding = if from_proxy {
+            parse_bungeecord_forwarding(server_address)
+        } else {
+            None
+        };
+        let Some(forwarding) = forwarding else {
+            warn!(
+                "{} connected without going through the proxy",
+                client
+                    .address
+                    .map(|address| address.to_string())
+                    .unwrap_or_default()
+            );
+            let disconnect = CDisconnectLogin {
+                reason: json!({
+                    "text": "This server can only be joined through its proxy."
+                })
+                .to_string(),
+            }
+            .encode();
+            client.send_packet(&disconnect);
+            client.close_connection();
+            return;
+        };
+        client.uuid = Some(forwarding.uuid);
+        client.address = Some(forwarding.address);
+        client.properties = forwarding.properties;
+    }
+
     fn complete_player_login(&mut self, client_idx: usize) {
         let clients = &mut self.network.handshaking_clients;
         let username = clients[client_idx].username.clone().unwrap();
 
-        let set_compression = CSetCompression {
-            threshold: COMPRESSION_THRESHOLD as i32,
+        // A negative threshold leaves compression off
+        if CONFIG.compression_threshold >= 0 {
+            let set_compression = CSetCompression {
+                threshold: CONFIG.compression_threshold as i32,
+            }
+            .encode();
+            clients[client_idx].send_packet(&set_compression);
+            clients[client_idx].set_compressed(true);
         }
-        .encode();
-        clients[client_idx].send_packet(&set_compression);
-        clients[client_idx].set_compressed(true);
 
         if let Some(whitelist) = &self.whitelist {
             // uuid will only be present if velocity is enabled in config
@@ -496,6 +872,19 @@ impl MinecraftServer {
             }
         };
 
+        if let Some(ban) = self.bans.iter().find(|ban| ban.uuid.0 == uuid) {
+            let disconnect = CDisconnectLogin {
+                reason: json!({
+                    "text": format!("You are banned from this server: {}", ban.reason)
+                })
+                .to_string(),
+            }
+            .encode();
+            clients[client_idx].send_packet(&disconnect);
+            clients[client_idx].close_connection();
+            return;
+        }
+
         let login_success = CLoginSuccess {
             uuid,
             username,
@@ -508,7 +897,14 @@ impl MinecraftServer {
     fn handle_message(&mut self, message: Message) {
         match message {
             Message::PlayerJoined(player) => {
-                info!("{} joined the game", player.username);
+                match player.client.address() {
+                    Some(address) => info!("{} joined the game from {}", player.username, address),
+                    None => info!("{} joined the game", player.username),
+                }
+                self.send_to_discord(format!(
+                    "**{}** joined the game",
+                    escape_markdown(&player.username)
+                ));
                 // Send player info to plots
                 let player_join_info = PlayerJoinInfo {
                     username: player.username.clone(),
@@ -517,6 +913,13 @@ impl MinecraftServer {
                     properties: player.properties.clone(),
                 };
                 database::ensure_user(&format!("{:032x}", player.uuid), &player.username);
+                plugins::dispatch(
+                    &Event::PlayerJoin(PlayerJoin {
+                        username: player.username.clone(),
+                        uuid: player.uuid,
+                    }),
+                    None,
+                );
                 self.broadcaster
                     .broadcast(BroadcastMessage::PlayerJoinedInfo(player_join_info));
                 self.send_player_to_plot(player, true);
@@ -524,13 +927,49 @@ impl MinecraftServer {
             Message::PlayerLeft(uuid) => {
                 if let Some((_, player)) = self.online_players.remove_entry(&uuid) {
                     info!("{} left the game", player.username);
+                    self.send_to_discord(format!(
+                        "**{}** left the game",
+                        escape_markdown(&player.username)
+                    ));
+                    plugins::dispatch(
+                        &Event::PlayerLeave(PlayerLeave {
+                            username: player.username,
+                            uuid,
+                        }),
+                        None,
+                    );
                 }
+                self.update_online_players();
+                database::update_last_seen(&format!("{:032x}", uuid));
                 self.broadcaster
                     .broadcast(BroadcastMessage::PlayerLeft(uuid));
             }
             Message::PlotUnload(plot_x, plot_z) => self.handle_plot_unload(plot_x, plot_z),
+            Message::PlotRestart(plot_x, plot_z, players) => {
+                self.handle_plot_restart(plot_x, plot_z, players)
+            }
+            Message::AdminAlert(alert) => {
+                self.send_to_discord(format!(":warning: {}", escape_markdown(&alert)));
+                self.fire_webhook(WebhookEvent::WatchdogAlert {
+                    alert: alert.clone(),
+                });
+                self.broadcaster
+                    .broadcast(BroadcastMessage::AdminAlert(alert));
+            }
             Message::ChatInfo(uuid, username, message) => {
                 info!("<{}> {}", username, message);
+                self.send_to_discord(format!(
+                    "**{}**: {}",
+                    escape_markdown(&username),
+                    escape_markdown(&message)
+                ));
+                plugins::dispatch(
+                    &Event::Chat(Chat {
+                        username: username.clone(),
+                        message: message.clone(),
+                    }),
+                    None,
+                );
                 self.broadcaster.broadcast(BroadcastMessage::Chat(
                     uuid,
                     TextComponent::from_legacy_text(
@@ -541,9 +980,120 @@ impl MinecraftServer {
                     ),
                 ));
             }
+            Message::StaffChatInfo(uuid, username, message) => {
+                info!("[Staff] <{}> {}", username, message);
+                self.broadcaster.broadcast(BroadcastMessage::StaffChat(
+                    uuid,
+                    TextComponent::from_legacy_text(
+                        &CONFIG
+                            .staff_chat_format
+                            .replace("{username}", &username)
+                            .replace("{message}", &message),
+                    ),
+                ));
+            }
+            Message::PrivateChat(uuid, username, other_username, message, sender) => {
+                let Some((&other_uuid, other_player)) = self
+                    .online_players
+                    .iter()
+                    .find(|(_, p)| p.username.eq_ignore_ascii_case(&other_username))
+                else {
+                    sender.send_error_message("Player not found!");
+                    return;
+                };
+                let Some(plot_list_entry) = self
+                    .running_plots
+                    .iter()
+                    .find(|p| p.plot_x == other_player.plot_x && p.plot_z == other_player.plot_z)
+                else {
+                    sender.send_error_message("Their plot wasn't loaded. How did this happen??");
+                    return;
+                };
+                info!("[{} -> {}] {}", username, other_player.username, message);
+                sender.send_raw_system_message(private_chat_message(
+                    &format!("me -> {}", other_player.username),
+                    &other_player.username,
+                    &message,
+                ));
+                let _ = plot_list_entry
+                    .priv_message_sender
+                    .send(PrivMessage::PrivateChat(
+                        other_uuid, uuid, username, message,
+                    ));
+            }
             Message::PlayerLeavePlot(player) => {
                 self.send_player_to_plot(player, false);
             }
+            Message::Transfer(username, host, port) => {
+                self.broadcaster
+                    .broadcast(BroadcastMessage::Transfer(username, host, port));
+            }
+            Message::Notify(username, notification) => {
+                self.broadcaster
+                    .broadcast(BroadcastMessage::Notify(username, notification));
+            }
+            Message::Restart => {
+                let Some((host, port)) = parse_address(&CONFIG.restart_fallback_address) else {
+                    error!("Can't restart without a valid `restart_fallback_address`");
+                    return;
+                };
+                info!("Transferring all players to {}:{}", host, port);
+                self.broadcaster
+                    .broadcast(BroadcastMessage::Transfer(None, host, port));
+                self.graceful_shutdown();
+            }
+            Message::ReloadPermissions => {
+                self.broadcaster
+                    .broadcast(BroadcastMessage::ReloadPermissions);
+            }
+            Message::AdminRequest(request, reply) => {
+                let _ = reply.send(self.handle_admin_request(request));
+            }
+            Message::ConsoleCommand(command, reply) => match command.trim() {
+                // These don't return, so the output is sent first
+                "stop" => {
+                    let _ = reply.send("Stopping the server.".to_string());
+                    self.graceful_shutdown();
+                }
+                "restart" => {
+                    let _ = reply.send("Restarting the server.".to_string());
+                    self.handle_message(Message::Restart);
+                }
+                command => {
+                    let output = self.handle_console_command(command);
+                    let _ = reply.send(output);
+                }
+            },
+            Message::DiscordChat(username, message) => {
+                info!("[Discord] <{}> {}", username, message);
+                let format = &CONFIG.discord.as_ref().unwrap().chat_format;
+                self.broadcaster.broadcast(BroadcastMessage::Chat(
+                    0,
+                    TextComponent::from_legacy_text(
+                        &format
+                            .replace("{username}", &username)
+                            .replace("{message}", &message),
+                    ),
+                ));
+            }
+            Message::Webhook(event) => self.fire_webhook(event),
+            Message::SubmitCrashReport(report) => {
+                let Some(telemetry) = &CONFIG.telemetry else {
+                    return;
+                };
+                self.async_rt.spawn(async move {
+                    let result = reqwest::Client::new()
+                        .post(&telemetry.crash_report_url)
+                        .json(&report)
+                        .send()
+                        .await
+                        .and_then(|res| res.error_for_status());
+                    match result {
+                        Ok(_) => info!("Submitted crash report"),
+                        Err(err) => warn!("Failed to submit crash report: {}", err),
+                    }
+                });
+            }
             Message::Shutdown => {
                 self.graceful_shutdown();
             }
@@ -588,6 +1138,37 @@ impl MinecraftServer {
                 self.broadcaster
                     .broadcast(BroadcastMessage::PlayerUpdateGamemode(uuid, gamemode));
             }
+            Message::ShareClipboard(username, other_username, clipboard, sender) => {
+                let Some((&other_uuid, other_player)) = self
+                    .online_players
+                    .iter()
+                    .find(|(_, p)| p.username.eq_ignore_ascii_case(&other_username))
+                else {
+                    sender.send_error_message("Player not found!");
+                    return;
+                };
+                let Some(plot_list_entry) = self
+                    .running_plots
+                    .iter()
+                    .find(|p| p.plot_x == other_player.plot_x && p.plot_z == other_player.plot_z)
+                else {
+                    sender.send_error_message("Their plot wasn't loaded. How did this happen??");
+                    return;
+                };
+                let msg = format!("Your clipboard was shared with {}.", other_player.username);
+                let _ = plot_list_entry
+                    .priv_message_sender
+                    .send(PrivMessage::ClipboardShared(
+                        other_uuid, username, clipboard,
+                    ));
+                sender.send_system_message(&msg);
+            }
+            Message::LoginVerified(client_id, profile) => {
+                self.handle_login_verified(client_id, profile)
+            }
+            Message::SkinResolved(client_id, username, properties) => {
+                self.handle_skin_resolved(client_id, username, properties)
+            }
             Message::WhitelistAdd(uuid, username, sender) => {
                 if let Some(whitelist) = &mut self.whitelist {
                     let msg = format!("{} was sucessfully added to the whitelist.", &username);
@@ -629,10 +1210,319 @@ impl MinecraftServer {
         }
     }
 
+    fn fire_webhook(&self, event: WebhookEvent) {
+        if CONFIG.webhooks.is_some() {
+            self.async_rt.spawn(webhooks::fire(event));
+        }
+    }
+
+    /// Posts a message to the Discord channel if the bridge is enabled
+    fn send_to_discord(&self, content: String) {
+        if let Some(discord) = &self.discord {
+            discord.send(content);
+        }
+    }
+
+    /// Disconnects a player from the server. Returns their name, or an error if they aren't online.
+    fn kick_player(&mut self, username: &str, reason: &str) -> Result<String, String> {
+        let Some(player) = self
+            .online_players
+            .values()
+            .find(|p| p.username.eq_ignore_ascii_case(username))
+        else {
+            return Err("Player not found!".to_string());
+        };
+        let username = player.username.clone();
+        let reason = match reason {
+            "" => "Kicked by an operator".to_string(),
+            reason => reason.to_string(),
+        };
+        info!("Kicking {}: {}", username, reason);
+        self.broadcaster
+            .broadcast(BroadcastMessage::Kick(username.clone(), reason));
+        Ok(username)
+    }
+
+    /// Bans a player who is online or has joined before, and kicks them if they are online.
+    /// Returns their name.
+    fn ban_player(&mut self, username: &str, reason: &str) -> Result<String, String> {
+        let online = self
+            .online_players
+            .iter()
+            .find(|(_, p)| p.username.eq_ignore_ascii_case(username))
+            .map(|(&uuid, p)| (uuid, p.username.clone()));
+        let player = online.or_else(|| {
+            let uuid = database::get_user_uuid(username)?;
+            let name = database::get_cached_username(uuid.clone())?;
+            Some((u128::from_str_radix(&uuid, 16).ok()?, name))
+        });
+        let Some((uuid, username)) = player else {
+            return Err("That player has never joined.".to_string());
+        };
+        let reason = match reason {
+            "" => "Banned by an operator".to_string(),
+            reason => reason.to_string(),
+        };
+        info!("Banning {}: {}", username, reason);
+        self.bans.retain(|ban| ban.uuid.0 != uuid);
+        self.bans.push(BanEntry {
+            uuid: HyphenatedUUID(uuid),
+            name: username.clone(),
+            reason: reason.clone(),
+        });
+        self.save_bans();
+        if self.online_players.contains_key(&uuid) {
+            self.broadcaster.broadcast(BroadcastMessage::Kick(
+                username.clone(),
+                format!("You are banned from this server: {}", reason),
+            ));
+        }
+        Ok(username)
+    }
+
+    /// Lets a banned player join again. Returns their name.
+    fn unban_player(&mut self, username: &str) -> Result<String, String> {
+        let Some(idx) = self
+            .bans
+            .iter()
+            .position(|ban| ban.name.eq_ignore_ascii_case(username))
+        else {
+            return Err("That player is not banned.".to_string());
+        };
+        let ban = self.bans.remove(idx);
+        info!("Unbanning {}", ban.name);
+        self.save_bans();
+        Ok(ban.name)
+    }
+
+    fn save_bans(&self) {
+        let bans = serde_json::to_string_pretty(&self.bans).unwrap();
+        if let Err(err) = fs::write("banned-players.json", bans) {
+            error!("Failed to save banned-players.json: {}", err);
+        }
+    }
+
+    fn handle_admin_request(&mut self, request: AdminRequest) -> AdminResponse {
+        let response = match request {
+            AdminRequest::Players => {
+                let players: Vec<_> = self
+                    .online_players
+                    .iter()
+                    .map(|(&uuid, player)| {
+                        json!({
+                            "name": player.username,
+                            "uuid": HyphenatedUUID(uuid).to_string(),
+                            "plot": [player.plot_x, player.plot_z],
+                        })
+                    })
+                    .collect();
+                json!(players)
+            }
+            AdminRequest::Plots => {
+                let threads = plot::threads::thread_infos();
+                let mut plots: Vec<(i32, i32, Option<String>)> = database::get_claimed_plots()
+                    .into_iter()
+                    .map(|(x, z, owner)| (x, z, Some(owner)))
+                    .collect();
+                for plot in &self.running_plots {
+                    if !plots
+                        .iter()
+                        .any(|&(x, z, _)| (x, z) == (plot.plot_x, plot.plot_z))
+                    {
+                        plots.push((plot.plot_x, plot.plot_z, None));
+                    }
+                }
+                let plots: Vec<_> = plots
+                    .into_iter()
+                    .map(|(x, z, owner)| {
+                        let running = self
+                            .running_plots
+                            .iter()
+                            .any(|p| (p.plot_x, p.plot_z) == (x, z));
+                        let players: Vec<&str> = self
+                            .online_players
+                            .values()
+                            .filter(|p| (p.plot_x, p.plot_z) == (x, z))
+                            .map(|p| p.username.as_str())
+                            .collect();
+                        let performance = threads.iter().find(|t| (t.x, t.z) == (x, z)).map(|t| {
+                            json!({
+                                "rtps": t.tps.to_string(),
+                                "utilization": t.utilization,
+                                "core": t.core,
+                            })
+                        });
+                        json!({
+                            "x": x,
+                            "z": z,
+                            "owner": owner,
+                            "running": running,
+                            "players": players,
+                            "performance": performance,
+                        })
+                    })
+                    .collect();
+                json!(plots)
+            }
+            AdminRequest::Bans => {
+                let bans: Vec<_> = self
+                    .bans
+                    .iter()
+                    .map(|ban| {
+                        json!({
+                            "name": ban.name,
+                            "uuid": ban.uuid.to_string(),
+                            "reason": ban.reason,
+                        })
+                    })
+                    .collect();
+                json!(bans)
+            }
+            AdminRequest::Compile { x, z, flags } => {
+                let Some(plot) = self
+                    .running_plots
+                    .iter()
+                    .find(|p| (p.plot_x, p.plot_z) == (x, z))
+                else {
+                    return Err("Plot is not running".to_string());
+                };
+                let _ = plot.priv_message_sender.send(PrivMessage::Compile(flags));
+                json!({ "compiling": [x, z] })
+            }
+            AdminRequest::Kick { player, reason } => {
+                let name = self.kick_player(&player, &reason)?;
+                json!({ "kicked": name })
+            }
+            AdminRequest::Ban { player, reason } => {
+                let name = self.ban_player(&player, &reason)?;
+                json!({ "banned": name })
+            }
+            AdminRequest::Unban { player } => {
+                let name = self.unban_player(&player)?;
+                json!({ "unbanned": name })
+            }
+        };
+        Ok(response)
+    }
+
+    /// Runs a command from the console and returns its output
+    fn handle_console_command(&mut self, command: &str) -> String {
+        let args: Vec<&str> = command.split_whitespace().collect();
+        match args.as_slice() {
+            [] | ["help"] => [
+                "Console commands:",
+                "list - Lists the players online",
+                "plots - Lists the running plots",
+                "say <message> - Sends a message to everyone",
+                "kick <player> [reason] - Disconnects a player",
+                "ban <player> [reason] - Bans a player from the server",
+                "unban <player> - Lets a banned player join again",
+                "bans - Lists the banned players",
+                "save - Saves all running plots and online players",
+                "perm reload - Reloads the permissions",
+                "restart - Sends everyone to restart_fallback_address and stops",
+                "stop - Saves everything and stops the server",
+            ]
+            .join("\n"),
+            ["list"] => {
+                let mut names: Vec<&str> = self
+                    .online_players
+                    .values()
+                    .map(|player| player.username.as_str())
+                    .collect();
+                names.sort_unstable();
+                format!(
+                    "There are {} of a max of {} players online: {}",
+                    names.len(),
+                    CONFIG.max_players,
+                    names.join(", ")
+                )
+            }
+            ["plots"] => {
+                let mut lines = vec![format!("{} plots are running:", self.running_plots.len())];
+                for plot in &self.running_plots {
+                    let players = self
+                        .online_players
+                        .values()
+                        .filter(|p| p.plot_x == plot.plot_x && p.plot_z == plot.plot_z)
+                        .count();
+                    lines.push(format!(
+                        "{},{}: {} player(s)",
+                        plot.plot_x, plot.plot_z, players
+                    ));
+                }
+                lines.join("\n")
+            }
+            ["say", message @ ..] if !message.is_empty() => {
+                let message = message.join(" ");
+                info!("[Server] {}", message);
+                self.send_to_discord(format!("**[Server]** {}", escape_markdown(&message)));
+                self.broadcaster.broadcast(BroadcastMessage::Chat(
+                    0,
+                    TextComponent::from_legacy_text(&format!("&d[Server] {}", message)),
+                ));
+                format!("[Server] {}", message)
+            }
+            ["kick", username, reason @ ..] => {
+                match self.kick_player(username, &reason.join(" ")) {
+                    Ok(username) => format!("Kicked {}", username),
+                    Err(err) => err,
+                }
+            }
+            ["ban", username, reason @ ..] => match self.ban_player(username, &reason.join(" ")) {
+                Ok(username) => format!("Banned {}", username),
+                Err(err) => err,
+            },
+            ["unban", username] => match self.unban_player(username) {
+                Ok(username) => format!("Unbanned {}", username),
+                Err(err) => err,
+            },
+            ["bans"] => {
+                let mut lines = vec![format!("{} players are banned:", self.bans.len())];
+                for ban in &self.bans {
+                    lines.push(format!("{}: {}", ban.name, ban.reason));
+                }
+                lines.join("\n")
+            }
+            ["save"] => {
+                self.broadcaster.broadcast(BroadcastMessage::Save);
+                format!("Saving {} plots.", self.running_plots.len())
+            }
+            ["perm", "reload"] => {
+                if !permissions::uses_luckperms() {
+                    if let Err(err) = groups::reload() {
+                        return format!("Failed to reload permissions: {:#}", err);
+                    }
+                }
+                self.broadcaster
+                    .broadcast(BroadcastMessage::ReloadPermissions);
+                "Permissions were reloaded.".to_string()
+            }
+            _ => "Unknown command. Type \"help\" for a list of commands.".to_string(),
+        }
+    }
+
+    fn update_online_players(&self) {
+        ONLINE_PLAYER_COUNT.store(self.online_players.len(), Ordering::Relaxed);
+        *ONLINE_PLAYER_NAMES.lock().unwrap() = self
+            .online_players
+            .values()
+            .map(|player| player.username.clone())
+            .collect();
+    }
+
     fn update(&mut self) {
         while let Ok(message) = self.receiver.try_recv() {
             self.handle_message(message);
         }
+        plugins::run_main_tasks();
+
+        if let Some(config) = &CONFIG.plot_expiry {
+            let interval = Duration::from_secs(config.check_interval * 60 * 60);
+            if self.last_expiry_check.elapsed() >= interval {
+                self.expire_inactive_plots();
+            }
+        }
         self.network.update();
 
         let mut client_idx = 0;
@@ -659,44 +1549,55 @@ impl MinecraftServer {
 
 impl ServerBoundPacketHandler for MinecraftServer {
     fn handle_handshake(&mut self, handshake: SHandshake, client_idx: usize) {
-        let clients = &mut self.network.handshaking_clients;
-        let client = &mut clients[client_idx];
         let next_state = match handshake.next_state {
             1 => NetworkState::Status,
-            2 => NetworkState::Login,
+            2 | 3 => NetworkState::Login,
             // TODO: Handle invalid next state
             _ => return,
         };
-        if next_state == NetworkState::Login && handshake.protocol_version != PROTOCOL_VERSION {
+        let supported = handshake.protocol_version == PROTOCOL_VERSION
+            || self.network.handshaking_clients[client_idx]
+                .protocol()
+                .is_some();
+        if next_state == NetworkState::Login && !supported {
             warn!("A player tried to connect using the wrong version");
+            let mut versions = vec![MC_VERSION.to_string()];
+            versions.extend(self.network.protocols().names());
+            let client = &mut self.network.handshaking_clients[client_idx];
             let disconnect = CDisconnectLogin {
-                reason: json!({ "text": format!("Version mismatch, I'm on {}!", MC_VERSION) })
-                    .to_string(),
+                reason: json!({
+                    "text": format!("Version mismatch, I'm on {}!", versions.join(", "))
+                })
+                .to_string(),
             }
             .encode();
             client.send_packet(&disconnect);
             client.close_connection();
+            return;
+        }
+        if next_state == NetworkState::Login {
+            if let Some(bungeecord) = CONFIG.bungeecord.as_ref().filter(|config| config.enabled) {
+                self.handle_bungeecord_handshake(bungeecord, &handshake.server_address, client_idx);
+            }
         }
     }
 
     fn handle_request(&mut self, _request: SRequest, client_idk: usize) {
         let client = &mut self.network.handshaking_clients[client_idk];
+        // Clients on a supported version are told that the server runs their version
+        let (version_name, protocol) = match client.protocol() {
+            Some(protocol) => (protocol.name().to_string(), protocol.version()),
+            None => (MC_VERSION.to_string(), PROTOCOL_VERSION),
+        };
+        let players = self
+            .online_players
+            .iter()
+            .map(|(uuid, entry)| (*uuid, entry.username.as_str()));
         let response = CResponse {
-            json_response: json!({
-                "version": {
-                    "name": MC_VERSION,
-                    "protocol": PROTOCOL_VERSION
-                },
-                "players": {
-                    "max": CONFIG.max_players,
-                    "online": self.online_players.len(),
-                    "sample": []
-                },
-                "description": {
-                    "text": CONFIG.motd
-                }
-            })
-            .to_string(),
+            json_response: self
+                .server_list
+                .response(&version_name, protocol, players)
+                .to_string(),
         }
         .encode();
         client.send_packet(&response);
@@ -745,9 +1646,9 @@ impl ServerBoundPacketHandler for MinecraftServer {
             coordinate_scale: 1.0,
             bed_works: false,
             respawn_anchor_works: false,
-            min_y: 0,
-            height: PLOT_BLOCK_HEIGHT,
-            logical_height: PLOT_BLOCK_HEIGHT,
+            min_y: *PLOT_MIN_Y,
+            height: *PLOT_BLOCK_HEIGHT,
+            logical_height: *PLOT_BLOCK_HEIGHT,
             infiniburn: "#minecraft:infiniburn_overworld".to_owned(),
             effects: "#minecraft:overworld".to_owned(),
             ambient_light: 1.0,
@@ -803,6 +1704,43 @@ impl ServerBoundPacketHandler for MinecraftServer {
         self.handle_player_enter_play(client_idx);
     }
 
+    fn handle_encryption_response(&mut self, packet: SEncryptionResponse, client_idx: usize) {
+        let Some(server_key) = &self.server_key else {
+            return;
+        };
+        let client = &mut self.network.handshaking_clients[client_idx];
+        let Some(verify_token) = client.verify_token.take() else {
+            return;
+        };
+        let shared_secret = server_key.decrypt(&packet.shared_secret);
+        let verified = server_key.decrypt(&packet.verify_token) == Some(verify_token);
+        let Some(shared_secret) = shared_secret.filter(|_| verified) else {
+            warn!("A player sent an invalid encryption response");
+            self.disconnect_login(client_idx, "Failed to verify username!");
+            return;
+        };
+        if !client.enable_encryption(&shared_secret) {
+            warn!("A player sent an invalid shared secret");
+            self.disconnect_login(client_idx, "Failed to verify username!");
+            return;
+        }
+
+        let server_hash = encryption::server_hash("", &shared_secret, server_key.public_key());
+        let username = client.username.clone().unwrap();
+        let client_id = client.id();
+        let sender = self.plot_sender.clone();
+        self.async_rt.spawn(async move {
+            let profile = match PlayerProfile::has_joined(&username, &server_hash).await {
+                Ok(profile) => Some(profile),
+                Err(err) => {
+                    warn!("Failed to verify {}: {:?}", username, err);
+                    None
+                }
+            };
+            let _ = sender.send(Message::LoginVerified(client_id, profile));
+        });
+    }
+
     fn handle_login_plugin_response(&mut self, packet: SLoginPluginResponse, client_idx: usize) {
         let clients = &mut self.network.handshaking_clients;
 
@@ -814,31 +1752,309 @@ impl ServerBoundPacketHandler for MinecraftServer {
             return;
         }
 
-        if !packet.successful {
-            error!("Velocity forwarding channel not understood by client");
-            return;
-        }
-
-        let velocity_response = VelocityResponseData::decode(&mut Cursor::new(&packet.data));
-
-        let velocity_response = match velocity_response {
+        let secret = CONFIG.velocity.as_ref().unwrap().secret.as_bytes();
+        let velocity_response = match verify_velocity_response(secret, &packet) {
             Ok(velocity_response) => velocity_response,
             Err(err) => {
-                error!("Could not decode velocity reponse data: {:?}", err);
+                warn!(
+                    "Velocity forwarding failed for {}: {}",
+                    clients[client_idx].username.as_deref().unwrap_or_default(),
+                    err
+                );
+                let disconnect = CDisconnectLogin {
+                    reason: json!({
+                        "text": "This server can only be joined through its proxy."
+                    })
+                    .to_string(),
+                }
+                .encode();
+                clients[client_idx].send_packet(&disconnect);
+                clients[client_idx].close_connection();
                 return;
             }
         };
 
-        let secret = CONFIG.velocity.as_ref().unwrap().secret.as_bytes();
-        let mut mac = <Hmac<Sha256>>::new_from_slice(secret).unwrap();
-        mac.update(&packet.data[32..]);
-        if mac.verify_slice(&packet.data[..32]).is_err() {
-            error!("Failed to verify velocity secret!");
-            return;
-        };
-
-        clients[client_idx].uuid = Some(velocity_response.uuid);
-        clients[client_idx].properties = velocity_response.properties;
+        let client = &mut clients[client_idx];
+        client.uuid = Some(velocity_response.uuid);
+        client.username = Some(velocity_response.username);
+        client.properties = velocity_response.properties;
+        match velocity_response.address.parse() {
+            Ok(address) => client.address = Some(address),
+            Err(_) => warn!(
+                "Velocity forwarded an invalid address: {}",
+                velocity_response.address
+            ),
+        }
         self.complete_player_login(client_idx);
     }
 }
+
+struct BungeeCordForwarding {
+    address: IpAddr,
+    uuid: u128,
+    properties: Vec<PlayerProperty>,
+}
+
+/// BungeeCord forwards the player in the server address of the handshake, as the host, the
+/// player's address, their uuid without hyphens and their properties as JSON, separated by nul
+/// characters
+fn parse_bungeecord_forwarding(server_address: &str) -> Option<BungeeCordForwarding> {
+    let mut parts = server_address.split('\0');
+    let _host = parts.next()?;
+    let address = parts.next()?.parse().ok()?;
+    let uuid = parts.next()?;
+    if uuid.len() != 32 {
+        return None;
+    }
+    let uuid = u128::from_str_radix(uuid, 16).ok()?;
+    let properties = match parts.next() {
+        Some(properties) => serde_json::from_str::<Vec<ProfileProperty>>(properties)
+            .ok()?
+            .into_iter()
+            .map(PlayerProperty::from)
+            .collect(),
+        None => Vec::new(),
+    };
+    Some(BungeeCordForwarding {
+        address,
+        uuid,
+        properties,
+    })
+}
+
+/// Checks that a forwarding response came from the proxy and decodes it
+fn verify_velocity_response(
+    secret: &[u8],
+    packet: &SLoginPluginResponse,
+) -> anyhow::Result<VelocityResponseData> {
+    if !packet.successful {
+        bail!("the client didn't understand the forwarding request");
+    }
+    if packet.data.len() < 32 {
+        bail!("the forwarding response is too short");
+    }
+    let (signature, data) = packet.data.split_at(32);
+    let mut mac = <Hmac<Sha256>>::new_from_slice(secret).unwrap();
+    mac.update(data);
+    if mac.verify_slice(signature).is_err() {
+        bail!("the forwarding secret doesn't match");
+    }
+
+    let response = VelocityResponseData::decode(&mut Cursor::new(&packet.data))
+        .map_err(|err| anyhow!("could not decode the forwarding response: {:?}", err))?;
+    if response.version < VELOCITY_MODERN_DEFAULT as i32 {
+        bail!("unsupported forwarding version {}", response.version);
+    }
+    Ok(response)
+}
+
+/// Formats a private message as `[header] message`. Clicking it starts a reply to `reply_to`.
+pub fn private_chat_message(header: &str, reply_to: &str, message: &str) -> TextComponent {
+    TextComponentBuilder::new(format!("[{}] ", header))
+        .color_code(ColorCode::Gray)
+        .click_event(ClickEvent::suggest_command(format!("/msg {} ", reply_to)))
+        .hover_text(format!("Click to reply to {}", reply_to))
+        .append(
+            TextComponentBuilder::new(message.to_string())
+                .color_code(ColorCode::White)
+                .finish(),
+        )
+        .finish()
+}
+
+/// Parses an address of the form `host[:port]` for transfers, using the default port if it is
+/// left out
+pub fn parse_address(address: &str) -> Option<(String, u16)> {
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (address, 25565),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port))
+}
+
+/// Derives the id of the resource pack from its url like vanilla does, so that clients recognize the
+/// same pack across joins
+fn resource_pack_uuid(url: &str) -> u128 {
+    u128::from_be_bytes(md5::compute(url).0) & (!(0xC << 60) & !(0xF << 76))
+        | ((0x8 << 60) | (0x3 << 76))
+}
+
+fn throttle() -> Throttle {
+    let velocity = CONFIG
+        .velocity
+        .as_ref()
+        .is_some_and(|config| config.enabled);
+    let bungeecord = CONFIG.bungeecord.as_ref().filter(|config| config.enabled);
+    // Behind a forwarding proxy every player connects from the proxy's address. Without knowing
+    // which addresses are proxies, each address can't be limited at all.
+    let unknown_proxies =
+        velocity || bungeecord.is_some_and(|config| config.allowed_proxies.is_empty());
+    let connections_per_minute = if unknown_proxies {
+        0
+    } else {
+        CONFIG.throttle.connections_per_minute
+    };
+    Throttle {
+        connections_per_minute,
+        exempt_addresses: bungeecord
+            .map(|config| config.allowed_proxies.clone())
+            .unwrap_or_default(),
+        logins_per_second: CONFIG.throttle.logins_per_second,
+        packets_per_second: CONFIG.throttle.packets_per_second,
+    }
+}
+
+fn compression() -> PacketCompression {
+    PacketCompression {
+        threshold: CONFIG.compression_threshold.max(0) as usize,
+        level: CONFIG.compression_level.clamp(0, 9) as u32,
+    }
+}
+
+/// The listener on `bind_address` followed by the extra ones in `listeners`
+fn listeners() -> Vec<Listener> {
+    let proxy_protocol = CONFIG
+        .proxy_protocol
+        .as_ref()
+        .filter(|config| config.enabled)
+        .map(|config| ProxyProtocol {
+            trusted_proxies: config.trusted_proxies.clone(),
+        });
+    let mut listeners = vec![Listener {
+        bind_address: CONFIG.bind_address.clone(),
+        proxy_protocol,
+    }];
+    for config in CONFIG.listeners.iter().flatten() {
+        listeners.push(Listener {
+            bind_address: config.bind_address.clone(),
+            proxy_protocol: config.proxy_protocol.then(|| ProxyProtocol {
+                trusted_proxies: config.trusted_proxies.clone(),
+            }),
+        });
+    }
+    listeners
+}
+
+/// Players are verified by the server itself only if no proxy does it
+fn online_mode() -> bool {
+    let velocity = CONFIG
+        .velocity
+        .as_ref()
+        .is_some_and(|config| config.enabled);
+    let bungeecord = CONFIG
+        .bungeecord
+        .as_ref()
+        .is_some_and(|config| config.enabled);
+    CONFIG.online_mode && !velocity && !bungeecord
+}
+
+/// Loads the protocol mappings in `./protocols`, which let clients on other versions join
+fn load_protocols() -> Protocols {
+    let mut protocols = Protocols::default();
+    let Ok(entries) = fs::read_dir("./protocols") else {
+        return protocols;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension() != Some("json".as_ref()) {
+            continue;
+        }
+        match load_protocol(&path) {
+            Ok(protocol) => {
+                info!("Loaded protocol {} for {}", protocol.version, protocol.name);
+                protocols.add(Arc::new(protocol));
+            }
+            Err(err) => error!(
+                "Failed to load protocol mapping {}: {:?}",
+                path.display(),
+                err
+            ),
+        }
+    }
+    protocols
+}
+
+fn load_protocol(path: &Path) -> anyhow::Result<MappedProtocol> {
+    let mut protocol: MappedProtocol = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if let Some(file) = &protocol.registry_data_file {
+        protocol.registry_data = Some(fs::read(path.with_file_name(file))?);
+    }
+    Ok(protocol)
+}
+
+#[test]
+fn verify_velocity_response_test() {
+    let mut data = Vec::new();
+    data.write_varint(VELOCITY_MODERN_DEFAULT as i32);
+    data.write_string(32767, "203.0.113.7");
+    data.write_uuid(0x1234);
+    data.write_string(16, "Player");
+    data.write_varint(0);
+    let mut mac = <Hmac<Sha256>>::new_from_slice(b"secret").unwrap();
+    mac.update(&data);
+    let signature = mac.finalize().into_bytes();
+    let packet = SLoginPluginResponse {
+        message_id: 0,
+        successful: true,
+        data: [signature.as_slice(), &data].concat(),
+    };
+
+    let response = verify_velocity_response(b"secret", &packet).unwrap();
+    assert_eq!(response.address, "203.0.113.7");
+    assert_eq!(response.uuid, 0x1234);
+    assert_eq!(response.username, "Player");
+    assert!(verify_velocity_response(b"other secret", &packet).is_err());
+
+    let mut tampered = packet.data.clone();
+    *tampered.last_mut().unwrap() = 1;
+    let tampered = SLoginPluginResponse {
+        data: tampered,
+        ..packet
+    };
+    assert!(verify_velocity_response(b"secret", &tampered).is_err());
+    let short = SLoginPluginResponse {
+        message_id: 0,
+        successful: true,
+        data: vec![0; 8],
+    };
+    assert!(verify_velocity_response(b"secret", &short).is_err());
+}
+
+#[test]
+fn parse_bungeecord_forwarding_test() {
+    let forwarding = parse_bungeecord_forwarding(
+        "mc.example.com\x00203.0.113.7\x00069a79f444e94726a5befca90e38aaf5\x00\
+         [{\"name\":\"textures\",\"value\":\"abc\",\"signature\":\"def\"}]",
+    )
+    .unwrap();
+    assert_eq!(forwarding.address, "203.0.113.7".parse::<IpAddr>().unwrap());
+    assert_eq!(forwarding.uuid, 0x069a79f444e94726a5befca90e38aaf5);
+    assert_eq!(forwarding.properties[0].name, "textures");
+    assert_eq!(forwarding.properties[0].signature.as_deref(), Some("def"));
+
+    let forwarding = parse_bungeecord_forwarding(
+        "mc.example.com\x00203.0.113.7\x00069a79f444e94726a5befca90e38aaf5",
+    )
+    .unwrap();
+    assert!(forwarding.properties.is_empty());
+    // A player connecting directly only sends the host
+    assert!(parse_bungeecord_forwarding("mc.example.com").is_none());
+    assert!(parse_bungeecord_forwarding("mc.example.com\x00203.0.113.7\x00Player").is_none());
+}
+
+#[test]
+fn parse_address_test() {
+    assert_eq!(
+        parse_address("example.com"),
+        Some(("example.com".to_string(), 25565))
+    );
+    assert_eq!(
+        parse_address("10.0.0.2:25566"),
+        Some(("10.0.0.2".to_string(), 25566))
+    );
+    assert_eq!(parse_address("example.com:port"), None);
+    assert_eq!(parse_address(""), None);
+}
@@ -0,0 +1,168 @@
+//! The status response shown in the server list. The `motd` and the `[server_list]` section of the
+//! config are read again whenever `Config.toml` changes, so they can be edited without restarting
+//! the server.
+
+use crate::config::{ServerListConfig, CONFIG};
+use crate::utils::HyphenatedUUID;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use mchprs_text::TextComponent;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::time::SystemTime;
+use tracing::warn;
+
+const CONFIG_FILE: &str = "Config.toml";
+/// The client doesn't show more players than this when hovering over the player count
+const MAX_SAMPLE_SIZE: usize = 12;
+
+/// The parts of the config the status response is made from
+#[derive(Deserialize)]
+struct StatusConfig {
+    motd: String,
+    #[serde(default)]
+    server_list: Option<ServerListConfig>,
+}
+
+pub struct ServerList {
+    motd: String,
+    config: Option<ServerListConfig>,
+    /// The favicon as a data url
+    favicon: Option<String>,
+    config_modified: Option<SystemTime>,
+}
+
+fn config_modified() -> Option<SystemTime> {
+    fs::metadata(CONFIG_FILE).and_then(|m| m.modified()).ok()
+}
+
+fn load_favicon(path: &str) -> Option<String> {
+    if path.is_empty() {
+        return None;
+    }
+    match fs::read(path) {
+        Ok(png) => Some(format!("data:image/png;base64,{}", STANDARD.encode(png))),
+        Err(err) => {
+            warn!("Failed to read the server icon {}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Turns `&` formatting codes into the `§` codes player names in the sample are shown with
+fn legacy_to_section_codes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(code) if c == '&' && code.is_ascii_alphanumeric() => result.push('§'),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+impl ServerList {
+    pub fn new() -> ServerList {
+        let config = CONFIG.server_list.clone();
+        let favicon = config.as_ref().and_then(|c| load_favicon(&c.favicon));
+        ServerList {
+            motd: CONFIG.motd.clone(),
+            config,
+            favicon,
+            config_modified: config_modified(),
+        }
+    }
+
+    fn reload_if_changed(&mut self) {
+        let modified = config_modified();
+        if modified == self.config_modified {
+            return;
+        }
+        self.config_modified = modified;
+        let status_config = fs::read_to_string(CONFIG_FILE)
+            .map_err(anyhow::Error::from)
+            .and_then(|config| Ok(toml::from_str::<StatusConfig>(&config)?));
+        match status_config {
+            Ok(status_config) => {
+                self.favicon = status_config
+                    .server_list
+                    .as_ref()
+                    .and_then(|c| load_favicon(&c.favicon));
+                self.motd = status_config.motd;
+                self.config = status_config.server_list;
+            }
+            Err(err) => warn!("Failed to reload the server list config: {}", err),
+        }
+    }
+
+    /// Builds the status response from the names and uuids of the players online
+    pub fn response<'a>(
+        &mut self,
+        version_name: &str,
+        protocol: i32,
+        players: impl ExactSizeIterator<Item = (u128, &'a str)>,
+    ) -> Value {
+        self.reload_if_changed();
+
+        let online = players.len();
+        let (max, sample) = match &self.config {
+            Some(config) => {
+                let max = config.max_players.unwrap_or(CONFIG.max_players);
+                let sample: Vec<Value> = if !config.sample.is_empty() {
+                    config
+                        .sample
+                        .iter()
+                        .map(|line| {
+                            json!({
+                                "name": legacy_to_section_codes(line),
+                                "id": HyphenatedUUID(0).to_string()
+                            })
+                        })
+                        .collect()
+                } else if config.show_players {
+                    players
+                        .take(MAX_SAMPLE_SIZE)
+                        .map(|(uuid, name)| {
+                            json!({ "name": name, "id": HyphenatedUUID(uuid).to_string() })
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                (max, sample)
+            }
+            None => (CONFIG.max_players, Vec::new()),
+        };
+        let description = TextComponent {
+            extra: TextComponent::from_legacy_text(&self.motd),
+            ..Default::default()
+        };
+
+        let mut response = json!({
+            "version": {
+                "name": version_name,
+                "protocol": protocol
+            },
+            "players": {
+                "max": max,
+                "online": online,
+                "sample": sample
+            },
+            "description": description
+        });
+        if let Some(favicon) = &self.favicon {
+            response["favicon"] = json!(favicon);
+        }
+        response
+    }
+}
+
+#[test]
+fn legacy_to_section_codes_test() {
+    assert_eq!(
+        legacy_to_section_codes("&aGreen & &lbold&"),
+        "§aGreen & §lbold&"
+    );
+}
@@ -0,0 +1,178 @@
+//! What the small servers running next to the game have in common: the admin api, the schematic
+//! http server and RCON. Each connection is handled on its own thread, with a limit on how many
+//! are handled at once and on how long clients can take to send something.
+
+use std::io::{self, BufRead, BufReader, Read, Take};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// The most bytes the request line and headers of an HTTP request can take up together
+const MAX_HEADER_SIZE: u64 = 8 * 1024;
+
+pub struct Limits {
+    /// How many connections are handled at once. Connections over the limit are dropped.
+    pub max_connections: usize,
+    /// How long a client can take to send something before the connection is dropped
+    pub read_timeout: Duration,
+}
+
+/// Counts a connection as handled until it is dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(connections: &Arc<AtomicUsize>, max_connections: usize) -> Option<ConnectionSlot> {
+        let taken = connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max_connections).then_some(count + 1)
+            })
+            .is_ok();
+        taken.then(|| ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Accepts connections on a thread named `name`, and handles each of them on its own thread
+pub fn serve(
+    name: &str,
+    listener: TcpListener,
+    limits: Limits,
+    handler: impl Fn(TcpStream) -> io::Result<()> + Send + Sync + 'static,
+) {
+    let name = name.to_string();
+    let handler = Arc::new(handler);
+    let connections = Arc::new(AtomicUsize::new(0));
+    std::thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let Some(slot) = ConnectionSlot::take(&connections, limits.max_connections) else {
+                    debug!("Dropped {} connection, too many are open", name);
+                    continue;
+                };
+                if let Err(err) = stream.set_read_timeout(Some(limits.read_timeout)) {
+                    debug!("{} connection failed: {}", name, err);
+                    continue;
+                }
+                let name = name.clone();
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    let _slot = slot;
+                    if let Err(err) = handler(stream) {
+                        debug!("{} connection failed: {}", name, err);
+                    }
+                });
+            }
+        })
+        .unwrap();
+}
+
+/// The request line and headers of an HTTP request, which the body can be read after
+pub struct HttpRequest<R> {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+    reader: BufReader<Take<R>>,
+}
+
+impl<R: Read> HttpRequest<R> {
+    /// Reads the request line and headers. If they aren't valid, the status to respond with is
+    /// returned instead.
+    pub fn read(stream: R) -> io::Result<Result<HttpRequest<R>, &'static str>> {
+        let mut reader = BufReader::new(stream.take(MAX_HEADER_SIZE));
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            // Running out of the limit before the empty line means the headers are too long
+            if reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+                let status = if reader.get_ref().limit() == 0 {
+                    "431 Request Header Fields Too Large"
+                } else {
+                    "400 Bad Request"
+                };
+                return Ok(Err(status));
+            }
+            if line.trim_end().is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+
+        let mut lines = lines.into_iter();
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Ok(Err("400 Bad Request"));
+        };
+        let headers = lines
+            .filter_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                Some((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect();
+        Ok(Ok(HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers,
+            reader,
+        }))
+    }
+
+    /// The value of a header, with the name compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Reads the body, as long as its `Content-Length` is given and at most `max_size`. Returns
+    /// `None` if it is too large.
+    pub fn read_body(&mut self, max_size: usize) -> io::Result<Option<Vec<u8>>> {
+        let content_length = match self.header("content-length") {
+            Some(length) => length.parse().unwrap_or(usize::MAX),
+            None => 0,
+        };
+        if content_length > max_size {
+            return Ok(None);
+        }
+        // The body has its own limit
+        self.reader.get_mut().set_limit(content_length as u64);
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body)?;
+        Ok(Some(body))
+    }
+}
+
+#[test]
+fn http_request_test() {
+    let data = b"POST /api/command HTTP/1.1\r\nHost: localhost\r\ncontent-LENGTH: 4\r\n\r\nlist";
+    let mut request = HttpRequest::read(&data[..]).unwrap().unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/api/command");
+    assert_eq!(request.header("Host"), Some("localhost"));
+    assert_eq!(request.read_body(1024).unwrap().unwrap(), b"list");
+
+    let mut request = HttpRequest::read(&data[..]).unwrap().unwrap();
+    assert!(request.read_body(3).unwrap().is_none());
+
+    let long = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "a".repeat(10_000));
+    assert!(matches!(
+        HttpRequest::read(long.as_bytes()).unwrap(),
+        Err("431 Request Header Fields Too Large")
+    ));
+    assert!(matches!(
+        HttpRequest::read(&b"GET\r\n\r\n"[..]).unwrap(),
+        Err("400 Bad Request")
+    ));
+}
@@ -0,0 +1,189 @@
+//! Webhooks that are sent a JSON payload when something happens on the server. Payloads are
+//! templates where `{name}` is replaced with the fields of the event, so they can be shaped for
+//! whatever receives them. String fields are escaped, so they can be put inside JSON strings.
+
+use crate::config::{WebhookConfig, CONFIG};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::time::Duration;
+use tracing::warn;
+
+/// How long a webhook has to answer before it is given up on
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap()
+});
+
+#[derive(Debug, Clone)]
+pub enum WebhookEvent {
+    ServerStarted,
+    ServerStopped,
+    PlotClaimed {
+        player: String,
+        plot_x: i32,
+        plot_z: i32,
+    },
+    CompileFinished {
+        plot_x: i32,
+        plot_z: i32,
+        duration_ms: u64,
+        warnings: usize,
+        optimize: bool,
+        io_only: bool,
+    },
+    WatchdogAlert {
+        alert: String,
+    },
+}
+
+impl WebhookEvent {
+    /// The name webhooks subscribe to the event with
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::ServerStarted => "server_started",
+            WebhookEvent::ServerStopped => "server_stopped",
+            WebhookEvent::PlotClaimed { .. } => "plot_claimed",
+            WebhookEvent::CompileFinished { .. } => "compile_finished",
+            WebhookEvent::WatchdogAlert { .. } => "watchdog_alert",
+        }
+    }
+
+    /// A description of the event for people to read
+    fn message(&self) -> String {
+        match self {
+            WebhookEvent::ServerStarted => "The server started".to_string(),
+            WebhookEvent::ServerStopped => "The server stopped".to_string(),
+            WebhookEvent::PlotClaimed {
+                player,
+                plot_x,
+                plot_z,
+            } => format!("{} claimed plot {},{}", player, plot_x, plot_z),
+            WebhookEvent::CompileFinished {
+                plot_x,
+                plot_z,
+                duration_ms,
+                warnings,
+                ..
+            } => format!(
+                "Plot {},{} compiled in {:.2}s with {} warnings",
+                plot_x,
+                plot_z,
+                *duration_ms as f64 / 1000.0,
+                warnings
+            ),
+            WebhookEvent::WatchdogAlert { alert } => alert.clone(),
+        }
+    }
+
+    /// The fields that can be put in payloads
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = vec![
+            ("event", self.name().into()),
+            ("message", self.message().into()),
+        ];
+        match self {
+            WebhookEvent::ServerStarted | WebhookEvent::ServerStopped => {}
+            WebhookEvent::PlotClaimed {
+                player,
+                plot_x,
+                plot_z,
+            } => fields.extend([
+                ("player", player.as_str().into()),
+                ("plot_x", (*plot_x).into()),
+                ("plot_z", (*plot_z).into()),
+            ]),
+            WebhookEvent::CompileFinished {
+                plot_x,
+                plot_z,
+                duration_ms,
+                warnings,
+                optimize,
+                io_only,
+            } => fields.extend([
+                ("plot_x", (*plot_x).into()),
+                ("plot_z", (*plot_z).into()),
+                ("duration_ms", (*duration_ms).into()),
+                ("warnings", (*warnings).into()),
+                ("optimize", (*optimize).into()),
+                ("io_only", (*io_only).into()),
+            ]),
+            WebhookEvent::WatchdogAlert { alert } => fields.push(("alert", alert.as_str().into())),
+        }
+        fields
+    }
+}
+
+/// Fills in the fields of an event in a payload template and checks that the result is JSON
+fn render(template: &str, event: &WebhookEvent) -> Result<Value> {
+    let mut payload = template.to_string();
+    for (name, value) in event.fields() {
+        let replacement = match value {
+            // Without the quotes, so the field can be part of a longer string
+            Value::String(str) => {
+                let escaped = Value::String(str).to_string();
+                escaped[1..escaped.len() - 1].to_string()
+            }
+            value => value.to_string(),
+        };
+        payload = payload.replace(&format!("{{{}}}", name), &replacement);
+    }
+    serde_json::from_str(&payload).context("The payload is not valid JSON")
+}
+
+async fn send(webhook: &WebhookConfig, event: &WebhookEvent) -> Result<()> {
+    let payload = render(&webhook.payload, event)?;
+    CLIENT
+        .post(&webhook.url)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Sends an event to every webhook subscribed to it
+pub async fn fire(event: WebhookEvent) {
+    let Some(webhooks) = &CONFIG.webhooks else {
+        return;
+    };
+    let subscribed = webhooks.iter().filter(|webhook| {
+        webhook.events.is_empty() || webhook.events.iter().any(|name| name == event.name())
+    });
+    for webhook in subscribed {
+        if let Err(err) = send(webhook, &event).await {
+            warn!(
+                "Failed to send {} webhook to {}: {:#}",
+                event.name(),
+                webhook.url,
+                err
+            );
+        }
+    }
+}
+
+#[test]
+fn render_test() {
+    let event = WebhookEvent::PlotClaimed {
+        player: "Player \"1\"".to_string(),
+        plot_x: -3,
+        plot_z: 4,
+    };
+    let payload = render(
+        r#"{"content": "**{event}**: {message}", "plot": [{plot_x}, {plot_z}]}"#,
+        &event,
+    )
+    .unwrap();
+    assert_eq!(
+        payload,
+        serde_json::json!({
+            "content": "**plot_claimed**: Player \"1\" claimed plot -3,4",
+            "plot": [-3, 4],
+        })
+    );
+    assert!(render(r#"{"content": {message}}"#, &event).is_err());
+}
@@ -0,0 +1,148 @@
+//! In online mode, the client sends a shared secret encrypted with the server's RSA key, and from
+//! then on everything sent in both directions is encrypted with AES/CFB8 using that secret as both
+//! the key and the IV.
+
+use openssl::pkey::Private;
+use openssl::rsa::{Padding, Rsa};
+use openssl::sha::Sha1;
+use openssl::symm::{Cipher, Crypter, Mode};
+use std::fmt;
+
+/// The length of the shared secret, which is an AES-128 key
+const SHARED_SECRET_LEN: usize = 16;
+
+pub struct ServerKey {
+    rsa: Rsa<Private>,
+    public_key: Vec<u8>,
+}
+
+impl ServerKey {
+    pub fn generate() -> ServerKey {
+        let rsa = Rsa::generate(1024).expect("failed to generate the server key");
+        let public_key = rsa
+            .public_key_to_der()
+            .expect("failed to encode the server key");
+        ServerKey { rsa, public_key }
+    }
+
+    /// The public key in DER format, as it is sent to clients
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let mut buf = vec![0; self.rsa.size() as usize];
+        let len = self
+            .rsa
+            .private_decrypt(data, &mut buf, Padding::PKCS1)
+            .ok()?;
+        buf.truncate(len);
+        Some(buf)
+    }
+}
+
+/// Returns the hash the client and the session server check the login with. It is a SHA-1 digest
+/// printed as a signed hexadecimal number, without leading zeros.
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let mut digest = hasher.finish();
+
+    let negative = digest[0] & 0x80 != 0;
+    if negative {
+        // Two's complement
+        let mut carry = true;
+        for byte in digest.iter_mut().rev() {
+            let (value, overflow) = (!*byte).overflowing_add(carry as u8);
+            *byte = value;
+            carry = overflow;
+        }
+    }
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let hex = hex.trim_start_matches('0');
+    if negative {
+        format!("-{}", hex)
+    } else {
+        hex.to_string()
+    }
+}
+
+/// One direction of an encrypted connection
+pub(crate) struct Cfb8 {
+    crypter: Crypter,
+    buf: Vec<u8>,
+}
+
+impl fmt::Debug for Cfb8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cfb8").finish_non_exhaustive()
+    }
+}
+
+impl Cfb8 {
+    pub(crate) fn new(shared_secret: &[u8], mode: Mode) -> Option<Cfb8> {
+        if shared_secret.len() != SHARED_SECRET_LEN {
+            return None;
+        }
+        let crypter = Crypter::new(
+            Cipher::aes_128_cfb8(),
+            mode,
+            shared_secret,
+            Some(shared_secret),
+        )
+        .ok()?;
+        Some(Cfb8 {
+            crypter,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Encrypts or decrypts the data in place
+    pub(crate) fn apply(&mut self, data: &mut [u8]) {
+        // `Crypter` wants room for an extra block even though CFB8 doesn't use it
+        self.buf
+            .resize(data.len() + Cipher::aes_128_cfb8().block_size(), 0);
+        let len = self
+            .crypter
+            .update(data, &mut self.buf)
+            .expect("CFB8 can encrypt any length");
+        data.copy_from_slice(&self.buf[..len]);
+    }
+}
+
+#[test]
+fn server_hash_test() {
+    // The examples from wiki.vg
+    assert_eq!(
+        server_hash("Notch", &[], &[]),
+        "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48"
+    );
+    assert_eq!(
+        server_hash("jeb_", &[], &[]),
+        "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1"
+    );
+    assert_eq!(
+        server_hash("simon", &[], &[]),
+        "88e16a1019277b15d58faf0541e11910eb756f6"
+    );
+}
+
+#[test]
+fn cfb8_round_trip_test() {
+    let secret = [7; SHARED_SECRET_LEN];
+    let mut encryptor = Cfb8::new(&secret, Mode::Encrypt).unwrap();
+    let mut decryptor = Cfb8::new(&secret, Mode::Decrypt).unwrap();
+    let message = b"stream ciphers keep their state between packets".to_vec();
+
+    let mut data = message.clone();
+    encryptor.apply(&mut data[..10]);
+    encryptor.apply(&mut data[10..]);
+    assert_ne!(data, message);
+    // The data can be decrypted in different chunks than it was encrypted in
+    decryptor.apply(&mut data[..3]);
+    decryptor.apply(&mut data[3..]);
+    assert_eq!(data, message);
+    assert!(Cfb8::new(&[0; 5], Mode::Encrypt).is_none());
+}
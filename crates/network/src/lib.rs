@@ -1,35 +1,55 @@
+pub mod encryption;
 mod nbt_util;
 pub mod packets;
+pub mod protocol;
+pub mod proxy_protocol;
+pub mod throttle;
 
+use encryption::Cfb8;
+use openssl::symm::Mode;
 use packets::serverbound::ServerBoundPacket;
-use packets::{read_packet, PacketEncoder, PlayerProperty};
-use std::net::{Shutdown, TcpListener, TcpStream};
-use std::sync::atomic::{AtomicBool, Ordering};
+use packets::{read_packet, PacketCompression, PacketDecoderExt, PacketEncoder, PlayerProperty};
+use protocol::{ConnectionProtocol, Protocol, Protocols, NATIVE_PROTOCOL_VERSION};
+use proxy_protocol::ProxyProtocol;
+use socket2::{Domain, Socket, Type};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc};
 use std::thread;
+use std::time::Duration;
+use throttle::{ConnectionThrottle, RateLimiter, Throttle};
 use tracing::warn;
 
+/// How long the thread reading a connection waits for the server to accept the client's
+/// encryption response
+const CIPHER_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many bytes of packets can wait to be written to a connection. Clients that stop reading
+/// are disconnected once this is reached, instead of the packets piling up in memory.
+const MAX_QUEUED_BYTES: usize = 128 * 1024 * 1024;
+
 pub use nbt_util::NBTCompound;
 
 #[derive(Debug)]
 pub struct PlayerPacketSender {
-    stream: Option<TcpStream>,
+    writer: Writer,
+    protocol: Arc<ConnectionProtocol>,
 }
 
 impl PlayerPacketSender {
     pub fn new(conn: &PlayerConn) -> PlayerPacketSender {
-        let stream = conn.client.stream.try_clone().ok();
-        if stream.is_none() {
-            warn!("Creating PlayerPacketSender with dead stream")
+        PlayerPacketSender {
+            writer: conn.client.writer.clone(),
+            protocol: conn.client.protocol.clone(),
         }
-        PlayerPacketSender { stream }
     }
 
     pub fn send_packet(&self, data: &PacketEncoder) {
-        if let Some(stream) = &self.stream {
-            // Going to assume stream is compressed since it should be after login
-            let _ = data.write_compressed(stream);
-        }
+        let Some(data) = self.protocol.translate(data) else {
+            return;
+        };
+        // Going to assume stream is compressed since it should be after login
+        self.writer.send(Outgoing::Packet(data.into_owned(), true));
     }
 }
 
@@ -48,7 +68,11 @@ pub struct HandshakingConn {
     pub username: Option<String>,
     pub uuid: Option<u128>,
     pub forwarding_message_id: Option<i32>,
+    /// The token sent in the encryption request in online mode
+    pub verify_token: Option<Vec<u8>>,
     pub properties: Vec<PlayerProperty>,
+    /// The address of the player. Behind a proxy, this is the address the proxy forwarded.
+    pub address: Option<IpAddr>,
 }
 
 impl HandshakingConn {
@@ -60,6 +84,31 @@ impl HandshakingConn {
         self.client.receive_packets(&mut true)
     }
 
+    /// Returns the protocol the client connected with, or `None` if it is the native protocol
+    pub fn protocol(&self) -> Option<&Arc<dyn Protocol>> {
+        self.client.protocol.protocol()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.client.id
+    }
+
+    /// Encrypts everything sent to and received from the client from now on. Returns false if the
+    /// shared secret isn't a valid key.
+    pub fn enable_encryption(&self, shared_secret: &[u8]) -> bool {
+        let (Some(encryptor), Some(decryptor)) = (
+            Cfb8::new(shared_secret, Mode::Encrypt),
+            Cfb8::new(shared_secret, Mode::Decrypt),
+        ) else {
+            return false;
+        };
+        // Packets that are already queued are still sent unencrypted
+        self.client
+            .writer
+            .send(Outgoing::EnableEncryption(encryptor));
+        self.client.decryptor_sender.send(decryptor).is_ok()
+    }
+
     pub fn set_compressed(&self, compressed: bool) {
         self.client.compressed.store(compressed, Ordering::Relaxed)
     }
@@ -74,6 +123,7 @@ impl From<HandshakingConn> for PlayerConn {
         PlayerConn {
             client: conn.client,
             alive: true,
+            address: conn.address,
         }
     }
 }
@@ -81,6 +131,7 @@ impl From<HandshakingConn> for PlayerConn {
 pub struct PlayerConn {
     client: NetworkClient,
     alive: bool,
+    address: Option<IpAddr>,
 }
 
 impl PlayerConn {
@@ -96,6 +147,18 @@ impl PlayerConn {
         self.alive
     }
 
+    pub fn address(&self) -> Option<IpAddr> {
+        self.address
+    }
+
+    /// The protocol version the client is on
+    pub fn protocol_version(&self) -> i32 {
+        self.client
+            .protocol
+            .protocol()
+            .map_or(NATIVE_PROTOCOL_VERSION, |protocol| protocol.version())
+    }
+
     pub fn close_connection(&mut self) {
         self.alive = false;
         self.client.close_connection();
@@ -110,24 +173,150 @@ pub struct NetworkClient {
     stream: TcpStream,
     packets: mpsc::Receiver<Box<dyn ServerBoundPacket>>,
     compressed: Arc<AtomicBool>,
+    protocol: Arc<ConnectionProtocol>,
+    /// Packets are compressed, encrypted and written by a thread of their own, so that the plot
+    /// threads sending them don't have to wait on it
+    writer: Writer,
+    decryptor_sender: mpsc::Sender<Cfb8>,
+    /// The address of the client, which is the one a proxy forwarded if there is one
+    address: Option<IpAddr>,
+}
+
+/// The stream of a connection as the thread reading its packets sees it
+struct ConnectionReader {
+    stream: TcpStream,
+    decryptor: Option<Cfb8>,
+}
+
+impl Read for ConnectionReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.stream.read(buf)?;
+        if let Some(decryptor) = &mut self.decryptor {
+            decryptor.apply(&mut buf[..len]);
+        }
+        Ok(len)
+    }
+}
+
+impl PacketDecoderExt for ConnectionReader {}
+
+/// What the thread writing a connection is asked to do, in the order it was asked
+enum Outgoing {
+    /// A packet and whether it is compressed
+    Packet(PacketEncoder, bool),
+    EnableEncryption(Cfb8),
+}
+
+impl Outgoing {
+    fn size(&self) -> usize {
+        match self {
+            Outgoing::Packet(packet, _) => packet.buffer.len(),
+            Outgoing::EnableEncryption(_) => 0,
+        }
+    }
+}
+
+/// Queues things for the thread writing a connection
+#[derive(Debug, Clone)]
+struct Writer {
+    sender: mpsc::Sender<Outgoing>,
+    /// How many bytes of packets are queued and not yet written
+    queued_bytes: Arc<AtomicUsize>,
+    stream: Arc<TcpStream>,
+}
+
+impl Writer {
+    fn send(&self, outgoing: Outgoing) {
+        let size = outgoing.size();
+        let queued = self.queued_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        if queued > MAX_QUEUED_BYTES {
+            // Only the packet going over the limit closes the connection, the ones after it are
+            // dropped
+            if queued - size <= MAX_QUEUED_BYTES {
+                warn!(
+                    "Disconnecting {:?} for not reading packets fast enough",
+                    self.stream.peer_addr().ok()
+                );
+                let _ = self.stream.shutdown(Shutdown::Both);
+            }
+            return;
+        }
+        let _ = self.sender.send(outgoing);
+    }
 }
 
 impl NetworkClient {
-    fn listen(
+    fn write(
         mut stream: TcpStream,
+        receiver: mpsc::Receiver<Outgoing>,
+        queued_bytes: Arc<AtomicUsize>,
+        compression: PacketCompression,
+    ) {
+        let mut encryptor: Option<Cfb8> = None;
+        let mut buf = Vec::new();
+        // Everything that is queued up is written at once
+        while let Ok(first) = receiver.recv() {
+            let mut written = 0;
+            for outgoing in std::iter::once(first).chain(receiver.try_iter()) {
+                written += outgoing.size();
+                match outgoing {
+                    Outgoing::Packet(packet, compressed) => {
+                        let start = buf.len();
+                        let _ = if compressed {
+                            packet.write_compressed(&mut buf, compression)
+                        } else {
+                            packet.write_uncompressed(&mut buf)
+                        };
+                        if let Some(encryptor) = &mut encryptor {
+                            encryptor.apply(&mut buf[start..]);
+                        }
+                    }
+                    Outgoing::EnableEncryption(cipher) => encryptor = Some(cipher),
+                }
+            }
+            if stream.write_all(&buf).is_err() {
+                return;
+            }
+            queued_bytes.fetch_sub(written, Ordering::Relaxed);
+            buf.clear();
+        }
+    }
+
+    fn listen(
+        stream: TcpStream,
         sender: mpsc::Sender<Box<dyn ServerBoundPacket>>,
         compressed: Arc<AtomicBool>,
+        protocol: Arc<ConnectionProtocol>,
+        decryptor_receiver: mpsc::Receiver<Cfb8>,
+        packets_per_second: u32,
     ) {
-        let mut state = NetworkState::Handshaking;
+        let address = stream.peer_addr().ok();
+        let mut reader = ConnectionReader {
+            stream,
+            decryptor: None,
+        };
+        let mut packet_limiter = RateLimiter::per_second(packets_per_second);
         loop {
-            let packet = match read_packet(&mut stream, &compressed, &mut state) {
+            let packet = match read_packet(&mut reader, &compressed, &protocol) {
                 Ok(packet) => packet,
                 // This will cause the client to disconnect
                 Err(_) => return,
             };
+            if !packet_limiter.allow() {
+                warn!("Disconnecting {:?} for sending too many packets", address);
+                let _ = reader.stream.shutdown(Shutdown::Both);
+                return;
+            }
             if sender.send(packet).is_err() {
                 return;
             }
+            if protocol.awaiting_cipher.swap(false, Ordering::Relaxed) {
+                match decryptor_receiver.recv_timeout(CIPHER_TIMEOUT) {
+                    Ok(decryptor) => reader.decryptor = Some(decryptor),
+                    // The server didn't accept the encryption response
+                    Err(_) => return,
+                }
+            }
         }
     }
 
@@ -148,13 +337,14 @@ impl NetworkClient {
     }
 
     pub fn send_packet(&self, data: &PacketEncoder) {
+        let Some(data) = self.protocol.translate(data) else {
+            return;
+        };
         // TODO: every call to `send_packet` with the same PacketEncoder will
         // lead to re-encoding the packet. It might be good to cache this.
-        if self.compressed.load(Ordering::Relaxed) {
-            let _ = data.write_compressed(&self.stream);
-        } else {
-            let _ = data.write_uncompressed(&self.stream);
-        }
+        let compressed = self.compressed.load(Ordering::Relaxed);
+        self.writer
+            .send(Outgoing::Packet(data.into_owned(), compressed));
     }
 
     pub fn close_connection(&self) {
@@ -162,60 +352,254 @@ impl NetworkClient {
     }
 }
 
+/// An address the server accepts connections on
+#[derive(Debug, Clone)]
+pub struct Listener {
+    pub bind_address: String,
+    /// Set if connections have to start with a PROXY protocol header
+    pub proxy_protocol: Option<ProxyProtocol>,
+}
+
+/// What every listener sets up new connections with
+struct ConnectionSettings {
+    protocols: Arc<Protocols>,
+    /// Shared by all listeners so that ids are unique
+    next_id: AtomicU32,
+    throttle: Throttle,
+    /// Shared by all listeners so that each address has the same limit on all of them
+    connection_throttle: ConnectionThrottle,
+    compression: PacketCompression,
+}
+
 /// This represents the network portion of a minecraft server
 pub struct NetworkServer {
+    /// New clients wait in this channel until the login limiter lets them in
     client_receiver: mpsc::Receiver<NetworkClient>,
+    protocols: Arc<Protocols>,
+    login_limiter: RateLimiter,
     /// These clients are either in the handshake, login, or ping state, once they shift to play,
     /// they will be moved to a plot
     pub handshaking_clients: Vec<HandshakingConn>,
 }
 
 impl NetworkServer {
-    fn listen(bind_address: &str, sender: mpsc::Sender<NetworkClient>) {
-        let listener = TcpListener::bind(bind_address).unwrap();
+    /// Binds a listener. IPv6 listeners only accept IPv6 connections, so that an IPv4 listener can
+    /// use the same port.
+    fn bind(bind_address: &str) -> io::Result<TcpListener> {
+        let address = bind_address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to bind to"))?;
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )?;
+        if address.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        socket.set_reuse_address(true)?;
+        socket.bind(&address.into())?;
+        socket.listen(128)?;
+        Ok(socket.into())
+    }
 
-        for (index, stream) in listener.incoming().enumerate() {
+    fn listen(
+        listener: TcpListener,
+        proxy_protocol: Option<ProxyProtocol>,
+        settings: Arc<ConnectionSettings>,
+        sender: mpsc::Sender<NetworkClient>,
+    ) {
+        for stream in listener.incoming() {
             let stream = stream.unwrap();
-            let (packet_sender, packet_receiver) = mpsc::channel();
-            let compressed = Arc::new(AtomicBool::new(false));
-            let client_stream = stream.try_clone().unwrap();
-            let client_compressed = compressed.clone();
-            thread::spawn(move || {
-                NetworkClient::listen(client_stream, packet_sender, client_compressed);
-            });
-            sender
-                .send(NetworkClient {
-                    // The index will increment after each client making it unique. We'll just use
-                    // this as the enitity id.
-                    id: index as u32,
-                    stream,
-                    packets: packet_receiver,
-                    compressed,
-                })
-                .unwrap();
+            // The id is unique across all listeners. We'll just use this as the enitity id.
+            let id = settings.next_id.fetch_add(1, Ordering::Relaxed);
+            match &proxy_protocol {
+                Some(proxy_protocol) => {
+                    // Waiting for the header would hold up everyone else connecting
+                    let proxy_protocol = proxy_protocol.clone();
+                    let settings = settings.clone();
+                    let sender = sender.clone();
+                    thread::spawn(move || {
+                        let Some(address) =
+                            NetworkServer::read_proxy_header(&stream, &proxy_protocol)
+                        else {
+                            let _ = stream.shutdown(Shutdown::Both);
+                            return;
+                        };
+                        if !NetworkServer::allow_connection(&settings.connection_throttle, address)
+                        {
+                            let _ = stream.shutdown(Shutdown::Both);
+                            return;
+                        }
+                        let client = NetworkServer::accept(id, stream, address, &settings);
+                        let _ = sender.send(client);
+                    });
+                }
+                None => {
+                    let address = stream.peer_addr().ok().map(|addr| addr.ip());
+                    if !NetworkServer::allow_connection(&settings.connection_throttle, address) {
+                        let _ = stream.shutdown(Shutdown::Both);
+                        continue;
+                    }
+                    let client = NetworkServer::accept(id, stream, address, &settings);
+                    sender.send(client).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Reads the PROXY protocol header of a connection, returning the address of the client.
+    /// `None` is returned if the connection should be closed.
+    fn read_proxy_header(
+        mut stream: &TcpStream,
+        proxy_protocol: &ProxyProtocol,
+    ) -> Option<Option<IpAddr>> {
+        let peer_address = stream.peer_addr().ok()?.ip();
+        if !proxy_protocol.trusts(peer_address) {
+            warn!(
+                "Closing a connection from {} which isn't a trusted proxy",
+                peer_address
+            );
+            return None;
+        }
+        stream
+            .set_read_timeout(Some(proxy_protocol::HEADER_TIMEOUT))
+            .ok()?;
+        let header = proxy_protocol::read_header(&mut stream);
+        stream.set_read_timeout(None).ok()?;
+        match header {
+            Ok(address) => Some(Some(address.map_or(peer_address, |address| address.ip()))),
+            Err(err) => {
+                warn!(
+                    "Invalid PROXY protocol header from {}: {}",
+                    peer_address, err
+                );
+                None
+            }
+        }
+    }
+
+    fn allow_connection(connection_throttle: &ConnectionThrottle, address: Option<IpAddr>) -> bool {
+        let Some(address) = address else {
+            return true;
+        };
+        let allowed = connection_throttle.allow(address);
+        if !allowed {
+            warn!(
+                "Closing a connection from {} which connects too often",
+                address
+            );
+        }
+        allowed
+    }
+
+    fn accept(
+        id: u32,
+        stream: TcpStream,
+        address: Option<IpAddr>,
+        settings: &ConnectionSettings,
+    ) -> NetworkClient {
+        let packets_per_second = settings.throttle.packets_per_second;
+        let (packet_sender, packet_receiver) = mpsc::channel();
+        let compressed = Arc::new(AtomicBool::new(false));
+        let client_stream = stream.try_clone().unwrap();
+        let client_compressed = compressed.clone();
+        let protocol = Arc::new(ConnectionProtocol::new(settings.protocols.clone()));
+        let client_protocol = protocol.clone();
+        let (decryptor_sender, decryptor_receiver) = mpsc::channel();
+        let (writer_sender, writer_receiver) = mpsc::channel();
+        let writer = Writer {
+            sender: writer_sender,
+            queued_bytes: Arc::new(AtomicUsize::new(0)),
+            stream: Arc::new(stream.try_clone().unwrap()),
+        };
+        let writer_stream = stream.try_clone().unwrap();
+        let queued_bytes = writer.queued_bytes.clone();
+        let compression = settings.compression;
+        thread::spawn(move || {
+            NetworkClient::write(writer_stream, writer_receiver, queued_bytes, compression)
+        });
+        thread::spawn(move || {
+            NetworkClient::listen(
+                client_stream,
+                packet_sender,
+                client_compressed,
+                client_protocol,
+                decryptor_receiver,
+                packets_per_second,
+            );
+        });
+        NetworkClient {
+            id,
+            stream,
+            packets: packet_receiver,
+            compressed,
+            protocol,
+            writer,
+            decryptor_sender,
+            address,
         }
     }
 
-    /// Creates a new `NetworkServer`. The server will then start accepting TCP clients.
-    pub fn new(bind_address: String) -> NetworkServer {
+    /// Creates a new `NetworkServer`. The server will then start accepting TCP clients on each of
+    /// `listeners`, on the native protocol and any of `protocols`.
+    pub fn new(
+        listeners: Vec<Listener>,
+        protocols: Protocols,
+        throttle: Throttle,
+        compression: PacketCompression,
+    ) -> NetworkServer {
         let (sender, receiver) = mpsc::channel();
-        thread::spawn(move || NetworkServer::listen(&bind_address, sender));
+        let protocols = Arc::new(protocols);
+        let settings = Arc::new(ConnectionSettings {
+            protocols: protocols.clone(),
+            next_id: AtomicU32::new(0),
+            connection_throttle: ConnectionThrottle::new(
+                throttle.connections_per_minute,
+                throttle.exempt_addresses.clone(),
+            ),
+            throttle: throttle.clone(),
+            compression,
+        });
+        for listener in listeners {
+            let tcp_listener = NetworkServer::bind(&listener.bind_address).unwrap_or_else(|err| {
+                panic!("Failed to bind to {}: {}", listener.bind_address, err)
+            });
+            let settings = settings.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                NetworkServer::listen(tcp_listener, listener.proxy_protocol, settings, sender)
+            });
+        }
         NetworkServer {
             client_receiver: receiver,
+            protocols,
+            login_limiter: RateLimiter::per_second(throttle.logins_per_second),
             handshaking_clients: Vec::new(),
         }
     }
 
+    /// Returns the protocols the server accepts other than the native one
+    pub fn protocols(&self) -> &Protocols {
+        &self.protocols
+    }
+
     pub fn update(&mut self) {
-        loop {
+        while !self.login_limiter.is_limited() {
             match self.client_receiver.try_recv() {
-                Ok(client) => self.handshaking_clients.push(HandshakingConn {
-                    client,
-                    username: None,
-                    uuid: None,
-                    forwarding_message_id: None,
-                    properties: vec![],
-                }),
+                Ok(client) => {
+                    self.login_limiter.allow();
+                    self.handshaking_clients.push(HandshakingConn {
+                        address: client.address,
+                        client,
+                        username: None,
+                        uuid: None,
+                        forwarding_message_id: None,
+                        verify_token: None,
+                        properties: vec![],
+                    })
+                }
                 Err(mpsc::TryRecvError::Empty) => break,
                 Err(mpsc::TryRecvError::Disconnected) => {
                     panic!("Client receiver channel disconnected!");
@@ -45,6 +45,24 @@ impl ClientBoundPacket for CDisconnectLogin {
     }
 }
 
+pub struct CEncryptionRequest {
+    pub server_id: String,
+    pub public_key: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+impl ClientBoundPacket for CEncryptionRequest {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_string(20, &self.server_id);
+        buf.write_varint(self.public_key.len() as i32);
+        buf.write_bytes(&self.public_key);
+        buf.write_varint(self.verify_token.len() as i32);
+        buf.write_bytes(&self.verify_token);
+        PacketEncoder::new(buf, 0x01)
+    }
+}
+
 pub struct CPong {
     pub payload: i64,
 }
@@ -345,6 +363,26 @@ impl ClientBoundPacket for CBlockEntityData {
     }
 }
 
+pub struct CBlockAction {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub action_id: u8,
+    pub action_param: u8,
+    pub block_type: i32,
+}
+
+impl ClientBoundPacket for CBlockAction {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_position(self.x, self.y, self.z);
+        buf.write_unsigned_byte(self.action_id);
+        buf.write_unsigned_byte(self.action_param);
+        buf.write_varint(self.block_type);
+        PacketEncoder::new(buf, 0x08)
+    }
+}
+
 pub struct CBlockUpdate {
     pub x: i32,
     pub y: i32,
@@ -361,6 +399,58 @@ impl ClientBoundPacket for CBlockUpdate {
     }
 }
 
+pub enum CBossEventAction {
+    Add {
+        title: TextComponent,
+        /// How full the bar is, between 0 and 1
+        progress: f32,
+        color: i32,
+        division: i32,
+        flags: u8,
+    },
+    Remove,
+    UpdateProgress(f32),
+    UpdateTitle(TextComponent),
+}
+
+pub struct CBossEvent {
+    pub uuid: u128,
+    pub action: CBossEventAction,
+}
+
+impl ClientBoundPacket for CBossEvent {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_uuid(self.uuid);
+        match &self.action {
+            CBossEventAction::Add {
+                title,
+                progress,
+                color,
+                division,
+                flags,
+            } => {
+                buf.write_varint(0);
+                buf.write_text_component(title);
+                buf.write_float(*progress);
+                buf.write_varint(*color);
+                buf.write_varint(*division);
+                buf.write_unsigned_byte(*flags);
+            }
+            CBossEventAction::Remove => buf.write_varint(1),
+            CBossEventAction::UpdateProgress(progress) => {
+                buf.write_varint(2);
+                buf.write_float(*progress);
+            }
+            CBossEventAction::UpdateTitle(title) => {
+                buf.write_varint(3);
+                buf.write_text_component(title);
+            }
+        }
+        PacketEncoder::new(buf, 0x0A)
+    }
+}
+
 pub struct CCommandSuggestionsResponseMatch {
     pub match_: String,
     pub tooltip: Option<TextComponent>,
@@ -392,7 +482,7 @@ impl ClientBoundPacket for CCommandSuggestionsResponse {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CDeclareCommandsNodeParser {
     Entity(i8),
     Vec2,
@@ -436,7 +526,7 @@ impl CDeclareCommandsNodeParser {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CCommandsNode {
     pub flags: i8,
     pub children: Vec<i32>,
@@ -557,7 +647,13 @@ impl ClientBoundPacket for CUnloadChunk {
 }
 
 pub enum CGameEventType {
+    EndRaining,
+    BeginRaining,
     ChangeGamemode,
+    /// The value is the rain level, from 0 to 1
+    RainLevelChange,
+    /// The value is the thunder level, from 0 to 1
+    ThunderLevelChange,
     /// Start waiting for level chunks
     WaitForChunks,
 }
@@ -571,7 +667,11 @@ impl ClientBoundPacket for CGameEvent {
     fn encode(&self) -> PacketEncoder {
         let mut buf = Vec::new();
         match self.reason {
+            CGameEventType::EndRaining => buf.write_unsigned_byte(1),
+            CGameEventType::BeginRaining => buf.write_unsigned_byte(2),
             CGameEventType::ChangeGamemode => buf.write_unsigned_byte(3),
+            CGameEventType::RainLevelChange => buf.write_unsigned_byte(7),
+            CGameEventType::ThunderLevelChange => buf.write_unsigned_byte(8),
             CGameEventType::WaitForChunks => buf.write_unsigned_byte(13),
         }
         buf.write_float(self.value);
@@ -605,6 +705,32 @@ pub struct CChunkDataBlockEntity {
     pub data: NBTCompound,
 }
 
+/// Sent before the chunks of a batch. Only clients on 1.20.2 or newer have this packet.
+pub struct CChunkBatchStart;
+
+impl CChunkBatchStart {
+    pub const MIN_PROTOCOL_VERSION: i32 = 764;
+}
+
+impl ClientBoundPacket for CChunkBatchStart {
+    fn encode(&self) -> PacketEncoder {
+        PacketEncoder::new(Vec::new(), 0x0D)
+    }
+}
+
+/// Sent after the chunks of a batch. The client answers with `SChunkBatchReceived`.
+pub struct CChunkBatchFinished {
+    pub batch_size: i32,
+}
+
+impl ClientBoundPacket for CChunkBatchFinished {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_varint(self.batch_size);
+        PacketEncoder::new(buf, 0x0C)
+    }
+}
+
 /// Chunk Data and Update Light
 pub struct CChunkData {
     pub chunk_x: i32,
@@ -710,6 +836,57 @@ impl ClientBoundPacket for CWorldEvent {
     }
 }
 
+pub enum CParticleData {
+    /// A colored particle that doesn't move, with its RGB color between 0 and 1
+    Dust { color: [f32; 3], scale: f32 },
+}
+
+impl CParticleData {
+    fn id(&self) -> i32 {
+        match self {
+            CParticleData::Dust { .. } => 14,
+        }
+    }
+}
+
+pub struct CParticle {
+    pub particle: CParticleData,
+    pub long_distance: bool,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub offset_z: f32,
+    pub max_speed: f32,
+    pub count: i32,
+}
+
+impl ClientBoundPacket for CParticle {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_varint(self.particle.id());
+        buf.write_bool(self.long_distance);
+        buf.write_double(self.x);
+        buf.write_double(self.y);
+        buf.write_double(self.z);
+        buf.write_float(self.offset_x);
+        buf.write_float(self.offset_y);
+        buf.write_float(self.offset_z);
+        buf.write_float(self.max_speed);
+        buf.write_int(self.count);
+        match &self.particle {
+            CParticleData::Dust { color, scale } => {
+                for component in color {
+                    buf.write_float(*component);
+                }
+                buf.write_float(*scale);
+            }
+        }
+        PacketEncoder::new(buf, 0x27)
+    }
+}
+
 pub struct CLoginDeathLocation {
     dimension_name: String,
     x: i32,
@@ -1042,6 +1219,30 @@ impl ClientBoundPacket for CResetScore {
     }
 }
 
+pub struct CAddResourcePack {
+    pub uuid: u128,
+    pub url: String,
+    /// The SHA-1 of the pack as a hex string, or empty to not check it
+    pub hash: String,
+    pub forced: bool,
+    pub prompt_message: Option<TextComponent>,
+}
+
+impl ClientBoundPacket for CAddResourcePack {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_uuid(self.uuid);
+        buf.write_string(32767, &self.url);
+        buf.write_string(40, &self.hash);
+        buf.write_bool(self.forced);
+        buf.write_bool(self.prompt_message.is_some());
+        if let Some(prompt_message) = &self.prompt_message {
+            buf.write_text_component(prompt_message);
+        }
+        PacketEncoder::new(buf, 0x44)
+    }
+}
+
 pub struct CSetHeadRotation {
     pub entity_id: i32,
     pub head_yaw: f32,
@@ -1068,7 +1269,7 @@ pub struct CUpdateSectionBlocksRecord {
 pub struct CUpdateSectionBlocks {
     pub chunk_x: i32,
     pub chunk_z: i32,
-    pub chunk_y: u32,
+    pub chunk_y: i32,
     pub records: Vec<CUpdateSectionBlocksRecord>,
 }
 
@@ -1268,11 +1469,13 @@ impl ClientBoundPacket for UpdateTime {
     }
 }
 
+/// A sound played at a position. `sound_id` is the id in the sound event registry plus one, or 0
+/// to play the sound named by `sound_name` instead, which can be any sound of a resource pack.
 pub struct CSoundEffect {
     pub sound_id: i32,
     pub sound_name: Option<String>,
-    pub has_fixed_range: Option<bool>,
-    pub range: Option<bool>,
+    /// The distance the sound can be heard from, if it isn't derived from the volume
+    pub range: Option<f32>,
     pub sound_category: i32,
     pub x: i32,
     pub y: i32,
@@ -1286,6 +1489,13 @@ impl ClientBoundPacket for CSoundEffect {
     fn encode(&self) -> PacketEncoder {
         let mut buf = Vec::new();
         buf.write_varint(self.sound_id);
+        if self.sound_id == 0 {
+            buf.write_string(32767, self.sound_name.as_deref().unwrap_or_default());
+            buf.write_bool(self.range.is_some());
+            if let Some(range) = self.range {
+                buf.write_float(range);
+            }
+        }
         buf.write_varint(self.sound_category);
         buf.write_int(self.x);
         buf.write_int(self.y);
@@ -1334,3 +1544,103 @@ impl ClientBoundPacket for CSystemChatMessage {
         PacketEncoder::new(buf, 0x69)
     }
 }
+
+pub struct CSetTitleText {
+    pub text: TextComponent,
+}
+
+impl ClientBoundPacket for CSetTitleText {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_text_component(&self.text);
+        PacketEncoder::new(buf, 0x63)
+    }
+}
+
+pub struct CSetSubtitleText {
+    pub text: TextComponent,
+}
+
+impl ClientBoundPacket for CSetSubtitleText {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_text_component(&self.text);
+        PacketEncoder::new(buf, 0x61)
+    }
+}
+
+/// The durations are in ticks
+pub struct CSetTitleAnimationTimes {
+    pub fade_in: i32,
+    pub stay: i32,
+    pub fade_out: i32,
+}
+
+impl ClientBoundPacket for CSetTitleAnimationTimes {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_int(self.fade_in);
+        buf.write_int(self.stay);
+        buf.write_int(self.fade_out);
+        PacketEncoder::new(buf, 0x64)
+    }
+}
+
+pub struct CClearTitles {
+    /// Whether the animation times are reset to the defaults as well
+    pub reset: bool,
+}
+
+impl ClientBoundPacket for CClearTitles {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_bool(self.reset);
+        PacketEncoder::new(buf, 0x0F)
+    }
+}
+
+pub struct CSetActionBarText {
+    pub text: TextComponent,
+}
+
+impl ClientBoundPacket for CSetActionBarText {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_text_component(&self.text);
+        PacketEncoder::new(buf, 0x4A)
+    }
+}
+
+pub struct CSetTabListHeaderAndFooter {
+    pub header: TextComponent,
+    pub footer: TextComponent,
+}
+
+impl ClientBoundPacket for CSetTabListHeaderAndFooter {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_text_component(&self.header);
+        buf.write_text_component(&self.footer);
+        PacketEncoder::new(buf, 0x6A)
+    }
+}
+
+/// Sends the client to another server. Only clients on 1.20.5 or newer have this packet, so its id
+/// is the one from 1.20.5 and it is only sent to clients on a protocol that maps it.
+pub struct CTransfer {
+    pub host: String,
+    pub port: u16,
+}
+
+impl CTransfer {
+    pub const MIN_PROTOCOL_VERSION: i32 = 766;
+}
+
+impl ClientBoundPacket for CTransfer {
+    fn encode(&self) -> PacketEncoder {
+        let mut buf = Vec::new();
+        buf.write_string(32767, &self.host);
+        buf.write_varint(self.port as i32);
+        PacketEncoder::new(buf, 0x73)
+    }
+}
@@ -4,6 +4,7 @@ pub mod serverbound;
 use crate::nbt_util::NBTCompound;
 
 use super::NetworkState;
+use crate::protocol::ConnectionProtocol;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use flate2::bufread::ZlibDecoder;
 use flate2::write::ZlibEncoder;
@@ -17,7 +18,14 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tracing::{error, trace};
 
-pub const COMPRESSION_THRESHOLD: usize = 256;
+/// How packets are compressed once compression is enabled for a connection
+#[derive(Debug, Clone, Copy)]
+pub struct PacketCompression {
+    /// Packets smaller than this many bytes are sent uncompressed
+    pub threshold: usize,
+    /// The zlib level, from 0 for the fastest to 9 for the smallest
+    pub level: u32,
+}
 
 #[derive(Debug)]
 pub struct SlotData {
@@ -72,32 +80,41 @@ pub enum PacketEncodeError {}
 
 fn read_compressed<T: PacketDecoderExt>(
     reader: &mut T,
-    network_state: &mut NetworkState,
+    connection: &ConnectionProtocol,
 ) -> DecodeResult<Box<dyn ServerBoundPacket>> {
     let decompressed_length = reader.read_varint()? as usize;
     let data = PacketDecoderExt::read_to_end(reader)?;
     // `data` is not compressed if `decompressed_length` is 0
     if decompressed_length == 0 {
-        read_decompressed(&mut Cursor::new(data), network_state)
+        read_decompressed(&mut Cursor::new(data), connection)
     } else {
         let mut decompresser = ZlibDecoder::new(data.as_slice());
         let mut decompressed_data = Vec::with_capacity(decompressed_length);
         decompresser.read_to_end(&mut decompressed_data)?;
-        read_decompressed(&mut Cursor::new(decompressed_data), network_state)
+        read_decompressed(&mut Cursor::new(decompressed_data), connection)
     }
 }
 
 fn read_decompressed<T: PacketDecoderExt>(
     reader: &mut T,
-    state: &mut NetworkState,
+    connection: &ConnectionProtocol,
 ) -> DecodeResult<Box<dyn ServerBoundPacket>> {
-    let packet_id = reader.read_varint()?;
+    let mut state = connection.state.lock().unwrap();
+    let mut packet_id = reader.read_varint()?;
+    if let Some(protocol) = connection.protocol() {
+        match protocol.serverbound_id(&state, packet_id) {
+            Some(native_id) => packet_id = native_id,
+            None => return Ok(Box::new(SUnknown)),
+        }
+    }
     let packet: Box<dyn ServerBoundPacket> = match *state {
         NetworkState::Handshaking if packet_id == 0x00 => {
             let handshake = SHandshake::decode(reader)?;
+            connection.select(handshake.protocol_version);
             match handshake.next_state {
                 1 => *state = NetworkState::Status,
-                2 => *state = NetworkState::Login,
+                // Clients transferred from another server log in like any other
+                2 | 3 => *state = NetworkState::Login,
                 _ => {}
             }
             Box::new(handshake)
@@ -105,6 +122,11 @@ fn read_decompressed<T: PacketDecoderExt>(
         NetworkState::Status if packet_id == 0x00 => Box::new(SRequest::decode(reader)?),
         NetworkState::Status if packet_id == 0x01 => Box::new(SPing::decode(reader)?),
         NetworkState::Login if packet_id == 0x00 => Box::new(SLoginStart::decode(reader)?),
+        NetworkState::Login if packet_id == 0x01 => {
+            // Everything the client sends after this is encrypted
+            connection.awaiting_cipher.store(true, Ordering::Relaxed);
+            Box::new(SEncryptionResponse::decode(reader)?)
+        }
         NetworkState::Login if packet_id == 0x02 => Box::new(SLoginPluginResponse::decode(reader)?),
         NetworkState::Login if packet_id == 0x03 => {
             *state = NetworkState::Configuration;
@@ -120,6 +142,7 @@ fn read_decompressed<T: PacketDecoderExt>(
         _ => match packet_id {
             0x04 => Box::new(SChatCommand::decode(reader)?),
             0x05 => Box::new(SChatMessage::decode(reader)?),
+            0x07 => Box::new(SChunkBatchReceived::decode(reader)?),
             0x09 => Box::new(SClientInformation::decode(reader)?),
             0x0A => Box::new(SCommandSuggestionsRequest::decode(reader)?),
             0x10 => Box::new(SPluginMessage::decode(reader)?),
@@ -131,11 +154,13 @@ fn read_decompressed<T: PacketDecoderExt>(
             0x20 => Box::new(SPlayerAbilities::decode(reader)?),
             0x21 => Box::new(SPlayerAction::decode(reader)?),
             0x22 => Box::new(SPlayerCommand::decode(reader)?),
+            0x28 => Box::new(SResourcePackResponse::decode(reader)?),
             0x2C => Box::new(SSetHeldItem::decode(reader)?),
             0x2F => Box::new(SSetCreativeModeSlot::decode(reader)?),
             0x32 => Box::new(SUpdateSign::decode(reader)?),
             0x33 => Box::new(SSwingArm::decode(reader)?),
             0x35 => Box::new(SUseItemOn::decode(reader)?),
+            0x36 => Box::new(SUseItem::decode(reader)?),
             _ => Box::new(SUnknown),
         },
     };
@@ -147,15 +172,15 @@ fn read_decompressed<T: PacketDecoderExt>(
 pub fn read_packet<T: PacketDecoderExt>(
     reader: &mut T,
     compressed: &Arc<AtomicBool>,
-    network_state: &mut NetworkState,
+    connection: &ConnectionProtocol,
 ) -> DecodeResult<Box<dyn ServerBoundPacket>> {
     let length = reader.read_varint()?;
     let data = reader.read_bytes(length as usize)?;
     let mut cursor = Cursor::new(data);
     if compressed.load(Ordering::Relaxed) {
-        read_compressed(&mut cursor, network_state)
+        read_compressed(&mut cursor, connection)
     } else {
-        read_decompressed(&mut cursor, network_state)
+        read_decompressed(&mut cursor, connection)
     }
 }
 
@@ -435,13 +460,14 @@ pub trait PacketEncoderExt: Write {
 
 impl PacketEncoderExt for Vec<u8> {}
 
+#[derive(Clone)]
 pub struct PacketEncoder {
-    buffer: Vec<u8>,
-    packet_id: u32,
+    pub(crate) buffer: Vec<u8>,
+    pub(crate) packet_id: u32,
 }
 
 impl PacketEncoder {
-    fn new(buffer: Vec<u8>, packet_id: u32) -> PacketEncoder {
+    pub(crate) fn new(buffer: Vec<u8>, packet_id: u32) -> PacketEncoder {
         trace!("Encoding packet with id {:#02x}", packet_id);
         PacketEncoder { buffer, packet_id }
     }
@@ -463,11 +489,15 @@ impl PacketEncoder {
         }
     }
 
-    pub fn write_compressed(&self, mut w: impl Write) -> io::Result<()> {
+    pub fn write_compressed(
+        &self,
+        mut w: impl Write,
+        compression: PacketCompression,
+    ) -> io::Result<()> {
         // TODO: zero allocation
         let packet_id = PacketEncoder::varint(self.packet_id as i32);
         let data = [packet_id.as_slice(), self.buffer.as_slice()].concat();
-        if self.buffer.len() < COMPRESSION_THRESHOLD {
+        if self.buffer.len() < compression.threshold {
             // Data Length adds another byte
             let packet_length = PacketEncoder::varint((1 + data.len()) as i32);
 
@@ -477,7 +507,7 @@ impl PacketEncoder {
             w.write_all(&data)?;
         } else {
             let data_length = PacketEncoder::varint(data.len() as i32);
-            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(compression.level));
             encoder.write_all(&data)?;
             let compressed = encoder.finish().unwrap();
             let packet_length =
@@ -1,4 +1,5 @@
 use super::{DecodeResult, PacketDecoderExt, PlayerProperty, SlotData};
+use std::io;
 
 pub trait ServerBoundPacketHandler {
     // Handshaking
@@ -8,6 +9,7 @@ pub trait ServerBoundPacketHandler {
     fn handle_ping(&mut self, _packet: SPing, _player_idx: usize) {}
     // Login
     fn handle_login_start(&mut self, _packet: SLoginStart, _player_idx: usize) {}
+    fn handle_encryption_response(&mut self, _packet: SEncryptionResponse, _player_idx: usize) {}
     fn handle_login_plugin_response(&mut self, _packet: SLoginPluginResponse, _player_idx: usize) {}
     fn handle_login_acknowledged(&mut self, _packet: SLoginAcknowledged, _player_idx: usize) {}
     // Configuration
@@ -29,6 +31,7 @@ pub trait ServerBoundPacketHandler {
     }
     fn handle_plugin_message(&mut self, _packet: SPluginMessage, _player_idx: usize) {}
     fn handle_keep_alive(&mut self, _packet: SKeepAlive, _player_idx: usize) {}
+    fn handle_chunk_batch_received(&mut self, _packet: SChunkBatchReceived, _player_idx: usize) {}
     fn handle_set_player_position(&mut self, _packet: SSetPlayerPosition, _player_idx: usize) {}
     fn handle_set_player_position_and_rotation(
         &mut self,
@@ -43,6 +46,13 @@ pub trait ServerBoundPacketHandler {
     fn handle_player_command(&mut self, _packet: SPlayerCommand, _player_idx: usize) {}
     fn handle_swing_arm(&mut self, _packet: SSwingArm, _player_idx: usize) {}
     fn handle_use_item_on(&mut self, _packet: SUseItemOn, _player_idx: usize) {}
+    fn handle_use_item(&mut self, _packet: SUseItem, _player_idx: usize) {}
+    fn handle_resource_pack_response(
+        &mut self,
+        _packet: SResourcePackResponse,
+        _player_idx: usize,
+    ) {
+    }
     fn handle_set_held_item(&mut self, _packet: SSetHeldItem, _player_idx: usize) {}
     fn handle_set_creative_mode_slot(&mut self, _packet: SSetCreativeModeSlot, _player_idx: usize) {
     }
@@ -141,6 +151,29 @@ impl ServerBoundPacket for SLoginStart {
     }
 }
 
+#[derive(Debug)]
+pub struct SEncryptionResponse {
+    pub shared_secret: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+impl ServerBoundPacket for SEncryptionResponse {
+    fn decode<T: PacketDecoderExt>(decoder: &mut T) -> DecodeResult<Self> {
+        let shared_secret_len = decoder.read_varint()?;
+        let shared_secret = decoder.read_bytes(shared_secret_len as usize)?;
+        let verify_token_len = decoder.read_varint()?;
+        let verify_token = decoder.read_bytes(verify_token_len as usize)?;
+        Ok(SEncryptionResponse {
+            shared_secret,
+            verify_token,
+        })
+    }
+
+    fn handle(self: Box<Self>, handler: &mut dyn ServerBoundPacketHandler, player_idx: usize) {
+        handler.handle_encryption_response(*self, player_idx);
+    }
+}
+
 #[derive(Debug)]
 pub struct SLoginPluginResponse {
     pub message_id: i32,
@@ -394,6 +427,24 @@ impl ServerBoundPacket for SKeepAlive {
     }
 }
 
+#[derive(Debug)]
+pub struct SChunkBatchReceived {
+    /// How many chunks per tick the client can take
+    pub chunks_per_tick: f32,
+}
+
+impl ServerBoundPacket for SChunkBatchReceived {
+    fn decode<T: PacketDecoderExt>(decoder: &mut T) -> DecodeResult<Self> {
+        Ok(SChunkBatchReceived {
+            chunks_per_tick: decoder.read_float()?,
+        })
+    }
+
+    fn handle(self: Box<Self>, handler: &mut dyn ServerBoundPacketHandler, player_idx: usize) {
+        handler.handle_chunk_batch_received(*self, player_idx);
+    }
+}
+
 #[derive(Debug)]
 pub struct SSetPlayerPosition {
     pub x: f64,
@@ -611,6 +662,86 @@ impl ServerBoundPacket for SUseItemOn {
     }
 }
 
+#[derive(Debug)]
+pub struct SUseItem {
+    pub hand: i32,
+    pub sequence: i32,
+}
+
+impl ServerBoundPacket for SUseItem {
+    fn decode<T: PacketDecoderExt>(decoder: &mut T) -> DecodeResult<Self> {
+        Ok(SUseItem {
+            hand: decoder.read_varint()?,
+            sequence: decoder.read_varint()?,
+        })
+    }
+
+    fn handle(self: Box<Self>, handler: &mut dyn ServerBoundPacketHandler, player_idx: usize) {
+        handler.handle_use_item(*self, player_idx);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourcePackResult {
+    SuccessfullyLoaded,
+    Declined,
+    FailedDownload,
+    Accepted,
+    Downloaded,
+    InvalidUrl,
+    FailedReload,
+    Discarded,
+}
+
+impl ResourcePackResult {
+    fn from_id(id: i32) -> DecodeResult<ResourcePackResult> {
+        use ResourcePackResult::*;
+        Ok(match id {
+            0 => SuccessfullyLoaded,
+            1 => Declined,
+            2 => FailedDownload,
+            3 => Accepted,
+            4 => Downloaded,
+            5 => InvalidUrl,
+            6 => FailedReload,
+            7 => Discarded,
+            _ => {
+                let err =
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid resource pack result");
+                return Err(err.into());
+            }
+        })
+    }
+
+    /// Whether the client won't end up using the pack
+    pub fn is_failure(self) -> bool {
+        use ResourcePackResult::*;
+        matches!(
+            self,
+            Declined | FailedDownload | InvalidUrl | FailedReload | Discarded
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct SResourcePackResponse {
+    pub uuid: u128,
+    pub result: ResourcePackResult,
+}
+
+impl ServerBoundPacket for SResourcePackResponse {
+    fn decode<T: PacketDecoderExt>(decoder: &mut T) -> DecodeResult<Self> {
+        Ok(SResourcePackResponse {
+            uuid: decoder.read_uuid()?,
+            result: ResourcePackResult::from_id(decoder.read_varint()?)?,
+        })
+    }
+
+    fn handle(self: Box<Self>, handler: &mut dyn ServerBoundPacketHandler, player_idx: usize) {
+        handler.handle_resource_pack_response(*self, player_idx);
+    }
+}
+
 #[derive(Debug)]
 pub struct SSetHeldItem {
     pub slot: i16,
@@ -0,0 +1,466 @@
+//! Clients on other versions than the one the server is written for are supported through a
+//! `Protocol`, which translates packets between the native protocol and the client's. Each
+//! connection picks its protocol from the version it sends in its handshake, and connections on
+//! the native version aren't translated at all.
+
+use crate::packets::{DecodeResult, PacketDecoderExt, PacketEncoder, PacketEncoderExt};
+use crate::NetworkState;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+
+/// The protocol version the packets in this crate are written for
+pub const NATIVE_PROTOCOL_VERSION: i32 = 765;
+
+const BLOCK_UPDATE_ID: u32 = 0x09;
+const CHUNK_DATA_ID: u32 = 0x25;
+const UPDATE_SECTION_BLOCKS_ID: u32 = 0x47;
+const REGISTRY_DATA_ID: u32 = 0x05;
+/// Block state containers with more bits per entry than this don't have a palette
+const MAX_INDIRECT_BLOCK_BITS: u8 = 8;
+const MAX_INDIRECT_BIOME_BITS: u8 = 3;
+
+pub trait Protocol: Send + Sync {
+    /// The protocol version clients send in their handshake
+    fn version(&self) -> i32;
+
+    /// The name of the Minecraft version, shown to players in the server list
+    fn name(&self) -> &str;
+
+    /// Maps the id of a packet from the client to the id of the same packet in the native
+    /// protocol. Packets that the native protocol doesn't have are dropped by returning `None`.
+    fn serverbound_id(&self, state: &NetworkState, packet_id: i32) -> Option<i32>;
+
+    /// Translates a packet of the native protocol for the client. Packets that the client doesn't
+    /// have are dropped by returning `None`.
+    fn translate_clientbound(
+        &self,
+        state: &NetworkState,
+        packet: &PacketEncoder,
+    ) -> Option<PacketEncoder>;
+}
+
+/// All protocols the server accepts other than the native one
+#[derive(Default)]
+pub struct Protocols {
+    protocols: HashMap<i32, Arc<dyn Protocol>>,
+}
+
+impl Protocols {
+    pub fn add(&mut self, protocol: Arc<dyn Protocol>) {
+        let version = protocol.version();
+        if version == NATIVE_PROTOCOL_VERSION {
+            warn!("Ignoring protocol {} since it is native", version);
+            return;
+        }
+        self.protocols.insert(version, protocol);
+    }
+
+    pub fn get(&self, version: i32) -> Option<Arc<dyn Protocol>> {
+        self.protocols.get(&version).cloned()
+    }
+
+    /// Returns the names of the added protocols, newest first
+    pub fn names(&self) -> Vec<String> {
+        let mut protocols: Vec<&Arc<dyn Protocol>> = self.protocols.values().collect();
+        protocols.sort_by_key(|protocol| -protocol.version());
+        protocols
+            .into_iter()
+            .map(|protocol| protocol.name().to_string())
+            .collect()
+    }
+}
+
+/// The protocol and state of a connection. It is shared between the thread reading the packets
+/// of the connection, which updates it, and everything sending packets to it.
+pub struct ConnectionProtocol {
+    protocols: Arc<Protocols>,
+    pub(crate) state: Mutex<NetworkState>,
+    protocol: OnceLock<Arc<dyn Protocol>>,
+    /// Set when the client sent its encryption response, after which the thread reading its
+    /// packets has to wait for the cipher
+    pub(crate) awaiting_cipher: AtomicBool,
+}
+
+impl fmt::Debug for ConnectionProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionProtocol")
+            .field(
+                "version",
+                &self.protocol().map(|protocol| protocol.version()),
+            )
+            .finish()
+    }
+}
+
+impl ConnectionProtocol {
+    pub(crate) fn new(protocols: Arc<Protocols>) -> ConnectionProtocol {
+        ConnectionProtocol {
+            protocols,
+            state: Mutex::new(NetworkState::Handshaking),
+            protocol: OnceLock::new(),
+            awaiting_cipher: AtomicBool::new(false),
+        }
+    }
+
+    /// Picks the protocol of the connection once the handshake is received. Versions that aren't
+    /// supported are left on the native protocol, and have to be disconnected by the server.
+    pub(crate) fn select(&self, version: i32) {
+        if let Some(protocol) = self.protocols.get(version) {
+            let _ = self.protocol.set(protocol);
+        }
+    }
+
+    /// Returns the protocol of the connection, or `None` if it is native
+    pub fn protocol(&self) -> Option<&Arc<dyn Protocol>> {
+        self.protocol.get()
+    }
+
+    pub(crate) fn translate<'a>(
+        &self,
+        packet: &'a PacketEncoder,
+    ) -> Option<Cow<'a, PacketEncoder>> {
+        match self.protocol.get() {
+            Some(protocol) => {
+                let state = self.state.lock().unwrap().clone();
+                protocol
+                    .translate_clientbound(&state, packet)
+                    .map(Cow::Owned)
+            }
+            None => Some(Cow::Borrowed(packet)),
+        }
+    }
+}
+
+/// Packet ids of a protocol that differ from the native protocol, in every state. A `null` id
+/// means that the other side doesn't have the packet.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PacketIdMapping {
+    pub status: HashMap<i32, Option<i32>>,
+    pub login: HashMap<i32, Option<i32>>,
+    pub configuration: HashMap<i32, Option<i32>>,
+    pub play: HashMap<i32, Option<i32>>,
+}
+
+impl PacketIdMapping {
+    fn map(&self, state: &NetworkState, packet_id: i32) -> Option<i32> {
+        let ids = match state {
+            NetworkState::Handshaking => return Some(packet_id),
+            NetworkState::Status => &self.status,
+            NetworkState::Login => &self.login,
+            NetworkState::Configuration => &self.configuration,
+            NetworkState::Play => &self.play,
+        };
+        ids.get(&packet_id).copied().unwrap_or(Some(packet_id))
+    }
+}
+
+/// A protocol whose packets are laid out like the native ones, but which has different packet
+/// ids, block state ids or registries. These are read from mapping files.
+#[derive(Deserialize)]
+pub struct MappedProtocol {
+    pub version: i32,
+    pub name: String,
+    /// Maps ids of packets from the client to native ids
+    #[serde(default)]
+    pub serverbound: PacketIdMapping,
+    /// Maps native ids of packets to the client's ids
+    #[serde(default)]
+    pub clientbound: PacketIdMapping,
+    /// Maps native block state ids to the client's block state ids
+    #[serde(default)]
+    pub block_states: HashMap<u32, u32>,
+    /// The file with the NBT that is sent to the client in place of the native registry data,
+    /// relative to the mapping file
+    #[serde(default)]
+    pub registry_data_file: Option<String>,
+    /// The contents of `registry_data_file`, loaded by the server
+    #[serde(skip)]
+    pub registry_data: Option<Vec<u8>>,
+}
+
+impl MappedProtocol {
+    fn block_state(&self, id: u32) -> u32 {
+        self.block_states.get(&id).copied().unwrap_or(id)
+    }
+
+    fn translate_block_update(&self, buffer: &[u8]) -> DecodeResult<Vec<u8>> {
+        let mut reader = Cursor::new(buffer);
+        let pos = reader.read_long()?;
+        let block_id = reader.read_varint()?;
+        let mut buf = Vec::with_capacity(buffer.len());
+        buf.write_long(pos);
+        buf.write_varint(self.block_state(block_id as u32) as i32);
+        Ok(buf)
+    }
+
+    fn translate_update_section_blocks(&self, buffer: &[u8]) -> DecodeResult<Vec<u8>> {
+        let mut reader = Cursor::new(buffer);
+        let pos = reader.read_long()?;
+        let len = reader.read_varint()?;
+        let mut buf = Vec::with_capacity(buffer.len());
+        buf.write_long(pos);
+        buf.write_varint(len);
+        for _ in 0..len {
+            let record = reader.read_varlong()? as u64;
+            let block_id = self.block_state((record >> 12) as u32) as u64;
+            buf.write_varlong(((block_id << 12) | (record & 0xFFF)) as i64);
+        }
+        Ok(buf)
+    }
+
+    fn translate_chunk_data(&self, buffer: &[u8]) -> DecodeResult<Vec<u8>> {
+        let mut reader = Cursor::new(buffer);
+        reader.read_int()?;
+        reader.read_int()?;
+        reader.read_nbt_compound()?;
+        let header_len = reader.position() as usize;
+        let data_len = reader.read_varint()? as usize;
+        let data = reader.read_bytes(data_len)?;
+        let rest = &buffer[reader.position() as usize..];
+
+        let mut sections = Cursor::new(data.as_slice());
+        let mut translated = Vec::with_capacity(data_len);
+        while (sections.position() as usize) < data_len {
+            translated.write_short(sections.read_short()?);
+            self.translate_container(
+                &mut sections,
+                &mut translated,
+                MAX_INDIRECT_BLOCK_BITS,
+                true,
+            )?;
+            self.translate_container(
+                &mut sections,
+                &mut translated,
+                MAX_INDIRECT_BIOME_BITS,
+                false,
+            )?;
+        }
+
+        let mut buf = Vec::with_capacity(buffer.len());
+        buf.write_bytes(&buffer[..header_len]);
+        buf.write_varint(translated.len() as i32);
+        buf.write_bytes(&translated);
+        buf.write_bytes(rest);
+        Ok(buf)
+    }
+
+    /// Copies a paletted container, translating its entries if it holds block states
+    fn translate_container(
+        &self,
+        reader: &mut Cursor<&[u8]>,
+        buf: &mut Vec<u8>,
+        max_indirect_bits: u8,
+        block_states: bool,
+    ) -> DecodeResult<()> {
+        let translate = |id: i32| {
+            if block_states {
+                self.block_state(id as u32) as i32
+            } else {
+                id
+            }
+        };
+
+        let bits_per_entry = reader.read_unsigned_byte()?;
+        buf.write_unsigned_byte(bits_per_entry);
+        if bits_per_entry == 0 {
+            buf.write_varint(translate(reader.read_varint()?));
+        } else if bits_per_entry <= max_indirect_bits {
+            let palette_len = reader.read_varint()?;
+            buf.write_varint(palette_len);
+            for _ in 0..palette_len {
+                buf.write_varint(translate(reader.read_varint()?));
+            }
+        }
+
+        let data_len = reader.read_varint()?;
+        buf.write_varint(data_len);
+        let direct = bits_per_entry > max_indirect_bits;
+        let bits = bits_per_entry as u64;
+        let mask = (1u64 << bits) - 1;
+        for _ in 0..data_len {
+            let mut long = reader.read_long()? as u64;
+            if direct && block_states {
+                // The entries of a direct container are the block states themselves
+                let mut translated = 0;
+                for i in 0..64 / bits {
+                    let id = (long >> (i * bits)) & mask;
+                    translated |= (self.block_state(id as u32) as u64 & mask) << (i * bits);
+                }
+                long = translated;
+            }
+            buf.write_long(long as i64);
+        }
+        Ok(())
+    }
+}
+
+impl Protocol for MappedProtocol {
+    fn version(&self) -> i32 {
+        self.version
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn serverbound_id(&self, state: &NetworkState, packet_id: i32) -> Option<i32> {
+        self.serverbound.map(state, packet_id)
+    }
+
+    fn translate_clientbound(
+        &self,
+        state: &NetworkState,
+        packet: &PacketEncoder,
+    ) -> Option<PacketEncoder> {
+        let packet_id = self.clientbound.map(state, packet.packet_id as i32)? as u32;
+        let buffer = match (state, packet.packet_id) {
+            (NetworkState::Configuration, REGISTRY_DATA_ID) => match &self.registry_data {
+                Some(registry_data) => Ok(registry_data.clone()),
+                None => Ok(packet.buffer.clone()),
+            },
+            (NetworkState::Play, BLOCK_UPDATE_ID) if !self.block_states.is_empty() => {
+                self.translate_block_update(&packet.buffer)
+            }
+            (NetworkState::Play, UPDATE_SECTION_BLOCKS_ID) if !self.block_states.is_empty() => {
+                self.translate_update_section_blocks(&packet.buffer)
+            }
+            (NetworkState::Play, CHUNK_DATA_ID) if !self.block_states.is_empty() => {
+                self.translate_chunk_data(&packet.buffer)
+            }
+            _ => Ok(packet.buffer.clone()),
+        };
+        match buffer {
+            Ok(buffer) => Some(PacketEncoder::new(buffer, packet_id)),
+            Err(err) => {
+                warn!(
+                    "Failed to translate packet {:#02x} for {}: {:?}",
+                    packet.packet_id, self.name, err
+                );
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn mapped_protocol_test() {
+    use crate::packets::clientbound::{
+        CBlockUpdate, CChunkData, CChunkDataSection, ClientBoundPacket,
+    };
+    use crate::packets::PalettedContainer;
+
+    let mut protocol = MappedProtocol {
+        version: 764,
+        name: "1.20.2".to_string(),
+        serverbound: PacketIdMapping::default(),
+        clientbound: PacketIdMapping::default(),
+        block_states: HashMap::from([(100, 200), (300, 301)]),
+        registry_data_file: None,
+        registry_data: None,
+    };
+    protocol.serverbound.play.insert(0x05, Some(0x04));
+    protocol.serverbound.play.insert(0x0E, None);
+    protocol.clientbound.play.insert(0x09, Some(0x0A));
+
+    assert_eq!(
+        protocol.serverbound_id(&NetworkState::Play, 0x05),
+        Some(0x04)
+    );
+    assert_eq!(protocol.serverbound_id(&NetworkState::Play, 0x0E), None);
+    assert_eq!(
+        protocol.serverbound_id(&NetworkState::Play, 0x15),
+        Some(0x15)
+    );
+    assert_eq!(
+        protocol.serverbound_id(&NetworkState::Login, 0x05),
+        Some(0x05)
+    );
+
+    let block_update = CBlockUpdate {
+        x: 1,
+        y: -5,
+        z: 3,
+        block_id: 100,
+    }
+    .encode();
+    let translated = protocol
+        .translate_clientbound(&NetworkState::Play, &block_update)
+        .unwrap();
+    let mut reader = Cursor::new(translated.buffer.as_slice());
+    assert_eq!(translated.packet_id, 0x0A);
+    assert_eq!(reader.read_position().unwrap(), (1, -5, 3));
+    assert_eq!(reader.read_varint().unwrap(), 200);
+
+    // One section with a palette and one with the block states stored directly
+    let mut direct = 0u64;
+    for (i, id) in [100u64, 300, 5, 100].into_iter().enumerate() {
+        direct |= id << (i * 15);
+    }
+    let biomes = || PalettedContainer {
+        bits_per_entry: 0,
+        palette: Some(vec![1]),
+        data_array: Vec::new(),
+    };
+    let chunk = CChunkData {
+        chunk_x: 0,
+        chunk_z: 0,
+        heightmaps: Default::default(),
+        chunk_sections: vec![
+            CChunkDataSection {
+                block_count: 1,
+                block_states: PalettedContainer {
+                    bits_per_entry: 4,
+                    palette: Some(vec![0, 100]),
+                    data_array: vec![0x10; 256],
+                },
+                biomes: biomes(),
+            },
+            CChunkDataSection {
+                block_count: 4,
+                block_states: PalettedContainer {
+                    bits_per_entry: 15,
+                    palette: None,
+                    data_array: vec![direct],
+                },
+                biomes: biomes(),
+            },
+        ],
+        block_entities: Vec::new(),
+    }
+    .encode();
+    let translated = protocol
+        .translate_clientbound(&NetworkState::Play, &chunk)
+        .unwrap();
+    let mut reader = Cursor::new(translated.buffer.as_slice());
+    reader.read_int().unwrap();
+    reader.read_int().unwrap();
+    reader.read_nbt_compound().unwrap();
+    reader.read_varint().unwrap();
+    // The first section
+    assert_eq!(reader.read_short().unwrap(), 1);
+    assert_eq!(reader.read_unsigned_byte().unwrap(), 4);
+    assert_eq!(reader.read_varint().unwrap(), 2);
+    assert_eq!(reader.read_varint().unwrap(), 0);
+    assert_eq!(reader.read_varint().unwrap(), 200);
+    assert_eq!(reader.read_varint().unwrap(), 256);
+    for _ in 0..256 {
+        assert_eq!(reader.read_long().unwrap(), 0x10);
+    }
+    assert_eq!(reader.read_unsigned_byte().unwrap(), 0);
+    assert_eq!(reader.read_varint().unwrap(), 1);
+    reader.read_varint().unwrap();
+    // The second section
+    assert_eq!(reader.read_short().unwrap(), 4);
+    assert_eq!(reader.read_unsigned_byte().unwrap(), 15);
+    assert_eq!(reader.read_varint().unwrap(), 1);
+    let long = reader.read_long().unwrap() as u64;
+    let ids: Vec<u64> = (0..4).map(|i| (long >> (i * 15)) & 0x7FFF).collect();
+    assert_eq!(ids, [200, 301, 5, 200]);
+}
@@ -0,0 +1,141 @@
+//! TCP load balancers like HAProxy can send the address of the client in a PROXY protocol header
+//! before anything else on the connection. Both the text format of version 1 and the binary format
+//! of version 2 are read.
+
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// How long a proxy may take to send the header
+pub(crate) const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// The longest a version 1 header can be, including the line break
+const V1_MAX_LEN: usize = 107;
+
+/// Accepting PROXY protocol headers on incoming connections
+#[derive(Debug, Clone, Default)]
+pub struct ProxyProtocol {
+    /// The addresses connections with a header are accepted from. If this is empty, every
+    /// connection is trusted to come from a proxy.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl ProxyProtocol {
+    pub fn trusts(&self, address: IpAddr) -> bool {
+        self.trusted_proxies.is_empty() || self.trusted_proxies.contains(&address)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads the header from the start of a connection. The address of the client is returned if the
+/// proxy forwarded one, which it doesn't for its own health checks.
+pub fn read_header(reader: &mut impl Read) -> io::Result<Option<SocketAddr>> {
+    let mut start = [0; 12];
+    reader.read_exact(&mut start)?;
+    if start == V2_SIGNATURE {
+        read_v2_header(reader)
+    } else if start.starts_with(b"PROXY ") {
+        read_v1_header(reader, &start)
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+fn read_v1_header(reader: &mut impl Read, start: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let mut line = start.to_vec();
+    // The header has to be read one byte at a time so that nothing after it is consumed
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY protocol header is too long"));
+        }
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        line.push(byte[0]);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY protocol header isn't text"))?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", source, _destination, source_port, _destination_port] => {
+            let ip = source
+                .parse::<IpAddr>()
+                .map_err(|_| invalid("invalid source address"))?;
+            let port = source_port
+                .parse::<u16>()
+                .map_err(|_| invalid("invalid source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("invalid PROXY protocol header")),
+    }
+}
+
+fn read_v2_header(reader: &mut impl Read) -> io::Result<Option<SocketAddr>> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header)?;
+    let [version_command, family, len @ ..] = header;
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let mut data = vec![0; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut data)?;
+
+    // Connections made by the proxy itself, like health checks, are LOCAL
+    if version_command & 0x0F == 0 {
+        return Ok(None);
+    }
+    let address = match family >> 4 {
+        // IPv4
+        1 if data.len() >= 12 => {
+            let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+            let port = u16::from_be_bytes([data[8], data[9]]);
+            SocketAddr::new(ip.into(), port)
+        }
+        // IPv6
+        2 if data.len() >= 36 => {
+            let ip: [u8; 16] = data[..16].try_into().unwrap();
+            let port = u16::from_be_bytes([data[32], data[33]]);
+            SocketAddr::new(Ipv6Addr::from(ip).into(), port)
+        }
+        // Unix sockets and unspecified addresses don't have an address we can use
+        _ => return Ok(None),
+    };
+    Ok(Some(address))
+}
+
+#[test]
+fn read_v1_header_test() {
+    let mut data =
+        io::Cursor::new(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 25565\r\n\x10".to_vec());
+    let address = read_header(&mut data).unwrap();
+    assert_eq!(address, Some("192.0.2.1:56324".parse().unwrap()));
+    // The packet after the header is left for the connection
+    assert_eq!(data.position(), data.get_ref().len() as u64 - 1);
+
+    let mut data = io::Cursor::new(b"PROXY UNKNOWN\r\n".to_vec());
+    assert_eq!(read_header(&mut data).unwrap(), None);
+    let mut data = io::Cursor::new(b"\x10\x00\xfd\x05\x09localhost".to_vec());
+    assert!(read_header(&mut data).is_err());
+}
+
+#[test]
+fn read_v2_header_test() {
+    let mut data = V2_SIGNATURE.to_vec();
+    data.extend([0x21, 0x11, 0, 12]);
+    data.extend([192, 0, 2, 1, 198, 51, 100, 1]);
+    data.extend(56324u16.to_be_bytes());
+    data.extend(25565u16.to_be_bytes());
+    data.push(0x10);
+    let mut data = io::Cursor::new(data);
+    let address = read_header(&mut data).unwrap();
+    assert_eq!(address, Some("192.0.2.1:56324".parse().unwrap()));
+    assert_eq!(data.position(), data.get_ref().len() as u64 - 1);
+
+    let mut data = V2_SIGNATURE.to_vec();
+    data.extend([0x20, 0x00, 0, 0]);
+    assert_eq!(read_header(&mut io::Cursor::new(data)).unwrap(), None);
+}
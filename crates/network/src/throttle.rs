@@ -0,0 +1,135 @@
+//! Limits on how fast clients can connect and send packets, so that bots flooding the server can't
+//! take it down. A limit of 0 means there is no limit.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CONNECTION_WINDOW: Duration = Duration::from_secs(60);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    /// How many times each address can connect per minute
+    pub connections_per_minute: u32,
+    /// Addresses that can connect as often as they like, such as forwarding proxies that every
+    /// player connects through
+    pub exempt_addresses: Vec<IpAddr>,
+    /// How many new connections are handled each second. The others wait in a queue.
+    pub logins_per_second: u32,
+    /// How many packets each connection can send per second before it is disconnected
+    pub packets_per_second: u32,
+}
+
+/// Counts events in fixed windows of time
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    window_start: Instant,
+    count: u32,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: u32, window: Duration) -> RateLimiter {
+        RateLimiter {
+            limit,
+            window,
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    pub(crate) fn per_second(limit: u32) -> RateLimiter {
+        RateLimiter::new(limit, RATE_WINDOW)
+    }
+
+    /// Whether the limit has been reached in the current window
+    pub(crate) fn is_limited(&mut self) -> bool {
+        if self.limit == 0 {
+            return false;
+        }
+        if self.window_start.elapsed() >= self.window {
+            self.window_start = Instant::now();
+            self.count = 0;
+        }
+        self.count >= self.limit
+    }
+
+    /// Counts an event, returning whether it is still within the limit
+    pub(crate) fn allow(&mut self) -> bool {
+        if self.is_limited() {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+/// Limits how often each address can connect
+#[derive(Debug)]
+pub(crate) struct ConnectionThrottle {
+    limit: u32,
+    exempt_addresses: Vec<IpAddr>,
+    addresses: Mutex<HashMap<IpAddr, RateLimiter>>,
+}
+
+impl ConnectionThrottle {
+    pub(crate) fn new(
+        connections_per_minute: u32,
+        exempt_addresses: Vec<IpAddr>,
+    ) -> ConnectionThrottle {
+        ConnectionThrottle {
+            limit: connections_per_minute,
+            exempt_addresses,
+            addresses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn allow(&self, address: IpAddr) -> bool {
+        if self.limit == 0 || self.exempt_addresses.contains(&address) {
+            return true;
+        }
+        let mut addresses = self.addresses.lock().unwrap();
+        // Forget addresses that haven't connected within the window
+        addresses.retain(|_, limiter| limiter.window_start.elapsed() < CONNECTION_WINDOW);
+        addresses
+            .entry(address)
+            .or_insert_with(|| RateLimiter::new(self.limit, CONNECTION_WINDOW))
+            .allow()
+    }
+}
+
+#[test]
+fn rate_limiter_test() {
+    let mut limiter = RateLimiter::new(2, Duration::from_millis(50));
+    assert!(limiter.allow());
+    assert!(limiter.allow());
+    assert!(!limiter.allow());
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(limiter.allow());
+
+    let mut unlimited = RateLimiter::per_second(0);
+    assert!((0..1000).all(|_| unlimited.allow()));
+}
+
+#[test]
+fn connection_throttle_test() {
+    let throttle = ConnectionThrottle::new(1, Vec::new());
+    let first: IpAddr = "192.0.2.1".parse().unwrap();
+    let second: IpAddr = "192.0.2.2".parse().unwrap();
+    assert!(throttle.allow(first));
+    assert!(!throttle.allow(first));
+    assert!(throttle.allow(second));
+}
+
+#[test]
+fn connection_throttle_exempt_test() {
+    let proxy: IpAddr = "192.0.2.1".parse().unwrap();
+    let other: IpAddr = "192.0.2.2".parse().unwrap();
+    let throttle = ConnectionThrottle::new(1, vec![proxy]);
+    assert!((0..100).all(|_| throttle.allow(proxy)));
+    assert!(throttle.allow(other));
+    assert!(!throttle.allow(other));
+}
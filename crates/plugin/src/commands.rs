@@ -0,0 +1,349 @@
+//! Commands registered by plugins. A command can declare its arguments, which the server sends
+//! to clients for tab completion and parses before the plugin gets them.
+
+use crate::PlotHandle;
+use mchprs_blocks::BlockPos;
+
+/// What an argument accepts
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgType {
+    /// A single word
+    Word,
+    /// The rest of the command. Has to be the last argument.
+    Text,
+    Integer {
+        min: i32,
+        max: i32,
+    },
+    Float {
+        min: f32,
+        max: f32,
+    },
+    /// Three coordinates, which can be relative to the player with `~`
+    BlockPos,
+    /// The name of a player
+    Player,
+    /// One of a list of words
+    Choice(Vec<String>),
+}
+
+impl ArgType {
+    /// The name of the type in usage strings
+    fn usage_name(&self) -> String {
+        match self {
+            ArgType::Word => "word".to_string(),
+            ArgType::Text => "text".to_string(),
+            ArgType::Integer { .. } => "int".to_string(),
+            ArgType::Float { .. } => "float".to_string(),
+            ArgType::BlockPos => "pos".to_string(),
+            ArgType::Player => "player".to_string(),
+            ArgType::Choice(choices) => choices.join("|"),
+        }
+    }
+
+    fn from_usage_name(name: &str) -> Option<ArgType> {
+        Some(match name {
+            "word" => ArgType::Word,
+            "text" => ArgType::Text,
+            "int" => ArgType::Integer {
+                min: i32::MIN,
+                max: i32::MAX,
+            },
+            "float" => ArgType::Float {
+                min: f32::MIN,
+                max: f32::MAX,
+            },
+            "pos" => ArgType::BlockPos,
+            "player" => ArgType::Player,
+            _ if name.contains('|') => {
+                ArgType::Choice(name.split('|').map(str::to_string).collect())
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgSpec {
+    pub name: String,
+    pub ty: ArgType,
+    /// Optional arguments can only be followed by other optional arguments
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    pub fn new(name: impl Into<String>, ty: ArgType) -> ArgSpec {
+        ArgSpec {
+            name: name.into(),
+            ty,
+            optional: false,
+        }
+    }
+
+    pub fn optional(name: impl Into<String>, ty: ArgType) -> ArgSpec {
+        ArgSpec {
+            optional: true,
+            ..ArgSpec::new(name, ty)
+        }
+    }
+}
+
+/// A parsed argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    /// Words, text, players and choices
+    String(String),
+    Integer(i32),
+    Float(f32),
+    BlockPos(BlockPos),
+}
+
+/// A command registered by a plugin
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    pub name: String,
+    pub description: String,
+    /// The arguments of the command, or `None` if the plugin parses them itself, in which case
+    /// anything is accepted after the command
+    pub args: Option<Vec<ArgSpec>>,
+    /// The permission node players need to run the command
+    pub permission: Option<String>,
+}
+
+impl CommandSpec {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> CommandSpec {
+        CommandSpec {
+            name: name.into(),
+            description: description.into(),
+            args: None,
+            permission: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: ArgSpec) -> CommandSpec {
+        self.args.get_or_insert_with(Vec::new).push(arg);
+        self
+    }
+
+    pub fn permission(mut self, permission: impl Into<String>) -> CommandSpec {
+        self.permission = Some(permission.into());
+        self
+    }
+
+    /// Declares the arguments with a usage string, like `<pos:pos> <mode:on|off> [count:int]`.
+    /// Arguments in `<>` are required and those in `[]` are optional. The types are `word`,
+    /// `text`, `int`, `float`, `pos`, `player`, or a list of choices separated by `|`.
+    pub fn usage(mut self, usage: &str) -> Result<CommandSpec, String> {
+        let mut args = Vec::new();
+        for part in usage.split_whitespace() {
+            let (inner, optional) = if let Some(inner) = part.strip_prefix('<') {
+                (inner.strip_suffix('>'), false)
+            } else if let Some(inner) = part.strip_prefix('[') {
+                (inner.strip_suffix(']'), true)
+            } else {
+                (None, false)
+            };
+            let inner = inner.ok_or_else(|| format!("`{}` is not in <> or []", part))?;
+            let (name, ty) = inner.split_once(':').unwrap_or((inner, "word"));
+            let ty = ArgType::from_usage_name(ty)
+                .ok_or_else(|| format!("`{}` is not an argument type", ty))?;
+            args.push(ArgSpec {
+                name: name.to_string(),
+                ty,
+                optional,
+            });
+        }
+        self.args = Some(args);
+        self.check()?;
+        Ok(self)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let args = self.args.as_deref().unwrap_or_default();
+        for (idx, arg) in args.iter().enumerate() {
+            if arg.ty == ArgType::Text && idx + 1 != args.len() {
+                return Err(format!("`{}` takes the rest of the command", arg.name));
+            }
+            if !arg.optional && idx > 0 && args[idx - 1].optional {
+                return Err(format!("`{}` comes after an optional argument", arg.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// How the command is used, like `/fill <pos:pos> [count:int]`
+    pub fn usage_string(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for arg in self.args.iter().flatten() {
+            let (open, close) = if arg.optional { ('[', ']') } else { ('<', '>') };
+            usage += &format!(" {}{}:{}{}", open, arg.name, arg.ty.usage_name(), close);
+        }
+        usage
+    }
+
+    /// Parses the arguments a player gave. Relative coordinates are relative to `origin`.
+    pub fn parse(&self, raw: &[&str], origin: BlockPos) -> Result<CommandArgs, String> {
+        let mut parsed = CommandArgs {
+            raw: raw.iter().map(|arg| arg.to_string()).collect(),
+            values: Vec::new(),
+        };
+        let Some(specs) = &self.args else {
+            return Ok(parsed);
+        };
+        let mut rest = raw;
+        for spec in specs {
+            if rest.is_empty() {
+                if spec.optional {
+                    break;
+                }
+                return Err(format!("Missing argument `{}`", spec.name));
+            }
+            let value = match &spec.ty {
+                ArgType::Text => {
+                    let value = ArgValue::String(rest.join(" "));
+                    rest = &[];
+                    value
+                }
+                ArgType::BlockPos => {
+                    let [x, y, z, ..] = rest else {
+                        return Err(format!("`{}` needs three coordinates", spec.name));
+                    };
+                    let coord = |coord: &str, origin: i32| -> Result<i32, String> {
+                        let parsed = match coord.strip_prefix('~') {
+                            Some("") => Ok(origin),
+                            Some(offset) => offset.parse::<i32>().map(|offset| origin + offset),
+                            None => coord.parse(),
+                        };
+                        parsed.map_err(|_| format!("`{}` is not a coordinate", coord))
+                    };
+                    let pos = BlockPos::new(
+                        coord(x, origin.x)?,
+                        coord(y, origin.y)?,
+                        coord(z, origin.z)?,
+                    );
+                    rest = &rest[3..];
+                    ArgValue::BlockPos(pos)
+                }
+                ty => {
+                    let word = rest[0];
+                    rest = &rest[1..];
+                    parse_word(ty, word)
+                        .ok_or_else(|| format!("`{}` is not a valid {}", word, ty.usage_name()))?
+                }
+            };
+            parsed.values.push((spec.name.clone(), value));
+        }
+        if !rest.is_empty() {
+            return Err("Too many arguments".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_word(ty: &ArgType, word: &str) -> Option<ArgValue> {
+    Some(match ty {
+        ArgType::Integer { min, max } => {
+            let value = word.parse().ok()?;
+            ArgValue::Integer(Some(value).filter(|value| (min..=max).contains(&value))?)
+        }
+        ArgType::Float { min, max } => {
+            let value = word.parse().ok()?;
+            ArgValue::Float(Some(value).filter(|value| (min..=max).contains(&value))?)
+        }
+        ArgType::Choice(choices) => {
+            ArgValue::String(choices.iter().find(|choice| *choice == word)?.clone())
+        }
+        _ => ArgValue::String(word.to_string()),
+    })
+}
+
+/// The arguments a command was run with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandArgs {
+    /// The arguments as they were typed, split on spaces
+    pub raw: Vec<String>,
+    /// The parsed arguments by name, for commands that declare them
+    pub values: Vec<(String, ArgValue)>,
+}
+
+impl CommandArgs {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values
+            .iter()
+            .find(|(arg, _)| arg == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            ArgValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            ArgValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            ArgValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn pos(&self, name: &str) -> Option<BlockPos> {
+        match self.get(name)? {
+            ArgValue::BlockPos(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// The player that ran a command and the plot they are on
+pub struct CommandSender<'a> {
+    pub username: &'a str,
+    pub uuid: u128,
+    pub plot: &'a mut dyn PlotHandle,
+}
+
+impl CommandSender<'_> {
+    /// Sends a message to the player that ran the command
+    pub fn reply(&mut self, message: &str) {
+        self.plot.send_message(self.username, message);
+    }
+}
+
+#[test]
+fn parse_command_args() {
+    let spec = CommandSpec::new("fill", "")
+        .usage("<from:pos> <mode:on|off> [count:int] [note:text]")
+        .unwrap();
+    assert_eq!(
+        spec.usage_string(),
+        "/fill <from:pos> <mode:on|off> [count:int] [note:text]"
+    );
+
+    let origin = BlockPos::new(10, 20, 30);
+    let args = spec.parse(&["~", "~1", "5", "on"], origin).unwrap();
+    assert_eq!(args.pos("from"), Some(BlockPos::new(10, 21, 5)));
+    assert_eq!(args.string("mode"), Some("on"));
+    assert_eq!(args.integer("count"), None);
+
+    let args = spec
+        .parse(&["0", "0", "0", "off", "3", "a", "b"], origin)
+        .unwrap();
+    assert_eq!(args.integer("count"), Some(3));
+    assert_eq!(args.string("note"), Some("a b"));
+
+    assert!(spec.parse(&["0", "0", "0"], origin).is_err());
+    assert!(spec.parse(&["0", "0", "0", "maybe"], origin).is_err());
+    assert!(spec.parse(&["0", "0", "x", "on"], origin).is_err());
+
+    assert!(CommandSpec::new("bad", "").usage("[a] <b>").is_err());
+    assert!(CommandSpec::new("bad", "").usage("<a:text> <b>").is_err());
+    assert!(CommandSpec::new("bad", "").usage("<a:thing>").is_err());
+}
@@ -0,0 +1,208 @@
+//! A server-wide event bus. Everything that is passed to `Plugin::on_event` is also published on
+//! the bus, where code can subscribe to just the events it cares about.
+//!
+//! Synchronous subscribers run on the thread that published the event before it carries on, so
+//! they should be quick. Asynchronous subscribers run one after the other on a thread of the bus,
+//! so slow work like network requests doesn't hold up plots.
+
+use crate::events::*;
+use crate::Event;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+type Handler = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// An event that can be subscribed to on its own
+pub trait BusEvent: Sized + 'static {
+    fn from_event(event: &Event) -> Option<&Self>;
+}
+
+macro_rules! bus_events {
+    ($($name:ident),*) => {
+        $(
+            impl BusEvent for $name {
+                fn from_event(event: &Event) -> Option<&Self> {
+                    match event {
+                        Event::$name(event) => Some(event),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+bus_events!(
+    PlayerJoin,
+    PlayerLeave,
+    Chat,
+    BlockPlace,
+    BlockBreak,
+    PlotLoad,
+    PlotUnload,
+    OutputChange,
+    ChunkLoad,
+    CompileStart,
+    CompileFinish,
+    CommandRun
+);
+
+/// Identifies a subscription, so that it can be removed again with [`EventBus::unsubscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+#[derive(Default)]
+pub struct EventBus {
+    next_id: AtomicU64,
+    sync: RwLock<Vec<(SubscriptionId, Handler)>>,
+    /// Shared with the thread that runs the asynchronous subscribers
+    asynchronous: Arc<RwLock<Vec<(SubscriptionId, Handler)>>>,
+    /// Started with the first asynchronous subscriber
+    worker: Mutex<Option<Sender<Event>>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        Default::default()
+    }
+
+    fn next_id(&self) -> SubscriptionId {
+        SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn typed<E: BusEvent>(handler: impl Fn(&E) + Send + Sync + 'static) -> Handler {
+        Arc::new(move |event| {
+            if let Some(event) = E::from_event(event) {
+                handler(event);
+            }
+        })
+    }
+
+    /// Runs `handler` for every event of type `E` on the thread that publishes it
+    pub fn subscribe<E: BusEvent>(
+        &self,
+        handler: impl Fn(&E) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.add_sync(Self::typed(handler))
+    }
+
+    /// Runs `handler` for every event on the thread that publishes it
+    pub fn subscribe_all(
+        &self,
+        handler: impl Fn(&Event) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.add_sync(Arc::new(handler))
+    }
+
+    fn add_sync(&self, handler: Handler) -> SubscriptionId {
+        let id = self.next_id();
+        self.sync.write().unwrap().push((id, handler));
+        id
+    }
+
+    /// Runs `handler` for every event of type `E` on the thread of the bus
+    pub fn subscribe_async<E: BusEvent>(
+        &self,
+        handler: impl Fn(&E) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.add_async(Self::typed(handler))
+    }
+
+    /// Runs `handler` for every event on the thread of the bus
+    pub fn subscribe_all_async(
+        &self,
+        handler: impl Fn(&Event) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.add_async(Arc::new(handler))
+    }
+
+    fn add_async(&self, handler: Handler) -> SubscriptionId {
+        let mut worker = self.worker.lock().unwrap();
+        if worker.is_none() {
+            let (sender, receiver) = mpsc::channel::<Event>();
+            let handlers = self.asynchronous.clone();
+            thread::Builder::new()
+                .name("Event bus".to_string())
+                .spawn(move || {
+                    for event in receiver {
+                        let handlers = handlers.read().unwrap().clone();
+                        for (_, handler) in handlers {
+                            handler(&event);
+                        }
+                    }
+                })
+                .expect("failed to spawn event bus thread");
+            *worker = Some(sender);
+        }
+        let id = self.next_id();
+        self.asynchronous.write().unwrap().push((id, handler));
+        id
+    }
+
+    /// Removes a subscription. Returns false if it was already removed.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        for handlers in [&self.sync, &*self.asynchronous] {
+            let mut handlers = handlers.write().unwrap();
+            if let Some(idx) = handlers
+                .iter()
+                .position(|(handler_id, _)| *handler_id == id)
+            {
+                handlers.remove(idx);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Runs the synchronous subscribers and queues the event for the asynchronous ones.
+    /// Subscribers may subscribe, unsubscribe and publish themselves.
+    pub fn publish(&self, event: &Event) {
+        // The handlers are cloned so that the lock isn't held while they run
+        let handlers = self.sync.read().unwrap().clone();
+        for (_, handler) in handlers {
+            handler(event);
+        }
+        if !self.asynchronous.read().unwrap().is_empty() {
+            if let Some(worker) = &*self.worker.lock().unwrap() {
+                let _ = worker.send(event.clone());
+            }
+        }
+    }
+}
+
+#[test]
+fn event_bus() {
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    let bus = EventBus::new();
+    let chats = Arc::new(AtomicUsize::new(0));
+    let id = bus.subscribe({
+        let chats = chats.clone();
+        move |chat: &Chat| {
+            assert_eq!(chat.message, "hi");
+            chats.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    bus.subscribe_async(move |load: &PlotLoad| {
+        sender.lock().unwrap().send(load.x).unwrap();
+    });
+
+    let chat = Event::Chat(Chat {
+        username: "Player".to_string(),
+        message: "hi".to_string(),
+    });
+    bus.publish(&chat);
+    bus.publish(&Event::PlotLoad(PlotLoad { x: 3, z: 0 }));
+    assert_eq!(chats.load(Ordering::Relaxed), 1);
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(3));
+
+    assert!(bus.unsubscribe(id));
+    assert!(!bus.unsubscribe(id));
+    bus.publish(&chat);
+    assert_eq!(chats.load(Ordering::Relaxed), 1);
+}
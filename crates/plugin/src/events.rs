@@ -0,0 +1,122 @@
+//! The events plugins are told about in `Plugin::on_event`
+
+use mchprs_blocks::BlockPos;
+
+#[derive(Debug, Clone)]
+pub struct PlayerJoin {
+    pub username: String,
+    pub uuid: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerLeave {
+    pub username: String,
+    pub uuid: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chat {
+    pub username: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockPlace {
+    pub username: String,
+    pub pos: BlockPos,
+    /// The block state id of the placed block
+    pub block: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockBreak {
+    pub username: String,
+    pub pos: BlockPos,
+    /// The block state id of the block before it was broken
+    pub block: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlotLoad {
+    pub x: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlotUnload {
+    pub x: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputChange {
+    pub pos: BlockPos,
+    /// The new signal strength of the component
+    pub power: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkLoad {
+    /// The chunk coordinates, not the block coordinates
+    pub x: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileStart {
+    /// The plot coordinates
+    pub x: i32,
+    pub z: i32,
+    /// The flags redpiler was started with, like `--optimize`
+    pub flags: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileFinish {
+    /// The plot coordinates
+    pub x: i32,
+    pub z: i32,
+    pub duration_ms: u64,
+    pub warnings: usize,
+    pub optimize: bool,
+    pub io_only: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandRun {
+    pub username: String,
+    /// The command as the player typed it, without the slash
+    pub command: String,
+}
+
+/// New events may be added without changing the api version, so plugins should ignore events they
+/// don't know about.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    /// Sent on the server thread
+    PlayerJoin(PlayerJoin),
+    /// Sent on the server thread
+    PlayerLeave(PlayerLeave),
+    /// Sent on the server thread
+    Chat(Chat),
+    /// Sent on the thread of the plot the block was placed on
+    BlockPlace(BlockPlace),
+    /// Sent on the thread of the plot the block was broken on
+    BlockBreak(BlockBreak),
+    /// Sent on the thread of the plot after it was loaded
+    PlotLoad(PlotLoad),
+    /// Sent on the thread of the plot before it is unloaded
+    PlotUnload(PlotUnload),
+    /// Sent on the thread of the plot when a component watched with `PlotHandle::watch_output`
+    /// changes while redpiler is running
+    OutputChange(OutputChange),
+    /// Sent on the thread of the plot for each of its chunks after it was loaded
+    ChunkLoad(ChunkLoad),
+    /// Sent on the thread of the plot before redpiler compiles it
+    CompileStart(CompileStart),
+    /// Sent on the thread of the plot after redpiler compiled it
+    CompileFinish(CompileFinish),
+    /// Sent on the thread of the plot the player is on before the command runs
+    CommandRun(CommandRun),
+}
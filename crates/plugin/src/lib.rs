@@ -0,0 +1,147 @@
+//! The API that plugins for MCHPRS are written against. A plugin is a dynamic library (a `cdylib`)
+//! which exports a [`Plugin`] with [`declare_plugin!`]. The server loads every library in its
+//! `plugins` directory at startup.
+//!
+//! Plugins are shared between the server thread and the thread of every plot, so they have to be
+//! `Send` and `Sync` and keep their state behind locks. Events that happen on a plot are handled on
+//! the thread of that plot, and get a [`PlotHandle`] to look at and change it with. Plugins that
+//! only care about a few kinds of events can subscribe to them on the [`EventBus`] instead.
+//!
+//! Rust has no stable ABI, so plugins have to be built with the same compiler as the server and
+//! against the same version of this crate. Plugins built against another [`API_VERSION`] are not
+//! loaded.
+
+pub mod commands;
+pub mod event_bus;
+pub mod events;
+pub mod storage;
+pub mod tasks;
+
+pub use commands::{ArgSpec, ArgType, ArgValue, CommandArgs, CommandSender, CommandSpec};
+pub use event_bus::EventBus;
+pub use events::Event;
+pub use mchprs_blocks::BlockPos;
+pub use storage::{Storage, StorageScope};
+pub use tasks::{MainTask, PlotTask, TaskHandle};
+
+use std::sync::Arc;
+
+/// Changes whenever the API changes in a way that breaks plugins
+pub const API_VERSION: u32 = 7;
+
+/// The name of the function `declare_plugin!` exports to create the plugin with
+#[doc(hidden)]
+pub const CREATE_SYMBOL: &[u8] = b"_mchprs_plugin_create";
+/// The name of the function `declare_plugin!` exports the api version with
+#[doc(hidden)]
+pub const API_VERSION_SYMBOL: &[u8] = b"_mchprs_plugin_api_version";
+
+pub trait Plugin: Send + Sync {
+    /// The name of the plugin, used in logs
+    fn name(&self) -> &str;
+
+    /// Called once on the server thread after all plugins are loaded. The handle can be kept to
+    /// talk to the server from anywhere later on.
+    fn on_enable(&self, _server: Arc<dyn ServerHandle>) {}
+
+    /// Called on the server thread when the server stops
+    fn on_disable(&self) {}
+
+    /// Called for everything that happens on the server. `plot` is the plot the event happened on,
+    /// or `None` for events on the server thread.
+    fn on_event(&self, _event: &Event, _plot: Option<&mut dyn PlotHandle>) {}
+
+    /// The commands the plugin handles. They are added to the commands players can run, and their
+    /// arguments to the tab completion of clients.
+    fn commands(&self) -> Vec<CommandSpec> {
+        Vec::new()
+    }
+
+    /// Called on the plot thread of the player when they run one of the plugin's commands. The
+    /// server checks the permission of the command and parses its arguments first.
+    fn on_command(&self, _command: &str, _args: &CommandArgs, _sender: &mut CommandSender<'_>) {}
+}
+
+/// Lets plugins talk to the server from any thread. Messages use `&` color codes.
+pub trait ServerHandle: Send + Sync {
+    /// Sends a message to everyone on the server
+    fn broadcast(&self, message: &str);
+    /// Sends a message to a player, wherever they are
+    fn send_message(&self, username: &str, message: &str);
+    /// Runs a console command, like `say` or `kick`
+    fn run_console_command(&self, command: &str);
+    /// The event bus of the server, where every event is published
+    fn events(&self) -> &EventBus;
+    /// Runs `task` on the main thread of the server after `delay` game ticks, and then every
+    /// `period` game ticks if it has one
+    fn schedule(&self, delay: u64, period: Option<u64>, task: MainTask) -> TaskHandle;
+    /// Runs `task` on the thread of the plot at plot coordinates `x` and `z` after `delay` game
+    /// ticks, and then every `period` game ticks if it has one. Tasks only run while the plot is
+    /// loaded, and wait for it otherwise.
+    fn schedule_on_plot(
+        &self,
+        x: i32,
+        z: i32,
+        delay: u64,
+        period: Option<u64>,
+        task: PlotTask,
+    ) -> TaskHandle;
+    /// The storage of a plugin for a scope, which is kept across restarts. Plugins should pass
+    /// their own name.
+    fn storage(&self, plugin: &str, scope: StorageScope) -> Arc<dyn Storage>;
+}
+
+/// A plot, which events that happen on it get to look at and change it with. Messages use `&`
+/// color codes.
+pub trait PlotHandle {
+    /// The plot coordinates, not the block coordinates
+    fn x(&self) -> i32;
+    fn z(&self) -> i32;
+    /// The block state id at a position, or `None` if the position is outside of the plot
+    fn get_block(&self, pos: BlockPos) -> Option<u32>;
+    /// Sets the block state at a position. Returns false if the position is outside of the plot.
+    fn set_block(&mut self, pos: BlockPos, block: u32) -> bool;
+    /// Flips a lever or presses a button, also while redpiler is running. Returns false if there
+    /// is no lever or button at the position.
+    fn use_block(&mut self, pos: BlockPos) -> bool;
+    /// The signal strength of a component in the circuit redpiler compiled, or `None` if redpiler
+    /// isn't running or there is no component at the position. Lamps, trapdoors and note blocks
+    /// read 15 while powered.
+    fn read_output(&self, pos: BlockPos) -> Option<u8>;
+    /// Turns a lever or pressure plate on or off, or presses a button, in the circuit redpiler
+    /// compiled. Returns false if redpiler isn't running or there is no input at the position.
+    fn set_input(&mut self, pos: BlockPos, powered: bool) -> bool;
+    /// The position of the component a sign with `name` on its first line is placed on, or `None`
+    /// if there is no such sign on the plot
+    fn find_named(&self, name: &str) -> Option<BlockPos>;
+    /// Sends an [`Event::OutputChange`] whenever the output of the component at a position changes
+    /// while redpiler is running. Watches last until the plot is unloaded.
+    fn watch_output(&mut self, pos: BlockPos);
+    /// The names of the players on the plot
+    fn players(&self) -> Vec<String>;
+    /// Sends a message to a player on the plot
+    fn send_message(&mut self, username: &str, message: &str);
+}
+
+/// Exports a plugin from a dynamic library, so that the server can load it. Takes an expression
+/// creating the plugin.
+///
+/// ```ignore
+/// mchprs_plugin::declare_plugin!(MyPlugin::default());
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($plugin:expr) => {
+        #[no_mangle]
+        pub extern "C" fn _mchprs_plugin_api_version() -> u32 {
+            $crate::API_VERSION
+        }
+
+        /// Returns a `Box<Box<dyn Plugin>>`, since trait objects can't cross the boundary as is
+        #[no_mangle]
+        pub extern "C" fn _mchprs_plugin_create() -> *mut ::std::ffi::c_void {
+            let plugin: ::std::boxed::Box<dyn $crate::Plugin> = ::std::boxed::Box::new($plugin);
+            ::std::boxed::Box::into_raw(::std::boxed::Box::new(plugin)) as *mut ::std::ffi::c_void
+        }
+    };
+}
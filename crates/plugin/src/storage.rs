@@ -0,0 +1,28 @@
+//! Persistent key/value storage plugins get with `ServerHandle::storage`
+
+/// What a storage belongs to. Every plugin has its own storage for each scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageScope {
+    /// The plugin itself
+    Plugin,
+    /// The plot at plot coordinates `x` and `z`
+    Plot { x: i32, z: i32 },
+    /// The player with this uuid
+    Player(u128),
+}
+
+/// Values stored by key, which are kept across restarts. The server saves changes in the
+/// background, so storage can be written to often.
+pub trait Storage: Send + Sync {
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+    fn set(&self, key: &str, value: &[u8]);
+    /// Returns false if there was no value for the key
+    fn remove(&self, key: &str) -> bool;
+    /// The keys in alphabetical order
+    fn keys(&self) -> Vec<String>;
+
+    /// The value as a string, or `None` if it isn't UTF-8
+    fn get_string(&self, key: &str) -> Option<String> {
+        String::from_utf8(self.get(key)?).ok()
+    }
+}
@@ -0,0 +1,37 @@
+//! Tasks plugins schedule with `ServerHandle::schedule` and `ServerHandle::schedule_on_plot`
+
+use crate::PlotHandle;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a game tick is. Delays and periods of tasks are counted in game ticks, no matter how
+/// fast the redstone on a plot is ticking.
+pub const GAME_TICK: Duration = Duration::from_millis(50);
+
+/// A task that runs on the main thread of the server
+pub type MainTask = Box<dyn FnMut() + Send>;
+/// A task that runs on the thread of a plot
+pub type PlotTask = Box<dyn FnMut(&mut dyn PlotHandle) + Send>;
+
+/// Cancels a scheduled task. Clones of a handle refer to the same task.
+#[derive(Debug, Clone, Default)]
+pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskHandle {
+    pub fn new() -> TaskHandle {
+        Default::default()
+    }
+
+    /// Stops the task from running again. If it is running right now, that run still finishes.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// False once the task was cancelled, or after it ran if it doesn't repeat
+    pub fn is_active(&self) -> bool {
+        !self.cancelled.load(Ordering::Relaxed)
+    }
+}
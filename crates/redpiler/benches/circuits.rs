@@ -0,0 +1,401 @@
+//! Benchmarks redpiler on circuits like the ones it runs on plots: compile time, ticks per second
+//! and how much memory a compile takes. The circuits are generated rather than loaded from
+//! schematics so that the numbers don't depend on files outside of the repository:
+//!
+//! - `alu`: lanes of comparators in compare and subtract mode, with levers on their sides like
+//!   the operands of an ALU
+//! - `cpu`: long chains of torch inverters and repeaters, like the control logic of a CPU
+//! - `song`: tracks of repeaters that play note blocks, like a note block song player
+//!
+//! Run with `cargo bench -p mchprs_redpiler`. The memory a compile takes is printed before the
+//! benchmarks start.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::blocks::{
+    Block, ComparatorMode, Instrument, Lever, LeverFace, RedstoneComparator, RedstoneRepeater,
+};
+use mchprs_blocks::{BlockDirection, BlockPos};
+use mchprs_redpiler::{Compiler, CompilerOptions};
+use mchprs_redstone::wire;
+use mchprs_world::storage::Chunk;
+use mchprs_world::{TickEntry, TickPriority, World};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the bytes allocated, to measure the memory a compile takes
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// The size of the world in chunks
+const WORLD_CHUNKS: i32 = 8;
+/// How many ticks are run per iteration of the tick benchmarks
+const TICKS: u64 = 1000;
+/// How often the inputs of a circuit are used while ticking
+const INPUT_PERIOD: u64 = 20;
+const FLAGS: [&str; 2] = ["", "-io"];
+
+struct BenchWorld {
+    chunks: Vec<Chunk>,
+    to_be_ticked: Vec<TickEntry>,
+}
+
+impl BenchWorld {
+    fn new() -> BenchWorld {
+        let mut chunks = Vec::new();
+        for x in 0..WORLD_CHUNKS {
+            for z in 0..WORLD_CHUNKS {
+                chunks.push(Chunk::empty(x, z, 1));
+            }
+        }
+        BenchWorld {
+            chunks,
+            to_be_ticked: Vec::new(),
+        }
+    }
+
+    fn bounds() -> (BlockPos, BlockPos) {
+        let max = WORLD_CHUNKS * 16 - 1;
+        (BlockPos::new(0, 0, 0), BlockPos::new(max, 15, max))
+    }
+
+    fn get_chunk_index_for_block(&self, block_x: i32, block_z: i32) -> Option<usize> {
+        let chunk_x = block_x >> 4;
+        let chunk_z = block_z >> 4;
+        if !(0..WORLD_CHUNKS).contains(&chunk_x) || !(0..WORLD_CHUNKS).contains(&chunk_z) {
+            return None;
+        }
+        Some((chunk_x * WORLD_CHUNKS + chunk_z) as usize)
+    }
+
+    /// Runs a tick without redpiler, which is how circuits settle after being built
+    fn tick(&mut self) {
+        self.to_be_ticked
+            .sort_by_key(|e| (e.ticks_left, e.tick_priority));
+        for pending in &mut self.to_be_ticked {
+            pending.ticks_left = pending.ticks_left.saturating_sub(1);
+        }
+        while self.to_be_ticked.first().map_or(1, |e| e.ticks_left) == 0 {
+            let entry = self.to_be_ticked.remove(0);
+            mchprs_redstone::tick(self.get_block(entry.pos), self, entry.pos);
+        }
+    }
+}
+
+impl World for BenchWorld {
+    fn get_block_raw(&self, pos: BlockPos) -> u32 {
+        match self.get_chunk_index_for_block(pos.x, pos.z) {
+            Some(idx) if (0..16).contains(&pos.y) => {
+                self.chunks[idx].get_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32)
+            }
+            _ => 0,
+        }
+    }
+
+    fn set_block_raw(&mut self, pos: BlockPos, block: u32) -> bool {
+        match self.get_chunk_index_for_block(pos.x, pos.z) {
+            Some(idx) if (0..16).contains(&pos.y) => {
+                self.chunks[idx].set_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32, block)
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_block_entity(&mut self, pos: BlockPos) {
+        if let Some(idx) = self.get_chunk_index_for_block(pos.x, pos.z) {
+            self.chunks[idx].delete_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF));
+        }
+    }
+
+    fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        let idx = self.get_chunk_index_for_block(pos.x, pos.z)?;
+        self.chunks[idx].get_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF))
+    }
+
+    fn set_block_entity(&mut self, pos: BlockPos, block_entity: BlockEntity) {
+        if let Some(idx) = self.get_chunk_index_for_block(pos.x, pos.z) {
+            self.chunks[idx]
+                .set_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF), block_entity);
+        }
+    }
+
+    fn get_chunk(&self, x: i32, z: i32) -> Option<&Chunk> {
+        let idx = self.get_chunk_index_for_block(x << 4, z << 4)?;
+        self.chunks.get(idx)
+    }
+
+    fn get_chunk_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        let idx = self.get_chunk_index_for_block(x << 4, z << 4)?;
+        self.chunks.get_mut(idx)
+    }
+
+    fn schedule_tick(&mut self, pos: BlockPos, delay: u32, priority: TickPriority) {
+        self.to_be_ticked.push(TickEntry {
+            pos,
+            ticks_left: delay,
+            tick_priority: priority,
+        });
+    }
+
+    fn pending_tick_at(&mut self, pos: BlockPos) -> bool {
+        self.to_be_ticked.iter().any(|e| e.pos == pos)
+    }
+}
+
+/// A circuit built on a sandstone floor at y = 0
+struct Circuit {
+    name: &'static str,
+    world: BenchWorld,
+    /// The levers that are flipped while ticking
+    inputs: Vec<BlockPos>,
+}
+
+/// Places the blocks of a circuit at y = 1 and lets it settle, so that it starts out like it
+/// would after being built by hand
+fn build(name: &'static str, blocks: Vec<(BlockPos, Block)>) -> Circuit {
+    let mut world = BenchWorld::new();
+    let size = WORLD_CHUNKS * 16;
+    for x in 0..size {
+        for z in 0..size {
+            world.set_block(BlockPos::new(x, 0, z), Block::Sandstone {});
+        }
+    }
+    for &(pos, block) in &blocks {
+        world.set_block(pos, block);
+    }
+    for &(pos, block) in &blocks {
+        if let Block::RedstoneWire { .. } = block {
+            let wire = wire::get_state_for_placement(&world, pos);
+            world.set_block(pos, Block::RedstoneWire { wire });
+        }
+    }
+    for &(pos, _) in &blocks {
+        mchprs_redstone::update(world.get_block(pos), &mut world, pos);
+    }
+    for _ in 0..1000 {
+        if world.to_be_ticked.is_empty() {
+            break;
+        }
+        world.tick();
+    }
+
+    let inputs = blocks
+        .iter()
+        .filter(|(_, block)| matches!(block, Block::Lever { .. }))
+        .map(|&(pos, _)| pos)
+        .collect();
+    Circuit {
+        name,
+        world,
+        inputs,
+    }
+}
+
+fn lever() -> Block {
+    Block::Lever {
+        lever: Lever {
+            face: LeverFace::Floor,
+            ..Default::default()
+        },
+    }
+}
+
+/// Repeaters face the side they take their input from, so these point along +x
+fn repeater(delay: u8) -> Block {
+    Block::RedstoneRepeater {
+        repeater: RedstoneRepeater {
+            delay,
+            facing: BlockDirection::West,
+            ..Default::default()
+        },
+    }
+}
+
+fn alu() -> Circuit {
+    let mut blocks = Vec::new();
+    for lane in 0..32 {
+        let z = lane * 3;
+        blocks.push((BlockPos::new(0, 1, z), lever()));
+        for x in 1..120 {
+            let pos = BlockPos::new(x, 1, z);
+            if x % 2 == 1 {
+                blocks.push((
+                    pos,
+                    Block::RedstoneWire {
+                        wire: Default::default(),
+                    },
+                ));
+                continue;
+            }
+            let mode = match (x / 2 + lane) % 3 {
+                0 => ComparatorMode::Subtract,
+                _ => ComparatorMode::Compare,
+            };
+            let comparator = RedstoneComparator {
+                mode,
+                facing: BlockDirection::West,
+                ..Default::default()
+            };
+            blocks.push((pos, Block::RedstoneComparator { comparator }));
+            if x % 8 == 2 {
+                blocks.push((BlockPos::new(x, 1, z + 1), lever()));
+            }
+        }
+    }
+    build("alu", blocks)
+}
+
+fn cpu() -> Circuit {
+    let mut blocks = Vec::new();
+    for lane in 0..64 {
+        let z = lane * 2;
+        blocks.push((BlockPos::new(0, 1, z), lever()));
+        // An inverter is a repeater powering a block with a torch on its other side
+        for stage in 0..40 {
+            let x = 1 + stage * 3;
+            blocks.push((BlockPos::new(x, 1, z), repeater((stage % 4 + 1) as u8)));
+            blocks.push((BlockPos::new(x + 1, 1, z), Block::Sandstone {}));
+            blocks.push((
+                BlockPos::new(x + 2, 1, z),
+                Block::RedstoneWallTorch {
+                    lit: true,
+                    facing: BlockDirection::East,
+                },
+            ));
+        }
+    }
+    build("cpu", blocks)
+}
+
+fn song() -> Circuit {
+    let instruments = [
+        Instrument::Harp,
+        Instrument::Basedrum,
+        Instrument::Snare,
+        Instrument::Bass,
+    ];
+    let mut blocks = Vec::new();
+    for track in 0..32 {
+        let z = track * 3;
+        blocks.push((BlockPos::new(0, 1, z), lever()));
+        // Each repeater powers a block, which plays the note block next to it
+        for beat in 0..60 {
+            let x = 1 + beat * 2;
+            blocks.push((BlockPos::new(x, 1, z), repeater(4)));
+            blocks.push((BlockPos::new(x + 1, 1, z), Block::Sandstone {}));
+            blocks.push((
+                BlockPos::new(x + 1, 1, z + 1),
+                Block::NoteBlock {
+                    instrument: instruments[(track % 4) as usize],
+                    note: ((beat + track) % 25) as u32,
+                    powered: false,
+                },
+            ));
+        }
+    }
+    build("song", blocks)
+}
+
+fn circuits() -> Vec<Circuit> {
+    vec![alu(), cpu(), song()]
+}
+
+fn compile(circuit: &Circuit, flags: &str) -> Compiler {
+    let mut compiler = Compiler::default();
+    compiler.compile(
+        &circuit.world,
+        BenchWorld::bounds(),
+        CompilerOptions::parse(flags),
+        circuit.world.to_be_ticked.clone(),
+        Default::default(),
+    );
+    compiler
+}
+
+fn print_memory(circuits: &[Circuit]) {
+    for circuit in circuits {
+        for flags in FLAGS {
+            let before = ALLOCATED.load(Ordering::Relaxed);
+            PEAK.store(before, Ordering::Relaxed);
+            let compiler = compile(circuit, flags);
+            let peak = PEAK.load(Ordering::Relaxed) - before;
+            let retained = ALLOCATED.load(Ordering::Relaxed).saturating_sub(before);
+            let stats = compiler.stats().unwrap_or_default();
+            println!(
+                "{} \"{}\": {} nodes, {} links, {} KiB peak while compiling, {} KiB after",
+                circuit.name,
+                flags,
+                stats.nodes,
+                stats.links,
+                peak / 1024,
+                retained / 1024
+            );
+        }
+    }
+}
+
+fn bench_compile(c: &mut Criterion) {
+    let circuits = circuits();
+    print_memory(&circuits);
+
+    let mut group = c.benchmark_group("compile");
+    group.sample_size(10);
+    for circuit in &circuits {
+        for flags in FLAGS {
+            group.bench_with_input(
+                BenchmarkId::new(circuit.name, flags),
+                &flags,
+                |b, &flags| b.iter(|| compile(circuit, flags)),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_tick(c: &mut Criterion) {
+    let circuits = circuits();
+    let mut group = c.benchmark_group("tick");
+    // Reported as ticks per second
+    group.throughput(Throughput::Elements(TICKS));
+    for circuit in &circuits {
+        for flags in FLAGS {
+            let mut compiler = compile(circuit, flags);
+            group.bench_function(BenchmarkId::new(circuit.name, flags), |b| {
+                b.iter(|| {
+                    for tick in 0..TICKS {
+                        if tick % INPUT_PERIOD == 0 {
+                            let idx = (tick / INPUT_PERIOD) as usize % circuit.inputs.len();
+                            compiler.on_use_block(circuit.inputs[idx]);
+                        }
+                        compiler.tick();
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_compile, bench_tick);
+criterion_main!(benches);
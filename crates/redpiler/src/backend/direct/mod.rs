@@ -5,7 +5,7 @@ mod node;
 mod tick;
 mod update;
 
-use super::JITBackend;
+use super::{BackendStats, JITBackend};
 use crate::backend::direct::node::ForwardLink;
 use crate::compile_graph::CompileGraph;
 use crate::task_monitor::TaskMonitor;
@@ -201,6 +201,13 @@ impl JITBackend for DirectBackend {
         self.events.clear();
     }
 
+    fn stats(&self) -> BackendStats {
+        BackendStats {
+            nodes: self.blocks.len(),
+            links: self.forward_links.len(),
+        }
+    }
+
     fn on_use_block(&mut self, pos: BlockPos) {
         let node_id = self.pos_map[&pos];
         let node = &self.nodes[node_id];
@@ -230,6 +237,37 @@ impl JITBackend for DirectBackend {
         }
     }
 
+    fn read_output(&self, pos: BlockPos) -> Option<u8> {
+        let node = &self.nodes[*self.pos_map.get(&pos)?];
+        Some(match node.ty {
+            NodeType::Lamp | NodeType::Trapdoor | NodeType::NoteBlock { .. } => {
+                bool_to_ss(node.powered)
+            }
+            _ => node.output_power,
+        })
+    }
+
+    fn set_input(&mut self, pos: BlockPos, powered: bool) -> bool {
+        let Some(&node_id) = self.pos_map.get(&pos) else {
+            return false;
+        };
+        let node = &self.nodes[node_id];
+        match node.ty {
+            NodeType::Button => {
+                if powered {
+                    self.on_use_block(pos);
+                }
+            }
+            NodeType::Lever | NodeType::PressurePlate => {
+                if node.powered != powered {
+                    self.set_node(node_id, powered, bool_to_ss(powered));
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
     fn tick(&mut self) {
         let mut queues = self.scheduler.queues_this_tick();
 
@@ -9,6 +9,13 @@ use enum_dispatch::enum_dispatch;
 use mchprs_blocks::BlockPos;
 use mchprs_world::{TickEntry, World};
 
+/// The size of a compiled circuit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackendStats {
+    pub nodes: usize,
+    pub links: usize,
+}
+
 #[enum_dispatch]
 pub trait JITBackend {
     fn compile(
@@ -31,6 +38,13 @@ pub trait JITBackend {
     fn flush<W: World>(&mut self, world: &mut W, io_only: bool);
     fn reset<W: World>(&mut self, world: &mut W, io_only: bool);
     fn has_pending_ticks(&self) -> bool;
+    /// The signal strength a node puts out, or `None` if there is no node at the position.
+    /// Lamps, trapdoors and note blocks read 15 while powered.
+    fn read_output(&self, pos: BlockPos) -> Option<u8>;
+    /// Turns a lever or pressure plate on or off, or presses a button. Returns false if there is
+    /// no input at the position.
+    fn set_input(&mut self, pos: BlockPos, powered: bool) -> bool;
+    fn stats(&self) -> BackendStats;
     /// Inspect block for debugging
     fn inspect(&mut self, pos: BlockPos);
 }
@@ -1,3 +1,4 @@
+use crate::ExpectedOutput;
 use mchprs_blocks::blocks::{ComparatorMode, Instrument};
 use mchprs_blocks::BlockPos;
 use petgraph::stable_graph::{NodeIndex, StableGraph};
@@ -70,7 +71,10 @@ impl NodeState {
 }
 
 #[derive(Debug, Default)]
-pub struct Annotations {}
+pub struct Annotations {
+    /// What the output should read, and how many ticks after the compile
+    pub expect: Vec<(u64, ExpectedOutput)>,
+}
 
 #[derive(Debug)]
 pub struct CompileNode {
@@ -0,0 +1,598 @@
+//! A fuzzer that compares redpiler against the redstone implementation in `mchprs_redstone`,
+//! which is what every backend has to behave like. It builds random circuits on a small floor,
+//! runs them with random lever flips and button presses, and checks that the blocks are powered on
+//! the same ticks with every backend and every combination of optimization passes. Optimized
+//! compiles only keep inputs and outputs in the world, so only their lamps are compared.
+//!
+//! Circuits that diverge are shrunk by removing blocks and inputs for as long as they still
+//! diverge, and printed together with their seed. The fuzzer takes a while, so it is ignored by
+//! default:
+//!
+//! ```sh
+//! REDPILER_FUZZ_CASES=1000 cargo test -p mchprs_redpiler fuzz -- --ignored --nocapture
+//! ```
+//!
+//! `REDPILER_FUZZ_SEED` runs the case of a single seed again.
+
+use crate::backend::{BackendDispatcher, JITBackend};
+use crate::passes::{make_default_pass_manager, Pass, PassManager};
+use crate::{block_powered_mut, BackendVariant, CompilerInput, CompilerOptions, TaskMonitor};
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::blocks::{
+    Block, ButtonFace, ComparatorMode, Lever, LeverFace, RedstoneComparator, RedstoneRepeater,
+    StoneButton,
+};
+use mchprs_blocks::{BlockDirection, BlockPos};
+use mchprs_redstone::wire;
+use mchprs_world::storage::Chunk;
+use mchprs_world::{TickEntry, TickPriority, World};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustc_hash::FxHashSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// The width of the floor circuits are built on
+const SIZE: i32 = 8;
+/// How many ticks a circuit runs for
+const TICKS: u64 = 100;
+/// How many ticks a circuit gets to settle after being built. Clocks never settle, which is fine
+/// as the backends start with the same pending ticks.
+const SETTLE_TICKS: u64 = 100;
+
+/// A world of a single chunk section, which is all a circuit needs
+#[derive(Clone)]
+struct FuzzWorld {
+    chunk: Chunk,
+    to_be_ticked: Vec<TickEntry>,
+}
+
+impl FuzzWorld {
+    fn new() -> FuzzWorld {
+        FuzzWorld {
+            chunk: Chunk::empty(0, 0, 1),
+            to_be_ticked: Vec::new(),
+        }
+    }
+
+    fn contains(pos: BlockPos) -> bool {
+        (0..16).contains(&pos.x) && (0..16).contains(&pos.y) && (0..16).contains(&pos.z)
+    }
+
+    /// Runs a tick the way the plot does without redpiler
+    fn tick(&mut self) {
+        self.to_be_ticked
+            .sort_by_key(|e| (e.ticks_left, e.tick_priority));
+        for pending in &mut self.to_be_ticked {
+            pending.ticks_left = pending.ticks_left.saturating_sub(1);
+        }
+        while self.to_be_ticked.first().map_or(1, |e| e.ticks_left) == 0 {
+            let entry = self.to_be_ticked.remove(0);
+            mchprs_redstone::tick(self.get_block(entry.pos), self, entry.pos);
+        }
+    }
+}
+
+impl World for FuzzWorld {
+    fn get_block_raw(&self, pos: BlockPos) -> u32 {
+        if !FuzzWorld::contains(pos) {
+            return 0;
+        }
+        self.chunk.get_block(pos.x as u32, pos.y, pos.z as u32)
+    }
+
+    fn set_block_raw(&mut self, pos: BlockPos, block: u32) -> bool {
+        if !FuzzWorld::contains(pos) {
+            return false;
+        }
+        self.chunk
+            .set_block(pos.x as u32, pos.y, pos.z as u32, block)
+    }
+
+    fn delete_block_entity(&mut self, pos: BlockPos) {
+        self.chunk.delete_block_entity(pos);
+    }
+
+    fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.chunk.get_block_entity(pos)
+    }
+
+    fn set_block_entity(&mut self, pos: BlockPos, block_entity: BlockEntity) {
+        if FuzzWorld::contains(pos) {
+            self.chunk.set_block_entity(pos, block_entity);
+        }
+    }
+
+    fn get_chunk(&self, x: i32, z: i32) -> Option<&Chunk> {
+        (x == 0 && z == 0).then_some(&self.chunk)
+    }
+
+    fn get_chunk_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        (x == 0 && z == 0).then_some(&mut self.chunk)
+    }
+
+    fn schedule_tick(&mut self, pos: BlockPos, delay: u32, priority: TickPriority) {
+        self.to_be_ticked.push(TickEntry {
+            pos,
+            ticks_left: delay,
+            tick_priority: priority,
+        });
+    }
+
+    fn pending_tick_at(&mut self, pos: BlockPos) -> bool {
+        self.to_be_ticked.iter().any(|e| e.pos == pos)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Circuit {
+    /// The blocks placed on top of the sandstone floor
+    blocks: Vec<(BlockPos, Block)>,
+    /// The levers and buttons that are used, with the tick they are used before
+    inputs: Vec<(u64, BlockPos)>,
+    ticks: u64,
+}
+
+fn random_block(rng: &mut StdRng) -> Option<Block> {
+    let facing = BlockDirection::values()[rng.random_range(0..4)];
+    Some(match rng.random_range(0..16) {
+        0..=3 => return None,
+        4..=6 => Block::RedstoneWire {
+            wire: wire::make_cross(0),
+        },
+        7 => Block::Sandstone {},
+        8 | 9 => Block::RedstoneRepeater {
+            repeater: RedstoneRepeater {
+                delay: rng.random_range(1..=4),
+                facing,
+                ..Default::default()
+            },
+        },
+        10 => Block::RedstoneComparator {
+            comparator: RedstoneComparator {
+                mode: match rng.random_bool(0.5) {
+                    true => ComparatorMode::Compare,
+                    false => ComparatorMode::Subtract,
+                },
+                facing,
+                ..Default::default()
+            },
+        },
+        11 => Block::RedstoneWallTorch { lit: true, facing },
+        12 => Block::RedstoneLamp { lit: false },
+        13 | 14 => Block::Lever {
+            lever: Lever {
+                face: LeverFace::Floor,
+                facing,
+                powered: false,
+            },
+        },
+        _ => Block::StoneButton {
+            button: StoneButton {
+                face: ButtonFace::Floor,
+                facing,
+                powered: false,
+            },
+        },
+    })
+}
+
+impl Circuit {
+    fn generate(seed: u64) -> Circuit {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut blocks = Vec::new();
+        for x in 0..SIZE {
+            for z in 0..SIZE {
+                if let Some(block) = random_block(&mut rng) {
+                    blocks.push((BlockPos::new(x, 1, z), block));
+                }
+            }
+        }
+        let mut circuit = Circuit {
+            blocks,
+            inputs: Vec::new(),
+            ticks: TICKS,
+        };
+        circuit.remove_hanging_torches();
+
+        let levers: Vec<BlockPos> = circuit
+            .blocks
+            .iter()
+            .filter(|(_, block)| matches!(block, Block::Lever { .. } | Block::StoneButton { .. }))
+            .map(|&(pos, _)| pos)
+            .collect();
+        if !levers.is_empty() {
+            for tick in 0..TICKS {
+                if rng.random_bool(0.1) {
+                    let pos = levers[rng.random_range(0..levers.len())];
+                    circuit.inputs.push((tick, pos));
+                }
+            }
+        }
+        circuit
+    }
+
+    /// Wall torches need a block to hang on
+    fn remove_hanging_torches(&mut self) {
+        let solid: FxHashSet<BlockPos> = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| matches!(block, Block::Sandstone {}))
+            .map(|&(pos, _)| pos)
+            .collect();
+        self.blocks.retain(|&(pos, block)| match block {
+            Block::RedstoneWallTorch { facing, .. } => {
+                solid.contains(&pos.offset(facing.opposite().block_face()))
+            }
+            _ => true,
+        });
+    }
+
+    fn without_block(&self, idx: usize) -> Circuit {
+        let mut circuit = self.clone();
+        let (pos, _) = circuit.blocks.remove(idx);
+        circuit.inputs.retain(|&(_, input)| input != pos);
+        circuit.remove_hanging_torches();
+        circuit
+    }
+
+    /// Builds the circuit and lets it settle, so that it starts out like it would after being
+    /// built by hand
+    fn build(&self) -> FuzzWorld {
+        let mut world = FuzzWorld::new();
+        for x in 0..SIZE {
+            for z in 0..SIZE {
+                world.set_block(BlockPos::new(x, 0, z), Block::Sandstone {});
+            }
+        }
+        for &(pos, block) in &self.blocks {
+            world.set_block(pos, block);
+        }
+        for &(pos, block) in &self.blocks {
+            if let Block::RedstoneWire { .. } = block {
+                let wire = wire::get_state_for_placement(&world, pos);
+                world.set_block(pos, Block::RedstoneWire { wire });
+            }
+        }
+        for &(pos, _) in &self.blocks {
+            mchprs_redstone::update(world.get_block(pos), &mut world, pos);
+        }
+        for _ in 0..SETTLE_TICKS {
+            if world.to_be_ticked.is_empty() {
+                break;
+            }
+            world.tick();
+        }
+        world
+    }
+
+    /// The blocks that are compared between the backends
+    fn compared_blocks(&self, config: &Config) -> Vec<BlockPos> {
+        self.blocks
+            .iter()
+            .filter(|(_, block)| match config.options.io_only {
+                true => matches!(block, Block::RedstoneLamp { .. }),
+                false => is_powered(*block).is_some(),
+            })
+            .map(|&(pos, _)| pos)
+            .collect()
+    }
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "blocks, on a sandstone floor at y = 0:")?;
+        for (pos, block) in &self.blocks {
+            writeln!(f, "  {}: {:?}", pos, block)?;
+        }
+        writeln!(f, "inputs:")?;
+        for (tick, pos) in &self.inputs {
+            writeln!(f, "  {}: use {}", tick, pos)?;
+        }
+        write!(f, "ticks: {}", self.ticks)
+    }
+}
+
+fn is_powered(mut block: Block) -> Option<bool> {
+    block_powered_mut(&mut block).map(|powered| *powered)
+}
+
+/// Whether each compared block is powered, after every tick
+type Trace = Vec<Vec<bool>>;
+
+fn read_blocks(world: &FuzzWorld, positions: &[BlockPos]) -> Vec<bool> {
+    positions
+        .iter()
+        .map(|&pos| is_powered(world.get_block(pos)).unwrap_or(false))
+        .collect()
+}
+
+struct Config {
+    variant: BackendVariant,
+    options: CompilerOptions,
+    /// The names of the optional passes that don't run
+    skipped: Vec<&'static str>,
+}
+
+impl Config {
+    /// Every backend without optimizations, and with every combination of optional passes
+    fn all() -> Vec<Config> {
+        let optimized = CompilerOptions {
+            optimize: true,
+            io_only: true,
+            ..Default::default()
+        };
+        let pass_manager = make_default_pass_manager::<FuzzWorld>();
+        // Analyses don't change the graph and the passes after them rely on them
+        let optional: Vec<&'static str> = pass_manager
+            .passes()
+            .iter()
+            .filter(|pass| {
+                !pass.should_run(&CompilerOptions::default())
+                    && pass.should_run(&optimized)
+                    && !pass.name().contains("::analysis::")
+            })
+            .map(|pass| pass.name())
+            .collect();
+
+        let mut configs = Vec::new();
+        for variant in [BackendVariant::Direct] {
+            configs.push(Config {
+                variant,
+                options: CompilerOptions {
+                    backend_variant: variant,
+                    ..Default::default()
+                },
+                skipped: Vec::new(),
+            });
+            for mask in 0..1u32 << optional.len() {
+                let skipped = optional
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, &name)| name)
+                    .collect();
+                configs.push(Config {
+                    variant,
+                    options: CompilerOptions {
+                        backend_variant: variant,
+                        ..optimized.clone()
+                    },
+                    skipped,
+                });
+            }
+        }
+        configs
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} backend, flags \"{}\"",
+            self.variant,
+            self.options.to_flags()
+        )?;
+        if !self.skipped.is_empty() {
+            let skipped: Vec<&str> = self
+                .skipped
+                .iter()
+                .map(|name| name.rsplit("::").next().unwrap_or(name))
+                .collect();
+            write!(f, ", without {}", skipped.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn run_redstone(circuit: &Circuit, positions: &[BlockPos]) -> Trace {
+    let mut world = circuit.build();
+    let mut inputs = circuit.inputs.iter().peekable();
+    let mut trace = Vec::new();
+    for tick in 0..circuit.ticks {
+        while let Some(&(_, pos)) = inputs.next_if(|(input_tick, _)| *input_tick == tick) {
+            mchprs_redstone::on_use(world.get_block(pos), &mut world, pos);
+        }
+        world.tick();
+        trace.push(read_blocks(&world, positions));
+    }
+    trace
+}
+
+fn run_redpiler(circuit: &Circuit, config: &Config, positions: &[BlockPos]) -> Trace {
+    let mut world = circuit.build();
+    let monitor = Arc::new(TaskMonitor::default());
+    let pass_manager = make_default_pass_manager::<FuzzWorld>();
+    let passes: Vec<&dyn Pass<FuzzWorld>> = pass_manager
+        .passes()
+        .iter()
+        .filter(|pass| !config.skipped.contains(&pass.name()))
+        .copied()
+        .collect();
+    let input = CompilerInput {
+        world: &world,
+        bounds: (BlockPos::new(0, 0, 0), BlockPos::new(SIZE - 1, 1, SIZE - 1)),
+        monitor: monitor.clone(),
+    };
+    let graph = PassManager::new(&passes).run_passes(&config.options, &input, monitor.clone());
+
+    let mut jit = match config.variant {
+        BackendVariant::Direct => BackendDispatcher::DirectBackend(Default::default()),
+    };
+    jit.compile(graph, world.to_be_ticked.clone(), &config.options, monitor);
+
+    let mut inputs = circuit.inputs.iter().peekable();
+    let mut trace = Vec::new();
+    for tick in 0..circuit.ticks {
+        while let Some(&(_, pos)) = inputs.next_if(|(input_tick, _)| *input_tick == tick) {
+            jit.on_use_block(pos);
+        }
+        jit.tick();
+        jit.flush(&mut world, config.options.io_only);
+        trace.push(read_blocks(&world, positions));
+    }
+    trace
+}
+
+#[derive(Debug)]
+enum Divergence {
+    Panic(String),
+    Mismatch {
+        tick: u64,
+        pos: BlockPos,
+        expected: bool,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Panic(message) => write!(f, "redpiler panicked: {}", message),
+            Divergence::Mismatch {
+                tick,
+                pos,
+                expected,
+            } => write!(
+                f,
+                "after tick {}, the block at {} should be {} but is {}",
+                tick,
+                pos,
+                if *expected { "powered" } else { "unpowered" },
+                if *expected { "unpowered" } else { "powered" },
+            ),
+        }
+    }
+}
+
+/// Runs a circuit on redstone and on redpiler and returns the first difference
+fn check(circuit: &Circuit, config: &Config) -> Option<Divergence> {
+    let positions = circuit.compared_blocks(config);
+    let expected = run_redstone(circuit, &positions);
+    let actual = match panic::catch_unwind(AssertUnwindSafe(|| {
+        run_redpiler(circuit, config, &positions)
+    })) {
+        Ok(trace) => trace,
+        Err(err) => {
+            let message = err
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| err.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return Some(Divergence::Panic(message));
+        }
+    };
+    for (tick, (expected, actual)) in expected.iter().zip(&actual).enumerate() {
+        if let Some(i) = (0..positions.len()).find(|&i| expected[i] != actual[i]) {
+            return Some(Divergence::Mismatch {
+                tick: tick as u64,
+                pos: positions[i],
+                expected: expected[i],
+            });
+        }
+    }
+    None
+}
+
+/// Removes blocks and inputs one at a time for as long as the circuit still diverges
+fn minimize(mut circuit: Circuit, config: &Config) -> Circuit {
+    if let Some(Divergence::Mismatch { tick, .. }) = check(&circuit, config) {
+        circuit.ticks = tick + 1;
+        circuit.inputs.retain(|&(input_tick, _)| input_tick <= tick);
+    }
+    loop {
+        let mut shrunk = false;
+        let mut i = 0;
+        while i < circuit.blocks.len() {
+            let candidate = circuit.without_block(i);
+            if check(&candidate, config).is_some() {
+                circuit = candidate;
+                shrunk = true;
+            } else {
+                i += 1;
+            }
+        }
+        let mut i = 0;
+        while i < circuit.inputs.len() {
+            let mut candidate = circuit.clone();
+            candidate.inputs.remove(i);
+            if check(&candidate, config).is_some() {
+                circuit = candidate;
+                shrunk = true;
+            } else {
+                i += 1;
+            }
+        }
+        if !shrunk {
+            return circuit;
+        }
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+#[test]
+#[ignore]
+fn fuzz_backends() {
+    let seeds = match env_u64("REDPILER_FUZZ_SEED") {
+        Some(seed) => seed..seed + 1,
+        None => {
+            let start = rand::random::<u64>() >> 1;
+            start..start + env_u64("REDPILER_FUZZ_CASES").unwrap_or(100)
+        }
+    };
+    let configs = Config::all();
+    let mut failures = 0;
+    for seed in seeds {
+        let circuit = Circuit::generate(seed);
+        let Some(config) = configs
+            .iter()
+            .find(|config| check(&circuit, config).is_some())
+        else {
+            continue;
+        };
+        failures += 1;
+        let circuit = minimize(circuit, config);
+        let divergence = check(&circuit, config).expect("minimized circuit stopped diverging");
+        println!(
+            "seed {} diverges with {}: {}\n{}\n",
+            seed, config, divergence, circuit
+        );
+    }
+    assert_eq!(failures, 0, "{} circuits diverged", failures);
+}
+
+#[test]
+fn fuzz_lever_lamp_test() {
+    let lever = BlockPos::new(0, 1, 0);
+    let circuit = Circuit {
+        blocks: vec![
+            (
+                lever,
+                Block::Lever {
+                    lever: Lever {
+                        face: LeverFace::Floor,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (BlockPos::new(1, 1, 0), Block::RedstoneLamp { lit: false }),
+        ],
+        inputs: vec![(2, lever), (10, lever)],
+        ticks: 20,
+    };
+    for config in Config::all() {
+        if let Some(divergence) = check(&circuit, &config) {
+            panic!("{} with {}", divergence, config);
+        }
+    }
+
+    // Circuits only depend on their seed
+    let first = Circuit::generate(7);
+    let second = Circuit::generate(7);
+    assert_eq!(first.blocks.len(), second.blocks.len());
+    assert_eq!(first.inputs, second.inputs);
+}
@@ -1,5 +1,7 @@
 mod backend;
 mod compile_graph;
+#[cfg(test)]
+mod fuzz;
 mod passes;
 mod ril;
 mod task_monitor;
@@ -13,6 +15,7 @@ use std::sync::Arc;
 use std::time::Instant;
 use tracing::{debug, error, trace, warn};
 
+pub use backend::BackendStats;
 pub use task_monitor::TaskMonitor;
 
 fn block_powered_mut(block: &mut Block) -> Option<&mut bool> {
@@ -59,6 +62,42 @@ pub enum BackendVariant {
     Direct,
 }
 
+/// What a sign on an output says it should read, see [`Expectation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedOutput {
+    Powered(bool),
+    Strength(u8),
+}
+
+impl ExpectedOutput {
+    /// Whether an output with this signal strength reads as expected
+    pub fn matches(self, strength: u8) -> bool {
+        match self {
+            ExpectedOutput::Powered(powered) => (strength > 0) == powered,
+            ExpectedOutput::Strength(expected) => strength == expected,
+        }
+    }
+}
+
+impl std::fmt::Display for ExpectedOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectedOutput::Powered(true) => write!(f, "on"),
+            ExpectedOutput::Powered(false) => write!(f, "off"),
+            ExpectedOutput::Strength(strength) => write!(f, "{}", strength),
+        }
+    }
+}
+
+/// An `expect:<value>@t+<ticks>` sign on a lamp, trapdoor or note block, which declares what the
+/// output should read some ticks after the circuit is compiled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expectation {
+    pub pos: BlockPos,
+    pub tick: u64,
+    pub output: ExpectedOutput,
+}
+
 impl CompilerOptions {
     pub fn parse(str: &str) -> CompilerOptions {
         let mut co: CompilerOptions = Default::default();
@@ -97,6 +136,26 @@ impl CompilerOptions {
         }
         co
     }
+
+    /// Turns the options back into flags that `parse` understands
+    pub fn to_flags(&self) -> String {
+        let flags = [
+            (self.optimize, "--optimize"),
+            (self.export, "--export"),
+            (self.io_only, "--io-only"),
+            (self.update, "--update"),
+            (self.export_dot_graph, "--export-dot"),
+            (self.wire_dot_out, "--wire-dot-out"),
+            (self.print_after_all, "--print-after-all"),
+            (self.print_before_backend, "--print-before-backend"),
+        ];
+        let flags: Vec<&str> = flags
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, flag)| flag)
+            .collect();
+        flags.join(" ")
+    }
 }
 
 #[derive(Default)]
@@ -104,6 +163,7 @@ pub struct Compiler {
     is_active: bool,
     jit: Option<BackendDispatcher>,
     options: CompilerOptions,
+    expectations: Vec<Expectation>,
 }
 
 impl Compiler {
@@ -135,7 +195,11 @@ impl Compiler {
         debug!("Starting compile");
         let start = Instant::now();
 
-        let input = CompilerInput { world, bounds };
+        let input = CompilerInput {
+            world,
+            bounds,
+            monitor: monitor.clone(),
+        };
         let pass_manager = make_default_pass_manager::<W>();
         let graph = pass_manager.run_passes(&options, &input, monitor.clone());
 
@@ -143,6 +207,18 @@ impl Compiler {
             return;
         }
 
+        self.expectations = graph
+            .node_weights()
+            .filter_map(|node| Some((node.block?.0, &node.annotations.expect)))
+            .flat_map(|(pos, expect)| {
+                expect
+                    .iter()
+                    .map(move |&(tick, output)| Expectation { pos, tick, output })
+            })
+            .collect();
+        self.expectations
+            .sort_by_key(|expectation| expectation.tick);
+
         let replace_jit = match self.jit {
             Some(BackendDispatcher::DirectBackend(_)) => {
                 options.backend_variant != BackendVariant::Direct
@@ -191,6 +267,13 @@ impl Compiler {
             });
         }
         self.options = Default::default();
+        self.expectations.clear();
+    }
+
+    /// The expectations of the signs on outputs in the compiled circuit, in the order of their
+    /// ticks
+    pub fn expectations(&self) -> &[Expectation] {
+        &self.expectations
     }
 
     fn backend(&mut self) -> &mut BackendDispatcher {
@@ -221,6 +304,24 @@ impl Compiler {
         self.backend().set_pressure_plate(pos, powered);
     }
 
+    /// The signal strength of the component at a position, or `None` if redpiler isn't running or
+    /// there is no component there
+    pub fn read_output(&self, pos: BlockPos) -> Option<u8> {
+        match (&self.jit, self.is_active) {
+            (Some(jit), true) => jit.read_output(pos),
+            _ => None,
+        }
+    }
+
+    /// Turns an input on or off. Returns false if redpiler isn't running or there is no lever,
+    /// button or pressure plate at the position.
+    pub fn set_input(&mut self, pos: BlockPos, powered: bool) -> bool {
+        match (&mut self.jit, self.is_active) {
+            (Some(jit), true) => jit.set_input(pos, powered),
+            _ => false,
+        }
+    }
+
     pub fn flush<W: World>(&mut self, world: &mut W) {
         let io_only = self.options.io_only;
         self.backend().flush(world, io_only);
@@ -237,11 +338,21 @@ impl Compiler {
     pub fn has_pending_ticks(&mut self) -> bool {
         self.backend().has_pending_ticks()
     }
+
+    /// The size of the compiled circuit, or `None` if redpiler isn't running
+    pub fn stats(&self) -> Option<BackendStats> {
+        match (&self.jit, self.is_active) {
+            (Some(jit), true) => Some(jit.stats()),
+            _ => None,
+        }
+    }
 }
 
 pub struct CompilerInput<'w, W: World> {
     pub world: &'w W,
     pub bounds: (BlockPos, BlockPos),
+    /// Passes report problems with the circuit to this
+    pub monitor: Arc<TaskMonitor>,
 }
 
 #[cfg(test)]
@@ -266,4 +377,15 @@ mod tests {
 
         assert_eq!(options, expected_options);
     }
+
+    #[test]
+    fn options_to_flags() {
+        let options = CompilerOptions::parse("-io -d --export-dot");
+        assert_eq!(
+            options.to_flags(),
+            "--optimize --io-only --export-dot --wire-dot-out"
+        );
+        assert_eq!(CompilerOptions::parse(&options.to_flags()), options);
+        assert_eq!(CompilerOptions::default().to_flags(), "");
+    }
 }
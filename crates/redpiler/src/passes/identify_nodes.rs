@@ -5,12 +5,15 @@
 //!
 //! If `optimize` is set in [`CompilerOptions`], redstone wires will not be added to the graph.
 //!
+//! Signs on components are read for annotations, such as `expect:1@t+40` on outputs, which the
+//! `/verify` command checks.
+//!
 //! There are no requirements for this pass.
 
 use super::Pass;
 use crate::compile_graph::{Annotations, CompileGraph, CompileNode, NodeIdx, NodeState, NodeType};
 use crate::passes::AnalysisInfos;
-use crate::{CompilerInput, CompilerOptions};
+use crate::{CompilerInput, CompilerOptions, ExpectedOutput, TaskMonitor};
 use itertools::Itertools;
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::Block;
@@ -52,7 +55,7 @@ impl<W: World> Pass<W> for IdentifyNodes {
         });
 
         for pos in second_pass {
-            apply_annotations(graph, options, &first_pass, plot, pos);
+            apply_annotations(graph, options, &first_pass, plot, &input.monitor, pos);
         }
     }
 
@@ -183,6 +186,7 @@ fn apply_annotations<W: World>(
     options: &CompilerOptions,
     first_pass: &FxHashMap<BlockPos, NodeIdx>,
     world: &W,
+    monitor: &TaskMonitor,
     pos: BlockPos,
 ) {
     let block = world.get_block(pos);
@@ -198,6 +202,7 @@ fn apply_annotations<W: World>(
                 vec![behind]
             } else {
                 warn!("Found sign with annotations, but bad rotation at {}", pos);
+                monitor.warn(pos, "Sign with annotations has a bad rotation".to_string());
                 return;
             }
         }
@@ -215,17 +220,20 @@ fn apply_annotations<W: World>(
     let target = targets.iter().flat_map(|pos| first_pass.get(pos)).next();
     if let Some(&node_idx) = target {
         for annotation in annotations {
-            let result = annotation.apply(graph, node_idx, options);
+            let result =
+                annotation.and_then(|annotation| annotation.apply(graph, node_idx, options));
             if let Err(msg) = result {
                 warn!("{} at {}", msg, pos);
+                monitor.warn(pos, msg);
             }
         }
     } else {
         warn!("Could not find component for annotation at {}", pos);
+        monitor.warn(pos, "Could not find component for annotation".to_string());
     }
 }
 
-fn parse_sign_annotations(entity: Option<&BlockEntity>) -> Vec<NodeAnnotation> {
+fn parse_sign_annotations(entity: Option<&BlockEntity>) -> Vec<Result<NodeAnnotation, String>> {
     if let Some(BlockEntity::Sign(sign)) = entity {
         sign.front_rows
             .iter()
@@ -237,11 +245,18 @@ fn parse_sign_annotations(entity: Option<&BlockEntity>) -> Vec<NodeAnnotation> {
     }
 }
 
-pub enum NodeAnnotation {}
+pub enum NodeAnnotation {
+    /// `expect:<value>@t+<ticks>`, what an output should read some ticks after the compile
+    Expect { tick: u64, output: ExpectedOutput },
+}
 
 impl NodeAnnotation {
-    fn parse(s: &str) -> Option<Self> {
+    /// Returns `None` if the text isn't an annotation, or an error if it is one that is malformed
+    fn parse(s: &str) -> Option<Result<Self, String>> {
         let s = s.trim().to_ascii_lowercase();
+        if let Some(expect) = s.strip_prefix("expect:") {
+            return Some(parse_expect(expect));
+        }
         if !(s.starts_with('[') && s.ends_with(']')) {
             return None;
         }
@@ -251,10 +266,75 @@ impl NodeAnnotation {
 
     fn apply(
         self,
-        _graph: &mut CompileGraph,
-        _node_idx: NodeIdx,
+        graph: &mut CompileGraph,
+        node_idx: NodeIdx,
         _options: &CompilerOptions,
     ) -> Result<(), String> {
-        match self {}
+        match self {
+            NodeAnnotation::Expect { tick, output } => {
+                let node = &mut graph[node_idx];
+                if !node.is_output {
+                    return Err(
+                        "Expectations can only be put on lamps, trapdoors and note blocks"
+                            .to_string(),
+                    );
+                }
+                node.annotations.expect.push((tick, output));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses what comes after `expect:`, like `1@t+40`. `0` and `1` (or `off` and `on`) expect the
+/// output to be off or on, and 2 to 15 expect an exact signal strength.
+fn parse_expect(s: &str) -> Result<NodeAnnotation, String> {
+    let malformed = || format!("Expectation expect:{} should look like expect:1@t+40", s);
+    let (value, time) = s.split_once('@').ok_or_else(malformed)?;
+    let output = match value {
+        "0" | "off" => ExpectedOutput::Powered(false),
+        "1" | "on" => ExpectedOutput::Powered(true),
+        _ => match value.parse() {
+            Ok(strength @ 2..=15) => ExpectedOutput::Strength(strength),
+            _ => return Err(malformed()),
+        },
+    };
+    let tick = match time.strip_prefix('t') {
+        Some("") => Some(0),
+        Some(offset) => offset
+            .strip_prefix('+')
+            .and_then(|offset| offset.parse().ok()),
+        None => time.parse().ok(),
     }
+    .ok_or_else(malformed)?;
+    Ok(NodeAnnotation::Expect { tick, output })
+}
+
+#[test]
+fn parse_expect_test() {
+    let expect = |s: &str| match NodeAnnotation::parse(s) {
+        Some(Ok(NodeAnnotation::Expect { tick, output })) => Some((tick, output)),
+        _ => None,
+    };
+    assert_eq!(
+        expect("expect:1@t+40"),
+        Some((40, ExpectedOutput::Powered(true)))
+    );
+    assert_eq!(
+        expect(" Expect:OFF@t "),
+        Some((0, ExpectedOutput::Powered(false)))
+    );
+    assert_eq!(
+        expect("expect:12@100"),
+        Some((100, ExpectedOutput::Strength(12)))
+    );
+    assert!(matches!(
+        NodeAnnotation::parse("expect:16@t+1"),
+        Some(Err(_))
+    ));
+    assert!(matches!(
+        NodeAnnotation::parse("expect:1@t-4"),
+        Some(Err(_))
+    ));
+    assert!(NodeAnnotation::parse("lamp").is_none());
 }
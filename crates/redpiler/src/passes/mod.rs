@@ -69,6 +69,12 @@ impl<'p, W: World> PassManager<'p, W> {
         Self { passes }
     }
 
+    /// The passes in the order they run, so the fuzzer can leave some of them out
+    #[cfg(test)]
+    pub fn passes(&self) -> &'p [&'p dyn Pass<W>] {
+        self.passes
+    }
+
     pub fn run_passes(
         &self,
         options: &CompilerOptions,
@@ -1,3 +1,4 @@
+use mchprs_blocks::BlockPos;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
@@ -7,6 +8,7 @@ pub struct TaskMonitor {
     max_progress: AtomicUsize,
     progress: AtomicUsize,
     message: Mutex<Option<Arc<String>>>,
+    warnings: Mutex<Vec<(BlockPos, String)>>,
 }
 
 impl TaskMonitor {
@@ -45,4 +47,13 @@ impl TaskMonitor {
     pub fn message(&self) -> Option<Arc<String>> {
         self.message.lock().unwrap().clone()
     }
+
+    /// Records a problem with the circuit at `pos` that didn't stop the compile
+    pub fn warn(&self, pos: BlockPos, message: String) {
+        self.warnings.lock().unwrap().push((pos, message));
+    }
+
+    pub fn warnings(&self) -> Vec<(BlockPos, String)> {
+        self.warnings.lock().unwrap().clone()
+    }
 }
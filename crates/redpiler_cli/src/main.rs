@@ -0,0 +1,264 @@
+mod world;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::BlockPos;
+use mchprs_core::plot::worldedit::load_schematic_file;
+use mchprs_redpiler::{Compiler, CompilerOptions};
+use mchprs_world::World;
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+use world::SchematicWorld;
+
+/// Compiles a schematic with redpiler and runs it without a server
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Path to the schematic. Positions are relative to its lowest corner.
+    schematic: PathBuf,
+
+    /// How many redstone ticks to run
+    #[arg(short, long, default_value_t = 100)]
+    ticks: u64,
+
+    /// Compiler flags like those of `/redpiler compile`, for example "-o --export-dot"
+    #[arg(short, long, default_value = "", allow_hyphen_values = true)]
+    flags: String,
+
+    /// An input to change before a tick, as `TICK:X,Y,Z` to flip a lever or press a button, or
+    /// `TICK:X,Y,Z=on|off` to set a lever or pressure plate
+    #[arg(short, long)]
+    input: Vec<Input>,
+
+    /// A file with one input per line, in the same format as `--input`. Empty lines and lines
+    /// starting with `#` are skipped.
+    #[arg(short, long)]
+    script: Option<PathBuf>,
+
+    /// A component to read, as `X,Y,Z`. Every lamp is read if none are given.
+    #[arg(short, long, value_parser = parse_pos)]
+    output: Vec<BlockPos>,
+
+    /// Print every change of an output
+    #[arg(long)]
+    trace: bool,
+
+    /// Write the outputs and their changes to a JSON file
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Input {
+    tick: u64,
+    pos: BlockPos,
+    /// `None` flips a lever or presses a button
+    powered: Option<bool>,
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Input, String> {
+        let (tick, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("missing tick in input: {}", s))?;
+        let tick = tick
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid tick: {}", tick))?;
+        let (pos, powered) = match rest.split_once('=') {
+            Some((pos, "on")) => (pos, Some(true)),
+            Some((pos, "off")) => (pos, Some(false)),
+            Some((_, state)) => return Err(format!("state must be on or off, not {}", state)),
+            None => (rest, None),
+        };
+        Ok(Input {
+            tick,
+            pos: parse_pos(pos)?,
+            powered,
+        })
+    }
+}
+
+fn parse_pos(s: &str) -> Result<BlockPos, String> {
+    let coords: Vec<i32> = s
+        .split(',')
+        .map(|coord| coord.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid position: {}", s))?;
+    match coords[..] {
+        [x, y, z] => Ok(BlockPos::new(x, y, z)),
+        _ => Err(format!("position must be X,Y,Z: {}", s)),
+    }
+}
+
+fn read_script(path: &Path) -> Result<Vec<Input>> {
+    let script = fs::read_to_string(path)
+        .with_context(|| format!("could not read script {}", path.display()))?;
+    let mut inputs = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.parse() {
+            Ok(input) => inputs.push(input),
+            Err(err) => bail!("{}:{}: {}", path.display(), i + 1, err),
+        }
+    }
+    Ok(inputs)
+}
+
+struct Output {
+    pos: BlockPos,
+    power: Option<u8>,
+    /// The tick each change happened after, with the new power
+    changes: Vec<(u64, Option<u8>)>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut inputs = args.input;
+    if let Some(script) = &args.script {
+        inputs.extend(read_script(script)?);
+    }
+    // Stable, so inputs on the same tick are applied in the order they were given
+    inputs.sort_by_key(|input| input.tick);
+
+    let clipboard = load_schematic_file(&args.schematic)
+        .with_context(|| format!("could not load {}", args.schematic.display()))?;
+    let world = SchematicWorld::new(&clipboard);
+    let bounds = world.bounds();
+
+    let mut output_positions = args.output;
+    if output_positions.is_empty() {
+        let (_, max) = bounds;
+        for y in 0..=max.y {
+            for z in 0..=max.z {
+                for x in 0..=max.x {
+                    let pos = BlockPos::new(x, y, z);
+                    if matches!(world.get_block(pos), Block::RedstoneLamp { .. }) {
+                        output_positions.push(pos);
+                    }
+                }
+            }
+        }
+    }
+
+    let options = CompilerOptions::parse(&args.flags);
+    let monitor = Default::default();
+    let mut compiler = Compiler::default();
+    let start = Instant::now();
+    compiler.compile(&world, bounds, options, world.to_be_ticked(), monitor);
+    let compile_time = start.elapsed();
+    let stats = compiler.stats().unwrap_or_default();
+    eprintln!(
+        "Compiled {} nodes and {} links in {:?}",
+        stats.nodes, stats.links, compile_time
+    );
+
+    let mut outputs: Vec<Output> = output_positions
+        .into_iter()
+        .map(|pos| Output {
+            pos,
+            power: compiler.read_output(pos),
+            changes: Vec::new(),
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut pending = inputs.iter().peekable();
+    for tick in 0..args.ticks {
+        while let Some(input) = pending.next_if(|input| input.tick == tick) {
+            match input.powered {
+                Some(powered) => {
+                    if !compiler.set_input(input.pos, powered) {
+                        eprintln!("There is no input at {}", input.pos);
+                    }
+                }
+                None => compiler.on_use_block(input.pos),
+            }
+        }
+        compiler.tick();
+        for output in &mut outputs {
+            let power = compiler.read_output(output.pos);
+            if power != output.power {
+                if args.trace {
+                    println!("{}: {} = {}", tick, output.pos, format_power(power));
+                }
+                output.power = power;
+                output.changes.push((tick, power));
+            }
+        }
+    }
+    let run_time = start.elapsed();
+    if pending.peek().is_some() {
+        eprintln!("Some inputs come after the last tick and were not applied");
+    }
+    eprintln!("Ran {} ticks in {:?}", args.ticks, run_time);
+
+    for output in &outputs {
+        println!("{} = {}", output.pos, format_power(output.power));
+    }
+
+    if let Some(path) = &args.json {
+        let outputs: Vec<_> = outputs
+            .iter()
+            .map(|output| {
+                json!({
+                    "pos": [output.pos.x, output.pos.y, output.pos.z],
+                    "power": output.power,
+                    "changes": output.changes,
+                })
+            })
+            .collect();
+        let report = json!({
+            "ticks": args.ticks,
+            "compile_ms": compile_time.as_secs_f64() * 1000.0,
+            "run_ms": run_time.as_secs_f64() * 1000.0,
+            "nodes": stats.nodes,
+            "links": stats.links,
+            "outputs": outputs,
+        });
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn format_power(power: Option<u8>) -> String {
+    match power {
+        Some(power) => power.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+#[test]
+fn parse_input() {
+    assert_eq!(
+        "10:1,2,-3".parse(),
+        Ok(Input {
+            tick: 10,
+            pos: BlockPos::new(1, 2, -3),
+            powered: None,
+        })
+    );
+    assert_eq!(
+        "0:4,5,6=off".parse(),
+        Ok(Input {
+            tick: 0,
+            pos: BlockPos::new(4, 5, 6),
+            powered: Some(false),
+        })
+    );
+    assert!("1,2,3".parse::<Input>().is_err());
+    assert!("5:1,2".parse::<Input>().is_err());
+    assert!("5:1,2,3=maybe".parse::<Input>().is_err());
+}
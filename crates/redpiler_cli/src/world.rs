@@ -0,0 +1,135 @@
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::BlockPos;
+use mchprs_core::plot::worldedit::WorldEditClipboard;
+use mchprs_world::storage::Chunk;
+use mchprs_world::{TickEntry, TickPriority, World};
+
+/// A world that only holds a schematic, with its lowest corner at 0, 0, 0
+pub struct SchematicWorld {
+    chunks: Vec<Chunk>,
+    to_be_ticked: Vec<TickEntry>,
+    /// The size in chunks
+    size_x: i32,
+    size_z: i32,
+    height: i32,
+    /// The highest position of the schematic
+    max: BlockPos,
+}
+
+impl SchematicWorld {
+    pub fn new(cb: &WorldEditClipboard) -> SchematicWorld {
+        let size_x = (cb.size_x as i32 + 15) >> 4;
+        let size_z = (cb.size_z as i32 + 15) >> 4;
+        let num_sections = (cb.size_y as usize).div_ceil(16).max(1);
+        let mut chunks = Vec::new();
+        for x in 0..size_x {
+            for z in 0..size_z {
+                chunks.push(Chunk::empty(x, z, num_sections));
+            }
+        }
+        let mut world = SchematicWorld {
+            chunks,
+            to_be_ticked: Vec::new(),
+            size_x,
+            size_z,
+            height: num_sections as i32 * 16,
+            max: BlockPos::new(
+                cb.size_x as i32 - 1,
+                cb.size_y as i32 - 1,
+                cb.size_z as i32 - 1,
+            ),
+        };
+
+        let mut i = 0;
+        for y in 0..cb.size_y as i32 {
+            for z in 0..cb.size_z as i32 {
+                for x in 0..cb.size_x as i32 {
+                    world.set_block_raw(BlockPos::new(x, y, z), cb.data.get_entry(i));
+                    i += 1;
+                }
+            }
+        }
+        for (&pos, block_entity) in &cb.block_entities {
+            world.set_block_entity(pos, block_entity.clone());
+        }
+        world
+    }
+
+    /// The area the schematic takes up
+    pub fn bounds(&self) -> (BlockPos, BlockPos) {
+        (BlockPos::new(0, 0, 0), self.max)
+    }
+
+    pub fn to_be_ticked(&self) -> Vec<TickEntry> {
+        self.to_be_ticked.clone()
+    }
+
+    fn get_chunk_index_for_block(&self, block_x: i32, block_z: i32) -> Option<usize> {
+        let chunk_x = block_x >> 4;
+        let chunk_z = block_z >> 4;
+        if chunk_x >= self.size_x || chunk_z >= self.size_z || chunk_x < 0 || chunk_z < 0 {
+            return None;
+        }
+        Some((chunk_x * self.size_z + chunk_z) as usize)
+    }
+}
+
+impl World for SchematicWorld {
+    fn get_block_raw(&self, pos: BlockPos) -> u32 {
+        match self.get_chunk_index_for_block(pos.x, pos.z) {
+            Some(idx) if (0..self.height).contains(&pos.y) => {
+                self.chunks[idx].get_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32)
+            }
+            _ => 0,
+        }
+    }
+
+    fn set_block_raw(&mut self, pos: BlockPos, block: u32) -> bool {
+        match self.get_chunk_index_for_block(pos.x, pos.z) {
+            Some(idx) if (0..self.height).contains(&pos.y) => {
+                self.chunks[idx].set_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32, block)
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_block_entity(&mut self, pos: BlockPos) {
+        if let Some(idx) = self.get_chunk_index_for_block(pos.x, pos.z) {
+            self.chunks[idx].delete_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF));
+        }
+    }
+
+    fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        let idx = self.get_chunk_index_for_block(pos.x, pos.z)?;
+        self.chunks[idx].get_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF))
+    }
+
+    fn set_block_entity(&mut self, pos: BlockPos, block_entity: BlockEntity) {
+        if let Some(idx) = self.get_chunk_index_for_block(pos.x, pos.z) {
+            self.chunks[idx]
+                .set_block_entity(BlockPos::new(pos.x & 0xF, pos.y, pos.z & 0xF), block_entity);
+        }
+    }
+
+    fn get_chunk(&self, x: i32, z: i32) -> Option<&Chunk> {
+        let idx = self.get_chunk_index_for_block(x << 4, z << 4)?;
+        self.chunks.get(idx)
+    }
+
+    fn get_chunk_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        let idx = self.get_chunk_index_for_block(x << 4, z << 4)?;
+        self.chunks.get_mut(idx)
+    }
+
+    fn schedule_tick(&mut self, pos: BlockPos, delay: u32, priority: TickPriority) {
+        self.to_be_ticked.push(TickEntry {
+            pos,
+            ticks_left: delay,
+            tick_priority: priority,
+        });
+    }
+
+    fn pending_tick_at(&mut self, pos: BlockPos) -> bool {
+        self.to_be_ticked.iter().any(|e| e.pos == pos)
+    }
+}
@@ -1,7 +1,7 @@
 use mchprs_blocks::block_entities::BlockEntity;
-use mchprs_blocks::blocks::{Block, ComparatorMode, RedstoneComparator};
-use mchprs_blocks::{BlockDirection, BlockFace, BlockPos};
-use mchprs_world::{TickPriority, World};
+use mchprs_blocks::blocks::{Block, ComparatorMode, RedstoneComparator, SculkSensorPhase};
+use mchprs_blocks::{BlockDirection, BlockPos};
+use mchprs_world::{ItemFrame, TickPriority, World};
 use tracing::warn;
 
 fn get_power_on_side(world: &impl World, pos: BlockPos, side: BlockDirection) -> u8 {
@@ -31,16 +31,27 @@ pub fn has_override(block: Block) -> bool {
         Block::Barrel { .. }
             | Block::Furnace { .. }
             | Block::Hopper { .. }
+            | Block::Dispenser { .. }
+            | Block::Dropper { .. }
             | Block::Cauldron { .. }
             | Block::Composter { .. }
             | Block::Cake { .. }
             | Block::EndPortalFrame { eye: true, .. }
+            | Block::CopperBulb { .. }
+            | Block::SculkSensor { .. }
+            | Block::CalibratedSculkSensor { .. }
+            | Block::Lectern { .. }
+            | Block::Jukebox { .. }
     )
 }
 
 pub fn get_override(block: Block, world: &impl World, pos: BlockPos) -> u8 {
     match block {
-        Block::Barrel { .. } | Block::Furnace { .. } | Block::Hopper { .. } => {
+        Block::Barrel { .. }
+        | Block::Furnace { .. }
+        | Block::Hopper { .. }
+        | Block::Dispenser { .. }
+        | Block::Dropper { .. } => {
             match world.get_block_entity(pos) {
                 Some(BlockEntity::Container {
                     comparator_override,
@@ -58,10 +69,61 @@ pub fn get_override(block: Block, world: &impl World, pos: BlockPos) -> u8 {
         Block::Composter { level } => level,
         Block::Cake { bites } => 14 - 2 * bites,
         Block::EndPortalFrame { eye: true, .. } => 15,
+        Block::CopperBulb { lit, .. } => super::bool_to_ss(lit),
+        Block::SculkSensor {
+            sculk_sensor_phase: SculkSensorPhase::Active,
+            ..
+        }
+        | Block::CalibratedSculkSensor {
+            sculk_sensor_phase: SculkSensorPhase::Active,
+            ..
+        } => match world.get_block_entity(pos) {
+            Some(BlockEntity::SculkSensor {
+                last_vibration_frequency,
+                ..
+            }) => *last_vibration_frequency,
+            _ => 0,
+        },
+        Block::SculkSensor { .. } | Block::CalibratedSculkSensor { .. } => 0,
+        Block::Lectern { has_book, .. } => {
+            if !has_book {
+                return 0;
+            }
+            match world.get_block_entity(pos) {
+                Some(&BlockEntity::Lectern { page, pages }) if pages > 1 => {
+                    let progress = page.min(pages - 1) as f32 / (pages - 1) as f32;
+                    (progress * 14.0).floor() as u8 + 1
+                }
+                _ => 15,
+            }
+        }
+        Block::Jukebox { .. } => match world.get_block_entity(pos) {
+            Some(BlockEntity::Jukebox {
+                record: Some(record),
+            }) => record.comparator_output(),
+            _ => 0,
+        },
         _ => unreachable!("Block does not override comparators"),
     }
 }
 
+/// Updates the comparators that could be reading the block at `pos`, including the ones reading
+/// it through a solid block
+pub fn update_comparators_around(world: &mut impl World, pos: BlockPos) {
+    for direction in BlockDirection::values() {
+        let face = direction.block_face();
+        let mut neighbor_pos = pos.offset(face);
+        let mut neighbor = world.get_block(neighbor_pos);
+        if neighbor.is_solid() {
+            neighbor_pos = neighbor_pos.offset(face);
+            neighbor = world.get_block(neighbor_pos);
+        }
+        if let Block::RedstoneComparator { comparator } = neighbor {
+            update(comparator, world, neighbor_pos);
+        }
+    }
+}
+
 pub fn get_far_input(world: &impl World, pos: BlockPos, facing: BlockDirection) -> Option<u8> {
     let face = facing.block_face();
     let input_pos = pos.offset(face);
@@ -72,11 +134,22 @@ pub fn get_far_input(world: &impl World, pos: BlockPos, facing: BlockDirection)
 
     let far_input_pos = input_pos.offset(face);
     let far_input_block = world.get_block(far_input_pos);
-    if has_override(far_input_block) {
-        Some(get_override(far_input_block, world, far_input_pos))
-    } else {
-        None
-    }
+    read_far_input(world, far_input_pos, far_input_block, facing)
+}
+
+/// Reads the block or item frame behind the solid block in front of a comparator
+fn read_far_input(
+    world: &impl World,
+    far_input_pos: BlockPos,
+    far_input_block: Block,
+    facing: BlockDirection,
+) -> Option<u8> {
+    let block_output =
+        has_override(far_input_block).then(|| get_override(far_input_block, world, far_input_pos));
+    let item_frame_output = world
+        .get_item_frame(far_input_pos, facing.block_facing())
+        .map(ItemFrame::comparator_output);
+    block_output.max(item_frame_output)
 }
 
 fn calculate_input_strength(comp: RedstoneComparator, world: &impl World, pos: BlockPos) -> u8 {
@@ -88,11 +161,8 @@ fn calculate_input_strength(comp: RedstoneComparator, world: &impl World, pos: B
     } else if base_input_strength < 15 && input_block.is_solid() {
         let far_input_pos = input_pos.offset(comp.facing.block_face());
         let far_input_block = world.get_block(far_input_pos);
-        if has_override(far_input_block) {
-            get_override(far_input_block, world, far_input_pos)
-        } else {
-            base_input_strength
-        }
+        read_far_input(world, far_input_pos, far_input_block, comp.facing)
+            .unwrap_or(base_input_strength)
     } else {
         base_input_strength
     }
@@ -134,7 +204,7 @@ fn on_state_change(comp: RedstoneComparator, world: &mut impl World, pos: BlockP
     let front_pos = pos.offset(comp.facing.opposite().block_face());
     let front_block = world.get_block(front_pos);
     super::update(front_block, world, front_pos);
-    for direction in &BlockFace::values() {
+    for direction in &super::update_order(world) {
         let neighbor_pos = front_pos.offset(*direction);
         let block = world.get_block(neighbor_pos);
         super::update(block, world, neighbor_pos);
@@ -154,7 +224,9 @@ pub fn update(comp: RedstoneComparator, world: &mut impl World, pos: BlockPos) {
         };
     if output_strength != old_strength || comp.powered != should_be_powered(comp, world, pos) {
         let front_block = world.get_block(pos.offset(comp.facing.opposite().block_face()));
-        let priority = if super::is_diode(front_block) {
+        let priority = if !world.comparator_tick_priority() {
+            TickPriority::Highest
+        } else if super::is_diode(front_block) {
             TickPriority::High
         } else {
             TickPriority::Normal
@@ -184,10 +256,10 @@ pub fn tick(mut comp: RedstoneComparator, world: &mut impl World, pos: BlockPos)
         let powered = comp.powered;
         if powered && !should_be_powered {
             comp.powered = false;
-            world.set_block(pos, Block::RedstoneComparator { comparator: comp });
+            super::set_block_state(world, pos, Block::RedstoneComparator { comparator: comp });
         } else if !powered && should_be_powered {
             comp.powered = true;
-            world.set_block(pos, Block::RedstoneComparator { comparator: comp });
+            super::set_block_state(world, pos, Block::RedstoneComparator { comparator: comp });
         }
         on_state_change(comp, world, pos);
     }
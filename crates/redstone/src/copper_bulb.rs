@@ -0,0 +1,57 @@
+use mchprs_blocks::blocks::{Block, CopperVariant};
+use mchprs_blocks::BlockPos;
+use mchprs_world::{TickPriority, World};
+
+pub fn on_neighbor_updated(
+    world: &mut impl World,
+    pos: BlockPos,
+    variant: CopperVariant,
+    lit: bool,
+    powered: bool,
+) {
+    if world.copper_bulb_delay() {
+        let should_be_powered = super::redstone_lamp_should_be_lit(world, pos);
+        if powered != should_be_powered && !world.pending_tick_at(pos) {
+            world.schedule_tick(pos, 1, TickPriority::Normal);
+        }
+    } else {
+        check_and_flip(world, pos, variant, lit, powered);
+    }
+}
+
+pub fn tick(
+    world: &mut impl World,
+    pos: BlockPos,
+    variant: CopperVariant,
+    lit: bool,
+    powered: bool,
+) {
+    check_and_flip(world, pos, variant, lit, powered);
+}
+
+/// Bulbs toggle their light every time they start being powered
+fn check_and_flip(
+    world: &mut impl World,
+    pos: BlockPos,
+    variant: CopperVariant,
+    lit: bool,
+    powered: bool,
+) {
+    let should_be_powered = super::redstone_lamp_should_be_lit(world, pos);
+    if powered == should_be_powered {
+        return;
+    }
+    let new_lit = if should_be_powered { !lit } else { lit };
+    super::set_block_state(
+        world,
+        pos,
+        Block::CopperBulb {
+            variant,
+            lit: new_lit,
+            powered: should_be_powered,
+        },
+    );
+    if new_lit != lit {
+        super::comparator::update_comparators_around(world, pos);
+    }
+}
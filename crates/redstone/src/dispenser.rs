@@ -0,0 +1,140 @@
+//! Dispensers and droppers activate on the rising edge of their input and eject one item from
+//! their inventory. Droppers facing a container insert the item into it instead. Items are not
+//! simulated as entities, so ejected items simply leave the world.
+
+use crate::{comparator, is_quasi_powered, redstone_lamp_should_be_lit, set_block_state};
+use mchprs_blocks::block_entities::{BlockEntity, ContainerType, InventoryEntry};
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::items::Item;
+use mchprs_blocks::{BlockFacing, BlockPos};
+use mchprs_world::{TickPriority, World};
+
+/// The world event for the sound of an item being dispensed
+const DISPENSE_EVENT: i32 = 1000;
+/// The world event for the click of a dispenser without any items
+const DISPENSE_FAIL_EVENT: i32 = 1001;
+/// The world event for the smoke coming out of a dispenser
+const SMOKE_EVENT: i32 = 2000;
+
+fn with_triggered(block: Block, triggered: bool) -> Block {
+    match block {
+        Block::Dispenser { facing, .. } => Block::Dispenser { facing, triggered },
+        Block::Dropper { facing, .. } => Block::Dropper { facing, triggered },
+        _ => block,
+    }
+}
+
+pub fn on_neighbor_updated(world: &mut impl World, pos: BlockPos, block: Block, triggered: bool) {
+    let should_be_triggered =
+        redstone_lamp_should_be_lit(world, pos) || is_quasi_powered(world, pos);
+    if should_be_triggered && !triggered {
+        world.schedule_tick(pos, 4, TickPriority::Normal);
+        set_block_state(world, pos, with_triggered(block, true));
+    } else if !should_be_triggered && triggered {
+        set_block_state(world, pos, with_triggered(block, false));
+    }
+}
+
+/// The data value of the smoke world event, which is the direction the smoke is emitted in
+fn smoke_direction(facing: BlockFacing) -> i32 {
+    match facing {
+        BlockFacing::Down => 0,
+        BlockFacing::Up => 1,
+        BlockFacing::North => 2,
+        BlockFacing::South => 3,
+        BlockFacing::West => 4,
+        BlockFacing::East => 5,
+    }
+}
+
+/// The type of container a dropper can insert items into. Furnaces are left out because
+/// items only go into specific slots depending on the side they are inserted from.
+fn insertable_container_type(block: Block) -> Option<ContainerType> {
+    Some(match block {
+        Block::Barrel {} => ContainerType::Barrel,
+        Block::Hopper {} => ContainerType::Hopper,
+        Block::Dispenser { .. } => ContainerType::Dispenser,
+        Block::Dropper { .. } => ContainerType::Dropper,
+        _ => return None,
+    })
+}
+
+/// Tries to insert one item of type `id` into the container at `pos`.
+/// Returns true if the item was inserted.
+fn insert_item(world: &mut impl World, pos: BlockPos, ty: ContainerType, id: u32) -> bool {
+    let mut inventory = match world.get_block_entity(pos) {
+        Some(BlockEntity::Container { inventory, .. }) => inventory.clone(),
+        // Empty containers may not have any block entity data
+        _ => Vec::new(),
+    };
+    let max_stack = Item::from_id(id).max_stack_size() as i8;
+    if let Some(entry) = inventory
+        .iter_mut()
+        .find(|entry| entry.id == id && entry.nbt.is_none() && entry.count < max_stack)
+    {
+        entry.count += 1;
+    } else if let Some(slot) =
+        (0..ty.num_slots() as i8).find(|slot| inventory.iter().all(|entry| entry.slot != *slot))
+    {
+        inventory.push(InventoryEntry {
+            id,
+            slot,
+            count: 1,
+            nbt: None,
+        });
+    } else {
+        return false;
+    }
+    let block_entity = BlockEntity::Container {
+        comparator_override: ty.comparator_override(&inventory),
+        inventory,
+        ty,
+    };
+    world.set_block_entity(pos, block_entity);
+    true
+}
+
+/// Ejects one item out of the dispenser or dropper at `pos`. Unlike vanilla, which picks a
+/// random slot, the first non-empty slot is used so circuits behave the same every time.
+pub fn dispense(world: &mut impl World, pos: BlockPos, facing: BlockFacing, dropper: bool) {
+    let Some(BlockEntity::Container { inventory, ty, .. }) = world.get_block_entity(pos) else {
+        world.play_world_event(pos, DISPENSE_FAIL_EVENT, 0);
+        return;
+    };
+    let ty = *ty;
+    let mut inventory = inventory.clone();
+    let Some(idx) = inventory
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.count > 0)
+        .min_by_key(|(_, entry)| entry.slot)
+        .map(|(idx, _)| idx)
+    else {
+        world.play_world_event(pos, DISPENSE_FAIL_EVENT, 0);
+        return;
+    };
+
+    let target_pos = pos.offset(facing.block_face());
+    let target_container = insertable_container_type(world.get_block(target_pos));
+    if let (true, Some(target_ty)) = (dropper, target_container) {
+        if !insert_item(world, target_pos, target_ty, inventory[idx].id) {
+            return;
+        }
+        comparator::update_comparators_around(world, target_pos);
+    } else {
+        world.play_world_event(pos, DISPENSE_EVENT, 0);
+        world.play_world_event(pos, SMOKE_EVENT, smoke_direction(facing));
+    }
+
+    inventory[idx].count -= 1;
+    if inventory[idx].count == 0 {
+        inventory.remove(idx);
+    }
+    let block_entity = BlockEntity::Container {
+        comparator_override: ty.comparator_override(&inventory),
+        inventory,
+        ty,
+    };
+    world.set_block_entity(pos, block_entity);
+    comparator::update_comparators_around(world, pos);
+}
@@ -0,0 +1,122 @@
+//! Doors open while either of their halves receive power. Doors that form a double door
+//! also open when their partner is powered, so a single input opens both of them.
+
+use crate::{redstone_lamp_should_be_lit, sculk, set_block_state};
+use mchprs_blocks::blocks::{Block, Door, DoorHalf, DoorHinge};
+use mchprs_blocks::{BlockFace, BlockPos};
+use mchprs_world::{GameEvent, World};
+
+pub fn get_door(block: Block) -> Option<Door> {
+    match block {
+        Block::IronDoor { door } | Block::OakDoor { door } => Some(door),
+        _ => None,
+    }
+}
+
+pub fn with_door(block: Block, door: Door) -> Block {
+    match block {
+        Block::IronDoor { .. } => Block::IronDoor { door },
+        Block::OakDoor { .. } => Block::OakDoor { door },
+        _ => block,
+    }
+}
+
+/// Returns the position of the other half of the door
+pub fn other_half_pos(door: Door, pos: BlockPos) -> BlockPos {
+    match door.half {
+        DoorHalf::Upper => pos.offset(BlockFace::Bottom),
+        DoorHalf::Lower => pos.offset(BlockFace::Top),
+    }
+}
+
+/// Returns the position of the lower half of the door that forms a double door with this one
+fn partner_pos(world: &impl World, door: Door, pos: BlockPos) -> Option<BlockPos> {
+    let lower_pos = match door.half {
+        DoorHalf::Upper => pos.offset(BlockFace::Bottom),
+        DoorHalf::Lower => pos,
+    };
+    let side = match door.hinge {
+        DoorHinge::Left => door.facing.rotate(),
+        DoorHinge::Right => door.facing.rotate_ccw(),
+    };
+    let partner_pos = lower_pos.offset(side.block_face());
+    let partner = get_door(world.get_block(partner_pos))?;
+    (partner.facing == door.facing
+        && partner.half == DoorHalf::Lower
+        && partner.hinge == door.hinge.other())
+    .then_some(partner_pos)
+}
+
+/// Returns the hinge a newly placed door should have so that it forms a double door
+/// with a neighboring door
+pub fn get_hinge_for_placement(world: &impl World, door: Door, pos: BlockPos) -> DoorHinge {
+    let left = get_door(world.get_block(pos.offset(door.facing.rotate_ccw().block_face())));
+    let right = get_door(world.get_block(pos.offset(door.facing.rotate().block_face())));
+    let pairs_with = |other: Option<Door>, hinge| {
+        other.is_some_and(|other| {
+            other.facing == door.facing && other.half == DoorHalf::Lower && other.hinge == hinge
+        })
+    };
+    if pairs_with(left, DoorHinge::Left) && !pairs_with(right, DoorHinge::Right) {
+        DoorHinge::Right
+    } else {
+        DoorHinge::Left
+    }
+}
+
+fn is_door_powered(world: &impl World, door: Door, pos: BlockPos) -> bool {
+    redstone_lamp_should_be_lit(world, pos)
+        || redstone_lamp_should_be_lit(world, other_half_pos(door, pos))
+}
+
+/// Sets the state of both halves of the door at `pos`
+pub fn set_door_state(world: &mut impl World, pos: BlockPos, open: bool, powered: bool) {
+    let block = world.get_block(pos);
+    let Some(door) = get_door(block) else {
+        return;
+    };
+    let new_door = Door {
+        open,
+        powered,
+        ..door
+    };
+    set_block_state(world, pos, with_door(block, new_door));
+
+    let other_pos = other_half_pos(door, pos);
+    let other_block = world.get_block(other_pos);
+    if let Some(other) = get_door(other_block) {
+        let new_other = Door {
+            open,
+            powered,
+            ..other
+        };
+        set_block_state(world, other_pos, with_door(other_block, new_other));
+    }
+
+    if door.open != open {
+        let event = if open {
+            GameEvent::BlockActivate
+        } else {
+            GameEvent::BlockDeactivate
+        };
+        sculk::emit_vibration(world, pos, event);
+    }
+}
+
+pub fn on_neighbor_updated(world: &mut impl World, pos: BlockPos, door: Door) {
+    let partner_pos = partner_pos(world, door, pos);
+    let partner = partner_pos.and_then(|pos| get_door(world.get_block(pos)));
+    let should_be_powered = is_door_powered(world, door, pos)
+        || partner_pos
+            .zip(partner)
+            .is_some_and(|(pos, partner)| is_door_powered(world, partner, pos));
+    if door.powered == should_be_powered {
+        return;
+    }
+    set_door_state(world, pos, should_be_powered, should_be_powered);
+    if let (Some(partner_pos), Some(partner)) = (partner_pos, partner) {
+        if partner.powered != should_be_powered {
+            set_door_state(world, partner_pos, should_be_powered, should_be_powered);
+        }
+    }
+}
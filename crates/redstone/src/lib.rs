@@ -3,14 +3,32 @@
 //! scenerio (i.e. regular buiding)
 
 pub mod comparator;
+pub mod copper_bulb;
+pub mod dispenser;
+pub mod door;
 pub mod noteblock;
+pub mod observer;
+pub mod piston;
+pub mod rail;
 pub mod repeater;
+pub mod sculk;
+pub mod target;
 pub mod wire;
 
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::{Block, ButtonFace, LeverFace, RedstoneWire};
 use mchprs_blocks::{BlockDirection, BlockFace, BlockPos};
-use mchprs_world::{TickPriority, World};
+use mchprs_world::{BlockEvent, GameEvent, TickPriority, World};
+
+/// Sets the block at `pos` and lets any observers watching it know about the change.
+/// Returns true if the block was changed.
+pub fn set_block_state(world: &mut impl World, pos: BlockPos, block: Block) -> bool {
+    let changed = world.set_block(pos, block);
+    if changed {
+        observer::update_observers(world, pos);
+    }
+    changed
+}
 
 pub fn bool_to_ss(b: bool) -> u8 {
     match b {
@@ -31,8 +49,20 @@ fn get_weak_power(
         Block::RedstoneWallTorch { lit: true, facing } if facing.block_face() != side => 15,
         Block::RedstoneBlock {} => 15,
         Block::StonePressurePlate { powered: true } => 15,
+        Block::DetectorRail { powered: true, .. } => 15,
         Block::Lever { lever } if lever.powered => 15,
         Block::StoneButton { button } if button.powered => 15,
+        Block::Target { power } => power,
+        Block::SculkSensor { power, .. } => power,
+        Block::CalibratedSculkSensor { power, facing, .. }
+            if facing.opposite().block_face() != side =>
+        {
+            power
+        }
+        Block::Observer {
+            facing,
+            powered: true,
+        } if facing.block_face() == side => 15,
         Block::RedstoneRepeater { repeater }
             if repeater.facing.block_face() == side && repeater.powered =>
         {
@@ -91,7 +121,14 @@ fn get_strong_power(
             } && button.powered,
         ),
         Block::StonePressurePlate { powered: true } if side == BlockFace::Top => 15,
+        Block::DetectorRail { powered: true, .. } if side == BlockFace::Top => 15,
+        Block::SculkSensor { power, .. } | Block::CalibratedSculkSensor { power, .. }
+            if side == BlockFace::Top =>
+        {
+            power
+        }
         Block::RedstoneWire { .. } => get_weak_power(block, world, pos, side, dust_power),
+        Block::Observer { .. } => get_weak_power(block, world, pos, side, dust_power),
         Block::RedstoneRepeater { .. } => get_weak_power(block, world, pos, side, dust_power),
         Block::RedstoneComparator { .. } => get_weak_power(block, world, pos, side, dust_power),
         _ => 0,
@@ -120,7 +157,8 @@ pub fn get_redstone_power(
     facing: BlockFace,
 ) -> u8 {
     if block.is_solid() {
-        get_max_strong_power(world, pos, true)
+        // Solid blocks like target blocks can emit power on their own
+        get_weak_power(block, world, pos, facing, true).max(get_max_strong_power(world, pos, true))
     } else {
         get_weak_power(block, world, pos, facing, true)
     }
@@ -133,7 +171,8 @@ fn get_redstone_power_no_dust(
     facing: BlockFace,
 ) -> u8 {
     if block.is_solid() {
-        get_max_strong_power(world, pos, false)
+        get_weak_power(block, world, pos, facing, false)
+            .max(get_max_strong_power(world, pos, false))
     } else {
         get_weak_power(block, world, pos, facing, false)
     }
@@ -145,6 +184,25 @@ pub fn torch_should_be_off(world: &impl World, pos: BlockPos) -> bool {
     get_redstone_power(bottom_block, world, bottom_pos, BlockFace::Top) > 0
 }
 
+/// The amount of game ticks a burnt out redstone torch stays off
+const TORCH_BURNOUT_TICKS: u32 = 160;
+/// The world event that plays the smoke particles and fizz sound of a torch burning out
+const TORCH_BURNOUT_EVENT: i32 = 1502;
+
+fn torch_toggled_too_frequently(world: &mut impl World, pos: BlockPos, log_toggle: bool) -> bool {
+    world
+        .torch_burnout()
+        .is_some_and(|burnout| burnout.toggled_too_frequently(pos, log_toggle))
+}
+
+/// Called after a redstone torch turned off, burns the torch out if it turned off too often
+fn check_torch_burnout(world: &mut impl World, pos: BlockPos) {
+    if torch_toggled_too_frequently(world, pos, true) {
+        world.play_world_event(pos, TORCH_BURNOUT_EVENT, 0);
+        world.schedule_tick(pos, TORCH_BURNOUT_TICKS, TickPriority::Normal);
+    }
+}
+
 pub fn wall_torch_should_be_off(
     world: &impl World,
     pos: BlockPos,
@@ -170,6 +228,26 @@ pub fn redstone_lamp_should_be_lit(world: &impl World, pos: BlockPos) -> bool {
     false
 }
 
+/// Returns true if the block at `pos` is powered through quasi-connectivity, meaning it is
+/// powered as if it were the upper half of a door. This is used for pistons, dispensers and
+/// droppers. Always returns false if quasi-connectivity is disabled in the world.
+pub fn is_quasi_powered(world: &impl World, pos: BlockPos) -> bool {
+    if !world.quasi_connectivity() {
+        return false;
+    }
+    let up_pos = pos.offset(BlockFace::Top);
+    for face in &BlockFace::values() {
+        if *face == BlockFace::Bottom {
+            continue;
+        }
+        let neighbor_pos = up_pos.offset(*face);
+        if get_redstone_power(world.get_block(neighbor_pos), world, neighbor_pos, *face) > 0 {
+            return true;
+        }
+    }
+    false
+}
+
 fn diode_get_input_strength(world: &impl World, pos: BlockPos, facing: BlockDirection) -> u8 {
     let input_pos = pos.offset(facing.block_face());
     let input_block = world.get_block(input_pos);
@@ -182,6 +260,21 @@ fn diode_get_input_strength(world: &impl World, pos: BlockPos, facing: BlockDire
     power
 }
 
+/// The registry id of `minecraft:bell`
+const BELL_BLOCK_TYPE: i32 = 795;
+
+/// Lets clients know that the bell at `pos` was rung so they can play its swinging animation
+fn ring_bell(world: &mut impl World, pos: BlockPos, facing: BlockDirection) {
+    let direction = match facing {
+        BlockDirection::North => 2,
+        BlockDirection::South => 3,
+        BlockDirection::West => 4,
+        BlockDirection::East => 5,
+    };
+    world.play_block_event(pos, BELL_BLOCK_TYPE, 1, direction);
+    sculk::emit_vibration(world, pos, GameEvent::BlockChange);
+}
+
 pub fn update(block: Block, world: &mut impl World, pos: BlockPos) {
     match block {
         Block::RedstoneWire { wire } => {
@@ -208,7 +301,7 @@ pub fn update(block: Block, world: &mut impl World, pos: BlockPos) {
             if lit && !should_be_lit {
                 world.schedule_tick(pos, 2, TickPriority::Normal);
             } else if !lit && should_be_lit {
-                world.set_block(pos, Block::RedstoneLamp { lit: true });
+                set_block_state(world, pos, Block::RedstoneLamp { lit: true });
             }
         }
         Block::IronTrapdoor {
@@ -223,7 +316,53 @@ pub fn update(block: Block, world: &mut impl World, pos: BlockPos) {
                     half,
                     powered: should_be_powered,
                 };
-                world.set_block(pos, new_block);
+                set_block_state(world, pos, new_block);
+            }
+        }
+        Block::IronDoor { door } | Block::OakDoor { door } => {
+            door::on_neighbor_updated(world, pos, door);
+        }
+        Block::OakFenceGate {
+            facing,
+            in_wall,
+            open,
+            powered,
+        } => {
+            let should_be_powered = redstone_lamp_should_be_lit(world, pos);
+            if powered != should_be_powered {
+                let new_block = Block::OakFenceGate {
+                    facing,
+                    in_wall,
+                    open: should_be_powered,
+                    powered: should_be_powered,
+                };
+                set_block_state(world, pos, new_block);
+                if open != should_be_powered {
+                    let event = if should_be_powered {
+                        GameEvent::BlockActivate
+                    } else {
+                        GameEvent::BlockDeactivate
+                    };
+                    sculk::emit_vibration(world, pos, event);
+                }
+            }
+        }
+        Block::Bell {
+            attachment,
+            facing,
+            powered,
+        } => {
+            let should_be_powered = redstone_lamp_should_be_lit(world, pos);
+            if powered != should_be_powered {
+                if should_be_powered {
+                    ring_bell(world, pos, facing);
+                }
+                let new_block = Block::Bell {
+                    attachment,
+                    facing,
+                    powered: should_be_powered,
+                };
+                set_block_state(world, pos, new_block);
             }
         }
         Block::NoteBlock {
@@ -249,9 +388,28 @@ pub fn update(block: Block, world: &mut impl World, pos: BlockPos) {
                 if should_be_powered && noteblock::is_noteblock_unblocked(world, pos) {
                     noteblock::play_note(world, pos, instrument, note);
                 }
-                world.set_block(pos, new_block);
+                set_block_state(world, pos, new_block);
             }
         }
+        Block::CopperBulb {
+            variant,
+            lit,
+            powered,
+        } => {
+            copper_bulb::on_neighbor_updated(world, pos, variant, lit, powered);
+        }
+        Block::Piston { extended, facing } => {
+            piston::on_neighbor_updated(world, pos, false, extended, facing);
+        }
+        Block::PoweredRail { powered, shape } | Block::ActivatorRail { powered, shape } => {
+            rail::on_neighbor_updated(world, pos, block, powered, shape);
+        }
+        Block::Dispenser { triggered, .. } | Block::Dropper { triggered, .. } => {
+            dispenser::on_neighbor_updated(world, pos, block, triggered);
+        }
+        Block::StickyPiston { extended, facing } => {
+            piston::on_neighbor_updated(world, pos, true, extended, facing);
+        }
         _ => {}
     }
 }
@@ -267,33 +425,66 @@ pub fn tick(block: Block, world: &mut impl World, pos: BlockPos) {
         Block::RedstoneTorch { lit } => {
             let should_be_off = torch_should_be_off(world, pos);
             if lit && should_be_off {
-                world.set_block(pos, Block::RedstoneTorch { lit: false });
+                set_block_state(world, pos, Block::RedstoneTorch { lit: false });
                 update_surrounding_blocks(world, pos);
-            } else if !lit && !should_be_off {
-                world.set_block(pos, Block::RedstoneTorch { lit: true });
+                check_torch_burnout(world, pos);
+            } else if !lit && !should_be_off && !torch_toggled_too_frequently(world, pos, false) {
+                set_block_state(world, pos, Block::RedstoneTorch { lit: true });
                 update_surrounding_blocks(world, pos);
             }
         }
         Block::RedstoneWallTorch { lit, facing } => {
             let should_be_off = wall_torch_should_be_off(world, pos, facing);
             if lit && should_be_off {
-                world.set_block(pos, Block::RedstoneWallTorch { lit: false, facing });
+                set_block_state(world, pos, Block::RedstoneWallTorch { lit: false, facing });
                 update_surrounding_blocks(world, pos);
-            } else if !lit && !should_be_off {
-                world.set_block(pos, Block::RedstoneWallTorch { lit: true, facing });
+                check_torch_burnout(world, pos);
+            } else if !lit && !should_be_off && !torch_toggled_too_frequently(world, pos, false) {
+                set_block_state(world, pos, Block::RedstoneWallTorch { lit: true, facing });
                 update_surrounding_blocks(world, pos);
             }
         }
         Block::RedstoneLamp { lit } => {
             let should_be_lit = redstone_lamp_should_be_lit(world, pos);
             if lit && !should_be_lit {
-                world.set_block(pos, Block::RedstoneLamp { lit: false });
+                set_block_state(world, pos, Block::RedstoneLamp { lit: false });
             }
         }
+        Block::MovingPiston { .. } => {
+            piston::finish_move(world, pos);
+        }
+        Block::Dispenser { facing, .. } => {
+            dispenser::dispense(world, pos, facing, false);
+        }
+        Block::Dropper { facing, .. } => {
+            dispenser::dispense(world, pos, facing, true);
+        }
+        Block::CopperBulb {
+            variant,
+            lit,
+            powered,
+        } => {
+            copper_bulb::tick(world, pos, variant, lit, powered);
+        }
+        Block::Observer { facing, powered } => {
+            observer::tick(world, pos, facing, powered);
+        }
+        Block::SculkSensor {
+            sculk_sensor_phase, ..
+        }
+        | Block::CalibratedSculkSensor {
+            sculk_sensor_phase, ..
+        } => {
+            sculk::tick(world, pos, sculk_sensor_phase);
+        }
+        Block::Target { power } if power > 0 => {
+            set_block_state(world, pos, Block::Target { power: 0 });
+            update_surrounding_blocks(world, pos);
+        }
         Block::StoneButton { mut button } => {
             if button.powered {
                 button.powered = false;
-                world.set_block(pos, Block::StoneButton { button });
+                set_block_state(world, pos, Block::StoneButton { button });
                 update_surrounding_blocks(world, pos);
                 match button.face {
                     ButtonFace::Ceiling => {
@@ -307,6 +498,7 @@ pub fn tick(block: Block, world: &mut impl World, pos: BlockPos) {
                         pos.offset(button.facing.opposite().block_face()),
                     ),
                 }
+                sculk::emit_vibration(world, pos, GameEvent::BlockDeactivate);
             }
         }
         _ => {}
@@ -314,11 +506,12 @@ pub fn tick(block: Block, world: &mut impl World, pos: BlockPos) {
 }
 
 pub fn update_wire_neighbors(world: &mut impl World, pos: BlockPos) {
-    for direction in &BlockFace::values() {
+    let faces = update_order(world);
+    for direction in &faces {
         let neighbor_pos = pos.offset(*direction);
         let block = world.get_block(neighbor_pos);
         update(block, world, neighbor_pos);
-        for n_direction in &BlockFace::values() {
+        for n_direction in &faces {
             let n_neighbor_pos = neighbor_pos.offset(*n_direction);
             let block = world.get_block(n_neighbor_pos);
             update(block, world, n_neighbor_pos);
@@ -326,8 +519,40 @@ pub fn update_wire_neighbors(world: &mut impl World, pos: BlockPos) {
     }
 }
 
+/// The order vanilla updates the neighbors of a block in
+const VANILLA_UPDATE_ORDER: [BlockFace; 6] = [
+    BlockFace::West,
+    BlockFace::East,
+    BlockFace::Bottom,
+    BlockFace::Top,
+    BlockFace::North,
+    BlockFace::South,
+];
+
+/// Returns the order the neighbors of a block are updated in
+fn update_order(world: &impl World) -> [BlockFace; 6] {
+    if world.strict_update_order() {
+        VANILLA_UPDATE_ORDER
+    } else {
+        BlockFace::values()
+    }
+}
+
+/// Runs a block event that was queued because the world has a strict update order
+pub fn run_block_event(world: &mut impl World, event: BlockEvent) {
+    match world.get_block(event.pos) {
+        Block::Piston { extended, facing } => {
+            piston::on_block_event(world, event.pos, false, extended, facing, event.ty);
+        }
+        Block::StickyPiston { extended, facing } => {
+            piston::on_block_event(world, event.pos, true, extended, facing, event.ty);
+        }
+        _ => {}
+    }
+}
+
 pub fn update_surrounding_blocks(world: &mut impl World, pos: BlockPos) {
-    for direction in &BlockFace::values() {
+    for direction in &update_order(world) {
         let neighbor_pos = pos.offset(*direction);
         let block = world.get_block(neighbor_pos);
         update(block, world, neighbor_pos);
@@ -360,19 +585,21 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
             if repeater.delay > 4 {
                 repeater.delay -= 4;
             }
-            world.set_block(pos, Block::RedstoneRepeater { repeater });
+            set_block_state(world, pos, Block::RedstoneRepeater { repeater });
+            sculk::emit_vibration(world, pos, GameEvent::BlockChange);
             true
         }
         Block::RedstoneComparator { comparator } => {
             let mut comparator = comparator;
             comparator.mode = comparator.mode.toggle();
             comparator::tick(comparator, world, pos);
-            world.set_block(pos, Block::RedstoneComparator { comparator });
+            set_block_state(world, pos, Block::RedstoneComparator { comparator });
+            sculk::emit_vibration(world, pos, GameEvent::BlockChange);
             true
         }
         Block::Lever { mut lever } => {
             lever.powered = !lever.powered;
-            world.set_block(pos, Block::Lever { lever });
+            set_block_state(world, pos, Block::Lever { lever });
             update_surrounding_blocks(world, pos);
             match lever.face {
                 LeverFace::Ceiling => {
@@ -386,12 +613,18 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
                     pos.offset(lever.facing.opposite().block_face()),
                 ),
             }
+            let event = if lever.powered {
+                GameEvent::BlockActivate
+            } else {
+                GameEvent::BlockDeactivate
+            };
+            sculk::emit_vibration(world, pos, event);
             true
         }
         Block::StoneButton { mut button } => {
             if !button.powered {
                 button.powered = true;
-                world.set_block(pos, Block::StoneButton { button });
+                set_block_state(world, pos, Block::StoneButton { button });
                 world.schedule_tick(pos, 10, TickPriority::Normal);
                 update_surrounding_blocks(world, pos);
                 match button.face {
@@ -406,6 +639,7 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
                         pos.offset(button.facing.opposite().block_face()),
                     ),
                 }
+                sculk::emit_vibration(world, pos, GameEvent::BlockActivate);
             }
             true
         }
@@ -419,7 +653,7 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
                 new_wire.power = wire.power;
                 new_wire = wire::get_regulated_sides(new_wire, world, pos);
                 if wire != new_wire {
-                    world.set_block(pos, Block::RedstoneWire { wire: new_wire });
+                    set_block_state(world, pos, Block::RedstoneWire { wire: new_wire });
                     update_wire_neighbors(world, pos);
                     return true;
                 }
@@ -430,7 +664,8 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
             let note = (note + 1) % 25;
             let instrument = noteblock::get_noteblock_instrument(world, pos);
 
-            world.set_block(
+            set_block_state(
+                world,
                 pos,
                 Block::NoteBlock {
                     instrument,
@@ -441,6 +676,7 @@ pub fn on_use(block: Block, world: &mut impl World, pos: BlockPos) -> bool {
 
             if noteblock::is_noteblock_unblocked(world, pos) {
                 noteblock::play_note(world, pos, instrument, note);
+                sculk::emit_vibration(world, pos, GameEvent::NoteBlockPlay);
             }
 
             true
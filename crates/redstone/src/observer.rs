@@ -0,0 +1,48 @@
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::{BlockFace, BlockFacing, BlockPos};
+use mchprs_world::{TickPriority, World};
+
+/// The length of the pulse an observer emits in game ticks
+pub const PULSE_LENGTH: u32 = 2;
+
+/// Schedules the observers that are watching the block at `pos`
+pub fn update_observers(world: &mut impl World, pos: BlockPos) {
+    for face in BlockFace::values() {
+        let observer_pos = pos.offset(face);
+        if let Block::Observer {
+            facing,
+            powered: false,
+        } = world.get_block(observer_pos)
+        {
+            if facing.opposite().block_face() == face && !world.pending_tick_at(observer_pos) {
+                world.schedule_tick(observer_pos, PULSE_LENGTH, TickPriority::Normal);
+            }
+        }
+    }
+}
+
+pub fn tick(world: &mut impl World, pos: BlockPos, facing: BlockFacing, powered: bool) {
+    if !powered {
+        world.schedule_tick(pos, PULSE_LENGTH, TickPriority::Normal);
+    }
+    super::set_block_state(
+        world,
+        pos,
+        Block::Observer {
+            facing,
+            powered: !powered,
+        },
+    );
+    update_neighbors_in_front(world, pos, facing);
+}
+
+fn update_neighbors_in_front(world: &mut impl World, pos: BlockPos, facing: BlockFacing) {
+    let front_pos = pos.offset(facing.opposite().block_face());
+    let front_block = world.get_block(front_pos);
+    super::update(front_block, world, front_pos);
+    for direction in &BlockFace::values() {
+        let neighbor_pos = front_pos.offset(*direction);
+        let block = world.get_block(neighbor_pos);
+        super::update(block, world, neighbor_pos);
+    }
+}
@@ -0,0 +1,326 @@
+//! Pistons and sticky pistons.
+//!
+//! While blocks are being moved they are represented by a `moving_piston` block with a
+//! [`BlockEntity::MovingPiston`] that holds the moved block state. The moved block is
+//! placed once the move finishes, [`MOVE_TICKS`] game ticks later.
+
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::blocks::{Block, PistonType};
+use mchprs_blocks::{BlockFace, BlockFacing, BlockPos};
+use mchprs_world::{BlockEvent, BlockEventType, TickPriority, World};
+
+/// The maximum amount of blocks a single piston can push
+pub const MAX_PUSH_LENGTH: usize = 12;
+/// The amount of game ticks it takes a piston to finish moving blocks
+pub const MOVE_TICKS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PushReaction {
+    Normal,
+    Destroy,
+    Block,
+}
+
+fn push_reaction(block: Block) -> PushReaction {
+    match block {
+        Block::Piston { extended, .. } | Block::StickyPiston { extended, .. } => {
+            if extended {
+                PushReaction::Block
+            } else {
+                PushReaction::Normal
+            }
+        }
+        Block::RedstoneWire { .. }
+        | Block::RedstoneTorch { .. }
+        | Block::RedstoneWallTorch { .. }
+        | Block::RedstoneRepeater { .. }
+        | Block::RedstoneComparator { .. }
+        | Block::Lever { .. }
+        | Block::StoneButton { .. }
+        | Block::StonePressurePlate { .. }
+        | Block::TripwireHook { .. }
+        | Block::Sign { .. }
+        | Block::WallSign { .. }
+        | Block::SeaPickle { .. }
+        | Block::Cake { .. } => PushReaction::Destroy,
+        Block::PistonHead { .. } | Block::MovingPiston { .. } | Block::EndPortalFrame { .. } => {
+            PushReaction::Block
+        }
+        block if block.has_block_entity() => PushReaction::Block,
+        _ => PushReaction::Normal,
+    }
+}
+
+fn piston_block(sticky: bool, extended: bool, facing: BlockFacing) -> Block {
+    if sticky {
+        Block::StickyPiston { extended, facing }
+    } else {
+        Block::Piston { extended, facing }
+    }
+}
+
+fn piston_type(sticky: bool) -> PistonType {
+    if sticky {
+        PistonType::Sticky
+    } else {
+        PistonType::Normal
+    }
+}
+
+fn is_in_world(world: &impl World, pos: BlockPos) -> bool {
+    world
+        .get_chunk(pos.x.div_euclid(16), pos.z.div_euclid(16))
+        .is_some_and(|chunk| (chunk.min_y..chunk.max_y()).contains(&pos.y))
+}
+
+pub fn should_extend(world: &impl World, pos: BlockPos, facing: BlockFacing) -> bool {
+    for face in BlockFace::values() {
+        // Pistons can't be powered through their face
+        if face == facing.block_face() {
+            continue;
+        }
+        let neighbor_pos = pos.offset(face);
+        if super::get_redstone_power(world.get_block(neighbor_pos), world, neighbor_pos, face) > 0 {
+            return true;
+        }
+    }
+    super::is_quasi_powered(world, pos)
+}
+
+/// Returns the positions of the blocks that would be pushed, ordered from closest to furthest
+/// away from the piston, and the positions of the blocks that would be destroyed.
+/// Returns `None` if the piston is unable to extend.
+fn resolve_push(
+    world: &impl World,
+    pos: BlockPos,
+    facing: BlockFacing,
+) -> Option<(Vec<BlockPos>, Vec<BlockPos>)> {
+    let mut to_push = Vec::new();
+    let mut to_destroy = Vec::new();
+    let mut cur_pos = pos;
+    loop {
+        cur_pos = cur_pos.offset(facing.block_face());
+        if !is_in_world(world, cur_pos) {
+            return None;
+        }
+        let block = world.get_block(cur_pos);
+        if block.can_place_block_in() {
+            break;
+        }
+        match push_reaction(block) {
+            PushReaction::Block => return None,
+            PushReaction::Destroy => {
+                to_destroy.push(cur_pos);
+                break;
+            }
+            PushReaction::Normal => {
+                if to_push.len() == MAX_PUSH_LENGTH {
+                    return None;
+                }
+                to_push.push(cur_pos);
+            }
+        }
+    }
+    Some((to_push, to_destroy))
+}
+
+fn start_move(
+    world: &mut impl World,
+    pos: BlockPos,
+    block: Block,
+    facing: BlockFacing,
+    ty: PistonType,
+    extending: bool,
+    source: bool,
+) {
+    super::set_block_state(world, pos, Block::MovingPiston { facing, ty });
+    world.set_block_entity(
+        pos,
+        BlockEntity::MovingPiston {
+            block_state: block.get_id(),
+            facing,
+            extending,
+            source,
+        },
+    );
+    world.schedule_tick(pos, MOVE_TICKS, TickPriority::Normal);
+}
+
+/// Places the block that is being moved at `pos`
+pub fn finish_move(world: &mut impl World, pos: BlockPos) {
+    let block = match world.get_block_entity(pos) {
+        Some(&BlockEntity::MovingPiston { block_state, .. }) => Block::from_id(block_state),
+        _ => Block::Air {},
+    };
+    world.delete_block_entity(pos);
+    super::set_block_state(world, pos, block);
+    super::update(block, world, pos);
+    super::update_surrounding_blocks(world, pos);
+}
+
+fn extend(world: &mut impl World, pos: BlockPos, sticky: bool, facing: BlockFacing) {
+    let Some((to_push, to_destroy)) = resolve_push(world, pos, facing) else {
+        return;
+    };
+    let face = facing.block_face();
+
+    for &destroy_pos in &to_destroy {
+        world.delete_block_entity(destroy_pos);
+        super::set_block_state(world, destroy_pos, Block::Air {});
+    }
+
+    // Start with the block furthest away so that no block is overwritten before it is moved.
+    // The position closest to the piston is then taken over by the piston head.
+    for &from in to_push.iter().rev() {
+        let block = world.get_block(from);
+        start_move(
+            world,
+            from.offset(face),
+            block,
+            facing,
+            PistonType::Normal,
+            true,
+            false,
+        );
+    }
+
+    let head_pos = pos.offset(face);
+    let head = Block::PistonHead {
+        facing,
+        short: false,
+        ty: piston_type(sticky),
+    };
+    start_move(
+        world,
+        head_pos,
+        head,
+        facing,
+        piston_type(sticky),
+        true,
+        true,
+    );
+    super::set_block_state(world, pos, piston_block(sticky, true, facing));
+
+    for &destroy_pos in &to_destroy {
+        super::update_surrounding_blocks(world, destroy_pos);
+    }
+    super::update_surrounding_blocks(world, pos);
+    super::update_surrounding_blocks(world, head_pos);
+    for &from in &to_push {
+        super::update_surrounding_blocks(world, from.offset(face));
+    }
+}
+
+fn retract(world: &mut impl World, pos: BlockPos, sticky: bool, facing: BlockFacing) {
+    let face = facing.block_face();
+    let head_pos = pos.offset(face);
+
+    // The base is turned into a moving block first so that it doesn't react to the updates below
+    start_move(
+        world,
+        pos,
+        piston_block(sticky, false, facing),
+        facing,
+        piston_type(sticky),
+        false,
+        true,
+    );
+
+    // If the piston hasn't finished extending yet, the moving blocks are placed instantly.
+    // Sticky pistons will leave their block behind in this case.
+    let mut dropped_block = false;
+    if let Some(BlockEntity::MovingPiston {
+        extending: true,
+        source: true,
+        ..
+    }) = world.get_block_entity(head_pos)
+    {
+        dropped_block = world.zero_tick_pistons();
+        let mut move_pos = head_pos;
+        while let Some(&BlockEntity::MovingPiston {
+            extending: true,
+            facing: move_facing,
+            ..
+        }) = world.get_block_entity(move_pos)
+        {
+            if move_facing != facing {
+                break;
+            }
+            finish_move(world, move_pos);
+            move_pos = move_pos.offset(face);
+        }
+    }
+
+    if !matches!(world.get_block(head_pos), Block::PistonHead { .. }) {
+        super::update_surrounding_blocks(world, pos);
+        return;
+    }
+
+    let pull_pos = head_pos.offset(face);
+    let pull_block = world.get_block(pull_pos);
+    if sticky
+        && !dropped_block
+        && !pull_block.can_place_block_in()
+        && push_reaction(pull_block) == PushReaction::Normal
+    {
+        super::set_block_state(world, pull_pos, Block::Air {});
+        start_move(
+            world,
+            head_pos,
+            pull_block,
+            facing.opposite(),
+            PistonType::Normal,
+            false,
+            false,
+        );
+        super::update_surrounding_blocks(world, pull_pos);
+    } else {
+        super::set_block_state(world, head_pos, Block::Air {});
+    }
+
+    super::update_surrounding_blocks(world, pos);
+    super::update_surrounding_blocks(world, head_pos);
+}
+
+pub fn on_neighbor_updated(
+    world: &mut impl World,
+    pos: BlockPos,
+    sticky: bool,
+    extended: bool,
+    facing: BlockFacing,
+) {
+    let should_extend = should_extend(world, pos, facing);
+    let ty = if should_extend && !extended {
+        BlockEventType::PistonExtend
+    } else if !should_extend && extended {
+        BlockEventType::PistonRetract
+    } else {
+        return;
+    };
+    if world.strict_update_order() {
+        world.queue_block_event(BlockEvent { pos, ty });
+    } else {
+        on_block_event(world, pos, sticky, extended, facing, ty);
+    }
+}
+
+/// Moves the piston if it still should when its queued block event is run
+pub fn on_block_event(
+    world: &mut impl World,
+    pos: BlockPos,
+    sticky: bool,
+    extended: bool,
+    facing: BlockFacing,
+    ty: BlockEventType,
+) {
+    let should_extend = should_extend(world, pos, facing);
+    match ty {
+        BlockEventType::PistonExtend if should_extend && !extended => {
+            extend(world, pos, sticky, facing);
+        }
+        BlockEventType::PistonRetract if !should_extend && extended => {
+            retract(world, pos, sticky, facing);
+        }
+        _ => {}
+    }
+}
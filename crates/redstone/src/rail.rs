@@ -0,0 +1,129 @@
+//! Powered and activator rails are powered either directly or through a chain of up to 8
+//! powered rails of the same kind leading to a directly powered one.
+
+use crate::{redstone_lamp_should_be_lit, set_block_state, update_surrounding_blocks};
+use mchprs_blocks::blocks::{Block, RailShape};
+use mchprs_blocks::{BlockFace, BlockPos};
+use mchprs_world::World;
+
+/// How many rails a rail can be away from a directly powered rail and still be powered by it
+const MAX_RAIL_DISTANCE: u8 = 8;
+
+fn with_powered(block: Block, powered: bool) -> Block {
+    match block {
+        Block::PoweredRail { shape, .. } => Block::PoweredRail { powered, shape },
+        Block::ActivatorRail { shape, .. } => Block::ActivatorRail { powered, shape },
+        _ => block,
+    }
+}
+
+fn get_rail(block: Block) -> Option<(bool, RailShape)> {
+    match block {
+        Block::PoweredRail { powered, shape } | Block::ActivatorRail { powered, shape } => {
+            Some((powered, shape))
+        }
+        _ => None,
+    }
+}
+
+/// Follows the rail at `pos` in one direction looking for a powered rail that has a signal
+fn find_powered_rail_signal(
+    world: &impl World,
+    block: Block,
+    pos: BlockPos,
+    shape: RailShape,
+    search_forward: bool,
+    distance: u8,
+) -> bool {
+    if distance >= MAX_RAIL_DISTANCE {
+        return false;
+    }
+    let (offset, straight_shape, check_below) = match (shape, search_forward) {
+        (RailShape::NorthSouth, true) => (BlockPos::new(0, 0, 1), shape, true),
+        (RailShape::NorthSouth, false) => (BlockPos::new(0, 0, -1), shape, true),
+        (RailShape::EastWest, true) => (BlockPos::new(-1, 0, 0), shape, true),
+        (RailShape::EastWest, false) => (BlockPos::new(1, 0, 0), shape, true),
+        (RailShape::AscendingEast, true) => (BlockPos::new(-1, 0, 0), RailShape::EastWest, true),
+        (RailShape::AscendingEast, false) => (BlockPos::new(1, 1, 0), RailShape::EastWest, false),
+        (RailShape::AscendingWest, true) => (BlockPos::new(-1, 1, 0), RailShape::EastWest, false),
+        (RailShape::AscendingWest, false) => (BlockPos::new(1, 0, 0), RailShape::EastWest, true),
+        (RailShape::AscendingNorth, true) => (BlockPos::new(0, 0, 1), RailShape::NorthSouth, true),
+        (RailShape::AscendingNorth, false) => {
+            (BlockPos::new(0, 1, -1), RailShape::NorthSouth, false)
+        }
+        (RailShape::AscendingSouth, true) => (BlockPos::new(0, 1, 1), RailShape::NorthSouth, false),
+        (RailShape::AscendingSouth, false) => {
+            (BlockPos::new(0, 0, -1), RailShape::NorthSouth, true)
+        }
+    };
+    let next_pos = pos + offset;
+    is_same_rail_with_power(
+        world,
+        block,
+        next_pos,
+        straight_shape,
+        search_forward,
+        distance,
+    ) || (check_below
+        && is_same_rail_with_power(
+            world,
+            block,
+            next_pos.offset(BlockFace::Bottom),
+            straight_shape,
+            search_forward,
+            distance,
+        ))
+}
+
+fn is_same_rail_with_power(
+    world: &impl World,
+    block: Block,
+    pos: BlockPos,
+    shape: RailShape,
+    search_forward: bool,
+    distance: u8,
+) -> bool {
+    let other = world.get_block(pos);
+    if std::mem::discriminant(&other) != std::mem::discriminant(&block) {
+        return false;
+    }
+    let Some((powered, other_shape)) = get_rail(other) else {
+        return false;
+    };
+    let connects = match shape {
+        RailShape::EastWest => matches!(
+            other_shape,
+            RailShape::EastWest | RailShape::AscendingEast | RailShape::AscendingWest
+        ),
+        _ => matches!(
+            other_shape,
+            RailShape::NorthSouth | RailShape::AscendingNorth | RailShape::AscendingSouth
+        ),
+    };
+    if !connects || !powered {
+        return false;
+    }
+    redstone_lamp_should_be_lit(world, pos)
+        || find_powered_rail_signal(world, other, pos, other_shape, search_forward, distance + 1)
+}
+
+pub fn on_neighbor_updated(
+    world: &mut impl World,
+    pos: BlockPos,
+    block: Block,
+    powered: bool,
+    shape: RailShape,
+) {
+    let should_be_powered = redstone_lamp_should_be_lit(world, pos)
+        || find_powered_rail_signal(world, block, pos, shape, true, 0)
+        || find_powered_rail_signal(world, block, pos, shape, false, 0);
+    if powered == should_be_powered {
+        return;
+    }
+    set_block_state(world, pos, with_powered(block, should_be_powered));
+    update_surrounding_blocks(world, pos);
+    update_surrounding_blocks(world, pos.offset(BlockFace::Bottom));
+    if shape.is_ascending() {
+        update_surrounding_blocks(world, pos.offset(BlockFace::Top));
+    }
+}
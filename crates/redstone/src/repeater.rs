@@ -1,5 +1,5 @@
 use mchprs_blocks::blocks::{Block, RedstoneRepeater};
-use mchprs_blocks::{BlockDirection, BlockFace, BlockPos};
+use mchprs_blocks::{BlockDirection, BlockPos};
 use mchprs_world::{TickPriority, World};
 
 pub fn get_state_for_placement(
@@ -35,7 +35,7 @@ fn on_state_change(rep: RedstoneRepeater, world: &mut impl World, pos: BlockPos)
     let front_pos = pos.offset(rep.facing.opposite().block_face());
     let front_block = world.get_block(front_pos);
     super::update(front_block, world, front_pos);
-    for direction in &BlockFace::values() {
+    for direction in &super::update_order(world) {
         let neighbor_pos = front_pos.offset(*direction);
         let block = world.get_block(neighbor_pos);
         super::update(block, world, neighbor_pos);
@@ -49,7 +49,9 @@ fn schedule_tick(
     should_be_powered: bool,
 ) {
     let front_block = world.get_block(pos.offset(rep.facing.opposite().block_face()));
-    let priority = if super::is_diode(front_block) {
+    let priority = if !world.repeater_tick_priority() {
+        TickPriority::High
+    } else if super::is_diode(front_block) {
         TickPriority::Highest
     } else if !should_be_powered {
         TickPriority::Higher
@@ -67,10 +69,10 @@ pub fn on_neighbor_updated(mut rep: RedstoneRepeater, world: &mut impl World, po
     let should_be_locked = should_be_locked(rep.facing, world, pos);
     if !rep.locked && should_be_locked {
         rep.locked = true;
-        world.set_block(pos, Block::RedstoneRepeater { repeater: rep });
+        super::set_block_state(world, pos, Block::RedstoneRepeater { repeater: rep });
     } else if rep.locked && !should_be_locked {
         rep.locked = false;
-        world.set_block(pos, Block::RedstoneRepeater { repeater: rep });
+        super::set_block_state(world, pos, Block::RedstoneRepeater { repeater: rep });
     }
 
     if !rep.locked && !world.pending_tick_at(pos) {
@@ -89,14 +91,19 @@ pub fn tick(mut rep: RedstoneRepeater, world: &mut impl World, pos: BlockPos) {
     let should_be_powered = should_be_powered(rep, world, pos);
     if rep.powered && !should_be_powered {
         rep.powered = false;
-        world.set_block(pos, Block::RedstoneRepeater { repeater: rep });
+        super::set_block_state(world, pos, Block::RedstoneRepeater { repeater: rep });
         on_state_change(rep, world, pos);
     } else if !rep.powered {
         if !should_be_powered {
-            world.schedule_tick(pos, rep.delay as u32, TickPriority::Higher);
+            let priority = if world.repeater_tick_priority() {
+                TickPriority::Higher
+            } else {
+                TickPriority::High
+            };
+            world.schedule_tick(pos, rep.delay as u32, priority);
         }
         rep.powered = true;
-        world.set_block(pos, Block::RedstoneRepeater { repeater: rep });
+        super::set_block_state(world, pos, Block::RedstoneRepeater { repeater: rep });
         on_state_change(rep, world, pos);
     }
 }
@@ -0,0 +1,182 @@
+//! Sculk sensors and the vibrations they listen to.
+//!
+//! Vibrations are emitted at a position and travel towards every sculk sensor in range at a speed
+//! of one block per game tick, unless the path between the two is blocked by wool.
+
+use mchprs_blocks::block_entities::{BlockEntity, Vibration};
+use mchprs_blocks::blocks::{Block, SculkSensorPhase};
+use mchprs_blocks::{BlockFace, BlockPos};
+use mchprs_world::{GameEvent, TickPriority, World};
+
+/// The range of regular sculk sensors
+pub const SENSOR_RANGE: i32 = 8;
+/// The range of calibrated sculk sensors
+pub const CALIBRATED_SENSOR_RANGE: i32 = 16;
+/// How long sculk sensors stay active after receiving a vibration
+pub const SENSOR_ACTIVE_TICKS: u32 = 30;
+/// How long calibrated sculk sensors stay active after receiving a vibration
+pub const CALIBRATED_SENSOR_ACTIVE_TICKS: u32 = 10;
+/// How long sculk sensors stay in cooldown before they can be activated again
+pub const COOLDOWN_TICKS: u32 = 10;
+
+fn distance(a: BlockPos, b: BlockPos) -> f64 {
+    let dx = (a.x - b.x) as f64;
+    let dy = (a.y - b.y) as f64;
+    let dz = (a.z - b.z) as f64;
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+/// Returns true if there is wool on the line between the centers of `from` and `to`
+fn is_occluded(world: &impl World, from: BlockPos, to: BlockPos) -> bool {
+    let steps = (distance(from, to) * 4.0).ceil() as i32;
+    for step in 1..steps {
+        let t = step as f64 / steps as f64;
+        let lerp = |a: i32, b: i32| (a as f64 + 0.5 + (b - a) as f64 * t).floor() as i32;
+        let pos = BlockPos::new(lerp(from.x, to.x), lerp(from.y, to.y), lerp(from.z, to.z));
+        if pos != from && pos != to && matches!(world.get_block(pos), Block::Wool { .. }) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the redstone power a sensor outputs for a vibration that travelled `distance` blocks
+fn power_for_distance(distance: f64, range: i32) -> u8 {
+    let power = 15 - (distance / range as f64 * 15.0).floor() as i32;
+    power.clamp(1, 15) as u8
+}
+
+/// Lets every sculk sensor in range know about `event` happening at `pos`
+pub fn emit_vibration(world: &mut impl World, pos: BlockPos, event: GameEvent) {
+    let range = CALIBRATED_SENSOR_RANGE;
+    for x in -range..=range {
+        for y in -range..=range {
+            for z in -range..=range {
+                let sensor_pos = BlockPos::new(pos.x + x, pos.y + y, pos.z + z);
+                match world.get_block(sensor_pos) {
+                    Block::SculkSensor {
+                        sculk_sensor_phase: SculkSensorPhase::Inactive,
+                        ..
+                    } => receive_vibration(world, sensor_pos, pos, event, false),
+                    Block::CalibratedSculkSensor {
+                        sculk_sensor_phase: SculkSensorPhase::Inactive,
+                        facing,
+                        ..
+                    } => {
+                        // Calibrated sensors only listen to the frequency of their input signal
+                        let input_pos = sensor_pos.offset(facing.block_face());
+                        let input_block = world.get_block(input_pos);
+                        let input = super::get_redstone_power(
+                            input_block,
+                            world,
+                            input_pos,
+                            facing.block_face(),
+                        );
+                        if input == 0 || input == event.frequency() {
+                            receive_vibration(world, sensor_pos, pos, event, true);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn receive_vibration(
+    world: &mut impl World,
+    pos: BlockPos,
+    source: BlockPos,
+    event: GameEvent,
+    calibrated: bool,
+) {
+    let range = if calibrated {
+        CALIBRATED_SENSOR_RANGE
+    } else {
+        SENSOR_RANGE
+    };
+    let distance = distance(pos, source);
+    if pos == source
+        || distance > range as f64
+        || world.pending_tick_at(pos)
+        || is_occluded(world, source, pos)
+    {
+        return;
+    }
+
+    let last_vibration_frequency = match world.get_block_entity(pos) {
+        Some(&BlockEntity::SculkSensor {
+            last_vibration_frequency,
+            ..
+        }) => last_vibration_frequency,
+        _ => 0,
+    };
+    world.set_block_entity(
+        pos,
+        BlockEntity::SculkSensor {
+            last_vibration_frequency,
+            vibration: Some(Vibration {
+                frequency: event.frequency(),
+                power: power_for_distance(distance, range),
+            }),
+            calibrated,
+        },
+    );
+    let travel_ticks = (distance.floor() as u32).max(1);
+    world.schedule_tick(pos, travel_ticks, TickPriority::Normal);
+}
+
+fn set_sensor_state(world: &mut impl World, pos: BlockPos, phase: SculkSensorPhase, power: u8) {
+    let block = match world.get_block(pos) {
+        Block::SculkSensor { .. } => Block::SculkSensor {
+            power,
+            sculk_sensor_phase: phase,
+        },
+        Block::CalibratedSculkSensor { facing, .. } => Block::CalibratedSculkSensor {
+            facing,
+            power,
+            sculk_sensor_phase: phase,
+        },
+        _ => return,
+    };
+    super::set_block_state(world, pos, block);
+    super::update_surrounding_blocks(world, pos);
+    super::update_surrounding_blocks(world, pos.offset(BlockFace::Bottom));
+}
+
+pub fn tick(world: &mut impl World, pos: BlockPos, phase: SculkSensorPhase) {
+    match phase {
+        SculkSensorPhase::Inactive => {
+            let Some(&BlockEntity::SculkSensor {
+                vibration: Some(vibration),
+                calibrated,
+                ..
+            }) = world.get_block_entity(pos)
+            else {
+                return;
+            };
+            world.set_block_entity(
+                pos,
+                BlockEntity::SculkSensor {
+                    last_vibration_frequency: vibration.frequency,
+                    vibration: None,
+                    calibrated,
+                },
+            );
+            let active_ticks = if calibrated {
+                CALIBRATED_SENSOR_ACTIVE_TICKS
+            } else {
+                SENSOR_ACTIVE_TICKS
+            };
+            world.schedule_tick(pos, active_ticks, TickPriority::Normal);
+            set_sensor_state(world, pos, SculkSensorPhase::Active, vibration.power);
+        }
+        SculkSensorPhase::Active => {
+            world.schedule_tick(pos, COOLDOWN_TICKS, TickPriority::Normal);
+            set_sensor_state(world, pos, SculkSensorPhase::Cooldown, 0);
+        }
+        SculkSensorPhase::Cooldown => {
+            set_sensor_state(world, pos, SculkSensorPhase::Inactive, 0);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use mchprs_blocks::blocks::Block;
+use mchprs_blocks::BlockPos;
+use mchprs_world::{TickPriority, World};
+
+/// How long a target block stays powered after being hit by an arrow or trident
+pub const ARROW_DURATION: u32 = 20;
+/// How long a target block stays powered after being hit by any other projectile
+pub const PROJECTILE_DURATION: u32 = 8;
+
+/// Makes the target block at `pos` output `power` for `duration` game ticks, as if it was hit by
+/// a projectile. Does nothing if the target block is already powered.
+pub fn trigger(world: &mut impl World, pos: BlockPos, power: u8, duration: u32) {
+    if !matches!(world.get_block(pos), Block::Target { .. }) || world.pending_tick_at(pos) {
+        return;
+    }
+    super::set_block_state(
+        world,
+        pos,
+        Block::Target {
+            power: power.min(15),
+        },
+    );
+    world.schedule_tick(pos, duration, TickPriority::Normal);
+    super::update_surrounding_blocks(world, pos);
+}
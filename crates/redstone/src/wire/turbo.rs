@@ -389,7 +389,7 @@ impl RedstoneWireTurbo {
         }
         if i != j {
             wire.power = j;
-            world.set_block(pos, Block::RedstoneWire { wire });
+            crate::set_block_state(world, pos, Block::RedstoneWire { wire });
         }
         wire
     }
@@ -1 +1,3 @@
 pub mod plot_data;
+pub mod plugin_data;
+pub mod schematic_index;
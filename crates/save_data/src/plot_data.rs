@@ -5,12 +5,13 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::BlockPos;
 use mchprs_world::storage::{Chunk, ChunkSection};
-use mchprs_world::TickEntry;
+use mchprs_world::{ItemFrame, TickEntry};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::str::FromStr;
 use std::{fmt, io};
 use thiserror::Error;
 
@@ -18,7 +19,13 @@ use thiserror::Error;
 /// 0: Initial plot data file with header (MC 1.18.2)
 /// 1: Add world send rate
 /// 2: Update to MC 1.20.4
-pub const VERSION: u32 = 2;
+/// 3: Add item frames
+/// 4: Add plot members
+/// 5: Add plot settings
+/// 6: Add plot time and weather
+/// 7: Add redpiler flags to resume with
+/// 8: Add the bottom y of each chunk
+pub const VERSION: u32 = 8;
 
 #[derive(Error, Debug)]
 pub enum PlotLoadError {
@@ -60,6 +67,12 @@ pub enum PlotSaveError {
 }
 
 static PLOT_MAGIC: &[u8; 8] = b"\x86MCHPRS\x00";
+/// Plot data after the header is either bincode or a zstd frame of it, which always starts with
+/// this magic number. Plots saved before compression was added are loaded as they are and
+/// compressed the next time they are saved.
+static ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xB5, 0x2F, 0xFD];
+/// The zstd level plots are saved with when no level is given
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct ChunkSectionData {
@@ -91,6 +104,8 @@ impl ChunkSectionData {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChunkData {
+    /// The y of the bottom of the lowest section
+    pub min_y: i32,
     pub sections: Vec<Option<ChunkSectionData>>,
     pub block_entities: FxHashMap<BlockPos, BlockEntity>,
 }
@@ -100,6 +115,7 @@ impl ChunkData {
     pub fn new(chunk: &mut Chunk) -> Self {
         chunk.flush();
         Self {
+            min_y: chunk.min_y,
             sections: chunk
                 .sections
                 .iter()
@@ -115,20 +131,38 @@ impl ChunkData {
         }
     }
 
+    /// Whether all blocks of the chunk are between `min_y` and the top of `num_sections` sections
+    /// starting there
+    pub fn fits_height(&self, min_y: i32, num_sections: usize) -> bool {
+        let max_y = min_y + num_sections as i32 * 16;
+        let sections_fit = self
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(_, section)| section.as_ref().is_some_and(|s| s.block_count > 0))
+            .all(|(index, _)| {
+                let y = self.min_y + index as i32 * 16;
+                (min_y..max_y).contains(&y)
+            });
+        sections_fit
+            && self
+                .block_entities
+                .keys()
+                .all(|pos| (min_y..max_y).contains(&pos.y))
+    }
+
     pub fn load(self, x: i32, z: i32) -> Chunk {
-        Chunk {
-            x,
-            z,
-            sections: self
-                .sections
-                .into_iter()
-                .map(|section| match section {
-                    Some(section) => section.load(),
-                    None => Default::default(),
-                })
-                .collect(),
-            block_entities: self.block_entities,
-        }
+        let mut chunk = Chunk::empty(x, z, 0).with_min_y(self.min_y);
+        chunk.sections = self
+            .sections
+            .into_iter()
+            .map(|section| match section {
+                Some(section) => section.load(),
+                None => Default::default(),
+            })
+            .collect();
+        chunk.block_entities = self.block_entities;
+        chunk
     }
 }
 
@@ -156,47 +190,247 @@ impl fmt::Display for Tps {
     }
 }
 
+/// Something the owner of a plot can allow other players to do on it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotPermission {
+    /// Placing, breaking and using blocks
+    Build,
+    WorldEdit,
+    /// Compiling and resetting redpiler
+    Redpiler,
+    /// Opening containers
+    Containers,
+}
+
+impl PlotPermission {
+    pub const ALL: [PlotPermission; 4] = [
+        PlotPermission::Build,
+        PlotPermission::WorldEdit,
+        PlotPermission::Redpiler,
+        PlotPermission::Containers,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlotPermission::Build => "build",
+            PlotPermission::WorldEdit => "worldedit",
+            PlotPermission::Redpiler => "redpiler",
+            PlotPermission::Containers => "containers",
+        }
+    }
+}
+
+impl FromStr for PlotPermission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlotPermission::ALL
+            .into_iter()
+            .find(|permission| permission.name() == s)
+            .ok_or(())
+    }
+}
+
+/// The weather shown to the players on a plot
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotWeather {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl PlotWeather {
+    pub const ALL: [PlotWeather; 3] = [PlotWeather::Clear, PlotWeather::Rain, PlotWeather::Thunder];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlotWeather::Clear => "clear",
+            PlotWeather::Rain => "rain",
+            PlotWeather::Thunder => "thunder",
+        }
+    }
+}
+
+impl FromStr for PlotWeather {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlotWeather::ALL
+            .into_iter()
+            .find(|weather| weather.name() == s)
+            .ok_or(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlotMember {
+    pub uuid: u128,
+    pub permissions: Vec<PlotPermission>,
+}
+
+/// Settings the owner of a plot has changed. Settings which are `None` use the server default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlotSettings {
+    pub auto_redpiler: Option<bool>,
+    pub quasi_connectivity: Option<bool>,
+    pub strict_update_order: Option<bool>,
+    /// The flags redpiler compiles with when no flags are given, and when it compiles
+    /// automatically
+    pub compiler_flags: String,
+    /// The time of day on the plot in ticks, from 0 to 23999. The daylight cycle is always
+    /// stopped, and it is noon if this isn't set.
+    pub time: Option<i64>,
+    pub weather: Option<PlotWeather>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PlotData {
     pub tps: Tps,
     pub world_send_rate: WorldSendRate,
     pub chunk_data: Vec<ChunkData>,
     pub pending_ticks: Vec<TickEntry>,
+    pub item_frames: Vec<ItemFrame>,
+    /// Players other than the owner which are allowed to do things on the plot
+    pub members: Vec<PlotMember>,
+    /// Players which aren't allowed to do anything on the plot
+    pub denied: Vec<u128>,
+    pub settings: PlotSettings,
+    /// The flags redpiler was running with when the server shut down, so that the plot is
+    /// compiled again when it is loaded
+    pub redpiler_flags: Option<String>,
 }
 
 impl PlotData {
-    pub fn load_from_file(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
-        let mut file = File::open(&path)?;
-
+    /// Reads the header of a plot file. Returns what has to be fixed if the plot was saved by an
+    /// older version.
+    fn read_header(file: &mut File) -> Result<Option<FixInfo>, PlotLoadError> {
         let mut magic = [0; 8];
         file.read_exact(&mut magic)?;
         if &magic != PLOT_MAGIC {
-            return fixer::try_fix(path, FixInfo::InvalidHeader)?
-                .ok_or(PlotLoadError::InvalidHeader);
+            return Ok(Some(FixInfo::InvalidHeader));
         }
 
         let version = file.read_u32::<LittleEndian>()?;
-        if version < VERSION {
-            return fixer::try_fix(path, FixInfo::OldVersion { version })?
-                .ok_or(PlotLoadError::ConversionFailed(version));
-        }
         if version > VERSION {
             return Err(PlotLoadError::TooNew(version));
         }
+        Ok((version < VERSION).then_some(FixInfo::OldVersion { version }))
+    }
+
+    fn fix(
+        path: impl AsRef<Path>,
+        info: FixInfo,
+        compression_level: i32,
+    ) -> Result<PlotData, PlotLoadError> {
+        let error = match &info {
+            FixInfo::InvalidHeader => PlotLoadError::InvalidHeader,
+            FixInfo::OldVersion { version } => PlotLoadError::ConversionFailed(*version),
+        };
+        fixer::try_fix(path, info, compression_level)?.ok_or(error)
+    }
+
+    /// Converts a plot saved by an older version to the current one, and saves it again at
+    /// `compression_level` with the old file kept as a backup. Plots that are already current are
+    /// left as they are.
+    pub fn upgrade_file(
+        path: impl AsRef<Path>,
+        compression_level: i32,
+    ) -> Result<(), PlotLoadError> {
+        let info = PlotData::read_header(&mut File::open(&path)?)?;
+        if let Some(info) = info {
+            PlotData::fix(path, info, compression_level)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a plot. Plots saved by older versions are upgraded like with
+    /// [`PlotData::upgrade_file`], at the default compression level.
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+        let mut file = File::open(&path)?;
+        if let Some(info) = PlotData::read_header(&mut file)? {
+            return PlotData::fix(path, info, DEFAULT_COMPRESSION_LEVEL);
+        }
 
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
+        if buf.starts_with(ZSTD_MAGIC) {
+            buf = zstd::decode_all(buf.as_slice())?;
+        }
         Ok(bincode::deserialize(&buf)?)
     }
 
-    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), PlotSaveError> {
+    /// Saves the plot compressed with zstd at `compression_level`, from 1 for the fastest to 22
+    /// for the smallest, or uncompressed if it is 0
+    pub fn save_to_file(
+        &self,
+        path: impl AsRef<Path>,
+        compression_level: i32,
+    ) -> Result<(), PlotSaveError> {
         let mut file = File::create(path)?;
 
         file.write_all(PLOT_MAGIC)?;
         file.write_u32::<LittleEndian>(VERSION)?;
-        let data = bincode::serialize(self)?;
-        file.write_all(&data)?;
+        if compression_level > 0 {
+            let mut encoder = zstd::Encoder::new(&mut file, compression_level)?;
+            bincode::serialize_into(&mut encoder, self)?;
+            encoder.finish()?;
+        } else {
+            let data = bincode::serialize(self)?;
+            file.write_all(&data)?;
+        }
         file.sync_data()?;
         Ok(())
     }
 }
+
+#[test]
+fn plot_permission_name_test() {
+    for permission in PlotPermission::ALL {
+        assert_eq!(permission.name().parse(), Ok(permission));
+    }
+    assert_eq!("WorldEdit".parse::<PlotPermission>(), Err(()));
+}
+
+#[test]
+fn chunk_data_fits_height_test() {
+    let mut chunk = Chunk::empty(0, 0, 24);
+    chunk.set_block(3, 350, 9, 332);
+    let data = ChunkData::new(&mut chunk);
+    assert!(data.fits_height(0, 24));
+    assert!(data.fits_height(-64, 28));
+    // The section at y 336 would be cut off
+    assert!(!data.fits_height(-64, 24));
+    // Empty sections may be cut off
+    assert!(ChunkData::new(&mut Chunk::empty(0, 0, 24)).fits_height(-64, 24));
+}
+
+#[test]
+fn plot_data_compression_test() {
+    let mut chunk = Chunk::empty(0, 0, 4).with_min_y(-32);
+    chunk.set_block(3, -20, 9, 332);
+    let data = PlotData {
+        tps: Tps::Limited(10),
+        world_send_rate: WorldSendRate::default(),
+        chunk_data: vec![ChunkData::new(&mut chunk)],
+        pending_ticks: Vec::new(),
+        item_frames: Vec::new(),
+        members: Vec::new(),
+        denied: Vec::new(),
+        settings: PlotSettings::default(),
+        redpiler_flags: None,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("p0,0");
+    // Uncompressed plots from before compression was added still load
+    for compression_level in [0, DEFAULT_COMPRESSION_LEVEL] {
+        data.save_to_file(&path, compression_level).unwrap();
+        PlotData::upgrade_file(&path, compression_level).unwrap();
+        let loaded = PlotData::load_from_file(&path).unwrap();
+        let chunk = loaded.chunk_data[0].clone().load(0, 0);
+        assert_eq!(chunk.get_block(3, -20, 9), 332);
+        assert_eq!(loaded.tps, data.tps);
+    }
+}
@@ -7,9 +7,19 @@
 //! seperate download. As our save format changes in the future, the fixer
 //! module may become quite big.
 
-use super::{PlotData, PlotLoadError};
+use super::{
+    ChunkData, ChunkSectionData, PlotData, PlotLoadError, PlotMember, PlotSettings, Tps,
+    WorldSendRate,
+};
 use crate::plot_data::VERSION;
+use mchprs_blocks::block_entities::BlockEntity;
+use mchprs_blocks::BlockPos;
+use mchprs_world::{ItemFrame, TickEntry};
+use rustc_hash::FxHashMap;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 use tracing::debug;
 
@@ -35,19 +45,233 @@ fn make_backup(path: impl AsRef<Path>) -> Result<(), PlotLoadError> {
     Ok(())
 }
 
-pub fn try_fix(path: impl AsRef<Path>, info: FixInfo) -> Result<Option<PlotData>, PlotLoadError> {
+/// Reads the data of an old plot file, skipping its magic and version
+fn read_old_data<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, PlotLoadError> {
+    let mut file = fs::File::open(path)?;
+    let mut header = [0; 12];
+    file.read_exact(&mut header)?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(bincode::deserialize(&buf)?)
+}
+
+/// Chunk data from before worlds could go below y 0
+#[derive(Deserialize)]
+struct ChunkDataV7 {
+    sections: Vec<Option<ChunkSectionData>>,
+    block_entities: FxHashMap<BlockPos, BlockEntity>,
+}
+
+fn fix_chunks(chunk_data: Vec<ChunkDataV7>) -> Vec<ChunkData> {
+    chunk_data
+        .into_iter()
+        .map(|chunk| ChunkData {
+            min_y: 0,
+            sections: chunk.sections,
+            block_entities: chunk.block_entities,
+        })
+        .collect()
+}
+
+/// Plot data from before item frames were added
+#[derive(Deserialize)]
+struct PlotDataV2 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+}
+
+fn fix_v2(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV2 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: Vec::new(),
+        members: Vec::new(),
+        denied: Vec::new(),
+        settings: PlotSettings::default(),
+        redpiler_flags: None,
+    })
+}
+
+/// Plot data from before plot members were added
+#[derive(Deserialize)]
+struct PlotDataV3 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+}
+
+fn fix_v3(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV3 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: data.item_frames,
+        members: Vec::new(),
+        denied: Vec::new(),
+        settings: PlotSettings::default(),
+        redpiler_flags: None,
+    })
+}
+
+/// Plot data from before plot settings were added
+#[derive(Deserialize)]
+struct PlotDataV4 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+    members: Vec<PlotMember>,
+    denied: Vec<u128>,
+}
+
+fn fix_v4(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV4 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: data.item_frames,
+        members: data.members,
+        denied: data.denied,
+        settings: PlotSettings::default(),
+        redpiler_flags: None,
+    })
+}
+
+/// Plot settings from before plot time and weather were added
+#[derive(Deserialize)]
+struct PlotSettingsV5 {
+    auto_redpiler: Option<bool>,
+    quasi_connectivity: Option<bool>,
+    strict_update_order: Option<bool>,
+    compiler_flags: String,
+}
+
+#[derive(Deserialize)]
+struct PlotDataV5 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+    members: Vec<PlotMember>,
+    denied: Vec<u128>,
+    settings: PlotSettingsV5,
+}
+
+fn fix_v5(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV5 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: data.item_frames,
+        members: data.members,
+        denied: data.denied,
+        settings: PlotSettings {
+            auto_redpiler: data.settings.auto_redpiler,
+            quasi_connectivity: data.settings.quasi_connectivity,
+            strict_update_order: data.settings.strict_update_order,
+            compiler_flags: data.settings.compiler_flags,
+            time: None,
+            weather: None,
+        },
+        redpiler_flags: None,
+    })
+}
+
+/// Plot data from before redpiler could resume after a restart
+#[derive(Deserialize)]
+struct PlotDataV6 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+    members: Vec<PlotMember>,
+    denied: Vec<u128>,
+    settings: PlotSettings,
+}
+
+fn fix_v6(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV6 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: data.item_frames,
+        members: data.members,
+        denied: data.denied,
+        settings: data.settings,
+        redpiler_flags: None,
+    })
+}
+
+/// Plot data from before worlds could go below y 0
+#[derive(Deserialize)]
+struct PlotDataV7 {
+    tps: Tps,
+    world_send_rate: WorldSendRate,
+    chunk_data: Vec<ChunkDataV7>,
+    pending_ticks: Vec<TickEntry>,
+    item_frames: Vec<ItemFrame>,
+    members: Vec<PlotMember>,
+    denied: Vec<u128>,
+    settings: PlotSettings,
+    redpiler_flags: Option<String>,
+}
+
+fn fix_v7(path: impl AsRef<Path>) -> Result<PlotData, PlotLoadError> {
+    let data: PlotDataV7 = read_old_data(path)?;
+    Ok(PlotData {
+        tps: data.tps,
+        world_send_rate: data.world_send_rate,
+        chunk_data: fix_chunks(data.chunk_data),
+        pending_ticks: data.pending_ticks,
+        item_frames: data.item_frames,
+        members: data.members,
+        denied: data.denied,
+        settings: data.settings,
+        redpiler_flags: data.redpiler_flags,
+    })
+}
+
+pub fn try_fix(
+    path: impl AsRef<Path>,
+    info: FixInfo,
+    compression_level: i32,
+) -> Result<Option<PlotData>, PlotLoadError> {
     debug!("Trying to fix plot with {:?}", info);
     let result: Option<PlotData> = match info {
         FixInfo::OldVersion {
             version: version @ 0..=1,
         } => return Err(PlotLoadError::ConversionUnavailable(version)),
+        FixInfo::OldVersion { version: 2 } => Some(fix_v2(&path)?),
+        FixInfo::OldVersion { version: 3 } => Some(fix_v3(&path)?),
+        FixInfo::OldVersion { version: 4 } => Some(fix_v4(&path)?),
+        FixInfo::OldVersion { version: 5 } => Some(fix_v5(&path)?),
+        FixInfo::OldVersion { version: 6 } => Some(fix_v6(&path)?),
+        FixInfo::OldVersion { version: 7 } => Some(fix_v7(&path)?),
         _ => None,
     };
 
     Ok(match result {
         Some(data) => {
             make_backup(&path)?;
-            data.save_to_file(&path)?;
+            data.save_to_file(&path, compression_level)?;
             debug!("Successfully converted plot to version {}", VERSION);
             Some(data)
         }
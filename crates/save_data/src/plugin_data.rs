@@ -0,0 +1,105 @@
+//! Key/value data plugins store for themselves, for a plot or for a player, so that they don't
+//! need file formats of their own.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// Version History:
+/// 0: Initial plugin data
+pub const VERSION: u32 = 0;
+
+static PLUGIN_DATA_MAGIC: &[u8; 8] = b"\x86PLUGIN\x00";
+
+#[derive(Error, Debug)]
+pub enum PluginDataError {
+    #[error("plugin data serialization error")]
+    Serialize(#[from] bincode::Error),
+
+    #[error("invalid plugin data header")]
+    InvalidHeader,
+
+    #[error("plugin data version {0} too new to be loaded")]
+    TooNew(u32),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
+pub struct PluginData {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl PluginData {
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<PluginData, PluginDataError> {
+        let mut file = File::open(path)?;
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != PLUGIN_DATA_MAGIC {
+            return Err(PluginDataError::InvalidHeader);
+        }
+
+        let version = file.read_u32::<LittleEndian>()?;
+        if version > VERSION {
+            return Err(PluginDataError::TooNew(version));
+        }
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        Ok(bincode::deserialize(&buf)?)
+    }
+
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), PluginDataError> {
+        let mut file = File::create(path)?;
+
+        file.write_all(PLUGIN_DATA_MAGIC)?;
+        file.write_u32::<LittleEndian>(VERSION)?;
+        let data = bincode::serialize(self)?;
+        file.write_all(&data)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries.get(key).map(Vec::as_slice)
+    }
+
+    pub fn insert(&mut self, key: String, value: Vec<u8>) -> Option<Vec<u8>> {
+        self.entries.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.entries.remove(key)
+    }
+
+    /// The keys in alphabetical order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[test]
+fn plugin_data_roundtrip_test() {
+    let mut data = PluginData::default();
+    data.insert("wins".to_owned(), vec![3]);
+    data.insert("best".to_owned(), b"Notch".to_vec());
+    assert_eq!(data.remove("missing"), None);
+    assert_eq!(data.keys().collect::<Vec<_>>(), ["best", "wins"]);
+
+    let path = std::env::temp_dir().join(format!("mchprs_plugin_data_{}", std::process::id()));
+    data.save_to_file(&path).unwrap();
+    let loaded = PluginData::load_from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), data);
+}
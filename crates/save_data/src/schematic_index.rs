@@ -0,0 +1,138 @@
+//! An index of the schematics in the schematic folder along with their metadata, so that they can
+//! be listed and searched without reading every schematic file.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::ops::Bound;
+use std::path::Path;
+use thiserror::Error;
+
+/// Version History:
+/// 0: Initial schematic index
+pub const VERSION: u32 = 0;
+
+static INDEX_MAGIC: &[u8; 8] = b"\x86SCHIDX\x00";
+
+#[derive(Error, Debug)]
+pub enum SchematicIndexError {
+    #[error("schematic index serialization error")]
+    Serialize(#[from] bincode::Error),
+
+    #[error("invalid schematic index header")]
+    InvalidHeader,
+
+    #[error("schematic index version {0} too new to be loaded")]
+    TooNew(u32),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SchematicMetadata {
+    /// The username of the player who saved the schematic, if it was saved in game
+    pub author: Option<String>,
+    /// The width, height and length of the schematic
+    pub size: [u32; 3],
+    /// When the schematic was created, in seconds since the unix epoch
+    pub created: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct SchematicIndex {
+    /// Schematics by their path relative to the schematic folder, using `/` to separate folders
+    entries: BTreeMap<String, SchematicMetadata>,
+}
+
+impl SchematicIndex {
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<SchematicIndex, SchematicIndexError> {
+        let mut file = File::open(path)?;
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(SchematicIndexError::InvalidHeader);
+        }
+
+        let version = file.read_u32::<LittleEndian>()?;
+        if version > VERSION {
+            return Err(SchematicIndexError::TooNew(version));
+        }
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        Ok(bincode::deserialize(&buf)?)
+    }
+
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), SchematicIndexError> {
+        let mut file = File::create(path)?;
+
+        file.write_all(INDEX_MAGIC)?;
+        file.write_u32::<LittleEndian>(VERSION)?;
+        let data = bincode::serialize(self)?;
+        file.write_all(&data)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, name: String, metadata: SchematicMetadata) {
+        self.entries.insert(name, metadata);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<SchematicMetadata> {
+        self.entries.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SchematicMetadata> {
+        self.entries.get(name)
+    }
+
+    /// Returns the schematics in the folder and its subfolders whose path inside of the folder
+    /// contains the search, ignoring case. The paths are returned relative to the folder and in
+    /// alphabetical order.
+    pub fn search<'a>(
+        &'a self,
+        folder: &'a str,
+        search: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a SchematicMetadata)> + 'a {
+        let search = search.to_lowercase();
+        self.entries
+            .range::<str, _>((Bound::Included(folder), Bound::Unbounded))
+            .map_while(move |(name, metadata)| {
+                name.strip_prefix(folder).map(|name| (name, metadata))
+            })
+            .filter(move |(name, _)| name.to_lowercase().contains(&search))
+    }
+}
+
+#[test]
+fn schematic_index_search_test() {
+    let metadata = SchematicMetadata {
+        author: None,
+        size: [1, 2, 3],
+        created: 0,
+    };
+    let mut index = SchematicIndex::default();
+    for name in [
+        "a/Adder.schem",
+        "a/cpu/alu.schem",
+        "b/adder.schem",
+        "root.schem",
+    ] {
+        index.insert(name.to_owned(), metadata.clone());
+    }
+
+    let names = |folder, search| {
+        index
+            .search(folder, search)
+            .map(|(name, _)| name.to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names("a/", ""), ["Adder.schem", "cpu/alu.schem"]);
+    assert_eq!(names("", "adder"), ["a/Adder.schem", "b/adder.schem"]);
+    assert_eq!(names("b/", "alu"), Vec::<String>::new());
+}
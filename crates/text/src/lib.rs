@@ -87,8 +87,9 @@ pub enum TextColor {
 #[serde(rename_all = "snake_case")]
 enum ClickEventType {
     OpenUrl,
-    // RunCommand,
-    // SuggestCommand,
+    RunCommand,
+    SuggestCommand,
+    CopyToClipboard,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -97,6 +98,59 @@ pub struct ClickEvent {
     value: String,
 }
 
+impl ClickEvent {
+    pub fn open_url(url: impl Into<String>) -> Self {
+        Self {
+            action: ClickEventType::OpenUrl,
+            value: url.into(),
+        }
+    }
+
+    /// Runs the command as if the player sent it. The command has to start with a `/`.
+    pub fn run_command(command: impl Into<String>) -> Self {
+        Self {
+            action: ClickEventType::RunCommand,
+            value: command.into(),
+        }
+    }
+
+    /// Puts the command in the player's chat box, so that they can change it before sending it
+    pub fn suggest_command(command: impl Into<String>) -> Self {
+        Self {
+            action: ClickEventType::SuggestCommand,
+            value: command.into(),
+        }
+    }
+
+    pub fn copy_to_clipboard(text: impl Into<String>) -> Self {
+        Self {
+            action: ClickEventType::CopyToClipboard,
+            value: text.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+enum HoverEventType {
+    ShowText,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HoverEvent {
+    action: HoverEventType,
+    contents: Box<TextComponent>,
+}
+
+impl HoverEvent {
+    pub fn show_text(text: impl Into<TextComponent>) -> Self {
+        Self {
+            action: HoverEventType::ShowText,
+            contents: Box::new(text.into()),
+        }
+    }
+}
+
 /// This is only used for `TextComponent` serialize
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn is_false(field: &bool) -> bool {
@@ -131,6 +185,39 @@ impl TextComponentBuilder {
         self
     }
 
+    pub fn bold(mut self, val: bool) -> Self {
+        self.component.bold = val;
+        self
+    }
+
+    pub fn italic(mut self, val: bool) -> Self {
+        self.component.italic = val;
+        self
+    }
+
+    pub fn underlined(mut self, val: bool) -> Self {
+        self.component.underlined = val;
+        self
+    }
+
+    pub fn click_event(mut self, event: ClickEvent) -> Self {
+        self.component.click_event = Some(event);
+        self
+    }
+
+    /// Shows `text` when the component is hovered over
+    pub fn hover_text(mut self, text: impl Into<TextComponent>) -> Self {
+        self.component.hover_event = Some(HoverEvent::show_text(text));
+        self
+    }
+
+    /// Adds a component after this one. It inherits the style of this component, unless it sets
+    /// its own.
+    pub fn append(mut self, component: impl Into<TextComponent>) -> Self {
+        self.component.extra.push(component.into());
+        self
+    }
+
     pub fn finish(self) -> TextComponent {
         self.component
     }
@@ -154,6 +241,9 @@ pub struct TextComponent {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "clickEvent")]
     pub click_event: Option<ClickEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "hoverEvent")]
+    pub hover_event: Option<HoverEvent>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub extra: Vec<TextComponent>,
 }
@@ -233,10 +323,7 @@ impl TextComponent {
                 }
                 let mut new = component.clone();
                 new.text = matched.to_string();
-                new.click_event = Some(ClickEvent {
-                    action: ClickEventType::OpenUrl,
-                    value: matched.to_string(),
-                });
+                new.click_event = Some(ClickEvent::open_url(matched));
                 new_componenets.push(new);
                 last = index + matched.len();
             }
@@ -263,6 +350,7 @@ impl TextComponent {
             && self.extra.is_empty()
             && self.color.is_none()
             && self.click_event.is_none()
+            && self.hover_event.is_none()
     }
 }
 
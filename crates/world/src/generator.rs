@@ -0,0 +1,117 @@
+//! Generators decide what new plots look like. Every plot starts out the same, so a generator is
+//! only asked for the chunks of the plot at 0,0, and those chunks are copied for every other plot.
+
+use crate::storage::Chunk;
+use mchprs_blocks::blocks::Block;
+
+/// The plot grid chunks are generated for
+#[derive(Debug, Clone, Copy)]
+pub struct PlotLayout {
+    /// The width of a plot in blocks, including its half of the roads around it
+    pub plot_block_width: i32,
+    /// The y of the bottom of the world, a multiple of 16
+    pub min_y: i32,
+    /// The number of sections in a chunk
+    pub num_sections: usize,
+}
+
+pub trait PlotGenerator: Send + Sync {
+    /// Generates the chunk at the chunk coordinates `chunk_x` and `chunk_z`
+    fn generate_chunk(&self, layout: &PlotLayout, chunk_x: i32, chunk_z: i32) -> Chunk;
+}
+
+/// The classic plot world: a flat floor with roads between plots, and optionally a wall along the
+/// edge of each plot. The floor always starts at y 0, so worlds that go below 0 have empty space
+/// under it.
+pub struct FlatPlotGenerator {
+    pub floor: Block,
+    pub road: Block,
+    /// Placed on top of the floor, on the outermost blocks of the plot next to the road
+    pub wall: Option<Block>,
+    /// How many layers of blocks the floor and roads are
+    pub floor_height: u32,
+    /// The width of the road between two plots. Each plot has half of the road on its edges.
+    pub road_width: u32,
+}
+
+impl PlotGenerator for FlatPlotGenerator {
+    fn generate_chunk(&self, layout: &PlotLayout, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let road_start = self.road_width as i32 / 2;
+        let road_end = layout.plot_block_width - (self.road_width as i32 - road_start);
+        let is_road = |coord: i32| {
+            let local = coord.rem_euclid(layout.plot_block_width);
+            local < road_start || local >= road_end
+        };
+        let is_edge = |coord: i32| {
+            let local = coord.rem_euclid(layout.plot_block_width);
+            local == road_start || local == road_end - 1
+        };
+
+        let mut chunk =
+            Chunk::empty(chunk_x, chunk_z, layout.num_sections).with_min_y(layout.min_y);
+        let max_y = chunk.max_y();
+        let floor_height = (self.floor_height as i32).min(max_y);
+        for rx in 0..16 {
+            for rz in 0..16 {
+                let block_x = (chunk_x << 4) | rx;
+                let block_z = (chunk_z << 4) | rz;
+                let road = is_road(block_x) || is_road(block_z);
+                let block = if road { self.road } else { self.floor };
+                for y in layout.min_y.max(0)..floor_height {
+                    chunk.set_block(rx as u32, y, rz as u32, block.get_id());
+                }
+
+                let on_edge = is_edge(block_x) || is_edge(block_z);
+                if let Some(wall) = self.wall.filter(|_| !road && on_edge) {
+                    if (layout.min_y..max_y).contains(&floor_height) {
+                        chunk.set_block(rx as u32, floor_height, rz as u32, wall.get_id());
+                    }
+                }
+            }
+        }
+        chunk
+    }
+}
+
+/// Leaves plots completely empty
+pub struct VoidGenerator;
+
+impl PlotGenerator for VoidGenerator {
+    fn generate_chunk(&self, layout: &PlotLayout, chunk_x: i32, chunk_z: i32) -> Chunk {
+        Chunk::empty(chunk_x, chunk_z, layout.num_sections).with_min_y(layout.min_y)
+    }
+}
+
+#[test]
+fn flat_plot_generator_test() {
+    let stone = Block::from_name("stone").unwrap();
+    let sandstone = Block::from_name("sandstone").unwrap();
+    let wall = Block::from_name("quartz_block").unwrap();
+    let generator = FlatPlotGenerator {
+        floor: sandstone,
+        road: stone,
+        wall: Some(wall),
+        floor_height: 8,
+        road_width: 2,
+    };
+    let layout = PlotLayout {
+        plot_block_width: 32,
+        min_y: -16,
+        num_sections: 3,
+    };
+
+    let chunk = generator.generate_chunk(&layout, 0, 0);
+    // The road, then the wall on the outermost block of the plot, then the floor
+    assert_eq!(chunk.get_block(0, 7, 5), stone.get_id());
+    assert_eq!(chunk.get_block(0, 8, 5), 0);
+    assert_eq!(chunk.get_block(1, 7, 5), sandstone.get_id());
+    assert_eq!(chunk.get_block(1, 8, 5), wall.get_id());
+    assert_eq!(chunk.get_block(2, 8, 5), 0);
+    // The floor starts at 0, with empty space below it
+    assert_eq!(chunk.get_block(1, 0, 5), sandstone.get_id());
+    assert_eq!(chunk.get_block(1, -1, 5), 0);
+    // The other half of the road is on the far edge of the plot
+    let chunk = generator.generate_chunk(&layout, 1, 0);
+    assert_eq!(chunk.get_block(15, 7, 5), stone.get_id());
+    assert_eq!(chunk.get_block(14, 8, 5), wall.get_id());
+}
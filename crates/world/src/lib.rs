@@ -1,9 +1,11 @@
+pub mod generator;
 pub mod storage;
 
 use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::Block;
-use mchprs_blocks::BlockPos;
+use mchprs_blocks::{BlockFacing, BlockPos};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use storage::Chunk;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,6 +23,118 @@ pub struct TickEntry {
     pub pos: BlockPos,
 }
 
+/// An item frame entity. Item frames are the only entities that exist in the world, because
+/// comparators can read their rotation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemFrame {
+    /// The position of the block space the item frame is in
+    pub pos: BlockPos,
+    /// The direction the item frame is facing, pointing away from the block it is attached to
+    pub facing: BlockFacing,
+    /// The id of the item in the item frame
+    pub item: Option<u32>,
+    /// The rotation of the item, from 0 to 7
+    pub rotation: u8,
+    /// The network entity id, assigned by the server when the item frame is loaded
+    #[serde(skip)]
+    pub entity_id: u32,
+}
+
+impl ItemFrame {
+    /// The position of the block the item frame is attached to
+    pub fn attached_pos(&self) -> BlockPos {
+        self.pos.offset(self.facing.opposite().block_face())
+    }
+
+    /// The signal strength comparators read from this item frame
+    pub fn comparator_output(&self) -> u8 {
+        match self.item {
+            Some(_) => self.rotation % 8 + 1,
+            None => 0,
+        }
+    }
+}
+
+/// Events in the world that cause vibrations which can be picked up by sculk sensors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    BlockDeactivate,
+    BlockActivate,
+    BlockChange,
+    BlockDestroy,
+    BlockPlace,
+    NoteBlockPlay,
+}
+
+impl GameEvent {
+    /// The vibration frequency of the event, which is output by sculk sensors through comparators
+    pub fn frequency(self) -> u8 {
+        match self {
+            GameEvent::BlockDeactivate => 9,
+            GameEvent::BlockActivate => 10,
+            GameEvent::NoteBlockPlay => 10,
+            GameEvent::BlockChange => 11,
+            GameEvent::BlockDestroy => 12,
+            GameEvent::BlockPlace => 13,
+        }
+    }
+}
+
+/// The kind of action a block event performs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockEventType {
+    PistonExtend,
+    PistonRetract,
+}
+
+/// An action queued by a block that is run at the end of the tick, like vanilla's block events.
+/// These are only used when the world has a strict update order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEvent {
+    pub pos: BlockPos,
+    pub ty: BlockEventType,
+}
+
+/// Keeps track of recently toggled redstone torches so that torches toggling too quickly can
+/// burn out like they do in vanilla.
+#[derive(Debug, Clone, Default)]
+pub struct TorchBurnout {
+    game_time: u64,
+    recent_toggles: VecDeque<(BlockPos, u64)>,
+}
+
+impl TorchBurnout {
+    /// The amount of times a torch can turn off within [`Self::TOGGLE_WINDOW`] game ticks
+    /// before it burns out
+    pub const MAX_TOGGLES: usize = 8;
+    pub const TOGGLE_WINDOW: u64 = 60;
+
+    /// Advances the game time by one game tick
+    pub fn tick(&mut self) {
+        self.game_time += 1;
+    }
+
+    /// Returns true if the torch at `pos` has turned off too often recently. If `log_toggle` is
+    /// true, the torch turning off at the current game time is recorded first.
+    pub fn toggled_too_frequently(&mut self, pos: BlockPos, log_toggle: bool) -> bool {
+        while let Some(&(_, time)) = self.recent_toggles.front() {
+            if self.game_time - time <= Self::TOGGLE_WINDOW {
+                break;
+            }
+            self.recent_toggles.pop_front();
+        }
+        if log_toggle {
+            self.recent_toggles.push_back((pos, self.game_time));
+        }
+        let toggles = self
+            .recent_toggles
+            .iter()
+            .filter(|&&(toggle_pos, _)| toggle_pos == pos)
+            .count();
+        toggles >= Self::MAX_TOGGLES
+    }
+}
+
 pub trait World {
     /// Returns the block located at `pos`
     fn get_block(&self, pos: BlockPos) -> Block {
@@ -69,6 +183,74 @@ pub trait World {
         false
     }
 
+    /// Returns true if blocks like pistons should be powered by the block above them
+    fn quasi_connectivity(&self) -> bool {
+        true
+    }
+
+    /// Returns true if copper bulbs should toggle one game tick after being powered instead of
+    /// immediately
+    fn copper_bulb_delay(&self) -> bool {
+        false
+    }
+
+    /// Returns true if sticky pistons that retract before they finish extending should leave
+    /// their block behind like in vanilla. Otherwise they finish extending and pull the block
+    /// back.
+    fn zero_tick_pistons(&self) -> bool {
+        true
+    }
+
+    /// Returns true if repeaters should use vanilla's tick priorities, which depend on whether
+    /// they face another diode and whether they are turning off
+    fn repeater_tick_priority(&self) -> bool {
+        true
+    }
+
+    /// Returns true if comparators should use vanilla's tick priorities, which makes them miss
+    /// 1-tick pulses from repeaters. Otherwise they are ticked before any other component.
+    fn comparator_tick_priority(&self) -> bool {
+        true
+    }
+
+    /// Returns true if neighbor updates should be issued in vanilla's order and pistons should
+    /// move through the block event queue instead of immediately
+    fn strict_update_order(&self) -> bool {
+        false
+    }
+
+    /// Queues a block event to be run at the end of the tick. Events that are already queued
+    /// are not queued again.
+    #[allow(unused_variables)]
+    fn queue_block_event(&mut self, event: BlockEvent) {}
+
+    /// Returns the item frame at `pos` that is facing `facing` if it exists
+    #[allow(unused_variables)]
+    fn get_item_frame(&self, pos: BlockPos, facing: BlockFacing) -> Option<&ItemFrame> {
+        None
+    }
+
+    /// Returns the torch burnout tracker of this world, or `None` if torches can't burn out
+    fn torch_burnout(&mut self) -> Option<&mut TorchBurnout> {
+        None
+    }
+
+    /// Plays a world event such as a sound or particle effect at `pos`
+    #[allow(unused_variables)]
+    fn play_world_event(&mut self, pos: BlockPos, event: i32, data: i32) {}
+
+    /// Sends a block action, such as a bell ringing, to clients. `block_type` is the block's
+    /// registry id rather than its state id.
+    #[allow(unused_variables)]
+    fn play_block_event(
+        &mut self,
+        pos: BlockPos,
+        block_type: i32,
+        action_id: u8,
+        action_param: u8,
+    ) {
+    }
+
     #[allow(unused_variables)]
     fn play_sound(
         &mut self,
@@ -168,7 +350,10 @@ pub fn for_each_block_optimized<F, W: World>(
         let chunk = world
             .get_chunk(chunk_section_idx.x, chunk_section_idx.z)
             .unwrap();
-        if chunk.sections[chunk_section_idx.y as usize].block_count() == 0 {
+        if chunk
+            .section(chunk_section_idx.y)
+            .is_none_or(|section| section.block_count() == 0)
+        {
             // only air, skip section
             continue;
         }
@@ -194,7 +379,10 @@ pub fn for_each_block_mut_optimized<F, W: World>(
         let chunk = world
             .get_chunk(chunk_section_idx.x, chunk_section_idx.z)
             .unwrap();
-        if chunk.sections[chunk_section_idx.y as usize].block_count() == 0 {
+        if chunk
+            .section(chunk_section_idx.y)
+            .is_none_or(|section| section.block_count() == 0)
+        {
             // only air, skip section
             continue;
         }
@@ -445,7 +633,7 @@ mod test {
                 .unwrap();
             chunk.set_block(
                 pos.x.rem_euclid(16) as u32,
-                pos.y as u32,
+                pos.y,
                 pos.z.rem_euclid(16) as u32,
                 block,
             )
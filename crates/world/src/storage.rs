@@ -2,6 +2,8 @@ use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::BlockPos;
 use rustc_hash::FxHashMap;
 use std::mem;
+#[cfg(feature = "networking")]
+use std::sync::OnceLock;
 
 #[cfg(feature = "networking")]
 use mchprs_network::packets::{
@@ -362,7 +364,7 @@ impl ChunkSection {
     }
 
     #[cfg(feature = "networking")]
-    fn multi_block(&mut self, chunk_x: i32, chunk_y: u32, chunk_z: i32) -> &CUpdateSectionBlocks {
+    fn multi_block(&mut self, chunk_x: i32, chunk_y: i32, chunk_z: i32) -> &CUpdateSectionBlocks {
         self.multi_block.chunk_x = chunk_x;
         self.multi_block.chunk_y = chunk_y;
         self.multi_block.chunk_z = chunk_z;
@@ -408,12 +410,30 @@ pub struct Chunk {
     pub sections: Vec<ChunkSection>,
     pub x: i32,
     pub z: i32,
+    /// The y of the bottom of the lowest section, which is always a multiple of 16
+    pub min_y: i32,
     pub block_entities: FxHashMap<BlockPos, BlockEntity>,
+    /// The chunk data packet, shared by everyone the chunk is sent to until the chunk changes.
+    /// Changes made through `sections` or `block_entities` directly have to call `mark_dirty`.
+    #[cfg(feature = "networking")]
+    packet: OnceLock<PacketEncoder>,
 }
 
 impl Chunk {
+    /// Returns the chunk data packet. It is only encoded again after the chunk has changed.
     #[cfg(feature = "networking")]
-    pub fn encode_packet(&self) -> PacketEncoder {
+    pub fn encode_packet(&self) -> &PacketEncoder {
+        self.packet.get_or_init(|| self.encode_packet_uncached())
+    }
+
+    /// Drops the cached chunk data packet, so that it is encoded again the next time it is sent
+    pub fn mark_dirty(&mut self) {
+        #[cfg(feature = "networking")]
+        self.packet.take();
+    }
+
+    #[cfg(feature = "networking")]
+    fn encode_packet_uncached(&self) -> PacketEncoder {
         let block_height = self.sections.len() * 16;
         // Integer arithmetic trick: ceil(log2(x)) can be calculated with 32 - (x -
         // 1).leading_zeros(). See also: https://wiki.vg/Protocol#Chunk_Data_and_Update_Light
@@ -488,31 +508,56 @@ impl Chunk {
         .encode()
     }
 
+    /// Heightmaps count from the bottom of the chunk, so this is the number of blocks from
+    /// `min_y` up to and including the highest block in the column, or 0 if it is empty
     #[cfg(feature = "networking")]
     fn get_top_most_block(&self, x: u32, z: u32) -> u32 {
-        let mut top_most = 0;
-        for (section_y, section) in self.sections.iter().enumerate() {
+        for (section_y, section) in self.sections.iter().enumerate().rev() {
+            if section.block_count() == 0 {
+                continue;
+            }
             for y in (0..16).rev() {
-                let block_state = section.get_block(x, y, z);
-                if block_state != 0 && top_most < y + section_y as u32 * 16 {
-                    top_most = section_y as u32 * 16;
+                if section.get_block(x, y, z) != 0 {
+                    return section_y as u32 * 16 + y + 1;
                 }
             }
         }
-        top_most
+        0
+    }
+
+    /// The index into `sections` of the section containing `y`
+    fn section_index(&self, y: i32) -> Option<usize> {
+        usize::try_from((y - self.min_y) >> 4)
+            .ok()
+            .filter(|&index| index < self.sections.len())
+    }
+
+    /// Returns the section at the section coordinate `section_y`, which is `y >> 4`
+    pub fn section(&self, section_y: i32) -> Option<&ChunkSection> {
+        self.sections.get(self.section_index(section_y << 4)?)
+    }
+
+    /// The y of the top of the highest section, which is one above the highest block
+    pub fn max_y(&self) -> i32 {
+        self.min_y + self.sections.len() as i32 * 16
     }
 
     /// Sets a block in the chunk. Returns true if a block was changed.
-    pub fn set_block(&mut self, x: u32, y: u32, z: u32, block_id: u32) -> bool {
-        let section_y = (y >> 4) as usize;
-        let section = &mut self.sections[section_y];
-        section.set_block(x, y & 0xF, z, block_id)
+    pub fn set_block(&mut self, x: u32, y: i32, z: u32, block_id: u32) -> bool {
+        let Some(section_index) = self.section_index(y) else {
+            return false;
+        };
+        let section = &mut self.sections[section_index];
+        let changed = section.set_block(x, (y & 0xF) as u32, z, block_id);
+        if changed {
+            self.mark_dirty();
+        }
+        changed
     }
 
-    pub fn get_block(&self, x: u32, y: u32, z: u32) -> u32 {
-        let section_y = (y / 16) as usize;
-        match self.sections.get(section_y) {
-            Some(section) => section.get_block(x, y & 0xF, z),
+    pub fn get_block(&self, x: u32, y: i32, z: u32) -> u32 {
+        match self.section_index(y) {
+            Some(section_index) => self.sections[section_index].get_block(x, (y & 0xF) as u32, z),
             None => 0,
         }
     }
@@ -522,11 +567,14 @@ impl Chunk {
     }
 
     pub fn delete_block_entity(&mut self, pos: BlockPos) {
-        self.block_entities.remove(&pos);
+        if self.block_entities.remove(&pos).is_some() {
+            self.mark_dirty();
+        }
     }
 
     pub fn set_block_entity(&mut self, pos: BlockPos, block_entity: BlockEntity) {
         self.block_entities.insert(pos, block_entity);
+        self.mark_dirty();
     }
 
     pub fn compress(&mut self) {
@@ -540,21 +588,76 @@ impl Chunk {
             sections: (0..num_sections).map(|_| Default::default()).collect(),
             x,
             z,
+            min_y: 0,
             block_entities: FxHashMap::default(),
+            #[cfg(feature = "networking")]
+            packet: OnceLock::new(),
         }
     }
 
+    /// Moves the bottom of the chunk to `min_y`, which has to be a multiple of 16. The sections
+    /// keep their blocks, so the blocks move along with them.
+    pub fn with_min_y(mut self, min_y: i32) -> Chunk {
+        debug_assert_eq!(min_y & 0xF, 0, "min_y must be a multiple of 16");
+        self.min_y = min_y;
+        self.mark_dirty();
+        self
+    }
+
+    /// Changes which sections the chunk has, keeping every block at the same y. Sections outside
+    /// of the new range are dropped along with their blocks and block entities. Returns true if
+    /// any blocks were dropped.
+    pub fn set_height(&mut self, min_y: i32, num_sections: usize) -> bool {
+        if min_y == self.min_y && num_sections == self.sections.len() {
+            return false;
+        }
+        self.flush();
+        let shift = (min_y - self.min_y) >> 4;
+        let mut old_sections: Vec<Option<ChunkSection>> = std::mem::take(&mut self.sections)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.sections = (0..num_sections)
+            .map(|index| {
+                usize::try_from(index as i32 + shift)
+                    .ok()
+                    .and_then(|old_index| old_sections.get_mut(old_index))
+                    .and_then(Option::take)
+                    .unwrap_or_default()
+            })
+            .collect();
+        self.min_y = min_y;
+        let max_y = self.max_y();
+        self.block_entities
+            .retain(|pos, _| (min_y..max_y).contains(&pos.y));
+        self.mark_dirty();
+        old_sections
+            .iter()
+            .flatten()
+            .any(|section| section.block_count() > 0)
+    }
+
+    /// Whether some blocks were set that haven't been flushed to the sections yet
+    fn has_changes(&self) -> bool {
+        self.sections.iter().any(|section| section.changed)
+    }
+
     #[cfg(feature = "networking")]
     pub fn multi_blocks(&mut self) -> impl Iterator<Item = &CUpdateSectionBlocks> {
+        // The changes are written to the sections as the packets are made
+        if self.has_changes() {
+            self.mark_dirty();
+        }
         let x = self.x;
         let z = self.z;
+        let min_section_y = self.min_y >> 4;
         self.sections
             .iter_mut()
             .enumerate()
             .filter_map(move |(y, section)| {
                 section
                     .changed
-                    .then(move || section.multi_block(x, y as u32, z))
+                    .then(move || section.multi_block(x, min_section_y + y as i32, z))
             })
     }
 
@@ -566,8 +669,54 @@ impl Chunk {
     }
 
     pub fn flush(&mut self) {
+        if self.has_changes() {
+            self.mark_dirty();
+        }
         for section in &mut self.sections {
             section.flush();
         }
     }
 }
+
+#[cfg(feature = "networking")]
+#[test]
+fn chunk_packet_is_encoded_again_after_changes() {
+    let bytes = |chunk: &Chunk| {
+        let mut buf = Vec::new();
+        chunk.encode_packet().write_uncompressed(&mut buf).unwrap();
+        buf
+    };
+    let mut chunk = Chunk::empty(0, 0, 1);
+    let empty = bytes(&chunk);
+    assert!(std::ptr::eq(chunk.encode_packet(), chunk.encode_packet()));
+
+    chunk.set_block(1, 2, 3, 1);
+    chunk.multi_blocks().for_each(drop);
+    let changed = bytes(&chunk);
+    assert_ne!(changed, empty);
+
+    chunk.set_block(1, 2, 3, 2);
+    chunk.flush();
+    assert_ne!(bytes(&chunk), changed);
+}
+
+#[test]
+fn chunk_set_height_test() {
+    let mut chunk = Chunk::empty(0, 0, 2);
+    chunk.set_block(1, 2, 3, 1);
+    chunk.set_block(1, 20, 3, 2);
+    assert!(!chunk.set_height(-32, 4));
+    assert_eq!(chunk.min_y, -32);
+    assert_eq!(chunk.max_y(), 32);
+    assert_eq!(chunk.get_block(1, 2, 3), 1);
+    assert_eq!(chunk.get_block(1, 20, 3), 2);
+    assert!(chunk.set_block(1, -30, 3, 3));
+    assert_eq!(chunk.get_block(1, -30, 3), 3);
+    assert!(!chunk.set_block(1, -33, 3, 3));
+
+    // Dropping the section at the top removes its blocks
+    assert!(chunk.set_height(-32, 3));
+    assert_eq!(chunk.get_block(1, 20, 3), 0);
+    assert_eq!(chunk.get_block(1, 2, 3), 1);
+    assert_eq!(chunk.get_block(1, -30, 3), 3);
+}
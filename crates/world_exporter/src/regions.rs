@@ -54,7 +54,7 @@ fn serialize_chunk(chunk_x: i32, chunk_z: i32, chunk: ChunkData) -> Result<Vec<u
     nbt.insert("DataVersion", nbt::Value::Int(MC_DATA_VERSION))?;
     nbt.insert("xPos", nbt::Value::Int(chunk_x))?;
     nbt.insert("zPos", nbt::Value::Int(chunk_z))?;
-    nbt.insert("yPos", nbt::Value::Int(0))?;
+    nbt.insert("yPos", nbt::Value::Int(chunk.min_y >> 4))?;
     nbt.insert("Status", nbt::Value::String("minecraft:full".to_string()))?;
     nbt.insert("LastUpdate", nbt::Value::Long(0))?;
 
@@ -76,6 +76,7 @@ fn serialize_chunk(chunk_x: i32, chunk_z: i32, chunk: ChunkData) -> Result<Vec<u
     let mut sections = Vec::new();
     for (section_y, section) in chunk.sections.iter().enumerate() {
         let mut section_nbt = HashMap::new();
+        let section_y = (chunk.min_y >> 4) + section_y as i32;
         section_nbt.insert("Y".to_string(), nbt::Value::Byte(section_y as i8));
         let block_states = if section.block_count() != 0 {
             encode_section(section)
@@ -2,17 +2,23 @@ use mchprs_blocks::block_entities::BlockEntity;
 use mchprs_blocks::blocks::{
     Block, ComparatorMode, Lever, LeverFace, RedstoneComparator, RedstoneRepeater,
 };
-use mchprs_blocks::{BlockDirection, BlockPos};
+use mchprs_blocks::{BlockDirection, BlockFacing, BlockPos};
 use mchprs_redpiler::{BackendVariant, Compiler, CompilerOptions};
 use mchprs_redstone::wire::make_cross;
 use mchprs_world::storage::Chunk;
-use mchprs_world::{TickEntry, TickPriority, World};
+use mchprs_world::{BlockEvent, ItemFrame, TickEntry, TickPriority, TorchBurnout, World};
 
 #[derive(Clone)]
 pub struct TestWorld {
     chunks: Vec<Chunk>,
     to_be_ticked: Vec<TickEntry>,
     size: i32,
+    pub quasi_connectivity: bool,
+    pub torch_burnout: Option<TorchBurnout>,
+    pub item_frames: Vec<ItemFrame>,
+    pub strict_update_order: bool,
+    pub zero_tick_pistons: bool,
+    block_events: Vec<BlockEvent>,
 }
 
 impl TestWorld {
@@ -28,6 +34,12 @@ impl TestWorld {
             chunks,
             to_be_ticked: Vec::new(),
             size,
+            quasi_connectivity: true,
+            torch_burnout: None,
+            item_frames: Vec::new(),
+            strict_update_order: false,
+            zero_tick_pistons: true,
+            block_events: Vec::new(),
         }
     }
 
@@ -53,7 +65,7 @@ impl World for TestWorld {
             None => return 0,
         };
         let chunk = &self.chunks[chunk_index];
-        chunk.get_block((pos.x & 0xF) as u32, pos.y as u32, (pos.z & 0xF) as u32)
+        chunk.get_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32)
     }
 
     /// Sets a block in storage. Returns true if a block was changed.
@@ -69,12 +81,7 @@ impl World for TestWorld {
         }
 
         let chunk = &mut self.chunks[chunk_index];
-        chunk.set_block(
-            (pos.x & 0xF) as u32,
-            pos.y as u32,
-            (pos.z & 0xF) as u32,
-            block,
-        )
+        chunk.set_block((pos.x & 0xF) as u32, pos.y, (pos.z & 0xF) as u32, block)
     }
 
     fn delete_block_entity(&mut self, pos: BlockPos) {
@@ -121,6 +128,34 @@ impl World for TestWorld {
     fn pending_tick_at(&mut self, pos: BlockPos) -> bool {
         self.to_be_ticked.iter().any(|e| e.pos == pos)
     }
+
+    fn quasi_connectivity(&self) -> bool {
+        self.quasi_connectivity
+    }
+
+    fn strict_update_order(&self) -> bool {
+        self.strict_update_order
+    }
+
+    fn zero_tick_pistons(&self) -> bool {
+        self.zero_tick_pistons
+    }
+
+    fn queue_block_event(&mut self, event: BlockEvent) {
+        if !self.block_events.contains(&event) {
+            self.block_events.push(event);
+        }
+    }
+
+    fn get_item_frame(&self, pos: BlockPos, facing: BlockFacing) -> Option<&ItemFrame> {
+        self.item_frames
+            .iter()
+            .find(|frame| frame.pos == pos && frame.facing == facing)
+    }
+
+    fn torch_burnout(&mut self) -> Option<&mut TorchBurnout> {
+        self.torch_burnout.as_mut()
+    }
 }
 
 struct RedpilerInstance {
@@ -151,7 +186,7 @@ pub enum TestBackend {
 }
 
 pub struct BackendRunner {
-    world: TestWorld,
+    pub world: TestWorld,
     redpiler: Option<RedpilerInstance>,
 }
 
@@ -176,6 +211,9 @@ impl BackendRunner {
             return;
         }
 
+        if let Some(torch_burnout) = &mut self.world.torch_burnout {
+            torch_burnout.tick();
+        }
         self.world
             .to_be_ticked
             .sort_by_key(|e| (e.ticks_left, e.tick_priority));
@@ -186,6 +224,11 @@ impl BackendRunner {
             let entry = self.world.to_be_ticked.remove(0);
             mchprs_redstone::tick(self.world.get_block(entry.pos), &mut self.world, entry.pos);
         }
+        while !self.world.block_events.is_empty() {
+            for event in std::mem::take(&mut self.world.block_events) {
+                mchprs_redstone::run_block_event(&mut self.world, event);
+            }
+        }
     }
 
     pub fn use_block(&mut self, pos: BlockPos) {
@@ -197,6 +240,13 @@ impl BackendRunner {
         mchprs_redstone::on_use(self.world.get_block(pos), &mut self.world, pos);
     }
 
+    /// The compiler, if running on redpiler
+    pub fn compiler(&mut self) -> Option<&mut Compiler> {
+        self.redpiler
+            .as_mut()
+            .map(|redpiler| &mut redpiler.compiler)
+    }
+
     pub fn check_block_powered(&self, pos: BlockPos, powered: bool) {
         if let Some(redpiler) = &self.redpiler {
             assert_eq!(
@@ -214,6 +264,10 @@ impl BackendRunner {
         );
     }
 
+    pub fn check_block(&self, pos: BlockPos, block: Block) {
+        assert_eq!(self.world.get_block(pos), block);
+    }
+
     pub fn check_powered_for(&mut self, pos: BlockPos, powered: bool, ticks: usize) {
         for _ in 0..ticks {
             self.check_block_powered(pos, powered);
@@ -233,7 +287,14 @@ fn is_block_powered(block: Block) -> Option<bool> {
         Block::StonePressurePlate { powered } => powered,
         Block::RedstoneLamp { lit } => lit,
         Block::IronTrapdoor { powered, .. } => powered,
+        Block::IronDoor { door } => door.powered,
+        Block::OakDoor { door } => door.powered,
+        Block::OakFenceGate { powered, .. } => powered,
+        Block::Bell { powered, .. } => powered,
         Block::NoteBlock { powered, .. } => powered,
+        Block::Piston { extended, .. } => extended,
+        Block::StickyPiston { extended, .. } => extended,
+        Block::Observer { powered, .. } => powered,
         _ => return None,
     })
 }
@@ -1,9 +1,14 @@
 mod common;
 use common::*;
 
-use mchprs_blocks::blocks::Block;
-use mchprs_blocks::BlockDirection;
-use mchprs_world::World;
+use mchprs_blocks::block_entities::{BlockEntity, ContainerType, InventoryEntry, MusicDisc};
+use mchprs_blocks::blocks::{
+    Block, ComparatorMode, Door, DoorHalf, DoorHinge, Lever, LeverFace, PistonType, RailShape,
+};
+use mchprs_blocks::items::Item;
+use mchprs_blocks::{BlockColorVariant, BlockDirection, BlockFace, BlockFacing};
+use mchprs_redstone::comparator;
+use mchprs_world::{ItemFrame, World};
 
 test_all_backends!(lever_on_off);
 fn lever_on_off(backend: TestBackend) {
@@ -182,3 +187,670 @@ fn wire_no_reach(backend: TestBackend) {
     runner.use_block(lever_pos);
     runner.check_block_powered(trapdoor_pos, false);
 }
+
+// Pistons are only implemented in the base redstone implementation
+
+#[test]
+fn piston_push() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+    let head_pos = pos(2, 1, 0);
+    let stone_pos = pos(3, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    let piston = Block::Piston {
+        extended: false,
+        facing: BlockFacing::East,
+    };
+    place_on_block(&mut world, piston_pos, piston);
+    world.set_block(head_pos, Block::Stone {});
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block_powered(piston_pos, true);
+    runner.tick();
+    runner.tick();
+    runner.check_block(
+        head_pos,
+        Block::PistonHead {
+            facing: BlockFacing::East,
+            short: false,
+            ty: PistonType::Normal,
+        },
+    );
+    runner.check_block(stone_pos, Block::Stone {});
+
+    runner.use_block(lever_pos);
+    runner.tick();
+    runner.tick();
+    runner.check_block(piston_pos, piston);
+    runner.check_block(head_pos, Block::Air {});
+    runner.check_block(stone_pos, Block::Stone {});
+}
+
+#[test]
+fn sticky_piston_pull() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+    let head_pos = pos(2, 1, 0);
+    let stone_pos = pos(3, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    let piston = Block::StickyPiston {
+        extended: false,
+        facing: BlockFacing::East,
+    };
+    place_on_block(&mut world, piston_pos, piston);
+    world.set_block(head_pos, Block::Stone {});
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.tick();
+    runner.tick();
+    runner.check_block(stone_pos, Block::Stone {});
+
+    runner.use_block(lever_pos);
+    runner.tick();
+    runner.tick();
+    runner.check_block(piston_pos, piston);
+    runner.check_block(head_pos, Block::Stone {});
+    runner.check_block(stone_pos, Block::Air {});
+}
+
+#[test]
+fn sticky_piston_drops_block_when_retracted_early() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+    let stone_pos = pos(3, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::StickyPiston {
+            extended: false,
+            facing: BlockFacing::East,
+        },
+    );
+    world.set_block(pos(2, 1, 0), Block::Stone {});
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.use_block(lever_pos);
+    runner.tick();
+    runner.tick();
+    runner.check_block_powered(piston_pos, false);
+    runner.check_block(pos(2, 1, 0), Block::Air {});
+    runner.check_block(stone_pos, Block::Stone {});
+}
+
+#[test]
+fn strict_update_order_delays_piston() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+    let head_pos = pos(2, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    world.strict_update_order = true;
+    make_lever(&mut world, lever_pos);
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::Piston {
+            extended: false,
+            facing: BlockFacing::East,
+        },
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    // The piston only starts moving once the block events run at the end of the tick
+    runner.check_block_powered(piston_pos, false);
+    runner.tick();
+    runner.check_block_powered(piston_pos, true);
+    runner.tick();
+    runner.tick();
+    runner.check_block(
+        head_pos,
+        Block::PistonHead {
+            facing: BlockFacing::East,
+            short: false,
+            ty: PistonType::Normal,
+        },
+    );
+}
+
+#[test]
+fn sticky_piston_pulls_block_back_without_zero_ticks() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+    let stone_pos = pos(2, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    world.zero_tick_pistons = false;
+    make_lever(&mut world, lever_pos);
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::StickyPiston {
+            extended: false,
+            facing: BlockFacing::East,
+        },
+    );
+    world.set_block(stone_pos, Block::Stone {});
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.use_block(lever_pos);
+    runner.tick();
+    runner.tick();
+    runner.check_block(stone_pos, Block::Stone {});
+    runner.check_block(pos(3, 1, 0), Block::Air {});
+}
+
+#[test]
+fn piston_push_limit() {
+    let lever_pos = pos(0, 1, 0);
+    let piston_pos = pos(1, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::Piston {
+            extended: false,
+            facing: BlockFacing::East,
+        },
+    );
+    for x in 2..15 {
+        world.set_block(pos(x, 1, 0), Block::Stone {});
+    }
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block_powered(piston_pos, false);
+}
+
+#[test]
+fn piston_quasi_connectivity() {
+    let piston_pos = pos(1, 1, 1);
+    let lever_pos = pos(1, 2, 2);
+
+    let mut world = TestWorld::new(1);
+    // The lever is attached to a block that is not next to the piston
+    world.set_block(pos(1, 2, 3), Block::Sandstone {});
+    world.set_block(
+        lever_pos,
+        Block::Lever {
+            lever: Lever::new(LeverFace::Wall, BlockDirection::North, false),
+        },
+    );
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::Piston {
+            extended: false,
+            facing: BlockFacing::Up,
+        },
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block_powered(piston_pos, true);
+}
+
+#[test]
+fn observer_pulse() {
+    let lever_pos = pos(0, 1, 0);
+    let observer_pos = pos(1, 1, 0);
+    let trapdoor_pos = pos(2, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        observer_pos,
+        Block::Observer {
+            facing: BlockFacing::West,
+            powered: false,
+        },
+    );
+    world.set_block(trapdoor_pos, trapdoor());
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_powered_for(trapdoor_pos, false, 2);
+    runner.check_powered_for(trapdoor_pos, true, 2);
+    runner.check_block_powered(trapdoor_pos, false);
+
+    runner.use_block(lever_pos);
+    runner.check_powered_for(trapdoor_pos, false, 2);
+    runner.check_powered_for(trapdoor_pos, true, 2);
+    runner.check_block_powered(trapdoor_pos, false);
+}
+
+#[test]
+fn piston_quasi_connectivity_disabled() {
+    let piston_pos = pos(1, 1, 1);
+    let lever_pos = pos(1, 2, 2);
+
+    let mut world = TestWorld::new(1);
+    world.quasi_connectivity = false;
+    world.set_block(pos(1, 2, 3), Block::Sandstone {});
+    world.set_block(
+        lever_pos,
+        Block::Lever {
+            lever: Lever::new(LeverFace::Wall, BlockDirection::North, false),
+        },
+    );
+    place_on_block(
+        &mut world,
+        piston_pos,
+        Block::Piston {
+            extended: false,
+            facing: BlockFacing::Up,
+        },
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block_powered(piston_pos, false);
+}
+
+#[test]
+fn target_trigger() {
+    let target_pos = pos(1, 1, 0);
+    let trapdoor_pos = pos(2, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    world.set_block(target_pos, Block::Target { power: 0 });
+    world.set_block(trapdoor_pos, trapdoor());
+    mchprs_redstone::target::trigger(
+        &mut world,
+        target_pos,
+        15,
+        mchprs_redstone::target::PROJECTILE_DURATION,
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.check_powered_for(trapdoor_pos, true, 8);
+    runner.check_block_powered(trapdoor_pos, false);
+}
+
+#[test]
+fn double_door_opens_together() {
+    let lever_pos = pos(0, 1, 0);
+    let left_pos = pos(1, 1, 0);
+    let right_pos = pos(2, 1, 0);
+    let door = |half, hinge, open| Block::IronDoor {
+        door: Door {
+            facing: BlockDirection::North,
+            half,
+            hinge,
+            open,
+            powered: open,
+        },
+    };
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    for (pos, hinge) in [(left_pos, DoorHinge::Left), (right_pos, DoorHinge::Right)] {
+        world.set_block(pos, door(DoorHalf::Lower, hinge, false));
+        world.set_block(
+            pos.offset(BlockFace::Top),
+            door(DoorHalf::Upper, hinge, false),
+        );
+    }
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block(right_pos, door(DoorHalf::Lower, DoorHinge::Right, true));
+    runner.check_block(
+        right_pos.offset(BlockFace::Top),
+        door(DoorHalf::Upper, DoorHinge::Right, true),
+    );
+    runner.check_block_powered(left_pos, true);
+
+    runner.use_block(lever_pos);
+    runner.check_block_powered(left_pos, false);
+    runner.check_block_powered(right_pos, false);
+}
+
+#[test]
+fn fence_gate_and_bell_on_off() {
+    let lever_pos = pos(1, 1, 1);
+    let gate_pos = pos(0, 1, 1);
+    let bell_pos = pos(2, 1, 1);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        gate_pos,
+        Block::OakFenceGate {
+            facing: BlockDirection::North,
+            in_wall: false,
+            open: false,
+            powered: false,
+        },
+    );
+    place_on_block(
+        &mut world,
+        bell_pos,
+        Block::Bell {
+            attachment: Default::default(),
+            facing: BlockDirection::North,
+            powered: false,
+        },
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block(
+        gate_pos,
+        Block::OakFenceGate {
+            facing: BlockDirection::North,
+            in_wall: false,
+            open: true,
+            powered: true,
+        },
+    );
+    runner.check_block_powered(bell_pos, true);
+
+    runner.use_block(lever_pos);
+    runner.check_block_powered(gate_pos, false);
+    runner.check_block_powered(bell_pos, false);
+}
+
+#[test]
+fn dropper_moves_item_into_container() {
+    let lever_pos = pos(0, 1, 0);
+    let dropper_pos = pos(1, 1, 0);
+    let barrel_pos = pos(2, 1, 0);
+    let item_count = |world: &TestWorld, pos| match world.get_block_entity(pos) {
+        Some(BlockEntity::Container { inventory, .. }) => {
+            inventory.iter().map(|entry| entry.count).sum()
+        }
+        _ => 0,
+    };
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        dropper_pos,
+        Block::Dropper {
+            facing: BlockFacing::East,
+            triggered: false,
+        },
+    );
+    let inventory = vec![InventoryEntry {
+        id: Item::Redstone {}.get_id(),
+        slot: 0,
+        count: 2,
+        nbt: None,
+    }];
+    world.set_block_entity(
+        dropper_pos,
+        BlockEntity::Container {
+            comparator_override: ContainerType::Dropper.comparator_override(&inventory),
+            inventory,
+            ty: ContainerType::Dropper,
+        },
+    );
+    world.set_block(barrel_pos, Block::Barrel {});
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    for _ in 0..4 {
+        assert_eq!(item_count(&runner.world, barrel_pos), 0);
+        runner.tick();
+    }
+    assert_eq!(item_count(&runner.world, dropper_pos), 1);
+    assert_eq!(item_count(&runner.world, barrel_pos), 1);
+
+    // Staying powered does not fire the dropper again
+    for _ in 0..8 {
+        runner.tick();
+    }
+    assert_eq!(item_count(&runner.world, dropper_pos), 1);
+}
+
+#[test]
+fn powered_rail_chain() {
+    let lever_pos = pos(0, 1, 0);
+    let rail = |powered| Block::PoweredRail {
+        powered,
+        shape: RailShape::EastWest,
+    };
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    for x in 1..=10 {
+        place_on_block(&mut world, pos(x, 1, 0), rail(false));
+    }
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    // The directly powered rail can power up to 8 more rails
+    for x in 1..=9 {
+        runner.check_block(pos(x, 1, 0), rail(true));
+    }
+    runner.check_block(pos(10, 1, 0), rail(false));
+
+    runner.use_block(lever_pos);
+    for x in 1..=10 {
+        runner.check_block(pos(x, 1, 0), rail(false));
+    }
+}
+
+#[test]
+fn copper_bulb_toggle() {
+    let lever_pos = pos(0, 1, 0);
+    let bulb_pos = pos(1, 1, 0);
+    let bulb = |lit, powered| Block::CopperBulb {
+        variant: Default::default(),
+        lit,
+        powered,
+    };
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(bulb_pos, bulb(false, false));
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_block(bulb_pos, bulb(true, true));
+    runner.use_block(lever_pos);
+    runner.check_block(bulb_pos, bulb(true, false));
+    runner.use_block(lever_pos);
+    runner.check_block(bulb_pos, bulb(false, true));
+    runner.use_block(lever_pos);
+    runner.check_block(bulb_pos, bulb(false, false));
+}
+
+#[test]
+fn sculk_sensor_vibration() {
+    let lever_pos = pos(0, 1, 0);
+    let sensor_pos = pos(5, 1, 0);
+    let trapdoor_pos = pos(6, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        sensor_pos,
+        Block::SculkSensor {
+            power: 0,
+            sculk_sensor_phase: Default::default(),
+        },
+    );
+    world.set_block(trapdoor_pos, trapdoor());
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    // The vibration travels one block per tick
+    runner.check_powered_for(trapdoor_pos, false, 5);
+    runner.check_powered_for(trapdoor_pos, true, 30);
+    runner.check_block_powered(trapdoor_pos, false);
+}
+
+#[test]
+fn sculk_sensor_wool_occlusion() {
+    let lever_pos = pos(0, 1, 0);
+    let sensor_pos = pos(5, 1, 0);
+    let trapdoor_pos = pos(6, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        pos(3, 1, 0),
+        Block::Wool {
+            color: BlockColorVariant::White,
+        },
+    );
+    world.set_block(
+        sensor_pos,
+        Block::SculkSensor {
+            power: 0,
+            sculk_sensor_phase: Default::default(),
+        },
+    );
+    world.set_block(trapdoor_pos, trapdoor());
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    runner.use_block(lever_pos);
+    runner.check_powered_for(trapdoor_pos, false, 40);
+}
+
+#[test]
+fn torch_burnout() {
+    let lever_pos = pos(0, 1, 0);
+    let torch_pos = pos(1, 0, 0);
+
+    let mut world = TestWorld::new(1);
+    world.torch_burnout = Some(Default::default());
+    make_lever(&mut world, lever_pos);
+    world.set_block(
+        torch_pos,
+        Block::RedstoneWallTorch {
+            lit: true,
+            facing: BlockDirection::East,
+        },
+    );
+
+    let mut runner = BackendRunner::new(world, TestBackend::Redstone);
+    for toggle in 1..=8 {
+        runner.use_block(lever_pos);
+        runner.tick();
+        runner.check_block_powered(torch_pos, false);
+        runner.use_block(lever_pos);
+        runner.tick();
+        // The eighth time the torch turns off it burns out and stays off
+        runner.check_block_powered(torch_pos, toggle < 8);
+    }
+
+    runner.check_powered_for(torch_pos, false, 159);
+    runner.check_block_powered(torch_pos, true);
+}
+
+#[test]
+fn redpiler_circuit_io() {
+    let lever_pos = pos(0, 1, 0);
+    let lamp_pos = pos(1, 0, 0);
+
+    let mut world = TestWorld::new(1);
+    make_lever(&mut world, lever_pos);
+    world.set_block(lamp_pos, Block::RedstoneLamp { lit: false });
+
+    let backend = TestBackend::Redpiler(mchprs_redpiler::BackendVariant::Direct);
+    let mut runner = BackendRunner::new(world, backend);
+    let compiler = runner.compiler().unwrap();
+    assert_eq!(compiler.read_output(lamp_pos), Some(0));
+    assert_eq!(compiler.read_output(pos(5, 5, 5)), None);
+
+    assert!(compiler.set_input(lever_pos, true));
+    assert_eq!(compiler.read_output(lever_pos), Some(15));
+    assert_eq!(compiler.read_output(lamp_pos), Some(15));
+    // Setting an input to the state it is already in does nothing
+    assert!(compiler.set_input(lever_pos, true));
+    assert_eq!(compiler.read_output(lever_pos), Some(15));
+    // Only levers, buttons and pressure plates are inputs
+    assert!(!compiler.set_input(lamp_pos, true));
+
+    runner.tick();
+    runner.check_block_powered(lamp_pos, true);
+}
+
+#[test]
+fn comparator_reads_lectern_and_jukebox() {
+    let lectern_pos = pos(0, 0, 0);
+    let jukebox_pos = pos(1, 0, 0);
+
+    let mut world = TestWorld::new(1);
+    let lectern = Block::Lectern {
+        facing: BlockDirection::North,
+        has_book: true,
+        powered: false,
+    };
+    world.set_block(lectern_pos, lectern);
+    world.set_block_entity(lectern_pos, BlockEntity::Lectern { page: 2, pages: 5 });
+    let jukebox = Block::Jukebox { has_record: true };
+    world.set_block(jukebox_pos, jukebox);
+    world.set_block_entity(
+        jukebox_pos,
+        BlockEntity::Jukebox {
+            record: Some(MusicDisc::Cat),
+        },
+    );
+
+    assert!(comparator::has_override(lectern));
+    assert_eq!(comparator::get_override(lectern, &world, lectern_pos), 8);
+    assert!(comparator::has_override(jukebox));
+    assert_eq!(comparator::get_override(jukebox, &world, jukebox_pos), 2);
+
+    let empty_lectern = Block::Lectern {
+        facing: BlockDirection::North,
+        has_book: false,
+        powered: false,
+    };
+    assert_eq!(
+        comparator::get_override(empty_lectern, &world, lectern_pos),
+        0
+    );
+}
+
+#[test]
+fn comparator_reads_item_frame() {
+    let comparator_pos = pos(0, 1, 0);
+    let block_pos = pos(1, 1, 0);
+    let frame_pos = pos(2, 1, 0);
+
+    let mut world = TestWorld::new(1);
+    make_comparator(
+        &mut world,
+        comparator_pos,
+        ComparatorMode::Compare,
+        BlockDirection::East,
+    );
+    world.set_block(block_pos, Block::Sandstone {});
+    world.item_frames.push(ItemFrame {
+        pos: frame_pos,
+        facing: BlockFacing::East,
+        item: Some(0),
+        rotation: 5,
+        entity_id: 0,
+    });
+
+    assert_eq!(
+        comparator::get_far_input(&world, comparator_pos, BlockDirection::East),
+        Some(6)
+    );
+
+    // Item frames facing any other way are not read
+    world.item_frames[0].facing = BlockFacing::North;
+    assert_eq!(
+        comparator::get_far_input(&world, comparator_pos, BlockDirection::East),
+        None
+    );
+}